@@ -0,0 +1,77 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use sigma_rust::bench::{rule_set, EventGenerator};
+use sigma_rust::{rule_from_yaml, RuleSet};
+
+fn parse_benchmark(c: &mut Criterion) {
+    let wildcard = r#"
+title: Tokenization
+logsource:
+detection:
+    selection:
+        CommandLine|contains:
+            - 'a\*b*c?d'
+            - '*\\evil\\*.exe'
+            - 'foo*bar*baz'
+    condition: selection
+"#;
+    let base64 = r#"
+title: Base64 expansion
+logsource:
+detection:
+    selection:
+        CommandLine|base64offset|contains:
+            - 'IEX (New-Object Net.WebClient).DownloadString'
+            - 'Invoke-Mimikatz'
+    condition: selection
+"#;
+
+    c.bench_function("parse_wildcards", |b| {
+        b.iter(|| rule_from_yaml(black_box(wildcard)).unwrap())
+    });
+    c.bench_function("parse_base64offset", |b| {
+        b.iter(|| rule_from_yaml(black_box(base64)).unwrap())
+    });
+}
+
+fn evaluate_benchmark(c: &mut Criterion) {
+    let events = EventGenerator::new(42).events(1000);
+    let mut group = c.benchmark_group("evaluate");
+
+    for size in [10, 100, 1000] {
+        let rule_set = RuleSet::new(rule_set(size));
+        group.bench_with_input(BenchmarkId::new("event", size), &rule_set, |b, rule_set| {
+            let mut i = 0;
+            b.iter(|| {
+                i = (i + 1) % events.len();
+                rule_set.evaluate(black_box(&events[i]))
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("batch", size), &rule_set, |b, rule_set| {
+            b.iter(|| rule_set.evaluate_batch(black_box(&events)))
+        });
+    }
+    group.finish();
+}
+
+#[cfg(feature = "snapshot")]
+fn snapshot_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cold_start");
+    group.sample_size(10);
+    group.bench_function("yaml", |b| b.iter(|| RuleSet::new(rule_set(5000))));
+    let snapshot = RuleSet::new(rule_set(5000)).to_snapshot().unwrap();
+    group.bench_function("snapshot", |b| {
+        b.iter(|| RuleSet::from_snapshot(black_box(&snapshot)).unwrap())
+    });
+    group.finish();
+}
+
+#[cfg(feature = "snapshot")]
+criterion_group!(
+    benches,
+    parse_benchmark,
+    evaluate_benchmark,
+    snapshot_benchmark
+);
+#[cfg(not(feature = "snapshot"))]
+criterion_group!(benches, parse_benchmark, evaluate_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,11 @@
+fn main() {
+    // The protobuf code of the gRPC server is only needed by `tau-server`
+    #[cfg(feature = "server")]
+    {
+        std::env::set_var(
+            "PROTOC",
+            protoc_bin_vendored::protoc_bin_path().expect("protoc is available"),
+        );
+        tonic_build::compile_protos("proto/tau.proto").expect("proto/tau.proto compiles");
+    }
+}
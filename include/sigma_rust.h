@@ -0,0 +1,70 @@
+#ifndef SIGMA_RUST_H
+#define SIGMA_RUST_H
+
+/* This file is generated by cbindgen from src/ffi.rs, do not edit it manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// A compiled collection of rules
+typedef struct SigmaRuleSet SigmaRuleSet;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Compile the rules of a YAML string. Several rules are separated by `---`.
+//
+// Returns `NULL` and stores a message in `error`, unless it is `NULL`, if a rule cannot be
+// parsed.
+//
+// # Safety
+// `yaml` must be a valid, NUL-terminated string and `error` either `NULL` or a valid pointer.
+struct SigmaRuleSet *sigma_rule_set_from_yaml(const char *yaml, char **error);
+
+// Compile all `.yml` and `.yaml` files below a directory. Several rules of a file are separated
+// by `---`, and detections may include selections of other files with `tau-include`.
+//
+// Returns `NULL` and stores a message in `error`, unless it is `NULL`, if the directory cannot
+// be read or a rule cannot be parsed.
+//
+// # Safety
+// `path` must be a valid, NUL-terminated string and `error` either `NULL` or a valid pointer.
+struct SigmaRuleSet *sigma_rule_set_from_directory(const char *path, char **error);
+
+// The number of rules of a rule set
+//
+// # Safety
+// `rule_set` must be a rule set returned by this library that has not been freed.
+size_t sigma_rule_set_len(const struct SigmaRuleSet *rule_set);
+
+// Evaluate all rules against an event given as JSON and return the matches as a JSON array.
+//
+// Returns `NULL` and stores a message in `error`, unless it is `NULL`, if the event cannot be
+// parsed.
+//
+// # Safety
+// `rule_set` must be a rule set returned by this library that has not been freed, `event` a
+// valid, NUL-terminated string and `error` either `NULL` or a valid pointer.
+char *sigma_rule_set_evaluate(const struct SigmaRuleSet *rule_set, const char *event, char **error);
+
+// Free a rule set. Passing `NULL` is a no-op.
+//
+// # Safety
+// `rule_set` must be `NULL` or a rule set returned by this library that has not been freed.
+void sigma_rule_set_free(struct SigmaRuleSet *rule_set);
+
+// Free a string returned by this library. Passing `NULL` is a no-op.
+//
+// # Safety
+// `string` must be `NULL` or a string returned by this library that has not been freed.
+void sigma_string_free(char *string);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SIGMA_RUST_H */
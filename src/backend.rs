@@ -0,0 +1,298 @@
+//! Translate rules into the query languages of other systems, so the same rules used for
+//! streaming detection can drive retro-hunts in data warehouses and SIEMs.
+//!
+//! * [`to_sql`] compiles the detection of a rule into the expression of an SQL `WHERE`
+//!   clause. Regular expressions and CIDR ranges are delegated to a [`SqlDialect`], e.g.
+//!   [`PostgreSql`] or [`ClickHouse`].
+//! * [`to_kql`] compiles it into a Kusto Query Language (KQL) `where` predicate for
+//!   Microsoft Sentinel and Azure Data Explorer.
+//! * [`to_eql`] compiles it into an Event Query Language (EQL) query for Elastic Security.
+//! * [`to_logql`] compiles it into a LogQL query for Grafana Loki on a best-effort basis,
+//!   reporting the constructs it could not translate.
+//!
+//! Each field of the rule is expected to be a column of the queried table.
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::backend::{to_kql, to_sql, AnsiSql};
+//! use sigma_rust::rule_from_yaml;
+//!
+//! let rule = rule_from_yaml(
+//!     r#"
+//! title: Whoami
+//! logsource:
+//!     category: process_creation
+//! detection:
+//!     selection:
+//!         Image|endswith: '\whoami.exe'
+//!     filter:
+//!         User: SYSTEM
+//!     condition: selection and not filter
+//! "#,
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(
+//!     to_sql(&rule, &AnsiSql).unwrap(),
+//!     r#"LOWER("Image") LIKE '%\\whoami.exe' ESCAPE '\' AND NOT COALESCE(LOWER("User") = 'system', FALSE)"#
+//! );
+//! assert_eq!(
+//!     to_kql(&rule).unwrap(),
+//!     r#"Image endswith @"\whoami.exe" and not(User =~ @"system")"#
+//! );
+//! ```
+mod eql;
+mod kql;
+mod logql;
+mod sql;
+mod summary;
+
+pub use eql::to_eql;
+pub use kql::to_kql;
+pub use logql::{to_logql, LogQlOptions, LogQlQuery};
+pub use sql::{to_sql, AnsiSql, ClickHouse, MySql, PostgreSql, SqlDialect};
+pub(crate) use summary::to_summary;
+
+use crate::basevalue::BaseValue;
+use crate::detection::Ast;
+use crate::field::{CollectionMatch, Field, FieldValue, MatchModifier};
+use crate::rule::Rule;
+use crate::selection::{FieldGroup, Keyword, Selection};
+use crate::wildcard::{match_tokenized, WildcardPattern, WildcardToken};
+use std::sync::Arc;
+
+/// Errors raised when a rule cannot be translated
+#[derive(Debug, thiserror::Error)]
+pub enum BackendError {
+    #[error("The backend does not support {0}")]
+    Unsupported(String),
+}
+
+/// The boolean operators, ordered by their binding power
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Operator {
+    /// The operand of a condition that is not part of an operator
+    None,
+    Or,
+    And,
+}
+
+/// The parts of the translation that differ between query languages
+trait Language {
+    /// The separator that combines two conditions with an operator, including spaces
+    fn operator(&self, operator: Operator) -> &'static str;
+
+    /// A condition that is always true or always false
+    fn constant(&self, value: bool) -> String;
+
+    /// Negate a condition
+    fn not(&self, operand: String) -> String;
+
+    /// A condition that is true if any column contains the keyword
+    fn keyword(&self, keyword: &Keyword) -> Result<String, BackendError>;
+
+    /// A condition that is true if the field exists or does not exist
+    fn exists(&self, field: &Field, exists: bool) -> String;
+
+    /// A condition that is true if the field matches the value
+    fn value(&self, field: &Field, value: &FieldValue) -> Result<String, BackendError>;
+}
+
+/// Translate the condition of a rule, resolving the selections with the language
+fn translate(rule: &Rule, language: &dyn Language) -> Result<String, BackendError> {
+    let mut names: Vec<&str> = rule
+        .detection
+        .get_selections()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    names.sort_unstable();
+
+    Translator {
+        rule,
+        language,
+        names,
+    }
+    .condition(rule.detection.ast(), Operator::None)
+}
+
+struct Translator<'a> {
+    rule: &'a Rule,
+    language: &'a dyn Language,
+    /// The names of all selections in a deterministic order
+    names: Vec<&'a str>,
+}
+
+impl Translator<'_> {
+    /// Translate a condition that is an operand of the `parent` operator
+    fn condition(&self, ast: &Ast, parent: Operator) -> Result<String, BackendError> {
+        match ast {
+            Ast::Selection(name) => self.selection(name, parent),
+            Ast::OneOf(pattern) => self.quantifier(Some(pattern), Operator::Or, parent),
+            Ast::OneOfThem => self.quantifier(None, Operator::Or, parent),
+            Ast::AllOf(pattern) => self.quantifier(Some(pattern), Operator::And, parent),
+            Ast::AllOfThem => self.quantifier(None, Operator::And, parent),
+            Ast::Not(operand) => Ok(self.language.not(self.condition(operand, Operator::None)?)),
+            Ast::And(left, right) => self.binary(left, right, Operator::And, parent),
+            Ast::Or(left, right) => self.binary(left, right, Operator::Or, parent),
+        }
+    }
+
+    fn binary(
+        &self,
+        left: &Ast,
+        right: &Ast,
+        operator: Operator,
+        parent: Operator,
+    ) -> Result<String, BackendError> {
+        let terms = vec![
+            self.condition(left, operator)?,
+            self.condition(right, operator)?,
+        ];
+        Ok(self.join(terms, operator, parent))
+    }
+
+    fn quantifier(
+        &self,
+        pattern: Option<&[WildcardToken]>,
+        operator: Operator,
+        parent: Operator,
+    ) -> Result<String, BackendError> {
+        let terms = self
+            .names
+            .iter()
+            .filter(|name| pattern.map_or(true, |p| match_tokenized(p, name, false)))
+            .map(|name| self.selection(name, operator))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.join(terms, operator, parent))
+    }
+
+    fn selection(&self, name: &str, parent: Operator) -> Result<String, BackendError> {
+        let terms = match &self.rule.detection.get_selections()[name] {
+            Selection::Keyword(keywords) => keywords
+                .iter()
+                .map(|keyword| self.language.keyword(keyword))
+                .collect::<Result<Vec<_>, _>>()?,
+            Selection::Field(groups) => groups
+                .iter()
+                .map(|group| self.field_group(group, Operator::Or))
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        Ok(self.join(terms, Operator::Or, parent))
+    }
+
+    fn field_group(&self, group: &FieldGroup, parent: Operator) -> Result<String, BackendError> {
+        let terms = group
+            .fields
+            .iter()
+            .map(|field| self.field(field, Operator::And))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.join(terms, Operator::And, parent))
+    }
+
+    fn field(&self, field: &Field, parent: Operator) -> Result<String, BackendError> {
+        // Placeholder lists are translated with their current values
+        if let Some(live) = &field.live {
+            return match live.current() {
+                Some(field) => self.field(&field, parent),
+                None => Ok(self.join(vec![], Operator::Or, parent)),
+            };
+        }
+        let modifier = &field.modifier;
+        if let Some(exists) = modifier.exists {
+            return Ok(self.language.exists(field, exists));
+        }
+
+        let require_all =
+            modifier.match_all || matches!(modifier.collection, Some(CollectionMatch::All));
+        let operator = if require_all {
+            Operator::And
+        } else {
+            Operator::Or
+        };
+        let terms = field
+            .values
+            .iter()
+            .map(|value| match value {
+                FieldValue::Base(BaseValue::String(domain))
+                    if modifier.match_modifier == Some(MatchModifier::SubdomainOf)
+                        && !modifier.fieldref =>
+                {
+                    // A single term is not joined below and needs the parentheses of the parent
+                    let parent = if field.values.len() == 1 {
+                        parent
+                    } else {
+                        operator
+                    };
+                    self.subdomain(field, domain, parent)
+                }
+                _ => self.language.value(field, value),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.join(terms, operator, parent))
+    }
+
+    /// The domain itself or any name ending with a dot and the domain, compared
+    /// case-insensitively. Like the engine, the domain is taken literally and fully qualified
+    /// names with a trailing dot match as well.
+    fn subdomain(
+        &self,
+        field: &Field,
+        domain: &str,
+        parent: Operator,
+    ) -> Result<String, BackendError> {
+        let domain = domain.strip_suffix('.').unwrap_or(domain);
+        if domain.is_empty() {
+            return Ok(self.join(vec![], Operator::Or, parent));
+        }
+        let name: Vec<char> = domain.to_lowercase().chars().collect();
+        let subdomain: Vec<char> = std::iter::once('.').chain(name.iter().copied()).collect();
+        let fully_qualified = |chars: &[char]| chars.iter().copied().chain(['.']).collect();
+        let terms = [
+            vec![WildcardToken::Pattern(name.clone())],
+            vec![WildcardToken::Pattern(fully_qualified(&name))],
+            vec![
+                WildcardToken::Star,
+                WildcardToken::Pattern(subdomain.clone()),
+            ],
+            vec![
+                WildcardToken::Star,
+                WildcardToken::Pattern(fully_qualified(&subdomain)),
+            ],
+        ]
+        .into_iter()
+        .map(|tokens| {
+            let pattern = WildcardPattern::new(tokens);
+            self.language
+                .value(field, &FieldValue::WildcardPattern(Arc::new(pattern)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.join(terms, Operator::Or, parent))
+    }
+
+    /// Join terms with an operator and wrap them in parentheses if the parent operator binds
+    /// stronger. Conditions without terms are always false for `or` and always true for `and`,
+    /// just like `any` and `all` of an empty iterator.
+    fn join(&self, terms: Vec<String>, operator: Operator, parent: Operator) -> String {
+        match terms.len() {
+            0 => self.language.constant(operator == Operator::And),
+            1 => terms.into_iter().next().unwrap(),
+            _ => {
+                let expression = terms.join(self.language.operator(operator));
+                if parent > operator {
+                    format!("({})", expression)
+                } else {
+                    expression
+                }
+            }
+        }
+    }
+}
+
+/// Returns the literal of wildcard tokens without any wildcards
+fn literal(tokens: &[WildcardToken]) -> Option<String> {
+    match tokens {
+        [WildcardToken::Pattern(chars)] => Some(chars.iter().collect()),
+        _ => None,
+    }
+}
@@ -0,0 +1,278 @@
+use super::{literal, translate, BackendError, Language, Operator};
+use crate::basevalue::BaseValue;
+use crate::field::{Field, FieldValue, MatchModifier};
+use crate::rule::Rule;
+use crate::selection::Keyword;
+use crate::wildcard::WildcardToken;
+
+/// Translate a rule into an Event Query Language (EQL) query for Elastic Security.
+///
+/// The event category of the query is derived from the logsource category of the rule,
+/// e.g. `process` for `process_creation`, and is `any` for unknown categories.
+/// Regular expressions of the `|re` modifier are passed through and must be compatible
+/// with the Lucene regular expression syntax used by EQL.
+pub fn to_eql(rule: &Rule) -> Result<String, BackendError> {
+    let category = rule
+        .logsource
+        .category
+        .as_deref()
+        .and_then(event_category)
+        .unwrap_or("any");
+    Ok(format!("{} where {}", category, translate(rule, &Eql)?))
+}
+
+/// Map Sigma logsource categories to the ECS event categories used by EQL
+fn event_category(category: &str) -> Option<&'static str> {
+    match category {
+        "process_creation" | "process_termination" | "process_access" => Some("process"),
+        "network_connection" | "firewall" | "dns_query" | "dns" => Some("network"),
+        "image_load" => Some("library"),
+        "driver_load" => Some("driver"),
+        c if c.starts_with("file_") => Some("file"),
+        c if c.starts_with("registry_") => Some("registry"),
+        _ => None,
+    }
+}
+
+struct Eql;
+
+impl Language for Eql {
+    fn operator(&self, operator: Operator) -> &'static str {
+        match operator {
+            Operator::And => " and ",
+            _ => " or ",
+        }
+    }
+
+    fn constant(&self, value: bool) -> String {
+        value.to_string()
+    }
+
+    fn not(&self, operand: String) -> String {
+        format!("not ({})", operand)
+    }
+
+    fn keyword(&self, _: &Keyword) -> Result<String, BackendError> {
+        Err(BackendError::Unsupported(
+            "keyword selections, as they are not bound to a field".to_string(),
+        ))
+    }
+
+    fn exists(&self, field: &Field, exists: bool) -> String {
+        let operator = if exists { "!=" } else { "==" };
+        format!("{} {} null", identifier(&field.name), operator)
+    }
+
+    fn value(&self, field: &Field, value: &FieldValue) -> Result<String, BackendError> {
+        let name = identifier(&field.name);
+        let modifier = &field.modifier;
+        match value {
+            FieldValue::WildcardPattern(pattern) => Ok(wildcard(&name, pattern, modifier.cased)),
+            FieldValue::Regex(regex) => Ok(format!(
+                "{} regex {}",
+                name,
+                string(&format!(".*({}).*", regex.as_str()))
+            )),
+            #[cfg(feature = "cidr")]
+            FieldValue::Cidr(cidr) => Ok(format!(
+                "cidrMatch({}, {})",
+                name,
+                string(&cidr.to_string())
+            )),
+            FieldValue::Base(value) if modifier.fieldref => match modifier.match_modifier {
+                None => Ok(format!(
+                    "{} == {}",
+                    name,
+                    identifier(&value.value_to_string())
+                )),
+                Some(_) => Err(BackendError::Unsupported(
+                    "the fieldref modifier combined with other modifiers".to_string(),
+                )),
+            },
+            FieldValue::Base(BaseValue::Null) => Ok(format!("{} == null", name)),
+            FieldValue::Base(value) => {
+                let operator = match modifier.match_modifier {
+                    Some(MatchModifier::Gt) => ">",
+                    Some(MatchModifier::Gte) => ">=",
+                    Some(MatchModifier::Lt) => "<",
+                    Some(MatchModifier::Lte) => "<=",
+                    _ => "==",
+                };
+                let value = match value {
+                    BaseValue::String(s) => string(s),
+                    _ => value.value_to_string(),
+                };
+                Ok(format!("{} {} {}", name, operator, value))
+            }
+        }
+    }
+}
+
+/// Translate a wildcard pattern. EQL wildcards cannot be escaped, so patterns whose literals
+/// contain `*` or `?` are translated to regular expressions instead.
+fn wildcard(name: &str, tokens: &[WildcardToken], cased: bool) -> String {
+    let case = if cased { "" } else { "~" };
+    if let Some(literal) = literal(tokens) {
+        let operator = if cased { "==" } else { ":" };
+        return format!("{} {} {}", name, operator, string(&literal));
+    }
+
+    let escapable = tokens.iter().all(|token| match token {
+        WildcardToken::Pattern(chars) => !chars.iter().any(|c| matches!(c, '*' | '?')),
+        _ => true,
+    });
+    if escapable {
+        format!("{} like{} {}", name, case, string(&like_pattern(tokens)))
+    } else {
+        format!("{} regex{} {}", name, case, string(&lucene_regex(tokens)))
+    }
+}
+
+fn like_pattern(tokens: &[WildcardToken]) -> String {
+    tokens
+        .iter()
+        .map(|token| match token {
+            WildcardToken::Star => "*".to_string(),
+            WildcardToken::QuestionMark => "?".to_string(),
+            WildcardToken::Pattern(chars) => chars.iter().collect(),
+        })
+        .collect()
+}
+
+/// Lucene regular expressions always match the whole value, so no anchors are needed
+fn lucene_regex(tokens: &[WildcardToken]) -> String {
+    let mut result = String::new();
+    for token in tokens {
+        match token {
+            WildcardToken::Star => result.push_str(".*"),
+            WildcardToken::QuestionMark => result.push('.'),
+            WildcardToken::Pattern(chars) => {
+                for c in chars {
+                    if r#".?+*|{}[]()"\#@&<>~"#.contains(*c) {
+                        result.push('\\');
+                    }
+                    result.push(*c);
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Quote a field name with backticks if it is not a plain (possibly nested) field name
+fn identifier(name: &str) -> String {
+    let mut chars = name.chars();
+    let plain = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '@')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '@'));
+    if plain {
+        name.to_string()
+    } else {
+        format!("`{}`", name.replace('`', "``"))
+    }
+}
+
+fn string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    fn eql(logsource: &str, detection: &str) -> Result<String, BackendError> {
+        let rule = rule_from_yaml(&format!(
+            "title: Test\nlogsource:\n{}\ndetection:\n{}",
+            logsource, detection
+        ))
+        .unwrap();
+        to_eql(&rule)
+    }
+
+    #[test]
+    fn test_modifiers() {
+        let result = eql(
+            "    category: process_creation",
+            r#"
+    selection:
+        process.command_line|contains: 'say "hi"'
+        process.executable|cased|endswith: '\cmd.exe'
+        user.name:
+            - 'admin'
+            - 'x?y*'
+        Event ID|gte: 4
+        process.parent|exists: false
+        Elevated: true
+        Other|fieldref: user.name
+        Literal: 'what\?*'
+    condition: selection
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            [
+                r#"process where process.command_line like~ "*say \"hi\"*""#,
+                r#"process.executable like "*\\cmd.exe""#,
+                r#"(user.name : "admin" or user.name like~ "x?y*")"#,
+                r#"`Event ID` >= 4"#,
+                r#"process.parent == null"#,
+                r#"Elevated == true"#,
+                r#"Other == user.name"#,
+                r#"Literal regex~ "what\\?.*""#,
+            ]
+            .join(" and ")
+        );
+    }
+
+    #[cfg(feature = "cidr")]
+    #[test]
+    fn test_condition() {
+        let result = eql(
+            "    product: linux",
+            r#"
+    selection:
+        destination.ip|cidr: '10.0.0.0/8'
+    filter:
+        process.name|re: 'ssh[d]?'
+    condition: selection and not filter
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            r#"any where cidrMatch(destination.ip, "10.0.0.0/8") and not (process.name regex ".*(ssh[d]?).*")"#
+        );
+    }
+
+    #[test]
+    fn test_subdomain_of() {
+        let result = eql(
+            "    category: dns",
+            r#"
+    selection:
+        Status: 'ok'
+        QueryName|subdomainof: 'Evil.com.'
+    condition: selection
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            [
+                r#"network where Status : "ok""#,
+                r#"(QueryName : "evil.com" or QueryName : "evil.com." or QueryName like~ "*.evil.com" or QueryName like~ "*.evil.com.")"#,
+            ]
+            .join(" and ")
+        );
+    }
+
+    #[test]
+    fn test_event_category() {
+        assert_eq!(event_category("file_event"), Some("file"));
+        assert_eq!(event_category("registry_set"), Some("registry"));
+        assert_eq!(event_category("webserver"), None);
+    }
+}
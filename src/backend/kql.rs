@@ -0,0 +1,269 @@
+use super::{literal, translate, BackendError, Language, Operator};
+use crate::basevalue::BaseValue;
+use crate::field::{Field, FieldValue, MatchModifier};
+use crate::rule::Rule;
+use crate::selection::Keyword;
+use crate::wildcard::{to_regex, WildcardToken};
+
+/// Translate the detection of a rule into a Kusto Query Language (KQL) predicate that can be
+/// used in a `where` operator, e.g. in Microsoft Sentinel.
+///
+/// Wildcard patterns that consist of a single literal are lowered to the `contains`,
+/// `startswith`, `endswith` and `=~` operators (or their case-sensitive `_cs` variants for the
+/// `cased` modifier), other patterns to `matches regex`. The term index of `has` is not used,
+/// as it only matches whole terms and would miss matches within a term.
+pub fn to_kql(rule: &Rule) -> Result<String, BackendError> {
+    translate(rule, &Kql)
+}
+
+struct Kql;
+
+impl Language for Kql {
+    fn operator(&self, operator: Operator) -> &'static str {
+        match operator {
+            Operator::And => " and ",
+            _ => " or ",
+        }
+    }
+
+    fn constant(&self, value: bool) -> String {
+        value.to_string()
+    }
+
+    fn not(&self, operand: String) -> String {
+        format!("not({})", operand)
+    }
+
+    fn keyword(&self, _: &Keyword) -> Result<String, BackendError> {
+        Err(BackendError::Unsupported(
+            "keyword selections, as they are not bound to a column".to_string(),
+        ))
+    }
+
+    fn exists(&self, field: &Field, exists: bool) -> String {
+        let function = if exists { "isnotempty" } else { "isempty" };
+        format!("{}({})", function, identifier(&field.name))
+    }
+
+    fn value(&self, field: &Field, value: &FieldValue) -> Result<String, BackendError> {
+        let column = identifier(&field.name);
+        let modifier = &field.modifier;
+        match value {
+            FieldValue::WildcardPattern(pattern) => Ok(wildcard(&column, pattern, modifier.cased)),
+            FieldValue::Regex(regex) => Ok(format!(
+                "{} matches regex {}",
+                column,
+                string(regex.as_str())
+            )),
+            #[cfg(feature = "cidr")]
+            FieldValue::Cidr(cidr) => {
+                let function = if cidr.is_ipv4() {
+                    "ipv4_is_in_range"
+                } else {
+                    "ipv6_is_in_range"
+                };
+                Ok(format!(
+                    "{}({}, {})",
+                    function,
+                    column,
+                    string(&cidr.to_string())
+                ))
+            }
+            FieldValue::Base(value) if modifier.fieldref => match modifier.match_modifier {
+                None => Ok(format!(
+                    "{} == {}",
+                    column,
+                    identifier(&value.value_to_string())
+                )),
+                Some(_) => Err(BackendError::Unsupported(
+                    "the fieldref modifier combined with other modifiers".to_string(),
+                )),
+            },
+            FieldValue::Base(BaseValue::Null) => Ok(format!("isnull({})", column)),
+            FieldValue::Base(value) => {
+                let operator = match modifier.match_modifier {
+                    Some(MatchModifier::Gt) => ">",
+                    Some(MatchModifier::Gte) => ">=",
+                    Some(MatchModifier::Lt) => "<",
+                    Some(MatchModifier::Lte) => "<=",
+                    _ => "==",
+                };
+                let value = match value {
+                    BaseValue::String(s) => string(s),
+                    _ => value.value_to_string(),
+                };
+                Ok(format!("{} {} {}", column, operator, value))
+            }
+        }
+    }
+}
+
+/// Lower a wildcard pattern to the cheapest string operator that matches exactly the same values
+fn wildcard(column: &str, tokens: &[WildcardToken], cased: bool) -> String {
+    let (operator, literal): (&str, String) = match tokens {
+        [WildcardToken::Star, WildcardToken::Pattern(p), WildcardToken::Star] => {
+            ("contains", p.iter().collect())
+        }
+        [WildcardToken::Pattern(p), WildcardToken::Star] => ("startswith", p.iter().collect()),
+        [WildcardToken::Star, WildcardToken::Pattern(p)] => ("endswith", p.iter().collect()),
+        _ => match literal(tokens) {
+            Some(literal) => {
+                let operator = if cased { "==" } else { "=~" };
+                return format!("{} {} {}", column, operator, string(&literal));
+            }
+            None => {
+                let flags = if cased { "" } else { "(?i)" };
+                return format!(
+                    "{} matches regex {}",
+                    column,
+                    string(&format!("{}{}", flags, to_regex(tokens)))
+                );
+            }
+        },
+    };
+    let suffix = if cased { "_cs" } else { "" };
+    format!("{} {}{} {}", column, operator, suffix, string(&literal))
+}
+
+/// Quote a column name if it is not a plain identifier, e.g. because it contains a dot
+fn identifier(name: &str) -> String {
+    let mut chars = name.chars();
+    let plain = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        name.to_string()
+    } else {
+        format!("[{}]", string(name))
+    }
+}
+
+/// A verbatim string literal, in which backslashes are not escape characters
+fn string(value: &str) -> String {
+    format!("@\"{}\"", value.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    fn kql(detection: &str) -> Result<String, BackendError> {
+        let rule = rule_from_yaml(&format!(
+            "title: Test\nlogsource:\ndetection:\n{}",
+            detection
+        ))
+        .unwrap();
+        to_kql(&rule)
+    }
+
+    #[test]
+    fn test_modifiers() {
+        let result = kql(r#"
+    selection:
+        CommandLine|contains|all:
+            - 'invoke'
+            - 'say "hi"'
+        Image|cased|startswith: 'C:\Windows\'
+        User:
+            - 'admin'
+            - 'x?y*'
+        Event.ID|gte: 4
+        Parent|exists: false
+        Elevated: true
+        Token: null
+        Other|fieldref: User
+    condition: selection
+"#)
+        .unwrap();
+        assert_eq!(
+            result,
+            [
+                r#"CommandLine contains @"invoke""#,
+                r#"CommandLine contains @"say ""hi""""#,
+                r#"Image startswith_cs @"C:\Windows\""#,
+                r#"(User =~ @"admin" or User matches regex @"(?i)(?s)^x.y.*$")"#,
+                r#"[@"Event.ID"] >= 4"#,
+                r#"isempty(Parent)"#,
+                r#"Elevated == true"#,
+                r#"isnull(Token)"#,
+                r#"Other == User"#,
+            ]
+            .join(" and ")
+        );
+    }
+
+    #[cfg(feature = "cidr")]
+    #[test]
+    fn test_condition() {
+        let result = kql(r#"
+    selection_a:
+        - A|endswith: 'a'
+        - B|cased: 'b'
+    selection_b:
+        C|re: '\d+'
+    filter:
+        - D|cidr: '10.0.0.0/8'
+        - D|cidr: 'fe80::/10'
+    condition: 1 of selection_* and not filter
+"#)
+        .unwrap();
+        assert_eq!(
+            result,
+            r#"(A endswith @"a" or B == @"b" or C matches regex @"\d+") and not(ipv4_is_in_range(D, @"10.0.0.0/8") or ipv6_is_in_range(D, @"fe80::/10"))"#
+        );
+    }
+
+    #[test]
+    fn test_subdomain_of() {
+        let result = kql(r#"
+    selection:
+        QueryName|subdomainof:
+            - 'example.com'
+            - 'example.org'
+    condition: selection
+"#)
+        .unwrap();
+        assert_eq!(
+            result,
+            [
+                r#"(QueryName =~ @"example.com" or QueryName =~ @"example.com.""#,
+                r#"QueryName endswith @".example.com" or QueryName endswith @".example.com.""#,
+                r#"QueryName =~ @"example.org" or QueryName =~ @"example.org.""#,
+                r#"QueryName endswith @".example.org" or QueryName endswith @".example.org.")"#,
+            ]
+            .join(" or ")
+        );
+    }
+
+    #[test]
+    fn test_subdomain_of_with_other_fields() {
+        let result = kql(r#"
+    selection:
+        Status: 'ok'
+        QueryName|subdomainof: 'Evil.com.'
+    condition: selection
+"#)
+        .unwrap();
+        assert_eq!(
+            result,
+            [
+                r#"Status =~ @"ok""#,
+                r#"(QueryName =~ @"evil.com" or QueryName =~ @"evil.com." or QueryName endswith @".evil.com" or QueryName endswith @".evil.com.")"#,
+            ]
+            .join(" and ")
+        );
+    }
+
+    #[test]
+    fn test_radix_literals() {
+        let result = kql("    selection:\n        AccessMask: '0x1010'\n    condition: selection");
+        assert_eq!(result.unwrap(), r#"AccessMask =~ @"0x1010""#);
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert!(kql("    keywords:\n        - 'evil'\n    condition: keywords").is_err());
+    }
+}
@@ -0,0 +1,472 @@
+use crate::basevalue::BaseValue;
+use crate::detection::Ast;
+use crate::field::{CollectionMatch, Field, FieldValue, MatchModifier};
+use crate::rule::Rule;
+use crate::selection::{FieldGroup, Keyword, Selection};
+use crate::wildcard::{match_tokenized, WildcardToken};
+
+/// Options of the LogQL translation
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogQlOptions {
+    /// The log stream selector the query starts with, e.g. `{job="sysmon"}`
+    pub stream_selector: String,
+    /// The parser that extracts the fields of the log lines as labels, e.g. `json` or `logfmt`
+    pub parser: String,
+}
+
+impl Default for LogQlOptions {
+    fn default() -> Self {
+        Self {
+            stream_selector: r#"{job=~".+"}"#.to_string(),
+            parser: "json".to_string(),
+        }
+    }
+}
+
+/// A LogQL query together with the parts of the rule that could not be translated
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogQlQuery {
+    pub query: String,
+    /// Descriptions of the constructs of the rule that have no LogQL equivalent. They are
+    /// left out of the query, which then matches a superset of the events the rule matches.
+    pub unsupported: Vec<String>,
+}
+
+/// Translate a rule into a LogQL query for Grafana Loki.
+///
+/// Keyword selections that are part of the top-level conjunction of the condition become
+/// line filters. All other selections become a label filter expression over the fields
+/// extracted by the parser of `options`. Nested field names are flattened with `_`, just like
+/// the `json` parser does.
+///
+/// Translation is best effort: constructs without LogQL equivalent, e.g. `fieldref` or
+/// keywords below an `or`, are reported in [`LogQlQuery::unsupported`] and widened to match
+/// everything instead of failing.
+pub fn to_logql(rule: &Rule, options: &LogQlOptions) -> LogQlQuery {
+    let mut translator = Translator {
+        rule,
+        unsupported: vec![],
+    };
+
+    let mut conjuncts = vec![];
+    conjunction(rule.detection.ast(), &mut conjuncts);
+
+    let mut line_filters = vec![];
+    let mut label_filters = vec![];
+    for (ast, negated) in conjuncts {
+        match translator.line_filter(ast, negated) {
+            Some(filter) => line_filters.push(filter),
+            None => label_filters.extend(translator.condition(ast, negated)),
+        }
+    }
+
+    let mut query = options.stream_selector.clone();
+    for filter in line_filters {
+        query.push(' ');
+        query.push_str(&filter);
+    }
+    query.push_str(" | ");
+    query.push_str(&options.parser);
+    if !label_filters.is_empty() {
+        query.push_str(" | ");
+        query.push_str(&join(label_filters, Operator::And));
+    }
+
+    LogQlQuery {
+        query,
+        unsupported: translator.unsupported,
+    }
+}
+
+/// Collect the operands of the top-level conjunction of a condition. `not` is pushed down
+/// into `or`, as `not (a or b)` is `not a and not b`.
+fn conjunction<'a>(ast: &'a Ast, result: &mut Vec<(&'a Ast, bool)>) {
+    fn collect<'a>(ast: &'a Ast, negated: bool, result: &mut Vec<(&'a Ast, bool)>) {
+        match (ast, negated) {
+            (Ast::And(left, right), false) | (Ast::Or(left, right), true) => {
+                collect(left, negated, result);
+                collect(right, negated, result);
+            }
+            (Ast::Not(operand), _) => collect(operand, !negated, result),
+            _ => result.push((ast, negated)),
+        }
+    }
+    collect(ast, false, result)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Or,
+    And,
+}
+
+impl Operator {
+    /// `not (a and b)` is `not a or not b` and vice versa
+    fn negate(self, negated: bool) -> Self {
+        match (self, negated) {
+            (Self::And, true) => Self::Or,
+            (Self::Or, true) => Self::And,
+            _ => self,
+        }
+    }
+}
+
+struct Translator<'a> {
+    rule: &'a Rule,
+    unsupported: Vec<String>,
+}
+
+/// Expressions are `None` if they could not be translated and are treated as matching
+/// everything. As negations are pushed down to the comparisons, this always widens the query.
+type Expression = Option<String>;
+
+impl Translator<'_> {
+    fn unsupported(&mut self, construct: String) -> Expression {
+        self.unsupported.push(construct);
+        None
+    }
+
+    /// Translate a top-level keyword selection to a line filter
+    fn line_filter(&mut self, ast: &Ast, negated: bool) -> Option<String> {
+        let Ast::Selection(name) = ast else {
+            return None;
+        };
+        let Selection::Keyword(keywords) = &self.rule.detection.get_selections()[name] else {
+            return None;
+        };
+        let patterns: Vec<String> = keywords.iter().map(keyword_regex).collect();
+        let operator = if negated { "!~" } else { "|~" };
+        Some(format!(
+            "{} {}",
+            operator,
+            string(&format!("(?i){}", patterns.join("|")))
+        ))
+    }
+
+    fn condition(&mut self, ast: &Ast, negated: bool) -> Expression {
+        match ast {
+            Ast::Selection(name) => self.selection(name, negated),
+            Ast::OneOf(pattern) => self.quantifier(Some(pattern), Operator::Or, negated),
+            Ast::OneOfThem => self.quantifier(None, Operator::Or, negated),
+            Ast::AllOf(pattern) => self.quantifier(Some(pattern), Operator::And, negated),
+            Ast::AllOfThem => self.quantifier(None, Operator::And, negated),
+            Ast::Not(operand) => self.condition(operand, !negated),
+            Ast::And(left, right) => {
+                let terms = vec![
+                    self.condition(left, negated),
+                    self.condition(right, negated),
+                ];
+                combine(terms, Operator::And.negate(negated))
+            }
+            Ast::Or(left, right) => {
+                let terms = vec![
+                    self.condition(left, negated),
+                    self.condition(right, negated),
+                ];
+                combine(terms, Operator::Or.negate(negated))
+            }
+        }
+    }
+
+    fn quantifier(
+        &mut self,
+        pattern: Option<&[WildcardToken]>,
+        operator: Operator,
+        negated: bool,
+    ) -> Expression {
+        let mut names: Vec<&String> = self
+            .rule
+            .detection
+            .get_selections()
+            .keys()
+            .filter(|name| pattern.map_or(true, |p| match_tokenized(p, name, false)))
+            .collect();
+        names.sort_unstable();
+        let terms = names
+            .into_iter()
+            .map(|name| self.selection(name, negated))
+            .collect();
+        combine(terms, operator.negate(negated))
+    }
+
+    fn selection(&mut self, name: &str, negated: bool) -> Expression {
+        match &self.rule.detection.get_selections()[name] {
+            Selection::Keyword(_) => self.unsupported(format!(
+                "keyword selection '{}' outside of the top-level conjunction",
+                name
+            )),
+            Selection::Field(groups) => {
+                let terms = groups
+                    .iter()
+                    .map(|group| self.field_group(group, negated))
+                    .collect();
+                combine(terms, Operator::Or.negate(negated))
+            }
+        }
+    }
+
+    fn field_group(&mut self, group: &FieldGroup, negated: bool) -> Expression {
+        let terms = group
+            .fields
+            .iter()
+            .map(|field| self.field(field, negated))
+            .collect();
+        combine(terms, Operator::And.negate(negated))
+    }
+
+    fn field(&mut self, field: &Field, negated: bool) -> Expression {
+        // Placeholder lists are translated with their current values
+        if let Some(live) = &field.live {
+            return live.current().and_then(|field| self.field(&field, negated));
+        }
+        let label = label(&field.name);
+        let modifier = &field.modifier;
+        if let Some(exists) = modifier.exists {
+            let operator = if exists != negated { "!=" } else { "=" };
+            return Some(format!(r#"{}{}"""#, label, operator));
+        }
+        if modifier.fieldref {
+            return self.unsupported(format!("fieldref modifier of field '{}'", field.name));
+        }
+
+        let require_all =
+            modifier.match_all || matches!(modifier.collection, Some(CollectionMatch::All));
+        let operator = if require_all {
+            Operator::And
+        } else {
+            Operator::Or
+        };
+        let terms = field
+            .values
+            .iter()
+            .map(|value| self.value(field, &label, value, negated))
+            .collect();
+        combine(terms, operator.negate(negated))
+    }
+
+    fn value(
+        &mut self,
+        field: &Field,
+        label: &str,
+        value: &FieldValue,
+        negated: bool,
+    ) -> Expression {
+        let (equal, regex) = if negated { ("!=", "!~") } else { ("=", "=~") };
+        match value {
+            FieldValue::WildcardPattern(tokens) => {
+                if let ([WildcardToken::Pattern(literal)], true) =
+                    (&tokens[..], field.modifier.cased)
+                {
+                    let literal: String = literal.iter().collect();
+                    return Some(format!("{}{}{}", label, equal, string(&literal)));
+                }
+                let flags = if field.modifier.cased { "" } else { "(?i)" };
+                Some(format!(
+                    "{}{}{}",
+                    label,
+                    regex,
+                    string(&format!("{}{}", flags, wildcard_regex(tokens)))
+                ))
+            }
+            // Regular expressions of label filters are anchored, in contrast to Sigma
+            FieldValue::Regex(re) => Some(format!(
+                "{}{}{}",
+                label,
+                regex,
+                string(&format!("(?s).*(?:{}).*", re.as_str()))
+            )),
+            #[cfg(feature = "cidr")]
+            FieldValue::Cidr(cidr) => Some(format!(
+                "{}{}ip({})",
+                label,
+                equal,
+                string(&cidr.to_string())
+            )),
+            FieldValue::Base(value) => {
+                let operator = match (&field.modifier.match_modifier, negated) {
+                    (Some(MatchModifier::Gt), false) | (Some(MatchModifier::Lte), true) => ">",
+                    (Some(MatchModifier::Gte), false) | (Some(MatchModifier::Lt), true) => ">=",
+                    (Some(MatchModifier::Lt), false) | (Some(MatchModifier::Gte), true) => "<",
+                    (Some(MatchModifier::Lte), false) | (Some(MatchModifier::Gt), true) => "<=",
+                    (_, false) => "==",
+                    (_, true) => "!=",
+                };
+                match value {
+                    BaseValue::Int(_) | BaseValue::Unsigned(_) | BaseValue::Float(_) => {
+                        Some(format!("{}{}{}", label, operator, value.value_to_string()))
+                    }
+                    BaseValue::Boolean(_) | BaseValue::Null => Some(format!(
+                        "{}{}{}",
+                        label,
+                        equal,
+                        string(&value.value_to_string())
+                    )),
+                    #[cfg(any(feature = "chrono", feature = "time"))]
+                    BaseValue::Timestamp(_) => {
+                        self.unsupported(format!("timestamp comparison of field '{}'", field.name))
+                    }
+                    BaseValue::String(_) => {
+                        self.unsupported(format!("string comparison of field '{}'", field.name))
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Combine expressions with an operator. If any operand of an `or` matches everything, so
+/// does the whole expression, while such operands can be left out of an `and`.
+fn combine(terms: Vec<Expression>, operator: Operator) -> Expression {
+    if operator == Operator::Or && terms.iter().any(Option::is_none) {
+        return None;
+    }
+    let terms: Vec<String> = terms.into_iter().flatten().collect();
+    if terms.is_empty() {
+        return None;
+    }
+    Some(join(terms, operator))
+}
+
+/// Join terms with an operator. As `and` binds stronger than `or`, only disjunctions need
+/// parentheses.
+fn join(terms: Vec<String>, operator: Operator) -> String {
+    if terms.len() == 1 {
+        return terms.into_iter().next().unwrap();
+    }
+    match operator {
+        Operator::And => terms.join(" and "),
+        Operator::Or => format!("({})", terms.join(" or ")),
+    }
+}
+
+/// Convert wildcard tokens to an RE2 expression that matches the whole value
+fn wildcard_regex(tokens: &[WildcardToken]) -> String {
+    let mut result = String::from("(?s)");
+    for token in tokens {
+        match token {
+            WildcardToken::Star => result.push_str(".*"),
+            WildcardToken::QuestionMark => result.push('.'),
+            WildcardToken::Pattern(chars) => {
+                result.push_str(&crate::re::escape(&chars.iter().collect::<String>()))
+            }
+        }
+    }
+    result
+}
+
+/// Keywords match a whole value of the event, which is somewhere within the log line
+fn keyword_regex(keyword: &Keyword) -> String {
+    let tokens: &[WildcardToken] = &keyword.pattern;
+    let start = usize::from(matches!(tokens.first(), Some(WildcardToken::Star)));
+    let end = tokens.len()
+        - usize::from(tokens.len() > start && matches!(tokens.last(), Some(WildcardToken::Star)));
+    wildcard_regex(&tokens[start..end])
+        .trim_start_matches("(?s)")
+        .to_string()
+}
+
+/// Labels may only contain ASCII letters, digits and underscores
+fn label(name: &str) -> String {
+    let label: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if label.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", label)
+    } else {
+        label
+    }
+}
+
+/// A raw string literal, in which backslashes are not escape characters
+fn string(value: &str) -> String {
+    if value.contains('`') {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        format!("`{}`", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    fn logql(detection: &str) -> LogQlQuery {
+        let rule = rule_from_yaml(&format!(
+            "title: Test\nlogsource:\ndetection:\n{}",
+            detection
+        ))
+        .unwrap();
+        to_logql(&rule, &LogQlOptions::default())
+    }
+
+    #[cfg(feature = "cidr")]
+    #[test]
+    fn test_label_filters() {
+        let result = logql(
+            r#"
+    selection:
+        Image|endswith:
+            - '\cmd.exe'
+            - '\powershell.exe'
+        Event.ID|gte: 4
+        User|cased: SYSTEM
+    filter:
+        DestinationIp|cidr: '10.0.0.0/8'
+        Parent|exists: true
+    condition: selection and not filter
+"#,
+        );
+        assert!(result.unsupported.is_empty());
+        assert_eq!(
+            result.query,
+            [
+                r#"{job=~".+"} | json | (Image=~`(?i)(?s).*\\cmd\.exe` or Image=~`(?i)(?s).*\\powershell\.exe`)"#,
+                r#"Event_ID>=4"#,
+                r#"User=`SYSTEM`"#,
+                r#"(DestinationIp!=ip(`10.0.0.0/8`) or Parent="")"#,
+            ]
+            .join(" and ")
+        );
+    }
+
+    #[test]
+    fn test_line_filters() {
+        let result = logql(
+            r#"
+    keywords:
+        - 'mimikatz'
+        - '*sekurlsa::*'
+    filter:
+        - 'benign'
+    selection:
+        CommandLine|re: 'a+'
+    condition: keywords and not filter and selection
+"#,
+        );
+        assert!(result.unsupported.is_empty());
+        assert_eq!(
+            result.query,
+            r#"{job=~".+"} |~ `(?i)mimikatz|sekurlsa::` !~ `(?i)benign` | json | CommandLine=~`(?s).*(?:a+).*`"#
+        );
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let result = logql(
+            r#"
+    keywords:
+        - 'evil'
+    selection:
+        A|fieldref: B
+    other:
+        C: 1
+    condition: (keywords or other) and not (selection or other)
+"#,
+        );
+        assert_eq!(result.unsupported.len(), 2);
+        // `not (selection or other)` is widened to `not other`
+        assert_eq!(result.query, r#"{job=~".+"} | json | C!=1"#);
+    }
+}
@@ -0,0 +1,422 @@
+use super::{literal, translate, BackendError, Language, Operator};
+use crate::basevalue::BaseValue;
+use crate::field::{Field, FieldValue, MatchModifier};
+use crate::rule::Rule;
+use crate::selection::Keyword;
+use crate::wildcard::WildcardToken;
+#[cfg(feature = "cidr")]
+use cidr::IpCidr;
+
+/// Hooks for the parts of SQL that differ between database systems. The default
+/// implementations generate ANSI SQL and reject features without a standard representation.
+pub trait SqlDialect {
+    /// Quote a field name as a column identifier
+    fn identifier(&self, name: &str) -> String {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+
+    /// Quote a string literal
+    fn string(&self, value: &str) -> String {
+        format!("'{}'", value.replace('\'', "''"))
+    }
+
+    /// Lowercase an expression for case-insensitive comparisons
+    fn lower(&self, expression: &str) -> String {
+        format!("LOWER({})", expression)
+    }
+
+    /// Match a column against a `LIKE` pattern that escapes `%`, `_` and `\` with `\`
+    fn like(&self, column: &str, pattern: &str) -> String {
+        format!("{} LIKE {} ESCAPE '\\'", column, self.string(pattern))
+    }
+
+    /// Match a column case-insensitively against a lowercase `LIKE` pattern, see [`SqlDialect::like`]
+    fn ilike(&self, column: &str, pattern: &str) -> String {
+        self.like(&self.lower(column), pattern)
+    }
+
+    /// Match a column against a regular expression of the `|re` modifier
+    fn regex(&self, column: &str, pattern: &str) -> Result<String, BackendError> {
+        let _ = (column, pattern);
+        Err(BackendError::Unsupported("regular expressions".to_string()))
+    }
+
+    /// Check if the column contains an IP address within a CIDR range of the `|cidr` modifier
+    #[cfg(feature = "cidr")]
+    fn cidr(&self, column: &str, cidr: &IpCidr) -> Result<String, BackendError> {
+        let _ = (column, cidr);
+        Err(BackendError::Unsupported("CIDR ranges".to_string()))
+    }
+}
+
+/// ANSI SQL without support for regular expressions and CIDR ranges
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AnsiSql;
+
+impl SqlDialect for AnsiSql {}
+
+/// PostgreSQL, which supports regular expressions and the `inet` type
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PostgreSql;
+
+impl SqlDialect for PostgreSql {
+    fn regex(&self, column: &str, pattern: &str) -> Result<String, BackendError> {
+        Ok(format!("{} ~ {}", column, self.string(pattern)))
+    }
+
+    #[cfg(feature = "cidr")]
+    fn cidr(&self, column: &str, cidr: &IpCidr) -> Result<String, BackendError> {
+        Ok(format!(
+            "CAST({} AS inet) <<= {}",
+            column,
+            self.string(&cidr.to_string())
+        ))
+    }
+}
+
+/// MySQL, which supports regular expressions but has no CIDR operator
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MySql;
+
+impl SqlDialect for MySql {
+    fn identifier(&self, name: &str) -> String {
+        format!("`{}`", name.replace('`', "``"))
+    }
+
+    fn string(&self, value: &str) -> String {
+        format!("'{}'", value.replace('\\', "\\\\").replace('\'', "''"))
+    }
+
+    fn like(&self, column: &str, pattern: &str) -> String {
+        format!("{} LIKE {} ESCAPE '\\\\'", column, self.string(pattern))
+    }
+
+    fn regex(&self, column: &str, pattern: &str) -> Result<String, BackendError> {
+        Ok(format!("{} REGEXP {}", column, self.string(pattern)))
+    }
+}
+
+/// ClickHouse, which matches case-insensitively with `ILIKE`, supports RE2 regular expressions
+/// with `match` and CIDR ranges with `isIPAddressInRange`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ClickHouse;
+
+impl SqlDialect for ClickHouse {
+    fn identifier(&self, name: &str) -> String {
+        format!("`{}`", name.replace('\\', "\\\\").replace('`', "\\`"))
+    }
+
+    fn string(&self, value: &str) -> String {
+        format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+    }
+
+    fn lower(&self, expression: &str) -> String {
+        format!("lowerUTF8({})", expression)
+    }
+
+    // Backslash is the escape character of `LIKE` patterns, there is no `ESCAPE` clause
+    fn like(&self, column: &str, pattern: &str) -> String {
+        format!("{} LIKE {}", column, self.string(pattern))
+    }
+
+    fn ilike(&self, column: &str, pattern: &str) -> String {
+        format!("{} ILIKE {}", column, self.string(pattern))
+    }
+
+    fn regex(&self, column: &str, pattern: &str) -> Result<String, BackendError> {
+        Ok(format!("match({}, {})", column, self.string(pattern)))
+    }
+
+    #[cfg(feature = "cidr")]
+    fn cidr(&self, column: &str, cidr: &IpCidr) -> Result<String, BackendError> {
+        Ok(format!(
+            "isIPAddressInRange({}, {})",
+            column,
+            self.string(&cidr.to_string())
+        ))
+    }
+}
+
+/// Translate the detection of a rule into the expression of an SQL `WHERE` clause
+pub fn to_sql(rule: &Rule, dialect: &dyn SqlDialect) -> Result<String, BackendError> {
+    translate(rule, &Sql(dialect))
+}
+
+struct Sql<'a>(&'a dyn SqlDialect);
+
+impl Language for Sql<'_> {
+    fn operator(&self, operator: Operator) -> &'static str {
+        match operator {
+            Operator::And => " AND ",
+            _ => " OR ",
+        }
+    }
+
+    fn constant(&self, value: bool) -> String {
+        if value { "TRUE" } else { "FALSE" }.to_string()
+    }
+
+    // A comparison with a missing value is unknown in SQL, but false in the engine,
+    // so it must be made false before negating it
+    fn not(&self, operand: String) -> String {
+        format!("NOT COALESCE({}, FALSE)", operand)
+    }
+
+    fn keyword(&self, _: &Keyword) -> Result<String, BackendError> {
+        Err(BackendError::Unsupported(
+            "keyword selections, as they are not bound to a column".to_string(),
+        ))
+    }
+
+    fn exists(&self, field: &Field, exists: bool) -> String {
+        let operator = if exists { "IS NOT NULL" } else { "IS NULL" };
+        format!("{} {}", self.0.identifier(&field.name), operator)
+    }
+
+    fn value(&self, field: &Field, value: &FieldValue) -> Result<String, BackendError> {
+        let dialect = self.0;
+        let column = dialect.identifier(&field.name);
+        let modifier = &field.modifier;
+        match value {
+            // Patterns of case-insensitive fields are already lowercase
+            FieldValue::WildcardPattern(pattern) => Ok(match (literal(pattern), modifier.cased) {
+                (Some(literal), true) => format!("{} = {}", column, dialect.string(&literal)),
+                (Some(literal), false) => {
+                    format!("{} = {}", dialect.lower(&column), dialect.string(&literal))
+                }
+                (None, true) => dialect.like(&column, &like_pattern(pattern)),
+                (None, false) => dialect.ilike(&column, &like_pattern(pattern)),
+            }),
+            FieldValue::Regex(regex) => dialect.regex(&column, regex.as_str()),
+            #[cfg(feature = "cidr")]
+            FieldValue::Cidr(cidr) => dialect.cidr(&column, cidr),
+            FieldValue::Base(value) if modifier.fieldref => match modifier.match_modifier {
+                None => Ok(format!(
+                    "{} = {}",
+                    column,
+                    dialect.identifier(&value.value_to_string())
+                )),
+                Some(_) => Err(BackendError::Unsupported(
+                    "the fieldref modifier combined with other modifiers".to_string(),
+                )),
+            },
+            FieldValue::Base(BaseValue::Null) => Ok(format!("{} IS NULL", column)),
+            FieldValue::Base(value) => {
+                let operator = match modifier.match_modifier {
+                    Some(MatchModifier::Gt) => ">",
+                    Some(MatchModifier::Gte) => ">=",
+                    Some(MatchModifier::Lt) => "<",
+                    Some(MatchModifier::Lte) => "<=",
+                    _ => "=",
+                };
+                Ok(format!("{} {} {}", column, operator, self.literal(value)))
+            }
+        }
+    }
+}
+
+impl Sql<'_> {
+    fn literal(&self, value: &BaseValue) -> String {
+        match value {
+            BaseValue::String(s) => self.0.string(s),
+            BaseValue::Boolean(b) => self.constant(*b),
+            BaseValue::Null => "NULL".to_string(),
+            #[cfg(any(feature = "chrono", feature = "time"))]
+            BaseValue::Timestamp(_) => self.0.string(&value.value_to_string()),
+            BaseValue::Int(_) | BaseValue::Unsigned(_) | BaseValue::Float(_) => {
+                value.value_to_string()
+            }
+        }
+    }
+}
+
+/// Convert wildcard tokens to a `LIKE` pattern escaped with `\`
+fn like_pattern(tokens: &[WildcardToken]) -> String {
+    let mut result = String::new();
+    for token in tokens {
+        match token {
+            WildcardToken::Star => result.push('%'),
+            WildcardToken::QuestionMark => result.push('_'),
+            WildcardToken::Pattern(chars) => {
+                for c in chars {
+                    if matches!(c, '%' | '_' | '\\') {
+                        result.push('\\');
+                    }
+                    result.push(*c);
+                }
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    fn sql(detection: &str, dialect: &dyn SqlDialect) -> Result<String, BackendError> {
+        let rule = rule_from_yaml(&format!(
+            "title: Test\nlogsource:\ndetection:\n{}",
+            detection
+        ))
+        .unwrap();
+        to_sql(&rule, dialect)
+    }
+
+    #[test]
+    fn test_modifiers() {
+        let result = sql(
+            r#"
+    selection:
+        CommandLine|contains|all:
+            - 'invoke'
+            - '50%_off'
+        Image|cased|startswith: 'C:\Windows\'
+        User:
+            - 'admin'
+            - 'x?y*'
+        Pid|gte: 4
+        Parent|exists: false
+        Elevated: true
+        Token: null
+    condition: selection
+"#,
+            &AnsiSql,
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            [
+                r#"LOWER("CommandLine") LIKE '%invoke%' ESCAPE '\' AND LOWER("CommandLine") LIKE '%50\%\_off%' ESCAPE '\'"#,
+                r#""Image" LIKE 'C:\\Windows\\%' ESCAPE '\'"#,
+                r#"(LOWER("User") = 'admin' OR LOWER("User") LIKE 'x_y%' ESCAPE '\')"#,
+                r#""Pid" >= 4"#,
+                r#""Parent" IS NULL"#,
+                r#""Elevated" = TRUE"#,
+                r#""Token" IS NULL"#,
+            ]
+            .join(" AND ")
+        );
+    }
+
+    #[cfg(feature = "cidr")]
+    #[test]
+    fn test_condition() {
+        let detection = r#"
+    selection_a:
+        - A: 'a'
+        - B: 'b'
+    selection_b:
+        C|re: 'c+'
+    filter:
+        D|cidr: '10.0.0.0/8'
+    condition: (1 of selection_* or not filter) and all of them
+"#;
+        assert_eq!(
+            sql(detection, &PostgreSql).unwrap(),
+            [
+                r#"(LOWER("A") = 'a' OR LOWER("B") = 'b' OR "C" ~ 'c+' OR NOT COALESCE(CAST("D" AS inet) <<= '10.0.0.0/8', FALSE))"#,
+                r#"CAST("D" AS inet) <<= '10.0.0.0/8'"#,
+                r#"(LOWER("A") = 'a' OR LOWER("B") = 'b')"#,
+                r#""C" ~ 'c+'"#,
+            ]
+            .join(" AND ")
+        );
+        assert!(matches!(
+            sql(detection, &AnsiSql),
+            Err(BackendError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_dialects() {
+        let detection = r#"
+    selection:
+        Image|endswith: '\cmd.exe'
+        Comment: "it's"
+        Other|fieldref: Image
+        Cmd|re: '\d'
+    condition: selection
+"#;
+        assert_eq!(
+            sql(detection, &MySql).unwrap(),
+            r#"LOWER(`Image`) LIKE '%\\\\cmd.exe' ESCAPE '\\' AND LOWER(`Comment`) = 'it''s' AND `Other` = `Image` AND `Cmd` REGEXP '\\d'"#
+        );
+    }
+
+    #[cfg(feature = "cidr")]
+    #[test]
+    fn test_clickhouse() {
+        let detection = r#"
+    selection:
+        Image|endswith: '\cmd.exe'
+        User: "o'neil"
+        Path|cased|contains: '50%'
+        Cmd|re: '\d+'
+    filter:
+        DestinationIp|cidr: '10.0.0.0/8'
+    condition: selection and not filter
+"#;
+        assert_eq!(
+            sql(detection, &ClickHouse).unwrap(),
+            [
+                r#"`Image` ILIKE '%\\\\cmd.exe'"#,
+                r#"lowerUTF8(`User`) = 'o\'neil'"#,
+                r#"`Path` LIKE '%50\\%%'"#,
+                r#"match(`Cmd`, '\\d+')"#,
+                r#"NOT COALESCE(isIPAddressInRange(`DestinationIp`, '10.0.0.0/8'), FALSE)"#,
+            ]
+            .join(" AND ")
+        );
+    }
+
+    #[test]
+    fn test_subdomain_of() {
+        let result = sql(
+            r#"
+    selection:
+        Status: 'ok'
+        QueryName|subdomainof: 'Evil.com.'
+    condition: selection
+"#,
+            &AnsiSql,
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            [
+                r#"LOWER("Status") = 'ok'"#,
+                r#"(LOWER("QueryName") = 'evil.com' OR LOWER("QueryName") = 'evil.com.' OR LOWER("QueryName") LIKE '%.evil.com' ESCAPE '\' OR LOWER("QueryName") LIKE '%.evil.com.' ESCAPE '\')"#,
+            ]
+            .join(" AND ")
+        );
+    }
+
+    #[test]
+    fn test_radix_literals() {
+        let result = sql(
+            r#"
+    selection:
+        AccessMask: '0x1010'
+        Mode|gte: '0o700'
+    condition: selection
+"#,
+            &AnsiSql,
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            r#"LOWER("AccessMask") = '0x1010' AND "Mode" >= 448"#
+        );
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let keywords = "    keywords:\n        - 'evil'\n    condition: keywords";
+        assert!(sql(keywords, &PostgreSql).is_err());
+
+        let fieldref = "    selection:\n        A|fieldref|contains: B\n    condition: selection";
+        assert!(sql(fieldref, &PostgreSql).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use super::{translate, BackendError, Language, Operator};
+use crate::basevalue::BaseValue;
+use crate::field::{Field, FieldValue, MatchModifier};
+use crate::rule::Rule;
+use crate::selection::Keyword;
+use crate::wildcard::WildcardToken;
+
+/// Describe the detection of a rule in plain words, e.g. for documentation. The description is
+/// produced from the compiled rule, so expanded values like the variants of `base64offset` are
+/// listed and values of case-insensitive fields are lowercase.
+pub(crate) fn to_summary(rule: &Rule) -> String {
+    translate(rule, &Summary).expect("every construct can be summarized")
+}
+
+struct Summary;
+
+impl Language for Summary {
+    fn operator(&self, operator: Operator) -> &'static str {
+        match operator {
+            Operator::And => " and ",
+            _ => " or ",
+        }
+    }
+
+    fn constant(&self, value: bool) -> String {
+        value.to_string()
+    }
+
+    fn not(&self, operand: String) -> String {
+        format!("not ({})", operand)
+    }
+
+    fn keyword(&self, keyword: &Keyword) -> Result<String, BackendError> {
+        Ok(format!("any value matches {}", quote(&keyword.value)))
+    }
+
+    fn exists(&self, field: &Field, exists: bool) -> String {
+        if exists {
+            format!("{} exists", field.name)
+        } else {
+            format!("{} does not exist", field.name)
+        }
+    }
+
+    fn value(&self, field: &Field, value: &FieldValue) -> Result<String, BackendError> {
+        let name = &field.name;
+        let modifier = &field.modifier;
+        let cased = if modifier.cased {
+            " (case-sensitive)"
+        } else {
+            ""
+        };
+        Ok(match value {
+            FieldValue::WildcardPattern(pattern) => {
+                let (operator, tokens): (&str, &[WildcardToken]) = match &pattern[..] {
+                    [WildcardToken::Star, p @ WildcardToken::Pattern(_), WildcardToken::Star] => {
+                        ("contains", std::slice::from_ref(p))
+                    }
+                    [p @ WildcardToken::Pattern(_), WildcardToken::Star] => {
+                        ("starts with", std::slice::from_ref(p))
+                    }
+                    [WildcardToken::Star, p @ WildcardToken::Pattern(_)] => {
+                        ("ends with", std::slice::from_ref(p))
+                    }
+                    [WildcardToken::Pattern(_)] => ("is", &pattern[..]),
+                    tokens => ("matches", tokens),
+                };
+                format!(
+                    "{} {} {}{}",
+                    name,
+                    operator,
+                    quote(&wildcard(tokens)),
+                    cased
+                )
+            }
+            FieldValue::Regex(regex) => format!("{} matches regex {}", name, quote(regex.as_str())),
+            #[cfg(feature = "cidr")]
+            FieldValue::Cidr(cidr) => format!("{} is in {}", name, cidr),
+            FieldValue::Base(value) if modifier.fieldref => {
+                format!("{} equals the field {}", name, value.value_to_string())
+            }
+            FieldValue::Base(BaseValue::Null) => format!("{} is null", name),
+            FieldValue::Base(value) => {
+                let operator = match modifier.match_modifier {
+                    Some(MatchModifier::Gt) => ">",
+                    Some(MatchModifier::Gte) => ">=",
+                    Some(MatchModifier::Lt) => "<",
+                    Some(MatchModifier::Lte) => "<=",
+                    _ => "is",
+                };
+                let value = match value {
+                    BaseValue::String(s) => quote(s),
+                    _ => value.value_to_string(),
+                };
+                format!("{} {} {}{}", name, operator, value, cased)
+            }
+        })
+    }
+}
+
+/// Render wildcard tokens in the syntax of Sigma values
+fn wildcard(tokens: &[WildcardToken]) -> String {
+    let mut result = String::new();
+    for token in tokens {
+        match token {
+            WildcardToken::Star => result.push('*'),
+            WildcardToken::QuestionMark => result.push('?'),
+            WildcardToken::Pattern(chars) => {
+                for c in chars {
+                    if matches!(c, '*' | '?') {
+                        result.push('\\');
+                    }
+                    result.push(*c);
+                }
+            }
+        }
+    }
+    result
+}
+
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    fn summary(detection: &str) -> String {
+        let rule = rule_from_yaml(&format!(
+            "title: Test\nlogsource:\ndetection:\n{}",
+            detection
+        ))
+        .unwrap();
+        to_summary(&rule)
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(
+            summary(
+                r#"
+    selection:
+        Image|endswith: '\whoami.exe'
+        CommandLine|contains|all: [' /all', ' /priv']
+    filter:
+        User|cased: SYSTEM
+    condition: selection and not filter
+"#
+            ),
+            r"Image ends with '\whoami.exe' and CommandLine contains ' /all' and CommandLine contains ' /priv' and not (User is 'SYSTEM' (case-sensitive))"
+        );
+    }
+
+    #[cfg(feature = "cidr")]
+    #[test]
+    fn test_value_kinds() {
+        assert_eq!(
+            summary(
+                r#"
+    selection:
+        - Path: 'C:\*\temp?.exe'
+        - Name: 'it''s \*'
+        - SourceIp|cidr: 10.0.0.0/8
+        - CommandLine|re: 'a+b'
+        - Count|gte: 5
+        - Parent|fieldref: Image
+        - Hash: null
+        - Service|exists: false
+    keywords:
+        - mimikatz
+    condition: selection or keywords
+"#
+            ),
+            r"Path matches 'c:\*\temp?.exe' or Name is 'it''s \*' or SourceIp is in 10.0.0.0/8 or CommandLine matches regex 'a+b' or Count >= 5 or Parent equals the field Image or Hash is null or Service does not exist or any value matches 'mimikatz'"
+        );
+    }
+}
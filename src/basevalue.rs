@@ -1,7 +1,11 @@
 use crate::error::ParserError;
+use std::borrow::Cow;
 use std::cmp::Ordering;
+#[cfg(any(feature = "chrono", feature = "time"))]
+use std::time::SystemTime;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
 pub enum BaseValue {
     String(String),
     Int(i64),
@@ -9,6 +13,9 @@ pub enum BaseValue {
     Float(f64),
     Boolean(bool),
     Null,
+    /// A point in time, created from `chrono` or `time` timestamps, see [`crate::Event::insert`]
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    Timestamp(SystemTime),
 }
 
 impl From<i32> for BaseValue {
@@ -83,6 +90,12 @@ impl PartialEq for BaseValue {
             (Self::Float(a), Self::Float(b)) => a.eq(b),
             (Self::Boolean(a), Self::Boolean(b)) => a.eq(b),
             (Self::Null, Self::Null) => true,
+            #[cfg(any(feature = "chrono", feature = "time"))]
+            (Self::Timestamp(a), Self::Timestamp(b)) => a.eq(b),
+            #[cfg(any(feature = "chrono", feature = "time"))]
+            (Self::Timestamp(t), Self::String(s)) | (Self::String(s), Self::Timestamp(t)) => {
+                crate::timestamp::parse(s).is_some_and(|s| s.eq(t))
+            }
             _ => false,
         }
     }
@@ -97,12 +110,31 @@ impl PartialOrd for BaseValue {
             (Self::Float(a), Self::Float(b)) => a.partial_cmp(b),
             (Self::Boolean(a), Self::Boolean(b)) => a.partial_cmp(b),
             (Self::Null, Self::Null) => Some(Ordering::Equal),
+            // Strings are compared by their point in time if they are RFC 3339 timestamps
+            #[cfg(any(feature = "chrono", feature = "time"))]
+            (Self::Timestamp(a), Self::Timestamp(b)) => a.partial_cmp(b),
+            #[cfg(any(feature = "chrono", feature = "time"))]
+            (Self::Timestamp(a), Self::String(b)) => crate::timestamp::parse(b)
+                .as_ref()
+                .and_then(|b| a.partial_cmp(b)),
+            #[cfg(any(feature = "chrono", feature = "time"))]
+            (Self::String(a), Self::Timestamp(b)) => {
+                crate::timestamp::parse(a).and_then(|a| a.partial_cmp(b))
+            }
             _ => None,
         }
     }
 }
 
 impl BaseValue {
+    /// Like [`BaseValue::value_to_string`] but borrows strings instead of copying them
+    pub(crate) fn value_as_str(&self) -> Cow<'_, str> {
+        match self {
+            Self::String(s) => Cow::Borrowed(s),
+            _ => Cow::Owned(self.value_to_string()),
+        }
+    }
+
     pub(crate) fn value_to_string(&self) -> String {
         match self {
             Self::String(s) => s.to_string(),
@@ -111,20 +143,74 @@ impl BaseValue {
             Self::Unsigned(u) => u.to_string(),
             Self::Boolean(b) => b.to_string(),
             Self::Null => "".to_string(),
+            #[cfg(any(feature = "chrono", feature = "time"))]
+            Self::Timestamp(t) => crate::timestamp::format(t),
+        }
+    }
+
+    /// The value as a number if it is one or a string that contains one, including hexadecimal
+    /// and octal literals, see [`crate::FieldPolicy::Numeric`]
+    pub(crate) fn as_number(&self) -> Option<f64> {
+        match self {
+            Self::Int(i) => Some(*i as f64),
+            Self::Unsigned(u) => Some(*u as f64),
+            Self::Float(f) => Some(*f),
+            Self::String(s) => {
+                let s = s.trim();
+                s.parse()
+                    .ok()
+                    .filter(|f: &f64| f.is_finite())
+                    .or_else(|| Self::from_radix_literal(s)?.as_number())
+            }
+            _ => None,
+        }
+    }
+
+    /// Parse a hexadecimal or octal integer literal with an optional sign, e.g. `0x4D5A` or
+    /// `0o755`, into an [`BaseValue::Int`], or an [`BaseValue::Unsigned`] if it exceeds `i64`
+    pub(crate) fn from_radix_literal(s: &str) -> Option<Self> {
+        let (negative, literal) = match s.strip_prefix('-') {
+            Some(literal) => (true, literal),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (radix, digits) = if let Some(digits) = literal
+            .strip_prefix("0x")
+            .or_else(|| literal.strip_prefix("0X"))
+        {
+            (16, digits)
+        } else if let Some(digits) = literal
+            .strip_prefix("0o")
+            .or_else(|| literal.strip_prefix("0O"))
+        {
+            (8, digits)
+        } else {
+            return None;
+        };
+        // `from_str_radix` would accept another sign
+        if digits.starts_with(['+', '-']) {
+            return None;
+        }
+        let magnitude = u64::from_str_radix(digits, radix).ok()?;
+        if negative {
+            i64::try_from(-i128::from(magnitude)).ok().map(Self::Int)
+        } else {
+            Some(i64::try_from(magnitude).map_or(Self::Unsigned(magnitude), Self::Int))
         }
     }
 }
 
+/// Convert a number to the narrowest value that holds it, or return `$error` for numbers that
+/// are not representable as `f64`, e.g. with arbitrary precision
 macro_rules! number {
-    ($n:expr) => {
+    ($n:expr, $error:expr) => {
         if let Some(i) = $n.as_i64() {
             Ok(Self::Int(i))
         } else if let Some(u) = $n.as_u64() {
             Ok(Self::Unsigned(u))
+        } else if let Some(f) = $n.as_f64() {
+            Ok(Self::Float(f))
         } else {
-            Ok(Self::Float(
-                $n.as_f64().expect("Number is neither Int nor Unsigned"),
-            ))
+            Err($error)
         }
     };
 }
@@ -136,7 +222,9 @@ impl TryFrom<serde_json::Value> for BaseValue {
     fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
         match value {
             serde_json::Value::String(s) => Ok(Self::String(s)),
-            serde_json::Value::Number(n) => number!(n),
+            serde_json::Value::Number(n) => {
+                number!(n, Self::Error::InvalidFieldValue(n.to_string()))
+            }
             serde_json::Value::Bool(b) => Ok(Self::Boolean(b)),
             serde_json::Value::Null => Ok(Self::Null),
             _ => Err(Self::Error::InvalidFieldValue(format!("{:?}", value))),
@@ -144,13 +232,44 @@ impl TryFrom<serde_json::Value> for BaseValue {
     }
 }
 
+#[cfg(feature = "serde_json")]
+impl From<&BaseValue> for serde_json::Value {
+    fn from(value: &BaseValue) -> Self {
+        match value {
+            BaseValue::String(s) => Self::from(s.as_str()),
+            BaseValue::Int(i) => Self::from(*i),
+            BaseValue::Unsigned(u) => Self::from(*u),
+            BaseValue::Float(f) => Self::from(*f),
+            BaseValue::Boolean(b) => Self::from(*b),
+            BaseValue::Null => Self::Null,
+            #[cfg(any(feature = "chrono", feature = "time"))]
+            BaseValue::Timestamp(t) => Self::from(crate::timestamp::format(t)),
+        }
+    }
+}
+
+impl From<&BaseValue> for serde_yml::Value {
+    fn from(value: &BaseValue) -> Self {
+        match value {
+            BaseValue::String(s) => Self::from(s.as_str()),
+            BaseValue::Int(i) => Self::from(*i),
+            BaseValue::Unsigned(u) => Self::from(*u),
+            BaseValue::Float(f) => Self::from(*f),
+            BaseValue::Boolean(b) => Self::from(*b),
+            BaseValue::Null => Self::Null,
+            #[cfg(any(feature = "chrono", feature = "time"))]
+            BaseValue::Timestamp(t) => Self::from(crate::timestamp::format(t)),
+        }
+    }
+}
+
 impl TryFrom<serde_yml::Value> for BaseValue {
     type Error = ParserError;
 
     fn try_from(value: serde_yml::Value) -> Result<Self, Self::Error> {
         match value {
             serde_yml::Value::Bool(b) => Ok(Self::Boolean(b)),
-            serde_yml::Value::Number(n) => number!(n),
+            serde_yml::Value::Number(n) => number!(n, ParserError::InvalidYAML(n.to_string())),
             serde_yml::Value::String(s) => Ok(Self::String(s)),
             serde_yml::Value::Null => Ok(Self::Null),
             _ => Err(ParserError::InvalidYAML(format!("{:?}", value))),
@@ -197,4 +316,32 @@ mod tests {
         let base_value = BaseValue::try_from(v["EventID"].clone()).unwrap();
         assert_eq!(base_value, BaseValue::Unsigned(18446744073709551615));
     }
+
+    #[test]
+    fn test_radix_literals() {
+        let parse = BaseValue::from_radix_literal;
+        assert_eq!(parse("0x4D5A"), Some(BaseValue::Int(0x4D5A)));
+        assert_eq!(parse("0X4d5a"), Some(BaseValue::Int(0x4D5A)));
+        assert_eq!(parse("0o755"), Some(BaseValue::Int(0o755)));
+        assert_eq!(parse("-0x10"), Some(BaseValue::Int(-16)));
+        assert_eq!(parse("+0o10"), Some(BaseValue::Int(8)));
+        assert_eq!(parse("-0x8000000000000000"), Some(BaseValue::Int(i64::MIN)));
+        assert_eq!(
+            parse("0xFFFFFFFFFFFFFFFF"),
+            Some(BaseValue::Unsigned(u64::MAX))
+        );
+        for invalid in [
+            "0x",
+            "0x-1",
+            "0o8",
+            "0755",
+            "0xG",
+            "x10",
+            "0b101",
+            "-0xFFFFFFFFFFFFFFFF",
+        ] {
+            assert_eq!(parse(invalid), None, "{}", invalid);
+        }
+        assert_eq!(BaseValue::from(" 0x10 ").as_number(), Some(16.0));
+    }
 }
@@ -0,0 +1,200 @@
+//! Representative rule sets and synthetic events for benchmarking.
+//!
+//! The crate's own criterion benches are built on this module, but it is public so users can
+//! measure the engine against their own corpora, e.g. by loading their rule repository with
+//! [`Loader::load_dir`](crate::loader::Loader::load_dir) and evaluating [`EventGenerator`]
+//! events or their own logs.
+//!
+//! The generators are deterministic, so results are comparable across runs.
+use crate::{rule_from_yaml, Event, Rule};
+
+const IMAGES: [&str; 8] = [
+    "C:\\Windows\\System32\\cmd.exe",
+    "C:\\Windows\\System32\\WindowsPowerShell\\v1.0\\powershell.exe",
+    "C:\\Windows\\explorer.exe",
+    "C:\\Windows\\System32\\svchost.exe",
+    "C:\\Program Files\\Mozilla Firefox\\firefox.exe",
+    "C:\\Users\\Public\\temp\\update.exe",
+    "C:\\Windows\\System32\\whoami.exe",
+    "C:\\Windows\\System32\\rundll32.exe",
+];
+
+const COMMAND_LINES: [&str; 8] = [
+    "cmd.exe /c whoami /all",
+    "powershell.exe -NoProfile -EncodedCommand SQBFAFgAIAAoAE4AZQB3AC0ATwBiAGoAZQBjAHQA",
+    "explorer.exe",
+    "svchost.exe -k netsvcs -p -s Schedule",
+    "firefox.exe -contentproc --channel=1234 -childID 1",
+    "update.exe --silent --install",
+    "whoami /priv",
+    "rundll32.exe C:\\Users\\Public\\payload.dll,DllRegisterServer",
+];
+
+const USERS: [&str; 4] = ["SYSTEM", "admin", "alice", "svc_backup"];
+
+/// Field names used by the generated events and the rule set templates
+const FIELDS: [&str; 6] = [
+    "Image",
+    "CommandLine",
+    "ParentImage",
+    "User",
+    "Computer",
+    "SourceIp",
+];
+
+/// Detection templates covering the commonly used modifiers. `{n}` is replaced with the
+/// index of the rule, so the literals of the generated rules are distinct, and `{octet}`
+/// with the index modulo 256.
+const TEMPLATES: [&str; 8] = [
+    r#"
+    selection:
+        Image|endswith: '\tool{n}.exe'
+    condition: selection"#,
+    r#"
+    selection:
+        CommandLine|contains|all:
+            - ' -enc '
+            - 'payload{n}'
+    condition: selection"#,
+    r#"
+    selection:
+        ParentImage|endswith: '\explorer.exe'
+        Image|startswith: 'C:\Users\Public\{n}'
+    filter:
+        User: SYSTEM
+    condition: selection and not filter"#,
+    r#"
+    selection:
+        CommandLine|re: 'invoke-{n}[a-z]+'
+    condition: selection"#,
+    r#"
+    selection:
+        CommandLine|base64offset|contains: 'IEX {n}'
+    condition: selection"#,
+    r#"
+    selection:
+        SourceIp|cidr: '10.{octet}.0.0/16'
+    condition: selection"#,
+    r#"
+    keywords:
+        - 'mimikatz{n}'
+        - 'sekurlsa*{n}'
+    condition: keywords"#,
+    r#"
+    selection_img:
+        - Image|endswith: '\rundll32.exe'
+        - Image|endswith: '\regsvr32.exe'
+    selection_cli:
+        CommandLine|contains:
+            - 'DllRegisterServer{n}'
+            - 'javascript:{n}'
+    condition: all of selection_*"#,
+];
+
+/// Create `n` rules by cycling through templates that cover the commonly used modifiers
+pub fn rule_set(n: usize) -> Vec<Rule> {
+    (0..n)
+        .map(|i| {
+            let detection = TEMPLATES[i % TEMPLATES.len()]
+                .replace("{n}", &i.to_string())
+                .replace("{octet}", &(i % 256).to_string());
+            rule_from_yaml(&format!(
+                "title: Benchmark rule {}\nid: bench-{}\nlevel: medium\nlogsource:\n    category: process_creation\ndetection:{}",
+                i, i, detection
+            ))
+            .expect("benchmark rule templates are valid")
+        })
+        .collect()
+}
+
+/// A deterministic generator of synthetic process creation events
+#[derive(Debug, Clone)]
+pub struct EventGenerator {
+    state: u64,
+}
+
+impl EventGenerator {
+    /// Create a generator. The same seed always produces the same events.
+    pub fn new(seed: u64) -> Self {
+        // xorshift must not be seeded with zero
+        Self { state: seed | 1 }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    fn pick<'a>(&mut self, values: &[&'a str]) -> &'a str {
+        values[(self.next_u64() % values.len() as u64) as usize]
+    }
+
+    /// Generate the next event
+    pub fn event(&mut self) -> Event {
+        let mut event = Event::new();
+        event.insert(FIELDS[0], self.pick(&IMAGES));
+        event.insert(FIELDS[1], self.pick(&COMMAND_LINES));
+        event.insert(FIELDS[2], self.pick(&IMAGES));
+        event.insert(FIELDS[3], self.pick(&USERS));
+        let n = self.next_u64();
+        event.insert(FIELDS[4], format!("WS-{:04}", n % 10_000));
+        event.insert(
+            FIELDS[5],
+            format!(
+                "10.{}.{}.{}",
+                (n >> 16) % 256,
+                (n >> 24) % 256,
+                (n >> 32) % 256
+            ),
+        );
+        event
+    }
+
+    /// Generate `n` events
+    pub fn events(&mut self, n: usize) -> Vec<Event> {
+        (0..n).map(|_| self.event()).collect()
+    }
+}
+
+impl Iterator for EventGenerator {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.event())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RuleSet;
+
+    #[test]
+    fn test_rule_set() {
+        let rules = rule_set(TEMPLATES.len() * 40);
+        assert_eq!(rules.len(), 320);
+        assert_eq!(rules[3].id.as_deref(), Some("bench-3"));
+    }
+
+    #[test]
+    fn test_events_are_deterministic() {
+        let events = EventGenerator::new(42).events(10);
+        assert_eq!(events, EventGenerator::new(42).events(10));
+        assert_ne!(events, EventGenerator::new(7).events(10));
+        assert!(events
+            .iter()
+            .all(|e| FIELDS.iter().all(|f| e.get(f).is_some())));
+    }
+
+    #[test]
+    fn test_some_events_match() {
+        let rule_set = RuleSet::new(rule_set(64));
+        let matches: usize = EventGenerator::new(1)
+            .take(1000)
+            .map(|e| rule_set.evaluate(&e).len())
+            .sum();
+        assert!(matches > 0);
+    }
+}
@@ -0,0 +1,141 @@
+use sigma_rust::pipeline::Pipeline;
+use sigma_rust::{event_from_json, rule_from_yaml, MatchResult, RuleSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// The active rule set, shared by all connections. Replacing it does not affect evaluations
+/// that are in progress.
+#[derive(Clone)]
+pub struct Engine {
+    rule_set: Arc<RwLock<Arc<RuleSet>>>,
+    pipeline: Option<Arc<Pipeline>>,
+    metrics: Arc<Metrics>,
+}
+
+/// Counters of the work done by an engine since it was started
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub events: AtomicU64,
+    pub invalid_events: AtomicU64,
+    pub matches: AtomicU64,
+    pub rule_set_updates: AtomicU64,
+}
+
+impl Engine {
+    pub fn new(rule_set: RuleSet, pipeline: Option<Pipeline>) -> Self {
+        Self {
+            rule_set: Arc::new(RwLock::new(Arc::new(rule_set))),
+            pipeline: pipeline.map(Arc::new),
+            metrics: Arc::default(),
+        }
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn rule_set(&self) -> Arc<RuleSet> {
+        self.rule_set.read().unwrap().clone()
+    }
+
+    /// Compile the rules, applying the pipeline of the server, and replace the active rule set.
+    /// If a rule cannot be compiled, the active rule set is kept.
+    pub fn replace<S: AsRef<str>>(&self, rules: &[S]) -> Result<usize, String> {
+        let rules = rules
+            .iter()
+            .enumerate()
+            .map(|(index, yaml)| {
+                let rule = match &self.pipeline {
+                    Some(pipeline) => pipeline
+                        .rule_from_yaml(yaml.as_ref())
+                        .map_err(|e| e.to_string()),
+                    None => rule_from_yaml(yaml.as_ref()).map_err(|e| e.to_string()),
+                };
+                rule.map_err(|e| format!("rule {}: {}", index + 1, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let rule_set = RuleSet::new(rules);
+        let len = rule_set.len();
+        *self.rule_set.write().unwrap() = Arc::new(rule_set);
+        self.metrics
+            .rule_set_updates
+            .fetch_add(1, Ordering::Relaxed);
+        Ok(len)
+    }
+
+    /// Enable or disable the rules of the active rule set with the given id, or title for rules
+    /// without an id. Returns whether such a rule exists. Replacing the rule set enables all
+    /// rules again.
+    pub fn set_enabled(&self, rule_id: &str, enabled: bool) -> bool {
+        let mut rule_set = self.rule_set.write().unwrap();
+        if rule_set.is_enabled(rule_id).is_none() {
+            return false;
+        }
+        // Evaluations in progress keep using the previous rule set
+        Arc::make_mut(&mut rule_set).set_enabled(rule_id, enabled)
+    }
+
+    /// Evaluate the active rules against an event given as JSON
+    pub fn evaluate(&self, event: &str) -> Result<Vec<MatchResult>, String> {
+        self.metrics.events.fetch_add(1, Ordering::Relaxed);
+        let event = event_from_json(event).map_err(|e| {
+            self.metrics.invalid_events.fetch_add(1, Ordering::Relaxed);
+            e.to_string()
+        })?;
+        let results = self.rule_set().evaluate(&event);
+        self.metrics
+            .matches
+            .fetch_add(results.len() as u64, Ordering::Relaxed);
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub const RULE: &str = r#"
+title: Whoami
+id: 1a2b
+level: high
+logsource:
+    category: process_creation
+fields:
+    - Image
+detection:
+    selection:
+        Image|endswith: '\whoami.exe'
+    condition: selection
+"#;
+
+    #[test]
+    fn test_replace() {
+        let engine = Engine::new(RuleSet::new(vec![]), None);
+        let event = r#"{"Image": "C:\\whoami.exe"}"#;
+        assert!(engine.evaluate(event).unwrap().is_empty());
+
+        assert_eq!(engine.replace(&[RULE]), Ok(1));
+        let results = engine.evaluate(event).unwrap();
+        assert_eq!(results[0].rule_title, "Whoami");
+
+        // A broken rule keeps the active rule set
+        let err = engine.replace(&[RULE, "title: Broken"]).unwrap_err();
+        assert!(err.starts_with("rule 2:"));
+        assert_eq!(engine.rule_set().len(), 1);
+
+        assert!(engine.evaluate("not json").is_err());
+
+        assert!(!engine.set_enabled("unknown", false));
+        let rule_set = engine.rule_set();
+        assert!(engine.set_enabled("1a2b", false));
+        assert!(engine.evaluate(event).unwrap().is_empty());
+        assert_eq!(rule_set.disabled_len(), 0);
+        assert_eq!(engine.rule_set().disabled_len(), 1);
+
+        let metrics = engine.metrics();
+        assert_eq!(metrics.events.load(Ordering::Relaxed), 4);
+        assert_eq!(metrics.invalid_events.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.matches.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.rule_set_updates.load(Ordering::Relaxed), 1);
+    }
+}
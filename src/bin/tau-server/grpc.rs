@@ -0,0 +1,144 @@
+use crate::engine::Engine;
+use std::pin::Pin;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status, Streaming};
+
+pub mod proto {
+    tonic::include_proto!("tau.v1");
+}
+
+use proto::tau_server::Tau;
+use proto::{EvaluateResponse, Event, Match, SetRulesRequest, SetRulesResponse};
+
+pub use proto::tau_server::TauServer;
+
+/// The gRPC service, see `proto/tau.proto`
+pub struct TauService {
+    engine: Engine,
+}
+
+impl TauService {
+    pub fn new(engine: Engine) -> Self {
+        Self { engine }
+    }
+
+    fn evaluate_event(engine: &Engine, event: &Event) -> EvaluateResponse {
+        match engine.evaluate(&event.json) {
+            Ok(results) => EvaluateResponse {
+                matches: results
+                    .iter()
+                    .map(|result| {
+                        let json = result.to_json();
+                        Match {
+                            id: result.rule_id.clone(),
+                            title: result.rule_title.clone(),
+                            level: json["level"].as_str().map(str::to_string),
+                            tags: result.tags.clone(),
+                            fields: json["fields"].to_string(),
+                        }
+                    })
+                    .collect(),
+                error: None,
+            },
+            Err(error) => EvaluateResponse {
+                matches: vec![],
+                error: Some(error),
+            },
+        }
+    }
+}
+
+// The error type `tonic::Status` is given by tonic
+#[allow(clippy::result_large_err)]
+#[tonic::async_trait]
+impl Tau for TauService {
+    async fn set_rules(
+        &self,
+        request: Request<SetRulesRequest>,
+    ) -> Result<Response<SetRulesResponse>, Status> {
+        let rules = self
+            .engine
+            .replace(&request.into_inner().rules)
+            .map_err(Status::invalid_argument)?;
+        Ok(Response::new(SetRulesResponse {
+            rules: rules as u32,
+        }))
+    }
+
+    type EvaluateStream = Pin<Box<dyn Stream<Item = Result<EvaluateResponse, Status>> + Send>>;
+
+    async fn evaluate(
+        &self,
+        request: Request<Streaming<Event>>,
+    ) -> Result<Response<Self::EvaluateStream>, Status> {
+        let engine = self.engine.clone();
+        let responses = request
+            .into_inner()
+            .map(move |event| Ok(Self::evaluate_event(&engine, &event?)));
+        Ok(Response::new(Box::pin(responses)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::tau_client::TauClient;
+    use sigma_rust::RuleSet;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+
+    #[tokio::test]
+    async fn test_service() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let service = TauService::new(Engine::new(RuleSet::new(vec![]), None));
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(TauServer::new(service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let mut client = TauClient::connect(format!("http://{}", address))
+            .await
+            .unwrap();
+        let response = client
+            .set_rules(SetRulesRequest {
+                rules: vec![crate::engine::tests::RULE.to_string()],
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().rules, 1);
+
+        let status = client
+            .set_rules(SetRulesRequest {
+                rules: vec!["title: Broken".to_string()],
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        let events = ["{\"Image\": \"C:\\\\whoami.exe\"}", "not json", "{}"].map(|json| Event {
+            json: json.to_string(),
+        });
+        let mut responses = client
+            .evaluate(tokio_stream::iter(events))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let response = responses.message().await.unwrap().unwrap();
+        assert_eq!(response.matches.len(), 1);
+        assert_eq!(response.matches[0].title, "Whoami");
+        assert_eq!(response.matches[0].level.as_deref(), Some("high"));
+        assert_eq!(response.matches[0].fields, r#"{"Image":"C:\\whoami.exe"}"#);
+        assert!(responses.message().await.unwrap().unwrap().error.is_some());
+        assert!(responses
+            .message()
+            .await
+            .unwrap()
+            .unwrap()
+            .matches
+            .is_empty());
+        assert!(responses.message().await.unwrap().is_none());
+    }
+}
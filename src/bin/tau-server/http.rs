@@ -0,0 +1,223 @@
+use crate::engine::Engine;
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, put};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sigma_rust::MatchResult;
+use std::fmt::Write;
+use std::sync::atomic::Ordering;
+
+/// The HTTP API:
+///
+/// * `POST /evaluate` takes an event as JSON object and returns its matches as JSON array
+/// * `PUT /rules` takes `{"rules": ["<YAML>", ...]}` and replaces the rule set
+/// * `PUT /rules/{id}/enabled` takes `{"enabled": false}` and mutes the rule with the id, or
+///   title for rules without an id, until it is enabled again or the rule set is replaced
+/// * `GET /health` returns the status of the server and the number of rules
+/// * `GET /metrics` returns counters in the Prometheus text format
+pub fn router(engine: Engine) -> Router {
+    Router::new()
+        .route("/evaluate", post(evaluate))
+        .route("/rules", put(set_rules))
+        .route("/rules/:id/enabled", put(set_enabled))
+        .route("/health", get(health))
+        .route("/metrics", get(metrics))
+        .with_state(engine)
+}
+
+#[derive(Deserialize)]
+struct SetRules {
+    rules: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SetEnabled {
+    enabled: bool,
+}
+
+fn error(status: StatusCode, message: String) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+async fn evaluate(State(engine): State<Engine>, event: String) -> Response {
+    match engine.evaluate(&event) {
+        Ok(results) => Json(Value::Array(
+            results.iter().map(MatchResult::to_json).collect(),
+        ))
+        .into_response(),
+        Err(message) => error(StatusCode::BAD_REQUEST, message),
+    }
+}
+
+async fn set_rules(State(engine): State<Engine>, Json(request): Json<SetRules>) -> Response {
+    match engine.replace(&request.rules) {
+        Ok(rules) => Json(json!({ "rules": rules })).into_response(),
+        Err(message) => error(StatusCode::UNPROCESSABLE_ENTITY, message),
+    }
+}
+
+async fn set_enabled(
+    State(engine): State<Engine>,
+    Path(id): Path<String>,
+    Json(request): Json<SetEnabled>,
+) -> Response {
+    if engine.set_enabled(&id, request.enabled) {
+        Json(json!({ "id": id, "enabled": request.enabled })).into_response()
+    } else {
+        error(StatusCode::NOT_FOUND, format!("no rule with id '{}'", id))
+    }
+}
+
+async fn health(State(engine): State<Engine>) -> Json<Value> {
+    let rule_set = engine.rule_set();
+    Json(json!({
+        "status": "ok",
+        "rules": rule_set.len(),
+        "disabled_rules": rule_set.disabled_len(),
+    }))
+}
+
+async fn metrics(State(engine): State<Engine>) -> impl IntoResponse {
+    let metrics = engine.metrics();
+    let mut body = String::new();
+    for (name, kind, help, value) in [
+        (
+            "tau_events_total",
+            "counter",
+            "Events received for evaluation",
+            metrics.events.load(Ordering::Relaxed),
+        ),
+        (
+            "tau_invalid_events_total",
+            "counter",
+            "Events that could not be parsed",
+            metrics.invalid_events.load(Ordering::Relaxed),
+        ),
+        (
+            "tau_matches_total",
+            "counter",
+            "Rule matches",
+            metrics.matches.load(Ordering::Relaxed),
+        ),
+        (
+            "tau_rule_set_updates_total",
+            "counter",
+            "Times the rule set was replaced",
+            metrics.rule_set_updates.load(Ordering::Relaxed),
+        ),
+        (
+            "tau_rules",
+            "gauge",
+            "Rules of the active rule set",
+            engine.rule_set().len() as u64,
+        ),
+        (
+            "tau_disabled_rules",
+            "gauge",
+            "Rules of the active rule set that are disabled",
+            engine.rule_set().disabled_len() as u64,
+        ),
+    ] {
+        let _ = write!(
+            body,
+            "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"
+        );
+    }
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sigma_rust::RuleSet;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Send a request and return the status code and the body of the response
+    async fn request(address: &str, method: &str, path: &str, body: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, body.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_api() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let engine = Engine::new(RuleSet::new(vec![]), None);
+        tokio::spawn(async move { axum::serve(listener, router(engine)).await });
+
+        let rules = json!({ "rules": [crate::engine::tests::RULE] }).to_string();
+        assert_eq!(
+            request(&address, "PUT", "/rules", &rules).await,
+            (200, r#"{"rules":1}"#.to_string())
+        );
+        let (status, body) = request(&address, "PUT", "/rules", r#"{"rules": ["title: x"]}"#).await;
+        assert_eq!(status, 422);
+        assert!(body.contains("rule 1:"));
+
+        let (status, body) = request(
+            &address,
+            "POST",
+            "/evaluate",
+            r#"{"Image": "C:\\whoami.exe"}"#,
+        )
+        .await;
+        assert_eq!(status, 200);
+        let matches: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(matches[0]["title"], "Whoami");
+        let (status, _) = request(&address, "POST", "/evaluate", "not json").await;
+        assert_eq!(status, 400);
+
+        assert_eq!(
+            request(&address, "GET", "/health", "").await,
+            (
+                200,
+                r#"{"disabled_rules":0,"rules":1,"status":"ok"}"#.to_string()
+            )
+        );
+        let (status, body) = request(&address, "GET", "/metrics", "").await;
+        assert_eq!(status, 200);
+        assert!(body.contains("\ntau_events_total 2\n"));
+        assert!(body.contains("\ntau_matches_total 1\n"));
+        assert!(body.contains("\ntau_rules 1\n"));
+
+        let (status, _) =
+            request(&address, "PUT", "/rules/x/enabled", r#"{"enabled":false}"#).await;
+        assert_eq!(status, 404);
+        assert_eq!(
+            request(
+                &address,
+                "PUT",
+                "/rules/1a2b/enabled",
+                r#"{"enabled":false}"#
+            )
+            .await,
+            (200, r#"{"enabled":false,"id":"1a2b"}"#.to_string())
+        );
+        let (_, body) = request(
+            &address,
+            "POST",
+            "/evaluate",
+            r#"{"Image": "C:\\whoami.exe"}"#,
+        )
+        .await;
+        assert_eq!(body, "[]");
+        let (_, body) = request(&address, "GET", "/metrics", "").await;
+        assert!(body.contains("\ntau_disabled_rules 1\n"));
+    }
+}
@@ -0,0 +1,115 @@
+//! `tau-server` evaluates events sent by other services against Sigma rules, see
+//! `tau-server --help`.
+#[path = "../tau/args.rs"]
+mod args;
+mod engine;
+mod grpc;
+mod http;
+#[path = "../tau/rules.rs"]
+mod rules;
+
+use args::Args;
+use engine::Engine;
+use grpc::{TauServer, TauService};
+use sigma_rust::RuleSet;
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+
+const USAGE: &str = "\
+Usage: tau-server [--listen <ADDRESS>] [--http <ADDRESS>] [--rules <PATH>]... [--pipeline <FILE>]...
+
+Serve the gRPC API defined in proto/tau.proto. Clients replace the rule set with SetRules
+and stream events to Evaluate to receive their matches.
+
+The HTTP API evaluates single events instead:
+    POST /evaluate      Evaluate the event in the body and return the matches as JSON array
+    PUT /rules          Replace the rule set with the YAML rules of {\"rules\": [...]}
+    PUT /rules/{id}/enabled
+                        Enable or disable the rule with {\"enabled\": true} or false
+    GET /health         Return the status and the number of rules
+    GET /metrics        Return counters in the Prometheus text format
+
+Options:
+    --listen <ADDRESS>  The address of the gRPC API (default 127.0.0.1:50051)
+    --http <ADDRESS>    Serve the HTTP API on this address as well
+    --rules <PATH>      A rule file or a directory that is searched for .yml and .yaml files,
+                        loaded at startup. May be given several times.
+    --pipeline <FILE>   A processing pipeline applied to all rules, including the rules set
+                        by clients. May be given several times.
+    --help              Print this message";
+
+fn main() -> ExitCode {
+    let result = Args::parse(
+        std::env::args().skip(1),
+        &["listen", "http", "rules", "pipeline"],
+        &["help"],
+    )
+    .and_then(|args| {
+        if args.flag("help") {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        if let Some(arg) = args.positional.first() {
+            return Err(format!("unexpected argument '{}'\n\n{}", arg, USAGE));
+        }
+        run(&args)
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    let listen: SocketAddr = args
+        .value("listen")
+        .unwrap_or("127.0.0.1:50051")
+        .parse()
+        .map_err(|e| format!("invalid --listen address: {}", e))?;
+    let http: Option<SocketAddr> = args
+        .value("http")
+        .map(|address| address.parse())
+        .transpose()
+        .map_err(|e| format!("invalid --http address: {}", e))?;
+    let pipeline = rules::pipeline(args.values("pipeline"))?;
+    let loaded = rules::load(args.values("rules"), pipeline.as_ref())?;
+    rules::write_diagnostics(&loaded, &mut std::io::stderr())?;
+    let engine = Engine::new(RuleSet::new(loaded.rules), pipeline);
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    runtime.block_on(async {
+        // Both servers stop on Ctrl-C
+        let (stop, stopped) = watch::channel(());
+        tokio::spawn(async move {
+            let _ = tokio::signal::ctrl_c().await;
+            let _ = stop.send(());
+        });
+        let shutdown = |mut stopped: watch::Receiver<()>| async move {
+            let _ = stopped.changed().await;
+        };
+
+        eprintln!("serving {} rules on {}", engine.rule_set().len(), listen);
+        let grpc = tonic::transport::Server::builder()
+            .add_service(TauServer::new(TauService::new(engine.clone())))
+            .serve_with_shutdown(listen, shutdown(stopped.clone()));
+        let http = async {
+            let Some(address) = http else {
+                return Ok(());
+            };
+            let listener = TcpListener::bind(address)
+                .await
+                .map_err(|e| format!("cannot listen on {}: {}", address, e))?;
+            eprintln!("serving the HTTP API on {}", address);
+            axum::serve(listener, http::router(engine))
+                .with_graceful_shutdown(shutdown(stopped))
+                .await
+                .map_err(|e| e.to_string())
+        };
+        tokio::try_join!(async { grpc.await.map_err(|e| e.to_string()) }, http).map(|_| ())
+    })
+}
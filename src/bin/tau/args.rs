@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+/// The parsed command line arguments of a subcommand
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub positional: Vec<String>,
+    options: HashMap<String, Vec<String>>,
+    flags: Vec<String>,
+}
+
+impl Args {
+    /// Parse the arguments after the subcommand. `options` are the names of the options that
+    /// take a value, `flags` the names of the options that do not.
+    pub fn parse<I: IntoIterator<Item = String>>(
+        args: I,
+        options: &[&str],
+        flags: &[&str],
+    ) -> Result<Self, String> {
+        let mut result = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                result.positional.push(arg);
+                continue;
+            };
+            let (name, value) = match name.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (name, None),
+            };
+            if flags.contains(&name) {
+                if value.is_some() {
+                    return Err(format!("--{} does not take a value", name));
+                }
+                result.flags.push(name.to_string());
+            } else if options.contains(&name) {
+                let value = match value {
+                    Some(value) => value,
+                    None => args
+                        .next()
+                        .ok_or_else(|| format!("--{} requires a value", name))?,
+                };
+                result
+                    .options
+                    .entry(name.to_string())
+                    .or_default()
+                    .push(value);
+            } else {
+                return Err(format!("unknown option --{}", name));
+            }
+        }
+        Ok(result)
+    }
+
+    /// All values of an option that may be given several times
+    pub fn values(&self, name: &str) -> &[String] {
+        self.options.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// The last value of an option
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values(name).last().map(String::as_str)
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|f| f == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(
+            args.iter().map(|s| s.to_string()),
+            &["rules", "format"],
+            &["quiet"],
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let args = parse(&[
+            "--rules",
+            "a",
+            "--rules=b",
+            "file",
+            "--quiet",
+            "--format=json",
+        ])
+        .unwrap();
+        assert_eq!(args.values("rules"), ["a", "b"]);
+        assert_eq!(args.value("format"), Some("json"));
+        assert_eq!(args.value("events"), None);
+        assert_eq!(args.positional, ["file"]);
+        assert!(args.flag("quiet"));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse(&["--rules"]).is_err());
+        assert!(parse(&["--quiet=yes"]).is_err());
+        assert!(parse(&["--unknown"]).is_err());
+    }
+}
@@ -0,0 +1,307 @@
+use crate::args::Args;
+use crate::rules;
+use serde_json::json;
+use sigma_rust::profile::Profile;
+use sigma_rust::{event_from_json, Event, RuleSet};
+use std::cmp::Reverse;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::time::{Duration, Instant};
+
+pub const USAGE: &str = "\
+Usage: tau bench --rules <PATH>... [--events <FILE>] [--iterations <N>] [--top <N>] [--format <FORMAT>]
+
+Measure how fast rules evaluate a corpus of newline-delimited JSON events. The events are
+parsed before the measurement, so only the evaluation is timed.
+
+Options:
+    --rules <PATH>      A rule file or a directory that is searched for .yml and .yaml files.
+                        May be given several times.
+    --events <FILE>     The events, one JSON object per line. Reads from stdin if omitted or -.
+    --iterations <N>    How often the corpus is evaluated (default 3)
+    --top <N>           How many of the slowest rules are reported (default 10)
+    --format <FORMAT>   text (default) or json, e.g. to compare rule set revisions
+    --quiet             Do not report rules and events that cannot be parsed";
+
+/// The evaluation time of a single rule
+#[derive(Debug, PartialEq)]
+struct RuleTiming {
+    title: String,
+    id: Option<String>,
+    evaluations: u64,
+    total: Duration,
+    /// The fraction of the evaluation time of all rules spent on this rule
+    share: f64,
+}
+
+#[derive(Debug)]
+struct Report {
+    rules: usize,
+    events: usize,
+    iterations: u32,
+    /// The number of matches of a single pass over the events
+    matches: usize,
+    elapsed: Duration,
+    /// The rules that took the longest to evaluate, slowest first
+    slowest: Vec<RuleTiming>,
+    /// The resident memory of the compiled rules in bytes, if it can be measured
+    rule_set_memory: Option<u64>,
+    /// The peak resident memory of the process in bytes, if it can be measured
+    peak_memory: Option<u64>,
+}
+
+impl Report {
+    fn events_per_second(&self) -> f64 {
+        let events = self.events as f64 * f64::from(self.iterations);
+        events / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    fn render_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "{} rules, {} events, {} iterations",
+            self.rules, self.events, self.iterations
+        )?;
+        writeln!(
+            out,
+            "throughput: {:.0} events/s ({:.2?} per event), {} matches per iteration",
+            self.events_per_second(),
+            mean(
+                self.elapsed,
+                self.events as u64 * u64::from(self.iterations)
+            ),
+            self.matches
+        )?;
+        writeln!(
+            out,
+            "memory: {} rule set, {} peak",
+            format_bytes(self.rule_set_memory),
+            format_bytes(self.peak_memory)
+        )?;
+        if self.slowest.is_empty() {
+            return Ok(());
+        }
+        writeln!(out, "slowest rules:")?;
+        for timing in &self.slowest {
+            let id = timing
+                .id
+                .as_ref()
+                .map_or(String::new(), |id| format!(" ({})", id));
+            writeln!(
+                out,
+                "  {:>5.1}% {:>10.2?} total {:>10.2?} mean  {}{}",
+                timing.share * 100.0,
+                timing.total,
+                mean(timing.total, timing.evaluations),
+                timing.title,
+                id
+            )?;
+        }
+        Ok(())
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "rules": self.rules,
+            "events": self.events,
+            "iterations": self.iterations,
+            "matches": self.matches,
+            "elapsed_nanos": self.elapsed.as_nanos() as u64,
+            "events_per_second": self.events_per_second(),
+            "rule_set_memory": self.rule_set_memory,
+            "peak_memory": self.peak_memory,
+            "slowest": self.slowest.iter().map(|timing| json!({
+                "title": timing.title,
+                "id": timing.id,
+                "evaluations": timing.evaluations,
+                "total_nanos": timing.total.as_nanos() as u64,
+                "share": timing.share,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+pub fn run(args: &Args, out: &mut dyn Write, err: &mut dyn Write) -> Result<(), String> {
+    if args.values("rules").is_empty() {
+        return Err("--rules is required".to_string());
+    }
+    let iterations = number(args, "iterations", 3)?;
+    let top = number(args, "top", 10)? as usize;
+    let json = match args.value("format").unwrap_or("text") {
+        "text" => false,
+        "json" => true,
+        format => return Err(format!("unknown format '{}'", format)),
+    };
+    let quiet = args.flag("quiet");
+
+    let memory_before = memory("VmRSS");
+    let mut loaded = rules::load(args.values("rules"), None)?;
+    let rule_set = RuleSet::new(std::mem::take(&mut loaded.rules));
+    let rule_set_memory = memory("VmRSS")
+        .zip(memory_before)
+        .map(|(after, before)| after.saturating_sub(before));
+
+    let input: Box<dyn BufRead> = match args.value("events") {
+        None | Some("-") => Box::new(BufReader::new(io::stdin())),
+        Some(path) => Box::new(BufReader::new(
+            File::open(path).map_err(|e| format!("cannot open {}: {}", path, e))?,
+        )),
+    };
+    let mut events = vec![];
+    for (index, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| format!("cannot read events: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match event_from_json(&line) {
+            Ok(event) => events.push(event),
+            Err(e) if !quiet => {
+                writeln!(err, "skipping line {}: {}", index + 1, e).map_err(|e| e.to_string())?
+            }
+            Err(_) => {}
+        }
+    }
+    if !quiet {
+        rules::write_diagnostics(&loaded, err)?;
+    }
+
+    let mut report = measure(&rule_set, &events, iterations, top);
+    report.rule_set_memory = rule_set_memory;
+    report.peak_memory = memory("VmHWM");
+
+    if json {
+        writeln!(out, "{}", report.to_json()).map_err(|e| e.to_string())
+    } else {
+        report.render_text(out).map_err(|e| e.to_string())
+    }
+}
+
+/// Evaluate the events `iterations` times and profile the rules in an additional pass, which
+/// also warms up the caches
+fn measure(rule_set: &RuleSet, events: &[Event], iterations: u32, top: usize) -> Report {
+    let mut profile = Profile::new();
+    let mut matches = 0;
+    for event in events {
+        matches += rule_set.evaluate_profiled(event, &mut profile).len();
+    }
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        for event in events {
+            std::hint::black_box(rule_set.evaluate(event));
+        }
+    }
+    let elapsed = start.elapsed();
+
+    let mut slowest: Vec<RuleTiming> = rule_set
+        .rules()
+        .iter()
+        .filter_map(|rule| {
+            let stats = profile.rule(rule)?;
+            Some(RuleTiming {
+                title: rule.title.clone(),
+                id: rule.id.clone(),
+                evaluations: stats.evaluations,
+                total: Duration::from_nanos(stats.nanos),
+                share: 0.0,
+            })
+        })
+        .collect();
+    let total: Duration = slowest.iter().map(|timing| timing.total).sum();
+    for timing in &mut slowest {
+        timing.share = timing.total.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON);
+    }
+    slowest.sort_by_key(|timing| Reverse(timing.total));
+    slowest.truncate(top);
+
+    Report {
+        rules: rule_set.len(),
+        events: events.len(),
+        iterations,
+        matches,
+        elapsed,
+        slowest,
+        rule_set_memory: None,
+        peak_memory: None,
+    }
+}
+
+fn number(args: &Args, name: &str, default: u32) -> Result<u32, String> {
+    args.value(name).map_or(Ok(default), |value| {
+        value
+            .parse()
+            .map_err(|_| format!("--{} must be a non-negative number", name))
+    })
+}
+
+/// Read a memory statistic of the process in bytes from `/proc/self/status`. Returns `None`
+/// on platforms without procfs.
+fn memory(field: &str) -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    status.lines().find_map(|line| {
+        let value = line.strip_prefix(field)?.strip_prefix(':')?;
+        let kib: u64 = value.trim().strip_suffix("kB")?.trim().parse().ok()?;
+        Some(kib * 1024)
+    })
+}
+
+fn mean(total: Duration, count: u64) -> Duration {
+    Duration::from_nanos((total.as_nanos() / u128::from(count.max(1))) as u64)
+}
+
+fn format_bytes(bytes: Option<u64>) -> String {
+    match bytes {
+        None => "unknown".to_string(),
+        Some(bytes) => format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sigma_rust::rule_from_yaml;
+
+    fn rule(title: &str, image: &str) -> sigma_rust::Rule {
+        rule_from_yaml(&format!(
+            r#"
+title: {}
+logsource:
+    category: process_creation
+detection:
+    selection:
+        Image|endswith: '{}'
+    condition: selection
+"#,
+            title, image
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_measure() {
+        let rule_set = RuleSet::new(vec![
+            rule("Whoami", "\\whoami.exe"),
+            rule("Net", "\\net.exe"),
+        ]);
+        let events: Vec<Event> = ["C:\\whoami.exe", "C:\\net.exe", "C:\\whoami.exe"]
+            .iter()
+            .map(|image| event_from_json(&json!({ "Image": image }).to_string()).unwrap())
+            .collect();
+
+        let report = measure(&rule_set, &events, 2, 1);
+        assert_eq!((report.rules, report.events, report.iterations), (2, 3, 2));
+        assert_eq!(report.matches, 3);
+        assert_eq!(report.slowest.len(), 1);
+        assert!(report.events_per_second() > 0.0);
+
+        let result = report.to_json();
+        assert_eq!(result["matches"], 3);
+        assert_eq!(result["slowest"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(Some(3 * 1024 * 1024 / 2)), "1.5 MiB");
+        assert_eq!(format_bytes(None), "unknown");
+    }
+}
@@ -0,0 +1,100 @@
+use crate::args::Args;
+use sigma_rust::capability::{Capabilities, Coverage};
+use sigma_rust::loader::rule_files;
+use sigma_rust::validation::{Severity, Validator};
+use std::fs;
+use std::io::Write;
+
+pub const USAGE: &str = "\
+Usage: tau check [--deny-warnings] [--errors-only] [--coverage] <PATH>...
+
+Parse and lint rules and print their errors and warnings, one per line.
+
+Arguments:
+    <PATH>              A rule file or a directory that is searched for .yml and .yaml files
+
+Options:
+    --deny-warnings     Fail if any rule has warnings
+    --errors-only       Do not print warnings
+    --coverage          Print which Sigma features the rules use and how many of them
+                        are fully supported";
+
+/// Check the rules and return whether they passed
+pub fn run(args: &Args, out: &mut dyn Write) -> Result<bool, String> {
+    if args.positional.is_empty() {
+        return Err("no rule paths given".to_string());
+    }
+    let deny_warnings = args.flag("deny-warnings");
+    let errors_only = args.flag("errors-only");
+    let mut coverage = args.flag("coverage").then(Coverage::new);
+
+    let mut files = vec![];
+    let (mut errors, mut warnings) = (0, 0);
+    for path in &args.positional {
+        let (found, unreadable) = rule_files(path);
+        files.extend(found);
+        for diagnostic in unreadable {
+            errors += 1;
+            writeln!(out, "{}", diagnostic).map_err(|e| e.to_string())?;
+        }
+    }
+    let mut validator = Validator::new();
+    for path in &files {
+        let yaml = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read {}: {}", path.display(), err))?;
+        let issues = validator.validate_yaml(&yaml);
+        if let Some(coverage) = &mut coverage {
+            coverage.add(&Capabilities::from_yaml(&yaml));
+        }
+        for issue in issues {
+            match issue.severity {
+                Severity::Error => errors += 1,
+                Severity::Warning => warnings += 1,
+            }
+            if issue.severity == Severity::Error || !errors_only {
+                writeln!(out, "{}: {}", path.display(), issue).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    writeln!(
+        out,
+        "checked {} rules: {} errors, {} warnings",
+        files.len(),
+        errors,
+        warnings
+    )
+    .map_err(|e| e.to_string())?;
+    if let Some(coverage) = coverage {
+        write_coverage(&coverage, out).map_err(|e| e.to_string())?;
+    }
+
+    Ok(errors == 0 && (warnings == 0 || !deny_warnings))
+}
+
+fn write_coverage(coverage: &Coverage, out: &mut dyn Write) -> std::io::Result<()> {
+    writeln!(
+        out,
+        "coverage: {} fully supported, {} degraded, {} unsupported",
+        coverage.supported_rules, coverage.degraded_rules, coverage.unsupported_rules
+    )?;
+    for (name, count) in coverage
+        .modifiers
+        .iter()
+        .map(|(name, count)| (format!("modifier {}", name), count))
+        .chain(
+            coverage
+                .conditions
+                .iter()
+                .map(|(name, count)| (format!("condition {}", name), count)),
+        )
+        .chain(
+            coverage
+                .limitations
+                .iter()
+                .map(|(name, count)| (format!("unsupported {}", name), count)),
+        )
+    {
+        writeln!(out, "  {}: {} rules", name, count)?;
+    }
+    Ok(())
+}
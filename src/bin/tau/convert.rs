@@ -0,0 +1,138 @@
+use crate::args::Args;
+use crate::rules;
+use sigma_rust::backend::{
+    to_eql, to_kql, to_logql, to_sql, AnsiSql, BackendError, ClickHouse, LogQlOptions, MySql,
+    PostgreSql,
+};
+use sigma_rust::Rule;
+use std::io::Write;
+
+pub const USAGE: &str = "\
+Usage: tau convert --target <TARGET> [--pipeline <FILE>]... <PATH>...
+
+Translate rules into queries of another system and print one query per line, in the
+order the rule files are loaded.
+
+Arguments:
+    <PATH>              A rule file or a directory that is searched for .yml and .yaml files
+
+Options:
+    --target <TARGET>   sql, postgresql, mysql, clickhouse, kql, eql or logql
+    --pipeline <FILE>   A processing pipeline applied to the rules before they are translated.
+                        May be given several times; pipelines are applied by priority.
+    --quiet             Do not report rules that cannot be loaded or translated";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    Sql,
+    PostgreSql,
+    MySql,
+    ClickHouse,
+    Kql,
+    Eql,
+    LogQl,
+}
+
+impl Target {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "sql" => Ok(Self::Sql),
+            "postgresql" => Ok(Self::PostgreSql),
+            "mysql" => Ok(Self::MySql),
+            "clickhouse" => Ok(Self::ClickHouse),
+            "kql" => Ok(Self::Kql),
+            "eql" => Ok(Self::Eql),
+            "logql" => Ok(Self::LogQl),
+            _ => Err(format!("unknown target '{}'", name)),
+        }
+    }
+
+    /// Translate the rule. LogQL translations are best effort, so the constructs that were
+    /// left out are returned as warnings.
+    fn convert(&self, rule: &Rule) -> Result<(String, Vec<String>), BackendError> {
+        let query = match self {
+            Self::Sql => to_sql(rule, &AnsiSql)?,
+            Self::PostgreSql => to_sql(rule, &PostgreSql)?,
+            Self::MySql => to_sql(rule, &MySql)?,
+            Self::ClickHouse => to_sql(rule, &ClickHouse)?,
+            Self::Kql => to_kql(rule)?,
+            Self::Eql => to_eql(rule)?,
+            Self::LogQl => {
+                let query = to_logql(rule, &LogQlOptions::default());
+                return Ok((query.query, query.unsupported));
+            }
+        };
+        Ok((query, vec![]))
+    }
+}
+
+/// Convert the rules and return whether all of them could be converted
+pub fn run(args: &Args, out: &mut dyn Write, err: &mut dyn Write) -> Result<bool, String> {
+    let target = Target::parse(args.value("target").ok_or("--target is required")?)?;
+    if args.positional.is_empty() {
+        return Err("no rule paths given".to_string());
+    }
+    let quiet = args.flag("quiet");
+    let pipeline = rules::pipeline(args.values("pipeline"))?;
+
+    let loaded = rules::load(&args.positional, pipeline.as_ref())?;
+    let mut success = !loaded.has_errors();
+    if !quiet {
+        rules::write_diagnostics(&loaded, err)?;
+    }
+
+    for rule in &loaded.rules {
+        match target.convert(rule) {
+            Ok((query, warnings)) => {
+                writeln!(out, "{}", query).map_err(|e| e.to_string())?;
+                if !quiet {
+                    for warning in warnings {
+                        writeln!(err, "{}: {} is not translated", rule.title, warning)
+                            .map_err(|e| e.to_string())?;
+                    }
+                }
+            }
+            Err(e) => {
+                success = false;
+                if !quiet {
+                    writeln!(err, "skipping {}: {}", rule.title, e).map_err(|e| e.to_string())?;
+                }
+            }
+        }
+    }
+    out.flush().map_err(|e| e.to_string())?;
+    Ok(success)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sigma_rust::rule_from_yaml;
+
+    #[test]
+    fn test_convert() {
+        let rule = rule_from_yaml(
+            r#"
+title: Whoami
+logsource:
+    category: process_creation
+detection:
+    selection:
+        Image|endswith: '\whoami.exe'
+    condition: selection
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Target::parse("kql").unwrap().convert(&rule).unwrap(),
+            (r#"Image endswith @"\whoami.exe""#.to_string(), vec![])
+        );
+        assert!(Target::parse("clickhouse")
+            .unwrap()
+            .convert(&rule)
+            .unwrap()
+            .0
+            .starts_with("`Image` ILIKE"));
+        assert!(Target::parse("splunk").is_err());
+    }
+}
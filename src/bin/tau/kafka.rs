@@ -0,0 +1,237 @@
+use crate::args::Args;
+use crate::rules;
+use kafka::consumer::{Consumer, FetchOffset, GroupOffsetStorage, MessageSets};
+use kafka::producer::{Producer, Record, RequiredAcks};
+use serde_json::{json, Value};
+use sigma_rust::{event_from_json, RuleSet};
+use std::io::Write;
+use std::str;
+
+pub const USAGE: &str = "\
+Usage: tau kafka --brokers <HOSTS> --input <TOPIC>... --output <TOPIC> --rules <PATH>... [OPTIONS]
+
+Consume JSON events from Kafka topics, evaluate them and produce the matches to a topic.
+The offsets of the consumer group are only committed after the brokers acknowledged the
+matches of the consumed events, so every event is evaluated at least once. Events that
+are not valid JSON are reported and committed, so they do not block the partition.
+
+Options:
+    --brokers <HOSTS>   The bootstrap brokers, comma separated, e.g. localhost:9092
+    --input <TOPIC>     A topic to consume events from. May be given several times.
+    --output <TOPIC>    The topic the matches are produced to, one JSON document per match
+    --group <GROUP>     The consumer group (default tau)
+    --rules <PATH>      A rule file or a directory that is searched for .yml and .yaml files.
+                        May be given several times.
+    --pipeline <FILE>   A processing pipeline applied to the rules. May be given several times.
+    --earliest          Start at the earliest offset if the group has no committed offset,
+                        instead of the latest
+    --quiet             Do not report rules and events that cannot be parsed";
+
+/// The location of a consumed message
+#[derive(Debug, Clone, Copy)]
+struct Source<'a> {
+    topic: &'a str,
+    partition: i32,
+    offset: i64,
+}
+
+/// An output record: the key, i.e. the id or title of the rule, and the match as JSON
+type Output = (String, String);
+
+pub fn run(args: &Args, err: &mut dyn Write) -> Result<(), String> {
+    let brokers: Vec<String> = args
+        .value("brokers")
+        .ok_or("--brokers is required")?
+        .split(',')
+        .map(|host| host.trim().to_string())
+        .collect();
+    let inputs = args.values("input");
+    if inputs.is_empty() {
+        return Err("--input is required".to_string());
+    }
+    let output = args.value("output").ok_or("--output is required")?;
+    if args.values("rules").is_empty() {
+        return Err("--rules is required".to_string());
+    }
+    let quiet = args.flag("quiet");
+
+    let pipeline = rules::pipeline(args.values("pipeline"))?;
+    let loaded = rules::load(args.values("rules"), pipeline.as_ref())?;
+    if !quiet {
+        rules::write_diagnostics(&loaded, err)?;
+    }
+    let rule_set = RuleSet::new(loaded.rules);
+
+    let mut consumer = inputs
+        .iter()
+        .fold(Consumer::from_hosts(brokers.clone()), |builder, topic| {
+            builder.with_topic(topic.clone())
+        })
+        .with_group(args.value("group").unwrap_or("tau").to_string())
+        .with_fallback_offset(if args.flag("earliest") {
+            FetchOffset::Earliest
+        } else {
+            FetchOffset::Latest
+        })
+        .with_offset_storage(Some(GroupOffsetStorage::Kafka))
+        .create()
+        .map_err(|e| format!("cannot create the consumer: {}", e))?;
+    let mut producer = Producer::from_hosts(brokers)
+        .with_required_acks(RequiredAcks::All)
+        .create()
+        .map_err(|e| format!("cannot create the producer: {}", e))?;
+
+    loop {
+        let sets = consumer
+            .poll()
+            .map_err(|e| format!("cannot consume events: {}", e))?;
+        if sets.is_empty() {
+            continue;
+        }
+        let outputs = evaluate_sets(&rule_set, &sets, quiet, err)?;
+        produce(&mut producer, output, &outputs)?;
+        for set in sets.iter() {
+            consumer
+                .consume_messageset(set)
+                .map_err(|e| e.to_string())?;
+        }
+        consumer
+            .commit_consumed()
+            .map_err(|e| format!("cannot commit the offsets: {}", e))?;
+    }
+}
+
+fn evaluate_sets(
+    rule_set: &RuleSet,
+    sets: &MessageSets,
+    quiet: bool,
+    err: &mut dyn Write,
+) -> Result<Vec<Output>, String> {
+    let mut outputs = vec![];
+    for set in sets.iter() {
+        for message in set.messages() {
+            let source = Source {
+                topic: set.topic(),
+                partition: set.partition(),
+                offset: message.offset,
+            };
+            evaluate(rule_set, source, message.value, &mut outputs, quiet, err)?;
+        }
+    }
+    Ok(outputs)
+}
+
+/// Evaluate the payload of a message and add the matches to `outputs`
+fn evaluate(
+    rule_set: &RuleSet,
+    source: Source,
+    payload: &[u8],
+    outputs: &mut Vec<Output>,
+    quiet: bool,
+    err: &mut dyn Write,
+) -> Result<(), String> {
+    let event = str::from_utf8(payload)
+        .map_err(|e| e.to_string())
+        .and_then(|json| event_from_json(json).map_err(|e| e.to_string()));
+    let event = match event {
+        Ok(event) => event,
+        Err(e) => {
+            if !quiet {
+                writeln!(
+                    err,
+                    "skipping {}/{}@{}: {}",
+                    source.topic, source.partition, source.offset, e
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            return Ok(());
+        }
+    };
+    for result in rule_set.evaluate(&event) {
+        let mut json = result.to_json();
+        json["source"] = json!({
+            "topic": source.topic,
+            "partition": source.partition,
+            "offset": source.offset,
+        });
+        let key = result.rule_id.unwrap_or(result.rule_title);
+        outputs.push((key, Value::to_string(&json)));
+    }
+    Ok(())
+}
+
+/// Produce the matches and wait until all brokers acknowledged them
+fn produce(producer: &mut Producer, topic: &str, outputs: &[Output]) -> Result<(), String> {
+    if outputs.is_empty() {
+        return Ok(());
+    }
+    let records: Vec<_> = outputs
+        .iter()
+        .map(|(key, value)| Record::from_key_value(topic, key.as_str(), value.as_str()))
+        .collect();
+    let confirms = producer
+        .send_all(&records)
+        .map_err(|e| format!("cannot produce matches: {}", e))?;
+    for confirm in confirms {
+        for partition in confirm.partition_confirms {
+            if let Err(code) = partition.offset {
+                return Err(format!(
+                    "cannot produce matches to {}/{}: {:?}",
+                    confirm.topic, partition.partition, code
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sigma_rust::rule_from_yaml;
+
+    #[test]
+    fn test_evaluate() {
+        let rule_set = RuleSet::new(vec![rule_from_yaml(
+            r#"
+title: Whoami
+id: 1a2b
+logsource:
+fields:
+    - Image
+detection:
+    selection:
+        Image|endswith: '\whoami.exe'
+    condition: selection
+"#,
+        )
+        .unwrap()]);
+        let source = Source {
+            topic: "events",
+            partition: 3,
+            offset: 42,
+        };
+        let (mut outputs, mut err) = (vec![], vec![]);
+        for payload in [
+            &br#"{"Image": "C:\\whoami.exe"}"#[..],
+            br#"{"Image": "C:\\explorer.exe"}"#,
+            b"not json",
+            b"\xff",
+        ] {
+            evaluate(&rule_set, source, payload, &mut outputs, false, &mut err).unwrap();
+        }
+
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].0, "1a2b");
+        let value: Value = serde_json::from_str(&outputs[0].1).unwrap();
+        assert_eq!(value["title"], "Whoami");
+        assert_eq!(value["fields"]["Image"], "C:\\whoami.exe");
+        assert_eq!(
+            value["source"],
+            json!({"topic": "events", "partition": 3, "offset": 42})
+        );
+        let err = String::from_utf8(err).unwrap();
+        assert_eq!(err.lines().count(), 2);
+        assert!(err.starts_with("skipping events/3@42:"));
+    }
+}
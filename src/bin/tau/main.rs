@@ -0,0 +1,148 @@
+//! `tau` evaluates Sigma rules from the command line, see `tau help`.
+mod args;
+mod benchmark;
+mod check;
+mod convert;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod matching;
+mod report;
+mod rules;
+mod schema;
+
+use args::Args;
+use std::io::{self, Write};
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: tau <COMMAND> [OPTIONS]
+
+Commands:
+    match       Evaluate rules against newline-delimited JSON events
+    check       Parse and lint rules
+    convert     Translate rules into SQL, KQL, EQL or LogQL queries
+    bench       Measure the throughput of rules on a corpus of events
+    kafka       Evaluate events consumed from Kafka (requires the kafka feature)
+    report      Render a Markdown or HTML catalog of rules
+    schema      Print the JSON Schema of rules
+    help        Print this message or the help of a command
+
+Exit codes:
+    0           Success
+    1           A checked rule has errors, or warnings with --deny-warnings, or a rule
+                cannot be converted
+    2           Invalid arguments or unreadable input";
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let command = args.next();
+    let stdout = io::stdout();
+    let stderr = io::stderr();
+    let (mut out, mut err) = (stdout.lock(), stderr.lock());
+
+    let result = match command.as_deref() {
+        Some("match") => Args::parse(
+            args,
+            &["rules", "events", "format", "webhook"],
+            &["quiet", "help"],
+        )
+        .and_then(|args| {
+            if args.flag("help") {
+                return help(&mut out, matching::USAGE);
+            }
+            matching::run(&args, &mut out, &mut err).map(|_| ExitCode::SUCCESS)
+        }),
+        Some("check") => Args::parse(
+            args,
+            &[],
+            &["deny-warnings", "errors-only", "coverage", "help"],
+        )
+        .and_then(|args| {
+            if args.flag("help") {
+                return help(&mut out, check::USAGE);
+            }
+            match check::run(&args, &mut out)? {
+                true => Ok(ExitCode::SUCCESS),
+                false => Ok(ExitCode::FAILURE),
+            }
+        }),
+        Some("convert") => {
+            Args::parse(args, &["target", "pipeline"], &["quiet", "help"]).and_then(|args| {
+                if args.flag("help") {
+                    return help(&mut out, convert::USAGE);
+                }
+                match convert::run(&args, &mut out, &mut err)? {
+                    true => Ok(ExitCode::SUCCESS),
+                    false => Ok(ExitCode::FAILURE),
+                }
+            })
+        }
+        Some("bench") => Args::parse(
+            args,
+            &["rules", "events", "iterations", "top", "format"],
+            &["quiet", "help"],
+        )
+        .and_then(|args| {
+            if args.flag("help") {
+                return help(&mut out, benchmark::USAGE);
+            }
+            benchmark::run(&args, &mut out, &mut err).map(|_| ExitCode::SUCCESS)
+        }),
+        Some("report") => Args::parse(args, &["format", "title", "pipeline"], &["quiet", "help"])
+            .and_then(|args| {
+                if args.flag("help") {
+                    return help(&mut out, report::USAGE);
+                }
+                report::run(&args, &mut out, &mut err).map(|_| ExitCode::SUCCESS)
+            }),
+        Some("schema") => Args::parse(args, &[], &["help"]).and_then(|args| {
+            if args.flag("help") {
+                return help(&mut out, schema::USAGE);
+            }
+            schema::run(&mut out).map(|_| ExitCode::SUCCESS)
+        }),
+        #[cfg(feature = "kafka")]
+        Some("kafka") => Args::parse(
+            args,
+            &["brokers", "input", "output", "group", "rules", "pipeline"],
+            &["earliest", "quiet", "help"],
+        )
+        .and_then(|args| {
+            if args.flag("help") {
+                return help(&mut out, kafka::USAGE);
+            }
+            kafka::run(&args, &mut err).map(|_| ExitCode::SUCCESS)
+        }),
+        #[cfg(not(feature = "kafka"))]
+        Some("kafka") => Err("tau was built without the kafka feature".to_string()),
+        Some("help") | Some("--help") | Some("-h") => {
+            let usage = match args.next().as_deref() {
+                Some("match") => matching::USAGE,
+                Some("check") => check::USAGE,
+                Some("convert") => convert::USAGE,
+                Some("bench") => benchmark::USAGE,
+                Some("report") => report::USAGE,
+                Some("schema") => schema::USAGE,
+                #[cfg(feature = "kafka")]
+                Some("kafka") => kafka::USAGE,
+                _ => USAGE,
+            };
+            help(&mut out, usage)
+        }
+        Some(command) => Err(format!("unknown command '{}'\n\n{}", command, USAGE)),
+        None => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(code) => code,
+        Err(message) => {
+            let _ = writeln!(err, "error: {}", message);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn help(out: &mut dyn Write, usage: &str) -> Result<ExitCode, String> {
+    writeln!(out, "{}", usage).map_err(|e| e.to_string())?;
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,263 @@
+use crate::args::Args;
+use crate::rules;
+use serde_json::{json, Value};
+use sigma_rust::output::OutputFormat;
+use sigma_rust::sink::AlertSink;
+use sigma_rust::{event_from_json, MatchResult, RuleSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+
+pub const USAGE: &str = "\
+Usage: tau match --rules <PATH>... [--events <FILE>] [--format <FORMAT>]
+
+Evaluate rules against newline-delimited JSON events and print the matches.
+
+Options:
+    --rules <PATH>      A rule file or a directory that is searched for .yml and .yaml files.
+                        May be given several times.
+    --events <FILE>     The events, one JSON object per line. Reads from stdin if omitted or -.
+    --format <FORMAT>   text (default), json, ecs or ocsf. All but text print one JSON
+                        document per match and line.
+    --webhook <URL>     Also post every match as a JSON document to the URL, in the ecs or
+                        ocsf format if selected (requires the webhook feature)
+    --quiet             Do not report rules and events that cannot be parsed";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Json,
+    Alert(OutputFormat),
+}
+
+impl Format {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "ecs" => Ok(Self::Alert(OutputFormat::Ecs)),
+            "ocsf" => Ok(Self::Alert(OutputFormat::Ocsf)),
+            _ => Err(format!("unknown format '{}'", name)),
+        }
+    }
+
+    fn render(&self, line: usize, result: &MatchResult) -> String {
+        match self {
+            Self::Text => {
+                let level = json!(result.level);
+                let id = result
+                    .rule_id
+                    .as_ref()
+                    .map_or(String::new(), |id| format!(" ({})", id));
+                format!(
+                    "{}: [{}] {}{}",
+                    line,
+                    level.as_str().unwrap_or("unknown"),
+                    result.rule_title,
+                    id
+                )
+            }
+            Self::Json => json!({
+                "line": line,
+                "id": result.rule_id,
+                "title": result.rule_title,
+                "level": result.level,
+                "tags": result.tags,
+                "fields": result
+                    .fields
+                    .iter()
+                    .map(|(k, v)| (k.clone(), Value::from(v)))
+                    .collect::<serde_json::Map<_, _>>(),
+            })
+            .to_string(),
+            Self::Alert(format) => format.render(result).to_string(),
+        }
+    }
+}
+
+pub fn run(args: &Args, out: &mut dyn Write, err: &mut dyn Write) -> Result<(), String> {
+    let format = Format::parse(args.value("format").unwrap_or("text"))?;
+    if args.values("rules").is_empty() {
+        return Err("--rules is required".to_string());
+    }
+    let quiet = args.flag("quiet");
+
+    let loaded = rules::load(args.values("rules"), None)?;
+    if !quiet {
+        rules::write_diagnostics(&loaded, err)?;
+    }
+    let rule_set = RuleSet::new(loaded.rules);
+
+    let events: Box<dyn BufRead> = match args.value("events") {
+        None | Some("-") => Box::new(BufReader::new(io::stdin())),
+        Some(path) => Box::new(BufReader::new(
+            File::open(path).map_err(|e| format!("cannot open {}: {}", path, e))?,
+        )),
+    };
+    let mut webhook = webhook(args, format)?;
+    evaluate(
+        &rule_set,
+        events,
+        format,
+        quiet,
+        out,
+        err,
+        webhook
+            .as_mut()
+            .map(|sink| sink.as_mut() as &mut dyn AlertSink),
+    )
+}
+
+#[cfg(feature = "webhook")]
+fn webhook(args: &Args, format: Format) -> Result<Option<Box<dyn AlertSink>>, String> {
+    Ok(args.value("webhook").map(|url| {
+        let sink = sigma_rust::sink::WebhookSink::new(url);
+        let sink = match format {
+            Format::Alert(format) => sink.with_format(format),
+            _ => sink,
+        };
+        Box::new(sink) as Box<dyn AlertSink>
+    }))
+}
+
+#[cfg(not(feature = "webhook"))]
+fn webhook(args: &Args, _: Format) -> Result<Option<Box<dyn AlertSink>>, String> {
+    match args.value("webhook") {
+        Some(_) => Err("tau was built without the webhook feature".to_string()),
+        None => Ok(None),
+    }
+}
+
+fn evaluate(
+    rule_set: &RuleSet,
+    events: impl BufRead,
+    format: Format,
+    quiet: bool,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+    mut sink: Option<&mut dyn AlertSink>,
+) -> Result<(), String> {
+    for (index, line) in events.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.map_err(|e| format!("cannot read events: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = match event_from_json(&line) {
+            Ok(event) => event,
+            Err(e) => {
+                if !quiet {
+                    writeln!(err, "skipping line {}: {}", line_number, e)
+                        .map_err(|e| e.to_string())?;
+                }
+                continue;
+            }
+        };
+        for result in rule_set.evaluate(&event) {
+            writeln!(out, "{}", format.render(line_number, &result)).map_err(|e| e.to_string())?;
+            // An unreachable sink must not stop the evaluation
+            if let Some(Err(e)) = sink.as_deref_mut().map(|sink| sink.send(&result)) {
+                writeln!(err, "cannot send match of line {}: {}", line_number, e)
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    if let Some(Err(e)) = sink.map(|sink| sink.flush()) {
+        writeln!(err, "cannot send matches: {}", e).map_err(|e| e.to_string())?;
+    }
+    out.flush().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sigma_rust::rule_from_yaml;
+
+    fn rule_set() -> RuleSet {
+        RuleSet::new(vec![rule_from_yaml(
+            r#"
+title: Whoami
+id: 1a2b
+level: high
+logsource:
+fields:
+    - Image
+detection:
+    selection:
+        Image|endswith: '\whoami.exe'
+    condition: selection
+"#,
+        )
+        .unwrap()])
+    }
+
+    const EVENTS: &str = "{\"Image\": \"C:\\\\whoami.exe\"}\n\nnot json\n{\"Image\": \"cmd.exe\"}\n{\"Image\": \"whoami.exe\"}\n";
+
+    fn run_format(format: &str) -> (String, String) {
+        let (mut out, mut err) = (vec![], vec![]);
+        evaluate(
+            &rule_set(),
+            EVENTS.as_bytes(),
+            Format::parse(format).unwrap(),
+            false,
+            &mut out,
+            &mut err,
+            None,
+        )
+        .unwrap();
+        (
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(err).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_text() {
+        let (out, err) = run_format("text");
+        assert_eq!(out, "1: [high] Whoami (1a2b)\n");
+        assert!(err.starts_with("skipping line 3:"));
+    }
+
+    #[test]
+    fn test_json() {
+        let (out, _) = run_format("json");
+        let result: Value = serde_json::from_str(out.trim()).unwrap();
+        assert_eq!(
+            result,
+            json!({
+                "line": 1,
+                "id": "1a2b",
+                "title": "Whoami",
+                "level": "high",
+                "tags": [],
+                "fields": {"Image": "C:\\whoami.exe"},
+            })
+        );
+
+        let (out, _) = run_format("ecs");
+        let result: Value = serde_json::from_str(out.trim()).unwrap();
+        assert_eq!(result["rule"]["name"], "Whoami");
+    }
+
+    #[test]
+    fn test_sink() {
+        let mut sink: Vec<MatchResult> = vec![];
+        let (mut out, mut err) = (vec![], vec![]);
+        evaluate(
+            &rule_set(),
+            EVENTS.as_bytes(),
+            Format::Text,
+            true,
+            &mut out,
+            &mut err,
+            Some(&mut sink),
+        )
+        .unwrap();
+        assert_eq!(sink.len(), 1);
+        assert_eq!(sink[0].rule_title, "Whoami");
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        assert!(Format::parse("xml").is_err());
+    }
+}
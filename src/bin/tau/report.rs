@@ -0,0 +1,48 @@
+use crate::args::Args;
+use crate::rules;
+use sigma_rust::report::Report;
+use std::io::Write;
+
+pub const USAGE: &str = "\
+Usage: tau report [--format <FORMAT>] [--title <TITLE>] [--pipeline <FILE>]... <PATH>...
+
+Render a catalog of the rules, grouped by logsource and ATT&CK technique, that documents
+every rule with its metadata and a summary of its detection.
+
+Arguments:
+    <PATH>              A rule file or a directory that is searched for .yml and .yaml files
+
+Options:
+    --format <FORMAT>   markdown (default) or html
+    --title <TITLE>     The title of the catalog [default: Detection rules]
+    --pipeline <FILE>   A processing pipeline applied to the rules before they are documented.
+                        May be given several times; pipelines are applied by priority.
+    --quiet             Do not report rules that cannot be loaded";
+
+pub fn run(args: &Args, out: &mut dyn Write, err: &mut dyn Write) -> Result<(), String> {
+    let html = match args.value("format").unwrap_or("markdown") {
+        "markdown" => false,
+        "html" => true,
+        format => return Err(format!("unknown format '{}'", format)),
+    };
+    if args.positional.is_empty() {
+        return Err("no rule paths given".to_string());
+    }
+    let pipeline = rules::pipeline(args.values("pipeline"))?;
+
+    let loaded = rules::load(&args.positional, pipeline.as_ref())?;
+    if !args.flag("quiet") {
+        rules::write_diagnostics(&loaded, err)?;
+    }
+
+    let mut report = Report::new(&loaded.rules);
+    if let Some(title) = args.value("title") {
+        report = report.with_title(title);
+    }
+    let rendered = if html {
+        report.to_html()
+    } else {
+        report.to_markdown()
+    };
+    write!(out, "{}", rendered).map_err(|e| e.to_string())
+}
@@ -0,0 +1,44 @@
+use sigma_rust::loader::{Loaded, Loader};
+use sigma_rust::pipeline::Pipeline;
+use std::fs;
+use std::io::Write;
+
+/// Load the pipeline files and chain them in the order of their priorities
+pub fn pipeline(files: &[String]) -> Result<Option<Pipeline>, String> {
+    if files.is_empty() {
+        return Ok(None);
+    }
+    let pipelines = files
+        .iter()
+        .map(|path| {
+            fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|yaml| Pipeline::from_yaml(&yaml).map_err(|err| err.to_string()))
+                .map_err(|err| format!("cannot load pipeline {}: {}", path, err))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Some(Pipeline::chain(pipelines)))
+}
+
+/// Load the rules of the files and directories with a [`Loader`], see [`Loader::load_paths`].
+/// The pipeline, if any, is applied to every rule before it is compiled, and detections may
+/// include selections of other files.
+pub fn load(paths: &[String], pipeline: Option<&Pipeline>) -> Result<Loaded, String> {
+    // Unlike unreadable rule files, a path given explicitly that does not exist is a usage error
+    for path in paths {
+        fs::metadata(path).map_err(|err| format!("cannot read {}: {}", path, err))?;
+    }
+    let mut loader = Loader::new().with_includes(true);
+    if let Some(pipeline) = pipeline {
+        loader = loader.with_pipeline(pipeline.clone());
+    }
+    Ok(loader.load_paths(paths))
+}
+
+/// Print the diagnostics of the skipped and degraded rules, one per line
+pub fn write_diagnostics(loaded: &Loaded, out: &mut dyn Write) -> Result<(), String> {
+    for diagnostic in &loaded.diagnostics {
+        writeln!(out, "{}", diagnostic).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
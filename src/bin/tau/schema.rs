@@ -0,0 +1,13 @@
+use sigma_rust::schema::rule_schema;
+use std::io::Write;
+
+pub const USAGE: &str = "\
+Usage: tau schema
+
+Print the JSON Schema of the rules tau accepts, including the supported field modifiers.
+Editors and validation services can use it to complete and validate rule files.";
+
+pub fn run(out: &mut dyn Write) -> Result<(), String> {
+    let schema = serde_json::to_string_pretty(&rule_schema()).map_err(|e| e.to_string())?;
+    writeln!(out, "{}", schema).map_err(|e| e.to_string())
+}
@@ -0,0 +1,194 @@
+//! Process wide caches for compiled field values.
+//!
+//! Many rules share identical values, e.g. `|endswith: '\cmd.exe'` appears in dozens of
+//! rules of the public Sigma repository. Compiled wildcard patterns and regular expressions
+//! are therefore shared between all fields that use the same value, so memory and compile
+//! time scale with the number of distinct patterns instead of the total number of values.
+//!
+//! The caches only hold weak references, so a compiled value is freed as soon as the last
+//! rule using it is dropped. CIDRs are not cached as an `IpCidr` is smaller than a pointer
+//! to a shared value and cheap to parse.
+use crate::config::{CaseFolding, EngineConfig, WildcardMode};
+use crate::re::{self, Regex, RegexBuilder};
+use crate::wildcard::{WildcardPattern, WildcardToken};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::hash::Hash;
+use std::sync::{Arc, LazyLock, Mutex, PoisonError, Weak};
+
+/// Wildcard patterns are keyed by their tokens, the wildcard mode, whether they match
+/// case-insensitive with full case folding and the size and DFA size limits of the lazy DFA
+type WildcardKey = (Vec<WildcardToken>, WildcardMode, bool, bool, usize, usize);
+static WILDCARD_PATTERNS: LazyLock<Cache<WildcardKey, WildcardPattern>> =
+    LazyLock::new(Cache::default);
+/// Regexes are keyed by their pattern as well as the size and DFA size limits
+static REGEXES: LazyLock<Cache<(String, usize, usize), Regex>> = LazyLock::new(Cache::default);
+
+/// The minimum number of entries before dead entries are pruned
+const MIN_PRUNE_THRESHOLD: usize = 64;
+
+/// A map of weak references to shared values
+struct Cache<K, V: ?Sized> {
+    inner: Mutex<CacheInner<K, V>>,
+}
+
+struct CacheInner<K, V: ?Sized> {
+    entries: HashMap<K, Weak<V>>,
+    prune_threshold: usize,
+}
+
+impl<K, V: ?Sized> Default for Cache<K, V> {
+    fn default() -> Self {
+        Self {
+            inner: Mutex::new(CacheInner {
+                entries: HashMap::new(),
+                prune_threshold: MIN_PRUNE_THRESHOLD,
+            }),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V: ?Sized> Cache<K, V> {
+    /// Get the shared value of a key or create it with `f` if no live value exists
+    fn get_or_try_insert<E>(
+        &self,
+        key: K,
+        f: impl FnOnce(&K) -> Result<Arc<V>, E>,
+    ) -> Result<Arc<V>, E> {
+        // A panic while holding the lock cannot leave the map in an inconsistent state
+        let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(value) = inner.entries.get(&key).and_then(Weak::upgrade) {
+            return Ok(value);
+        }
+
+        let value = f(&key)?;
+        if inner.entries.len() >= inner.prune_threshold {
+            inner.entries.retain(|_, v| v.strong_count() > 0);
+            inner.prune_threshold = MIN_PRUNE_THRESHOLD.max(inner.entries.len() * 2);
+        }
+        inner.entries.insert(key, Arc::downgrade(&value));
+        Ok(value)
+    }
+
+    /// Get the shared value of a key or create it with `f` if no live value exists
+    fn get_or_insert(&self, key: K, f: impl FnOnce(&K) -> Arc<V>) -> Arc<V> {
+        match self.get_or_try_insert(key, |key| Ok::<_, Infallible>(f(key))) {
+            Ok(value) => value,
+            Err(err) => match err {},
+        }
+    }
+
+    #[cfg(test)]
+    fn live_entries(&self) -> usize {
+        let inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        inner
+            .entries
+            .values()
+            .filter(|v| v.strong_count() > 0)
+            .count()
+    }
+}
+
+/// Get a shared wildcard pattern compiled according to the wildcard mode and case folding of
+/// the config
+pub(crate) fn wildcard_pattern(
+    tokens: Vec<WildcardToken>,
+    lowercase: bool,
+    config: &EngineConfig,
+) -> Arc<WildcardPattern> {
+    let graphemes = lowercase && config.case_folding == CaseFolding::Full;
+    let key = (
+        tokens,
+        config.wildcard_mode,
+        lowercase,
+        graphemes,
+        config.regex_size_limit,
+        config.regex_dfa_size_limit,
+    );
+    WILDCARD_PATTERNS.get_or_insert(key, |(tokens, mode, lowercase, graphemes, ..)| {
+        Arc::new(match mode {
+            _ if *graphemes => WildcardPattern::with_graphemes(tokens.clone()),
+            WildcardMode::Tokens => WildcardPattern::new(tokens.clone()),
+            WildcardMode::Dfa => WildcardPattern::with_dfa(tokens.clone(), *lowercase, config),
+        })
+    })
+}
+
+/// Get a shared regular expression compiled with the limits of the config
+pub(crate) fn regex(pattern: &str, config: &EngineConfig) -> Result<Arc<Regex>, re::Error> {
+    let key = (
+        pattern.to_string(),
+        config.regex_size_limit,
+        config.regex_dfa_size_limit,
+    );
+    REGEXES.get_or_try_insert(key, |(pattern, size_limit, dfa_size_limit)| {
+        RegexBuilder::new(pattern)
+            .size_limit(*size_limit)
+            .dfa_size_limit(*dfa_size_limit)
+            .build()
+            .map(Arc::new)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wildcard::tokenize;
+
+    #[test]
+    fn test_wildcard_patterns_are_shared() {
+        let config = EngineConfig::default();
+        let a = wildcard_pattern(tokenize("*\\cache-test-a.exe", true), true, &config);
+        let b = wildcard_pattern(tokenize("*\\CACHE-TEST-A.EXE", true), true, &config);
+        let c = wildcard_pattern(tokenize("*\\CACHE-TEST-A.EXE", false), false, &config);
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+
+        let dfa = EngineConfig {
+            wildcard_mode: WildcardMode::Dfa,
+            ..Default::default()
+        };
+        let d = wildcard_pattern(tokenize("*\\cache-test-a.exe", true), true, &dfa);
+        assert!(!Arc::ptr_eq(&a, &d));
+
+        // A pattern whose DFA exceeds the limits falls back to the token matcher
+        let limited = EngineConfig {
+            regex_size_limit: 1 << 10,
+            ..dfa
+        };
+        let e = wildcard_pattern(tokenize("*\\cache-test-a.exe", true), true, &limited);
+        assert!(!Arc::ptr_eq(&d, &e));
+    }
+
+    #[test]
+    fn test_regexes_are_shared() {
+        let config = EngineConfig::default();
+        let a = regex("cache-test-(a|b)", &config).unwrap();
+        let b = regex("cache-test-(a|b)", &config).unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(regex("cache-test-(", &config).is_err());
+
+        let limited = EngineConfig {
+            regex_dfa_size_limit: 1 << 10,
+            ..Default::default()
+        };
+        let c = regex("cache-test-(a|b)", &limited).unwrap();
+        assert!(!Arc::ptr_eq(&a, &c));
+    }
+
+    #[test]
+    fn test_dead_entries_are_pruned() {
+        let cache: Cache<usize, str> = Cache::default();
+        for i in 0..MIN_PRUNE_THRESHOLD * 4 {
+            cache.get_or_insert(i, |i| Arc::from(i.to_string()));
+        }
+        assert_eq!(cache.live_entries(), 0);
+        assert!(cache.inner.lock().unwrap().entries.len() <= MIN_PRUNE_THRESHOLD);
+
+        let kept = cache.get_or_insert(1, |_| Arc::from("kept"));
+        let again = cache.get_or_insert(1, |_| Arc::from("other"));
+        assert_eq!(&*again, "kept");
+        drop((kept, again));
+        assert_eq!(cache.live_entries(), 0);
+    }
+}
@@ -0,0 +1,457 @@
+//! Report which Sigma features rules use and which of them tau cannot evaluate.
+//!
+//! [`Capabilities::from_yaml`] inspects the source of a rule and lists the field modifiers,
+//! condition constructs and placeholders it uses, whether it is a correlation rule, and the
+//! features that keep it from being compiled. Selections that would be disabled when the rule is
+//! loaded with [`crate::loader::Loader::with_degraded_selections`] are listed as degraded.
+//!
+//! [`Coverage`] sums up the capabilities of a whole rule corpus, so maintainers can quantify
+//! how many of their rules tau evaluates fully and which missing features matter most.
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::capability::{Capabilities, Coverage};
+//!
+//! let rule = r#"
+//! title: Whoami
+//! logsource:
+//!     category: process_creation
+//! detection:
+//!     selection:
+//!         Image|endswith: '\whoami.exe'
+//!     admins:
+//!         User|expand: '%admins%'
+//!     condition: selection and not 1 of admins*
+//! "#;
+//!
+//! let capabilities = Capabilities::from_yaml(rule);
+//! assert!(capabilities.modifiers.contains("endswith"));
+//! assert_eq!(
+//!     capabilities.conditions.iter().collect::<Vec<_>>(),
+//!     [&"1 of", &"and", &"not"]
+//! );
+//! assert!(capabilities.placeholders.contains("admins"));
+//! assert_eq!(capabilities.unsupported[0].feature, "placeholder '%admins%'");
+//! assert!(!capabilities.compiles);
+//!
+//! let mut coverage = Coverage::new();
+//! coverage.add(&capabilities);
+//! assert_eq!(coverage.unsupported_rules, 1);
+//! ```
+use crate::detection::{degrade_selections, locate, Ast};
+use crate::error::ParserError;
+use crate::field::MODIFIERS;
+use crate::pipeline::placeholder;
+use crate::rule::Rule;
+use serde::Deserialize;
+use serde_yml::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A feature of a rule that tau cannot evaluate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limitation {
+    /// The feature, e.g. `correlation`, `modifier 'x'`, `placeholder '%x%'` or `selection 'x'`
+    pub feature: String,
+    /// The [stable code](crate::error) of the error raised by the feature, if any
+    pub code: Option<&'static str>,
+    pub message: String,
+}
+
+/// The Sigma features a rule uses and how far tau supports them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Capabilities {
+    /// The field modifiers, in lowercase
+    pub modifiers: BTreeSet<String>,
+    /// The constructs of the condition: `and`, `or`, `not`, `1 of`, `all of` and `them`
+    pub conditions: BTreeSet<&'static str>,
+    /// Whether a selection is a list of keywords, which are searched in all values of an event
+    pub keywords: bool,
+    /// Whether the document is a correlation rule
+    pub correlation: bool,
+    /// The names of the placeholders of values with the `expand` modifier
+    pub placeholders: BTreeSet<String>,
+    /// Whether the rule can be compiled as it is
+    pub compiles: bool,
+    /// Features that keep the rule from being compiled
+    pub unsupported: Vec<Limitation>,
+    /// Selections that never match if the rule is loaded with degraded selections
+    pub degraded: Vec<Limitation>,
+}
+
+impl Capabilities {
+    /// Inspect the source of a rule. Apply processing pipelines beforehand, as they may resolve
+    /// placeholders and change modifiers.
+    pub fn from_yaml(yaml: &str) -> Self {
+        let mut result = Self::default();
+        let mut rule: Value = match serde_yml::from_str(yaml) {
+            Ok(rule) => rule,
+            Err(err) => {
+                result.unsupported.push(Limitation {
+                    feature: "yaml".to_string(),
+                    code: None,
+                    message: err.to_string(),
+                });
+                return result;
+            }
+        };
+        if rule.get("correlation").is_some() {
+            result.correlation = true;
+            result.unsupported.push(Limitation {
+                feature: "correlation".to_string(),
+                code: None,
+                message: "Correlation rules are not supported".to_string(),
+            });
+            return result;
+        }
+
+        if let Some(detection) = rule.get("detection").and_then(Value::as_mapping) {
+            for (name, selection) in detection {
+                match name.as_str() {
+                    Some("condition") => result.inspect_condition(selection),
+                    Some(_) => result.inspect_selection(selection),
+                    None => {}
+                }
+            }
+        }
+
+        result.compiles = match Rule::deserialize(&rule) {
+            Ok(_) => true,
+            Err(err) => {
+                result.inspect_error(yaml, &mut rule, err);
+                false
+            }
+        };
+        result
+    }
+
+    /// Whether the rule is evaluated with all its selections
+    pub fn is_fully_supported(&self) -> bool {
+        self.compiles && self.unsupported.is_empty()
+    }
+
+    fn inspect_selection(&mut self, selection: &Value) {
+        match selection {
+            Value::Mapping(fields) => {
+                for (field, values) in fields {
+                    self.inspect_field(field.as_str().unwrap_or_default(), values);
+                }
+            }
+            Value::Sequence(items) if items.iter().all(Value::is_mapping) => {
+                items.iter().for_each(|item| self.inspect_selection(item));
+            }
+            Value::Sequence(_) | Value::String(_) | Value::Number(_) => self.keywords = true,
+            _ => {}
+        }
+    }
+
+    fn inspect_field(&mut self, field: &str, values: &Value) {
+        for modifier in field.split('|').skip(1) {
+            let modifier = modifier.to_lowercase();
+            if modifier == "expand" {
+                let values = match values {
+                    Value::Sequence(values) => values.iter().collect(),
+                    value => vec![value],
+                };
+                for mut rest in values.into_iter().filter_map(Value::as_str) {
+                    while let Some((name, _, end)) = placeholder(rest) {
+                        if self.placeholders.insert(name.to_string()) {
+                            self.unsupported.push(Limitation {
+                                feature: format!("placeholder '%{}%'", name),
+                                code: Some("TAU0024"),
+                                message: "Placeholders must be resolved by a processing pipeline or a placeholder list of the engine config"
+                                    .to_string(),
+                            });
+                        }
+                        rest = rest.get(end..).unwrap_or_default();
+                    }
+                }
+            } else if !MODIFIERS.contains(&modifier.as_str()) && !self.modifiers.contains(&modifier)
+            {
+                let error = ParserError::UnknownModifier(modifier.clone());
+                self.unsupported.push(Limitation {
+                    feature: format!("modifier '{}'", modifier),
+                    code: Some(error.code()),
+                    message: error.to_string(),
+                });
+            }
+            self.modifiers.insert(modifier);
+        }
+    }
+
+    fn inspect_condition(&mut self, condition: &Value) {
+        if let Some(ast) = condition.as_str().and_then(|c| Ast::new(c).ok()) {
+            self.visit(&ast);
+        }
+    }
+
+    fn visit(&mut self, ast: &Ast) {
+        match ast {
+            Ast::Selection(_) => {}
+            Ast::OneOf(_) => {
+                self.conditions.insert("1 of");
+            }
+            Ast::AllOf(_) => {
+                self.conditions.insert("all of");
+            }
+            Ast::OneOfThem => self.conditions.extend(["1 of", "them"]),
+            Ast::AllOfThem => self.conditions.extend(["all of", "them"]),
+            Ast::Not(operand) => {
+                self.conditions.insert("not");
+                self.visit(operand);
+            }
+            Ast::And(left, right) | Ast::Or(left, right) => {
+                self.conditions.insert(match ast {
+                    Ast::And(..) => "and",
+                    _ => "or",
+                });
+                self.visit(left);
+                self.visit(right);
+            }
+        }
+    }
+
+    /// Explain why the rule cannot be compiled
+    fn inspect_error(&mut self, yaml: &str, rule: &mut Value, err: serde_yml::Error) {
+        let mut degraded_rule = rule.clone();
+        let degraded = degrade_selections(yaml, &mut degraded_rule);
+        if !degraded.is_empty() && Rule::deserialize(&degraded_rule).is_ok() {
+            self.degraded = degraded
+                .into_iter()
+                .map(|(name, _, error)| Limitation {
+                    feature: format!("selection '{}'", name),
+                    code: Some(error.code()),
+                    message: error.to_string(),
+                })
+                .collect();
+            if !self.unsupported.is_empty() {
+                return;
+            }
+        }
+
+        let (code, message) = match locate(yaml, rule) {
+            Some((_, error)) => (Some(error.code()), error.to_string()),
+            None => (None, err.to_string()),
+        };
+        let explained = self
+            .unsupported
+            .iter()
+            .any(|limitation| code.is_some() && limitation.code == code);
+        if !explained {
+            self.unsupported.push(Limitation {
+                feature: "rule".to_string(),
+                code,
+                message,
+            });
+        }
+    }
+}
+
+/// The unsupported feature that raised an error, named like [`Limitation::feature`], or `None`
+/// if the rule is invalid rather than using a feature tau lacks
+pub(crate) fn unsupported_feature(error: &ParserError) -> Option<String> {
+    match error {
+        ParserError::UnknownModifier(modifier) | ParserError::DisabledModifier(modifier, _) => {
+            Some(format!("modifier '{}'", modifier.to_lowercase()))
+        }
+        ParserError::UnresolvedPlaceholder(value) => Some(format!("placeholder '{}'", value)),
+        ParserError::UnresolvedValueList(name) => Some(format!("value list '{}'", name)),
+        ParserError::LegacyCondition(_) => Some("legacy condition".to_string()),
+        ParserError::LimitExceeded(limit, _) => Some(format!("limit '{}'", limit)),
+        ParserError::Located { error, .. } => unsupported_feature(error),
+        _ => None,
+    }
+}
+
+/// The capabilities of a rule corpus, see [`Capabilities`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    /// The number of inspected rules
+    pub rules: usize,
+    /// The number of rules that are evaluated with all their selections
+    pub supported_rules: usize,
+    /// The number of rules that can only be loaded with degraded selections
+    pub degraded_rules: usize,
+    /// The number of rules that cannot be loaded
+    pub unsupported_rules: usize,
+    /// The number of rules using each modifier
+    pub modifiers: BTreeMap<String, usize>,
+    /// The number of rules using each condition construct
+    pub conditions: BTreeMap<&'static str, usize>,
+    /// The number of rules that cannot be compiled because of each feature, e.g.
+    /// `correlation` or `modifier`. Only the kind of the feature is counted, not its name.
+    pub limitations: BTreeMap<String, usize>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the capabilities of a rule
+    pub fn add(&mut self, capabilities: &Capabilities) {
+        self.rules += 1;
+        if capabilities.is_fully_supported() {
+            self.supported_rules += 1;
+        } else if !capabilities.degraded.is_empty() {
+            self.degraded_rules += 1;
+        } else {
+            self.unsupported_rules += 1;
+        }
+        for modifier in &capabilities.modifiers {
+            *self.modifiers.entry(modifier.clone()).or_default() += 1;
+        }
+        for condition in &capabilities.conditions {
+            *self.conditions.entry(condition).or_default() += 1;
+        }
+        let kinds: BTreeSet<&str> = capabilities
+            .unsupported
+            .iter()
+            .chain(&capabilities.degraded)
+            .map(|limitation| {
+                limitation
+                    .feature
+                    .split_once(' ')
+                    .map_or(limitation.feature.as_str(), |(kind, _)| kind)
+            })
+            .collect();
+        for kind in kinds {
+            *self.limitations.entry(kind.to_string()).or_default() += 1;
+        }
+    }
+
+    /// The fraction of rules that are evaluated with all their selections
+    pub fn supported_ratio(&self) -> Option<f64> {
+        (self.rules > 0).then(|| self.supported_rules as f64 / self.rules as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supported() {
+        let capabilities = Capabilities::from_yaml(
+            r#"
+title: Test
+logsource:
+detection:
+    keywords:
+        - mimikatz
+    selection:
+        - CommandLine|contains|all: ['-enc', 'IEX']
+        - Image|endswith: '\powershell.exe'
+    filter:
+        User|re: '^SYSTEM$'
+    condition: (keywords or all of selection*) and not filter
+"#,
+        );
+        assert!(capabilities.is_fully_supported());
+        assert!(capabilities.keywords);
+        assert!(!capabilities.correlation);
+        assert_eq!(
+            capabilities.modifiers.iter().collect::<Vec<_>>(),
+            ["all", "contains", "endswith", "re"]
+        );
+        assert_eq!(
+            capabilities.conditions.iter().collect::<Vec<_>>(),
+            [&"all of", &"and", &"not", &"or"]
+        );
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let capabilities = Capabilities::from_yaml(
+            r#"
+title: Test
+logsource:
+detection:
+    selection:
+        User|expand:
+            - '%admins%\%users%'
+            - '%admins%'
+    condition: selection
+"#,
+        );
+        assert_eq!(
+            capabilities.placeholders.iter().collect::<Vec<_>>(),
+            ["admins", "users"]
+        );
+        assert_eq!(capabilities.unsupported.len(), 2);
+        assert!(!capabilities.compiles);
+    }
+
+    #[test]
+    fn test_correlation() {
+        let capabilities = Capabilities::from_yaml(
+            r#"
+title: Many failed logons
+correlation:
+    type: event_count
+    rules:
+        - failed_logon
+    group-by:
+        - User
+    timespan: 5m
+    condition:
+        gte: 10
+"#,
+        );
+        assert!(capabilities.correlation);
+        assert!(!capabilities.compiles);
+        assert_eq!(capabilities.unsupported[0].feature, "correlation");
+    }
+
+    #[test]
+    fn test_degraded() {
+        let capabilities = Capabilities::from_yaml(
+            r#"
+title: Test
+logsource:
+detection:
+    selection:
+        Image|endswith: '\whoami.exe'
+    future:
+        CommandLine|unknown: whoami
+    condition: selection or future
+"#,
+        );
+        assert!(!capabilities.compiles);
+        assert_eq!(capabilities.unsupported.len(), 1);
+        assert_eq!(capabilities.unsupported[0].feature, "modifier 'unknown'");
+        assert_eq!(capabilities.unsupported[0].code, Some("TAU0002"));
+        assert_eq!(capabilities.degraded[0].feature, "selection 'future'");
+
+        let mut coverage = Coverage::new();
+        coverage.add(&capabilities);
+        coverage.add(&Capabilities::from_yaml(
+            "title: Test\nlogsource:\ndetection:\n    selection:\n        Image: x\n    condition: selection",
+        ));
+        assert_eq!(
+            (
+                coverage.rules,
+                coverage.supported_rules,
+                coverage.degraded_rules
+            ),
+            (2, 1, 1)
+        );
+        assert_eq!(coverage.supported_ratio(), Some(0.5));
+        assert_eq!(coverage.modifiers["endswith"], 1);
+        assert_eq!(coverage.limitations["modifier"], 1);
+        assert_eq!(coverage.limitations["selection"], 1);
+    }
+
+    #[test]
+    fn test_other_errors() {
+        let capabilities = Capabilities::from_yaml(
+            "title: Test\nlogsource:\ndetection:\n    selection:\n        Image: x\n    condition: selection and missing",
+        );
+        assert!(!capabilities.compiles);
+        assert_eq!(capabilities.unsupported[0].feature, "rule");
+        assert_eq!(capabilities.unsupported[0].code, Some("TAU0015"));
+
+        let capabilities = Capabilities::from_yaml("title: [");
+        assert_eq!(capabilities.unsupported[0].feature, "yaml");
+        assert_eq!(Coverage::new().supported_ratio(), None);
+    }
+}
@@ -0,0 +1,748 @@
+use crate::placeholder::{PlaceholderList, Placeholders};
+use crate::rule::Rule;
+use crate::ruleset::{EvaluationOrder, MatchOrder, RuleSet};
+use crate::valuelist::ValueLists;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+/// The default compiled size limit of a regular expression, same as in the `regex` crate
+const DEFAULT_REGEX_SIZE_LIMIT: usize = 10 * (1 << 20);
+/// The default cache size limit of the lazy DFA of a regular expression, same as in the `regex` crate
+const DEFAULT_REGEX_DFA_SIZE_LIMIT: usize = 2 * (1 << 20);
+
+thread_local! {
+    static ACTIVE: RefCell<Option<EngineConfig>> = const { RefCell::new(None) };
+}
+
+/// How wildcard patterns of string values are compiled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum WildcardMode {
+    /// Match the wildcard tokens directly. This needs the least memory.
+    #[default]
+    Tokens,
+    /// Lower patterns containing wildcards to a lazy DFA, which matches in linear time
+    /// regardless of the number of stars at the cost of additional memory per pattern.
+    /// Patterns that are a single literal with leading or trailing stars are still matched
+    /// with a plain substring search.
+    Dfa,
+}
+
+/// How string values are compared case-insensitively
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CaseFolding {
+    /// Lowercase both strings character by character. This misses matches in which case
+    /// folding changes the length of a string, e.g. `straße` does not match `STRASSE`.
+    #[default]
+    Simple,
+    /// Fold both strings with full Unicode case folding, so `ß`, `ẞ` and `SS` all match `ss`,
+    /// and match wildcard patterns, including those of `contains`, `startswith` and `endswith`,
+    /// only on grapheme cluster boundaries: `endswith: e` does not match an `é` written as `e`
+    /// followed by a combining accent, and `?` matches a whole grapheme cluster. Non-ASCII
+    /// values are matched without the lazy DFA of [`WildcardMode::Dfa`] and such patterns are
+    /// not used to prefilter rules, so evaluation is slower.
+    Full,
+}
+
+/// How closely rules must follow the [Sigma specification](https://github.com/SigmaHQ/sigma-specification)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpecMode {
+    /// Reject anything outside the specification, e.g. modifiers that are not lowercase
+    #[default]
+    Strict,
+    /// Accept common deviations of community rules: modifiers in any case, and numbers and
+    /// booleans as values of `contains`, `startswith` and `endswith`, which are matched as their
+    /// string representation
+    Permissive,
+    /// Accept everything of [`SpecMode::Permissive`] and the deprecated condition syntax of
+    /// Sigma v1 rules: a list of conditions, which are combined with `or`, and aggregations
+    /// after a `|`, which are dropped so only their search is evaluated. The rewritten
+    /// constructs are reported by [`crate::Rule::legacy_warnings`].
+    Legacy,
+}
+
+/// How the values of a field are compared, regardless of the modifiers the rules use, see
+/// [`EngineConfig::field_policies`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FieldPolicy {
+    /// Compare case-insensitively, even if a rule uses `cased`. Regular expressions are
+    /// compiled with the `i` flag.
+    CaseInsensitive,
+    /// Compare as a file system path: case-insensitively, and `\` and `/` in the values of rules
+    /// also match the other separator. Fields with the `all` modifier are only compared
+    /// case-insensitively.
+    Path,
+    /// Match plain values that are IP addresses or networks like the `cidr` modifier, so
+    /// addresses match regardless of their notation, e.g. `::1` matches `0:0::1`. Requires the
+    /// `cidr` feature.
+    Ip,
+    /// Compare numbers numerically with event values of any numeric type and with strings that
+    /// contain a number, e.g. `EventID: 4688` matches `"4688"` and `4688.0`. Applies to plain
+    /// values and the values of `gt`, `gte`, `lt` and `lte`, and strings of rules that contain
+    /// a number are compared as numbers as well.
+    Numeric,
+    /// Compare as a command line: string values are split into arguments like Windows does,
+    /// respecting quotes and escaped quotes, and values of rules match whole arguments, e.g.
+    /// `CommandLine: '-enc'` matches `powershell -enc x` but not `C:\-enc\x.exe`. The values
+    /// of `contains`, `startswith`, `endswith` and `re` are matched against each argument, and
+    /// the field is not used to prefilter rules.
+    Arguments,
+}
+
+/// Caps on the size of rules, so compiling rules from untrusted sources stays cheap. Rules
+/// exceeding a limit fail to parse with [`crate::error::ParserError::LimitExceeded`]. The
+/// defaults are far above the size of real rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParserLimits {
+    /// The maximum number of values of a field as written in the rule
+    pub max_values_per_field: usize,
+    /// The maximum number of selections of a rule
+    pub max_selections: usize,
+    /// The maximum nesting depth of the condition, counting operators and parentheses
+    pub max_condition_depth: usize,
+    /// The maximum number of values of all fields of a rule after value transformations like
+    /// `base64offset` and `windash`, which multiply the values
+    pub max_expanded_values: usize,
+}
+
+impl Default for ParserLimits {
+    fn default() -> Self {
+        Self {
+            max_values_per_field: 100_000,
+            max_selections: 1_000,
+            max_condition_depth: 128,
+            max_expanded_values: 1_000_000,
+        }
+    }
+}
+
+/// A limit of [`ParserLimits`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Limit {
+    ValuesPerField,
+    Selections,
+    ConditionDepth,
+    ExpandedValues,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ValuesPerField => write!(f, "values per field"),
+            Self::Selections => write!(f, "selections"),
+            Self::ConditionDepth => write!(f, "nested levels of the condition"),
+            Self::ExpandedValues => write!(f, "values after transformations"),
+        }
+    }
+}
+
+/// Configuration of how rules are compiled and evaluated.
+///
+/// Rules parsed with [`EngineConfig::rule_from_yaml`] are compiled with this configuration,
+/// while [`crate::rule_from_yaml`] uses the defaults. Rule sets created with
+/// [`EngineConfig::rule_set`] are evaluated with its match order, evaluation order, match limit,
+/// time budget and parallelism. All options can be set with [`EngineConfig::builder`].
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::{EngineConfig, Event, FieldPolicy, MatchOrder, SpecMode};
+///
+/// let config = EngineConfig::builder()
+///     .spec_mode(SpecMode::Permissive)
+///     .field_policy("EventID", FieldPolicy::Numeric)
+///     .regex_size_limit(1 << 10)
+///     .match_order(MatchOrder::Severity)
+///     .build();
+///
+/// let rule = config.rule_from_yaml(
+///     r#"
+/// title: Pathological regex
+/// logsource:
+/// detection:
+///     selection:
+///         CommandLine|re: '\w{1000}'
+///     condition: selection
+/// "#,
+/// );
+/// assert!(rule.is_err());
+///
+/// let rule = config.rule_from_yaml(
+///     r#"
+/// title: Process creation
+/// logsource:
+/// detection:
+///     selection:
+///         EventID: 4688
+///     condition: selection
+/// "#,
+/// );
+/// let rule_set = config.rule_set(vec![rule.unwrap()]);
+/// assert_eq!(rule_set.match_order(), MatchOrder::Severity);
+/// assert_eq!(rule_set.evaluate(&Event::from([("EventID", "4688")])).len(), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineConfig {
+    /// The maximum size in bytes of a compiled `|re` pattern. Patterns exceeding it fail to parse.
+    pub regex_size_limit: usize,
+    /// The maximum size in bytes of the lazy DFA cache of a `|re` pattern
+    pub regex_dfa_size_limit: usize,
+    /// Values longer than this many bytes are not matched against `|re` patterns and are
+    /// treated as not matching.
+    ///
+    /// Regular expressions run in linear time of the input, so this bounds the time a single
+    /// match can take. It replaces a match timeout, which the `regex` crate does not support.
+    pub regex_max_input_len: Option<usize>,
+    /// How wildcard patterns are compiled
+    pub wildcard_mode: WildcardMode,
+    /// How case-insensitive wildcard patterns are matched
+    pub case_folding: CaseFolding,
+    /// How closely rules must follow the specification. The mode a rule was parsed with is
+    /// recorded in [`Rule::spec_mode`].
+    pub spec_mode: SpecMode,
+    /// Caps on the size of rules
+    pub limits: ParserLimits,
+    /// Comparison policies by field name, applied to every rule regardless of its modifiers,
+    /// e.g. to match all path fields case-insensitively
+    pub field_policies: HashMap<String, FieldPolicy>,
+    /// The lists that fields with the `expand` modifier match, see [`crate::placeholder`]
+    pub placeholders: Placeholders,
+    /// The lists that `listref:` values are replaced with, see [`crate::valuelist`]
+    pub value_lists: ValueLists,
+    /// The order of the matches of an event, see [`RuleSet::with_match_order`]
+    pub match_order: MatchOrder,
+    /// The order in which the rules are evaluated, see [`RuleSet::with_evaluation_order`]
+    pub evaluation_order: EvaluationOrder,
+    /// The maximum number of matches per event, see [`RuleSet::with_match_limit`]
+    pub match_limit: Option<usize>,
+    /// The time budget per event of [`RuleSet::evaluate_budgeted`], see
+    /// [`RuleSet::with_time_budget`]
+    pub time_budget: Option<Duration>,
+    /// Whether [`RuleSet::evaluate`] and [`RuleSet::evaluate_batch`] evaluate on the rayon thread
+    /// pool, see [`RuleSet::with_parallel`]
+    #[cfg(feature = "parallel")]
+    pub parallel: bool,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            regex_size_limit: DEFAULT_REGEX_SIZE_LIMIT,
+            regex_dfa_size_limit: DEFAULT_REGEX_DFA_SIZE_LIMIT,
+            regex_max_input_len: None,
+            wildcard_mode: WildcardMode::default(),
+            case_folding: CaseFolding::default(),
+            spec_mode: SpecMode::default(),
+            limits: ParserLimits::default(),
+            field_policies: HashMap::new(),
+            placeholders: Placeholders::default(),
+            value_lists: ValueLists::default(),
+            match_order: MatchOrder::default(),
+            evaluation_order: EvaluationOrder::default(),
+            match_limit: None,
+            time_budget: None,
+            #[cfg(feature = "parallel")]
+            parallel: false,
+        }
+    }
+}
+
+impl EngineConfig {
+    /// Create a builder starting from the default configuration
+    pub fn builder() -> EngineConfigBuilder {
+        EngineConfigBuilder::default()
+    }
+
+    /// Create a rule set that is evaluated with the options of this configuration. The rules
+    /// must already be compiled, e.g. with [`EngineConfig::rule_from_yaml`].
+    pub fn rule_set(&self, rules: Vec<Rule>) -> RuleSet {
+        let rule_set = RuleSet::new(rules)
+            .with_match_order(self.match_order)
+            .with_evaluation_order(self.evaluation_order);
+        let rule_set = match self.match_limit {
+            Some(limit) => rule_set.with_match_limit(limit),
+            None => rule_set,
+        };
+        let rule_set = match self.time_budget {
+            Some(budget) => rule_set.with_time_budget(budget),
+            None => rule_set,
+        };
+        #[cfg(feature = "parallel")]
+        let rule_set = rule_set.with_parallel(self.parallel);
+        rule_set
+    }
+
+    /// Parse a rule from a YAML string and compile it with this configuration
+    pub fn rule_from_yaml(&self, yaml: &str) -> Result<Rule, serde_yml::Error> {
+        self.scoped(|| serde_yml::from_str(yaml))
+    }
+
+    /// Restore a rule set from a snapshot and compile its regular expressions and wildcard
+    /// patterns with this configuration, see [`crate::snapshot`]
+    #[cfg(feature = "snapshot")]
+    pub fn rule_set_from_snapshot(
+        &self,
+        bytes: &[u8],
+    ) -> Result<crate::RuleSet, crate::snapshot::SnapshotError> {
+        self.scoped(|| crate::RuleSet::from_snapshot(bytes))
+    }
+
+    /// Run `f` with this configuration being the active one of the current thread
+    pub(crate) fn scoped<R>(&self, f: impl FnOnce() -> R) -> R {
+        struct Restore(Option<EngineConfig>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                ACTIVE.set(self.0.take());
+            }
+        }
+
+        let _restore = Restore(ACTIVE.replace(Some(self.clone())));
+        f()
+    }
+}
+
+/// Builds an [`EngineConfig`] option by option, see [`EngineConfig::builder`]. Options that are
+/// not set keep their default.
+#[derive(Debug, Clone, Default)]
+pub struct EngineConfigBuilder {
+    config: EngineConfig,
+}
+
+impl EngineConfigBuilder {
+    /// See [`EngineConfig::regex_size_limit`]
+    pub fn regex_size_limit(mut self, limit: usize) -> Self {
+        self.config.regex_size_limit = limit;
+        self
+    }
+
+    /// See [`EngineConfig::regex_dfa_size_limit`]
+    pub fn regex_dfa_size_limit(mut self, limit: usize) -> Self {
+        self.config.regex_dfa_size_limit = limit;
+        self
+    }
+
+    /// See [`EngineConfig::regex_max_input_len`]
+    pub fn regex_max_input_len(mut self, len: usize) -> Self {
+        self.config.regex_max_input_len = Some(len);
+        self
+    }
+
+    /// See [`EngineConfig::wildcard_mode`]
+    pub fn wildcard_mode(mut self, mode: WildcardMode) -> Self {
+        self.config.wildcard_mode = mode;
+        self
+    }
+
+    /// See [`EngineConfig::case_folding`]
+    pub fn case_folding(mut self, folding: CaseFolding) -> Self {
+        self.config.case_folding = folding;
+        self
+    }
+
+    /// See [`EngineConfig::spec_mode`]
+    pub fn spec_mode(mut self, mode: SpecMode) -> Self {
+        self.config.spec_mode = mode;
+        self
+    }
+
+    /// See [`EngineConfig::limits`]
+    pub fn limits(mut self, limits: ParserLimits) -> Self {
+        self.config.limits = limits;
+        self
+    }
+
+    /// Compare the values of a field with this policy, replacing a previous policy of the field
+    pub fn field_policy<S: Into<String>>(mut self, field: S, policy: FieldPolicy) -> Self {
+        self.config.field_policies.insert(field.into(), policy);
+        self
+    }
+
+    /// Replace all placeholder lists. The lists are shared with `placeholders`, so reloading
+    /// them also changes the values of the compiled rules.
+    pub fn placeholders(mut self, placeholders: Placeholders) -> Self {
+        self.config.placeholders = placeholders;
+        self
+    }
+
+    /// Add a placeholder list, replacing a previous list of the same name
+    pub fn placeholder(mut self, list: PlaceholderList) -> Self {
+        self.config.placeholders.insert(list);
+        self
+    }
+
+    /// Replace all value lists
+    pub fn value_lists(mut self, value_lists: ValueLists) -> Self {
+        self.config.value_lists = value_lists;
+        self
+    }
+
+    /// See [`RuleSet::with_match_order`]
+    pub fn match_order(mut self, order: MatchOrder) -> Self {
+        self.config.match_order = order;
+        self
+    }
+
+    /// See [`RuleSet::with_evaluation_order`]
+    pub fn evaluation_order(mut self, order: EvaluationOrder) -> Self {
+        self.config.evaluation_order = order;
+        self
+    }
+
+    /// See [`RuleSet::with_match_limit`]
+    pub fn match_limit(mut self, limit: usize) -> Self {
+        self.config.match_limit = Some(limit);
+        self
+    }
+
+    /// See [`RuleSet::with_time_budget`]
+    pub fn time_budget(mut self, budget: Duration) -> Self {
+        self.config.time_budget = Some(budget);
+        self
+    }
+
+    /// See [`RuleSet::with_parallel`]
+    #[cfg(feature = "parallel")]
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.config.parallel = parallel;
+        self
+    }
+
+    /// Build the configuration with the options set so far
+    pub fn build(self) -> EngineConfig {
+        self.config
+    }
+}
+
+/// Call `f` with the configuration that is active on the current thread while a rule is compiled
+pub(crate) fn with_active<R>(f: impl FnOnce(&EngineConfig) -> R) -> R {
+    ACTIVE.with_borrow(|config| match config {
+        Some(config) => f(config),
+        None => f(&EngineConfig::default()),
+    })
+}
+
+/// The spec mode of the configuration that is active on the current thread
+pub(crate) fn active_spec_mode() -> SpecMode {
+    with_active(|config| config.spec_mode)
+}
+
+/// The limits of the configuration that is active on the current thread
+pub(crate) fn active_limits() -> ParserLimits {
+    with_active(|config| config.limits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Event;
+
+    const RULE: &str = r#"
+title: Regex
+logsource:
+detection:
+    selection:
+        CommandLine|re:
+            - 'who.mi'
+            - '[a-z]{3}\d'
+    condition: selection
+"#;
+
+    #[test]
+    fn test_scoped_config() {
+        let config = EngineConfig {
+            regex_max_input_len: Some(8),
+            ..Default::default()
+        };
+        config.scoped(|| {
+            with_active(|active| assert_eq!(active, &config));
+            EngineConfig::default()
+                .scoped(|| with_active(|active| assert_eq!(active.regex_max_input_len, None)));
+            with_active(|active| assert_eq!(active, &config));
+        });
+        with_active(|active| assert_eq!(active, &EngineConfig::default()));
+    }
+
+    #[test]
+    fn test_regex_size_limit() {
+        let config = EngineConfig {
+            regex_size_limit: 100,
+            ..Default::default()
+        };
+        assert!(config.rule_from_yaml(RULE).is_err());
+        assert!(EngineConfig::default().rule_from_yaml(RULE).is_ok());
+    }
+
+    #[test]
+    fn test_regex_max_input_len() {
+        let config = EngineConfig {
+            regex_max_input_len: Some(8),
+            ..Default::default()
+        };
+        let rule = config.rule_from_yaml(RULE).unwrap();
+        assert!(rule.is_match(&Event::from([("CommandLine", "whoami")])));
+        assert!(!rule.is_match(&Event::from([("CommandLine", "cmd /c whoami")])));
+
+        let rule = crate::rule_from_yaml(RULE).unwrap();
+        assert!(rule.is_match(&Event::from([("CommandLine", "cmd /c whoami")])));
+    }
+
+    #[test]
+    fn test_wildcard_mode() {
+        let rule = r#"
+title: Wildcards
+logsource:
+detection:
+    selection:
+        CommandLine:
+            - '*invoke-*mimikatz*'
+            - 'c:/windows/*/cmd.exe'
+        Image|cased: '*?/Temp/*.exe'
+    condition: selection
+"#;
+        let config = EngineConfig {
+            wildcard_mode: WildcardMode::Dfa,
+            ..Default::default()
+        };
+        let dfa = config.rule_from_yaml(rule).unwrap();
+        let tokens = crate::rule_from_yaml(rule).unwrap();
+
+        let events = [
+            Event::from([
+                ("CommandLine", "Invoke-Foo; MIMIKATZ"),
+                ("Image", "C:/Temp/a.exe"),
+            ]),
+            Event::from([
+                ("CommandLine", "C:/Windows/System32/cmd.exe"),
+                ("Image", "C:/temp/a.exe"),
+            ]),
+            Event::from([
+                ("CommandLine", "C:/Windows/cmd.exe"),
+                ("Image", "C:/Temp/a.exe"),
+            ]),
+            Event::from([
+                ("CommandLine", "C:/Windows/x/cmd.exe"),
+                ("Image", "/Temp/a.exe"),
+            ]),
+        ];
+        for event in events.iter() {
+            assert_eq!(dfa.is_match(event), tokens.is_match(event), "{:?}", event);
+        }
+        assert!(dfa.is_match(&events[0]));
+        assert!(!dfa.is_match(&events[1]));
+    }
+
+    #[test]
+    fn test_case_folding() {
+        let rule = r#"
+title: Street
+logsource:
+detection:
+    selection:
+        Image|endswith: '\straße.exe'
+        User|cased: 'Weiß'
+    keywords:
+        - 'STRASSE'
+    condition: selection or keywords
+"#;
+        let config = EngineConfig::builder()
+            .case_folding(CaseFolding::Full)
+            .wildcard_mode(WildcardMode::Dfa)
+            .build();
+        let full = config.rule_set(vec![config.rule_from_yaml(rule).unwrap()]);
+        let simple = RuleSet::new(vec![crate::rule_from_yaml(rule).unwrap()]);
+
+        let event = Event::from([("Image", "C:\\STRASSE.EXE"), ("User", "Weiß")]);
+        assert_eq!(full.evaluate(&event).len(), 1);
+        assert!(simple.evaluate(&event).is_empty());
+        // Values of `cased` fields are still compared exactly
+        let event = Event::from([("Image", "C:\\STRASSE.EXE"), ("User", "WEISS")]);
+        assert!(full.evaluate(&event).is_empty());
+        let event = Event::from([("Comment", "straße")]);
+        assert_eq!(full.evaluate(&event).len(), 1);
+        assert!(simple.evaluate(&event).is_empty());
+    }
+
+    #[test]
+    fn test_spec_mode() {
+        let rule = r#"
+title: Deviations
+logsource:
+detection:
+    selection:
+        Image|EndsWith: '\whoami.exe'
+        ProcessId|contains: 42
+    condition: selection
+"#;
+        let err = crate::rule_from_yaml(rule).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Unknown field modifier 'EndsWith' provided, did you mean 'endswith'?"));
+        let err = crate::rule_from_yaml(&rule.replace("EndsWith", "endswith")).unwrap_err();
+        assert!(err.to_string().contains("must be used with string values"));
+
+        let config = EngineConfig {
+            spec_mode: SpecMode::Permissive,
+            ..Default::default()
+        };
+        let rule = config.rule_from_yaml(rule).unwrap();
+        assert_eq!(rule.spec_mode, SpecMode::Permissive);
+        assert!(rule.is_match(&Event::from([
+            ("Image", r"C:\Windows\whoami.exe"),
+            ("ProcessId", "1420"),
+        ])));
+        assert!(!rule.is_match(&Event::from([
+            ("Image", r"C:\Windows\whoami.exe"),
+            ("ProcessId", "1337"),
+        ])));
+
+        let rule = crate::rule_from_yaml(RULE).unwrap();
+        assert_eq!(rule.spec_mode, SpecMode::Strict);
+    }
+
+    #[test]
+    fn test_legacy_spec_mode() {
+        let rule = r#"
+title: Sigma v1
+logsource:
+detection:
+    selection:
+        Image|EndsWith: '\whoami.exe'
+    keywords:
+        - mimikatz
+    condition:
+        - selection | count() by User > 10
+        - keywords
+"#;
+        let permissive = EngineConfig {
+            spec_mode: SpecMode::Permissive,
+            ..Default::default()
+        };
+        let err = permissive.rule_from_yaml(rule).unwrap_err();
+        assert!(err.to_string().contains("a list of conditions"), "{}", err);
+        let err = permissive
+            .rule_from_yaml(&rule.replace("\n        - keywords", ""))
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("uses | count() by User > 10, a Sigma v1 construct"),
+            "{}",
+            err
+        );
+
+        let legacy = EngineConfig {
+            spec_mode: SpecMode::Legacy,
+            ..Default::default()
+        };
+        let rule = legacy.rule_from_yaml(rule).unwrap();
+        assert_eq!(rule.spec_mode, SpecMode::Legacy);
+        assert_eq!(rule.detection.get_condition(), "(selection) or (keywords)");
+        assert_eq!(
+            rule.legacy_warnings(),
+            [
+                "the list of conditions is combined with 'or'",
+                "the aggregation '| count() by User > 10' is ignored, only the search before it is evaluated"
+            ]
+        );
+        assert!(rule.is_match(&Event::from([("Image", r"C:\Windows\whoami.exe")])));
+        assert!(rule.is_match(&Event::from([("CommandLine", "mimikatz")])));
+        assert!(crate::rule_from_yaml(RULE)
+            .unwrap()
+            .legacy_warnings()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_builder() {
+        let config = EngineConfig::builder()
+            .regex_max_input_len(8)
+            .wildcard_mode(WildcardMode::Dfa)
+            .spec_mode(SpecMode::Permissive)
+            .field_policy("EventID", FieldPolicy::Numeric)
+            .placeholder(PlaceholderList::new("admins", ["root"]))
+            .match_order(MatchOrder::Severity)
+            .evaluation_order(EvaluationOrder::Priority)
+            .match_limit(1)
+            .time_budget(Duration::from_secs(1))
+            .build();
+        assert_eq!(
+            config,
+            EngineConfig {
+                regex_max_input_len: Some(8),
+                wildcard_mode: WildcardMode::Dfa,
+                spec_mode: SpecMode::Permissive,
+                field_policies: [("EventID".to_string(), FieldPolicy::Numeric)].into(),
+                placeholders: config.placeholders.clone(),
+                match_order: MatchOrder::Severity,
+                evaluation_order: EvaluationOrder::Priority,
+                match_limit: Some(1),
+                time_budget: Some(Duration::from_secs(1)),
+                ..Default::default()
+            }
+        );
+        assert_eq!(config.placeholders.names().collect::<Vec<_>>(), ["admins"]);
+        assert_eq!(EngineConfig::builder().build(), EngineConfig::default());
+
+        let rule = |title: &str, level: &str| {
+            config
+                .rule_from_yaml(&format!(
+                    "title: {}\nlevel: {}\nlogsource:\ndetection:\n    selection:\n        EventID|Contains: 46\n    condition: selection",
+                    title, level
+                ))
+                .unwrap()
+        };
+        let rule_set = config.rule_set(vec![rule("low", "low"), rule("high", "high")]);
+        assert_eq!(rule_set.match_order(), MatchOrder::Severity);
+        assert_eq!(rule_set.evaluation_order(), EvaluationOrder::Priority);
+        assert_eq!(rule_set.match_limit(), Some(1));
+        assert_eq!(rule_set.time_budget(), Some(Duration::from_secs(1)));
+        let matches = rule_set.evaluate(&Event::from([("EventID", "4688")]));
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].rule_title, "high");
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn test_limits() {
+        let config = EngineConfig {
+            limits: ParserLimits {
+                max_values_per_field: 2,
+                max_selections: 2,
+                max_condition_depth: 2,
+                max_expanded_values: 3,
+            },
+            ..Default::default()
+        };
+        let rule =
+            |detection: &str| format!("title: Limits\nlogsource:\ndetection:\n{}", detection);
+        let error = |detection: &str| {
+            config
+                .rule_from_yaml(&rule(detection))
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(config
+            .rule_from_yaml(&rule(
+                "    a:\n        A: [1, 2]\n    b:\n        B: 3\n    condition: a and b\n"
+            ))
+            .is_ok());
+        assert!(error("    a:\n        A: [1, 2, 3]\n    condition: a\n")
+            .contains("The rule exceeds the limit of 2 values per field"));
+        assert!(error(
+            "    a:\n        A: 1\n    b:\n        B: 2\n    c:\n        C: 3\n    condition: a\n"
+        )
+        .contains("The rule exceeds the limit of 2 selections"));
+        assert!(error("    a:\n        A: 1\n    condition: a and not a\n")
+            .contains("The rule exceeds the limit of 2 nested levels of the condition"));
+        assert!(error(
+            "    a:\n        A|base64offset: abc\n    b:\n        B: 1\n    condition: a or b\n"
+        )
+        .contains("The rule exceeds the limit of 3 values after transformations"));
+        assert!(
+            error("    a:\n        A|windash: -a -b\n    condition: a\n")
+                .contains("The rule exceeds the limit of 3 values after transformations")
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Attach the events preceding a match to the match result.
+//!
+//! When evaluating a stream of events, the [`ContextWindow`] remembers the last `N` events
+//! of every entity (e.g. host or user, identified by configurable key fields). If a rule
+//! matches, these events are attached to the [`MatchResult`] so analysts get the context
+//! of the match without querying the log store again.
+//!
+//! The number of tracked entities can be capped with [`ContextWindow::with_max_entities`],
+//! so the memory use of the window does not grow with the length of the stream.
+use crate::event::Event;
+use crate::lru::Lru;
+use crate::result::MatchResult;
+use std::collections::VecDeque;
+
+/// Keeps the most recent events per entity to attach them as context to match results.
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::context::ContextWindow;
+/// use sigma_rust::{rule_from_yaml, Event};
+///
+/// let rule = rule_from_yaml(
+///     r#"
+/// title: Whoami
+/// logsource:
+///     category: process_creation
+/// detection:
+///     selection:
+///         Image|endswith: '\whoami.exe'
+///     condition: selection
+/// "#,
+/// )
+/// .unwrap();
+///
+/// let mut window = ContextWindow::new(["Computer"], 2);
+/// let events = [
+///     Event::from([("Computer", "ws-01"), ("Image", "C:\\explorer.exe")]),
+///     Event::from([("Computer", "ws-01"), ("Image", "C:\\cmd.exe")]),
+///     Event::from([("Computer", "ws-02"), ("Image", "C:\\notepad.exe")]),
+///     Event::from([("Computer", "ws-01"), ("Image", "C:\\whoami.exe")]),
+/// ];
+///
+/// let mut matches = vec![];
+/// for event in events.iter() {
+///     let mut results: Vec<_> = rule.evaluate(event).into_iter().collect();
+///     window.process(event, &mut results);
+///     matches.extend(results);
+/// }
+///
+/// assert_eq!(matches.len(), 1);
+/// assert_eq!(matches[0].context, events[..2].to_vec());
+/// ```
+#[derive(Debug)]
+pub struct ContextWindow {
+    key_fields: Vec<String>,
+    size: usize,
+    /// The events of each entity, the entity whose last event was recorded longest ago is
+    /// evicted first
+    entities: Lru<VecDeque<Event>>,
+}
+
+impl ContextWindow {
+    /// Create a new context window that keeps the last `size` events of each entity.
+    /// Entities are identified by the values of `key_fields`.
+    pub fn new<I, S>(key_fields: I, size: usize) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            key_fields: key_fields.into_iter().map(|s| s.into()).collect(),
+            size,
+            entities: Lru::default(),
+        }
+    }
+
+    /// Track at most `max_entities` entities. When a new entity would exceed the limit,
+    /// the entity whose last event was recorded longest ago is forgotten.
+    /// With a limit of 0, no events are recorded.
+    pub fn with_max_entities(mut self, max_entities: usize) -> Self {
+        self.entities.set_max(max_entities);
+        self
+    }
+
+    /// Get the events that preceded `event` for the same entity, oldest first
+    pub fn preceding(&self, event: &Event) -> Vec<Event> {
+        event
+            .entity_key(&self.key_fields)
+            .and_then(|key| self.entities.get(&key))
+            .map(|events| events.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Remember an event as context for future matches of the same entity
+    pub fn record(&mut self, event: &Event) {
+        if self.size == 0 {
+            return;
+        }
+        let Some(key) = event.entity_key(&self.key_fields) else {
+            return;
+        };
+
+        let size = self.size;
+        let Some(events) = self.entities.touch(&key, || VecDeque::with_capacity(size)) else {
+            return;
+        };
+        if events.len() == self.size {
+            events.pop_front();
+        }
+        events.push_back(event.clone());
+    }
+
+    /// Attach the preceding events of the entity to all `results` of `event`
+    /// and afterward record the event itself
+    pub fn process(&mut self, event: &Event, results: &mut [MatchResult]) {
+        if !results.is_empty() {
+            let context = self.preceding(event);
+            for result in results.iter_mut() {
+                result.context = context.clone();
+            }
+        }
+        self.record(event);
+    }
+
+    /// Forget all events of an entity
+    pub fn clear(&mut self, entity: &str) {
+        self.entities.remove(entity);
+    }
+
+    /// The number of entities currently tracked
+    pub fn entities(&self) -> usize {
+        self.entities.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_size() {
+        let mut window = ContextWindow::new(["host"], 2);
+        for i in 0..5 {
+            window.record(&Event::from([("host", "a"), ("n", &*i.to_string())]));
+        }
+        window.record(&Event::from([("host", "b"), ("n", "0")]));
+        window.record(&Event::from([("user", "c")]));
+
+        let preceding = window.preceding(&Event::from([("host", "a")]));
+        assert_eq!(
+            preceding,
+            vec![
+                Event::from([("host", "a"), ("n", "3")]),
+                Event::from([("host", "a"), ("n", "4")])
+            ]
+        );
+        assert_eq!(window.preceding(&Event::from([("host", "b")])).len(), 1);
+        assert!(window.preceding(&Event::from([("user", "c")])).is_empty());
+        assert_eq!(window.entities(), 2);
+
+        window.clear("a");
+        assert!(window.preceding(&Event::from([("host", "a")])).is_empty());
+    }
+
+    #[test]
+    fn test_max_entities() {
+        let mut window = ContextWindow::new(["host"], 1).with_max_entities(2);
+        window.record(&Event::from([("host", "a")]));
+        window.record(&Event::from([("host", "b")]));
+        window.record(&Event::from([("host", "a"), ("n", "1")]));
+        // b was seen longest ago and is evicted
+        window.record(&Event::from([("host", "c")]));
+
+        assert_eq!(window.entities(), 2);
+        assert!(window.preceding(&Event::from([("host", "b")])).is_empty());
+        assert_eq!(
+            window.preceding(&Event::from([("host", "a")])),
+            vec![Event::from([("host", "a"), ("n", "1")])]
+        );
+
+        window.clear("a");
+        window.record(&Event::from([("host", "d")]));
+        assert_eq!(window.entities(), 2);
+        assert_eq!(window.preceding(&Event::from([("host", "c")])).len(), 1);
+
+        let mut window = ContextWindow::new(["host"], 1).with_max_entities(0);
+        window.record(&Event::from([("host", "a")]));
+        assert_eq!(window.entities(), 0);
+    }
+
+    #[test]
+    fn test_zero_size() {
+        let mut window = ContextWindow::new(["host"], 0);
+        window.record(&Event::from([("host", "a")]));
+        assert_eq!(window.entities(), 0);
+    }
+}
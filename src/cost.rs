@@ -0,0 +1,224 @@
+//! Estimate how expensive compiled rules are to evaluate.
+//!
+//! [`Rule::cost`](crate::Rule::cost) counts what makes a rule slow to evaluate: the values of
+//! its fields after modifiers like `base64offset` and `windash` expanded them, its regular
+//! expressions and their operators, the stars of its wildcard patterns, and its keywords,
+//! which are matched against every value of an event. [`RuleCost::score`] weighs them into a
+//! single number, so rules can be split into those evaluated inline with the event stream and
+//! those deferred to batch evaluation.
+//!
+//! The estimate is static and relative: it ranks rules by their worst case and does not predict
+//! evaluation times. [`crate::RuleSet::evaluate_profiled`] measures the actual times.
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::rule_from_yaml;
+//!
+//! let rule = rule_from_yaml(
+//!     r#"
+//! title: Encoded command
+//! logsource:
+//! detection:
+//!     selection:
+//!         CommandLine|base64offset|contains: 'IEX'
+//!         Image|re: '.*\\(powershell|pwsh)\.exe$'
+//!     condition: selection
+//! "#,
+//! )
+//! .unwrap();
+//!
+//! let cost = rule.cost();
+//! assert_eq!((cost.fields, cost.values), (2, 4));
+//! assert_eq!((cost.regexes, cost.regex_complexity), (1, 3));
+//! assert!(cost.score() > 20);
+//! ```
+use crate::detection::Detection;
+use crate::field::{Field, FieldValue};
+use crate::selection::Selection;
+use crate::wildcard::WildcardToken;
+use serde::{Deserialize, Serialize};
+use std::iter::Sum;
+use std::ops::Add;
+
+/// The cost of a regular expression in addition to its operators
+const REGEX_WEIGHT: u64 = 10;
+/// The cost of a keyword, which is matched against all values of an event
+const KEYWORD_WEIGHT: u64 = 10;
+
+/// What makes a rule expensive to evaluate, see the [module documentation](self)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleCost {
+    /// The number of fields of all selections
+    pub fields: usize,
+    /// The number of compiled field values, including regular expressions and the values of
+    /// the placeholder lists of fields with the `expand` modifier
+    pub values: usize,
+    /// The number of regular expressions
+    pub regexes: usize,
+    /// The number of repetitions, alternations, character classes and dots of all regular
+    /// expressions, each of which can make matching backtrack or scan further
+    pub regex_complexity: usize,
+    /// The number of stars of all wildcard patterns, including the implicit ones of the
+    /// `contains`, `startswith` and `endswith` modifiers
+    pub wildcard_stars: usize,
+    /// The number of keywords of keyword selections
+    pub keywords: usize,
+}
+
+impl RuleCost {
+    /// A single number to rank rules by: every value and every wildcard star counts once,
+    /// every regular expression and keyword ten times, and every operator of a regular
+    /// expression once more
+    pub fn score(&self) -> u64 {
+        let count = |n: usize| n as u64;
+        count(self.values)
+            + count(self.wildcard_stars)
+            + REGEX_WEIGHT * count(self.regexes)
+            + count(self.regex_complexity)
+            + KEYWORD_WEIGHT * count(self.keywords)
+    }
+
+    pub(crate) fn of(detection: &Detection) -> Self {
+        let mut cost = Self::default();
+        for selection in detection.get_selections().values() {
+            match selection {
+                Selection::Keyword(keywords) => {
+                    cost.keywords += keywords.len();
+                    cost.wildcard_stars += keywords
+                        .iter()
+                        .map(|keyword| stars(&keyword.pattern))
+                        .sum::<usize>();
+                }
+                Selection::Field(_) => {
+                    for field in selection.fields() {
+                        cost.add_field(field);
+                    }
+                }
+            }
+        }
+        cost
+    }
+
+    fn add_field(&mut self, field: &Field) {
+        self.fields += 1;
+        let live = field.live.as_ref().and_then(|live| live.current());
+        let values = match &live {
+            Some(live) => &live.values,
+            None => &field.values,
+        };
+        self.values += values.len();
+        for value in values {
+            match value {
+                FieldValue::WildcardPattern(pattern) => self.wildcard_stars += stars(pattern),
+                FieldValue::Regex(regex) => {
+                    self.regexes += 1;
+                    self.regex_complexity += regex_complexity(regex.as_str());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Add for RuleCost {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            fields: self.fields + other.fields,
+            values: self.values + other.values,
+            regexes: self.regexes + other.regexes,
+            regex_complexity: self.regex_complexity + other.regex_complexity,
+            wildcard_stars: self.wildcard_stars + other.wildcard_stars,
+            keywords: self.keywords + other.keywords,
+        }
+    }
+}
+
+/// The total cost of many rules, e.g. of a rule set
+impl Sum for RuleCost {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+fn stars(tokens: &[WildcardToken]) -> usize {
+    tokens
+        .iter()
+        .filter(|token| matches!(token, WildcardToken::Star))
+        .count()
+}
+
+/// The number of operators of a regular expression that make matching it more expensive. This
+/// is an approximation that does not parse the expression, e.g. a dot in a character class is
+/// counted as well.
+fn regex_complexity(pattern: &str) -> usize {
+    let mut complexity = 0;
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '*' | '+' | '?' | '{' | '|' | '[' | '.' => complexity += 1,
+            _ => {}
+        }
+    }
+    complexity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    #[test]
+    fn test_cost() {
+        let rule = rule_from_yaml(
+            r#"
+title: Test
+logsource:
+detection:
+    selection:
+        Image|endswith:
+            - '\cmd.exe'
+            - '\powershell.exe'
+        CommandLine: '*-enc*bypass*'
+    keywords:
+        - 'mimikatz'
+        - 'sekurlsa'
+    regex:
+        User|re: '^adm(in)?[0-9]+\.local$'
+    condition: selection or keywords or regex
+"#,
+        )
+        .unwrap();
+        let cost = rule.cost();
+        assert_eq!(
+            cost,
+            RuleCost {
+                fields: 3,
+                values: 4,
+                regexes: 1,
+                regex_complexity: 3,
+                wildcard_stars: 5,
+                keywords: 2,
+            }
+        );
+        assert_eq!(cost.score(), 4 + 5 + 10 + 3 + 20);
+        assert_eq!(
+            [cost, RuleCost::default(), cost]
+                .into_iter()
+                .sum::<RuleCost>(),
+            cost + cost
+        );
+    }
+
+    #[test]
+    fn test_regex_complexity() {
+        assert_eq!(regex_complexity("abc"), 0);
+        assert_eq!(regex_complexity(r"\.exe$"), 0);
+        assert_eq!(regex_complexity(r"(a|b)+.*\\"), 4);
+        assert_eq!(regex_complexity("[a-z]{2,}"), 2);
+    }
+}
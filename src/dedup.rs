@@ -0,0 +1,279 @@
+//! Collapse repeated matches into a single finding.
+//!
+//! A rule that matches the same activity over and over, e.g. a scheduled task running every
+//! minute on the same host, raises the same alert many times. The [`Deduplicator`] is applied
+//! after matching and collapses the matches of a rule with the same values of the key fields
+//! into one [`Deduplicated`] finding per window, which counts the matches and records when the
+//! first and the last of them were seen.
+//!
+//! In contrast to a [`RateLimiter`](crate::ratelimit::RateLimiter), matches are held back until
+//! their window closes, so each finding is reported exactly once and with its final count.
+use crate::result::MatchResult;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// The matches of a rule with the same key that were collapsed by a [`Deduplicator`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deduplicated {
+    /// The first match of the rule and key, without context events
+    pub result: MatchResult,
+    /// The key fields and their values, see [`Deduplicator::with_key_field`]
+    pub key: Vec<(String, String)>,
+    /// The number of collapsed matches, including the first one
+    pub count: u64,
+    pub first_seen: SystemTime,
+    pub last_seen: SystemTime,
+}
+
+impl Deduplicated {
+    /// A JSON representation of the finding: the JSON of the first match with the key, the
+    /// count, and the timestamps in milliseconds since the Unix epoch
+    #[cfg(feature = "serde_json")]
+    pub fn to_json(&self) -> serde_json::Value {
+        let millis = |time: SystemTime| {
+            time.duration_since(SystemTime::UNIX_EPOCH)
+                .map(|duration| duration.as_millis() as u64)
+                .unwrap_or_default()
+        };
+        let mut json = self.result.to_json();
+        if let Some(object) = json.as_object_mut() {
+            object.insert(
+                "key".to_string(),
+                self.key
+                    .iter()
+                    .map(|(field, value)| (field.clone(), value.clone().into()))
+                    .collect::<serde_json::Map<_, _>>()
+                    .into(),
+            );
+            object.insert("count".to_string(), self.count.into());
+            object.insert("first_seen".to_string(), millis(self.first_seen).into());
+            object.insert("last_seen".to_string(), millis(self.last_seen).into());
+        }
+        json
+    }
+}
+
+/// Collapses the matches of every rule and key within a window into one finding.
+///
+/// The window of a key opens with its first match and closes `window` later. Matches are
+/// collected until then, and the finding is returned by [`Deduplicator::add`] when the next
+/// match of the key arrives, or by [`Deduplicator::expired`].
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::dedup::Deduplicator;
+/// use sigma_rust::{rule_from_yaml, Event};
+/// use std::time::{Duration, SystemTime};
+///
+/// let rule = rule_from_yaml(
+///     r#"
+/// title: Whoami
+/// logsource:
+/// fields:
+///     - Computer
+/// detection:
+///     selection:
+///         Image|endswith: '\whoami.exe'
+///     condition: selection
+/// "#,
+/// )
+/// .unwrap();
+/// let result = rule
+///     .evaluate(&Event::from([("Image", "C:\\whoami.exe"), ("Computer", "ws-01")]))
+///     .unwrap();
+///
+/// let mut dedup = Deduplicator::new(Duration::from_secs(300)).with_key_field("Computer");
+/// let start = SystemTime::now();
+/// for minute in 0..3 {
+///     assert!(dedup.add(&result, start + Duration::from_secs(minute * 60)).is_none());
+/// }
+///
+/// let findings = dedup.expired(start + Duration::from_secs(300));
+/// assert_eq!(findings.len(), 1);
+/// assert_eq!(findings[0].count, 3);
+/// assert_eq!(findings[0].last_seen, start + Duration::from_secs(120));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Deduplicator {
+    window: Duration,
+    key_fields: Vec<String>,
+    open: HashMap<DedupKey, Deduplicated>,
+}
+
+/// The rule key and the values of the key fields
+type DedupKey = (String, Vec<String>);
+
+impl Deduplicator {
+    /// Collapse the matches of each rule within `window`
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            key_fields: vec![],
+            open: HashMap::new(),
+        }
+    }
+
+    /// Collapse only the matches of a rule with the same value of `field`, e.g. per host. The
+    /// value is taken from [`MatchResult::fields`], so the field has to be listed in the
+    /// `fields` of the rules. May be called several times to key by a combination of fields.
+    pub fn with_key_field<S: Into<String>>(mut self, field: S) -> Self {
+        self.key_fields.push(field.into());
+        self
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Add a match seen at the time `now`. If the window of its rule and key has closed, the
+    /// finding of that window is returned and a new window is opened.
+    pub fn add(&mut self, result: &MatchResult, now: SystemTime) -> Option<Deduplicated> {
+        let rule = result.rule_id.as_deref().unwrap_or(&result.rule_title);
+        let values: Vec<String> = self
+            .key_fields
+            .iter()
+            .map(|field| {
+                result
+                    .fields
+                    .get(field)
+                    .map(|value| value.value_to_string())
+                    .unwrap_or_default()
+            })
+            .collect();
+        let key = (rule.to_string(), values);
+
+        let closed = match self.open.get_mut(&key) {
+            Some(finding) if !is_closed(self.window, finding, now) => {
+                finding.count += 1;
+                finding.last_seen = finding.last_seen.max(now);
+                return None;
+            }
+            Some(_) => self.open.remove(&key),
+            None => None,
+        };
+        let finding = Deduplicated {
+            result: MatchResult {
+                context: vec![],
+                ..result.clone()
+            },
+            key: self.key_fields.iter().cloned().zip(key.1.clone()).collect(),
+            count: 1,
+            first_seen: now,
+            last_seen: now,
+        };
+        self.open.insert(key, finding);
+        closed
+    }
+
+    /// Take the findings whose window has closed at the time `now`, oldest first
+    pub fn expired(&mut self, now: SystemTime) -> Vec<Deduplicated> {
+        let mut expired = vec![];
+        let window = self.window;
+        self.open.retain(|_, finding| {
+            if is_closed(window, finding, now) {
+                expired.push(finding.clone());
+                return false;
+            }
+            true
+        });
+        expired.sort_by_key(|finding| finding.first_seen);
+        expired
+    }
+
+    /// Take all findings, including those whose window is still open, e.g. before shutting
+    /// down. The findings are sorted oldest first.
+    pub fn drain(&mut self) -> Vec<Deduplicated> {
+        let mut findings: Vec<_> = self.open.drain().map(|(_, finding)| finding).collect();
+        findings.sort_by_key(|finding| finding.first_seen);
+        findings
+    }
+
+    /// The number of rules and keys with an open window
+    pub fn len(&self) -> usize {
+        self.open.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.open.is_empty()
+    }
+}
+
+fn is_closed(window: Duration, finding: &Deduplicated, now: SystemTime) -> bool {
+    now.duration_since(finding.first_seen).unwrap_or_default() >= window
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result::whoami_match;
+
+    #[test]
+    fn test_window() {
+        let mut dedup = Deduplicator::new(Duration::from_secs(10));
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        assert_eq!(dedup.add(&whoami_match("ws-01"), at(0)), None);
+        assert_eq!(dedup.add(&whoami_match("ws-02"), at(3)), None);
+        // Without key fields, the matches of a rule are collapsed regardless of their fields
+        assert_eq!(dedup.len(), 1);
+        assert!(dedup.expired(at(9)).is_empty());
+
+        let finding = dedup.add(&whoami_match("ws-01"), at(10)).unwrap();
+        assert_eq!(finding.count, 2);
+        assert_eq!((finding.first_seen, finding.last_seen), (at(0), at(3)));
+        assert_eq!(finding.result.rule_id.as_deref(), Some("1a2b"));
+        assert!(finding.key.is_empty());
+
+        // The match that closed the window opened a new one
+        let findings = dedup.drain();
+        assert_eq!(findings.len(), 1);
+        assert_eq!((findings[0].count, findings[0].first_seen), (1, at(10)));
+        assert!(dedup.is_empty());
+    }
+
+    #[test]
+    fn test_key_fields() {
+        let mut dedup = Deduplicator::new(Duration::from_secs(60)).with_key_field("Computer");
+        let start = SystemTime::UNIX_EPOCH;
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        for (host, secs) in [("ws-01", 0), ("ws-02", 1), ("ws-01", 2), ("ws-01", 30)] {
+            assert_eq!(dedup.add(&whoami_match(host), at(secs)), None);
+        }
+
+        let findings = dedup.expired(at(61));
+        assert_eq!(
+            findings
+                .iter()
+                .map(|finding| (finding.key[0].1.as_str(), finding.count))
+                .collect::<Vec<_>>(),
+            vec![("ws-01", 3), ("ws-02", 1)]
+        );
+        assert_eq!(findings[0].last_seen, at(30));
+        assert_eq!(
+            findings[0].key,
+            vec![("Computer".to_string(), "ws-01".to_string())]
+        );
+        assert!(dedup.is_empty());
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_json() {
+        let finding = Deduplicated {
+            result: whoami_match("ws-01"),
+            key: vec![("Computer".to_string(), "ws-01".to_string())],
+            count: 4,
+            first_seen: SystemTime::UNIX_EPOCH + Duration::from_secs(1),
+            last_seen: SystemTime::UNIX_EPOCH + Duration::from_millis(2500),
+        };
+        let json = finding.to_json();
+        assert_eq!(json["id"], "1a2b");
+        assert_eq!(json["key"], serde_json::json!({"Computer": "ws-01"}));
+        assert_eq!(json["count"], 4);
+        assert_eq!(
+            (&json["first_seen"], &json["last_seen"]),
+            (&1000.into(), &2500.into())
+        );
+    }
+}
@@ -1,23 +1,26 @@
+mod analysis;
 mod ast;
 mod lexer;
-
-use crate::detection::ast::Ast;
-use crate::error::ParserError;
+mod proxy;
+mod span;
+
+use crate::config::{self, Limit, SpecMode};
+pub(crate) use crate::detection::analysis::Finding;
+pub(crate) use crate::detection::ast::Ast;
+pub(crate) use crate::detection::span::{locate, locate_error, locate_rule_error};
+use crate::error::{ParserError, Span};
+use crate::evaluation::{EvalContext, SelectionId};
 use crate::event::Event;
+use crate::field::Field;
 use crate::selection::Selection;
-use crate::wildcard::match_tokenized;
-use serde::Deserialize;
+use crate::wildcard::{match_tokenized, WildcardToken};
+use proxy::DetectionProxy;
+use serde::{Deserialize, Serialize};
 use serde_yml::Value;
+use std::cmp::Reverse;
 use std::collections::HashMap;
 
-#[derive(Deserialize, Debug)]
-struct DetectionProxy {
-    #[serde(flatten)]
-    selections: HashMap<String, Value>,
-    condition: String,
-}
-
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(try_from = "DetectionProxy")]
 pub struct Detection {
     #[serde(flatten)]
@@ -25,12 +28,33 @@ pub struct Detection {
     condition: String,
     #[serde(skip)]
     ast: Ast,
+    #[serde(skip)]
+    legacy_warnings: Vec<String>,
+    /// The ids of the selections that other rules of the rule set contain as well
+    #[serde(skip)]
+    shared: HashMap<String, SelectionId>,
+}
+
+/// Detections are equal if they have equal selections and the same condition, regardless of
+/// the order in which the condition is evaluated
+impl PartialEq for Detection {
+    fn eq(&self, other: &Self) -> bool {
+        self.selections == other.selections && self.condition == other.condition
+    }
 }
 
 impl TryFrom<DetectionProxy> for Detection {
     type Error = ParserError;
 
     fn try_from(other: DetectionProxy) -> Result<Self, Self::Error> {
+        let limits = config::active_limits();
+        if other.selections.len() > limits.max_selections {
+            return Err(ParserError::LimitExceeded(
+                Limit::Selections,
+                limits.max_selections,
+            ));
+        }
+
         let mut selections = HashMap::with_capacity(other.selections.len());
         for (name, selection) in other.selections {
             match Selection::try_from(selection) {
@@ -47,20 +71,152 @@ impl TryFrom<DetectionProxy> for Detection {
                 }
             }
         }
-        let result = Self::new(selections, other.condition)?;
+        let values: usize = selections.values().map(Selection::value_count).sum();
+        if values > limits.max_expanded_values {
+            return Err(ParserError::LimitExceeded(
+                Limit::ExpandedValues,
+                limits.max_expanded_values,
+            ));
+        }
+
+        let (condition, legacy_warnings) = legacy_condition(other.conditions)?;
+        let mut result = Self::new(selections, condition)?;
+        result.legacy_warnings = legacy_warnings;
         Ok(result)
     }
 }
 
+/// Combine the conditions of a detection into one. The Sigma v1 constructs of a list of
+/// conditions, which matches if any of them matches, and of aggregations after a `|`, e.g.
+/// `selection | count() by User > 10`, are only accepted with [`SpecMode::Legacy`]. A list is
+/// combined with `or` and aggregations are dropped, so only their search is evaluated. Returns
+/// the condition with a warning for every rewritten construct.
+fn legacy_condition(conditions: Vec<String>) -> Result<(String, Vec<String>), ParserError> {
+    let legacy = config::active_spec_mode() == SpecMode::Legacy;
+    let mut warnings = vec![];
+    if conditions.len() != 1 {
+        if !legacy {
+            return Err(ParserError::LegacyCondition(
+                "a list of conditions".to_string(),
+            ));
+        }
+        warnings.push("the list of conditions is combined with 'or'".to_string());
+    }
+
+    let mut searches = Vec::with_capacity(conditions.len());
+    for condition in conditions {
+        let Some((search, aggregation)) = condition.split_once('|') else {
+            searches.push(condition);
+            continue;
+        };
+        let aggregation = format!("| {}", aggregation.trim());
+        if !legacy {
+            return Err(ParserError::LegacyCondition(aggregation));
+        }
+        warnings.push(format!(
+            "the aggregation '{}' is ignored, only the search before it is evaluated",
+            aggregation
+        ));
+        searches.push(search.trim().to_string());
+    }
+
+    let condition = match searches.as_slice() {
+        [condition] => condition.clone(),
+        _ => searches
+            .iter()
+            .map(|search| format!("({})", search))
+            .collect::<Vec<_>>()
+            .join(" or "),
+    };
+    Ok((condition, warnings))
+}
+
+/// Replace the selections of the detection of a rule that cannot be parsed with empty ones, which
+/// the parsed rule must disable with [`Detection::disable_selection`]. Returns the names of the
+/// replaced selections with their errors and positions in `yaml`. Nothing is replaced if the
+/// condition cannot be parsed or negates, as disabling a negated selection would make the rule
+/// match more instead of less.
+pub(crate) fn degrade_selections(
+    yaml: &str,
+    rule: &mut Value,
+) -> Vec<(String, Option<Span>, ParserError)> {
+    let Some(detection) = rule.get_mut("detection").and_then(Value::as_mapping_mut) else {
+        return vec![];
+    };
+    let negates = match detection.get("condition").and_then(Value::as_str) {
+        Some(condition) => Ast::new(condition).map_or(true, |ast| ast.negates()),
+        None => true,
+    };
+    if negates {
+        return vec![];
+    }
+
+    let mut result = vec![];
+    for (name, selection) in detection.iter_mut() {
+        let Some(name) = name.as_str().filter(|name| *name != "condition") else {
+            continue;
+        };
+        let parsed =
+            proxy::resolve_merge_keys(name, selection.clone()).and_then(Selection::try_from);
+        if let Err(error) = parsed {
+            let span = span::selection_span(yaml, name, selection);
+            result.push((name.to_string(), span, error));
+            *selection = Value::Mapping(Default::default());
+        }
+    }
+    result
+}
+
 impl Detection {
+    /// Parse a detection from the YAML values of its selections, like a detection of a rule
+    /// written in YAML
+    pub(crate) fn from_values(
+        selections: HashMap<String, Value>,
+        condition: String,
+    ) -> Result<Self, ParserError> {
+        Self::try_from(DetectionProxy {
+            selections,
+            conditions: vec![condition],
+        })
+    }
+
+    /// Replace a selection with one that never matches
+    pub(crate) fn disable_selection(&mut self, name: &str) {
+        if let Some(selection) = self.selections.get_mut(name) {
+            *selection = Selection::Field(vec![]);
+        }
+        self.shared.remove(name);
+    }
+
     pub fn get_selections(&self) -> &HashMap<String, Selection> {
         &self.selections
     }
 
+    /// Mark a selection as shared with other rules, so its result is evaluated once per event
+    pub(crate) fn share_selection(&mut self, name: &str, id: SelectionId) {
+        self.shared.insert(name.to_string(), id);
+    }
+
+    /// Forget the ids of shared selections, which are only valid within the rule set that
+    /// assigned them
+    pub(crate) fn unshare_selections(&mut self) {
+        self.shared.clear();
+    }
+
     pub fn get_condition(&self) -> &str {
         &self.condition
     }
 
+    /// Warnings about the Sigma v1 constructs of the condition that were rewritten with
+    /// [`SpecMode::Legacy`]
+    pub fn legacy_warnings(&self) -> &[String] {
+        &self.legacy_warnings
+    }
+
+    pub(crate) fn ast(&self) -> &Ast {
+        &self.ast
+    }
+
     pub(crate) fn new<S: AsRef<str>>(
         selections: HashMap<String, Selection>,
         condition: S,
@@ -69,6 +225,8 @@ impl Detection {
             selections,
             condition: condition.as_ref().into(),
             ast: Ast::default(),
+            legacy_warnings: vec![],
+            shared: HashMap::new(),
         };
         result.parse_ast()?;
         Ok(result)
@@ -93,20 +251,217 @@ impl Detection {
     }
 
     pub(crate) fn evaluate(&self, event: &Event) -> bool {
-        self.eval(event, &self.ast, &mut HashMap::new())
+        self.evaluate_in(&EvalContext::new(event))
     }
 
-    fn evaluate_selection(
+    pub(crate) fn evaluate_in(&self, ctx: &EvalContext) -> bool {
+        self.eval(ctx, &self.ast, &mut SelectionMemo::default())
+    }
+
+    /// Like [`Detection::evaluate_in`] but calls `observer` with the name and the result of
+    /// every selection that is evaluated
+    pub(crate) fn evaluate_observed(
         &self,
+        ctx: &EvalContext,
+        observer: &mut dyn FnMut(&str, bool),
+    ) -> bool {
+        let mut memo = SelectionMemo {
+            observer: Some(observer),
+            ..Default::default()
+        };
+        self.eval(ctx, &self.ast, &mut memo)
+    }
+
+    /// Reorder the operands of `and` and `or` in the condition, so the operand that most
+    /// likely decides the result is evaluated first. `match_rate` returns the observed
+    /// fraction of evaluations in which a selection matched.
+    pub(crate) fn reorder(&mut self, match_rate: &dyn Fn(&str) -> Option<f64>) {
+        Self::reorder_ast(&mut self.ast, match_rate);
+    }
+
+    /// Reorders the operands and returns the estimated probability that the node matches
+    fn reorder_ast(ast: &mut Ast, match_rate: &dyn Fn(&str) -> Option<f64>) -> f64 {
+        // Used for selections without statistics and quantifiers over several selections
+        const UNKNOWN: f64 = 0.5;
+
+        match ast {
+            Ast::Selection(s) => match_rate(s).unwrap_or(UNKNOWN),
+            Ast::Not(operand) => 1.0 - Self::reorder_ast(operand, match_rate),
+            Ast::And(left, right) => {
+                let (l, r) = (
+                    Self::reorder_ast(left, match_rate),
+                    Self::reorder_ast(right, match_rate),
+                );
+                // The operand that most likely fails short-circuits the evaluation
+                if r < l {
+                    std::mem::swap(left, right);
+                }
+                l * r
+            }
+            Ast::Or(left, right) => {
+                let (l, r) = (
+                    Self::reorder_ast(left, match_rate),
+                    Self::reorder_ast(right, match_rate),
+                );
+                // The operand that most likely matches short-circuits the evaluation
+                if r > l {
+                    std::mem::swap(left, right);
+                }
+                1.0 - (1.0 - l) * (1.0 - r)
+            }
+            Ast::OneOf(_) | Ast::OneOfThem | Ast::AllOf(_) | Ast::AllOfThem => UNKNOWN,
+        }
+    }
+
+    /// Returns the names of the selections that are not referenced by the condition, sorted
+    pub(crate) fn unused_selections(&self) -> Vec<&str> {
+        fn collect<'a>(ast: &'a Ast, patterns: &mut Vec<&'a [WildcardToken]>, all: &mut bool) {
+            match ast {
+                Ast::OneOf(p) | Ast::AllOf(p) => patterns.push(p),
+                Ast::OneOfThem | Ast::AllOfThem => *all = true,
+                Ast::Not(operand) => collect(operand, patterns, all),
+                Ast::And(left, right) | Ast::Or(left, right) => {
+                    collect(left, patterns, all);
+                    collect(right, patterns, all);
+                }
+                Ast::Selection(_) => {}
+            }
+        }
+
+        let (mut patterns, mut all) = (vec![], false);
+        collect(&self.ast, &mut patterns, &mut all);
+        if all {
+            return vec![];
+        }
+        let referenced = self.ast.selections();
+        let mut result: Vec<&str> = self
+            .selections
+            .keys()
+            .map(String::as_str)
+            .filter(|name| {
+                !referenced.contains(name)
+                    && !patterns.iter().any(|p| match_tokenized(p, name, false))
+            })
+            .collect();
+        result.sort_unstable();
+        result
+    }
+
+    /// Iterate over all fields of all selections
+    pub(crate) fn fields(&self) -> impl Iterator<Item = &Field> {
+        self.selections.values().flat_map(|s| s.fields())
+    }
+
+    /// Whether a selection matches keywords against all values of the event
+    pub(crate) fn has_keywords(&self) -> bool {
+        self.selections
+            .values()
+            .any(|s| matches!(s, Selection::Keyword(_)))
+    }
+
+    /// Iterate over all fields of all selections
+    pub(crate) fn fields_mut(&mut self) -> impl Iterator<Item = &mut Field> {
+        self.selections.values_mut().flat_map(|s| s.fields_mut())
+    }
+
+    /// Returns a list of lowercase literals of which at least one must be contained in a
+    /// value of the event if the detection matches. Returns `None` if the condition cannot
+    /// be narrowed down to literals, e.g. because it is negated.
+    pub(crate) fn required_literals(&self) -> Option<Vec<String>> {
+        // Prefer the literals whose shortest literal is the longest, as they are the most selective
+        self.requirements(&self.ast, &Selection::required_literals, &|literals| {
+            literals.iter().map(String::len).min().unwrap_or(0)
+        })
+    }
+
+    /// Returns a list of field names of which at least one must be present in the event
+    /// if the detection matches. Returns `None` if the condition cannot be narrowed down
+    /// to fields, e.g. because it is negated or uses keywords.
+    pub(crate) fn required_fields(&self) -> Option<Vec<String>> {
+        // Prefer fewer fields, as the rule is skipped if none of them is present
+        self.requirements(&self.ast, &Selection::required_fields, &|fields| {
+            Reverse(fields.len())
+        })
+    }
+
+    /// Derives a list of requirements of which at least one must be fulfilled if the
+    /// condition matches. If several lists qualify, the one with the highest rank is chosen.
+    fn requirements<K: Ord>(
+        &self,
+        ast: &Ast,
+        selection_requirements: &dyn Fn(&Selection) -> Option<Vec<String>>,
+        rank: &dyn Fn(&[String]) -> K,
+    ) -> Option<Vec<String>> {
+        let of_selection = |name: &str| selection_requirements(self.selections.get(name)?);
+        let any_of = |names: Vec<&String>| {
+            let mut result = vec![];
+            for name in names {
+                result.extend(of_selection(name)?);
+            }
+            Some(result)
+        };
+        let all_of = |names: Vec<&String>| {
+            names
+                .into_iter()
+                .filter_map(|name| of_selection(name))
+                .max_by_key(|requirements| rank(requirements))
+        };
+        let recurse = |ast: &Ast| self.requirements(ast, selection_requirements, rank);
+
+        match ast {
+            Ast::Selection(s) => of_selection(s),
+            Ast::OneOf(s) => any_of(
+                self.selections
+                    .keys()
+                    .filter(|name| match_tokenized(s, name, false))
+                    .collect(),
+            ),
+            Ast::OneOfThem => any_of(self.selections.keys().collect()),
+            Ast::AllOf(s) => all_of(
+                self.selections
+                    .keys()
+                    .filter(|name| match_tokenized(s, name, false))
+                    .collect(),
+            ),
+            Ast::AllOfThem => all_of(self.selections.keys().collect()),
+            Ast::Not(_) => None,
+            Ast::Or(left, right) => {
+                let mut result = recurse(left)?;
+                result.extend(recurse(right)?);
+                Some(result)
+            }
+            Ast::And(left, right) => match (recurse(left), recurse(right)) {
+                (Some(l), Some(r)) => {
+                    if rank(&r) > rank(&l) {
+                        Some(r)
+                    } else {
+                        Some(l)
+                    }
+                }
+                (l, r) => l.or(r),
+            },
+        }
+    }
+
+    fn evaluate_selection<'a>(
+        &'a self,
         name: &str,
-        lookup: &mut HashMap<String, bool>,
-        event: &Event,
+        lookup: &mut SelectionMemo<'a, '_>,
+        ctx: &EvalContext,
     ) -> bool {
         if let Some(e) = lookup.get(name) {
-            *e
-        } else if let Some(selection) = self.selections.get(name) {
-            let eval = selection.evaluate(event);
-            lookup.insert(name.to_string(), eval);
+            e
+        } else if let Some((name, selection)) = self.selections.get_key_value(name) {
+            let id = if self.shared.is_empty() {
+                None
+            } else {
+                self.shared.get(name).copied()
+            };
+            let eval = ctx.selection(id, || selection.evaluate_in(ctx));
+            lookup.insert(name, eval);
+            if let Some(observer) = lookup.observer.as_mut() {
+                observer(name, eval);
+            }
             eval
         } else {
             // should never happen because we check before evaluate
@@ -115,38 +470,76 @@ impl Detection {
         }
     }
 
-    fn eval(&self, event: &Event, ast: &Ast, lookup: &mut HashMap<String, bool>) -> bool {
+    fn eval<'a>(
+        &'a self,
+        ctx: &EvalContext,
+        ast: &Ast,
+        lookup: &mut SelectionMemo<'a, '_>,
+    ) -> bool {
         match ast {
-            Ast::Selection(s) => self.evaluate_selection(s, lookup, event),
+            Ast::Selection(s) => self.evaluate_selection(s, lookup, ctx),
             Ast::OneOf(s) => self
                 .selections
                 .keys()
                 .filter(|name| match_tokenized(s, name, false))
-                .any(|name| self.evaluate_selection(name, lookup, event)),
+                .any(|name| self.evaluate_selection(name, lookup, ctx)),
             Ast::OneOfThem => self
                 .selections
                 .keys()
-                .any(|name| self.evaluate_selection(name, lookup, event)),
+                .any(|name| self.evaluate_selection(name, lookup, ctx)),
             Ast::AllOf(s) => self
                 .selections
                 .keys()
                 .filter(|name| match_tokenized(s, name, false))
-                .all(|name| self.evaluate_selection(name, lookup, event)),
+                .all(|name| self.evaluate_selection(name, lookup, ctx)),
             Ast::AllOfThem => self
                 .selections
                 .keys()
-                .all(|name| self.evaluate_selection(name, lookup, event)),
-            Ast::Not(ref operand) => !self.eval(event, operand, lookup),
+                .all(|name| self.evaluate_selection(name, lookup, ctx)),
+            Ast::Not(ref operand) => !self.eval(ctx, operand, lookup),
             Ast::Or(ref left, ref right) => {
-                self.eval(event, left, lookup) || self.eval(event, right, lookup)
+                self.eval(ctx, left, lookup) || self.eval(ctx, right, lookup)
             }
             Ast::And(ref left, ref right) => {
-                self.eval(event, left, lookup) && self.eval(event, right, lookup)
+                self.eval(ctx, left, lookup) && self.eval(ctx, right, lookup)
             }
         }
     }
 }
 
+/// Called with the name and the result of every evaluated selection
+type SelectionObserver<'o> = &'o mut dyn FnMut(&str, bool);
+
+/// The number of selection results remembered while evaluating a condition
+const MEMO_SIZE: usize = 16;
+
+/// Remembers the results of evaluated selections without allocating. Conditions rarely
+/// reference more selections than fit into the memo; if it is full, further selections
+/// are evaluated again each time they are referenced.
+#[derive(Default)]
+struct SelectionMemo<'a, 'o> {
+    entries: [(&'a str, bool); MEMO_SIZE],
+    len: usize,
+    observer: Option<SelectionObserver<'o>>,
+}
+
+impl<'a> SelectionMemo<'a, '_> {
+    fn get(&self, name: &str) -> Option<bool> {
+        self.entries
+            .iter()
+            .take(self.len)
+            .find(|(n, _)| *n == name)
+            .map(|(_, result)| *result)
+    }
+
+    fn insert(&mut self, name: &'a str, result: bool) {
+        if let Some(entry) = self.entries.get_mut(self.len) {
+            *entry = (name, result);
+            self.len += 1;
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -157,6 +550,87 @@ mod tests {
         assert!(matches!(err, ParserError::UndefinedIdentifiers(_)));
     }
 
+    #[test]
+    fn test_required_literals() {
+        let detection_yaml = r#"
+    selection_1:
+        Image|endswith: '\cmd.exe'
+    selection_2:
+        CommandLine|contains: 'whoami'
+    filter:
+        User: admin
+    condition: selection_1 and not filter
+"#;
+        let detection: Detection = serde_yml::from_str(detection_yaml).unwrap();
+        assert_eq!(
+            detection.required_literals(),
+            Some(vec!["\\cmd.exe".to_string()])
+        );
+
+        let detection = Detection::new(detection.selections, "1 of selection_*").unwrap();
+        let mut literals = detection.required_literals().unwrap();
+        literals.sort();
+        assert_eq!(
+            literals,
+            vec!["\\cmd.exe".to_string(), "whoami".to_string()]
+        );
+
+        let detection = Detection::new(detection.selections, "all of selection_*").unwrap();
+        assert_eq!(
+            detection.required_literals(),
+            Some(vec!["\\cmd.exe".to_string()])
+        );
+
+        let detection = Detection::new(detection.selections, "selection_1 or not filter").unwrap();
+        assert_eq!(detection.required_literals(), None);
+
+        let detection = Detection::new(detection.selections, "all of nothing*").unwrap();
+        assert_eq!(detection.required_literals(), None);
+
+        let detection = Detection::new(detection.selections, "1 of nothing*").unwrap();
+        assert_eq!(detection.required_literals(), Some(vec![]));
+    }
+
+    #[test]
+    fn test_required_fields() {
+        let detection_yaml = r#"
+    selection_1:
+        Image|endswith: '\cmd.exe'
+        CommandLine|contains: 'whoami'
+    selection_2:
+        - ParentImage: 'explorer.exe'
+        - User: admin
+    filter:
+        Hashes|exists: false
+    keywords:
+        - evil
+    condition: selection_1 and selection_2
+"#;
+        let detection: Detection = serde_yml::from_str(detection_yaml).unwrap();
+        assert_eq!(detection.required_fields().map(|f| f.len()), Some(1));
+
+        let detection = Detection::new(detection.selections, "selection_2").unwrap();
+        assert_eq!(
+            detection.required_fields(),
+            Some(vec!["ParentImage".to_string(), "User".to_string()])
+        );
+
+        let detection = Detection::new(detection.selections, "selection_2 and filter").unwrap();
+        assert_eq!(
+            detection.required_fields(),
+            Some(vec!["ParentImage".to_string(), "User".to_string()])
+        );
+
+        let detection = Detection::new(detection.selections, "filter").unwrap();
+        assert_eq!(detection.required_fields(), None);
+
+        let detection = Detection::new(detection.selections, "selection_1 or keywords").unwrap();
+        assert_eq!(detection.required_fields(), None);
+
+        let detection = Detection::new(detection.selections, "not selection_1").unwrap();
+        assert_eq!(detection.required_fields(), None);
+    }
+
     #[test]
     fn test_evaluate() {
         let detection_yaml = r#"
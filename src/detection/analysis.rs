@@ -0,0 +1,377 @@
+use super::{Ast, Detection};
+use crate::selection::Selection;
+use crate::wildcard::{match_tokenized, WildcardToken};
+use std::collections::HashMap;
+
+/// Conditions over more selections are not checked for being unsatisfiable or redundant, as
+/// every combination of matching selections is evaluated
+const MAX_SELECTIONS: usize = 12;
+
+/// A problem of the condition of a detection that does not prevent its evaluation
+#[derive(Debug, PartialEq)]
+pub(crate) enum Finding<'a> {
+    /// A quantifier whose pattern matches no selection, e.g. `1 of sel_*` without a selection
+    /// starting with `sel_`
+    EmptyQuantifier(String),
+    /// A field that can never match, see [`crate::field::Field::is_contradictory`]
+    ContradictoryField { selection: &'a str, field: &'a str },
+    /// The condition can never match, e.g. `selection and not selection`
+    Unsatisfiable,
+    /// An operand of `and` or `or` that never changes the result of the condition, e.g. one
+    /// `selection` of `selection or selection`
+    Redundant(String),
+}
+
+impl Detection {
+    /// Find conditions that can never match or contain operands that do not change the result.
+    ///
+    /// Selections are treated as independent of each other, except for selections that can
+    /// never match because of a contradictory field. Every combination of matching selections
+    /// is evaluated, so conditions over more than a few selections are only checked for empty
+    /// quantifiers and contradictory fields.
+    pub(crate) fn analyze(&self) -> Vec<Finding<'_>> {
+        let mut findings = vec![];
+        self.empty_quantifiers(&self.ast, &mut findings);
+
+        let mut never_matching = vec![];
+        let mut selections: Vec<_> = self.selections.iter().collect();
+        selections.sort_unstable_by_key(|(name, _)| *name);
+        for (name, selection) in selections {
+            let Selection::Field(groups) = selection else {
+                continue;
+            };
+            let mut dead_groups = 0;
+            for group in groups {
+                let contradictory: Vec<_> = group
+                    .fields
+                    .iter()
+                    .filter(|field| field.is_contradictory())
+                    .collect();
+                for field in &contradictory {
+                    findings.push(Finding::ContradictoryField {
+                        selection: name,
+                        field: &field.name,
+                    });
+                }
+                if !contradictory.is_empty() {
+                    dead_groups += 1;
+                }
+            }
+            if dead_groups == groups.len() {
+                never_matching.push(name.as_str());
+            }
+        }
+
+        let Some(truth_table) = TruthTable::new(self, &never_matching) else {
+            return findings;
+        };
+        if !truth_table.is_satisfiable(&self.ast) {
+            findings.push(Finding::Unsatisfiable);
+            return findings;
+        }
+        let expected = truth_table.evaluate_all(&self.ast, &HashMap::new());
+        truth_table.redundant_operands(
+            &self.ast,
+            &mut 0,
+            &expected,
+            &mut HashMap::new(),
+            &mut findings,
+        );
+        findings
+    }
+
+    fn empty_quantifiers(&self, ast: &Ast, findings: &mut Vec<Finding>) {
+        match ast {
+            Ast::OneOf(pattern) | Ast::AllOf(pattern) => {
+                if !self
+                    .selections
+                    .keys()
+                    .any(|name| match_tokenized(pattern, name, false))
+                {
+                    findings.push(Finding::EmptyQuantifier(source(ast)));
+                }
+            }
+            Ast::Not(operand) => self.empty_quantifiers(operand, findings),
+            Ast::And(left, right) | Ast::Or(left, right) => {
+                self.empty_quantifiers(left, findings);
+                self.empty_quantifiers(right, findings);
+            }
+            Ast::Selection(_) | Ast::OneOfThem | Ast::AllOfThem => {}
+        }
+    }
+}
+
+/// Evaluates a condition for every combination of matching selections
+struct TruthTable<'a> {
+    detection: &'a Detection,
+    /// The selections that may match, the bit of a selection in an assignment is its index
+    variables: HashMap<&'a str, usize>,
+}
+
+impl<'a> TruthTable<'a> {
+    fn new(detection: &'a Detection, never_matching: &[&str]) -> Option<Self> {
+        let mut names: Vec<&str> = detection
+            .selections
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !never_matching.contains(name))
+            .collect();
+        if names.len() > MAX_SELECTIONS {
+            return None;
+        }
+        names.sort_unstable();
+        Some(Self {
+            detection,
+            variables: names.into_iter().enumerate().map(|(i, n)| (n, i)).collect(),
+        })
+    }
+
+    fn is_satisfiable(&self, ast: &Ast) -> bool {
+        self.evaluate_all(ast, &HashMap::new()).contains(&true)
+    }
+
+    /// Evaluate the condition for every assignment of the selections. Nodes are numbered in
+    /// pre-order, the nodes in `replaced` evaluate to the given constant.
+    fn evaluate_all(&self, ast: &Ast, replaced: &HashMap<usize, bool>) -> Vec<bool> {
+        (0..1u32 << self.variables.len())
+            .map(|assignment| self.evaluate(ast, assignment, replaced, &mut 0))
+            .collect()
+    }
+
+    fn evaluate(
+        &self,
+        ast: &Ast,
+        assignment: u32,
+        replaced: &HashMap<usize, bool>,
+        id: &mut usize,
+    ) -> bool {
+        let node = *id;
+        *id += 1;
+        let selection = |name: &str| {
+            self.variables
+                .get(name)
+                .is_some_and(|bit| assignment & (1 << bit) != 0)
+        };
+        // Whether each of the selections a quantifier expands to matches
+        let matching = |pattern: Option<&[WildcardToken]>| -> Vec<bool> {
+            self.detection
+                .selections
+                .keys()
+                .filter(|name| pattern.map_or(true, |p| match_tokenized(p, name, false)))
+                .map(|name| selection(name))
+                .collect()
+        };
+        // Operands are always evaluated, so the numbers of the nodes stay the same
+        let result = match ast {
+            Ast::Selection(name) => selection(name),
+            Ast::OneOf(pattern) => matching(Some(pattern)).contains(&true),
+            Ast::OneOfThem => matching(None).contains(&true),
+            Ast::AllOf(pattern) => !matching(Some(pattern)).contains(&false),
+            Ast::AllOfThem => !matching(None).contains(&false),
+            Ast::Not(operand) => !self.evaluate(operand, assignment, replaced, id),
+            Ast::And(left, right) => {
+                let left = self.evaluate(left, assignment, replaced, id);
+                self.evaluate(right, assignment, replaced, id) && left
+            }
+            Ast::Or(left, right) => {
+                let left = self.evaluate(left, assignment, replaced, id);
+                self.evaluate(right, assignment, replaced, id) || left
+            }
+        };
+        replaced.get(&node).copied().unwrap_or(result)
+    }
+
+    /// Report the outermost operands of `and` and `or` that can be replaced by their neutral
+    /// element without changing the result of the condition. Redundant operands stay replaced,
+    /// so of two equal operands only the first is reported.
+    fn redundant_operands(
+        &self,
+        ast: &Ast,
+        id: &mut usize,
+        expected: &[bool],
+        replaced: &mut HashMap<usize, bool>,
+        findings: &mut Vec<Finding>,
+    ) {
+        *id += 1;
+        match ast {
+            Ast::Not(operand) => self.redundant_operands(operand, id, expected, replaced, findings),
+            Ast::And(left, right) | Ast::Or(left, right) => {
+                let neutral = matches!(ast, Ast::And(..));
+                for operand in [left, right] {
+                    replaced.insert(*id, neutral);
+                    if self.evaluate_all(&self.detection.ast, replaced) == expected {
+                        findings.push(Finding::Redundant(source(operand)));
+                        *id += size(operand);
+                    } else {
+                        replaced.remove(id);
+                        self.redundant_operands(operand, id, expected, replaced, findings);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The number of nodes of a condition
+fn size(ast: &Ast) -> usize {
+    match ast {
+        Ast::Not(operand) => 1 + size(operand),
+        Ast::And(left, right) | Ast::Or(left, right) => 1 + size(left) + size(right),
+        _ => 1,
+    }
+}
+
+/// The condition as it would be written in a rule
+fn source(ast: &Ast) -> String {
+    let pattern = |tokens: &[WildcardToken]| -> String {
+        tokens
+            .iter()
+            .map(|token| match token {
+                WildcardToken::Star => "*".to_string(),
+                WildcardToken::QuestionMark => "?".to_string(),
+                WildcardToken::Pattern(chars) => chars.iter().collect(),
+            })
+            .collect()
+    };
+    let operand = |ast: &Ast| match ast {
+        Ast::And(..) | Ast::Or(..) => format!("({})", source(ast)),
+        _ => source(ast),
+    };
+    match ast {
+        Ast::Selection(name) => name.clone(),
+        Ast::OneOf(tokens) => format!("1 of {}", pattern(tokens)),
+        Ast::OneOfThem => "1 of them".to_string(),
+        Ast::AllOf(tokens) => format!("all of {}", pattern(tokens)),
+        Ast::AllOfThem => "all of them".to_string(),
+        Ast::Not(inner) => format!("not {}", operand(inner)),
+        Ast::And(left, right) => format!("{} and {}", operand(left), operand(right)),
+        Ast::Or(left, right) => format!("{} or {}", operand(left), operand(right)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    fn analyze(condition: &str, selections: &str) -> Vec<String> {
+        let yaml = format!(
+            "title: Test\nlogsource:\ndetection:\n{}    condition: {}\n",
+            selections, condition
+        );
+        let rule = rule_from_yaml(&yaml).unwrap();
+        rule.detection
+            .analyze()
+            .into_iter()
+            .map(|finding| format!("{:?}", finding))
+            .collect()
+    }
+
+    const SELECTIONS: &str =
+        "    sel_a:\n        A: 1\n    sel_b:\n        B: 1\n    filter:\n        C: 1\n";
+
+    #[test]
+    fn test_satisfiable() {
+        for condition in [
+            "sel_a",
+            "sel_a and not filter",
+            "1 of sel_* and not filter",
+            "all of them",
+            "not (sel_a and sel_b)",
+        ] {
+            assert_eq!(analyze(condition, SELECTIONS), Vec::<String>::new());
+        }
+    }
+
+    #[test]
+    fn test_unsatisfiable() {
+        for condition in [
+            "sel_a and not sel_a",
+            "all of sel_* and not sel_b",
+            "(sel_a or sel_b) and not 1 of sel_*",
+            "1 of them and not (sel_a or sel_b or filter)",
+        ] {
+            assert_eq!(
+                analyze(condition, SELECTIONS),
+                ["Unsatisfiable"],
+                "{}",
+                condition
+            );
+        }
+    }
+
+    #[test]
+    fn test_empty_quantifier() {
+        assert_eq!(
+            analyze("sel_a or 1 of other_*", SELECTIONS),
+            [
+                "EmptyQuantifier(\"1 of other_*\")",
+                "Redundant(\"1 of other_*\")"
+            ]
+        );
+        assert_eq!(
+            analyze("sel_a and 1 of other_*", SELECTIONS),
+            ["EmptyQuantifier(\"1 of other_*\")", "Unsatisfiable"]
+        );
+    }
+
+    #[test]
+    fn test_contradictory_field() {
+        let selections =
+            "    selection:\n        Image|all:\n            - a.exe\n            - b.exe\n";
+        assert_eq!(
+            analyze("selection", selections),
+            [
+                "ContradictoryField { selection: \"selection\", field: \"Image\" }",
+                "Unsatisfiable"
+            ]
+        );
+
+        // Values that differ only in case are the same value for case-insensitive fields
+        for selections in [
+            "    selection:\n        Image|all:\n            - a.exe\n            - A.EXE\n",
+            "    selection:\n        Image|contains|all:\n            - a\n            - b\n",
+            "    selection:\n        Image|all:\n            - a*\n            - b*\n",
+        ] {
+            assert_eq!(analyze("selection", selections), Vec::<String>::new());
+        }
+
+        // A selection with another working group can still match
+        let selections = "    selection:\n        - Image|all:\n            - a.exe\n            - b.exe\n        - Image: c.exe\n";
+        assert_eq!(
+            analyze("selection", selections),
+            ["ContradictoryField { selection: \"selection\", field: \"Image\" }"]
+        );
+    }
+
+    #[test]
+    fn test_redundant() {
+        assert_eq!(
+            analyze("sel_a or sel_a", SELECTIONS),
+            ["Redundant(\"sel_a\")"]
+        );
+        assert_eq!(
+            analyze("sel_a or (sel_a and sel_b)", SELECTIONS),
+            ["Redundant(\"sel_a and sel_b\")"]
+        );
+        assert_eq!(
+            analyze(
+                "1 of sel_* and not filter or sel_b and not filter",
+                SELECTIONS
+            ),
+            ["Redundant(\"sel_b and not filter\")"]
+        );
+    }
+
+    #[test]
+    fn test_too_many_selections() {
+        let selections: String = (0..=MAX_SELECTIONS)
+            .map(|i| format!("    sel_{}:\n        A: {}\n", i, i))
+            .collect();
+        assert_eq!(
+            analyze("sel_0 and not sel_0", &selections),
+            Vec::<String>::new()
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::config::{self, Limit};
 use crate::detection::lexer::{Lexer, Token};
 use crate::error::ParserError;
 use crate::wildcard::WildcardToken;
@@ -30,7 +31,7 @@ impl InfixOperator {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) enum Ast {
     Selection(String),
     OneOf(Vec<WildcardToken>),
@@ -51,7 +52,8 @@ impl Default for Ast {
 impl Ast {
     pub(crate) fn new(input: &str) -> Result<Self, ParserError> {
         let mut lexer = Lexer::new(input);
-        Self::parse_token_stream(&mut lexer, 0)
+        let max_depth = config::active_limits().max_condition_depth;
+        Ok(Self::parse_token_stream(&mut lexer, 0, 1, max_depth)?.0)
     }
 
     fn tokenize_selection_string(selection: String) -> Vec<WildcardToken> {
@@ -77,26 +79,47 @@ impl Ast {
         result
     }
 
-    fn parse_token_stream(lexer: &mut Lexer, min_binding_power: u8) -> Result<Self, ParserError> {
-        let mut left = match lexer.next() {
-            Token::Selection(s) => Self::Selection(s),
-            Token::OneOf(s) => Self::OneOf(Self::tokenize_selection_string(s)),
-            Token::OneOfThem => Self::OneOfThem,
-            Token::AllOf(s) => Self::AllOf(Self::tokenize_selection_string(s)),
-            Token::AllOfThem => Self::AllOfThem,
+    /// Parse the condition at nesting level `level` and return it with its depth. Both the
+    /// levels of the parser and the depth of the tree are limited by `max_depth`, so neither
+    /// parsing nor evaluating the condition can overflow the stack.
+    fn parse_token_stream(
+        lexer: &mut Lexer,
+        min_binding_power: u8,
+        level: usize,
+        max_depth: usize,
+    ) -> Result<(Self, usize), ParserError> {
+        let exceeded = || ParserError::LimitExceeded(Limit::ConditionDepth, max_depth);
+        if level > max_depth {
+            return Err(exceeded());
+        }
+
+        let (mut left, mut depth) = match lexer.next() {
+            Token::Selection(s) => (Self::Selection(s), 1),
+            Token::OneOf(s) => (Self::OneOf(Self::tokenize_selection_string(s)), 1),
+            Token::OneOfThem => (Self::OneOfThem, 1),
+            Token::AllOf(s) => (Self::AllOf(Self::tokenize_selection_string(s)), 1),
+            Token::AllOfThem => (Self::AllOfThem, 1),
             Token::OpeningParenthesis => {
-                let left = Self::parse_token_stream(lexer, 0)?;
+                let left = Self::parse_token_stream(lexer, 0, level + 1, max_depth)?;
                 if lexer.next() != Token::ClosingParenthesis {
                     return Err(ParserError::MissingClosingParenthesis());
                 }
                 left
             }
             Token::Not => {
-                let right = Self::parse_token_stream(lexer, PrefixOperator::Not.binding_power())?;
-                Self::Not(Box::new(right))
+                let (right, depth) = Self::parse_token_stream(
+                    lexer,
+                    PrefixOperator::Not.binding_power(),
+                    level + 1,
+                    max_depth,
+                )?;
+                (Self::Not(Box::new(right)), depth + 1)
             }
             t => return Err(ParserError::UnexpectedToken(t.to_string())),
         };
+        if depth > max_depth {
+            return Err(exceeded());
+        }
 
         loop {
             let operator = match lexer.peek() {
@@ -112,16 +135,19 @@ impl Ast {
             }
             lexer.next();
 
-            left = {
-                let right = Self::parse_token_stream(lexer, bp)?;
-                match operator {
-                    InfixOperator::And => Self::And(Box::new(left), Box::new(right)),
-                    InfixOperator::Or => Self::Or(Box::new(left), Box::new(right)),
-                }
+            let (right, right_depth) = Self::parse_token_stream(lexer, bp, level + 1, max_depth)?;
+            left = match operator {
+                InfixOperator::And => Self::And(Box::new(left), Box::new(right)),
+                InfixOperator::Or => Self::Or(Box::new(left), Box::new(right)),
             };
+            depth = depth.max(right_depth) + 1;
+            // Checked for every operator, as long chains of them nest to the left
+            if depth > max_depth {
+                return Err(exceeded());
+            }
         }
 
-        Ok(left)
+        Ok((left, depth))
     }
 
     pub(crate) fn selections(&self) -> HashSet<&str> {
@@ -130,6 +156,15 @@ impl Ast {
         result
     }
 
+    /// Whether the condition contains a `not`
+    pub(crate) fn negates(&self) -> bool {
+        match self {
+            Self::Not(_) => true,
+            Self::Or(left, right) | Self::And(left, right) => left.negates() || right.negates(),
+            _ => false,
+        }
+    }
+
     fn selections_recursive<'a>(current: &'a Self, acc: &mut HashSet<&'a str>) {
         match current {
             Self::Selection(s) => _ = acc.insert(s),
@@ -229,4 +264,21 @@ mod tests {
             Ast::new(" write TargetLogonId from selection1 (if not selection2) ").unwrap_err();
         assert!(matches!(err, ParserError::InvalidOperator(ref a) if a == "TargetLogonId"));
     }
+
+    #[test]
+    fn test_depth_limit() {
+        let max_depth = crate::config::ParserLimits::default().max_condition_depth;
+        let nested = |n: usize| format!("{}x{}", "(".repeat(n), ")".repeat(n));
+        assert!(Ast::new(&nested(max_depth - 1)).is_ok());
+        let err = Ast::new(&nested(100_000)).unwrap_err();
+        assert!(matches!(
+            err,
+            ParserError::LimitExceeded(Limit::ConditionDepth, depth) if depth == max_depth
+        ));
+
+        let chain = vec!["x"; max_depth + 1].join(" or ");
+        assert!(Ast::new(&vec!["x"; max_depth].join(" or ")).is_ok());
+        assert!(Ast::new(&chain).is_err());
+        assert!(Ast::new(&format!("{}x", "not ".repeat(100_000))).is_err());
+    }
 }
@@ -0,0 +1,408 @@
+use crate::error::ParserError;
+use serde::de::{self, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_yml::value::{Tag, TaggedValue};
+use serde_yml::{Mapping, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+/// The detection of a rule as written, before its selections and condition are parsed.
+///
+/// YAML allows duplicate keys and serde would silently keep the last selection of a name,
+/// so selections and mappings within selections are checked for duplicate keys, which almost
+/// always indicate a broken rule.
+#[derive(Debug)]
+pub(super) struct DetectionProxy {
+    pub(super) selections: HashMap<String, Value>,
+    /// A single condition, or the list of conditions of a legacy Sigma v1 rule
+    pub(super) conditions: Vec<String>,
+}
+
+impl<'de> Deserialize<'de> for DetectionProxy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(DetectionVisitor)
+    }
+}
+
+struct DetectionVisitor;
+
+impl<'de> Visitor<'de> for DetectionVisitor {
+    type Value = DetectionProxy;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a mapping of selections and a condition")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut selections = HashMap::new();
+        let mut conditions = None;
+        while let Some(name) = map.next_key::<String>()? {
+            if name == "condition" {
+                if conditions.is_some() {
+                    return Err(de::Error::duplicate_field("condition"));
+                }
+                conditions = Some(map.next_value::<Conditions>()?.0);
+                continue;
+            }
+            let selection = map.next_value_seed(UniqueKeys {
+                selection: name.as_str(),
+            })?;
+            if selections.insert(name.clone(), selection).is_some() {
+                return Err(de::Error::custom(ParserError::DuplicateSelection(name)));
+            }
+        }
+        Ok(DetectionProxy {
+            selections,
+            conditions: conditions.ok_or_else(|| de::Error::missing_field("condition"))?,
+        })
+    }
+}
+
+/// The value of the `condition` key, either a string or a list of strings
+struct Conditions(Vec<String>);
+
+impl<'de> Deserialize<'de> for Conditions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ConditionsVisitor)
+    }
+}
+
+struct ConditionsVisitor;
+
+impl<'de> Visitor<'de> for ConditionsVisitor {
+    type Value = Conditions;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a condition or a list of conditions")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Conditions, E> {
+        Ok(Conditions(vec![v.to_string()]))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Conditions, E> {
+        Ok(Conditions(vec![v]))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Conditions, A::Error> {
+        let mut result = vec![];
+        while let Some(condition) = seq.next_element()? {
+            result.push(condition);
+        }
+        Ok(Conditions(result))
+    }
+}
+
+/// Deserializes a YAML value like [`Value`], but fails on duplicate keys of mappings with an
+/// error that names the selection
+#[derive(Clone, Copy)]
+struct UniqueKeys<'a> {
+    selection: &'a str,
+}
+
+impl<'de> DeserializeSeed<'de> for UniqueKeys<'_> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for UniqueKeys<'_> {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any YAML value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut result = vec![];
+        while let Some(value) = seq.next_element_seed(self)? {
+            result.push(value);
+        }
+        Ok(Value::Sequence(result))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut result = Mapping::new();
+        while let Some(key) = map.next_key_seed(self)? {
+            if result.contains_key(&key) {
+                let key = match key {
+                    Value::String(key) => key,
+                    key => format!("{:?}", key),
+                };
+                return Err(de::Error::custom(ParserError::DuplicateField(
+                    self.selection.to_string(),
+                    key,
+                )));
+            }
+            let value = map.next_value_seed(self)?;
+            result.insert(key, value);
+        }
+        // Aliases are deserialized through this visitor as well, so mappings merged into this
+        // one have their own merge keys resolved already
+        merge(self.selection, result)
+            .map(Value::Mapping)
+            .map_err(de::Error::custom)
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Value, A::Error> {
+        let (tag, contents) = data.variant::<String>()?;
+        let value = contents.newtype_variant_seed(self)?;
+        Ok(Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new(tag),
+            value,
+        })))
+    }
+}
+
+/// Resolve the merge keys of a selection that was not parsed by [`DetectionProxy`], e.g. to
+/// parse the selections of a rule one by one
+pub(super) fn resolve_merge_keys(selection: &str, value: Value) -> Result<Value, ParserError> {
+    match value {
+        Value::Mapping(mapping) => {
+            let mapping = mapping
+                .into_iter()
+                .map(|(key, value)| Ok((key, resolve_merge_keys(selection, value)?)))
+                .collect::<Result<_, ParserError>>()?;
+            merge(selection, mapping).map(Value::Mapping)
+        }
+        Value::Sequence(values) => values
+            .into_iter()
+            .map(|value| resolve_merge_keys(selection, value))
+            .collect::<Result<_, _>>()
+            .map(Value::Sequence),
+        value => Ok(value),
+    }
+}
+
+/// Resolve the YAML merge key `<<` of a mapping, which serde_yml keeps as a plain key.
+///
+/// Keys of the mapping itself override merged keys, and of a list of merged mappings the
+/// earlier ones take precedence, as the YAML merge key specification requires. Merged keys
+/// come first, where the merge key is usually written.
+fn merge(selection: &str, mut mapping: Mapping) -> Result<Mapping, ParserError> {
+    let invalid = || ParserError::InvalidMergeKey(selection.to_string());
+    let sources = match mapping.shift_remove("<<") {
+        None => return Ok(mapping),
+        Some(Value::Mapping(source)) => vec![source],
+        Some(Value::Sequence(sources)) => sources
+            .into_iter()
+            .map(|source| match source {
+                Value::Mapping(source) => Ok(source),
+                _ => Err(invalid()),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(invalid()),
+    };
+    let mut result = Mapping::new();
+    for (key, value) in sources.into_iter().flatten() {
+        if !mapping.contains_key(&key) && !result.contains_key(&key) {
+            result.insert(key, value);
+        }
+    }
+    result.extend(mapping);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxy(yaml: &str) -> Result<DetectionProxy, String> {
+        serde_yml::from_str(yaml).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn test_values() {
+        let detection = proxy(
+            r#"
+selection:
+    - Image|endswith: '\whoami.exe'
+      Count: 5
+      Ratio: 0.5
+      Empty: null
+    - Flag: true
+      Tagged: !custom value
+keywords:
+    - mimikatz
+condition: selection or keywords
+"#,
+        )
+        .unwrap();
+        assert_eq!(detection.conditions, ["selection or keywords"]);
+        let expected: Value = serde_yml::from_str(
+            r#"
+- Image|endswith: '\whoami.exe'
+  Count: 5
+  Ratio: 0.5
+  Empty: null
+- Flag: true
+  Tagged: !custom value
+"#,
+        )
+        .unwrap();
+        assert_eq!(detection.selections["selection"], expected);
+        assert_eq!(
+            detection.selections["keywords"],
+            Value::Sequence(vec!["mimikatz".into()])
+        );
+    }
+
+    #[test]
+    fn test_duplicate_selection() {
+        let err = proxy("selection:\n    a: 1\nselection:\n    b: 2\ncondition: selection\n")
+            .unwrap_err();
+        assert!(
+            err.contains("Selection 'selection' is defined more than once"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_duplicate_field() {
+        let err = proxy("selection:\n    a: 1\n    a: 2\ncondition: selection\n").unwrap_err();
+        assert!(
+            err.contains("Field 'a' is defined more than once in selection 'selection'"),
+            "{}",
+            err
+        );
+
+        let err = proxy("selection:\n    - a: 1\n    - b: 2\n      b: 3\ncondition: selection\n")
+            .unwrap_err();
+        assert!(
+            err.contains("Field 'b' is defined more than once in selection 'selection'"),
+            "{}",
+            err
+        );
+
+        // The same field in different mappings of a list is fine
+        assert!(proxy("selection:\n    - a: 1\n    - a: 2\ncondition: selection\n").is_ok());
+    }
+
+    #[test]
+    fn test_merge_keys() {
+        let detection = proxy(
+            r#"
+base: &base
+    Image|endswith: '\cmd.exe'
+    User: admin
+other: &other
+    User: system
+    ParentImage|endswith: '\explorer.exe'
+merged:
+    <<: *base
+    CommandLine|contains: whoami
+overridden:
+    CommandLine|contains: whoami
+    <<: *base
+    User: guest
+listed:
+    <<: [*other, *base]
+nested:
+    - <<: *base
+    - <<: *other
+      User: root
+chained: &chained
+    <<: *base
+    Hashes: abc
+rechained:
+    <<: *chained
+condition: 1 of them
+"#,
+        )
+        .unwrap();
+        let yaml = |yaml: &str| serde_yml::from_str::<Value>(yaml).unwrap();
+        assert_eq!(
+            detection.selections["merged"],
+            yaml("{Image|endswith: '\\cmd.exe', User: admin, CommandLine|contains: whoami}")
+        );
+        assert_eq!(
+            detection.selections["overridden"],
+            yaml("{Image|endswith: '\\cmd.exe', CommandLine|contains: whoami, User: guest}")
+        );
+        assert_eq!(
+            detection.selections["listed"],
+            yaml("{User: system, ParentImage|endswith: '\\explorer.exe', Image|endswith: '\\cmd.exe'}")
+        );
+        assert_eq!(
+            detection.selections["nested"],
+            yaml("[{Image|endswith: '\\cmd.exe', User: admin}, {ParentImage|endswith: '\\explorer.exe', User: root}]")
+        );
+        assert_eq!(
+            detection.selections["rechained"],
+            yaml("{Image|endswith: '\\cmd.exe', User: admin, Hashes: abc}")
+        );
+
+        for invalid in ["<<: 5", "<<: [*base, 5]", "<<: [[*base]]"] {
+            let err = proxy(&format!(
+                "base: &base\n    a: 1\nsel:\n    {invalid}\ncondition: sel\n"
+            ))
+            .unwrap_err();
+            assert!(
+                err.contains("The merge key '<<' in selection 'sel' must be a mapping"),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_duplicate_condition() {
+        let err = proxy("selection:\n    a: 1\ncondition: selection\ncondition: selection\n")
+            .unwrap_err();
+        assert!(err.contains("duplicate field `condition`"), "{}", err);
+    }
+
+    #[test]
+    fn test_condition_list() {
+        let detection =
+            proxy("a:\n    x: 1\nb:\n    y: 2\ncondition:\n    - a\n    - b\n").unwrap();
+        assert_eq!(detection.conditions, ["a", "b"]);
+
+        let err = proxy("a:\n    x: 1\ncondition: 5\n").unwrap_err();
+        assert!(
+            err.contains("expected a condition or a list of conditions"),
+            "{}",
+            err
+        );
+        assert!(proxy("a:\n    x: 1\ncondition: [a, [b]]\n").is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use super::proxy::resolve_merge_keys;
+use super::{Detection, DetectionProxy};
+use crate::error::{ParserError, Span};
+use crate::field::Field;
+use crate::selection::Selection;
+use serde_yml::Value;
+
+/// Attach the position of the offending selection, field or condition to an error of the
+/// detection of a rule. Errors of the detection are raised by `TryFrom` conversions, which
+/// serde_yml reports at the start of the document, so the detection is parsed again to find
+/// the key that fails, which is then looked up in the source. Other errors are returned as is.
+pub(crate) fn locate_error(yaml: &str, err: serde_yml::Error) -> serde_yml::Error {
+    match serde_yml::from_str(yaml) {
+        Ok(rule) => locate_rule_error(yaml, &rule, err),
+        Err(_) => err,
+    }
+}
+
+/// Like [`locate_error`] for a rule that was parsed from `yaml` and possibly transformed, e.g.
+/// by a processing pipeline. Keys that cannot be found in the source are not located.
+pub(crate) fn locate_rule_error(
+    yaml: &str,
+    rule: &Value,
+    err: serde_yml::Error,
+) -> serde_yml::Error {
+    match locate(yaml, rule) {
+        Some((span, error)) => serde::de::Error::custom(ParserError::Located {
+            span,
+            error: Box::new(error),
+        }),
+        None => err,
+    }
+}
+
+/// The first error of the detection of a rule and its position in the source
+pub(crate) fn locate(yaml: &str, rule: &Value) -> Option<(Span, ParserError)> {
+    let detection = rule.get("detection")?;
+
+    // Selections are checked in the order of the source, so the first error is reported
+    for (name, selection) in detection.as_mapping()? {
+        let Some(name) = name.as_str().filter(|name| *name != "condition") else {
+            continue;
+        };
+        let parsed = resolve_merge_keys(name, selection.clone()).and_then(Selection::try_from);
+        if let Err(error) = parsed {
+            let error = match error {
+                ParserError::SelectionParsingError(_, error) => {
+                    ParserError::SelectionParsingError(name.to_string(), error)
+                }
+                error => error,
+            };
+            return Some((selection_span(yaml, name, selection)?, error));
+        }
+    }
+
+    let proxy: DetectionProxy = serde_yml::from_value(detection.clone()).ok()?;
+    let error = Detection::try_from(proxy).err()?;
+    Some((find(yaml, &["detection", "condition"])?, error))
+}
+
+/// The position of the first field of a selection that cannot be parsed, or of the selection
+/// itself if its fields are not the problem
+pub(super) fn selection_span(yaml: &str, name: &str, selection: &Value) -> Option<Span> {
+    let mut path = vec!["detection", name];
+    path.extend(failing_field(selection));
+    find(yaml, &path)
+}
+
+/// The name of the first field of a selection that cannot be parsed
+fn failing_field(selection: &Value) -> Option<&str> {
+    let groups = match selection {
+        Value::Mapping(_) => std::slice::from_ref(selection),
+        Value::Sequence(groups) => groups.as_slice(),
+        _ => return None,
+    };
+    groups
+        .iter()
+        .filter_map(Value::as_mapping)
+        .flatten()
+        .find_map(|(name, value)| {
+            let name = name.as_str()?;
+            Field::from_yaml(name, value.clone())
+                .is_err()
+                .then_some(name)
+        })
+}
+
+/// Find the key at the end of a path of nested block mapping keys, e.g. `detection`,
+/// `selection`, `Image|endswith`. Keys of mappings in sequences are found as well, flow
+/// mappings are not supported.
+fn find(yaml: &str, path: &[&str]) -> Option<Span> {
+    let mut depth = 0;
+    // The column of the keys of the current block and the column of its parent key
+    let mut block: Option<usize> = None;
+    let mut parent: Option<usize> = None;
+
+    for (number, line) in yaml.lines().enumerate() {
+        let content = line.trim_start();
+        if content.is_empty() || content.starts_with('#') || content == "---" {
+            continue;
+        }
+        let indent = line.len() - content.len();
+        if parent.is_some_and(|parent| indent <= parent) {
+            return None;
+        }
+
+        let mut column = indent;
+        let mut rest = content;
+        while let Some(item) = rest.strip_prefix("- ") {
+            let item_content = item.trim_start();
+            column += 2 + item.len() - item_content.len();
+            rest = item_content;
+        }
+        if rest == "-" {
+            continue;
+        }
+        let block_column = *block.get_or_insert(column);
+        if column != block_column || key(rest) != path.get(depth).copied() {
+            continue;
+        }
+
+        depth += 1;
+        if depth == path.len() {
+            return Some(Span {
+                line: number + 1,
+                column: column + 1,
+            });
+        }
+        parent = Some(column);
+        block = None;
+    }
+    None
+}
+
+/// The key of a line of a block mapping
+fn key(line: &str) -> Option<&str> {
+    for quote in ['\'', '"'] {
+        if let Some(quoted) = line.strip_prefix(quote) {
+            let end = quoted.find(quote)?;
+            return quoted[end + 1..]
+                .trim_start()
+                .starts_with(':')
+                .then_some(&quoted[..end]);
+        }
+    }
+    let end = line
+        .char_indices()
+        .find(|&(i, c)| {
+            c == ':'
+                && line[i + 1..]
+                    .chars()
+                    .next()
+                    .map_or(true, char::is_whitespace)
+        })?
+        .0;
+    Some(line[..end].trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    const RULE: &str = r#"title: Test
+logsource:
+    product: windows
+detection:
+    selection:
+        - Image|endswith: '\whoami.exe'
+        - CommandLine|contains: whoami
+    filter:
+        User: SYSTEM
+        'Parent|staartswith': cmd.exe
+    condition: selection and not filter
+"#;
+
+    #[test]
+    fn test_field_error() {
+        let err = rule_from_yaml(RULE).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 10, column 9: Unknown field modifier 'staartswith' provided, did you mean 'startswith'?"
+        );
+    }
+
+    #[test]
+    fn test_value_error() {
+        let yaml = RULE.replace("CommandLine|contains: whoami", "CommandLine|re: '(a'");
+        let err = rule_from_yaml(&yaml).unwrap_err().to_string();
+        assert!(
+            err.starts_with("line 7, column 11: Failed to parse regular expression"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_selection_error() {
+        let yaml = RULE.replace(
+            "        User: SYSTEM\n        'Parent|staartswith': cmd.exe\n",
+            "        []\n",
+        );
+        let err = rule_from_yaml(&yaml).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 8, column 5: Selection 'filter' has an error: 'Selection without fields detected'"
+        );
+    }
+
+    #[test]
+    fn test_condition_error() {
+        let yaml = RULE
+            .replace("'Parent|staartswith'", "Parent|startswith")
+            .replace("not filter", "not filter2");
+        let err = rule_from_yaml(&yaml).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 11, column 5: Condition references undefined identifiers: '[\"filter2\"]'"
+        );
+    }
+
+    #[test]
+    fn test_merge_keys() {
+        let yaml = RULE.replace(
+            "    selection:\n        - Image|endswith: '\\whoami.exe'\n",
+            "    base: &base\n        Image|endswith: '\\whoami.exe'\n    selection:\n        - <<: *base\n",
+        );
+        let err = rule_from_yaml(&yaml).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("line 12, column 9: Unknown field modifier"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_other_errors_are_kept() {
+        let yaml = RULE.replace("title: Test", "title: [Test");
+        let err = rule_from_yaml(&yaml).unwrap_err();
+        assert!(err.location().is_some());
+        assert!(!err.to_string().contains("Unknown field modifier"));
+    }
+
+    #[test]
+    fn test_find() {
+        let yaml = "  title: x\n  detection:\n    sel:\n      -\n        a: 1\n      - \"b|re\": x\n    condition: sel\n";
+        assert_eq!(
+            find(yaml, &["detection", "sel", "a"]),
+            Some(Span { line: 5, column: 9 })
+        );
+        assert_eq!(
+            find(yaml, &["detection", "sel", "b|re"]),
+            Some(Span { line: 6, column: 9 })
+        );
+        assert_eq!(
+            find(yaml, &["detection", "condition"]),
+            Some(Span { line: 7, column: 5 })
+        );
+        assert_eq!(find(yaml, &["detection", "a"]), None);
+        assert_eq!(find(yaml, &["title", "sel"]), None);
+    }
+}
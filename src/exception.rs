@@ -0,0 +1,238 @@
+//! Runtime exceptions to suppress matches of noisy rules without editing rule files.
+//!
+//! Exceptions are keyed by rule id and use the same syntax as a Sigma selection.
+//! They are evaluated after the rule itself: a match is suppressed if any exception
+//! registered for the rule matches the event.
+//!
+//! ```yaml
+//! - rule: 5a0a8e1c-2a52-4d55-a5a7-41a2b5c4f0e1
+//!   description: Backup agent legitimately spawns cmd.exe
+//!   selection:
+//!     User: svc_backup
+//!     ParentImage|endswith: '\backup.exe'
+//! ```
+use crate::error::ParserError;
+use crate::event::Event;
+use crate::result::MatchResult;
+use crate::rule::Rule;
+use crate::selection::Selection;
+use serde::Deserialize;
+use serde_yml::Value;
+use std::collections::HashMap;
+
+#[derive(Deserialize)]
+struct ExceptionProxy {
+    rule: String,
+    description: Option<String>,
+    selection: Value,
+}
+
+/// A single exception for a rule
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "ExceptionProxy")]
+pub struct Exception {
+    /// The id of the rule the exception applies to
+    pub rule: String,
+    /// An optional description why the exception exists
+    pub description: Option<String>,
+    selection: Selection,
+}
+
+impl TryFrom<ExceptionProxy> for Exception {
+    type Error = ParserError;
+
+    fn try_from(other: ExceptionProxy) -> Result<Self, Self::Error> {
+        Ok(Self {
+            rule: other.rule,
+            description: other.description,
+            selection: Selection::try_from(other.selection)?,
+        })
+    }
+}
+
+impl Exception {
+    /// Check if the exception matches the event
+    pub fn is_match(&self, event: &Event) -> bool {
+        self.selection.evaluate(event)
+    }
+}
+
+/// A collection of exceptions keyed by rule id
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::exception::ExceptionSet;
+/// use sigma_rust::{rule_from_yaml, Event};
+///
+/// let rule = rule_from_yaml(
+///     r#"
+/// title: Cmd spawned
+/// id: 5a0a8e1c-2a52-4d55-a5a7-41a2b5c4f0e1
+/// logsource:
+///     category: process_creation
+/// detection:
+///     selection:
+///         Image|endswith: '\cmd.exe'
+///     condition: selection
+/// "#,
+/// )
+/// .unwrap();
+///
+/// let exceptions = ExceptionSet::from_yaml(
+///     r#"
+/// - rule: 5a0a8e1c-2a52-4d55-a5a7-41a2b5c4f0e1
+///   selection:
+///     User: svc_backup
+/// "#,
+/// )
+/// .unwrap();
+///
+/// let event = Event::from([("Image", "C:\\cmd.exe"), ("User", "svc_backup")]);
+/// assert!(rule.is_match(&event));
+/// assert!(exceptions.evaluate(&rule, &event).is_none());
+/// ```
+#[derive(Debug, Default)]
+pub struct ExceptionSet {
+    exceptions: HashMap<String, Vec<Exception>>,
+}
+
+impl ExceptionSet {
+    /// Create an empty exception set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a list of exceptions from a YAML string
+    pub fn from_yaml(yaml: &str) -> Result<Self, serde_yml::Error> {
+        let exceptions: Vec<Exception> = serde_yml::from_str(yaml)?;
+        let mut result = Self::new();
+        for exception in exceptions {
+            result.add(exception);
+        }
+        Ok(result)
+    }
+
+    /// Add an exception to the set
+    pub fn add(&mut self, exception: Exception) {
+        self.exceptions
+            .entry(exception.rule.clone())
+            .or_default()
+            .push(exception);
+    }
+
+    /// Remove all exceptions of a rule and return them
+    pub fn remove(&mut self, rule_id: &str) -> Vec<Exception> {
+        self.exceptions.remove(rule_id).unwrap_or_default()
+    }
+
+    /// Get the exceptions registered for a rule
+    pub fn get(&self, rule_id: &str) -> &[Exception] {
+        self.exceptions
+            .get(rule_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The total number of exceptions
+    pub fn len(&self) -> usize {
+        self.exceptions.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Check if a match of the rule with the given id on the event is suppressed
+    pub fn is_suppressed(&self, rule_id: &str, event: &Event) -> bool {
+        self.get(rule_id).iter().any(|e| e.is_match(event))
+    }
+
+    /// Apply the exceptions to a match result; returns `None` if the match is suppressed
+    pub fn filter(&self, result: MatchResult, event: &Event) -> Option<MatchResult> {
+        match &result.rule_id {
+            Some(id) if self.is_suppressed(id, event) => None,
+            _ => Some(result),
+        }
+    }
+
+    /// Evaluate the rule against the event and apply the exceptions to the result
+    pub fn evaluate(&self, rule: &Rule, event: &Event) -> Option<MatchResult> {
+        self.filter(rule.evaluate(event)?, event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    const EXCEPTIONS: &str = r#"
+- rule: rule-1
+  description: Backup agent
+  selection:
+    User: svc_backup
+    ParentImage|endswith: '\backup.exe'
+- rule: rule-1
+  selection:
+    - Computer: build-01
+    - Computer: build-02
+- rule: rule-2
+  selection:
+    User|contains: admin
+"#;
+
+    #[test]
+    fn test_parse_exceptions() {
+        let exceptions = ExceptionSet::from_yaml(EXCEPTIONS).unwrap();
+        assert_eq!(exceptions.len(), 3);
+        assert_eq!(exceptions.get("rule-1").len(), 2);
+        assert_eq!(
+            exceptions.get("rule-1")[0].description.as_deref(),
+            Some("Backup agent")
+        );
+        assert!(exceptions.get("unknown").is_empty());
+    }
+
+    #[test]
+    fn test_invalid_exception() {
+        let yaml = r#"
+- rule: rule-1
+  selection:
+    User|unknownmodifier: svc_backup
+"#;
+        assert!(ExceptionSet::from_yaml(yaml).is_err());
+    }
+
+    #[test]
+    fn test_suppress_matches() {
+        let rule = rule_from_yaml(
+            r#"
+        title: Cmd
+        id: rule-1
+        logsource:
+        detection:
+            selection:
+                Image|endswith: '\cmd.exe'
+            condition: selection
+        "#,
+        )
+        .unwrap();
+        let mut exceptions = ExceptionSet::from_yaml(EXCEPTIONS).unwrap();
+
+        let event = Event::from([
+            ("Image", "C:\\cmd.exe"),
+            ("User", "svc_backup"),
+            ("ParentImage", "C:\\backup.exe"),
+        ]);
+        assert!(exceptions.evaluate(&rule, &event).is_none());
+
+        let event = Event::from([("Image", "C:\\cmd.exe"), ("User", "svc_backup")]);
+        assert!(exceptions.evaluate(&rule, &event).is_some());
+
+        let event = Event::from([("Image", "C:\\cmd.exe"), ("Computer", "build-02")]);
+        assert!(exceptions.evaluate(&rule, &event).is_none());
+
+        assert_eq!(exceptions.remove("rule-1").len(), 2);
+        assert!(exceptions.evaluate(&rule, &event).is_some());
+    }
+}
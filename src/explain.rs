@@ -0,0 +1,350 @@
+//! Explain why a rule does not match an event.
+//!
+//! [`Rule::explain`](crate::Rule::explain) evaluates the condition of a rule like the engine
+//! does and follows it down to the first selection that decided the result. For a selection
+//! that did not match, it reports the first field that failed together with the value of the
+//! event the field was compared to, so rule authors can debug a rule that does not fire
+//! without bisecting it by hand.
+use crate::detection::{Ast, Detection};
+use crate::evaluation::EvalContext;
+use crate::event::{Event, EventValue};
+use crate::field::Field;
+use crate::selection::Selection;
+use crate::wildcard::match_tokenized;
+use serde_yml::Value;
+use std::cmp::Reverse;
+use std::fmt;
+
+/// The result of [`Rule::explain`](crate::Rule::explain)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    /// Whether the rule matches the event
+    pub matched: bool,
+    /// The name and the result of every evaluated selection, in evaluation order
+    pub selections: Vec<(String, bool)>,
+    /// The first reason why the rule does not match, `None` if it matches
+    pub mismatch: Option<Mismatch>,
+}
+
+/// Why a rule does not match an event
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    /// A selection that the condition requires did not match. `field` is the first failing
+    /// field of the field group that came closest to matching, and `None` for keyword
+    /// selections.
+    Unmatched {
+        selection: String,
+        field: Option<FieldMismatch>,
+    },
+    /// A selection matched that the condition negates, e.g. a filter in
+    /// `selection and not filter`
+    Negated { selection: String },
+    /// A quantifier such as `1 of sel_*` matches no selection of the rule
+    EmptyQuantifier,
+}
+
+/// A field that did not match the event
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMismatch {
+    /// The field with its modifiers as written in the rule, e.g. `Image|endswith`
+    pub field: String,
+    /// The values of the field as written in the rule, see [`Field::to_yaml`]
+    pub expected: Value,
+    /// The value of the event the field was compared to, `None` if the event does not
+    /// contain the field
+    pub actual: Option<EventValue>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unmatched {
+                selection,
+                field: None,
+            } => write!(f, "selection '{selection}' did not match"),
+            Self::Unmatched {
+                selection,
+                field: Some(field),
+            } => write!(f, "selection '{selection}' did not match: {field}"),
+            Self::Negated { selection } => write!(
+                f,
+                "selection '{selection}' matched, but the condition negates it"
+            ),
+            Self::EmptyQuantifier => write!(f, "a quantifier matches no selection"),
+        }
+    }
+}
+
+impl fmt::Display for FieldMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} expected {}",
+            self.field,
+            describe_yaml(&self.expected)
+        )?;
+        match &self.actual {
+            Some(actual) => write!(f, ", got {}", describe_event_value(actual)),
+            None => write!(f, ", but the event does not contain the field"),
+        }
+    }
+}
+
+fn describe_yaml(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("'{s}'"),
+        Value::Sequence(values) => {
+            let values: Vec<_> = values.iter().map(describe_yaml).collect();
+            format!("[{}]", values.join(", "))
+        }
+        other => serde_yml::to_string(other)
+            .map(|s| s.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn describe_event_value(value: &EventValue) -> String {
+    match value {
+        EventValue::Value(value) => format!("'{}'", value.value_to_string()),
+        EventValue::Sequence(values) => {
+            let values: Vec<_> = values.iter().map(describe_event_value).collect();
+            format!("[{}]", values.join(", "))
+        }
+        EventValue::Map(_) => "a map".to_string(),
+    }
+}
+
+pub(crate) fn explain(detection: &Detection, event: &Event) -> Explanation {
+    let mut explainer = Explainer {
+        detection,
+        ctx: EvalContext::new(event),
+        selections: vec![],
+    };
+    let matched = explainer.eval(detection.ast());
+    let mismatch = (!matched).then(|| explainer.blame(detection.ast(), true));
+    Explanation {
+        matched,
+        selections: explainer.selections,
+        mismatch,
+    }
+}
+
+struct Explainer<'a> {
+    detection: &'a Detection,
+    ctx: EvalContext<'a>,
+    selections: Vec<(String, bool)>,
+}
+
+impl Explainer<'_> {
+    fn selection(&mut self, name: &str) -> bool {
+        if let Some((_, result)) = self.selections.iter().find(|(n, _)| n == name) {
+            return *result;
+        }
+        let result = self
+            .detection
+            .get_selections()
+            .get(name)
+            .is_some_and(|selection| selection.evaluate_in(&self.ctx));
+        self.selections.push((name.to_string(), result));
+        result
+    }
+
+    /// The names of the selections a quantifier refers to, sorted to explain deterministically
+    fn quantified(&self, ast: &Ast) -> Vec<String> {
+        let mut names: Vec<_> = self
+            .detection
+            .get_selections()
+            .keys()
+            .filter(|name| match ast {
+                Ast::OneOf(pattern) | Ast::AllOf(pattern) => match_tokenized(pattern, name, false),
+                _ => true,
+            })
+            .cloned()
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Evaluate the condition like [`Detection::evaluate`], remembering selection results
+    fn eval(&mut self, ast: &Ast) -> bool {
+        match ast {
+            Ast::Selection(name) => self.selection(name),
+            Ast::OneOf(_) | Ast::OneOfThem => {
+                self.quantified(ast).iter().any(|name| self.selection(name))
+            }
+            Ast::AllOf(_) | Ast::AllOfThem => {
+                self.quantified(ast).iter().all(|name| self.selection(name))
+            }
+            Ast::Not(operand) => !self.eval(operand),
+            Ast::And(left, right) => self.eval(left) && self.eval(right),
+            Ast::Or(left, right) => self.eval(left) || self.eval(right),
+        }
+    }
+
+    /// Find the reason why `ast` does not evaluate to `expected`: the first operand, or the
+    /// first selection of a quantifier, that does not evaluate to `expected` either
+    fn blame(&mut self, ast: &Ast, expected: bool) -> Mismatch {
+        match ast {
+            Ast::Selection(name) => self.selection_mismatch(name, expected),
+            Ast::Not(operand) => self.blame(operand, !expected),
+            Ast::And(left, right) | Ast::Or(left, right) => {
+                if self.eval(left) != expected {
+                    self.blame(left, expected)
+                } else {
+                    self.blame(right, expected)
+                }
+            }
+            Ast::OneOf(_) | Ast::OneOfThem | Ast::AllOf(_) | Ast::AllOfThem => {
+                let names = self.quantified(ast);
+                match names.iter().find(|name| self.selection(name) != expected) {
+                    Some(name) => self.selection_mismatch(name, expected),
+                    // `1 of` no selection is false and `all of` no selection is true
+                    None => Mismatch::EmptyQuantifier,
+                }
+            }
+        }
+    }
+
+    fn selection_mismatch(&mut self, name: &str, expected: bool) -> Mismatch {
+        if !expected {
+            return Mismatch::Negated {
+                selection: name.to_string(),
+            };
+        }
+        let field = match self.detection.get_selections().get(name) {
+            Some(Selection::Field(groups)) => groups
+                .iter()
+                .map(|group| {
+                    group
+                        .fields
+                        .iter()
+                        .map(|field| (field, field.evaluate_in(&self.ctx)))
+                        .collect::<Vec<_>>()
+                })
+                .enumerate()
+                // The first of the groups with the most matching fields
+                .max_by_key(|(i, results)| {
+                    let matching = results.iter().filter(|(_, matched)| *matched).count();
+                    (matching, Reverse(*i))
+                })
+                .and_then(|(_, results)| {
+                    results
+                        .into_iter()
+                        .find(|(_, matched)| !matched)
+                        .map(|(field, _)| self.field_mismatch(field))
+                }),
+            _ => None,
+        };
+        Mismatch::Unmatched {
+            selection: name.to_string(),
+            field,
+        }
+    }
+
+    fn field_mismatch(&self, field: &Field) -> FieldMismatch {
+        FieldMismatch {
+            field: field.to_string(),
+            expected: field.to_yaml(),
+            actual: self.ctx.get(field.id, field.name()).cloned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    const RULE: &str = r#"
+title: Suspicious shell
+logsource:
+detection:
+    selection:
+        - Image|endswith: '\cmd.exe'
+          CommandLine|contains: 'whoami'
+        - Image|endswith: '\powershell.exe'
+    filter:
+        User: 'SYSTEM'
+    keywords:
+        - 'mimikatz'
+    condition: selection and not filter
+"#;
+
+    #[test]
+    fn test_unmatched_field() {
+        let rule = rule_from_yaml(RULE).unwrap();
+        let event = Event::from([("Image", "C:\\cmd.exe"), ("CommandLine", "cmd /c dir")]);
+        let explanation = rule.explain(&event);
+        assert!(!explanation.matched);
+        assert_eq!(
+            explanation.selections,
+            vec![("selection".to_string(), false)]
+        );
+        assert_eq!(
+            explanation.mismatch,
+            Some(Mismatch::Unmatched {
+                selection: "selection".to_string(),
+                field: Some(FieldMismatch {
+                    field: "CommandLine|contains".to_string(),
+                    expected: Value::from("whoami"),
+                    actual: Some(EventValue::from("cmd /c dir")),
+                }),
+            })
+        );
+
+        // The first group matches no field, so the missing field of the first group is reported
+        let explanation = rule.explain(&Event::from([("User", "bob")]));
+        assert_eq!(
+            explanation.mismatch.unwrap().to_string(),
+            "selection 'selection' did not match: Image|endswith expected '\\cmd.exe', but the event does not contain the field"
+        );
+    }
+
+    #[test]
+    fn test_negated() {
+        let rule = rule_from_yaml(RULE).unwrap();
+        let event = Event::from([("Image", "C:\\powershell.exe"), ("User", "SYSTEM")]);
+        let explanation = rule.explain(&event);
+        assert!(!explanation.matched);
+        assert_eq!(
+            explanation.selections,
+            vec![
+                ("selection".to_string(), true),
+                ("filter".to_string(), true)
+            ]
+        );
+        assert_eq!(
+            explanation.mismatch.unwrap().to_string(),
+            "selection 'filter' matched, but the condition negates it"
+        );
+
+        let explanation = rule.explain(&Event::from([("Image", "C:\\powershell.exe")]));
+        assert!(explanation.matched);
+        assert!(explanation.mismatch.is_none());
+        assert_eq!(
+            explanation.matched,
+            rule.is_match(&Event::from([("Image", "C:\\powershell.exe")]))
+        );
+    }
+
+    #[test]
+    fn test_quantifiers() {
+        let rule =
+            rule_from_yaml(&RULE.replace("selection and not filter", "all of them")).unwrap();
+        let event = Event::from([("Image", "C:\\powershell.exe"), ("User", "SYSTEM")]);
+        // The selections of a quantifier are explained in alphabetical order
+        assert_eq!(
+            rule.explain(&event).mismatch,
+            Some(Mismatch::Unmatched {
+                selection: "keywords".to_string(),
+                field: None,
+            })
+        );
+
+        let rule = rule_from_yaml(&RULE.replace("selection and not filter", "1 of sel_*")).unwrap();
+        assert_eq!(
+            rule.explain(&event).mismatch,
+            Some(Mismatch::EmptyQuantifier)
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! A C interface for embedding the engine into agents written in C, C++, Go or other languages
+//! with a C FFI.
+//!
+//! With the `ffi` feature enabled, build a shared library with
+//! `cargo rustc --lib --release --features ffi --crate-type cdylib` and include
+//! `include/sigma_rust.h`. Events are passed as JSON and matches are returned as a JSON array.
+//!
+//! ```c
+//! char *error = NULL;
+//! SigmaRuleSet *rules = sigma_rule_set_from_directory("rules/windows", &error);
+//! if (rules == NULL) {
+//!     fprintf(stderr, "%s\n", error);
+//!     sigma_string_free(error);
+//!     return 1;
+//! }
+//! char *matches = sigma_rule_set_evaluate(rules, "{\"Image\": \"C:\\\\whoami.exe\"}", &error);
+//! puts(matches);
+//! sigma_string_free(matches);
+//! sigma_rule_set_free(rules);
+//! ```
+//!
+//! Strings returned by the library must be released with [`sigma_string_free`] and rule sets
+//! with [`sigma_rule_set_free`]. A rule set may be evaluated from several threads at once.
+#![allow(unsafe_code)]
+use crate::loader::Loader;
+use crate::result::MatchResult;
+use crate::rule::Rule;
+use crate::validation::Severity;
+use crate::{event_from_json, RuleSet};
+use serde::Deserialize;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+/// A compiled collection of rules
+pub struct SigmaRuleSet(RuleSet);
+
+/// Compile the rules of a YAML string. Several rules are separated by `---`.
+///
+/// Returns `NULL` and stores a message in `error`, unless it is `NULL`, if a rule cannot be
+/// parsed.
+///
+/// # Safety
+/// `yaml` must be a valid, NUL-terminated string and `error` either `NULL` or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn sigma_rule_set_from_yaml(
+    yaml: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut SigmaRuleSet {
+    let rules = to_str(yaml).and_then(rules_from_yaml);
+    into_raw(rules, error)
+}
+
+/// Compile all `.yml` and `.yaml` files below a directory. Several rules of a file are separated
+/// by `---`, and detections may include selections of other files with `tau-include`.
+///
+/// Returns `NULL` and stores a message in `error`, unless it is `NULL`, if the directory cannot
+/// be read or a rule cannot be parsed.
+///
+/// # Safety
+/// `path` must be a valid, NUL-terminated string and `error` either `NULL` or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn sigma_rule_set_from_directory(
+    path: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut SigmaRuleSet {
+    let rules = to_str(path).and_then(|path| {
+        let loaded = Loader::new().with_includes(true).load_dir(path);
+        match loaded
+            .diagnostics
+            .iter()
+            .find(|d| d.severity == Severity::Error)
+        {
+            Some(error) => Err(error.to_string()),
+            None => Ok(loaded.rules),
+        }
+    });
+    into_raw(rules, error)
+}
+
+/// The number of rules of a rule set
+///
+/// # Safety
+/// `rule_set` must be a rule set returned by this library that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn sigma_rule_set_len(rule_set: *const SigmaRuleSet) -> usize {
+    (*rule_set).0.len()
+}
+
+/// Evaluate all rules against an event given as JSON and return the matches as a JSON array.
+///
+/// Returns `NULL` and stores a message in `error`, unless it is `NULL`, if the event cannot be
+/// parsed.
+///
+/// # Safety
+/// `rule_set` must be a rule set returned by this library that has not been freed, `event` a
+/// valid, NUL-terminated string and `error` either `NULL` or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn sigma_rule_set_evaluate(
+    rule_set: *const SigmaRuleSet,
+    event: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut c_char {
+    let result = to_str(event).and_then(|event| {
+        let event = event_from_json(event).map_err(|e| e.to_string())?;
+        let results = (*rule_set).0.evaluate(&event);
+        let json = serde_json::Value::Array(results.iter().map(MatchResult::to_json).collect());
+        Ok(json.to_string())
+    });
+    match result {
+        Ok(json) => to_c_string(json),
+        Err(message) => {
+            set_error(error, message);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free a rule set. Passing `NULL` is a no-op.
+///
+/// # Safety
+/// `rule_set` must be `NULL` or a rule set returned by this library that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn sigma_rule_set_free(rule_set: *mut SigmaRuleSet) {
+    if !rule_set.is_null() {
+        drop(Box::from_raw(rule_set));
+    }
+}
+
+/// Free a string returned by this library. Passing `NULL` is a no-op.
+///
+/// # Safety
+/// `string` must be `NULL` or a string returned by this library that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn sigma_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+unsafe fn to_str<'a>(string: *const c_char) -> Result<&'a str, String> {
+    if string.is_null() {
+        return Err("unexpected NULL pointer".to_string());
+    }
+    CStr::from_ptr(string)
+        .to_str()
+        .map_err(|_| "the string is not valid UTF-8".to_string())
+}
+
+unsafe fn set_error(error: *mut *mut c_char, message: String) {
+    if !error.is_null() {
+        *error = to_c_string(message);
+    }
+}
+
+unsafe fn into_raw(rules: Result<Vec<Rule>, String>, error: *mut *mut c_char) -> *mut SigmaRuleSet {
+    match rules {
+        Ok(rules) => Box::into_raw(Box::new(SigmaRuleSet(RuleSet::new(rules)))),
+        Err(message) => {
+            set_error(error, message);
+            ptr::null_mut()
+        }
+    }
+}
+
+#[allow(clippy::expect_used)]
+fn to_c_string(string: String) -> *mut c_char {
+    // JSON and error messages never contain NUL bytes, but be defensive
+    CString::new(string.replace('\0', ""))
+        .expect("NUL bytes were removed")
+        .into_raw()
+}
+
+fn rules_from_yaml(yaml: &str) -> Result<Vec<Rule>, String> {
+    serde_yml::Deserializer::from_str(yaml)
+        .map(|document| Rule::deserialize(document).map_err(|e| e.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const RULES: &str = r#"
+title: Whoami
+id: 1a2b
+logsource:
+    category: process_creation
+detection:
+    selection:
+        Image|endswith: '\whoami.exe'
+    condition: selection
+---
+title: Net
+logsource:
+    category: process_creation
+detection:
+    selection:
+        Image|endswith: '\net.exe'
+    condition: selection
+"#;
+
+    unsafe fn take(string: *mut c_char) -> String {
+        assert!(!string.is_null());
+        let result = CStr::from_ptr(string).to_str().unwrap().to_string();
+        sigma_string_free(string);
+        result
+    }
+
+    #[test]
+    fn test_evaluate() {
+        unsafe {
+            let yaml = CString::new(RULES).unwrap();
+            let mut error = ptr::null_mut();
+            let rule_set = sigma_rule_set_from_yaml(yaml.as_ptr(), &mut error);
+            assert!(error.is_null());
+            assert_eq!(sigma_rule_set_len(rule_set), 2);
+
+            let event = CString::new(r#"{"Image": "C:\\whoami.exe"}"#).unwrap();
+            let matches = take(sigma_rule_set_evaluate(
+                rule_set,
+                event.as_ptr(),
+                &mut error,
+            ));
+            let matches: serde_json::Value = serde_json::from_str(&matches).unwrap();
+            assert_eq!(matches[0]["id"], "1a2b");
+            assert_eq!(matches.as_array().unwrap().len(), 1);
+
+            let event = CString::new("not json").unwrap();
+            let matches = sigma_rule_set_evaluate(rule_set, event.as_ptr(), &mut error);
+            assert!(matches.is_null());
+            assert!(!take(error).is_empty());
+
+            sigma_rule_set_free(rule_set);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        unsafe {
+            let yaml = CString::new("title: Broken").unwrap();
+            let mut error = ptr::null_mut();
+            assert!(sigma_rule_set_from_yaml(yaml.as_ptr(), &mut error).is_null());
+            assert!(take(error).contains("missing field"));
+
+            // The error is optional
+            assert!(sigma_rule_set_from_yaml(yaml.as_ptr(), ptr::null_mut()).is_null());
+
+            let path = CString::new("/nonexistent/rules").unwrap();
+            assert!(sigma_rule_set_from_directory(path.as_ptr(), &mut error).is_null());
+            assert!(take(error).starts_with("/nonexistent/rules: error: "));
+
+            sigma_rule_set_free(ptr::null_mut());
+            sigma_string_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_directory() {
+        let dir = std::env::temp_dir().join(format!("sigma-ffi-{}", std::process::id()));
+        fs::create_dir_all(dir.join("windows")).unwrap();
+        fs::write(dir.join("windows/rules.yml"), RULES).unwrap();
+        fs::write(dir.join("README.md"), "not a rule").unwrap();
+
+        unsafe {
+            let path = CString::new(dir.to_str().unwrap()).unwrap();
+            let mut error = ptr::null_mut();
+            let rule_set = sigma_rule_set_from_directory(path.as_ptr(), &mut error);
+            assert!(error.is_null());
+            assert_eq!(sigma_rule_set_len(rule_set), 2);
+            sigma_rule_set_free(rule_set);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -6,103 +6,158 @@ pub use modifier::*;
 pub use value::*;
 
 use crate::basevalue::BaseValue;
+use crate::cache;
+use crate::config;
+use crate::config::{EngineConfig, FieldPolicy, Limit, SpecMode};
+use crate::enrich::command_line_arguments;
 use crate::error::ParserError;
-use crate::error::ParserError::{IPParsing, InvalidYAML};
-use crate::event::{Event, EventValue};
-use crate::field::transformation::{encode_base64, encode_base64_offset, windash_variations};
+#[cfg(feature = "cidr")]
+use crate::error::ParserError::IPParsing;
+use crate::error::ParserError::InvalidYAML;
+use crate::evaluation::{EvalContext, FieldId};
+use crate::event::EventValue;
+use crate::field::transformation::windash_variations;
+#[cfg(feature = "base64")]
+use crate::field::transformation::{encode_base64, encode_base64_offset};
 use crate::field::ValueTransformer::{Base64, Base64offset, Windash};
+use crate::placeholder::LiveValues;
+use crate::re::{RegexSet, RegexSetBuilder};
 use crate::wildcard::{tokenize, WildcardToken};
+#[cfg(feature = "cidr")]
 use cidr::IpCidr;
-use regex::Regex;
+use serde::ser::{Serialize, SerializeMap, Serializer};
 use serde_yml::Value;
+use std::fmt;
 use std::str::FromStr;
+use std::sync::Arc;
 
 // https://sigmahq.io/docs/basics/modifiers.html
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Field {
-    pub name: String,
-    pub values: Vec<FieldValue>,
+    pub(crate) name: String,
+    pub(crate) values: Vec<FieldValue>,
     pub(crate) modifier: Modifier,
+    /// All regular expressions of the field compiled into a single set, so each
+    /// event value is scanned only once instead of once per regular expression
+    pub(crate) regex_set: Option<RegexSet>,
+    /// The interned id of the field name, assigned when the field is part of a rule set
+    pub(crate) id: Option<FieldId>,
+    /// Values longer than this are not matched against the regular expressions of the field,
+    /// see [`crate::EngineConfig::regex_max_input_len`]
+    pub(crate) regex_max_input_len: Option<usize>,
+    /// The values as written in the rule, before they were transformed and compiled
+    pub(crate) source: Vec<BaseValue>,
+    /// The integers of hexadecimal and octal literals among the values, which plain values
+    /// also match in integer event values. They are kept apart from `values`, so backends
+    /// compare columns with the literals as written.
+    pub(crate) numbers: Vec<BaseValue>,
+    /// The current values of the placeholder lists of a field with the `expand` modifier,
+    /// which replace `values` when the field is evaluated
+    pub(crate) live: Option<Arc<LiveValues>>,
+}
+
+/// The name and modifiers of a field as parsed from the key of a selection.
+///
+/// Compiling the values happens once in [`FieldBuilder::build`], which returns a [`Field`]
+/// that is not modified afterwards, so evaluation only ever reads it.
+#[derive(Debug)]
+pub(crate) struct FieldBuilder {
+    name: String,
+    modifier: Modifier,
 }
 
-impl FromStr for Field {
+impl FromStr for FieldBuilder {
     type Err = ParserError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let result = Self {
+        Ok(Self {
             name: s.split("|").next().unwrap_or("").to_string(),
-            values: vec![],
             modifier: Modifier::from_str(s)?,
-        };
-
-        Ok(result)
+        })
     }
 }
 
-impl Field {
-    pub(crate) fn new<S: AsRef<str>>(
-        name_with_modifiers: S,
-        values: Vec<FieldValue>,
-    ) -> Result<Field, ParserError> {
-        match Self::from_str(name_with_modifiers.as_ref()) {
-            Ok(mut field) => {
-                field.values = values;
-                match field.bootstrap() {
-                    Ok(_) => Ok(field),
-                    Err(err) => Err(err),
-                }
-            }
-            Err(err) => Err(err),
+impl FieldBuilder {
+    /// Transform the values according to the modifiers and compile them
+    pub(crate) fn build(self, values: Vec<FieldValue>) -> Result<Field, ParserError> {
+        let Self { name, mut modifier } = self;
+        let source = values
+            .iter()
+            .filter_map(|value| match value {
+                FieldValue::Base(base) => Some(base.clone()),
+                _ => None,
+            })
+            .collect();
+        let mut values = config::with_active(|config| config.value_lists.resolve(values))?;
+
+        if values.is_empty() {
+            return Err(ParserError::EmptyValues(name.to_string()));
+        }
+        let limits = config::active_limits();
+        if values.len() > limits.max_values_per_field {
+            return Err(ParserError::LimitExceeded(
+                Limit::ValuesPerField,
+                limits.max_values_per_field,
+            ));
         }
-    }
-
-    pub(crate) fn from_yaml<S: AsRef<str>>(name: S, value: Value) -> Result<Field, ParserError> {
-        let field_values = match value {
-            Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Null => {
-                vec![FieldValue::try_from(value)?]
-            }
-            Value::Sequence(seq) => {
-                let mut result = Vec::with_capacity(seq.len());
-                for item in seq {
-                    result.push(FieldValue::try_from(item)?);
-                }
-                result
-            }
-            _ => return Err(InvalidYAML(format!("{:?}", value))),
-        };
-        Self::new(name, field_values)
-    }
 
-    fn bootstrap(&mut self) -> Result<(), ParserError> {
-        if self.values.is_empty() {
-            return Err(ParserError::EmptyValues(self.name.to_string()));
+        if modifier.expand {
+            let key = Modifier {
+                expand: false,
+                ..modifier.clone()
+            };
+            let key = match key.to_string() {
+                modifiers if modifiers.is_empty() => name.clone(),
+                modifiers => format!("{}|{}", name, modifiers),
+            };
+            let live = LiveValues::new(key, &values)?;
+            return Ok(Field {
+                name,
+                values,
+                modifier,
+                regex_set: None,
+                id: None,
+                regex_max_input_len: None,
+                source,
+                numbers: vec![],
+                live: Some(Arc::new(live)),
+            });
         }
 
-        if self.modifier.exists.is_some() {
-            if self.values.len() != 1 {
+        if modifier.exists.is_some() {
+            if values.len() != 1 {
                 return Err(ParserError::InvalidValueForExists());
             }
-            if let FieldValue::Base(BaseValue::Boolean(b)) = self.values[0] {
-                self.modifier.exists = Some(b);
+            if let [FieldValue::Base(BaseValue::Boolean(b))] = values.as_slice() {
+                modifier.exists = Some(*b);
             } else {
                 return Err(ParserError::InvalidValueForExists());
             }
         }
 
-        if self.modifier.value_transformer.is_some() {
-            let mut transformed_values: Vec<FieldValue> = Vec::with_capacity(self.values.len());
+        if let Some(value_transformer) = &modifier.value_transformer {
+            let mut transformed_values: Vec<FieldValue> = Vec::with_capacity(values.len());
 
-            for val in &self.values {
+            for val in &values {
                 let s = val.as_string()?;
-                match self.modifier.value_transformer.as_ref().unwrap() {
+                match value_transformer {
+                    #[cfg(feature = "base64")]
                     Base64(utf16) => {
                         transformed_values.push(FieldValue::from(encode_base64(s.as_str(), utf16)))
                     }
+                    #[cfg(feature = "base64")]
                     Base64offset(utf16) => transformed_values.extend(
                         encode_base64_offset(s.as_str(), utf16)
                             .into_iter()
                             .map(FieldValue::from),
                     ),
+                    #[cfg(not(feature = "base64"))]
+                    Base64(_) | Base64offset(_) => {
+                        return Err(ParserError::DisabledModifier(
+                            value_transformer.to_string(),
+                            "base64",
+                        ))
+                    }
                     Windash => transformed_values.extend(
                         windash_variations(s.as_str())
                             .into_iter()
@@ -111,41 +166,186 @@ impl Field {
                 }
             }
 
-            self.values = transformed_values;
+            if transformed_values.len() > limits.max_expanded_values {
+                return Err(ParserError::LimitExceeded(
+                    Limit::ExpandedValues,
+                    limits.max_expanded_values,
+                ));
+            }
+            values = transformed_values;
         }
 
+        let policy = config::with_active(|config| config.field_policies.get(&name).copied());
+        let spec_mode = config::active_spec_mode();
+        let ignore_case = matches!(
+            policy,
+            Some(FieldPolicy::CaseInsensitive | FieldPolicy::Path)
+        );
+        if ignore_case {
+            modifier.cased = false;
+        }
+        let plain = modifier.match_modifier.is_none()
+            && !modifier.fieldref
+            && modifier.value_transformer.is_none();
+        match policy {
+            Some(FieldPolicy::Numeric) => {
+                modifier.numeric = true;
+                if plain {
+                    for v in values.iter_mut() {
+                        if let FieldValue::Base(value @ BaseValue::String(_)) = v {
+                            if let Some(number) = value.as_number() {
+                                *value = BaseValue::Float(number);
+                            }
+                        }
+                    }
+                }
+            }
+            Some(FieldPolicy::Arguments) => modifier.arguments = true,
+            #[cfg(feature = "cidr")]
+            Some(FieldPolicy::Ip) if plain => {
+                for v in values.iter_mut() {
+                    if let FieldValue::Base(BaseValue::String(s)) = v {
+                        if let Ok(ip) = IpCidr::from_str(s) {
+                            *v = FieldValue::Cidr(ip);
+                        }
+                    }
+                }
+            }
+            #[cfg(not(feature = "cidr"))]
+            Some(FieldPolicy::Ip) => {
+                return Err(ParserError::DisabledModifier("cidr".to_string(), "cidr"))
+            }
+            _ => {}
+        }
+
+        // Hexadecimal and octal literals like `0x4D5A` are common for access masks and file
+        // permissions. Plain values also match integer event values, the values of `gt`, `gte`,
+        // `lt` and `lte` are compared as numbers only.
+        let numbers = if plain && !modifier.match_all && modifier.collection.is_none() {
+            values
+                .iter()
+                .filter_map(|v| match v {
+                    FieldValue::Base(BaseValue::String(s)) => BaseValue::from_radix_literal(s),
+                    _ => None,
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+        if modifier
+            .match_modifier
+            .as_ref()
+            .is_some_and(MatchModifier::is_order)
+        {
+            for v in values.iter_mut() {
+                if let FieldValue::Base(value) = v {
+                    let number = match value {
+                        BaseValue::String(s) => BaseValue::from_radix_literal(s),
+                        _ => None,
+                    };
+                    if let Some(number) = number {
+                        *value = number;
+                    }
+                }
+            }
+        }
+
+        let regex_max_input_len = match modifier.match_modifier {
+            Some(MatchModifier::Re) => config::with_active(|config| config.regex_max_input_len),
+            _ => None,
+        };
+
         let mut order_modifier_provided = false;
-        for v in self.values.iter_mut() {
-            match self.modifier.match_modifier {
-                Some(
-                    MatchModifier::StartsWith | MatchModifier::EndsWith | MatchModifier::Contains,
-                ) => {
-                    if !matches!(v, FieldValue::Base(BaseValue::String(_))) {
-                        return Err(ParserError::InvalidValueForStringModifier(
-                            self.name.to_string(),
-                        ));
+        for v in values.iter_mut() {
+            if modifier
+                .match_modifier
+                .as_ref()
+                .is_some_and(MatchModifier::is_string_match)
+                && !matches!(v, FieldValue::Base(BaseValue::String(_)))
+            {
+                match v {
+                    FieldValue::Base(
+                        value @ (BaseValue::Int(_)
+                        | BaseValue::Unsigned(_)
+                        | BaseValue::Float(_)
+                        | BaseValue::Boolean(_)),
+                    ) if spec_mode != SpecMode::Strict => {
+                        *v = FieldValue::from(value.value_to_string());
                     }
+                    _ => return Err(ParserError::InvalidValueForStringModifier(name.to_string())),
+                }
+            }
+
+            // Wildcards are never meant literally in addresses and numbers. Permissive rules may
+            // still compare with such strings, which never match as intended.
+            if let (
+                Some(
+                    modifier @ (MatchModifier::Cidr
+                    | MatchModifier::SubdomainOf
+                    | MatchModifier::Lt
+                    | MatchModifier::Lte
+                    | MatchModifier::Gt
+                    | MatchModifier::Gte),
+                ),
+                FieldValue::Base(BaseValue::String(s)),
+            ) = (&modifier.match_modifier, &v)
+            {
+                if s.contains(['*', '?'])
+                    && (matches!(modifier, MatchModifier::Cidr | MatchModifier::SubdomainOf)
+                        || spec_mode == SpecMode::Strict)
+                {
+                    return Err(ParserError::WildcardInValue(
+                        name.to_string(),
+                        modifier.to_string(),
+                        s.clone(),
+                    ));
                 }
+            }
+
+            match modifier.match_modifier {
+                #[cfg(feature = "cidr")]
                 Some(MatchModifier::Cidr) => match IpCidr::from_str(v.as_string()?.as_str()) {
                     Ok(ip) => *v = FieldValue::Cidr(ip),
                     Err(err) => return Err(IPParsing(v.as_string()?, err.to_string())),
                 },
-                Some(MatchModifier::Re) => match Regex::new(v.as_string()?.as_str()) {
-                    Ok(re) => *v = FieldValue::Regex(re),
-                    Err(err) => return Err(ParserError::RegexParsing(err)),
-                },
+                #[cfg(not(feature = "cidr"))]
+                Some(MatchModifier::Cidr) => {
+                    return Err(ParserError::DisabledModifier("cidr".to_string(), "cidr"))
+                }
+                Some(MatchModifier::Re) => {
+                    let mut pattern = v.as_string()?;
+                    if ignore_case {
+                        pattern.insert_str(0, "(?i)");
+                    }
+                    match config::with_active(|config| cache::regex(&pattern, config)) {
+                        Ok(re) => *v = FieldValue::Regex(re),
+                        Err(err) => return Err(ParserError::RegexParsing(err)),
+                    }
+                }
                 Some(
                     MatchModifier::Lt | MatchModifier::Lte | MatchModifier::Gt | MatchModifier::Gte,
                 ) => order_modifier_provided = true,
-                None => {}
+                // Domains are matched as plain strings, a trailing dot denotes the root zone
+                Some(MatchModifier::SubdomainOf) if !modifier.fieldref => {
+                    if let FieldValue::Base(BaseValue::String(s)) = v {
+                        if let Some(domain) = s.strip_suffix('.') {
+                            *s = domain.to_string();
+                        }
+                    }
+                }
+                _ => {}
             }
         }
 
-        if !self.modifier.fieldref && !order_modifier_provided {
-            for v in self.values.iter_mut() {
+        let regex_set =
+            config::with_active(|config| compile_regex_set(&values, &modifier, config))?;
+
+        let subdomain = modifier.match_modifier == Some(MatchModifier::SubdomainOf);
+        if !modifier.fieldref && !order_modifier_provided && !subdomain {
+            for v in values.iter_mut() {
                 if let FieldValue::Base(BaseValue::String(s)) = v {
-                    let mut tokens = tokenize(s, !self.modifier.cased);
-                    match self.modifier.match_modifier {
+                    let mut tokens = tokenize(s, !modifier.cased);
+                    match modifier.match_modifier {
                         Some(MatchModifier::StartsWith) => {
                             tokens.push(WildcardToken::Star);
                         }
@@ -159,16 +359,325 @@ impl Field {
                         _ => {}
                     }
 
-                    *v = FieldValue::WildcardPattern(tokens);
+                    *v = FieldValue::WildcardPattern(config::with_active(|config| {
+                        cache::wildcard_pattern(tokens, !modifier.cased, config)
+                    }));
                 }
             }
         }
 
+        // A path matches with either separator if any value may match, but not if all must
+        if policy == Some(FieldPolicy::Path) && !modifier.match_all && modifier.collection.is_none()
+        {
+            let variants: Vec<FieldValue> = values
+                .iter()
+                .filter_map(|v| match v {
+                    FieldValue::WildcardPattern(pattern) => swap_separators(pattern),
+                    _ => None,
+                })
+                .map(|tokens| {
+                    FieldValue::WildcardPattern(config::with_active(|config| {
+                        cache::wildcard_pattern(tokens, true, config)
+                    }))
+                })
+                .collect();
+            values.extend(variants);
+        }
+
+        Ok(Field {
+            name,
+            values,
+            modifier,
+            regex_set,
+            id: None,
+            regex_max_input_len,
+            source,
+            numbers,
+            live: None,
+        })
+    }
+}
+
+/// The tokens of a wildcard pattern with `\` and `/` swapped, or `None` if it contains neither
+fn swap_separators(tokens: &[WildcardToken]) -> Option<Vec<WildcardToken>> {
+    let mut swapped = false;
+    let result = tokens
+        .iter()
+        .map(|token| match token {
+            WildcardToken::Pattern(chars) => WildcardToken::Pattern(
+                chars
+                    .iter()
+                    .map(|c| match c {
+                        '\\' => {
+                            swapped = true;
+                            '/'
+                        }
+                        '/' => {
+                            swapped = true;
+                            '\\'
+                        }
+                        c => *c,
+                    })
+                    .collect(),
+            ),
+            token => token.clone(),
+        })
+        .collect();
+    swapped.then_some(result)
+}
+
+/// Compile all regular expressions of a field into a single set
+fn compile_regex_set(
+    values: &[FieldValue],
+    modifier: &Modifier,
+    config: &EngineConfig,
+) -> Result<Option<RegexSet>, ParserError> {
+    if values.len() <= 1 || modifier.fieldref {
+        return Ok(None);
+    }
+    let patterns: Option<Vec<&str>> = values
+        .iter()
+        .map(|v| match v {
+            FieldValue::Regex(re) => Some(re.as_str()),
+            _ => None,
+        })
+        .collect();
+    let Some(patterns) = patterns else {
+        return Ok(None);
+    };
+    RegexSetBuilder::new(patterns)
+        .size_limit(config.regex_size_limit)
+        .dfa_size_limit(config.regex_dfa_size_limit)
+        .build()
+        .map(Some)
+        .map_err(ParserError::RegexParsing)
+}
+
+/// Fields are equal if they have the same name, modifiers and compiled values. The id assigned
+/// by a rule set is not compared.
+impl PartialEq for Field {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.modifier == other.modifier
+            && self.values == other.values
+            && self.numbers == other.numbers
+            && self.regex_max_input_len == other.regex_max_input_len
+    }
+}
+
+/// Formats the field as the key of a selection, i.e. the name followed by the modifiers in
+/// their canonical order, e.g. `CommandLine|base64offset|contains`
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name)?;
+        for modifier in self.modifier.names() {
+            write!(f, "|{}", modifier)?;
+        }
         Ok(())
     }
+}
+
+/// Serializes the field as a mapping of its key to its values as written in the rule
+impl Serialize for Field {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(&self.to_string(), &self.to_yaml())?;
+        map.end()
+    }
+}
+
+impl Field {
+    pub(crate) fn new<S: AsRef<str>>(
+        name_with_modifiers: S,
+        values: Vec<FieldValue>,
+    ) -> Result<Field, ParserError> {
+        FieldBuilder::from_str(name_with_modifiers.as_ref())?.build(values)
+    }
+
+    pub(crate) fn from_yaml<S: AsRef<str>>(name: S, value: Value) -> Result<Field, ParserError> {
+        let field_values = match value {
+            Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Null => {
+                vec![FieldValue::try_from(value)?]
+            }
+            Value::Sequence(seq) => {
+                let mut result = Vec::with_capacity(seq.len());
+                for item in seq {
+                    result.push(FieldValue::try_from(item)?);
+                }
+                result
+            }
+            _ => return Err(InvalidYAML(format!("{:?}", value))),
+        };
+        Self::new(name, field_values)
+    }
+
+    /// Create a field from values that were already transformed and compiled by
+    /// [`FieldBuilder::build`], e.g. when a rule set is restored from a snapshot
+    #[cfg(feature = "snapshot")]
+    pub(crate) fn from_compiled(
+        name: String,
+        modifier: Modifier,
+        values: Vec<FieldValue>,
+        regex_max_input_len: Option<usize>,
+        source: Vec<BaseValue>,
+        numbers: Vec<BaseValue>,
+    ) -> Result<Field, ParserError> {
+        // The placeholders are resolved again with the lists of the active configuration
+        if modifier.expand {
+            let values = source.into_iter().map(FieldValue::Base).collect();
+            return FieldBuilder { name, modifier }.build(values);
+        }
+        let regex_set =
+            config::with_active(|config| compile_regex_set(&values, &modifier, config))?;
+        Ok(Self {
+            name,
+            values,
+            modifier,
+            regex_set,
+            id: None,
+            regex_max_input_len,
+            source,
+            numbers,
+            live: None,
+        })
+    }
 
-    pub(crate) fn evaluate(&self, event: &Event) -> bool {
-        let Some(event_value) = event.get(&self.name) else {
+    /// The name of the field in the event, without modifiers
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The names of the event fields the field reads: its own name and, with the `fieldref`
+    /// modifier, the names of the referenced fields
+    pub(crate) fn referenced_names(&self) -> impl Iterator<Item = String> + '_ {
+        let references: &[BaseValue] = if self.modifier.fieldref {
+            &self.source
+        } else {
+            &[]
+        };
+        std::iter::once(self.name.clone()).chain(references.iter().map(BaseValue::value_to_string))
+    }
+
+    /// The values of the field after they were transformed and compiled according to the
+    /// modifiers, see [`Field::to_yaml`] for the values as written in the rule
+    pub fn values(&self) -> &[FieldValue] {
+        &self.values
+    }
+
+    /// The values of the field as written in the rule, i.e. a single value or a list of values
+    ///
+    /// # Example
+    /// ```rust
+    /// use sigma_rust::{rule_from_yaml, Selection};
+    ///
+    /// let rule = rule_from_yaml(
+    ///     r#"
+    /// title: Encoded whoami
+    /// logsource:
+    /// detection:
+    ///     selection:
+    ///         CommandLine|base64offset|contains:
+    ///             - whoami
+    ///             - net user
+    ///     condition: selection
+    /// "#,
+    /// )
+    /// .unwrap();
+    /// let Selection::Field(groups) = &rule.detection.get_selections()["selection"] else {
+    ///     unreachable!()
+    /// };
+    /// let field = &groups[0].fields[0];
+    /// assert_eq!(field.to_string(), "CommandLine|base64offset|contains");
+    /// assert_eq!(
+    ///     field.to_yaml(),
+    ///     serde_yml::Value::from(vec!["whoami", "net user"])
+    /// );
+    /// ```
+    pub fn to_yaml(&self) -> Value {
+        match self.source.as_slice() {
+            [value] => Value::from(value),
+            values => Value::Sequence(values.iter().map(Value::from).collect()),
+        }
+    }
+
+    /// Returns a list of lowercase literals of which at least one must be contained in a
+    /// value of the event if the field matches. Returns `None` if no such list can be derived,
+    /// e.g. for regular expressions or numeric comparisons.
+    pub(crate) fn required_literals(&self) -> Option<Vec<String>> {
+        // Removing quotes while splitting arguments changes the value, so the literals of an
+        // argument are not necessarily contained in the raw event value, and neither are
+        // hexadecimal and octal literals in the integer event values they match
+        if self.modifier.exists.is_some()
+            || self.modifier.fieldref
+            || self.modifier.arguments
+            || !self.numbers.is_empty()
+        {
+            return None;
+        }
+
+        let mut result = Vec::with_capacity(self.values.len());
+        for value in &self.values {
+            match value {
+                FieldValue::WildcardPattern(pattern) => result.push(pattern.required_literal()?),
+                // The prefilter lowercases event values, and a subdomain ends with the domain
+                FieldValue::Base(BaseValue::String(domain))
+                    if !domain.is_empty()
+                        && self.modifier.match_modifier == Some(MatchModifier::SubdomainOf) =>
+                {
+                    result.push(domain.to_lowercase())
+                }
+                _ => return None,
+            }
+        }
+        Some(result)
+    }
+
+    /// Returns the name of the field if the field can only match when it is present in the event
+    pub(crate) fn required_field(&self) -> Option<String> {
+        match self.modifier.exists {
+            Some(false) => None,
+            _ => Some(self.name.clone()),
+        }
+    }
+
+    /// Whether the field requires a value to equal several different strings at once, e.g.
+    /// `Image|all: [a, b]`, which no single value can
+    pub(crate) fn is_contradictory(&self) -> bool {
+        let modifier = &self.modifier;
+        if !modifier.match_all
+            || modifier.match_modifier.is_some()
+            || modifier.fieldref
+            || modifier.exists.is_some()
+            || modifier.value_transformer.is_some()
+        {
+            return false;
+        }
+
+        let mut literals = vec![];
+        for value in &self.values {
+            match value {
+                FieldValue::WildcardPattern(pattern) => match &***pattern {
+                    [WildcardToken::Pattern(literal)] => literals.push(literal),
+                    _ => return false,
+                },
+                _ => return false,
+            }
+        }
+        literals.sort_unstable();
+        literals.dedup();
+        literals.len() > 1
+    }
+
+    #[cfg(test)]
+    pub(crate) fn evaluate(&self, event: &crate::event::Event) -> bool {
+        self.evaluate_in(&EvalContext::new(event))
+    }
+
+    pub(crate) fn evaluate_in(&self, ctx: &EvalContext) -> bool {
+        if let Some(live) = &self.live {
+            return live.current().is_some_and(|field| field.evaluate_in(ctx));
+        }
+        let Some(event_value) = ctx.get(self.id, &self.name) else {
             return matches!(self.modifier.exists, Some(false));
         };
 
@@ -176,33 +685,53 @@ impl Field {
             return true;
         };
 
-        let require_all = self.modifier.match_all || matches!(self.modifier.collection, Some(CollectionMatch::All));
+        // The arguments of interned fields are split once per event by the context
+        let arguments;
+        let event_value = match event_value {
+            EventValue::Value(BaseValue::String(s)) if self.modifier.arguments => {
+                match ctx.arguments(self.id, s) {
+                    Some(cached) => cached,
+                    None => {
+                        arguments = command_line_arguments(s);
+                        &arguments
+                    }
+                }
+            }
+            _ => event_value,
+        };
+
+        let require_all = self.modifier.match_all
+            || matches!(self.modifier.collection, Some(CollectionMatch::All));
+
+        if let Some(regex_set) = &self.regex_set {
+            return self.evaluate_regex_set(regex_set, event_value, require_all);
+        }
+
         let mut require_any_fired = false;
 
         for val in &self.values {
-            let cmp = if self.modifier.fieldref {
+            let fired = if self.modifier.fieldref {
                 let event_fieldref_value = if let FieldValue::Base(BaseValue::String(s)) = val {
-                    event.get(s)
+                    ctx.event().get(s)
                 } else if let FieldValue::Base(b) = val {
-                    event.get(b.value_to_string().as_str())
+                    ctx.event().get(b.value_to_string().as_str())
                 } else {
                     // Should never happen as we do not compile values if fieldref modifier is given
                     continue;
                 };
 
-                match event_fieldref_value {
-                    Some(EventValue::Value(v)) => &FieldValue::Base(v.clone()),
-                    _ => return false,
-                }
+                let Some(EventValue::Value(reference)) = event_fieldref_value else {
+                    return false;
+                };
+                Self::matches_any(event_value, |item| {
+                    item.matches_base(reference, &self.modifier)
+                })
+            } else if let Some(fired) = self.matches_folded(ctx, event_value, val) {
+                fired
             } else {
-                val
-            };
-
-            let fired = match event_value {
-                EventValue::Sequence(seq) => {
-                    seq.iter().any(|item| item.matches(cmp, &self.modifier))
-                }
-                _ => event_value.matches(cmp, &self.modifier),
+                Self::matches_any(event_value, |item| {
+                    !self.exceeds_regex_input_len(item) && item.matches(val, &self.modifier)
+                })
             };
 
             if fired {
@@ -215,17 +744,136 @@ impl Field {
             }
         }
 
+        if self.numbers.iter().any(|number| {
+            Self::matches_any(event_value, |item| {
+                item.matches_base(number, &self.modifier)
+            })
+        }) {
+            return true;
+        }
+
         require_all && require_any_fired
     }
+
+    /// Match a case-insensitive wildcard pattern against the event value lowercased once by
+    /// the evaluation context. Returns `None` if the pattern would not lowercase the value
+    /// by itself or the value cannot be cached.
+    fn matches_folded(
+        &self,
+        ctx: &EvalContext,
+        event_value: &EventValue,
+        val: &FieldValue,
+    ) -> Option<bool> {
+        if self.modifier.cased {
+            return None;
+        }
+        match (event_value, val) {
+            (EventValue::Value(BaseValue::String(s)), FieldValue::WildcardPattern(pattern))
+                if pattern.folds_case(s) =>
+            {
+                Some(pattern.is_match_folded(ctx.folded(self.id, s)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Sequences match if any of their items match
+    fn matches_any(event_value: &EventValue, matches: impl Fn(&EventValue) -> bool) -> bool {
+        match event_value {
+            EventValue::Sequence(seq) => seq.iter().any(matches),
+            _ => matches(event_value),
+        }
+    }
+
+    /// Whether a value is too long to be matched against the regular expressions of the field
+    fn exceeds_regex_input_len(&self, item: &EventValue) -> bool {
+        match (self.regex_max_input_len, item) {
+            (Some(max), EventValue::Value(v)) => v.value_as_str().len() > max,
+            _ => false,
+        }
+    }
+
+    fn evaluate_regex_set(
+        &self,
+        regex_set: &RegexSet,
+        event_value: &EventValue,
+        require_all: bool,
+    ) -> bool {
+        let items = match event_value {
+            EventValue::Sequence(seq) => seq.as_slice(),
+            _ => std::slice::from_ref(event_value),
+        };
+
+        // Tracking which regexes fired is only needed if all of them must match
+        let mut fired = if require_all {
+            vec![false; regex_set.len()]
+        } else {
+            vec![]
+        };
+        for item in items {
+            // We currently do not support matching against nested lists and hashmaps
+            let EventValue::Value(v) = item else {
+                continue;
+            };
+            if self.exceeds_regex_input_len(item) {
+                continue;
+            }
+            let haystack = v.value_as_str();
+            if !require_all {
+                if regex_set.is_match(&haystack) {
+                    return true;
+                }
+                continue;
+            }
+            regex_set.matches(&haystack).iter().for_each(|i| {
+                if let Some(fired) = fired.get_mut(i) {
+                    *fired = true;
+                }
+            });
+        }
+
+        require_all && fired.into_iter().all(|f| f)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::event::Event;
+    use crate::wildcard::fold_case;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_required_literals() {
+        let field = Field::new(
+            "test|contains",
+            vec![FieldValue::from("Hello"), FieldValue::from("a*world")],
+        )
+        .unwrap();
+        assert_eq!(
+            field.required_literals(),
+            Some(vec!["hello".to_string(), "world".to_string()])
+        );
+
+        let field = Field::new("test|cased", vec![FieldValue::from("Hello")]).unwrap();
+        assert_eq!(field.required_literals(), Some(vec!["hello".to_string()]));
+
+        let field = Field::new("test", vec![FieldValue::from("a"), FieldValue::from("*")]).unwrap();
+        assert_eq!(field.required_literals(), None);
+
+        let field = Field::new("test|re", vec![FieldValue::from("abc")]).unwrap();
+        assert_eq!(field.required_literals(), None);
+
+        let field = Field::new("test", vec![FieldValue::from(42)]).unwrap();
+        assert_eq!(field.required_literals(), None);
+
+        let field = Field::new("test|fieldref", vec![FieldValue::from("other")]).unwrap();
+        assert_eq!(field.required_literals(), None);
+    }
 
     #[test]
     fn test_parse_name_only() {
-        let field = Field::from_str("a").unwrap();
+        let field = FieldBuilder::from_str("a").unwrap();
         assert_eq!(field.name, "a");
         assert!(field.modifier.match_modifier.is_none());
         assert!(field.modifier.value_transformer.is_none());
@@ -234,7 +882,7 @@ mod tests {
 
     #[test]
     fn test_parse_contains_modifier() {
-        let field = Field::from_str("hello|contains").unwrap();
+        let field = FieldBuilder::from_str("hello|contains").unwrap();
         assert_eq!(field.name, "hello");
         assert_eq!(
             field.modifier.match_modifier.unwrap(),
@@ -246,23 +894,25 @@ mod tests {
 
     #[test]
     fn test_parse_value_transformer_modifier() {
-        let field = Field::from_str("hello|windash|contains").unwrap();
+        let field = FieldBuilder::from_str("hello|windash|contains").unwrap();
         assert_eq!(field.name, "hello");
         assert_eq!(field.modifier.match_modifier, Some(MatchModifier::Contains));
         assert_eq!(field.modifier.value_transformer, Some(Windash));
     }
 
+    #[cfg(feature = "base64")]
     #[test]
     fn test_parse_base64_modifier() {
-        let field = Field::from_str("hello|base64|endswith").unwrap();
+        let field = FieldBuilder::from_str("hello|base64|endswith").unwrap();
         assert_eq!(field.name, "hello");
         assert_eq!(field.modifier.match_modifier, Some(MatchModifier::EndsWith));
         assert_eq!(field.modifier.value_transformer, Some(Base64(None)));
     }
 
+    #[cfg(feature = "base64")]
     #[test]
     fn test_parse_utf16_modifier() {
-        let field = Field::from_str("hello|base64offset|utf16le|endswith").unwrap();
+        let field = FieldBuilder::from_str("hello|base64offset|utf16le|endswith").unwrap();
         assert_eq!(field.name, "hello");
         assert_eq!(field.modifier.match_modifier, Some(MatchModifier::EndsWith));
         assert_eq!(
@@ -305,19 +955,16 @@ mod tests {
 
     #[test]
     fn test_evaluate_startswith() {
-        let mut field = Field::new(
-            "test|startswith",
-            vec![
-                FieldValue::from("zsh"),
-                FieldValue::from("bash"),
-                FieldValue::from("pwsh"),
-            ],
-        )
-        .unwrap();
+        let values = vec![
+            FieldValue::from("zsh"),
+            FieldValue::from("bash"),
+            FieldValue::from("pwsh"),
+        ];
+        let field = Field::new("test|startswith", values.clone()).unwrap();
         let event = Event::from([("test", "zsh shutdown")]);
         assert!(field.evaluate(&event));
 
-        field.modifier.match_all = true;
+        let field = Field::new("test|startswith|all", values).unwrap();
         assert!(!field.evaluate(&event));
     }
 
@@ -389,12 +1036,12 @@ mod tests {
 
     #[test]
     fn test_evaluate_lt() {
-        let mut field =
-            Field::new("test|lt", vec![FieldValue::from(10), FieldValue::from(15)]).unwrap();
+        let values = vec![FieldValue::from(10), FieldValue::from(15)];
+        let field = Field::new("test|lt", values.clone()).unwrap();
         let event = Event::from([("test", 10)]);
         assert!(field.evaluate(&event));
 
-        field.modifier.match_all = true;
+        let field = Field::new("test|lt|all", values).unwrap();
         assert!(!field.evaluate(&event));
     }
 
@@ -414,59 +1061,55 @@ mod tests {
 
     #[test]
     fn test_evaluate_lte() {
-        let mut field =
-            Field::new("test|lte", vec![FieldValue::from(15), FieldValue::from(20)]).unwrap();
+        let values = vec![FieldValue::from(15), FieldValue::from(20)];
+        let field = Field::new("test|lte", values.clone()).unwrap();
         let event = Event::from([("test", 15)]);
         assert!(field.evaluate(&event));
 
-        field.modifier.match_all = true;
+        let field = Field::new("test|lte|all", values).unwrap();
         assert!(field.evaluate(&event));
     }
 
     #[test]
     fn test_evaluate_gt() {
-        let mut field = Field::new("test|gt", vec![FieldValue::from(10.1)]).unwrap();
+        let field = Field::new("test|gt", vec![FieldValue::from(10.1)]).unwrap();
         let event = Event::from([("test", 10.2)]);
         assert!(field.evaluate(&event));
 
-        field.modifier.match_all = true;
+        let field = Field::new("test|gt|all", vec![FieldValue::from(10.1)]).unwrap();
         assert!(field.evaluate(&event));
     }
 
     #[test]
     fn test_evaluate_gte() {
-        let mut field =
-            Field::new("test|gte", vec![FieldValue::from(15), FieldValue::from(10)]).unwrap();
+        let mut values = vec![FieldValue::from(15), FieldValue::from(10)];
+        let field = Field::new("test|gte", values.clone()).unwrap();
         let event = Event::from([("test", 15)]);
         assert!(field.evaluate(&event));
 
-        field.modifier.match_all = true;
-        assert!(field.evaluate(&event));
-
-        field.modifier.match_all = false;
+        let all = Field::new("test|gte|all", values.clone()).unwrap();
+        assert!(all.evaluate(&event));
 
         // We enforce strict type checking, so 15.0 will fail to compare against the int values
         let event = Event::from([("test", 14.0)]);
         assert!(!field.evaluate(&event));
 
         // If we add a float it will work though
-        field.values.push(FieldValue::from(12.34));
+        values.push(FieldValue::from(12.34));
+        let field = Field::new("test|gte", values.clone()).unwrap();
         assert!(field.evaluate(&event));
 
-        field.modifier.match_all = true;
-        assert!(!field.evaluate(&event));
+        let all = Field::new("test|gte|all", values).unwrap();
+        assert!(!all.evaluate(&event));
     }
 
     #[test]
     fn test_evaluate_regex() {
-        let mut field = Field::new(
-            "test|re",
-            vec![
-                FieldValue::from(r"hello (.*)d"),
-                FieldValue::from(r"goodbye (.*)"),
-            ],
-        )
-        .unwrap();
+        let values = vec![
+            FieldValue::from(r"hello (.*)d"),
+            FieldValue::from(r"goodbye (.*)"),
+        ];
+        let field = Field::new("test|re", values.clone()).unwrap();
 
         for val in &field.values {
             assert!(matches!(val, FieldValue::Regex(_)));
@@ -475,42 +1118,368 @@ mod tests {
         let event = Event::from([("test", "hello world")]);
         assert!(field.evaluate(&event));
 
-        field.modifier.match_all = true;
+        let field = Field::new("test|re|all", values).unwrap();
         assert!(!field.evaluate(&event));
     }
 
+    #[test]
+    fn test_evaluate_regex_set() {
+        let field = Field::new(
+            "test|re",
+            vec![FieldValue::from(r"^hello"), FieldValue::from(r"world$")],
+        )
+        .unwrap();
+        assert_eq!(field.regex_set.as_ref().unwrap().len(), 2);
+
+        assert!(field.evaluate(&Event::from([("test", "hello there")])));
+        assert!(field.evaluate(&Event::from([("test", "brave new world")])));
+        assert!(!field.evaluate(&Event::from([("test", "world hello")])));
+        assert!(!field.evaluate(&Event::from([("test", 42)])));
+
+        let field = Field::new(
+            "test|re|all",
+            vec![FieldValue::from(r"^hello"), FieldValue::from(r"world$")],
+        )
+        .unwrap();
+        assert!(field.evaluate(&Event::from([("test", "hello world")])));
+        assert!(!field.evaluate(&Event::from([("test", "hello there")])));
+
+        let mut event = Event::new();
+        event.insert(
+            "test",
+            EventValue::Sequence(vec![
+                EventValue::from("hello there"),
+                EventValue::from("brave new world"),
+            ]),
+        );
+        assert!(field.evaluate(&event));
+
+        let field = Field::new("test|re", vec![FieldValue::from(r"^hello")]).unwrap();
+        assert!(field.regex_set.is_none());
+    }
+
     #[test]
     fn test_invalid_regex() {
         let err = Field::new("test|re", vec![FieldValue::from(r"[")]).unwrap_err();
         assert!(matches!(err, ParserError::RegexParsing(_)));
     }
 
+    #[cfg(feature = "cidr")]
     #[test]
     fn test_cidr() {
-        let cidrs = ["10.0.0.0/16", "10.0.0.0/24"];
-        let mut field = Field::new(
-            "test|cidr",
-            cidrs.into_iter().map(FieldValue::from).collect(),
-        )
-        .unwrap();
+        let cidrs: Vec<FieldValue> = ["10.0.0.0/16", "10.0.0.0/24"]
+            .into_iter()
+            .map(FieldValue::from)
+            .collect();
+        let field = Field::new("test|cidr", cidrs.clone()).unwrap();
+        let all = Field::new("test|cidr|all", cidrs).unwrap();
 
         let event = Event::from([("test", "10.0.1.1")]);
         assert!(field.evaluate(&event));
-        field.modifier.match_all = true;
-
-        assert!(!field.evaluate(&event));
+        assert!(!all.evaluate(&event));
 
         let event = Event::from([("test", "10.1.2.3")]);
-        field.modifier.match_all = false;
         assert!(!field.evaluate(&event));
     }
 
+    #[test]
+    fn test_subdomain_of() {
+        let field = Field::new(
+            "QueryName|subdomainof",
+            vec![FieldValue::from("example.com.")],
+        )
+        .unwrap();
+        for name in ["example.com", "WWW.Example.COM.", "a.b.example.com"] {
+            assert!(
+                field.evaluate(&Event::from([("QueryName", name)])),
+                "{}",
+                name
+            );
+        }
+        for name in ["badexample.com", "example.com.evil.net", "com", ".com"] {
+            assert!(
+                !field.evaluate(&Event::from([("QueryName", name)])),
+                "{}",
+                name
+            );
+        }
+        assert_eq!(
+            field.required_literals(),
+            Some(vec!["example.com".to_string()])
+        );
+
+        let fieldref = Field::new(
+            "QueryName|fieldref|subdomainof",
+            vec![FieldValue::from("Zone")],
+        )
+        .unwrap();
+        let event = Event::from([("QueryName", "mail.corp.local"), ("Zone", "Corp.Local.")]);
+        assert!(fieldref.evaluate(&event));
+
+        let err = Field::new(
+            "QueryName|subdomainof",
+            vec![FieldValue::from("*.example.com")],
+        )
+        .unwrap_err();
+        assert!(matches!(err, ParserError::WildcardInValue(..)));
+        let err = Field::new("QueryName|subdomainof", vec![FieldValue::from(42)]).unwrap_err();
+        assert!(matches!(err, ParserError::InvalidValueForStringModifier(_)));
+    }
+
+    #[cfg(feature = "cidr")]
     #[test]
     fn test_cidr_invalid_ip() {
         let err = Field::new("test|cidr", vec![FieldValue::from("1.2.3.4.5.6/16")]).unwrap_err();
         assert!(matches!(err, IPParsing(_, _)));
     }
 
+    #[cfg(feature = "cidr")]
+    #[test]
+    fn test_wildcard_in_value() {
+        let err = Field::new("test|cidr", vec![FieldValue::from("10.*")]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The value '10.*' of field 'test' contains a wildcard, which the modifier 'cidr' does not support"
+        );
+        let err = Field::new("test|gte", vec![FieldValue::from("1?")]).unwrap_err();
+        assert!(matches!(err, ParserError::WildcardInValue(..)));
+
+        let config = EngineConfig {
+            spec_mode: SpecMode::Permissive,
+            ..Default::default()
+        };
+        config.scoped(|| {
+            assert!(Field::new("test|gte", vec![FieldValue::from("1?")]).is_ok());
+            assert!(Field::new("test|cidr", vec![FieldValue::from("10.*")]).is_err());
+        });
+    }
+
+    fn with_policy<R>(field: &str, policy: FieldPolicy, f: impl FnOnce() -> R) -> R {
+        let config = EngineConfig {
+            field_policies: [(field.to_string(), policy)].into(),
+            ..Default::default()
+        };
+        config.scoped(f)
+    }
+
+    #[test]
+    fn test_case_insensitive_policy() {
+        let (cased, re) = with_policy("Image", FieldPolicy::CaseInsensitive, || {
+            (
+                Field::new(
+                    "Image|endswith|cased",
+                    vec![FieldValue::from("\\WHOAMI.exe")],
+                )
+                .unwrap(),
+                Field::new("Image|re", vec![FieldValue::from("whoami\\.exe$")]).unwrap(),
+            )
+        });
+        let event = Event::from([("Image", "C:\\Windows\\whoami.EXE")]);
+        assert!(cased.evaluate(&event));
+        assert!(re.evaluate(&event));
+        assert_eq!(cased.to_string(), "Image|endswith");
+
+        let other = with_policy("User", FieldPolicy::CaseInsensitive, || {
+            Field::new(
+                "Image|endswith|cased",
+                vec![FieldValue::from("\\WHOAMI.exe")],
+            )
+            .unwrap()
+        });
+        assert!(!other.evaluate(&event));
+    }
+
+    #[test]
+    fn test_path_policy() {
+        let field = with_policy("Image", FieldPolicy::Path, || {
+            Field::new("Image|contains", vec![FieldValue::from("\\Temp\\A")]).unwrap()
+        });
+        assert!(field.evaluate(&Event::from([("Image", "C:\\temp\\a.exe")])));
+        assert!(field.evaluate(&Event::from([("Image", "/tmp/TEMP/a.exe")])));
+        assert!(!field.evaluate(&Event::from([("Image", "C:\\Temp.exe")])));
+
+        // All values must match the path as written
+        let field = with_policy("Image", FieldPolicy::Path, || {
+            Field::new(
+                "Image|contains|all",
+                vec![FieldValue::from("\\Temp"), FieldValue::from("A.exe")],
+            )
+            .unwrap()
+        });
+        assert_eq!(field.values.len(), 2);
+        assert!(field.evaluate(&Event::from([("Image", "C:\\temp\\a.exe")])));
+        assert!(!field.evaluate(&Event::from([("Image", "/temp/a.exe")])));
+    }
+
+    #[test]
+    fn test_numeric_policy() {
+        let (equals, gte) = with_policy("EventID", FieldPolicy::Numeric, || {
+            (
+                Field::new(
+                    "EventID",
+                    vec![FieldValue::from("4688"), FieldValue::from("46*")],
+                )
+                .unwrap(),
+                Field::new("EventID|gte", vec![FieldValue::from(4000)]).unwrap(),
+            )
+        });
+        for value in [
+            BaseValue::from("4688"),
+            BaseValue::from(" 4688 "),
+            BaseValue::Int(4688),
+            BaseValue::Unsigned(4688),
+            BaseValue::Float(4688.0),
+        ] {
+            let mut event = Event::new();
+            event.insert("EventID", value.clone());
+            assert!(equals.evaluate(&event), "{:?}", value);
+            assert!(gte.evaluate(&event), "{:?}", value);
+        }
+        // Values that are not numbers are still matched as strings
+        assert!(equals.evaluate(&Event::from([("EventID", "46xx")])));
+        let event = Event::from([("EventID", "39xx")]);
+        assert!(!equals.evaluate(&event));
+        assert!(!gte.evaluate(&event));
+
+        let strict = Field::new("EventID", vec![FieldValue::from(4688)]).unwrap();
+        assert!(!strict.evaluate(&Event::from([("EventID", "4688")])));
+    }
+
+    #[test]
+    fn test_arguments_policy() {
+        let (plain, startswith) = with_policy("CommandLine", FieldPolicy::Arguments, || {
+            (
+                Field::new("CommandLine", vec![FieldValue::from("-enc")]).unwrap(),
+                Field::new(
+                    "CommandLine|startswith",
+                    vec![FieldValue::from("C:\\Program Files\\")],
+                )
+                .unwrap(),
+            )
+        });
+        assert!(plain.evaluate(&Event::from([("CommandLine", "powershell -ENC x")])));
+        assert!(!plain.evaluate(&Event::from([("CommandLine", "C:\\-enc\\x.exe")])));
+        assert!(!plain.evaluate(&Event::from([("CommandLine", "powershell -encx")])));
+        assert!(plain.required_literals().is_none());
+
+        // Quoted arguments are matched without their quotes
+        let event = Event::from([("CommandLine", r#"cmd /c "C:\Program Files\x.exe""#)]);
+        assert!(startswith.evaluate(&event));
+        let event = Event::from([("CommandLine", r#"cmd /c "C:\Program" Files\x.exe"#)]);
+        assert!(!startswith.evaluate(&event));
+
+        // Without the policy, values are matched against the whole command line
+        let substring = Field::new("CommandLine|contains", vec![FieldValue::from("-enc")]).unwrap();
+        assert!(substring.evaluate(&Event::from([("CommandLine", "C:\\-enc\\x.exe")])));
+    }
+
+    #[test]
+    fn test_radix_literals() {
+        let field = Field::new(
+            "AccessMask",
+            vec![FieldValue::from("0x100"), FieldValue::from("0o755")],
+        )
+        .unwrap();
+        for value in [
+            BaseValue::Int(256),
+            BaseValue::Int(0o755),
+            BaseValue::from("0x100"),
+            BaseValue::from("0X100"),
+        ] {
+            let mut event = Event::new();
+            event.insert("AccessMask", value.clone());
+            assert!(field.evaluate(&event), "{:?}", value);
+        }
+        assert!(!field.evaluate(&Event::from([("AccessMask", "256")])));
+        assert!(!field.evaluate(&Event::from([("AccessMask", 257)])));
+        // The literals are kept as written
+        assert_eq!(field.to_yaml(), Value::from(vec!["0x100", "0o755"]));
+
+        let gte = Field::new("Mode|gte", vec![FieldValue::from("0o700")]).unwrap();
+        assert!(gte.evaluate(&Event::from([("Mode", 0o755)])));
+        assert!(!gte.evaluate(&Event::from([("Mode", 0o644)])));
+
+        // Backends only see the literals
+        assert_eq!(field.values().len(), 2);
+
+        // A value of `all` cannot match both a string and an integer
+        let all = Field::new("AccessMask|all", vec![FieldValue::from("0x100")]).unwrap();
+        assert!(!all.evaluate(&Event::from([("AccessMask", 256)])));
+        let contains = Field::new("AccessMask|contains", vec![FieldValue::from("0x1")]).unwrap();
+        assert!(!contains.evaluate(&Event::from([("AccessMask", 256)])));
+    }
+
+    #[cfg(feature = "cidr")]
+    #[test]
+    fn test_ip_policy() {
+        let field = with_policy("SourceIp", FieldPolicy::Ip, || {
+            Field::new(
+                "SourceIp",
+                vec![
+                    FieldValue::from("::1"),
+                    FieldValue::from("10.0.0.0/8"),
+                    FieldValue::from("unknown*"),
+                ],
+            )
+            .unwrap()
+        });
+        for (ip, expected) in [
+            ("0:0::1", true),
+            ("10.1.2.3", true),
+            ("11.0.0.1", false),
+            ("unknown-host", true),
+        ] {
+            assert_eq!(
+                field.evaluate(&Event::from([("SourceIp", ip)])),
+                expected,
+                "{}",
+                ip
+            );
+        }
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn test_display() {
+        for (key, values, expected) in [
+            ("Image|endswith", "'\\whoami.exe'", "Image|endswith"),
+            (
+                "CommandLine|Contains|All",
+                "[a, b]",
+                "CommandLine|contains|all",
+            ),
+            (
+                "CommandLine|base64offset|contains",
+                "[whoami, 'net user']",
+                "",
+            ),
+            ("EventID", "4688", ""),
+            ("Port|gte", "[1024, 1.5]", ""),
+            ("User", "null", ""),
+            ("Hidden|exists", "false", ""),
+            ("Target|fieldref", "Source", ""),
+        ] {
+            let expected = if expected.is_empty() { key } else { expected };
+            let config = EngineConfig {
+                spec_mode: SpecMode::Permissive,
+                ..Default::default()
+            };
+            let values: Value = serde_yml::from_str(values).unwrap();
+            let field = config.scoped(|| Field::from_yaml(key, values.clone()).unwrap());
+            assert_eq!(field.to_string(), expected);
+            assert_eq!(field.to_yaml(), values);
+
+            let yaml = serde_yml::to_string(&field).unwrap();
+            let mapping: serde_yml::Mapping = serde_yml::from_str(&yaml).unwrap();
+            let (key, values) = mapping.into_iter().next().unwrap();
+            let reparsed = Field::from_yaml(key.as_str().unwrap(), values).unwrap();
+            assert_eq!(reparsed.to_string(), expected);
+            assert_eq!(reparsed.modifier, field.modifier);
+            assert_eq!(reparsed.values.len(), field.values.len());
+        }
+    }
+
+    #[cfg(feature = "base64")]
     #[test]
     fn test_base64_utf16le() {
         let patterns = ["Add-MpPreference ", "Set-MpPreference "];
@@ -536,6 +1505,7 @@ mod tests {
         assert!(field.evaluate(&event));
     }
 
+    #[cfg(feature = "base64")]
     #[test]
     fn test_base64offset_utf16le() {
         let patterns = [
@@ -708,4 +1678,106 @@ mod tests {
         let event = Event::from([("value", "abcdefg"), ("reference", "cde")]);
         assert!(field.evaluate(&event));
     }
+
+    #[test]
+    fn test_match_folded() {
+        let mut field = Field::new(
+            "value|contains",
+            vec![FieldValue::from("ÄB*C"), FieldValue::from("straße")],
+        )
+        .unwrap();
+        field.id = Some(0);
+
+        for (value, expected) in [("xäbxcx", true), ("STRAßE", true), ("ÄBX", false)] {
+            let event = Event::from([("value", value)]);
+            let ctx = EvalContext::with_fields(&event, 1);
+            assert_eq!(field.evaluate_in(&ctx), expected, "{}", value);
+            assert_eq!(ctx.folded(Some(0), ""), Some(fold_case(value).as_str()));
+        }
+
+        let mut field = Field::new(
+            "value|contains|cased",
+            vec![FieldValue::from("ÄB*C"), FieldValue::from("straße")],
+        )
+        .unwrap();
+        field.id = Some(0);
+        let event = Event::from([("value", "xäbc")]);
+        let ctx = EvalContext::with_fields(&event, 1);
+        assert!(!field.evaluate_in(&ctx));
+        // Cased values are not lowercased
+        assert_eq!(ctx.folded(Some(0), ""), Some(""));
+    }
+
+    #[test]
+    fn test_evaluate_is_total() {
+        let modifiers = [
+            "",
+            "|contains",
+            "|startswith",
+            "|endswith",
+            "|re",
+            "|cidr",
+            "|gt",
+            "|lte",
+            "|exists",
+            "|cased",
+            "|all",
+            "|fieldref",
+            "|fieldref|contains",
+            "|windash",
+            "|base64offset|contains",
+            "|utf16le|base64",
+            "|contains|all|cased",
+        ];
+        let values: Vec<fn() -> FieldValue> = vec![
+            || FieldValue::from(""),
+            || FieldValue::from("*"),
+            || FieldValue::from("?"),
+            || FieldValue::from("\\*K\u{212a}ß"),
+            || FieldValue::from("10.0.0.0/8"),
+            || FieldValue::from("-a"),
+            || FieldValue::from("other"),
+            || FieldValue::from(i64::MIN),
+            || FieldValue::from(u64::MAX),
+            || FieldValue::from(f64::NAN),
+            || FieldValue::from(true),
+            || FieldValue::Base(BaseValue::Null),
+        ];
+        let event_values = || {
+            vec![
+                EventValue::from(""),
+                EventValue::from("\u{212a}\u{130}ẞ a*b?c 10.1.2.3 ::1"),
+                EventValue::from("x".repeat(1000)),
+                EventValue::from(-1),
+                EventValue::from(u64::MAX),
+                EventValue::from(f64::INFINITY),
+                EventValue::from(f64::NAN),
+                EventValue::from(false),
+                EventValue::from(None),
+                EventValue::Sequence(vec![]),
+                EventValue::Sequence(vec![EventValue::from("a"), EventValue::Sequence(vec![])]),
+                EventValue::Map(HashMap::from([("a".to_string(), EventValue::from(1))])),
+            ]
+        };
+
+        for modifier in modifiers {
+            for value in &values {
+                let Ok(mut field) = Field::new(format!("test{}", modifier), vec![value()]) else {
+                    continue;
+                };
+                for event_value in event_values() {
+                    for other in event_values() {
+                        let mut event = Event::new();
+                        event.insert("test", event_value.clone());
+                        event.insert("other", other);
+                        field.id = None;
+                        field.evaluate(&event);
+                        // Ids of another rule set must not be trusted either
+                        field.id = Some(7);
+                        field.evaluate_in(&EvalContext::with_fields(&event, 1));
+                    }
+                }
+            }
+        }
+    }
 }
@@ -1,9 +1,102 @@
+use crate::config::{self, SpecMode};
 use crate::error::ParserError;
 use crate::field::ValueTransformer::{Base64, Base64offset};
+use std::fmt;
 use std::str::FromStr;
 use strum::{Display, EnumString};
 
-#[derive(Debug, PartialEq, Display, EnumString)]
+/// All field modifiers, in lowercase. `expand` is resolved by processing pipelines or matches
+/// the placeholder lists of the engine config.
+pub(crate) const MODIFIERS: &[&str] = &[
+    "all",
+    "any",
+    "base64",
+    "base64offset",
+    "cased",
+    "cidr",
+    "contains",
+    "endswith",
+    "exists",
+    "expand",
+    "fieldref",
+    "gt",
+    "gte",
+    "lt",
+    "lte",
+    "re",
+    "startswith",
+    "subdomainof",
+    "utf16",
+    "utf16be",
+    "utf16le",
+    "wide",
+    "windash",
+];
+
+/// The modifiers closest to an unknown modifier by edit distance, if any is close enough to be
+/// a likely typo
+pub(crate) fn suggest_modifiers(unknown: &str) -> Vec<&'static str> {
+    let unknown = unknown.to_lowercase();
+    let max_distance = (unknown.chars().count() / 3).clamp(1, 2);
+    let distances: Vec<(usize, &str)> = MODIFIERS
+        .iter()
+        .map(|modifier| (edit_distance(&unknown, modifier), *modifier))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    let Some(min) = distances.iter().map(|(distance, _)| *distance).min() else {
+        return vec![];
+    };
+    distances
+        .into_iter()
+        .filter(|(distance, _)| *distance == min)
+        .map(|(_, modifier)| modifier)
+        .collect()
+}
+
+/// The " did you mean ..." hint of the message of an unknown modifier
+pub(crate) fn did_you_mean(unknown: &str) -> String {
+    let suggestions: Vec<String> = suggest_modifiers(unknown)
+        .into_iter()
+        .map(|modifier| format!("'{}'", modifier))
+        .collect();
+    match suggestions.split_last() {
+        None => String::new(),
+        Some((last, [])) => format!(", did you mean {}?", last),
+        Some((last, rest)) => format!(", did you mean {} or {}?", rest.join(", "), last),
+    }
+}
+
+/// The optimal string alignment distance, i.e. the Levenshtein distance that also counts the
+/// transposition of adjacent characters as a single edit
+// Only used for error messages while parsing, all indices are within the dimensions of the table
+#[allow(clippy::indexing_slicing)]
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+#[derive(Debug, Clone, PartialEq, Display, EnumString)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
 #[strum(serialize_all = "lowercase")]
 pub enum MatchModifier {
     Contains,
@@ -15,18 +108,34 @@ pub enum MatchModifier {
     Lte,
     Re,
     Cidr,
+    /// The event value is a DNS name equal to the value or one of its subdomains
+    SubdomainOf,
 }
 
 impl MatchModifier {
+    /// Whether the modifier requires string values: `contains`, `startswith`, `endswith` or
+    /// `subdomainof`
     pub(crate) fn is_string_match(&self) -> bool {
         matches!(
             self,
-            MatchModifier::Contains | MatchModifier::StartsWith | MatchModifier::EndsWith
+            MatchModifier::Contains
+                | MatchModifier::StartsWith
+                | MatchModifier::EndsWith
+                | MatchModifier::SubdomainOf
+        )
+    }
+
+    /// Whether the modifier compares the order of values: `gt`, `gte`, `lt` or `lte`
+    pub(crate) fn is_order(&self) -> bool {
+        matches!(
+            self,
+            MatchModifier::Gt | MatchModifier::Gte | MatchModifier::Lt | MatchModifier::Lte
         )
     }
 }
 
-#[derive(Debug, PartialEq, Display)]
+#[derive(Debug, Clone, PartialEq, Display)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
 pub enum Utf16Modifier {
     Utf16le,
     Utf16be,
@@ -34,7 +143,8 @@ pub enum Utf16Modifier {
     Wide,
 }
 
-#[derive(Debug, PartialEq, Display, EnumString)]
+#[derive(Debug, Clone, PartialEq, Display, EnumString)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
 #[strum(serialize_all = "lowercase")]
 pub enum ValueTransformer {
     Base64(Option<Utf16Modifier>),
@@ -42,14 +152,16 @@ pub enum ValueTransformer {
     Windash,
 }
 
-#[derive(Debug, PartialEq, Display, EnumString)]
+#[derive(Debug, Clone, PartialEq, Display, EnumString)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
 #[strum(serialize_all = "lowercase")]
 pub enum CollectionMatch {
     Any,
     All,
 }
 
-#[derive(Debug, Default, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
 pub struct Modifier {
     pub(crate) match_all: bool,
     pub(crate) collection: Option<CollectionMatch>,
@@ -58,13 +170,79 @@ pub struct Modifier {
     pub(crate) exists: Option<bool>,
     pub(crate) match_modifier: Option<MatchModifier>,
     pub(crate) value_transformer: Option<ValueTransformer>,
+    /// Numbers are compared numerically across types, set by [`crate::FieldPolicy::Numeric`]
+    pub(crate) numeric: bool,
+    /// String values are split into arguments, set by [`crate::FieldPolicy::Arguments`]
+    pub(crate) arguments: bool,
+    /// The values are placeholders of [`crate::placeholder::PlaceholderList`]s
+    pub(crate) expand: bool,
+}
+
+impl Modifier {
+    /// The names of the modifiers in their canonical order: transformations of the values
+    /// first, then how they are matched
+    pub(crate) fn names(&self) -> Vec<&'static str> {
+        let mut result = vec![];
+        if self.expand {
+            result.push("expand");
+        }
+        let (transformer, utf16) = match &self.value_transformer {
+            Some(Base64(utf16)) => (Some("base64"), utf16.as_ref()),
+            Some(Base64offset(utf16)) => (Some("base64offset"), utf16.as_ref()),
+            Some(ValueTransformer::Windash) => (Some("windash"), None),
+            None => (None, None),
+        };
+        result.extend(utf16.map(|utf16| match utf16 {
+            Utf16Modifier::Utf16le => "utf16le",
+            Utf16Modifier::Utf16be => "utf16be",
+            Utf16Modifier::Utf16 => "utf16",
+            Utf16Modifier::Wide => "wide",
+        }));
+        result.extend(transformer);
+        if self.fieldref {
+            result.push("fieldref");
+        }
+        result.extend(self.match_modifier.as_ref().map(|m| match m {
+            MatchModifier::Contains => "contains",
+            MatchModifier::StartsWith => "startswith",
+            MatchModifier::EndsWith => "endswith",
+            MatchModifier::Gt => "gt",
+            MatchModifier::Gte => "gte",
+            MatchModifier::Lt => "lt",
+            MatchModifier::Lte => "lte",
+            MatchModifier::Re => "re",
+            MatchModifier::Cidr => "cidr",
+            MatchModifier::SubdomainOf => "subdomainof",
+        }));
+        if self.cased {
+            result.push("cased");
+        }
+        match self.collection {
+            Some(CollectionMatch::All) => result.push("all"),
+            Some(CollectionMatch::Any) => result.push("any"),
+            None if self.match_all => result.push("all"),
+            None => {}
+        }
+        if self.exists.is_some() {
+            result.push("exists");
+        }
+        result
+    }
+}
+
+/// Formats the modifiers in their canonical order separated by `|`, e.g. `base64offset|contains`.
+/// A field without modifiers formats as an empty string.
+impl fmt::Display for Modifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.names().join("|"))
+    }
 }
 
 impl FromStr for Utf16Modifier {
     type Err = ParserError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
+        match s {
             "utf16le" => Ok(Utf16Modifier::Utf16le),
             "utf16be" => Ok(Utf16Modifier::Utf16be),
             "utf16" => Ok(Utf16Modifier::Utf16),
@@ -81,7 +259,13 @@ impl FromStr for Modifier {
         let mut utf16_modifier: Option<Utf16Modifier> = None;
         let mut result = Self::default();
 
-        for s in string.split("|").skip(1).map(|s| s.to_lowercase()) {
+        let permissive = config::active_spec_mode() != SpecMode::Strict;
+        for s in string.split("|").skip(1) {
+            let s = if permissive {
+                s.to_lowercase()
+            } else {
+                s.to_string()
+            };
             match s.as_str() {
                 "all" => {
                     result.match_all = true;
@@ -100,6 +284,10 @@ impl FromStr for Modifier {
                     result.cased = true;
                     continue;
                 }
+                "expand" => {
+                    result.expand = true;
+                    continue;
+                }
                 "exists" => {
                     // The real value of the exists modifier will be set during field parsing
                     // because it is the field value and here we only parse the field name.
@@ -166,14 +354,26 @@ impl FromStr for Modifier {
             }
         }
 
-        if let (Some(MatchModifier::Re) | Some(MatchModifier::Cidr), Some(_)) =
-            (&result.match_modifier, &result.value_transformer)
-        {
-            return Err(Self::Err::StandaloneViolation(
-                result.match_modifier.unwrap().to_string(),
+        #[cfg(not(feature = "cidr"))]
+        if result.match_modifier == Some(MatchModifier::Cidr) {
+            return Err(Self::Err::DisabledModifier("cidr".to_string(), "cidr"));
+        }
+        #[cfg(not(feature = "base64"))]
+        if let Some(transformer @ (Base64(_) | Base64offset(_))) = &result.value_transformer {
+            return Err(Self::Err::DisabledModifier(
+                transformer.to_string(),
+                "base64",
             ));
         }
 
+        if let (
+            Some(modifier @ (MatchModifier::Re | MatchModifier::Cidr | MatchModifier::SubdomainOf)),
+            Some(_),
+        ) = (&result.match_modifier, &result.value_transformer)
+        {
+            return Err(Self::Err::StandaloneViolation(modifier.to_string()));
+        }
+
         if result.exists.is_some() {
             let tmp = Self {
                 exists: Some(bool::default()),
@@ -197,6 +397,10 @@ mod test {
     fn test_unknown_modifier() {
         let err = Modifier::from_str("test|staartswith").unwrap_err();
         assert!(matches!(err, ParserError::UnknownModifier(ref a) if a == "staartswith"));
+        assert_eq!(
+            err.to_string(),
+            "Unknown field modifier 'staartswith' provided, did you mean 'startswith'?"
+        );
     }
 
     #[test]
@@ -229,6 +433,7 @@ mod test {
         assert!(matches!(err, ParserError::ConflictingModifiers(_, _)));
     }
 
+    #[cfg(feature = "cidr")]
     #[test]
     fn test_conflicting_cidr_modifier() {
         let err = Modifier::from_str("test|windash|cidr").unwrap_err();
@@ -265,4 +470,70 @@ mod test {
         assert_eq!(m.match_modifier, Some(MatchModifier::Contains));
         assert_eq!(m.collection, Some(CollectionMatch::Any));
     }
+
+    #[cfg(all(feature = "cidr", feature = "base64"))]
+    #[test]
+    fn test_display() {
+        for (modifiers, expected) in [
+            ("a", ""),
+            ("a|contains|all", "contains|all"),
+            ("a|all|contains", "contains|all"),
+            (
+                "a|contains|base64offset|utf16le",
+                "utf16le|base64offset|contains",
+            ),
+            ("a|windash|contains", "windash|contains"),
+            ("a|startswith|fieldref", "fieldref|startswith"),
+            ("a|cased|re", "re|cased"),
+            ("a|cidr|any", "cidr|any"),
+            ("a|exists", "exists"),
+        ] {
+            let modifier = Modifier::from_str(modifiers).unwrap();
+            assert_eq!(modifier.to_string(), expected);
+            let key = [vec!["a"], modifier.names()].concat().join("|");
+            let reparsed = Modifier::from_str(&key).unwrap();
+            assert_eq!(reparsed, modifier);
+        }
+    }
+
+    #[cfg(not(all(feature = "cidr", feature = "base64")))]
+    #[test]
+    fn test_disabled_modifiers() {
+        #[cfg(not(feature = "cidr"))]
+        {
+            let err = Modifier::from_str("test|cidr").unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "The field modifier 'cidr' requires the 'cidr' feature of this crate"
+            );
+        }
+        #[cfg(not(feature = "base64"))]
+        for name in [
+            "test|base64",
+            "test|base64offset|contains",
+            "test|utf16le|base64",
+        ] {
+            let err = Modifier::from_str(name).unwrap_err();
+            assert!(
+                matches!(err, ParserError::DisabledModifier(_, "base64")),
+                "{}",
+                name
+            );
+        }
+        assert!(Modifier::from_str("test|windash|contains").is_ok());
+    }
+
+    #[test]
+    fn test_suggest_modifiers() {
+        assert_eq!(suggest_modifiers("containz"), ["contains"]);
+        assert_eq!(suggest_modifiers("EndWith"), ["endswith"]);
+        assert_eq!(suggest_modifiers("base64ofsfet"), ["base64offset"]);
+        assert_eq!(suggest_modifiers("gtt"), ["gt", "gte"]);
+        assert!(suggest_modifiers("something").is_empty());
+
+        assert_eq!(did_you_mean("xyz"), "");
+        assert_eq!(did_you_mean("lte"), ", did you mean 'lte'?");
+        assert_eq!(did_you_mean("ltt"), ", did you mean 'lt' or 'lte'?");
+        assert_eq!(did_you_mean("utf1"), ", did you mean 'utf16'?");
+    }
 }
@@ -1,8 +1,12 @@
+#[cfg(feature = "base64")]
 use crate::field::Utf16Modifier;
+#[cfg(feature = "base64")]
 use base64::engine::general_purpose::STANDARD_NO_PAD;
+#[cfg(feature = "base64")]
 use base64::Engine;
 use std::collections::HashMap;
 
+#[cfg(feature = "base64")]
 pub fn encode_base64(input: &str, utf16modifier: &Option<Utf16Modifier>) -> String {
     let mut encoded = match utf16modifier {
         Some(Utf16Modifier::Utf16le | Utf16Modifier::Wide) => STANDARD_NO_PAD.encode(
@@ -32,6 +36,7 @@ pub fn encode_base64(input: &str, utf16modifier: &Option<Utf16Modifier>) -> Stri
     }
 }
 
+#[cfg(feature = "base64")]
 pub fn encode_base64_offset(input: &str, utf16modifier: &Option<Utf16Modifier>) -> Vec<String> {
     let mut encoded = vec![];
 
@@ -103,9 +108,12 @@ pub fn windash_variations(input: &str) -> Vec<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "cidr")]
     use cidr::IpCidr;
+    #[cfg(feature = "cidr")]
     use std::net::IpAddr;
 
+    #[cfg(feature = "base64")]
     #[test]
     fn test_base64_encoding() {
         assert_eq!(encode_base64("/bin/bash", &None), "L2Jpbi9iYXNo");
@@ -114,6 +122,7 @@ mod tests {
         assert_eq!(encode_base64("", &None), "");
     }
 
+    #[cfg(feature = "base64")]
     #[test]
     fn test_base64_encoding_utf16_le() {
         assert_eq!(
@@ -123,6 +132,7 @@ mod tests {
         assert_eq!(encode_base64("", &Some(Utf16Modifier::Utf16le)), "");
     }
 
+    #[cfg(feature = "base64")]
     #[test]
     fn test_base64_encoding_utf16_be() {
         assert_eq!(
@@ -136,6 +146,7 @@ mod tests {
         assert_eq!(encode_base64("", &Some(Utf16Modifier::Utf16be)), "");
     }
 
+    #[cfg(feature = "base64")]
     #[test]
     fn test_base64_sub_modifiers_docs_example() {
         // https://github.com/SigmaHQ/sigma-specification/blob/main/appendix/sigma-modifiers-appendix.md#encoding
@@ -161,6 +172,7 @@ mod tests {
         assert_eq!(encode_base64("cmd", &Some(Utf16Modifier::Wide)), "YwBtAGQA");
     }
 
+    #[cfg(feature = "base64")]
     #[test]
     fn test_base64_offset_bash() {
         let encoded = encode_base64_offset("/bin/bash", &None);
@@ -172,6 +184,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "base64")]
     #[test]
     fn test_base64_offset_sh() {
         let encoded = encode_base64_offset("/bin/sh", &None);
@@ -183,6 +196,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "base64")]
     #[test]
     fn test_base64_offset_zsh() {
         let encoded = encode_base64_offset("/bin/zsh", &None);
@@ -194,6 +208,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "base64")]
     #[test]
     fn test_base64_offset_1() {
         let encoded = encode_base64_offset("1", &None);
@@ -205,6 +220,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "base64")]
     #[test]
     fn test_base64_offset_utf16le() {
         let encoded = encode_base64_offset("::FromBase64String", &Some(Utf16Modifier::Utf16le));
@@ -220,6 +236,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "base64")]
     #[test]
     fn test_base64_offset_utf16le_preference() {
         // values taken from
@@ -273,6 +290,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "base64")]
     #[test]
     fn test_base64_offset_empty() {
         let encoded = encode_base64_offset("", &None);
@@ -337,6 +355,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "cidr")]
     #[test]
     fn test_cidr_ipv4() {
         let cidr: IpCidr = "192.168.1.0/24".parse().expect("Invalid CIDR");
@@ -345,6 +364,7 @@ mod tests {
         assert!(cidr.contains(&ip))
     }
 
+    #[cfg(feature = "cidr")]
     #[test]
     fn test_cidr_ipv6() {
         let cidr: IpCidr = "2001:db8::/32".parse().expect("Invalid CIDR");
@@ -1,17 +1,37 @@
 use crate::basevalue::BaseValue;
 use crate::field::ParserError;
-use crate::wildcard::WildcardToken;
+use crate::re::Regex;
+use crate::wildcard::WildcardPattern;
+#[cfg(feature = "cidr")]
 use cidr::IpCidr;
-use regex::Regex;
+use std::sync::Arc;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum FieldValue {
     Base(BaseValue),
-    WildcardPattern(Vec<WildcardToken>),
-    Regex(Regex),
+    WildcardPattern(Arc<WildcardPattern>),
+    Regex(Arc<Regex>),
+    #[cfg(feature = "cidr")]
     Cidr(IpCidr),
 }
 
+/// Compiled values are compared by their source, i.e. regular expressions by their pattern and
+/// wildcard patterns by their tokens and whether they match with full case folding
+impl PartialEq for FieldValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Base(a), Self::Base(b)) => a == b,
+            (Self::WildcardPattern(a), Self::WildcardPattern(b)) => {
+                ***a == ***b && a.is_folded() == b.is_folded()
+            }
+            (Self::Regex(a), Self::Regex(b)) => a.as_str() == b.as_str(),
+            #[cfg(feature = "cidr")]
+            (Self::Cidr(a), Self::Cidr(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
 impl<T> From<T> for FieldValue
 where
     T: Into<BaseValue>,
@@ -0,0 +1,459 @@
+//! The relationships between the rules of a collection.
+//!
+//! Rules refer to other rules by id or name in three ways: the `related` attribute lists the
+//! rules a rule was derived from, obsoletes, etc., a correlation rule lists the rules whose
+//! matches it correlates, and a filter rule lists the rules whose matches it suppresses.
+//! [`RuleGraph`] links the rules of a collection along these references and traverses them,
+//! e.g. to never deploy a correlation without the rules it is based on.
+//!
+//! Correlation and filter rules cannot be evaluated, so the [`Loader`](crate::loader::Loader)
+//! skips them but keeps their references as [`MetaRule`]s, see
+//! [`Loaded::graph`](crate::loader::Loaded::graph).
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::loader::Loader;
+//!
+//! let logon = r#"
+//! title: Failed logon
+//! name: failed_logon
+//! logsource:
+//!     product: windows
+//! detection:
+//!     selection:
+//!         EventID: 4625
+//!     condition: selection
+//! "#;
+//! let brute_force = r#"
+//! title: Brute force
+//! name: brute_force
+//! correlation:
+//!     type: event_count
+//!     rules: failed_logon
+//!     group-by: [TargetUserName]
+//!     timespan: 5m
+//!     condition:
+//!         gte: 10
+//! "#;
+//!
+//! let loaded = Loader::new().load([("logon.yml", logon), ("brute_force.yml", brute_force)]);
+//! let graph = loaded.graph();
+//! let dependencies: Vec<_> = graph
+//!     .dependencies("brute_force")
+//!     .iter()
+//!     .map(|node| node.title.as_str())
+//!     .collect();
+//! assert_eq!(dependencies, ["Failed logon"]);
+//!
+//! let order: Vec<_> = graph
+//!     .deployment_order()
+//!     .unwrap()
+//!     .iter()
+//!     .map(|node| node.title.as_str())
+//!     .collect();
+//! assert_eq!(order, ["Failed logon", "Brute force"]);
+//! ```
+use crate::rule::{Related, RelatedType, Rule};
+use serde_yml::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+/// The kind of a rule in a [`RuleGraph`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuleKind {
+    /// A rule with a `detection`, which is evaluated against events
+    Detection,
+    /// A rule with a `correlation` of the matches of other rules
+    Correlation,
+    /// A rule with a `filter` that suppresses matches of other rules
+    Filter,
+}
+
+impl fmt::Display for RuleKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Detection => write!(f, "detection"),
+            Self::Correlation => write!(f, "correlation"),
+            Self::Filter => write!(f, "filter"),
+        }
+    }
+}
+
+/// A correlation or filter rule, reduced to the references needed to link it to other rules
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetaRule {
+    /// [`RuleKind::Correlation`] or [`RuleKind::Filter`]
+    pub kind: RuleKind,
+    pub title: String,
+    pub id: Option<String>,
+    pub name: Option<String>,
+    /// The ids or names of the rules the correlation or filter applies to
+    pub rules: Vec<String>,
+    pub related: Vec<Related>,
+}
+
+impl MetaRule {
+    /// Read the references of a correlation or filter rule. Returns `None` for other rules.
+    pub fn from_yaml(rule: &Value) -> Option<Self> {
+        let (kind, section) = if let Some(section) = rule.get("correlation") {
+            (RuleKind::Correlation, section)
+        } else if let Some(section) = rule.get("filter") {
+            (RuleKind::Filter, section)
+        } else {
+            return None;
+        };
+        let string = |key: &str| rule.get(key).and_then(Value::as_str).map(str::to_string);
+        let rules = match section.get("rules") {
+            Some(Value::String(rule)) => vec![rule.clone()],
+            Some(Value::Sequence(rules)) => rules
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            _ => vec![],
+        };
+        let related = rule
+            .get("related")
+            .and_then(|related| serde_yml::from_value(related.clone()).ok())
+            .unwrap_or_default();
+        Some(Self {
+            kind,
+            title: string("title").unwrap_or_default(),
+            id: string("id"),
+            name: string("name"),
+            rules,
+            related,
+        })
+    }
+}
+
+/// A rule of a [`RuleGraph`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub kind: RuleKind,
+    pub title: String,
+    pub id: Option<String>,
+    pub name: Option<String>,
+}
+
+/// How a rule refers to another rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    /// The `related` attribute of the rule lists the other rule
+    Related(RelatedType),
+    /// The correlation rule correlates the matches of the other rule
+    Correlates,
+    /// The filter rule suppresses matches of the other rule
+    Filters,
+}
+
+impl Relation {
+    /// Whether the referring rule is useless without the referred rule, so both must be
+    /// deployed together
+    pub fn is_dependency(&self) -> bool {
+        matches!(self, Self::Correlates | Self::Filters)
+    }
+}
+
+/// The rules of a collection linked by their references to each other
+#[derive(Debug, Clone, Default)]
+pub struct RuleGraph {
+    nodes: Vec<Node>,
+    /// The rules every rule refers to
+    outgoing: Vec<Vec<(usize, Relation)>>,
+    /// The rules that refer to every rule
+    incoming: Vec<Vec<(usize, Relation)>>,
+    /// The index of the rule with an id or name. The first rule wins if several share one.
+    keys: HashMap<String, usize>,
+    /// References to rules that are not part of the collection
+    unresolved: Vec<(usize, String, Relation)>,
+}
+
+impl RuleGraph {
+    /// Link detection rules and the correlation and filter rules of a collection
+    pub fn new(rules: &[Rule], meta_rules: &[MetaRule]) -> Self {
+        let mut nodes = Vec::with_capacity(rules.len() + meta_rules.len());
+        let mut references = Vec::with_capacity(nodes.capacity());
+        for rule in rules {
+            nodes.push(Node {
+                kind: RuleKind::Detection,
+                title: rule.title.clone(),
+                id: rule.id.clone(),
+                name: rule.name.clone(),
+            });
+            references.push(related_references(
+                rule.related.as_deref().unwrap_or_default(),
+            ));
+        }
+        for meta_rule in meta_rules {
+            nodes.push(Node {
+                kind: meta_rule.kind,
+                title: meta_rule.title.clone(),
+                id: meta_rule.id.clone(),
+                name: meta_rule.name.clone(),
+            });
+            let relation = match meta_rule.kind {
+                RuleKind::Filter => Relation::Filters,
+                _ => Relation::Correlates,
+            };
+            let mut rule_references: Vec<_> = meta_rule
+                .rules
+                .iter()
+                .map(|rule| (rule.as_str(), relation))
+                .collect();
+            rule_references.extend(related_references(&meta_rule.related));
+            references.push(rule_references);
+        }
+
+        let mut keys = HashMap::new();
+        for (i, node) in nodes.iter().enumerate() {
+            for key in node.id.iter().chain(&node.name) {
+                keys.entry(key.clone()).or_insert(i);
+            }
+        }
+
+        let mut graph = Self {
+            outgoing: vec![vec![]; nodes.len()],
+            incoming: vec![vec![]; nodes.len()],
+            nodes,
+            keys,
+            unresolved: vec![],
+        };
+        for (from, references) in references.into_iter().enumerate() {
+            for (key, relation) in references {
+                match graph.keys.get(key) {
+                    Some(&to) => {
+                        graph.outgoing[from].push((to, relation));
+                        graph.incoming[to].push((from, relation));
+                    }
+                    None => graph.unresolved.push((from, key.to_string(), relation)),
+                }
+            }
+        }
+        graph
+    }
+
+    /// All rules, the detection rules first, in the order they were given
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// The rule with an id or name
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        self.keys.get(key).map(|&i| &self.nodes[i])
+    }
+
+    /// The rules the rule with an id or name refers to
+    pub fn references(&self, key: &str) -> Vec<(&Node, Relation)> {
+        self.linked(key, &self.outgoing)
+    }
+
+    /// The rules that refer to the rule with an id or name
+    pub fn referenced_by(&self, key: &str) -> Vec<(&Node, Relation)> {
+        self.linked(key, &self.incoming)
+    }
+
+    /// The rules that must be deployed together with the rule with an id or name, e.g. the
+    /// rules a correlation is based on, including the dependencies of those rules
+    pub fn dependencies(&self, key: &str) -> Vec<&Node> {
+        self.reachable(key, &self.outgoing)
+    }
+
+    /// The correlation and filter rules that depend on the rule with an id or name, directly or
+    /// through other rules, and become useless without it
+    pub fn dependents(&self, key: &str) -> Vec<&Node> {
+        self.reachable(key, &self.incoming)
+    }
+
+    /// The references to rules that are not part of the collection, with the rule that refers
+    /// to them. Rules listed as `renamed` or `obsolete` by `related` are often missing on
+    /// purpose, while a missing dependency, see [`Relation::is_dependency`], prevents the
+    /// deployment of the rule that refers to it.
+    pub fn unresolved(&self) -> impl Iterator<Item = (&Node, &str, Relation)> {
+        self.unresolved
+            .iter()
+            .map(|(from, key, relation)| (&self.nodes[*from], key.as_str(), *relation))
+    }
+
+    /// All rules ordered so that every rule comes after its dependencies. Rules without
+    /// dependencies keep their order. Returns `None` if the dependencies form a cycle.
+    pub fn deployment_order(&self) -> Option<Vec<&Node>> {
+        let mut pending: Vec<usize> = self
+            .outgoing
+            .iter()
+            .map(|references| {
+                references
+                    .iter()
+                    .filter(|(_, relation)| relation.is_dependency())
+                    .count()
+            })
+            .collect();
+        let mut ready: VecDeque<_> = (0..self.nodes.len()).filter(|&i| pending[i] == 0).collect();
+        let mut result = Vec::with_capacity(self.nodes.len());
+        while let Some(i) = ready.pop_front() {
+            result.push(&self.nodes[i]);
+            for &(dependent, relation) in &self.incoming[i] {
+                if relation.is_dependency() {
+                    pending[dependent] -= 1;
+                    if pending[dependent] == 0 {
+                        ready.push_back(dependent);
+                    }
+                }
+            }
+        }
+        (result.len() == self.nodes.len()).then_some(result)
+    }
+
+    fn linked(&self, key: &str, links: &[Vec<(usize, Relation)>]) -> Vec<(&Node, Relation)> {
+        self.keys.get(key).map_or(vec![], |&i| {
+            links[i]
+                .iter()
+                .map(|&(j, relation)| (&self.nodes[j], relation))
+                .collect()
+        })
+    }
+
+    /// The rules reachable from a rule by following dependencies, in breadth-first order
+    fn reachable(&self, key: &str, links: &[Vec<(usize, Relation)>]) -> Vec<&Node> {
+        let Some(&start) = self.keys.get(key) else {
+            return vec![];
+        };
+        let mut visited = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        let mut result = vec![];
+        while let Some(i) = queue.pop_front() {
+            for &(j, relation) in &links[i] {
+                if relation.is_dependency() && visited.insert(j) {
+                    result.push(&self.nodes[j]);
+                    queue.push_back(j);
+                }
+            }
+        }
+        result
+    }
+}
+
+fn related_references(related: &[Related]) -> Vec<(&str, Relation)> {
+    related
+        .iter()
+        .map(|related| (related.id.as_str(), Relation::Related(related.related_type)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    fn rule(id: &str, related: &str) -> Rule {
+        rule_from_yaml(&format!(
+            "title: {id}\nid: {id}\n{related}logsource:\ndetection:\n    selection:\n        EventID: 1\n    condition: selection"
+        ))
+        .unwrap()
+    }
+
+    fn meta(kind: RuleKind, name: &str, rules: &[&str]) -> MetaRule {
+        MetaRule {
+            kind,
+            title: name.to_string(),
+            id: None,
+            name: Some(name.to_string()),
+            rules: rules.iter().map(|rule| rule.to_string()).collect(),
+            related: vec![],
+        }
+    }
+
+    fn titles(nodes: Vec<&Node>) -> Vec<&str> {
+        nodes.into_iter().map(|node| node.title.as_str()).collect()
+    }
+
+    #[test]
+    fn test_meta_rule_from_yaml() {
+        let filter: Value = serde_yml::from_str(
+            "title: Admin\nid: f1\nfilter:\n    rules: [a, b]\n    selection:\n        User: admin\n    condition: not selection\nrelated:\n    - id: f0\n      type: renamed",
+        )
+        .unwrap();
+        let filter = MetaRule::from_yaml(&filter).unwrap();
+        assert_eq!(filter.kind, RuleKind::Filter);
+        assert_eq!((filter.id.as_deref(), filter.name), (Some("f1"), None));
+        assert_eq!(filter.rules, vec!["a", "b"]);
+        assert_eq!(filter.related[0].related_type, RelatedType::Renamed);
+
+        let rule: Value = serde_yml::from_str("title: Rule\ndetection: {}").unwrap();
+        assert!(MetaRule::from_yaml(&rule).is_none());
+    }
+
+    #[test]
+    fn test_traversal() {
+        let rules = vec![
+            rule("a", ""),
+            rule(
+                "b",
+                "related:\n    - id: a\n      type: derived\n    - id: old\n      type: obsolete\n",
+            ),
+        ];
+        let meta_rules = vec![
+            meta(RuleKind::Correlation, "both", &["a", "b"]),
+            meta(RuleKind::Correlation, "chained", &["both"]),
+            meta(RuleKind::Filter, "filter", &["b", "missing"]),
+        ];
+        let graph = RuleGraph::new(&rules, &meta_rules);
+        assert_eq!(graph.nodes().len(), 5);
+        assert_eq!(graph.get("chained").unwrap().kind, RuleKind::Correlation);
+        assert!(graph.get("old").is_none());
+
+        assert_eq!(
+            graph.references("b")[0],
+            (
+                graph.get("a").unwrap(),
+                Relation::Related(RelatedType::Derived)
+            )
+        );
+        let referenced_by: Vec<_> = graph
+            .referenced_by("b")
+            .into_iter()
+            .map(|(node, relation)| (node.title.as_str(), relation))
+            .collect();
+        assert_eq!(
+            referenced_by,
+            vec![
+                ("both", Relation::Correlates),
+                ("filter", Relation::Filters)
+            ]
+        );
+
+        // Related rules are not dependencies
+        assert!(graph.dependencies("b").is_empty());
+        assert_eq!(
+            titles(graph.dependencies("chained")),
+            vec!["both", "a", "b"]
+        );
+        assert_eq!(titles(graph.dependents("a")), vec!["both", "chained"]);
+        assert!(graph.dependencies("unknown").is_empty());
+
+        let unresolved: Vec<_> = graph
+            .unresolved()
+            .map(|(node, key, relation)| (node.title.as_str(), key, relation.is_dependency()))
+            .collect();
+        assert_eq!(
+            unresolved,
+            vec![("b", "old", false), ("filter", "missing", true)]
+        );
+
+        assert_eq!(
+            titles(graph.deployment_order().unwrap()),
+            vec!["a", "b", "both", "filter", "chained"]
+        );
+    }
+
+    #[test]
+    fn test_cycle() {
+        let meta_rules = vec![
+            meta(RuleKind::Correlation, "x", &["y"]),
+            meta(RuleKind::Correlation, "y", &["x"]),
+        ];
+        let graph = RuleGraph::new(&[rule("a", "")], &meta_rules);
+        assert!(graph.deployment_order().is_none());
+        assert_eq!(titles(graph.dependencies("x")), vec!["y"]);
+    }
+}
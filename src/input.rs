@@ -0,0 +1,258 @@
+//! Read events from JSON files for retro-hunting.
+//!
+//! [`FileEvents`] reads events from a file of newline-delimited JSON objects or from a JSON
+//! array, and [`RuleSet::evaluate_file`](crate::RuleSet::evaluate_file) evaluates a rule set
+//! against them. Gzip compressed files are recognized by their content and decompressed with the
+//! `gzip` feature. [`Format::Auto`] tells the formats apart by the first character of the
+//! decompressed content.
+//!
+//! Lines that are not valid events are skipped and recorded in [`FileEvents::errors`], so a
+//! single broken line does not abort a hunt over a large file.
+//!
+//! # Example
+//! ```rust,no_run
+//! use sigma_rust::input::Format;
+//! use sigma_rust::RuleSet;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! # let rule_set = RuleSet::new(vec![]);
+//! for result in rule_set.evaluate_file("events.ndjson.gz", Format::Ndjson)? {
+//!     println!("{}", result.rule_title);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use crate::event::Event;
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+/// The first bytes of gzip compressed data
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// The format of a file of events
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// [`Format::Json`] if the content starts with `[`, else [`Format::Ndjson`]
+    #[default]
+    Auto,
+    /// One JSON object per line, empty lines are skipped
+    Ndjson,
+    /// A JSON array of objects, or a single object
+    Json,
+}
+
+/// A line of a file that is not a valid event, or the error that stopped reading the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidEvent {
+    /// The number of the line, starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+/// The events of a file, see the [module documentation](self)
+pub struct FileEvents {
+    source: Source,
+    errors: Vec<InvalidEvent>,
+}
+
+enum Source {
+    Lines {
+        reader: Box<dyn BufRead + Send>,
+        line: usize,
+    },
+    Events(std::vec::IntoIter<Event>),
+    Done,
+}
+
+impl std::fmt::Debug for FileEvents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileEvents")
+            .field("errors", &self.errors)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonEvents {
+    Many(Vec<Event>),
+    One(Event),
+}
+
+impl FileEvents {
+    /// Open a file of events. Fails if the file cannot be opened, and for [`Format::Json`] if
+    /// it is not a valid JSON array of events.
+    pub fn open<P: AsRef<Path>>(path: P, format: Format) -> io::Result<Self> {
+        Self::from_reader(File::open(path)?, format)
+    }
+
+    /// Read events from any reader, e.g. standard input
+    pub fn from_reader<R: Read + Send + 'static>(reader: R, format: Format) -> io::Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let mut reader: Box<dyn BufRead + Send> = if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+            gunzip(reader)?
+        } else {
+            Box::new(reader)
+        };
+        let format = match format {
+            Format::Auto => {
+                let start = reader.fill_buf()?;
+                match start.iter().find(|byte| !byte.is_ascii_whitespace()) {
+                    Some(b'[') => Format::Json,
+                    _ => Format::Ndjson,
+                }
+            }
+            format => format,
+        };
+        let source = match format {
+            Format::Json => {
+                let events = match serde_json::from_reader(reader).map_err(io::Error::from)? {
+                    JsonEvents::Many(events) => events,
+                    JsonEvents::One(event) => vec![event],
+                };
+                Source::Events(events.into_iter())
+            }
+            _ => Source::Lines { reader, line: 0 },
+        };
+        Ok(Self {
+            source,
+            errors: vec![],
+        })
+    }
+
+    /// The lines that were skipped so far because they are not valid events. If the file could
+    /// not be read to its end, e.g. because it is truncated, the last error says why.
+    pub fn errors(&self) -> &[InvalidEvent] {
+        &self.errors
+    }
+}
+
+impl Iterator for FileEvents {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        let (reader, line) = match &mut self.source {
+            Source::Lines { reader, line } => (reader, line),
+            Source::Events(events) => return events.next(),
+            Source::Done => return None,
+        };
+        let mut buffer = vec![];
+        loop {
+            buffer.clear();
+            *line += 1;
+            match reader.read_until(b'\n', &mut buffer) {
+                Ok(0) => break,
+                Ok(_) if buffer.iter().all(u8::is_ascii_whitespace) => continue,
+                Ok(_) => match serde_json::from_slice(&buffer) {
+                    Ok(event) => return Some(event),
+                    Err(err) => self.errors.push(InvalidEvent {
+                        line: *line,
+                        message: err.to_string(),
+                    }),
+                },
+                Err(err) => {
+                    self.errors.push(InvalidEvent {
+                        line: *line,
+                        message: err.to_string(),
+                    });
+                    break;
+                }
+            }
+        }
+        self.source = Source::Done;
+        None
+    }
+}
+
+#[cfg(feature = "gzip")]
+fn gunzip<R: BufRead + Send + 'static>(reader: R) -> io::Result<Box<dyn BufRead + Send>> {
+    let decoder = flate2::bufread::MultiGzDecoder::new(reader);
+    Ok(Box::new(BufReader::new(decoder)))
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gunzip<R: BufRead + Send + 'static>(_: R) -> io::Result<Box<dyn BufRead + Send>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "reading gzip compressed events requires the gzip feature of this crate",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rule_from_yaml, RuleSet};
+    use std::io::Cursor;
+
+    const NDJSON: &str = r#"{"Image": "C:\\cmd.exe"}
+
+{"Image": "C:\\notepad.exe"}
+not json
+{"Image": "C:\\Windows\\cmd.exe"}
+"#;
+
+    fn read(data: impl Into<Vec<u8>>, format: Format) -> FileEvents {
+        FileEvents::from_reader(Cursor::new(data.into()), format).unwrap()
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let mut events = read(NDJSON, Format::Auto);
+        assert_eq!(events.by_ref().count(), 3);
+        assert_eq!(events.errors().len(), 1);
+        assert_eq!(events.errors()[0].line, 4);
+        assert_eq!(events.next(), None);
+    }
+
+    #[test]
+    fn test_json() {
+        let array = r#" [{"Image": "a"}, {"Image": "b"}]"#;
+        assert_eq!(read(array, Format::Auto).count(), 2);
+        assert_eq!(read(array, Format::Json).count(), 2);
+        assert_eq!(read(r#"{"Image": "a"}"#, Format::Json).count(), 1);
+        assert!(FileEvents::from_reader(Cursor::new("[{}, 5]"), Format::Json).is_err());
+
+        // A JSON array is not a valid line of newline-delimited JSON
+        let mut events = read(array, Format::Ndjson);
+        assert_eq!(events.next(), None);
+        assert_eq!(events.errors().len(), 1);
+    }
+
+    #[test]
+    fn test_evaluate_file() {
+        let rule = rule_from_yaml(
+            "title: cmd\nlogsource:\ndetection:\n    selection:\n        Image|endswith: '\\cmd.exe'\n    condition: selection",
+        )
+        .unwrap();
+        let rule_set = RuleSet::new(vec![rule]);
+        let path = std::env::temp_dir().join(format!("sigma-events-{}.ndjson", std::process::id()));
+        std::fs::write(&path, NDJSON).unwrap();
+        let matches = rule_set
+            .evaluate_file(&path, Format::Ndjson)
+            .map(Iterator::count);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(matches.unwrap(), 2);
+        assert!(rule_set.evaluate_file(&path, Format::Ndjson).is_err());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(NDJSON.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut events = read(compressed.clone(), Format::Auto);
+        assert_eq!(events.by_ref().count(), 3);
+        assert_eq!(events.errors().len(), 1);
+
+        // A truncated file yields the events before the end and reports the error
+        let mut events = read(&compressed[..compressed.len() - 8], Format::Ndjson);
+        assert_eq!(events.by_ref().count(), 3);
+        assert_eq!(events.errors().len(), 2);
+    }
+}
@@ -0,0 +1,492 @@
+//! Import threat intelligence indicators as placeholder values.
+//!
+//! Rules reference indicator lists with the `expand` modifier, e.g. `DestinationIp|expand: '%ips%'`.
+//! [`Indicators`] collects the IP addresses, domains, URLs, hashes, file names and email addresses
+//! of [MISP](https://www.misp-project.org) attribute exports and
+//! [STIX 2.1](https://oasis-open.github.io/cti-documentation/stix/intro) indicator bundles, and
+//! [`Indicators::pipeline`] turns them into a processing pipeline that resolves these placeholders.
+//! The placeholder names are listed in [`IndicatorKind`].
+//!
+//! MISP attributes whose `to_ids` flag is false are context rather than indicators and are
+//! skipped, as are revoked STIX indicators. STIX patterns are reduced to their equality
+//! comparisons, so `[ipv4-addr:value = '198.51.100.1' AND network-traffic:dst_port = 443]`
+//! yields the IP address only.
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::intel::Indicators;
+//! use sigma_rust::Event;
+//!
+//! let indicators = Indicators::from_misp(
+//!     r#"{"Event": {"Attribute": [
+//!         {"type": "ip-dst", "value": "198.51.100.1", "to_ids": true},
+//!         {"type": "domain", "value": "evil.example", "to_ids": true}
+//!     ]}}"#,
+//! )
+//! .unwrap();
+//!
+//! let rule = indicators
+//!     .pipeline()
+//!     .rule_from_yaml(
+//!         r#"
+//! title: Connection to a known C2 server
+//! logsource:
+//!     category: network_connection
+//! detection:
+//!     selection:
+//!         DestinationIp|expand: '%ips%'
+//!     condition: selection
+//! "#,
+//!     )
+//!     .unwrap();
+//!
+//! assert!(rule.is_match(&Event::from([("DestinationIp", "198.51.100.1")])));
+//! ```
+use crate::pipeline::{Pipeline, ProcessingItem, Transformation};
+use crate::re::Regex;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::LazyLock;
+
+/// Matches the equality comparisons of a STIX pattern, e.g. `file:hashes.'SHA-256' = '...'`
+#[allow(clippy::expect_used)]
+static STIX_COMPARISON: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"([a-z0-9-]+):([A-Za-z0-9_.'-]+)\s*=\s*'((?:[^'\\]|\\.)*)'")
+        .expect("the STIX comparison regex is valid")
+});
+
+/// Errors raised when threat intelligence cannot be imported
+#[derive(Debug, thiserror::Error)]
+pub enum IntelError {
+    #[error("Failed to parse JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Unsupported {0} format: {1}")]
+    Format(&'static str, String),
+}
+
+/// The kinds of indicators, each of which is resolved by a placeholder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IndicatorKind {
+    /// IPv4 and IPv6 addresses and networks, `%ips%`
+    Ip,
+    /// Domain and host names, `%domains%`
+    Domain,
+    /// `%urls%`
+    Url,
+    /// `%md5%`
+    Md5,
+    /// `%sha1%`
+    Sha1,
+    /// `%sha256%`
+    Sha256,
+    /// `%filenames%`
+    Filename,
+    /// `%emails%`
+    Email,
+}
+
+impl IndicatorKind {
+    /// The name of the placeholder that resolves to indicators of this kind
+    pub fn placeholder(&self) -> &'static str {
+        match self {
+            Self::Ip => "ips",
+            Self::Domain => "domains",
+            Self::Url => "urls",
+            Self::Md5 => "md5",
+            Self::Sha1 => "sha1",
+            Self::Sha256 => "sha256",
+            Self::Filename => "filenames",
+            Self::Email => "emails",
+        }
+    }
+
+    /// The kinds of a MISP attribute type. Composite types like `domain|ip` contain a value of
+    /// each kind separated by `|`, [`None`] marks parts that are ignored, e.g. ports.
+    fn from_misp(attribute_type: &str) -> &'static [Option<IndicatorKind>] {
+        match attribute_type {
+            "ip-src" | "ip-dst" => &[Some(Self::Ip)],
+            "ip-src|port" | "ip-dst|port" => &[Some(Self::Ip), None],
+            "domain" | "hostname" => &[Some(Self::Domain)],
+            "domain|ip" => &[Some(Self::Domain), Some(Self::Ip)],
+            "hostname|port" => &[Some(Self::Domain), None],
+            "url" | "uri" | "link" => &[Some(Self::Url)],
+            "md5" => &[Some(Self::Md5)],
+            "sha1" => &[Some(Self::Sha1)],
+            "sha256" => &[Some(Self::Sha256)],
+            "filename" => &[Some(Self::Filename)],
+            "filename|md5" => &[Some(Self::Filename), Some(Self::Md5)],
+            "filename|sha1" => &[Some(Self::Filename), Some(Self::Sha1)],
+            "filename|sha256" => &[Some(Self::Filename), Some(Self::Sha256)],
+            "email" | "email-src" | "email-dst" => &[Some(Self::Email)],
+            _ => &[],
+        }
+    }
+
+    /// The kind of the object path of a STIX comparison
+    fn from_stix(object: &str, property: &str) -> Option<IndicatorKind> {
+        let property = property.replace('\'', "");
+        match (object, property.to_ascii_lowercase().as_str()) {
+            ("ipv4-addr" | "ipv6-addr", "value") => Some(Self::Ip),
+            ("domain-name", "value") => Some(Self::Domain),
+            ("url", "value") => Some(Self::Url),
+            ("file", "hashes.md5") => Some(Self::Md5),
+            ("file", "hashes.sha-1" | "hashes.sha1") => Some(Self::Sha1),
+            ("file", "hashes.sha-256" | "hashes.sha256") => Some(Self::Sha256),
+            ("file", "name") => Some(Self::Filename),
+            ("email-addr", "value") => Some(Self::Email),
+            _ => None,
+        }
+    }
+}
+
+/// Deduplicated indicators grouped by kind, see the [module documentation](self)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Indicators {
+    values: BTreeMap<IndicatorKind, BTreeSet<String>>,
+}
+
+impl Indicators {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Import the attributes of a MISP export. Accepted are a single event (`{"Event": ...}`),
+    /// the responses of the `events/restSearch` and `attributes/restSearch` endpoints and plain
+    /// lists of events or attributes. The attributes of objects are included.
+    pub fn from_misp(json: &str) -> Result<Self, IntelError> {
+        let mut result = Self::new();
+        result.add_misp(&serde_json::from_str(json)?)?;
+        Ok(result)
+    }
+
+    /// Import the indicators of a STIX 2.1 bundle or a list of STIX objects
+    pub fn from_stix(json: &str) -> Result<Self, IntelError> {
+        let json: Value = serde_json::from_str(json)?;
+        let objects = match &json {
+            Value::Array(objects) => objects,
+            Value::Object(object) => match object.get("objects") {
+                Some(Value::Array(objects)) => objects,
+                _ if object.get("type").and_then(Value::as_str) == Some("indicator") => {
+                    std::slice::from_ref(&json)
+                }
+                _ => {
+                    return Err(IntelError::Format(
+                        "STIX",
+                        "expected a bundle, an indicator or a list of objects".to_string(),
+                    ))
+                }
+            },
+            _ => {
+                return Err(IntelError::Format(
+                    "STIX",
+                    "expected a JSON object or array".to_string(),
+                ))
+            }
+        };
+
+        let mut result = Self::new();
+        for object in objects {
+            let is_indicator = object.get("type").and_then(Value::as_str) == Some("indicator");
+            let is_stix_pattern = object
+                .get("pattern_type")
+                .and_then(Value::as_str)
+                .map_or(true, |pattern_type| pattern_type == "stix");
+            let revoked = object.get("revoked").and_then(Value::as_bool) == Some(true);
+            if !is_indicator || !is_stix_pattern || revoked {
+                continue;
+            }
+            let Some(pattern) = object.get("pattern").and_then(Value::as_str) else {
+                continue;
+            };
+            for comparison in STIX_COMPARISON.captures_iter(pattern) {
+                if let Some(kind) = IndicatorKind::from_stix(&comparison[1], &comparison[2]) {
+                    let value = comparison[3].replace("\\'", "'").replace("\\\\", "\\");
+                    result.insert(kind, value);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Add an indicator. Surrounding whitespace is removed and empty values are ignored.
+    pub fn insert<S: AsRef<str>>(&mut self, kind: IndicatorKind, value: S) {
+        let value = value.as_ref().trim();
+        if !value.is_empty() {
+            self.values
+                .entry(kind)
+                .or_default()
+                .insert(value.to_string());
+        }
+    }
+
+    /// Add all indicators of another collection, e.g. to combine several feeds
+    pub fn merge(&mut self, other: Indicators) {
+        for (kind, values) in other.values {
+            self.values.entry(kind).or_default().extend(values);
+        }
+    }
+
+    /// The indicators of a kind in lexicographic order
+    pub fn get(&self, kind: IndicatorKind) -> impl Iterator<Item = &str> {
+        self.values
+            .get(&kind)
+            .into_iter()
+            .flat_map(|values| values.iter().map(String::as_str))
+    }
+
+    /// The total number of indicators
+    pub fn len(&self) -> usize {
+        self.values.values().map(BTreeSet::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// A pipeline that defines a variable for every kind of indicator and resolves the
+    /// corresponding placeholders. Kinds without indicators resolve to an empty list, so a rule
+    /// whose selection only references such a kind fails to parse instead of never matching.
+    /// Combine it with other pipelines using [`Pipeline::chain`].
+    pub fn pipeline(&self) -> Pipeline {
+        let kinds = [
+            IndicatorKind::Ip,
+            IndicatorKind::Domain,
+            IndicatorKind::Url,
+            IndicatorKind::Md5,
+            IndicatorKind::Sha1,
+            IndicatorKind::Sha256,
+            IndicatorKind::Filename,
+            IndicatorKind::Email,
+        ];
+        let mut pipeline = Pipeline::new().with_item(
+            ProcessingItem::new(Transformation::ValuePlaceholders {
+                include: Some(kinds.iter().map(|k| k.placeholder().to_string()).collect()),
+                exclude: None,
+            })
+            .with_id("intel_placeholders"),
+        );
+        for kind in kinds {
+            pipeline = pipeline.with_var(kind.placeholder(), self.get(kind));
+        }
+        pipeline
+    }
+
+    fn add_misp(&mut self, json: &Value) -> Result<(), IntelError> {
+        match json {
+            Value::Array(items) => {
+                for item in items {
+                    self.add_misp(item)?;
+                }
+            }
+            Value::Object(object) => {
+                if let Some(response) = object.get("response") {
+                    return self.add_misp(response);
+                }
+                if let Some(event) = object.get("Event") {
+                    return self.add_misp(event);
+                }
+                if object.contains_key("type") && object.contains_key("value") {
+                    self.add_misp_attribute(json);
+                    return Ok(());
+                }
+                for key in ["Attribute", "Object"] {
+                    if let Some(Value::Array(items)) = object.get(key) {
+                        for item in items {
+                            self.add_misp(item)?;
+                        }
+                    }
+                }
+            }
+            _ => {
+                return Err(IntelError::Format(
+                    "MISP",
+                    "expected events or attributes".to_string(),
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn add_misp_attribute(&mut self, attribute: &Value) {
+        // MISP exports the flags as booleans, older versions as strings
+        let to_ids = match attribute.get("to_ids") {
+            Some(Value::Bool(to_ids)) => *to_ids,
+            Some(Value::String(to_ids)) => to_ids != "0" && to_ids != "false",
+            _ => true,
+        };
+        let (Some(attribute_type), Some(value)) = (
+            attribute.get("type").and_then(Value::as_str),
+            attribute.get("value").and_then(Value::as_str),
+        ) else {
+            return;
+        };
+        if !to_ids {
+            return;
+        }
+
+        let kinds = IndicatorKind::from_misp(attribute_type);
+        let parts: Vec<&str> = if kinds.len() > 1 {
+            value.splitn(kinds.len(), '|').collect()
+        } else {
+            vec![value]
+        };
+        for (kind, part) in kinds.iter().zip(parts) {
+            if let Some(kind) = kind {
+                self.insert(*kind, part);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Event;
+
+    const MISP: &str = r#"{
+        "response": [{
+            "Event": {
+                "info": "Campaign",
+                "Attribute": [
+                    {"type": "ip-dst|port", "value": "198.51.100.1|443", "to_ids": true},
+                    {"type": "domain|ip", "value": "evil.example|203.0.113.7", "to_ids": "1"},
+                    {"type": "filename|sha256", "value": "dropper.exe|abc123", "to_ids": true},
+                    {"type": "comment", "value": "not an indicator", "to_ids": false},
+                    {"type": "ip-src", "value": "192.0.2.1", "to_ids": false}
+                ],
+                "Object": [{
+                    "name": "file",
+                    "Attribute": [{"type": "md5", "value": "d41d8cd9", "to_ids": true}]
+                }]
+            }
+        }]
+    }"#;
+
+    const STIX: &str = r#"{
+        "type": "bundle",
+        "id": "bundle--1",
+        "objects": [
+            {
+                "type": "indicator",
+                "pattern_type": "stix",
+                "pattern": "[ipv4-addr:value = '198.51.100.1'] OR [domain-name:value = 'evil.example']"
+            },
+            {
+                "type": "indicator",
+                "pattern": "[file:hashes.'SHA-256' = 'abc123' AND file:name = 'o\\'brien.exe']"
+            },
+            {
+                "type": "indicator",
+                "revoked": true,
+                "pattern": "[url:value = 'http://revoked.example/']"
+            },
+            {
+                "type": "indicator",
+                "pattern_type": "yara",
+                "pattern": "rule x { condition: true }"
+            },
+            {"type": "malware", "name": "Evil"}
+        ]
+    }"#;
+
+    #[test]
+    fn test_misp() {
+        let indicators = Indicators::from_misp(MISP).unwrap();
+        let get = |kind| indicators.get(kind).collect::<Vec<_>>();
+        assert_eq!(get(IndicatorKind::Ip), ["198.51.100.1", "203.0.113.7"]);
+        assert_eq!(get(IndicatorKind::Domain), ["evil.example"]);
+        assert_eq!(get(IndicatorKind::Filename), ["dropper.exe"]);
+        assert_eq!(get(IndicatorKind::Sha256), ["abc123"]);
+        assert_eq!(get(IndicatorKind::Md5), ["d41d8cd9"]);
+        assert_eq!(indicators.len(), 6);
+
+        // A plain attribute list of attributes/restSearch
+        let attributes = Indicators::from_misp(
+            r#"{"response": {"Attribute": [{"type": "url", "value": "http://evil.example/"}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            attributes.get(IndicatorKind::Url).collect::<Vec<_>>(),
+            ["http://evil.example/"]
+        );
+
+        assert!(matches!(
+            Indicators::from_misp("42"),
+            Err(IntelError::Format("MISP", _))
+        ));
+        assert!(matches!(
+            Indicators::from_misp("{"),
+            Err(IntelError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_stix() {
+        let indicators = Indicators::from_stix(STIX).unwrap();
+        let get = |kind| indicators.get(kind).collect::<Vec<_>>();
+        assert_eq!(get(IndicatorKind::Ip), ["198.51.100.1"]);
+        assert_eq!(get(IndicatorKind::Domain), ["evil.example"]);
+        assert_eq!(get(IndicatorKind::Sha256), ["abc123"]);
+        assert_eq!(get(IndicatorKind::Filename), ["o'brien.exe"]);
+        assert!(get(IndicatorKind::Url).is_empty());
+        assert_eq!(indicators.len(), 4);
+
+        assert!(matches!(
+            Indicators::from_stix(r#"{"type": "malware"}"#),
+            Err(IntelError::Format("STIX", _))
+        ));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut indicators = Indicators::from_misp(MISP).unwrap();
+        indicators.merge(Indicators::from_stix(STIX).unwrap());
+        assert_eq!(indicators.get(IndicatorKind::Ip).count(), 2);
+        assert_eq!(indicators.get(IndicatorKind::Filename).count(), 2);
+
+        indicators.insert(IndicatorKind::Email, "  ");
+        assert_eq!(indicators.get(IndicatorKind::Email).count(), 0);
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let indicators = Indicators::from_misp(MISP).unwrap();
+        let rule = indicators
+            .pipeline()
+            .rule_from_yaml(
+                r#"
+title: Known bad
+logsource:
+    category: network_connection
+detection:
+    network:
+        DestinationIp|expand: '%ips%'
+    dns:
+        QueryName|expand: '%domains%'
+    condition: network or dns
+"#,
+            )
+            .unwrap();
+
+        assert!(rule.is_match(&Event::from([("DestinationIp", "203.0.113.7")])));
+        assert!(rule.is_match(&Event::from([("QueryName", "evil.example")])));
+        assert!(!rule.is_match(&Event::from([("DestinationIp", "192.0.2.1")])));
+
+        // There are no email indicators
+        let err = indicators
+            .pipeline()
+            .rule_from_yaml(
+                r#"
+title: Known bad sender
+logsource:
+detection:
+    selection:
+        Sender|expand: '%emails%'
+    condition: selection
+"#,
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("No values provided"));
+    }
+}
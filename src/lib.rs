@@ -1,21 +1,117 @@
-#![forbid(unsafe_code)]
+// Only the C interface needs unsafe code
+#![cfg_attr(not(feature = "ffi"), forbid(unsafe_code))]
+#![cfg_attr(feature = "ffi", deny(unsafe_code))]
+// Evaluating rules must never panic, whatever the event contains. Modules that only convert or
+// analyze rules allow what they need where they are declared.
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic,
+        clippy::indexing_slicing
+    )
+)]
 //! `sigma-rust` is a library for parsing and checking Sigma rules against log events.
 
+#[allow(clippy::unwrap_used, clippy::expect_used, clippy::indexing_slicing)]
+pub mod backend;
 mod basevalue;
+#[cfg(feature = "bench")]
+#[allow(clippy::expect_used, clippy::indexing_slicing)]
+pub mod bench;
+mod cache;
+pub mod capability;
+mod config;
+pub mod context;
+pub mod cost;
+pub mod dedup;
 mod detection;
-mod error;
+pub mod enrich;
+pub mod error;
+mod evaluation;
 mod event;
+pub mod exception;
+pub mod explain;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 mod field;
+#[allow(clippy::indexing_slicing)]
+pub mod graph;
+#[cfg(feature = "serde_json")]
+pub mod input;
+#[cfg(feature = "serde_json")]
+pub mod intel;
+pub mod loader;
+mod lru;
+#[cfg(feature = "serde_json")]
+pub mod output;
+pub mod pipeline;
+pub mod placeholder;
+pub mod pool;
+pub mod profile;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod ratelimit;
+mod re;
+#[allow(clippy::indexing_slicing)]
+pub mod report;
+mod result;
+pub mod routing;
 mod rule;
+mod ruleset;
+#[cfg(feature = "serde_json")]
+pub mod schema;
+pub mod scoring;
 mod selection;
+#[cfg(feature = "http")]
+#[allow(clippy::indexing_slicing)]
+pub mod sigmahq;
+pub mod sink;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+pub mod taxonomy;
+pub mod tenant;
+#[cfg(any(feature = "chrono", feature = "time"))]
+mod timestamp;
+#[allow(clippy::indexing_slicing)]
+pub mod validation;
+pub mod valuelist;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 mod wildcard;
 
-pub use event::Event;
-pub use rule::{Level, Rule, Status};
+pub use basevalue::BaseValue;
+pub use config::{
+    CaseFolding, EngineConfig, EngineConfigBuilder, FieldPolicy, Limit, ParserLimits, SpecMode,
+    WildcardMode,
+};
+pub use error::Span;
+pub use event::{Event, EventValue};
+pub use field::{Field, FieldValue, Modifier};
+pub use result::MatchResult;
+pub use rule::{Level, Logsource, Related, RelatedType, Rule, RuleBuilder, Status};
+#[cfg(feature = "async")]
+pub use ruleset::{AsyncMatchStream, DEFAULT_YIELD_BUDGET};
+pub use ruleset::{BudgetedMatches, EvaluationOrder, MatchOrder, MatchStream, RuleSet};
+pub use selection::{FieldGroup, Keyword, Selection};
+
+// Compiled rules are not mutated during evaluation, so a single rule set can be shared by
+// worker threads behind an `Arc`. This fails to compile if a type loses `Send` or `Sync`.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<RuleSet>();
+    assert_send_sync::<Rule>();
+    assert_send_sync::<Field>();
+    assert_send_sync::<Selection>();
+    assert_send_sync::<Event>();
+    assert_send_sync::<MatchResult>();
+    assert_send_sync::<tenant::Tenants>();
+};
 
 /// Parse a rule from a YAML string
 pub fn rule_from_yaml(yaml: &str) -> Result<Rule, serde_yml::Error> {
-    serde_yml::from_str(yaml)
+    serde_yml::from_str(yaml).map_err(|err| detection::locate_error(yaml, err))
 }
 
 /// Parse an event from a JSON string
@@ -0,0 +1,911 @@
+//! Load collections of rules without aborting on broken rules.
+//!
+//! [`rule_from_yaml`](crate::rule_from_yaml) fails on the first error of a rule. When loading a
+//! whole rule repository, the rules that cannot be parsed are better skipped and reported
+//! together. A [`Loader`] does that: it returns every rule it could parse together with a
+//! [`Diagnostic`] for every rule it skipped, including the position of the error in the source
+//! if it is known.
+//!
+//! Optionally, rules of which only some selections cannot be parsed, e.g. because they use a
+//! modifier that is not supported, are degraded instead of skipped: the broken selections never
+//! match and a warning is reported for each of them. This keeps the rest of the rule working, so
+//! it should be used with care. Rules whose condition contains `not` are never degraded, as a
+//! selection that never matches would make them match more events instead of less.
+//!
+//! Rules are parsed with the [`EngineConfig`] given to [`Loader::with_config`]. With
+//! [`SpecMode::Legacy`](crate::SpecMode::Legacy), a warning is reported for every Sigma v1
+//! construct that was rewritten to load a rule.
+//!
+//! With [`Loader::with_includes`], the detection of a rule may include selections from other
+//! files with the [`INCLUDE_KEY`], so shared selections like filters of known good software are
+//! maintained in one place. An included file is a mapping of selections, which may include
+//! further files. Paths are relative to the including file, each file is included once per rule
+//! and cyclic includes are reported as errors.
+//!
+//! [`Loaded::report`] sums up the diagnostics of a large rule tree: how many rules were skipped
+//! or degraded, and how many of them because of each unsupported modifier or construct, so the
+//! missing features that affect the most rules can be addressed first.
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::loader::Loader;
+//! use sigma_rust::validation::Severity;
+//!
+//! let rule = r#"
+//! title: Whoami
+//! logsource:
+//!     category: process_creation
+//! detection:
+//!     selection:
+//!         Image|endswith: '\whoami.exe'
+//!     future:
+//!         CommandLine|unknown: whoami
+//!     condition: selection or future
+//! "#;
+//!
+//! let loaded = Loader::new().load([("whoami.yml", rule)]);
+//! assert!(loaded.rules.is_empty());
+//! assert_eq!(loaded.diagnostics[0].severity, Severity::Error);
+//!
+//! let loaded = Loader::new()
+//!     .with_degraded_selections(true)
+//!     .load([("whoami.yml", rule)]);
+//! assert_eq!(loaded.rules.len(), 1);
+//! assert_eq!(loaded.diagnostics[0].severity, Severity::Warning);
+//! assert_eq!(loaded.diagnostics[0].span.unwrap().line, 9);
+//! ```
+use crate::capability::unsupported_feature;
+use crate::config::EngineConfig;
+use crate::detection::{degrade_selections, locate};
+use crate::error::{ParserError, Span};
+use crate::graph::{MetaRule, RuleGraph, RuleKind};
+use crate::pipeline::Pipeline;
+use crate::rule::Rule;
+use crate::validation::Severity;
+use serde::Deserialize;
+use serde_yml::Mapping;
+use serde_yml::Value;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The key of a detection that includes the selections of other files, either a path or a list
+/// of paths, see [`Loader::with_includes`]
+pub const INCLUDE_KEY: &str = "tau-include";
+
+/// A problem found while loading a rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// The name of the source of the rule, e.g. its path
+    pub source: String,
+    /// [`Severity::Error`] if the rule was skipped, [`Severity::Warning`] if it was degraded or
+    /// uses legacy syntax
+    pub severity: Severity,
+    /// The [stable code](crate::error) of the error, if the rule could be read as YAML
+    pub code: Option<&'static str>,
+    /// The feature tau does not support that caused the problem, e.g. `modifier 'x'` or
+    /// `correlation`, named like [`Limitation::feature`](crate::capability::Limitation::feature).
+    /// `None` if the rule itself is invalid.
+    pub feature: Option<String>,
+    pub message: String,
+    /// The position of the problem in the source, if known
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)?;
+        if let Some(span) = self.span {
+            write!(f, ":{}:{}", span.line, span.column)?;
+        }
+        write!(f, ": {}", self.severity)?;
+        if let Some(code) = self.code {
+            write!(f, "[{}]", code)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// The rules a [`Loader`] could load and the problems it found
+#[derive(Debug, Default)]
+pub struct Loaded {
+    pub rules: Vec<Rule>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// The references of the correlation and filter rules, which are skipped
+    pub meta_rules: Vec<MetaRule>,
+}
+
+impl Loaded {
+    /// Whether a rule was skipped
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    /// Link the loaded rules and the skipped correlation and filter rules by their references
+    /// to each other, see [`RuleGraph`]
+    pub fn graph(&self) -> RuleGraph {
+        RuleGraph::new(&self.rules, &self.meta_rules)
+    }
+
+    /// Count the skipped and degraded rules by the reasons of their diagnostics
+    pub fn report(&self) -> LoadReport {
+        let mut skipped = BTreeSet::new();
+        let mut degraded = BTreeSet::new();
+        let mut reasons: BTreeMap<String, (BTreeSet<&str>, BTreeSet<&str>)> = BTreeMap::new();
+        for diagnostic in &self.diagnostics {
+            let source = diagnostic.source.as_str();
+            let reason = diagnostic
+                .feature
+                .clone()
+                .or_else(|| diagnostic.code.map(str::to_string))
+                .unwrap_or_else(|| "other".to_string());
+            let sources = reasons.entry(reason).or_default();
+            match diagnostic.severity {
+                Severity::Error => {
+                    skipped.insert(source);
+                    sources.0.insert(source);
+                }
+                Severity::Warning => {
+                    degraded.insert(source);
+                    sources.1.insert(source);
+                }
+            }
+        }
+        LoadReport {
+            loaded: self.rules.len(),
+            degraded: degraded.len(),
+            skipped: skipped.len(),
+            reasons: reasons
+                .into_iter()
+                .map(|(reason, (skipped, degraded))| {
+                    let count = ReasonCount {
+                        skipped: skipped.len(),
+                        degraded: degraded.len(),
+                    };
+                    (reason, count)
+                })
+                .collect(),
+        }
+    }
+
+    fn error(&mut self, source: String, message: String, span: Option<Span>) {
+        self.diagnostics.push(Diagnostic {
+            source,
+            severity: Severity::Error,
+            code: None,
+            feature: None,
+            message,
+            span,
+        });
+    }
+}
+
+/// How many rules were skipped or degraded when loading a collection and why, see
+/// [`Loaded::report`]
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::loader::Loader;
+///
+/// let rule = |modifier: &str| {
+///     format!(
+///         "title: Test\nlogsource:\ndetection:\n    selection:\n        Image|{}: x\n    condition: selection",
+///         modifier
+///     )
+/// };
+/// let loaded = Loader::new().load([
+///     ("a.yml", rule("contains")),
+///     ("b.yml", rule("fuzzy")),
+///     ("c.yml", rule("fuzzy")),
+/// ]);
+///
+/// let report = loaded.report();
+/// assert_eq!((report.loaded, report.skipped), (1, 2));
+/// assert_eq!(report.reasons["modifier 'fuzzy'"].skipped, 2);
+/// assert_eq!(report.to_string().lines().nth(1), Some("  modifier 'fuzzy': 2 skipped"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    /// The number of loaded rules, including degraded ones
+    pub loaded: usize,
+    /// The number of loaded rules with disabled selections or rewritten legacy syntax
+    pub degraded: usize,
+    /// The number of rules and files that could not be loaded
+    pub skipped: usize,
+    /// The number of rules affected by each reason: the unsupported feature of a diagnostic,
+    /// else the code of its error, else `other`, e.g. for files that are not valid YAML
+    pub reasons: BTreeMap<String, ReasonCount>,
+}
+
+/// The number of rules a reason of a [`LoadReport`] affected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReasonCount {
+    pub skipped: usize,
+    pub degraded: usize,
+}
+
+impl ReasonCount {
+    pub fn total(&self) -> usize {
+        self.skipped + self.degraded
+    }
+}
+
+impl LoadReport {
+    /// The reasons ordered by the number of rules they affected, most first. Reasons affecting
+    /// the same number of rules are ordered by name.
+    pub fn by_impact(&self) -> Vec<(&str, ReasonCount)> {
+        let mut reasons: Vec<(&str, ReasonCount)> = self
+            .reasons
+            .iter()
+            .map(|(reason, count)| (reason.as_str(), *count))
+            .collect();
+        reasons.sort_by_key(|(_, count)| std::cmp::Reverse(count.total()));
+        reasons
+    }
+}
+
+/// A summary line followed by a line per reason, ordered by impact
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} rules loaded, {} of them degraded, {} skipped",
+            self.loaded, self.degraded, self.skipped
+        )?;
+        for (reason, count) in self.by_impact() {
+            write!(f, "\n  {}: ", reason)?;
+            match (count.skipped, count.degraded) {
+                (skipped, 0) => write!(f, "{} skipped", skipped)?,
+                (0, degraded) => write!(f, "{} degraded", degraded)?,
+                (skipped, degraded) => write!(f, "{} skipped, {} degraded", skipped, degraded)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Loads rules leniently, see the [module documentation](self)
+#[derive(Debug, Clone, Default)]
+pub struct Loader {
+    pipeline: Option<Pipeline>,
+    degrade: bool,
+    includes: bool,
+    config: EngineConfig,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a processing pipeline to every rule before it is compiled
+    pub fn with_pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.pipeline = Some(pipeline);
+        self
+    }
+
+    /// Degrade rules of which only some selections cannot be parsed instead of skipping them
+    pub fn with_degraded_selections(mut self, degrade: bool) -> Self {
+        self.degrade = degrade;
+        self
+    }
+
+    /// Resolve the [`INCLUDE_KEY`] of detections, see the [module documentation](self).
+    /// Paths are relative to the source name of a rule, which [`Loader::load_paths`] sets to its
+    /// path. Disabled by default, as including files lets a rule read any file of the system;
+    /// rules that include files are skipped then.
+    pub fn with_includes(mut self, includes: bool) -> Self {
+        self.includes = includes;
+        self
+    }
+
+    /// Parse the rules with this configuration instead of the default one
+    pub fn with_config(mut self, config: EngineConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Load rules from pairs of source names and YAML strings. A string may contain several
+    /// rules separated by `---`.
+    pub fn load<I, S, Y>(&self, documents: I) -> Loaded
+    where
+        I: IntoIterator<Item = (S, Y)>,
+        S: Into<String>,
+        Y: AsRef<str>,
+    {
+        let mut result = Loaded::default();
+        for (source, yaml) in documents {
+            self.load_into(&mut result, source.into(), yaml.as_ref());
+        }
+        result
+    }
+
+    /// Load all `.yml` and `.yaml` files of a directory recursively, see [`Loader::load_paths`]
+    pub fn load_dir<P: AsRef<Path>>(&self, path: P) -> Loaded {
+        self.load_paths([path])
+    }
+
+    /// Load rule files and directories, see [`rule_files`]. A file may contain several rules
+    /// separated by `---`. Files and directories that cannot be read are reported as errors.
+    pub fn load_paths<I, P>(&self, paths: I) -> Loaded
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut result = Loaded::default();
+        for path in paths {
+            let (files, diagnostics) = rule_files(path);
+            result.diagnostics.extend(diagnostics);
+            for path in files {
+                let source = path.display().to_string();
+                match fs::read_to_string(&path) {
+                    Ok(yaml) => self.load_into(&mut result, source, &yaml),
+                    Err(err) => result.error(source, err.to_string(), None),
+                }
+            }
+        }
+        result
+    }
+
+    /// Load the documents of a YAML stream, with the positions of the diagnostics relative to
+    /// the whole stream
+    fn load_into(&self, result: &mut Loaded, source: String, yaml: &str) {
+        for (offset, document) in documents(yaml) {
+            let start = result.diagnostics.len();
+            self.config
+                .scoped(|| self.parse_into(result, source.clone(), document));
+            for diagnostic in result.diagnostics.iter_mut().skip(start) {
+                if let Some(span) = &mut diagnostic.span {
+                    span.line += offset;
+                }
+            }
+        }
+    }
+
+    fn parse_into(&self, result: &mut Loaded, source: String, yaml: &str) {
+        let mut rule: Value = match serde_yml::from_str(yaml) {
+            Ok(rule) => rule,
+            Err(err) => {
+                // The rule parser reports duplicate selections and fields more precisely
+                let err = serde_yml::from_str::<Rule>(yaml).err().unwrap_or(err);
+                let span = err.location().map(|location| Span {
+                    line: location.line(),
+                    column: location.column(),
+                });
+                return result.error(source, err.to_string(), span);
+            }
+        };
+        if let Some(Value::Mapping(detection)) = rule.get_mut("detection") {
+            if detection.contains_key(INCLUDE_KEY) {
+                if !self.includes {
+                    result.diagnostics.push(Diagnostic {
+                        source,
+                        severity: Severity::Error,
+                        code: None,
+                        feature: Some("include".to_string()),
+                        message: "Including files is disabled".to_string(),
+                        span: None,
+                    });
+                    return;
+                }
+                let base = Path::new(&source).parent().unwrap_or(Path::new(""));
+                if let Err(err) = Includes::default().resolve(detection, base) {
+                    return result.error(source, err, None);
+                }
+            }
+        }
+        if let Some(pipeline) = &self.pipeline {
+            if let Err(err) = pipeline.apply(&mut rule) {
+                return result.error(source, err.to_string(), None);
+            }
+        }
+        if let Some(meta_rule) = MetaRule::from_yaml(&rule) {
+            let message = match meta_rule.kind {
+                RuleKind::Filter => "Filter rules are not supported",
+                _ => "Correlation rules are not supported",
+            };
+            result.diagnostics.push(Diagnostic {
+                source,
+                severity: Severity::Error,
+                code: None,
+                feature: Some(meta_rule.kind.to_string()),
+                message: message.to_string(),
+                span: None,
+            });
+            return result.meta_rules.push(meta_rule);
+        }
+
+        let err = match Rule::deserialize(&rule) {
+            Ok(rule) => {
+                for warning in rule.legacy_warnings() {
+                    result.diagnostics.push(Diagnostic {
+                        source: source.clone(),
+                        severity: Severity::Warning,
+                        code: Some("TAU0023"),
+                        feature: Some("legacy condition".to_string()),
+                        message: format!("legacy syntax: {}", warning),
+                        span: None,
+                    });
+                }
+                return result.rules.push(rule);
+            }
+            Err(err) => err,
+        };
+        if self.degrade {
+            let mut degraded_rule = rule.clone();
+            let degraded = degrade_selections(yaml, &mut degraded_rule);
+            if !degraded.is_empty() {
+                if let Ok(mut parsed) = Rule::deserialize(&degraded_rule) {
+                    for (name, span, error) in degraded {
+                        parsed.detection.disable_selection(&name);
+                        result.diagnostics.push(Diagnostic {
+                            source: source.clone(),
+                            severity: Severity::Warning,
+                            code: Some(error.code()),
+                            feature: unsupported_feature(&error),
+                            message: format!(
+                                "selection '{}' is disabled: {}",
+                                name,
+                                without_selection(error)
+                            ),
+                            span,
+                        });
+                    }
+                    return result.rules.push(parsed);
+                }
+            }
+        }
+
+        match locate(yaml, &rule) {
+            Some((span, error)) => result.diagnostics.push(Diagnostic {
+                source,
+                severity: Severity::Error,
+                code: Some(error.code()),
+                feature: unsupported_feature(&error),
+                message: error.to_string(),
+                span: Some(span),
+            }),
+            None => result.error(source, err.to_string(), None),
+        }
+    }
+}
+
+/// The rule files of a path: the path itself if it is a file, regardless of its extension, or
+/// the `.yml` and `.yaml` files of a directory and its subdirectories, ordered by name. The
+/// directories that cannot be read are returned as errors.
+pub fn rule_files<P: AsRef<Path>>(path: P) -> (Vec<PathBuf>, Vec<Diagnostic>) {
+    let mut files = vec![];
+    let mut errors = Loaded::default();
+    let path = path.as_ref();
+    if path.is_dir() {
+        collect_files(path, &mut files, &mut errors);
+    } else {
+        files.push(path.to_path_buf());
+    }
+    (files, errors.diagnostics)
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>, errors: &mut Loaded) {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(err) => return errors.error(path.display().to_string(), err.to_string(), None),
+    };
+    let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for path in entries.into_iter().map(|entry| entry.path()) {
+        if path.is_dir() {
+            collect_files(&path, files, errors);
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext == "yml" || ext == "yaml")
+        {
+            files.push(path);
+        }
+    }
+}
+
+/// Split a YAML stream into its documents, each with the number of lines before it. Documents
+/// without content, like the one before a leading `---`, are skipped.
+fn documents(yaml: &str) -> Vec<(usize, &str)> {
+    let mut result = vec![];
+    let (mut start, mut start_line, mut end) = (0, 0, 0);
+    for (i, line) in yaml.split_inclusive('\n').enumerate() {
+        if line.trim_end() == "---" {
+            result.push((start_line, yaml.get(start..end).unwrap_or_default()));
+            start = end + line.len();
+            start_line = i + 1;
+        }
+        end += line.len();
+    }
+    result.push((start_line, yaml.get(start..).unwrap_or_default()));
+    result.retain(|(_, document)| {
+        document.lines().any(|line| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+    });
+    result
+}
+
+/// The files included into the detection of a rule so far
+#[derive(Default)]
+struct Includes {
+    /// The files currently being included, outermost first, to detect cycles
+    stack: Vec<(PathBuf, String)>,
+    included: HashSet<PathBuf>,
+}
+
+impl Includes {
+    /// Replace the [`INCLUDE_KEY`] of `selections` with the selections of the files it names,
+    /// relative to `base`
+    fn resolve(&mut self, selections: &mut Mapping, base: &Path) -> Result<(), String> {
+        let paths = match selections.shift_remove(INCLUDE_KEY) {
+            None => return Ok(()),
+            Some(Value::String(path)) => vec![path],
+            Some(Value::Sequence(paths)) => paths
+                .into_iter()
+                .map(|path| match path {
+                    Value::String(path) => Ok(path),
+                    _ => Err(format!(
+                        "'{}' must be a path or a list of paths",
+                        INCLUDE_KEY
+                    )),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => {
+                return Err(format!(
+                    "'{}' must be a path or a list of paths",
+                    INCLUDE_KEY
+                ))
+            }
+        };
+        for path in paths {
+            let path = base.join(path);
+            let name = path.display().to_string();
+            let canonical = fs::canonicalize(&path)
+                .map_err(|err| format!("Failed to include '{}': {}", name, err))?;
+            if self.stack.iter().any(|(file, _)| *file == canonical) {
+                let mut cycle: Vec<&str> =
+                    self.stack.iter().map(|(_, name)| name.as_str()).collect();
+                cycle.push(&name);
+                return Err(format!("Cyclic include: {}", cycle.join(" -> ")));
+            }
+            if !self.included.insert(canonical.clone()) {
+                continue;
+            }
+            let yaml = fs::read_to_string(&canonical)
+                .map_err(|err| format!("Failed to include '{}': {}", name, err))?;
+            let mut included = match serde_yml::from_str(&yaml) {
+                Ok(Value::Mapping(included)) => included,
+                Ok(_) => {
+                    return Err(format!(
+                        "The included file '{}' must be a mapping of selections",
+                        name
+                    ))
+                }
+                Err(err) => return Err(format!("Failed to include '{}': {}", name, err)),
+            };
+            self.stack.push((canonical, name.clone()));
+            self.resolve(&mut included, path.parent().unwrap_or(Path::new("")))?;
+            self.stack.pop();
+            for (key, selection) in included {
+                let key = match key {
+                    Value::String(key) if key != "condition" => key,
+                    key => {
+                        return Err(format!(
+                            "The included file '{}' must only contain selections, got '{:?}'",
+                            name, key
+                        ))
+                    }
+                };
+                if selections.contains_key(key.as_str()) {
+                    return Err(format!(
+                        "Selection '{}' of the included file '{}' is defined more than once",
+                        key, name
+                    ));
+                }
+                selections.insert(Value::String(key), selection);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The message of an error without the name of its selection, which is given separately
+fn without_selection(error: ParserError) -> String {
+    match error {
+        ParserError::SelectionParsingError(_, error) => error.to_string(),
+        error => error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Event;
+
+    const RULE: &str = r#"title: Whoami
+logsource:
+    category: process_creation
+detection:
+    selection:
+        Image|endswith: '\whoami.exe'
+    future:
+        CommandLine|unknown: whoami
+    condition: selection or future
+"#;
+
+    #[test]
+    fn test_skip() {
+        let loaded = Loader::new().load([
+            ("broken.yml", RULE),
+            ("syntax.yml", "title: [x"),
+            ("valid.yml", &RULE.replace("|unknown", "|contains")),
+        ]);
+        assert_eq!(loaded.rules.len(), 1);
+        assert!(loaded.has_errors());
+        assert_eq!(
+            loaded.diagnostics[0].to_string(),
+            "broken.yml:8:9: error[TAU0002]: Unknown field modifier 'unknown' provided"
+        );
+        assert_eq!(loaded.diagnostics[1].source, "syntax.yml");
+        assert!(loaded.diagnostics[1].span.is_some());
+        assert_eq!(loaded.diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn test_multiple_documents() {
+        let valid = RULE.replace("|unknown", "|contains");
+        let yaml = format!("---\n{}---\n# empty\n---\n{}", valid, RULE);
+        let loaded = Loader::new().load([("rules.yml", yaml)]);
+        assert_eq!(loaded.rules.len(), 1);
+        assert_eq!(
+            loaded.diagnostics[0].to_string(),
+            "rules.yml:21:9: error[TAU0002]: Unknown field modifier 'unknown' provided"
+        );
+        assert_eq!(documents("# only a comment\n---\n"), []);
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let yaml = RULE.replace("    future:", "    selection:");
+        let loaded = Loader::new()
+            .with_degraded_selections(true)
+            .load([("duplicate.yml", yaml)]);
+        assert!(loaded.rules.is_empty());
+        assert!(loaded.diagnostics[0]
+            .message
+            .contains("Selection 'selection' is defined more than once"));
+        assert!(loaded.diagnostics[0].span.is_some());
+    }
+
+    #[test]
+    fn test_degrade() {
+        let loaded = Loader::new()
+            .with_degraded_selections(true)
+            .load([("broken.yml", RULE)]);
+        assert!(!loaded.has_errors());
+        assert_eq!(
+            loaded.diagnostics[0].to_string(),
+            "broken.yml:8:9: warning[TAU0002]: selection 'future' is disabled: Unknown field modifier 'unknown' provided"
+        );
+
+        let rule = &loaded.rules[0];
+        assert!(rule.is_match(&Event::from([("Image", r"C:\whoami.exe")])));
+        assert!(!rule.is_match(&Event::from([("CommandLine", "whoami")])));
+
+        // Without the working selection the rule cannot match at all, but is still loaded
+        let yaml = RULE.replace("selection or future", "selection and future");
+        let loaded = Loader::new()
+            .with_degraded_selections(true)
+            .load([("broken.yml", yaml)]);
+        assert_eq!(loaded.rules.len(), 1);
+        assert!(!loaded.rules[0].is_match(&Event::from([("Image", r"C:\whoami.exe")])));
+    }
+
+    #[test]
+    fn test_no_degradation_under_negation() {
+        let yaml = RULE.replace("selection or future", "selection and not future");
+        let loaded = Loader::new()
+            .with_degraded_selections(true)
+            .load([("broken.yml", yaml)]);
+        assert!(loaded.rules.is_empty());
+        assert_eq!(loaded.diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_legacy_syntax() {
+        let yaml = RULE
+            .replace("|unknown", "|contains")
+            .replace("selection or future", "selection | count() > 5");
+        let loaded = Loader::new().load([("legacy.yml", &yaml)]);
+        assert!(loaded.rules.is_empty());
+        assert_eq!(loaded.diagnostics[0].code, Some("TAU0023"));
+
+        let config = EngineConfig {
+            spec_mode: crate::SpecMode::Legacy,
+            ..Default::default()
+        };
+        let loaded = Loader::new()
+            .with_config(config)
+            .load([("legacy.yml", &yaml)]);
+        assert_eq!(loaded.rules.len(), 1);
+        assert!(!loaded.has_errors());
+        assert_eq!(
+            loaded.diagnostics[0].to_string(),
+            "legacy.yml: warning[TAU0023]: legacy syntax: the aggregation '| count() > 5' is ignored, only the search before it is evaluated"
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let valid = RULE.replace("|unknown", "|contains");
+        let two_degraded = RULE.replace(
+            "    condition:",
+            "    other:\n        User|unknown: root\n    condition:",
+        );
+        let two_degraded =
+            two_degraded.replace("selection or future", "selection or future or other");
+        let correlation =
+            "title: Many logons\ncorrelation:\n    type: event_count\n    rules: [logon]\n";
+        let legacy = valid.replace("selection or future", "selection | count() > 5");
+        let config = EngineConfig {
+            spec_mode: crate::SpecMode::Legacy,
+            ..Default::default()
+        };
+        let loaded = Loader::new()
+            .with_degraded_selections(true)
+            .with_config(config)
+            .load([
+                ("valid.yml", valid.as_str()),
+                ("degraded.yml", RULE),
+                ("two_degraded.yml", &two_degraded),
+                ("negated.yml", &RULE.replace("or future", "and not future")),
+                ("correlation.yml", correlation),
+                ("legacy.yml", &legacy),
+                ("syntax.yml", "title: [x"),
+                ("undefined.yml", &valid.replace("or future", "or missing")),
+            ]);
+        assert_eq!(
+            loaded.diagnostics[4].feature.as_deref(),
+            Some("correlation")
+        );
+        assert_eq!(loaded.meta_rules[0].rules, ["logon"]);
+
+        let report = loaded.report();
+        assert_eq!((report.loaded, report.degraded, report.skipped), (4, 3, 4));
+        assert_eq!(
+            report.reasons["modifier 'unknown'"],
+            ReasonCount {
+                skipped: 1,
+                degraded: 2
+            }
+        );
+        assert_eq!(
+            report
+                .by_impact()
+                .iter()
+                .map(|(r, c)| (*r, c.total()))
+                .collect::<Vec<_>>(),
+            [
+                ("modifier 'unknown'", 3),
+                ("TAU0015", 1),
+                ("correlation", 1),
+                ("legacy condition", 1),
+                ("other", 1)
+            ]
+        );
+        assert_eq!(
+            report.to_string(),
+            "4 rules loaded, 3 of them degraded, 4 skipped
+  modifier 'unknown': 1 skipped, 2 degraded
+  TAU0015: 1 skipped
+  correlation: 1 skipped
+  legacy condition: 1 degraded
+  other: 1 skipped"
+        );
+        assert_eq!(Loaded::default().report(), LoadReport::default());
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = std::env::temp_dir().join(format!("sigma-loader-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("broken.yml"), RULE).unwrap();
+        fs::write(
+            dir.join("nested/valid.yaml"),
+            RULE.replace("|unknown", "|contains"),
+        )
+        .unwrap();
+        fs::write(dir.join("README.md"), "not a rule").unwrap();
+
+        let loaded = Loader::new().load_dir(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.rules.len(), 1);
+        assert_eq!(loaded.diagnostics.len(), 1);
+        assert!(loaded.diagnostics[0].source.ends_with("broken.yml"));
+
+        let loaded = Loader::new().load_dir(&dir);
+        assert!(loaded.has_errors());
+    }
+
+    #[test]
+    fn test_includes() {
+        let dir = std::env::temp_dir().join(format!("sigma-includes-{}", std::process::id()));
+        fs::create_dir_all(dir.join("rules")).unwrap();
+        fs::create_dir_all(dir.join("filters")).unwrap();
+        fs::write(
+            dir.join("filters/common.yml"),
+            "tau-include: [system.yml, system.yml]\nfilter_admin:\n    User: admin\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("filters/system.yml"),
+            "filter_system:\n    User: SYSTEM\n",
+        )
+        .unwrap();
+        let rule = RULE
+            .replace("|unknown", "|contains")
+            .replace(
+                "    selection:",
+                "    tau-include:\n        - ../filters/common.yml\n        - ../filters/system.yml\n    selection:",
+            )
+            .replace("selection or future", "selection and not 1 of filter_*");
+        fs::write(dir.join("rules/rule.yml"), &rule).unwrap();
+        fs::write(dir.join("filters/cycle.yml"), "tau-include: loop.yml\n").unwrap();
+        fs::write(dir.join("filters/loop.yml"), "tau-include: cycle.yml\n").unwrap();
+        fs::write(
+            dir.join("rules/cycle.yml"),
+            rule.replace("../filters/system.yml", "../filters/cycle.yml"),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("rules/duplicate.yml"),
+            rule.replace(
+                "    selection:",
+                "    filter_admin:\n        User: root\n    selection:",
+            ),
+        )
+        .unwrap();
+
+        let loaded = Loader::new()
+            .with_includes(true)
+            .load_dir(dir.join("rules"));
+        let disabled = Loader::new().load_dir(dir.join("rules"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded.rules.len(), 1);
+        let rule = &loaded.rules[0];
+        assert!(rule.is_match(&Event::from([("Image", r"C:\whoami.exe"), ("User", "bob")])));
+        assert!(!rule.is_match(&Event::from([
+            ("Image", r"C:\whoami.exe"),
+            ("User", "admin")
+        ])));
+        assert!(!rule.is_match(&Event::from([
+            ("Image", r"C:\whoami.exe"),
+            ("User", "SYSTEM")
+        ])));
+
+        assert_eq!(loaded.diagnostics.len(), 2);
+        assert!(loaded.diagnostics[0].source.ends_with("cycle.yml"));
+        let path = |path: &str| dir.join("rules").join(path).display().to_string();
+        assert_eq!(
+            loaded.diagnostics[0].message,
+            format!(
+                "Cyclic include: {} -> {} -> {}",
+                path("../filters/cycle.yml"),
+                path("../filters/loop.yml"),
+                path("../filters/cycle.yml")
+            )
+        );
+        assert!(loaded.diagnostics[1]
+            .message
+            .starts_with("Selection 'filter_admin' of the included file"));
+
+        assert!(disabled.rules.is_empty());
+        assert_eq!(disabled.report().reasons["include"].skipped, 3);
+    }
+}
@@ -0,0 +1,130 @@
+//! A map from entity keys to state that forgets the least recently used entity once it is full.
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug)]
+struct Entry<V> {
+    /// The sequence number of the last use
+    last_used: u64,
+    value: V,
+}
+
+/// A map from entity keys to values holding at most `max` entries. When a new key would exceed
+/// the limit, the entry that was used longest ago is evicted.
+#[derive(Debug)]
+pub(crate) struct Lru<V> {
+    max: Option<usize>,
+    entries: HashMap<String, Entry<V>>,
+    /// Keys ordered by the sequence number of their last use
+    recency: BTreeMap<u64, String>,
+    sequence: u64,
+}
+
+impl<V> Default for Lru<V> {
+    fn default() -> Self {
+        Self {
+            max: None,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            sequence: 0,
+        }
+    }
+}
+
+impl<V> Lru<V> {
+    /// Hold at most `max` entries. With a limit of 0, no entry is ever inserted.
+    pub(crate) fn set_max(&mut self, max: usize) {
+        self.max = Some(max);
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&V> {
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    /// Get the value of `key` without marking it as used
+    pub(crate) fn get_mut(&mut self, key: &str) -> Option<&mut V> {
+        self.entries.get_mut(key).map(|entry| &mut entry.value)
+    }
+
+    /// Mark `key` as the most recently used entry and get its value. A missing entry is inserted
+    /// with the value of `default`, evicting the least recently used entry if the map is full.
+    /// Returns `None` if the limit is 0.
+    pub(crate) fn touch(&mut self, key: &str, default: impl FnOnce() -> V) -> Option<&mut V> {
+        self.sequence += 1;
+        let last_used = self.sequence;
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                self.recency.remove(&entry.last_used);
+                entry.last_used = last_used;
+            }
+            None => {
+                if self.max == Some(0) {
+                    return None;
+                }
+                if self.max.is_some_and(|max| self.entries.len() >= max) {
+                    if let Some((_, oldest)) = self.recency.pop_first() {
+                        self.entries.remove(&oldest);
+                    }
+                }
+                self.entries.insert(
+                    key.to_string(),
+                    Entry {
+                        last_used,
+                        value: default(),
+                    },
+                );
+            }
+        }
+        self.recency.insert(last_used, key.to_string());
+        self.entries.get_mut(key).map(|entry| &mut entry.value)
+    }
+
+    pub(crate) fn remove(&mut self, key: &str) -> Option<V> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.last_used);
+        Some(entry.value)
+    }
+
+    /// Keep only the entries for which `f` returns true
+    pub(crate) fn retain(&mut self, mut f: impl FnMut(&mut V) -> bool) {
+        let recency = &mut self.recency;
+        self.entries.retain(|_, entry| {
+            let keep = f(&mut entry.value);
+            if !keep {
+                recency.remove(&entry.last_used);
+            }
+            keep
+        });
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let mut lru = Lru::default();
+        lru.set_max(2);
+        lru.touch("a", || 1);
+        lru.touch("b", || 2);
+        lru.touch("a", || 0);
+        lru.touch("c", || 3);
+
+        assert_eq!(lru.len(), 2);
+        assert_eq!(lru.get("a"), Some(&1));
+        assert_eq!(lru.get("b"), None);
+
+        assert_eq!(lru.remove("a"), Some(1));
+        lru.retain(|value| *value != 3);
+        assert_eq!(lru.len(), 0);
+        assert!(lru.recency.is_empty());
+
+        lru.set_max(0);
+        assert_eq!(lru.touch("d", || 4), None);
+        assert_eq!(lru.len(), 0);
+    }
+}
@@ -0,0 +1,385 @@
+//! Render [`MatchResult`]s into alert formats understood by SIEM backends.
+//!
+//! Two formats are supported:
+//! * [Elastic Common Schema (ECS)](https://www.elastic.co/guide/en/ecs/current/index.html) documents
+//! * [OCSF Detection Finding](https://schema.ocsf.io/classes/detection_finding) events
+use crate::result::MatchResult;
+use crate::rule::Level;
+use serde_json::{json, Map, Value};
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const PRODUCT_NAME: &str = env!("CARGO_PKG_NAME");
+const PRODUCT_VERSION: &str = env!("CARGO_PKG_VERSION");
+const OCSF_VERSION: &str = "1.1.0";
+
+// https://schema.ocsf.io/classes/detection_finding
+const OCSF_CATEGORY_FINDINGS: u32 = 2;
+const OCSF_CLASS_DETECTION_FINDING: u32 = 2004;
+const OCSF_ACTIVITY_CREATE: u32 = 1;
+
+/// The supported alert output formats
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutputFormat {
+    Ecs,
+    Ocsf,
+}
+
+impl OutputFormat {
+    /// Render a match result in this format
+    pub fn render(&self, result: &MatchResult) -> Value {
+        match self {
+            Self::Ecs => to_ecs(result),
+            Self::Ocsf => to_ocsf(result),
+        }
+    }
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// `SystemTime::now` panics on `wasm32-unknown-unknown`, so the clock of the JavaScript
+/// host is used if the `wasm` feature is enabled
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn now_millis() -> u64 {
+    #[cfg(feature = "wasm")]
+    return js_sys::Date::now() as u64;
+    #[cfg(not(feature = "wasm"))]
+    return 0;
+}
+
+fn extracted_fields(result: &MatchResult) -> Map<String, Value> {
+    result
+        .fields
+        .iter()
+        .map(|(k, v)| (k.to_string(), Value::from(v)))
+        .collect()
+}
+
+fn context_events(result: &MatchResult) -> Value {
+    Value::Array(result.context.iter().map(Value::from).collect())
+}
+
+fn annotations(result: &MatchResult) -> Value {
+    result
+        .annotations
+        .iter()
+        .map(|(k, v)| (k.to_string(), Value::from(v)))
+        .collect::<Map<_, _>>()
+        .into()
+}
+
+/// ECS `event.severity` values as used by Elastic Security detection rules
+fn ecs_severity(level: Option<Level>) -> u32 {
+    match level {
+        None | Some(Level::Informational) => 0,
+        Some(Level::Low) => 21,
+        Some(Level::Medium) => 47,
+        Some(Level::High) => 73,
+        Some(Level::Critical) => 99,
+    }
+}
+
+/// OCSF `severity_id` and `severity` caption
+fn ocsf_severity(level: Option<Level>) -> (u32, &'static str) {
+    match level {
+        None => (0, "Unknown"),
+        Some(Level::Informational) => (1, "Informational"),
+        Some(Level::Low) => (2, "Low"),
+        Some(Level::Medium) => (3, "Medium"),
+        Some(Level::High) => (4, "High"),
+        Some(Level::Critical) => (5, "Critical"),
+    }
+}
+
+/// Render a match result as an Elastic Common Schema alert document.
+///
+/// Rule metadata is mapped to the `rule.*` field set, the extracted event fields
+/// are placed under `sigma.fields` as they do not follow any ECS naming. `@timestamp` is the
+/// [`MatchResult::timestamp`] of the event, or the current time if the event has none.
+pub fn to_ecs(result: &MatchResult) -> Value {
+    let mut rule = Map::new();
+    rule.insert("name".to_string(), json!(result.rule_title));
+    rule.insert("ruleset".to_string(), json!("sigma"));
+    if let Some(id) = &result.rule_id {
+        rule.insert("id".to_string(), json!(id));
+        rule.insert("uuid".to_string(), json!(id));
+    }
+    if let Some(description) = &result.rule_description {
+        rule.insert("description".to_string(), json!(description));
+    }
+    if let Some(author) = &result.rule_author {
+        let authors: Vec<&str> = author.split(',').map(str::trim).collect();
+        rule.insert("author".to_string(), json!(authors));
+    }
+    if let Some(reference) = result.references.first() {
+        rule.insert("reference".to_string(), json!(reference));
+    }
+    if let Some(category) = &result.logsource.category {
+        rule.insert("category".to_string(), json!(category));
+    }
+
+    let mut sigma = Map::new();
+    if let Some(level) = result.level {
+        sigma.insert("level".to_string(), json!(level));
+    }
+    if let Some(status) = result.status {
+        sigma.insert("status".to_string(), json!(status));
+    }
+    sigma.insert(
+        "logsource".to_string(),
+        json!({
+            "product": result.logsource.product,
+            "category": result.logsource.category,
+            "service": result.logsource.service,
+        }),
+    );
+    sigma.insert(
+        "fields".to_string(),
+        Value::Object(extracted_fields(result)),
+    );
+    if !result.context.is_empty() {
+        sigma.insert("context".to_string(), context_events(result));
+    }
+    if !result.annotations.is_empty() {
+        sigma.insert("annotations".to_string(), annotations(result));
+    }
+
+    json!({
+        "@timestamp": result
+            .timestamp
+            .as_ref()
+            .map_or_else(|| json!(now_millis()), Value::from),
+        "ecs": {"version": "8.11.0"},
+        "event": {
+            "kind": "alert",
+            "severity": ecs_severity(result.level),
+            "provider": PRODUCT_NAME,
+        },
+        "rule": rule,
+        "tags": result.tags,
+        "threat": {
+            "framework": "MITRE ATT&CK",
+            "technique": result.attack_techniques()
+                .into_iter()
+                .map(|id| json!({"id": id}))
+                .collect::<Vec<Value>>(),
+        },
+        "sigma": sigma,
+    })
+}
+
+/// Render a match result as an OCSF Detection Finding (class 2004).
+///
+/// The extracted event fields are placed in the `unmapped` object.
+pub fn to_ocsf(result: &MatchResult) -> Value {
+    let (severity_id, severity) = ocsf_severity(result.level);
+
+    let mut analytic = Map::new();
+    analytic.insert("name".to_string(), json!(result.rule_title));
+    analytic.insert("type".to_string(), json!("Rule"));
+    analytic.insert("type_id".to_string(), json!(1));
+    if let Some(id) = &result.rule_id {
+        analytic.insert("uid".to_string(), json!(id));
+    }
+
+    let mut finding_info = Map::new();
+    finding_info.insert("title".to_string(), json!(result.rule_title));
+    finding_info.insert("analytic".to_string(), Value::Object(analytic));
+    if let Some(id) = &result.rule_id {
+        finding_info.insert("uid".to_string(), json!(id));
+    }
+    if let Some(description) = &result.rule_description {
+        finding_info.insert("desc".to_string(), json!(description));
+    }
+    if !result.tags.is_empty() {
+        finding_info.insert("types".to_string(), json!(result.tags));
+    }
+    let attacks: Vec<Value> = result
+        .attack_techniques()
+        .into_iter()
+        .map(|uid| json!({"technique": {"uid": uid}}))
+        .collect();
+    if !attacks.is_empty() {
+        finding_info.insert("attacks".to_string(), Value::Array(attacks));
+    }
+
+    let mut unmapped = extracted_fields(result);
+    if !result.context.is_empty() {
+        unmapped.insert("context".to_string(), context_events(result));
+    }
+    if !result.annotations.is_empty() {
+        unmapped.insert("annotations".to_string(), annotations(result));
+    }
+
+    json!({
+        "activity_id": OCSF_ACTIVITY_CREATE,
+        "category_uid": OCSF_CATEGORY_FINDINGS,
+        "class_uid": OCSF_CLASS_DETECTION_FINDING,
+        "type_uid": OCSF_CLASS_DETECTION_FINDING * 100 + OCSF_ACTIVITY_CREATE,
+        "severity_id": severity_id,
+        "severity": severity,
+        "status_id": 1,
+        "time": now_millis(),
+        "metadata": {
+            "version": OCSF_VERSION,
+            "product": {
+                "name": PRODUCT_NAME,
+                "version": PRODUCT_VERSION,
+            },
+        },
+        "finding_info": finding_info,
+        "unmapped": unmapped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rule_from_yaml, Event};
+
+    fn match_result() -> MatchResult {
+        let rule = rule_from_yaml(
+            r#"
+        title: Suspicious Shell
+        id: 0d1b5e7a-2f4c-4b8e-9a57-6f1a3c2e9d10
+        description: Detects a suspicious shell
+        author: Alice, Bob
+        level: critical
+        tags:
+            - attack.execution
+            - attack.t1059.001
+        references:
+            - https://example.org
+        logsource:
+            category: process_creation
+            product: linux
+        detection:
+            selection:
+                Image|endswith: /bash
+            condition: selection
+        fields:
+            - Image
+        "#,
+        )
+        .unwrap();
+        let event = Event::from([("Image", "/bin/bash"), ("User", "root")]);
+        rule.evaluate(&event).unwrap()
+    }
+
+    #[test]
+    fn test_ecs() {
+        let ecs = OutputFormat::Ecs.render(&match_result());
+
+        assert_eq!(ecs["event"]["kind"], "alert");
+        assert_eq!(ecs["event"]["severity"], 99);
+        assert_eq!(ecs["rule"]["name"], "Suspicious Shell");
+        assert_eq!(ecs["rule"]["id"], "0d1b5e7a-2f4c-4b8e-9a57-6f1a3c2e9d10");
+        assert_eq!(ecs["rule"]["author"], json!(["Alice", "Bob"]));
+        assert_eq!(ecs["rule"]["reference"], "https://example.org");
+        assert_eq!(ecs["rule"]["category"], "process_creation");
+        assert_eq!(ecs["threat"]["technique"][0]["id"], "T1059.001");
+        assert_eq!(ecs["sigma"]["level"], "critical");
+        assert_eq!(ecs["sigma"]["fields"], json!({"Image": "/bin/bash"}));
+        assert!(ecs["@timestamp"].is_u64());
+    }
+
+    #[test]
+    fn test_ecs_timestamp() {
+        let rule = rule_from_yaml(
+            r#"
+        title: Suspicious Shell
+        logsource:
+            category: process_creation
+        detection:
+            selection:
+                Image|endswith: /bash
+            condition: selection
+        "#,
+        )
+        .unwrap();
+        let event = Event::from([
+            ("Image", "/bin/bash"),
+            ("@timestamp", "2024-05-01T12:00:00.000Z"),
+        ]);
+        let ecs = to_ecs(&rule.evaluate(&event).unwrap());
+        assert_eq!(ecs["@timestamp"], "2024-05-01T12:00:00.000Z");
+    }
+
+    #[test]
+    fn test_ocsf() {
+        let ocsf = OutputFormat::Ocsf.render(&match_result());
+
+        assert_eq!(ocsf["class_uid"], 2004);
+        assert_eq!(ocsf["type_uid"], 200401);
+        assert_eq!(ocsf["severity_id"], 5);
+        assert_eq!(ocsf["severity"], "Critical");
+        assert_eq!(ocsf["finding_info"]["title"], "Suspicious Shell");
+        assert_eq!(
+            ocsf["finding_info"]["analytic"]["uid"],
+            "0d1b5e7a-2f4c-4b8e-9a57-6f1a3c2e9d10"
+        );
+        assert_eq!(
+            ocsf["finding_info"]["attacks"][0]["technique"]["uid"],
+            "T1059.001"
+        );
+        assert_eq!(ocsf["unmapped"], json!({"Image": "/bin/bash"}));
+        assert!(ocsf["time"].is_u64());
+    }
+
+    #[test]
+    fn test_annotations() {
+        let mut result = match_result();
+        result
+            .annotations
+            .insert("asset_criticality".to_string(), "high".into());
+
+        let ecs = to_ecs(&result);
+        assert_eq!(
+            ecs["sigma"]["annotations"],
+            json!({"asset_criticality": "high"})
+        );
+        let ocsf = to_ocsf(&result);
+        assert_eq!(
+            ocsf["unmapped"]["annotations"],
+            json!({"asset_criticality": "high"})
+        );
+        assert_eq!(result.to_json()["annotations"]["asset_criticality"], "high");
+        assert!(match_result().to_json().get("annotations").is_none());
+    }
+
+    #[test]
+    fn test_context() {
+        let mut result = match_result();
+        result.context = vec![Event::from([("Image", "/usr/bin/sudo")])];
+
+        let ecs = to_ecs(&result);
+        assert_eq!(ecs["sigma"]["context"], json!([{"Image": "/usr/bin/sudo"}]));
+
+        let ocsf = to_ocsf(&result);
+        assert_eq!(
+            ocsf["unmapped"]["context"],
+            json!([{"Image": "/usr/bin/sudo"}])
+        );
+    }
+
+    #[test]
+    fn test_attack_techniques() {
+        let tags = vec![
+            "attack.t1003".to_string(),
+            "attack.credential_access".to_string(),
+            "attack.T1055.012".to_string(),
+            "car.2013-05-002".to_string(),
+        ];
+        let result = MatchResult {
+            tags,
+            ..match_result()
+        };
+        assert_eq!(result.attack_techniques(), vec!["T1003", "T1055.012"]);
+    }
+}
@@ -0,0 +1,1530 @@
+//! Processing pipelines in the format of [pySigma](https://github.com/SigmaHQ/pySigma), which
+//! adapt rules to the schema of the events before they are compiled.
+//!
+//! A pipeline is a list of processing items. Each item consists of a transformation and
+//! optional conditions that restrict the rules and detection items it applies to. The
+//! following transformations are supported:
+//!
+//! * `field_name_mapping`, `field_name_prefix_mapping`, `field_name_prefix` and
+//!   `field_name_suffix` rename fields. Mapping a field to several names matches any of them.
+//! * `drop_detection_item` removes fields from the detection.
+//! * `add_condition` adds a selection that must match in addition to the condition.
+//! * `change_logsource` replaces the logsource of the rule.
+//! * `value_placeholders` and `wildcard_placeholders` resolve placeholders like `%admins%`
+//!   of values with the `expand` modifier, using the `vars` of the pipeline or `*`.
+//! * `replace_string`, `map_string`, `set_value` and `convert_type` rewrite values.
+//! * `normalize_registry_paths` normalizes Windows registry paths like the
+//!   [`NormalizeRegistryPaths`](crate::enrich::NormalizeRegistryPaths) enricher of events,
+//!   which is not part of pySigma.
+//! * `add_field`, `remove_field` and `set_field` change the `fields` of the rule.
+//! * `rule_failure` and `detection_item_failure` reject rules.
+//!
+//! Items are restricted with `rule_conditions` (`logsource`, `contains_detection_item`,
+//! `processing_item_applied` and `tag`), `field_name_conditions` (`include_fields` and
+//! `exclude_fields`) and `detection_item_conditions` (`match_string` and `is_null`). Each
+//! kind of condition is combined with the `*_cond_op` and negated with `*_cond_not` keys.
+//!
+//! Pipelines can also be built in Rust with [`Pipeline::with_item`], [`ProcessingItem`] and
+//! the constructors of [`Transformation`], e.g. to adapt community rules to a custom schema:
+//!
+//! ```rust
+//! use sigma_rust::pipeline::{Pipeline, ProcessingItem, Transformation};
+//!
+//! let pipeline = Pipeline::new()
+//!     .with_item(
+//!         ProcessingItem::new(Transformation::rename_field("Image", "process.executable"))
+//!             .for_logsource(Some("process_creation"), Some("windows"), None),
+//!     )
+//!     .with_item(
+//!         ProcessingItem::new(Transformation::add_field_condition("event.code", 1))
+//!             .for_logsource(Some("process_creation"), Some("windows"), None),
+//!     );
+//! ```
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::pipeline::Pipeline;
+//! use sigma_rust::Event;
+//!
+//! let pipeline = Pipeline::from_yaml(
+//!     r#"
+//! name: ECS
+//! transformations:
+//!   - id: ecs_process_creation
+//!     type: field_name_mapping
+//!     mapping:
+//!       Image: process.executable
+//!     rule_conditions:
+//!       - type: logsource
+//!         category: process_creation
+//! "#,
+//! )
+//! .unwrap();
+//!
+//! let rule = pipeline
+//!     .rule_from_yaml(
+//!         r#"
+//! title: Whoami
+//! logsource:
+//!     category: process_creation
+//! detection:
+//!     selection:
+//!         Image|endswith: '\whoami.exe'
+//!     condition: selection
+//! "#,
+//!     )
+//!     .unwrap();
+//!
+//! let event = Event::from([("process.executable", "C:\\Windows\\whoami.exe")]);
+//! assert!(rule.is_match(&event));
+//! ```
+use crate::detection::locate_rule_error;
+use crate::enrich::normalize_registry_path_with;
+use crate::re::Regex;
+use crate::rule::Rule;
+use serde::{Deserialize, Deserializer};
+use serde_yml::{Mapping, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Errors raised when a pipeline is applied to a rule
+#[derive(Debug, thiserror::Error)]
+pub enum PipelineError {
+    #[error("Failed to parse rule: {0}")]
+    Yaml(#[from] serde_yml::Error),
+
+    #[error("Rule rejected by the pipeline: {0}")]
+    Failure(String),
+
+    #[error("The placeholder '{0}' is not defined by the pipeline")]
+    UndefinedPlaceholder(String),
+
+    #[error("The field '{0}' occurs more than once in a selection after the transformation")]
+    ConflictingField(String),
+
+    #[error("Invalid regular expression: {0}")]
+    Regex(#[from] crate::re::Error),
+
+    #[error("The value '{0}' cannot be converted to a number")]
+    NotANumber(String),
+}
+
+/// A processing pipeline, see the [module documentation](self)
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Pipeline {
+    pub name: Option<String>,
+    /// Pipelines with a lower priority are applied first when they are chained
+    #[serde(default)]
+    pub priority: i64,
+    /// The values of the placeholders resolved by `value_placeholders`
+    #[serde(default, deserialize_with = "string_lists")]
+    pub vars: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub transformations: Vec<ProcessingItem>,
+}
+
+/// A transformation together with the conditions that decide where it is applied
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProcessingItem {
+    /// The identifier that is checked by `processing_item_applied` conditions
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub transformation: Transformation,
+    #[serde(default)]
+    pub rule_conditions: Vec<RuleCondition>,
+    #[serde(default)]
+    pub rule_cond_op: ConditionOp,
+    #[serde(default)]
+    pub rule_cond_not: bool,
+    #[serde(default)]
+    pub field_name_conditions: Vec<FieldNameCondition>,
+    #[serde(default)]
+    pub field_name_cond_op: ConditionOp,
+    #[serde(default)]
+    pub field_name_cond_not: bool,
+    #[serde(default)]
+    pub detection_item_conditions: Vec<DetectionItemCondition>,
+    #[serde(default)]
+    pub detection_item_cond_op: ConditionOp,
+    #[serde(default)]
+    pub detection_item_cond_not: bool,
+}
+
+/// The transformations of a processing item, see the [module documentation](self)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Transformation {
+    FieldNameMapping {
+        #[serde(deserialize_with = "string_lists")]
+        mapping: HashMap<String, Vec<String>>,
+    },
+    FieldNamePrefixMapping {
+        #[serde(deserialize_with = "string_lists")]
+        mapping: HashMap<String, Vec<String>>,
+    },
+    FieldNamePrefix {
+        prefix: String,
+    },
+    FieldNameSuffix {
+        suffix: String,
+    },
+    DropDetectionItem,
+    AddCondition {
+        conditions: Mapping,
+        #[serde(default)]
+        negated: bool,
+    },
+    ChangeLogsource {
+        category: Option<String>,
+        product: Option<String>,
+        service: Option<String>,
+    },
+    ValuePlaceholders {
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+    },
+    WildcardPlaceholders {
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+    },
+    ReplaceString {
+        regex: String,
+        replacement: String,
+    },
+    MapString {
+        #[serde(deserialize_with = "string_lists")]
+        mapping: HashMap<String, Vec<String>>,
+    },
+    SetValue {
+        value: Value,
+    },
+    /// Normalize the values with [`normalize_registry_path`], e.g. `HKEY_LOCAL_MACHINE` to
+    /// `HKLM` and `ControlSet001` to `CurrentControlSet`. Trailing separators of `contains` and
+    /// `startswith` values are kept, and values of the `re`, `cidr`, `fieldref` and numeric
+    /// modifiers are not normalized.
+    ///
+    /// [`normalize_registry_path`]: crate::enrich::normalize_registry_path
+    NormalizeRegistryPaths,
+    ConvertType {
+        target_type: TargetType,
+    },
+    AddField {
+        #[serde(deserialize_with = "string_list")]
+        field: Vec<String>,
+    },
+    RemoveField {
+        #[serde(deserialize_with = "string_list")]
+        field: Vec<String>,
+    },
+    SetField {
+        fields: Vec<String>,
+    },
+    RuleFailure {
+        message: String,
+    },
+    DetectionItemFailure {
+        message: String,
+    },
+    /// Rewrite the string values with a function, only available through the Rust API
+    #[serde(skip)]
+    RewriteValues(ValueRewrite),
+}
+
+/// A function that rewrites string values, see [`Transformation::rewrite_values`]
+#[derive(Clone)]
+pub struct ValueRewrite(Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+impl std::fmt::Debug for ValueRewrite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ValueRewrite")
+    }
+}
+
+impl PartialEq for ValueRewrite {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// The target of a `convert_type` transformation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetType {
+    Str,
+    Num,
+}
+
+/// How the conditions of a processing item are combined
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConditionOp {
+    #[default]
+    And,
+    Or,
+}
+
+/// Conditions on the whole rule
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleCondition {
+    /// Matches if all given attributes equal the ones of the logsource of the rule
+    Logsource {
+        category: Option<String>,
+        product: Option<String>,
+        service: Option<String>,
+    },
+    /// Matches if the detection contains a field with the value
+    ContainsDetectionItem { field: String, value: Value },
+    /// Matches if the processing item with the id was applied to the rule before
+    ProcessingItemApplied { processing_item_id: String },
+    /// Matches if the rule has the tag
+    Tag { tag: String },
+}
+
+/// Conditions on the name of a field
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FieldNameCondition {
+    IncludeFields {
+        #[serde(deserialize_with = "string_list")]
+        fields: Vec<String>,
+    },
+    ExcludeFields {
+        #[serde(deserialize_with = "string_list")]
+        fields: Vec<String>,
+    },
+}
+
+/// Conditions on the values of a field or keyword list
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DetectionItemCondition {
+    /// Matches if any string value matches the regular expression
+    MatchString {
+        pattern: String,
+        #[serde(default)]
+        negate: bool,
+    },
+    /// Matches if all values are null
+    IsNull {
+        #[serde(default)]
+        negate: bool,
+    },
+}
+
+impl Pipeline {
+    /// Create an empty pipeline, to which processing items are added with
+    /// [`Pipeline::with_item`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a processing item
+    pub fn with_item(mut self, item: ProcessingItem) -> Self {
+        self.transformations.push(item);
+        self
+    }
+
+    /// Define the values of a placeholder resolved by `value_placeholders`
+    pub fn with_var<I, S>(mut self, name: &str, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.vars.insert(
+            name.to_string(),
+            values.into_iter().map(Into::into).collect(),
+        );
+        self
+    }
+
+    /// Parse a pipeline from a YAML string
+    pub fn from_yaml(yaml: &str) -> Result<Self, serde_yml::Error> {
+        serde_yml::from_str(yaml)
+    }
+
+    /// Combine several pipelines into one. The transformations are applied in the order of
+    /// the priorities of the pipelines and the variables are merged.
+    pub fn chain<I: IntoIterator<Item = Pipeline>>(pipelines: I) -> Self {
+        let mut pipelines: Vec<Pipeline> = pipelines.into_iter().collect();
+        pipelines.sort_by_key(|p| p.priority);
+
+        let mut result = Self::default();
+        for pipeline in pipelines {
+            result.name = result.name.or(pipeline.name);
+            result.vars.extend(pipeline.vars);
+            result.transformations.extend(pipeline.transformations);
+        }
+        result
+    }
+
+    /// Parse a rule from a YAML string and apply the pipeline to it before it is compiled
+    pub fn rule_from_yaml(&self, yaml: &str) -> Result<Rule, PipelineError> {
+        let mut rule: Value = serde_yml::from_str(yaml)?;
+        self.apply(&mut rule)?;
+        Ok(Rule::deserialize(&rule).map_err(|err| locate_rule_error(yaml, &rule, err))?)
+    }
+
+    /// Apply the pipeline to the YAML representation of a rule
+    pub fn apply(&self, rule: &mut Value) -> Result<(), PipelineError> {
+        let Some(rule) = rule.as_mapping_mut() else {
+            // Let the rule parser report the error
+            return Ok(());
+        };
+        let mut state = RuleState::new(rule);
+        for item in &self.transformations {
+            if item.apply(&mut state, &self.vars)? {
+                state.applied.extend(item.id.clone());
+            }
+        }
+        state.write(rule);
+        Ok(())
+    }
+}
+
+impl ProcessingItem {
+    /// Create a processing item that applies the transformation to all rules and fields
+    pub fn new(transformation: Transformation) -> Self {
+        Self {
+            id: None,
+            transformation,
+            rule_conditions: vec![],
+            rule_cond_op: ConditionOp::default(),
+            rule_cond_not: false,
+            field_name_conditions: vec![],
+            field_name_cond_op: ConditionOp::default(),
+            field_name_cond_not: false,
+            detection_item_conditions: vec![],
+            detection_item_cond_op: ConditionOp::default(),
+            detection_item_cond_not: false,
+        }
+    }
+
+    /// Set the identifier that is checked by `processing_item_applied` conditions
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    /// Only apply the item to rules whose logsource has the given attributes. Attributes that
+    /// are `None` match any value.
+    pub fn for_logsource(
+        self,
+        category: Option<&str>,
+        product: Option<&str>,
+        service: Option<&str>,
+    ) -> Self {
+        self.when(RuleCondition::Logsource {
+            category: category.map(str::to_string),
+            product: product.map(str::to_string),
+            service: service.map(str::to_string),
+        })
+    }
+
+    /// Only apply the item to rules that fulfill the condition in addition to the existing
+    /// rule conditions
+    pub fn when(mut self, condition: RuleCondition) -> Self {
+        self.rule_conditions.push(condition);
+        self
+    }
+
+    /// Only apply the item to the given fields
+    pub fn for_fields<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.field_name_conditions
+            .push(FieldNameCondition::IncludeFields {
+                fields: fields.into_iter().map(Into::into).collect(),
+            });
+        self
+    }
+
+    /// Apply the transformation and return whether it was applied
+    fn apply(
+        &self,
+        state: &mut RuleState,
+        vars: &HashMap<String, Vec<String>>,
+    ) -> Result<bool, PipelineError> {
+        let rule_matches = combine(
+            self.rule_conditions.iter().map(|c| state.matches(c)),
+            self.rule_cond_op,
+            self.rule_cond_not,
+        );
+        if !rule_matches {
+            return Ok(false);
+        }
+
+        match &self.transformation {
+            Transformation::ChangeLogsource {
+                category,
+                product,
+                service,
+            } => {
+                for (key, value) in [
+                    ("category", category),
+                    ("product", product),
+                    ("service", service),
+                ] {
+                    match value {
+                        Some(value) => state.logsource.insert(key.into(), value.as_str().into()),
+                        None => state.logsource.remove(key),
+                    };
+                }
+                Ok(true)
+            }
+            Transformation::AddCondition {
+                conditions,
+                negated,
+            } => {
+                state.add_condition(conditions.clone(), *negated);
+                Ok(true)
+            }
+            Transformation::AddField { field } => {
+                let fields = state.fields.get_or_insert_with(Vec::new);
+                for f in field {
+                    if !fields.contains(f) {
+                        fields.push(f.clone());
+                    }
+                }
+                Ok(true)
+            }
+            Transformation::RemoveField { field } => {
+                if let Some(fields) = &mut state.fields {
+                    fields.retain(|f| !field.contains(f));
+                }
+                Ok(true)
+            }
+            Transformation::SetField { fields } => {
+                state.fields = Some(fields.clone());
+                Ok(true)
+            }
+            Transformation::RuleFailure { message } => Err(PipelineError::Failure(message.clone())),
+            transformation => {
+                let mut applied = false;
+                state.transform_items(&mut |item| {
+                    if !self.matches(item)? {
+                        return Ok(vec![item.clone()]);
+                    }
+                    applied = true;
+                    transformation.apply(item.clone(), vars)
+                })?;
+                if applied {
+                    state.map_fields(transformation, &|field| self.matches_field_name(field));
+                }
+                Ok(applied)
+            }
+        }
+    }
+
+    fn matches_field_name(&self, field: Option<&str>) -> bool {
+        combine(
+            self.field_name_conditions.iter().map(|c| c.matches(field)),
+            self.field_name_cond_op,
+            self.field_name_cond_not,
+        )
+    }
+
+    /// Check the field name and detection item conditions
+    fn matches(&self, item: &Item) -> Result<bool, PipelineError> {
+        let field_name_matches = self.matches_field_name(item.field.as_deref());
+        let detection_item_matches = combine(
+            self.detection_item_conditions
+                .iter()
+                .map(|c| c.matches(item))
+                .collect::<Result<Vec<_>, _>>()?,
+            self.detection_item_cond_op,
+            self.detection_item_cond_not,
+        );
+        Ok(field_name_matches && detection_item_matches)
+    }
+}
+
+/// Combine the results of conditions. Items without conditions always apply.
+fn combine<I: IntoIterator<Item = bool>>(results: I, op: ConditionOp, not: bool) -> bool {
+    let mut results = results.into_iter().peekable();
+    if results.peek().is_none() {
+        return true;
+    }
+    let result = match op {
+        ConditionOp::And => results.all(|r| r),
+        ConditionOp::Or => results.any(|r| r),
+    };
+    result != not
+}
+
+impl FieldNameCondition {
+    fn matches(&self, field: Option<&str>) -> bool {
+        match self {
+            Self::IncludeFields { fields } => field.is_some_and(|f| fields.iter().any(|i| i == f)),
+            Self::ExcludeFields { fields } => !field.is_some_and(|f| fields.iter().any(|e| e == f)),
+        }
+    }
+}
+
+impl DetectionItemCondition {
+    fn matches(&self, item: &Item) -> Result<bool, PipelineError> {
+        match self {
+            Self::MatchString { pattern, negate } => {
+                let regex = Regex::new(pattern)?;
+                let result = item
+                    .values
+                    .iter()
+                    .any(|v| v.as_str().is_some_and(|s| regex.is_match(s)));
+                Ok(result != *negate)
+            }
+            Self::IsNull { negate } => Ok(item.values.iter().all(Value::is_null) != *negate),
+        }
+    }
+}
+
+impl Transformation {
+    /// Rename a field
+    pub fn rename_field(from: &str, to: &str) -> Self {
+        Self::rename_fields([(from, to)])
+    }
+
+    /// Rename several fields
+    pub fn rename_fields<'a, I: IntoIterator<Item = (&'a str, &'a str)>>(mapping: I) -> Self {
+        Self::FieldNameMapping {
+            mapping: mapping
+                .into_iter()
+                .map(|(from, to)| (from.to_string(), vec![to.to_string()]))
+                .collect(),
+        }
+    }
+
+    /// Replace string values that are equal to a key of the mapping with its value
+    pub fn map_values<'a, I: IntoIterator<Item = (&'a str, &'a str)>>(mapping: I) -> Self {
+        Self::MapString {
+            mapping: mapping
+                .into_iter()
+                .map(|(from, to)| (from.to_string(), vec![to.to_string()]))
+                .collect(),
+        }
+    }
+
+    /// Rewrite all string values with a function, e.g. to convert paths to another format.
+    /// Values of the `re` and `cidr` modifiers are not rewritten.
+    pub fn rewrite_values<F: Fn(&str) -> String + Send + Sync + 'static>(f: F) -> Self {
+        Self::RewriteValues(ValueRewrite(Arc::new(f)))
+    }
+
+    /// Add a field that must match the value in addition to the condition of the rule,
+    /// e.g. the event id of the events a logsource refers to
+    pub fn add_field_condition<V: Into<Value>>(field: &str, value: V) -> Self {
+        let mut conditions = Mapping::new();
+        conditions.insert(field.into(), value.into());
+        Self::AddCondition {
+            conditions,
+            negated: false,
+        }
+    }
+
+    /// Transform a detection item. Returns the items of which any must match instead, which
+    /// is empty if the item is dropped.
+    fn apply(
+        &self,
+        mut item: Item,
+        vars: &HashMap<String, Vec<String>>,
+    ) -> Result<Vec<Item>, PipelineError> {
+        match self {
+            Self::FieldNameMapping { .. }
+            | Self::FieldNamePrefixMapping { .. }
+            | Self::FieldNamePrefix { .. }
+            | Self::FieldNameSuffix { .. } => {
+                let Some(field) = &item.field else {
+                    return Ok(vec![item]);
+                };
+                Ok(self
+                    .map_field(field)
+                    .into_iter()
+                    .map(|field| Item {
+                        field: Some(field),
+                        ..item.clone()
+                    })
+                    .collect())
+            }
+            Self::DropDetectionItem => Ok(vec![]),
+            Self::ValuePlaceholders { include, exclude } => {
+                item.expand(include, exclude, &|name| {
+                    vars.get(name)
+                        .cloned()
+                        .ok_or_else(|| PipelineError::UndefinedPlaceholder(name.to_string()))
+                })?;
+                Ok(vec![item])
+            }
+            Self::WildcardPlaceholders { include, exclude } => {
+                item.expand(include, exclude, &|_| Ok(vec!["*".to_string()]))?;
+                Ok(vec![item])
+            }
+            Self::ReplaceString { regex, replacement } => {
+                let regex = Regex::new(regex)?;
+                item.rewrite(&|s| regex.replace_all(s, replacement.as_str()).into_owned());
+                Ok(vec![item])
+            }
+            Self::RewriteValues(rewrite) => {
+                item.rewrite(&*rewrite.0);
+                Ok(vec![item])
+            }
+            Self::NormalizeRegistryPaths => {
+                // Values compared as numbers and names of other fields are no paths
+                if !["gt", "gte", "lt", "lte", "fieldref"]
+                    .iter()
+                    .any(|modifier| item.has_modifier(modifier))
+                {
+                    let trim = !item.has_modifier("contains") && !item.has_modifier("startswith");
+                    item.rewrite(&|s| normalize_registry_path_with(s, trim));
+                }
+                Ok(vec![item])
+            }
+            Self::MapString { mapping } => {
+                item.values = item
+                    .values
+                    .into_iter()
+                    .flat_map(|value| match value.as_str().and_then(|s| mapping.get(s)) {
+                        Some(mapped) => mapped.iter().map(|s| Value::from(s.as_str())).collect(),
+                        None => vec![value],
+                    })
+                    .collect();
+                Ok(vec![item])
+            }
+            Self::SetValue { value } => {
+                item.values = vec![value.clone()];
+                Ok(vec![item])
+            }
+            Self::ConvertType { target_type } => {
+                for value in item.values.iter_mut() {
+                    *value = convert(value, *target_type)?;
+                }
+                Ok(vec![item])
+            }
+            Self::DetectionItemFailure { message } => Err(PipelineError::Failure(message.clone())),
+            Self::ChangeLogsource { .. }
+            | Self::AddCondition { .. }
+            | Self::AddField { .. }
+            | Self::RemoveField { .. }
+            | Self::SetField { .. }
+            | Self::RuleFailure { .. } => Ok(vec![item]),
+        }
+    }
+
+    /// Returns the new names of a field for transformations that rename fields
+    fn map_field(&self, field: &str) -> Vec<String> {
+        match self {
+            Self::FieldNameMapping { mapping } => match mapping.get(field) {
+                Some(names) if !names.is_empty() => names.clone(),
+                _ => vec![field.to_string()],
+            },
+            Self::FieldNamePrefixMapping { mapping } => {
+                // The longest matching prefix wins
+                let prefix = mapping
+                    .keys()
+                    .filter(|prefix| field.starts_with(prefix.as_str()))
+                    .max_by_key(|prefix| prefix.len());
+                match prefix {
+                    Some(prefix) => mapping[prefix]
+                        .iter()
+                        .map(|p| format!("{}{}", p, &field[prefix.len()..]))
+                        .collect(),
+                    None => vec![field.to_string()],
+                }
+            }
+            Self::FieldNamePrefix { prefix } => vec![format!("{}{}", prefix, field)],
+            Self::FieldNameSuffix { suffix } => vec![format!("{}{}", field, suffix)],
+            _ => vec![field.to_string()],
+        }
+    }
+}
+
+fn convert(value: &Value, target_type: TargetType) -> Result<Value, PipelineError> {
+    match (target_type, value) {
+        (TargetType::Str, Value::Number(n)) => Ok(Value::from(n.to_string())),
+        (TargetType::Str, Value::Bool(b)) => Ok(Value::from(b.to_string())),
+        (TargetType::Num, Value::String(s)) => {
+            if let Ok(i) = s.parse::<i64>() {
+                Ok(Value::from(i))
+            } else if let Ok(f) = s.parse::<f64>() {
+                Ok(Value::from(f))
+            } else {
+                Err(PipelineError::NotANumber(s.clone()))
+            }
+        }
+        _ => Ok(value.clone()),
+    }
+}
+
+/// A field of a selection with its values, or the values of a keyword selection
+#[derive(Debug, Clone, PartialEq)]
+struct Item {
+    /// `None` for keywords
+    field: Option<String>,
+    modifiers: Vec<String>,
+    values: Vec<Value>,
+}
+
+impl Item {
+    fn new(key: Option<&str>, value: Value) -> Self {
+        let (field, modifiers) = match key {
+            Some(key) => {
+                let mut parts = key.split('|');
+                let field = parts.next().unwrap_or_default().to_string();
+                (Some(field), parts.map(str::to_string).collect())
+            }
+            None => (None, vec![]),
+        };
+        let values = match value {
+            Value::Sequence(values) => values,
+            value => vec![value],
+        };
+        Self {
+            field,
+            modifiers,
+            values,
+        }
+    }
+
+    fn key(&self) -> String {
+        let mut key = self.field.clone().unwrap_or_default();
+        for modifier in &self.modifiers {
+            key.push('|');
+            key.push_str(modifier);
+        }
+        key
+    }
+
+    fn value(&self) -> Value {
+        match &self.values[..] {
+            [value] => value.clone(),
+            values => Value::Sequence(values.to_vec()),
+        }
+    }
+
+    /// Rewrite the string values, except for regular expressions and CIDR ranges
+    fn rewrite(&mut self, f: &dyn Fn(&str) -> String) {
+        if self.has_modifier("re") || self.has_modifier("cidr") {
+            return;
+        }
+        for value in self.values.iter_mut() {
+            if let Value::String(s) = value {
+                *s = f(s);
+            }
+        }
+    }
+
+    fn has_modifier(&self, modifier: &str) -> bool {
+        self.modifiers
+            .iter()
+            .any(|m| m.eq_ignore_ascii_case(modifier))
+    }
+
+    /// Replace the placeholders of the values with the results of `resolve`, if the item has
+    /// the `expand` modifier. A value with a placeholder that resolves to several values is
+    /// replaced by all combinations. The modifier is removed once no placeholders remain.
+    fn expand(
+        &mut self,
+        include: &Option<Vec<String>>,
+        exclude: &Option<Vec<String>>,
+        resolve: &dyn Fn(&str) -> Result<Vec<String>, PipelineError>,
+    ) -> Result<(), PipelineError> {
+        if !self.has_modifier("expand") {
+            return Ok(());
+        }
+        let selected = |name: &str| {
+            include
+                .as_ref()
+                .map_or(true, |i| i.iter().any(|n| n == name))
+                && !exclude
+                    .as_ref()
+                    .is_some_and(|e| e.iter().any(|n| n == name))
+        };
+
+        let mut values = vec![];
+        let mut remaining = false;
+        for value in std::mem::take(&mut self.values) {
+            let Value::String(s) = value else {
+                values.push(value);
+                continue;
+            };
+            let mut expanded = vec![String::new()];
+            let mut rest = s.as_str();
+            while let Some((name, start, end)) = placeholder(rest) {
+                let prefix = &rest[..start];
+                let replacements = if selected(name) {
+                    resolve(name)?
+                } else {
+                    remaining = true;
+                    vec![rest[start..end].to_string()]
+                };
+                expanded = expanded
+                    .iter()
+                    .flat_map(|e| {
+                        replacements
+                            .iter()
+                            .map(move |r| format!("{}{}{}", e, prefix, r))
+                    })
+                    .collect();
+                rest = &rest[end..];
+            }
+            values.extend(expanded.into_iter().map(|e| Value::from(e + rest)));
+        }
+        self.values = values;
+        if !remaining {
+            self.modifiers.retain(|m| !m.eq_ignore_ascii_case("expand"));
+        }
+        Ok(())
+    }
+}
+
+/// Find the first placeholder `%name%` and return its name and range
+pub(crate) fn placeholder(s: &str) -> Option<(&str, usize, usize)> {
+    let start = s.find('%')?;
+    let len = s[start + 1..].find('%')?;
+    let name = &s[start + 1..start + 1 + len];
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        // Not a placeholder, continue after the first percent sign
+        let (name, s2, e2) = placeholder(&s[start + 1..])?;
+        return Some((name, start + 1 + s2, start + 1 + e2));
+    }
+    Some((name, start, start + len + 2))
+}
+
+/// The parsed definition of a selection
+#[derive(Debug)]
+enum Definition {
+    Keywords(Item),
+    /// Field groups that are combined with `or`
+    Groups(Vec<Vec<Item>>),
+    /// A definition that is left to the rule parser to reject
+    Invalid(Value),
+}
+
+impl Definition {
+    fn new(value: Value) -> Self {
+        let definition = match &value {
+            Value::Mapping(mapping) => group(mapping).map(|group| Self::Groups(vec![group])),
+            Value::Sequence(seq) if !seq.is_empty() && seq.iter().all(Value::is_mapping) => seq
+                .iter()
+                .map(|v| group(v.as_mapping()?))
+                .collect::<Option<Vec<_>>>()
+                .map(Self::Groups),
+            Value::Sequence(seq) if !seq.iter().any(Value::is_mapping) => {
+                Some(Self::Keywords(Item::new(None, value.clone())))
+            }
+            _ => None,
+        };
+        definition.unwrap_or(Self::Invalid(value))
+    }
+
+    fn to_value(&self) -> Result<Value, PipelineError> {
+        match self {
+            Self::Keywords(item) => Ok(Value::Sequence(item.values.clone())),
+            Self::Groups(groups) => {
+                let mut result = vec![];
+                for group in groups {
+                    let mut mapping = Mapping::new();
+                    for item in group {
+                        let key = item.key();
+                        if mapping.contains_key(key.as_str()) {
+                            return Err(PipelineError::ConflictingField(key));
+                        }
+                        mapping.insert(key.into(), item.value());
+                    }
+                    result.push(Value::Mapping(mapping));
+                }
+                Ok(match <[Value; 1]>::try_from(result) {
+                    Ok([value]) => value,
+                    Err(result) => Value::Sequence(result),
+                })
+            }
+            Self::Invalid(value) => Ok(value.clone()),
+        }
+    }
+}
+
+fn group(mapping: &Mapping) -> Option<Vec<Item>> {
+    mapping
+        .iter()
+        .map(|(k, v)| Some(Item::new(Some(k.as_str()?), v.clone())))
+        .collect()
+}
+
+/// The parts of a rule that are transformed by a pipeline
+struct RuleState {
+    logsource: Mapping,
+    tags: Vec<String>,
+    fields: Option<Vec<String>>,
+    selections: Vec<(String, Definition)>,
+    condition: Value,
+    /// The ids of the processing items applied to the rule
+    applied: HashSet<String>,
+    /// Selections that could not be parsed, e.g. with non-string keys
+    invalid: Mapping,
+}
+
+impl RuleState {
+    fn new(rule: &Mapping) -> Self {
+        let logsource = rule
+            .get("logsource")
+            .and_then(Value::as_mapping)
+            .cloned()
+            .unwrap_or_default();
+        let strings = |key: &str| -> Option<Vec<String>> {
+            let seq = rule.get(key)?.as_sequence()?;
+            Some(
+                seq.iter()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect(),
+            )
+        };
+
+        let mut selections = vec![];
+        let mut condition = Value::Null;
+        let mut invalid = Mapping::new();
+        if let Some(detection) = rule.get("detection").and_then(Value::as_mapping) {
+            for (name, value) in detection {
+                match name.as_str() {
+                    Some("condition") => condition = value.clone(),
+                    Some(name) => {
+                        selections.push((name.to_string(), Definition::new(value.clone())))
+                    }
+                    None => {
+                        invalid.insert(name.clone(), value.clone());
+                    }
+                }
+            }
+        }
+
+        Self {
+            logsource,
+            tags: strings("tags").unwrap_or_default(),
+            fields: strings("fields"),
+            selections,
+            condition,
+            applied: HashSet::new(),
+            invalid,
+        }
+    }
+
+    fn write(self, rule: &mut Mapping) {
+        rule.insert("logsource".into(), Value::Mapping(self.logsource));
+        if let Some(fields) = self.fields {
+            rule.insert(
+                "fields".into(),
+                fields.into_iter().map(Value::from).collect(),
+            );
+        }
+        let Some(detection) = rule.get_mut("detection").and_then(Value::as_mapping_mut) else {
+            return;
+        };
+        let mut result = self.invalid;
+        for (name, definition) in &self.selections {
+            // Conflicts were reported by `transform_items`, so every definition converts
+            if let Ok(value) = definition.to_value() {
+                result.insert(name.as_str().into(), value);
+            }
+        }
+        result.insert("condition".into(), self.condition);
+        *detection = result;
+    }
+
+    fn matches(&self, condition: &RuleCondition) -> bool {
+        match condition {
+            RuleCondition::Logsource {
+                category,
+                product,
+                service,
+            } => [
+                ("category", category),
+                ("product", product),
+                ("service", service),
+            ]
+            .into_iter()
+            .all(|(key, expected)| {
+                expected.as_ref().map_or(true, |expected| {
+                    self.logsource.get(key).and_then(Value::as_str) == Some(expected.as_str())
+                })
+            }),
+            RuleCondition::ContainsDetectionItem { field, value } => self.items().any(|item| {
+                item.field.as_deref() == Some(field.as_str()) && item.values.contains(value)
+            }),
+            RuleCondition::ProcessingItemApplied { processing_item_id } => {
+                self.applied.contains(processing_item_id)
+            }
+            RuleCondition::Tag { tag } => self.tags.contains(tag),
+        }
+    }
+
+    fn items(&self) -> impl Iterator<Item = &Item> {
+        self.selections
+            .iter()
+            .flat_map(|(_, definition)| -> Box<dyn Iterator<Item = &Item>> {
+                match definition {
+                    Definition::Keywords(item) => Box::new(std::iter::once(item)),
+                    Definition::Groups(groups) => Box::new(groups.iter().flatten()),
+                    Definition::Invalid(_) => Box::new(std::iter::empty()),
+                }
+            })
+    }
+
+    /// Replace every item with the items returned by `transform`. If several items are
+    /// returned for an item of a field group, the group is split into one group per item,
+    /// so any of them must match.
+    fn transform_items(
+        &mut self,
+        transform: &mut dyn FnMut(&Item) -> Result<Vec<Item>, PipelineError>,
+    ) -> Result<(), PipelineError> {
+        for (_, definition) in self.selections.iter_mut() {
+            match definition {
+                Definition::Keywords(item) => {
+                    // Keywords cannot be split or dropped, only their values are transformed
+                    if let Some(transformed) = transform(item)?.into_iter().next() {
+                        item.values = transformed.values;
+                    }
+                }
+                Definition::Groups(groups) => {
+                    let mut result = vec![];
+                    for group in groups.iter() {
+                        let mut alternatives: Vec<Vec<Item>> = vec![vec![]];
+                        for item in group {
+                            let transformed = transform(item)?;
+                            if transformed.is_empty() {
+                                continue;
+                            }
+                            alternatives = alternatives
+                                .into_iter()
+                                .flat_map(|alternative| {
+                                    transformed.iter().map(move |item| {
+                                        let mut alternative = alternative.clone();
+                                        alternative.push(item.clone());
+                                        alternative
+                                    })
+                                })
+                                .collect();
+                        }
+                        result.extend(alternatives);
+                    }
+                    *groups = result;
+                }
+                Definition::Invalid(_) => {}
+            }
+            definition.to_value()?;
+        }
+        Ok(())
+    }
+
+    /// Rename the fields of the rule that are displayed with a match
+    fn map_fields(
+        &mut self,
+        transformation: &Transformation,
+        matches: &dyn Fn(Option<&str>) -> bool,
+    ) {
+        if let Some(fields) = &mut self.fields {
+            let mut result: Vec<String> = vec![];
+            for field in fields.iter() {
+                let mapped = if matches(Some(field)) {
+                    transformation.map_field(field)
+                } else {
+                    vec![field.clone()]
+                };
+                for mapped in mapped {
+                    if !result.contains(&mapped) {
+                        result.push(mapped);
+                    }
+                }
+            }
+            *fields = result;
+        }
+    }
+
+    /// Add a selection that must match in addition to the condition of the rule
+    #[allow(clippy::unwrap_used)] // an unused name is always found
+    fn add_condition(&mut self, conditions: Mapping, negated: bool) {
+        let name = (0..)
+            .map(|i| format!("_cond_{}", i))
+            .find(|name| self.selections.iter().all(|(n, _)| n != name))
+            .unwrap();
+        let not = if negated { "not " } else { "" };
+        let wrap = |condition: &str| format!("{}{} and ({})", not, name, condition);
+        self.condition = match &self.condition {
+            Value::String(condition) => Value::from(wrap(condition)),
+            Value::Sequence(conditions) => conditions
+                .iter()
+                .map(|c| c.as_str().map_or(c.clone(), |c| Value::from(wrap(c))))
+                .collect(),
+            condition => condition.clone(),
+        };
+        self.selections
+            .push((name, Definition::new(Value::Mapping(conditions))));
+    }
+}
+
+/// Deserialize a mapping whose values are a string or a list of strings
+fn string_lists<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, Vec<String>>, D::Error> {
+    let mapping: HashMap<String, Value> = HashMap::deserialize(deserializer)?;
+    mapping
+        .into_iter()
+        .map(|(k, v)| Ok((k, strings(v).map_err(serde::de::Error::custom)?)))
+        .collect()
+}
+
+/// Deserialize a string or a list of strings
+fn string_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    strings(Value::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+fn strings(value: Value) -> Result<Vec<String>, String> {
+    let scalar = |value: Value| match value {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        value => Err(format!("expected a string, got {:?}", value)),
+    };
+    match value {
+        Value::Sequence(seq) => seq.into_iter().map(scalar).collect(),
+        value => Ok(vec![scalar(value)?]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enrich::{Enricher, NormalizeRegistryPaths};
+    use crate::event::Event;
+
+    const RULE: &str = r#"
+title: Test
+logsource:
+    category: process_creation
+    product: windows
+fields:
+    - Image
+    - User
+detection:
+    selection:
+        Image|endswith: '\cmd.exe'
+        User|expand: '%admins%'
+    keywords:
+        - 'evil'
+    condition: selection or keywords
+"#;
+
+    fn pipeline(yaml: &str) -> Pipeline {
+        Pipeline::from_yaml(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_field_name_mapping() {
+        let pipeline = pipeline(
+            r#"
+vars:
+    admins: [root, admin]
+transformations:
+    - id: mapping
+      type: field_name_mapping
+      mapping:
+          Image: [process.executable, process.name]
+          User: user.name
+    - type: value_placeholders
+    - type: field_name_prefix
+      prefix: win.
+      rule_conditions:
+          - type: processing_item_applied
+            processing_item_id: mapping
+      field_name_conditions:
+          - type: include_fields
+            fields: [user.name]
+"#,
+        );
+        let rule = pipeline.rule_from_yaml(RULE).unwrap();
+        assert_eq!(
+            rule.fields,
+            Some(vec![
+                "process.executable".to_string(),
+                "process.name".to_string(),
+                "win.user.name".to_string()
+            ])
+        );
+
+        assert!(rule.is_match(&Event::from([
+            ("process.name", "C:\\cmd.exe"),
+            ("win.user.name", "admin")
+        ])));
+        assert!(rule.is_match(&Event::from([
+            ("process.executable", "C:\\cmd.exe"),
+            ("win.user.name", "root")
+        ])));
+        assert!(!rule.is_match(&Event::from([
+            ("Image", "C:\\cmd.exe"),
+            ("win.user.name", "root")
+        ])));
+        assert!(!rule.is_match(&Event::from([
+            ("process.name", "C:\\cmd.exe"),
+            ("win.user.name", "guest")
+        ])));
+    }
+
+    #[test]
+    fn test_rule_conditions() {
+        let yaml = r#"
+transformations:
+    - type: wildcard_placeholders
+    - type: add_condition
+      conditions:
+          EventID: 1
+      rule_conditions:
+          - type: logsource
+            product: windows
+          - type: tag
+            tag: attack.t1059
+      rule_cond_op: or
+    - type: change_logsource
+      category: process
+      rule_cond_not: true
+      rule_conditions:
+          - type: logsource
+            product: linux
+"#;
+        let rule = pipeline(yaml).rule_from_yaml(RULE).unwrap();
+        assert_eq!(rule.logsource.category.as_deref(), Some("process"));
+        assert_eq!(rule.logsource.product, None);
+        assert_eq!(
+            rule.detection.get_condition(),
+            "_cond_0 and (selection or keywords)"
+        );
+
+        let mut event = Event::from([("Image", "C:\\cmd.exe"), ("User", "x")]);
+        assert!(!rule.is_match(&event));
+        event.insert("EventID", 1);
+        assert!(rule.is_match(&event));
+    }
+
+    #[test]
+    fn test_value_transformations() {
+        let yaml = r#"
+transformations:
+    - type: drop_detection_item
+      field_name_conditions:
+          - type: include_fields
+            fields: [User]
+    - type: replace_string
+      regex: '^\\(.*)$'
+      replacement: '/$1'
+      field_name_conditions:
+          - type: exclude_fields
+            fields: [User]
+    - type: map_string
+      mapping:
+          evil: [bad, worse]
+"#;
+        let rule = pipeline(yaml).rule_from_yaml(RULE).unwrap();
+        assert!(rule.is_match(&Event::from([("Image", "C:/cmd.exe")])));
+        assert!(!rule.is_match(&Event::from([("Image", "C:\\cmd.exe")])));
+        assert!(rule.is_match(&Event::from([("Other", "worse")])));
+        assert!(!rule.is_match(&Event::from([("Other", "evil")])));
+    }
+
+    #[test]
+    fn test_normalize_registry_paths() {
+        let yaml = r#"
+transformations:
+    - type: normalize_registry_paths
+      field_name_conditions:
+          - type: include_fields
+            fields: [TargetObject]
+"#;
+        let rule = pipeline(yaml)
+            .rule_from_yaml(
+                r#"
+title: Services
+logsource:
+    category: registry_set
+detection:
+    selection:
+        TargetObject|startswith: 'HKEY_LOCAL_MACHINE\SYSTEM\ControlSet001\Services\'
+    condition: selection
+"#,
+            )
+            .unwrap();
+        let enricher = NormalizeRegistryPaths::default();
+        for path in [
+            r"HKEY_LOCAL_MACHINE\SYSTEM\CurrentControlSet\Services\evil\ImagePath",
+            r"\REGISTRY\MACHINE\SYSTEM\ControlSet002\Services\evil\ImagePath",
+        ] {
+            let event = Event::from([("TargetObject", path)]);
+            assert!(!rule.is_match(&event), "{}", path);
+            assert!(rule.is_match(&enricher.enrich(event)), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_normalize_registry_paths_by_modifier() {
+        let yaml = "transformations:\n    - type: normalize_registry_paths";
+        let rule = |selection: &str| {
+            pipeline(yaml)
+                .rule_from_yaml(&format!(
+                    "title: Run\nlogsource:\ndetection:\n    selection:\n        {}\n    condition: selection",
+                    selection
+                ))
+                .unwrap()
+        };
+        let enricher = NormalizeRegistryPaths::default();
+        let matches = |rule: &Rule, path: &str| {
+            rule.is_match(&enricher.enrich(Event::from([("TargetObject", path)])))
+        };
+
+        // The trailing separator of a substring ends the key
+        let contains = rule(r"TargetObject|contains: '\CurrentVersion\Run\'");
+        assert!(matches(
+            &contains,
+            r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Windows\CurrentVersion\Run\evil"
+        ));
+        assert!(!matches(
+            &contains,
+            r"HKLM\SOFTWARE\Microsoft\Windows\CurrentVersion\RunOnce\evil"
+        ));
+
+        // Events never end with a separator once normalized
+        let endswith = rule(r"TargetObject|endswith: '\CurrentVersion\Run\'");
+        assert!(matches(
+            &endswith,
+            r"HKLM\SOFTWARE\Microsoft\Windows\CurrentVersion\Run\"
+        ));
+
+        // Regular expressions are no paths
+        let re = rule(r"TargetObject|re: '^HKEY_LOCAL_MACHINE\\SOFTWARE\\.*\\$'");
+        assert!(re.is_match(&Event::from([(
+            "TargetObject",
+            r"HKEY_LOCAL_MACHINE\SOFTWARE\Run\"
+        )])));
+        assert!(!matches(&re, r"HKEY_LOCAL_MACHINE\SOFTWARE\Run\"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let undefined = pipeline("transformations:\n    - type: value_placeholders");
+        assert!(matches!(
+            undefined.rule_from_yaml(RULE),
+            Err(PipelineError::UndefinedPlaceholder(name)) if name == "admins"
+        ));
+
+        let failure = pipeline(
+            r#"
+transformations:
+    - type: detection_item_failure
+      message: Image is not available
+      field_name_conditions:
+          - type: include_fields
+            fields: Image
+"#,
+        );
+        assert!(matches!(
+            failure.rule_from_yaml(RULE),
+            Err(PipelineError::Failure(message)) if message == "Image is not available"
+        ));
+
+        let conflict = pipeline(
+            r#"
+transformations:
+    - type: field_name_mapping
+      mapping:
+          Image: User
+    - type: value_placeholders
+      include: [nothing]
+"#,
+        );
+        assert!(matches!(
+            conflict.rule_from_yaml(&RULE.replace("Image|endswith", "Image|expand")),
+            Err(PipelineError::ConflictingField(field)) if field == "User|expand"
+        ));
+
+        assert!(Pipeline::from_yaml("transformations:\n    - type: unknown").is_err());
+    }
+
+    #[test]
+    fn test_chain() {
+        let first = Pipeline {
+            priority: 20,
+            transformations: pipeline(
+                "transformations:\n    - type: field_name_suffix\n      suffix: _b",
+            )
+            .transformations,
+            ..Default::default()
+        };
+        let second = Pipeline {
+            name: Some("second".to_string()),
+            priority: 10,
+            vars: HashMap::from([("admins".to_string(), vec!["root".to_string()])]),
+            transformations: pipeline(
+                "transformations:\n    - type: field_name_suffix\n      suffix: _a\n    - type: value_placeholders",
+            )
+            .transformations,
+        };
+        let pipeline = Pipeline::chain([first, second]);
+        assert_eq!(pipeline.name.as_deref(), Some("second"));
+
+        let rule = pipeline.rule_from_yaml(RULE).unwrap();
+        assert!(rule.is_match(&Event::from([
+            ("Image_a_b", "C:\\cmd.exe"),
+            ("User_a_b", "root")
+        ])));
+    }
+
+    #[test]
+    fn test_programmatic() {
+        let pipeline = Pipeline::new()
+            .with_var("admins", ["root"])
+            .with_item(ProcessingItem::new(Transformation::ValuePlaceholders {
+                include: None,
+                exclude: None,
+            }))
+            .with_item(
+                ProcessingItem::new(Transformation::rename_fields([
+                    ("Image", "process.executable"),
+                    ("User", "user.name"),
+                ]))
+                .with_id("ecs")
+                .for_logsource(Some("process_creation"), Some("windows"), None),
+            )
+            .with_item(
+                ProcessingItem::new(Transformation::rewrite_values(|s| s.replace('\\', "/")))
+                    .for_fields(["process.executable"]),
+            )
+            .with_item(
+                ProcessingItem::new(Transformation::add_field_condition("event.code", 1)).when(
+                    RuleCondition::ProcessingItemApplied {
+                        processing_item_id: "ecs".to_string(),
+                    },
+                ),
+            )
+            .with_item(
+                ProcessingItem::new(Transformation::rename_field("Image", "Path")).for_logsource(
+                    None,
+                    Some("linux"),
+                    None,
+                ),
+            );
+
+        let rule = pipeline.rule_from_yaml(RULE).unwrap();
+        let mut event = Event::from([("process.executable", "C:/cmd.exe"), ("user.name", "root")]);
+        assert!(!rule.is_match(&event));
+        event.insert("event.code", 1);
+        assert!(rule.is_match(&event));
+        // The condition was added to the whole rule, including the keywords
+        let mut event = Event::from([("keyword", "evil")]);
+        assert!(!rule.is_match(&event));
+        event.insert("event.code", 1);
+        assert!(rule.is_match(&event));
+    }
+
+    #[test]
+    fn test_placeholder() {
+        assert_eq!(placeholder("a%b%c"), Some(("b", 1, 4)));
+        assert_eq!(placeholder("50% of %x%"), Some(("x", 7, 10)));
+        assert_eq!(placeholder("100%"), None);
+        assert_eq!(placeholder("%a b%"), None);
+    }
+}
@@ -0,0 +1,524 @@
+//! Placeholder value lists that can be updated while rules are evaluated.
+//!
+//! A field with the `expand` modifier, e.g. `DestinationIp|expand: '%c2_ips%'`, matches the
+//! values of the list of that name in [`EngineConfig::placeholders`](crate::EngineConfig). In
+//! contrast to placeholders resolved by a [processing pipeline](crate::pipeline), the compiled
+//! rule keeps a handle to the list: replacing its values, e.g. when an IOC feed is updated,
+//! takes effect for the next evaluated event without recompiling the rule set. A field compiles
+//! the new values with its other modifiers the first time it is evaluated after the change.
+//!
+//! Lists can be read from newline-delimited text files with one value per line, or from CSV
+//! files, of which the first column is used. Empty lines and lines starting with `#` are
+//! skipped, so a CSV header must be commented out. [`Placeholders::reload`] reads the files of
+//! all lists again.
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::placeholder::{PlaceholderList, Placeholders};
+//! use sigma_rust::{EngineConfig, Event};
+//!
+//! let c2_ips = PlaceholderList::new("c2_ips", ["198.51.100.1"]);
+//! let mut placeholders = Placeholders::new();
+//! placeholders.insert(c2_ips.clone());
+//!
+//! let config = EngineConfig {
+//!     placeholders,
+//!     ..Default::default()
+//! };
+//! let rule = config
+//!     .rule_from_yaml(
+//!         r#"
+//! title: Connection to a known C2 server
+//! logsource:
+//!     category: network_connection
+//! detection:
+//!     selection:
+//!         DestinationIp|expand: '%c2_ips%'
+//!     condition: selection
+//! "#,
+//!     )
+//!     .unwrap();
+//!
+//! let event = Event::from([("DestinationIp", "203.0.113.7")]);
+//! assert!(!rule.is_match(&event));
+//! c2_ips.set(["198.51.100.1", "203.0.113.7"]);
+//! assert!(rule.is_match(&event));
+//! ```
+use crate::config::{self, EngineConfig};
+use crate::error::ParserError;
+use crate::field::{Field, FieldValue};
+use crate::pipeline::placeholder;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// Errors raised when a placeholder list cannot be read
+#[derive(Debug, thiserror::Error)]
+pub enum PlaceholderError {
+    #[error("Failed to read the placeholder list {}: {1}", .0.display())]
+    Io(PathBuf, #[source] std::io::Error),
+}
+
+/// A named list of values that can be replaced at any time. Clones share the values, so a
+/// clone kept by the application updates the rules compiled with the list.
+#[derive(Debug, Clone)]
+pub struct PlaceholderList {
+    inner: Arc<ListInner>,
+}
+
+#[derive(Debug)]
+struct ListInner {
+    name: String,
+    path: Option<PathBuf>,
+    values: RwLock<Arc<[String]>>,
+    version: AtomicU64,
+}
+
+impl PlaceholderList {
+    /// Create a list resolving the placeholder `%name%` to the values
+    pub fn new<S, I, V>(name: S, values: I) -> Self
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        Self::with_path(
+            name.into(),
+            None,
+            values.into_iter().map(Into::into).collect(),
+        )
+    }
+
+    /// Read a list from a text or CSV file, see the [module documentation](self)
+    pub fn from_file<S: Into<String>, P: AsRef<Path>>(
+        name: S,
+        path: P,
+    ) -> Result<Self, PlaceholderError> {
+        let path = path.as_ref();
+        let values = read_values(path)?;
+        Ok(Self::with_path(
+            name.into(),
+            Some(path.to_path_buf()),
+            values,
+        ))
+    }
+
+    fn with_path(name: String, path: Option<PathBuf>, values: Vec<String>) -> Self {
+        Self {
+            inner: Arc::new(ListInner {
+                name,
+                path,
+                values: RwLock::new(values.into()),
+                version: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// The name of the placeholder, without percent signs
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// The file the list was read from
+    pub fn path(&self) -> Option<&Path> {
+        self.inner.path.as_deref()
+    }
+
+    /// The current values of the list
+    pub fn values(&self) -> Arc<[String]> {
+        self.inner
+            .values
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replace the values of the list. Rules using the list match the new values from the
+    /// next evaluated event on.
+    pub fn set<I, V>(&self, values: I)
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        let values: Arc<[String]> = values.into_iter().map(Into::into).collect();
+        *self
+            .inner
+            .values
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = values;
+        self.inner.version.fetch_add(1, Ordering::Release);
+    }
+
+    /// Read the file of the list again. Returns whether the values changed, which they never do
+    /// for lists that were not read from a file.
+    pub fn reload(&self) -> Result<bool, PlaceholderError> {
+        let Some(path) = &self.inner.path else {
+            return Ok(false);
+        };
+        let values = read_values(path)?;
+        if *self.values() == *values {
+            return Ok(false);
+        }
+        self.set(values);
+        Ok(true)
+    }
+
+    /// Incremented whenever the values are replaced
+    fn version(&self) -> u64 {
+        self.inner.version.load(Ordering::Acquire)
+    }
+}
+
+/// The placeholder lists available to rules, keyed by name
+#[derive(Debug, Clone, Default)]
+pub struct Placeholders {
+    lists: BTreeMap<String, PlaceholderList>,
+}
+
+/// Placeholders are equal if they consist of the same lists, not just of equal values
+impl PartialEq for Placeholders {
+    fn eq(&self, other: &Self) -> bool {
+        self.lists.len() == other.lists.len()
+            && self
+                .lists
+                .iter()
+                .zip(&other.lists)
+                .all(|((a, a_list), (b, b_list))| {
+                    a == b && Arc::ptr_eq(&a_list.inner, &b_list.inner)
+                })
+    }
+}
+
+impl Placeholders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a list under its name and return the list it replaces
+    pub fn insert(&mut self, list: PlaceholderList) -> Option<PlaceholderList> {
+        self.lists.insert(list.name().to_string(), list)
+    }
+
+    /// Add the lists of all `.txt` and `.csv` files of a directory, named after the file
+    /// without its extension, e.g. `c2_ips.txt` resolves `%c2_ips%`. Returns the number of
+    /// added lists.
+    pub fn load_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, PlaceholderError> {
+        let path = path.as_ref();
+        let entries = fs::read_dir(path).map_err(|err| PlaceholderError::Io(path.into(), err))?;
+        let mut added = 0;
+        for entry in entries {
+            let path = entry
+                .map_err(|err| PlaceholderError::Io(path.into(), err))?
+                .path();
+            let is_list = path.extension().is_some_and(|ext| {
+                ext.eq_ignore_ascii_case("txt") || ext.eq_ignore_ascii_case("csv")
+            });
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if is_list && path.is_file() {
+                self.insert(PlaceholderList::from_file(name, &path)?);
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PlaceholderList> {
+        self.lists.get(name)
+    }
+
+    /// Read the files of all lists again and return the number of lists whose values changed.
+    /// Lists whose file cannot be read keep their values, and the first error is returned
+    /// after all other lists were reloaded.
+    pub fn reload(&self) -> Result<usize, PlaceholderError> {
+        let mut changed = 0;
+        let mut error = None;
+        for list in self.lists.values() {
+            match list.reload() {
+                Ok(true) => changed += 1,
+                Ok(false) => {}
+                Err(err) => {
+                    error.get_or_insert(err);
+                }
+            }
+        }
+        error.map_or(Ok(changed), Err)
+    }
+
+    /// The names of all lists in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.lists.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lists.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lists.is_empty()
+    }
+}
+
+fn read_values(path: &Path) -> Result<Vec<String>, PlaceholderError> {
+    let text = fs::read_to_string(path).map_err(|err| PlaceholderError::Io(path.into(), err))?;
+    let csv = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    Ok(parse_values(&text, csv))
+}
+
+/// The values of a list file, see the [module documentation](self)
+fn parse_values(text: &str, csv: bool) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| if csv { first_column(line) } else { line.into() })
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// The first column of a CSV line, unquoted
+fn first_column(line: &str) -> String {
+    let Some(quoted) = line.strip_prefix('"') else {
+        return line.split(',').next().unwrap_or_default().trim().into();
+    };
+    let mut result = String::new();
+    let mut chars = quoted.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if chars.peek() == Some(&'"') => {
+                chars.next();
+                result.push('"');
+            }
+            '"' => break,
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// The values of a field with the `expand` modifier, compiled from the current values of its
+/// placeholder lists whenever they changed
+#[derive(Debug)]
+pub(crate) struct LiveValues {
+    /// The name of the field with all modifiers except `expand`
+    key: String,
+    lists: Vec<PlaceholderList>,
+    config: EngineConfig,
+    compiled: RwLock<Compiled>,
+}
+
+#[derive(Debug)]
+struct Compiled {
+    version: u64,
+    /// `None` if the lists are empty, so the field never matches
+    field: Option<Arc<Field>>,
+}
+
+impl LiveValues {
+    /// Resolve the placeholders of the values with the lists of the active configuration and
+    /// compile their current values
+    pub(crate) fn new(key: String, values: &[FieldValue]) -> Result<Self, ParserError> {
+        let mut config = config::with_active(EngineConfig::clone);
+        let mut lists = Vec::with_capacity(values.len());
+        for value in values {
+            let value = match value {
+                FieldValue::Base(value) => value.value_to_string(),
+                value => format!("{:?}", value),
+            };
+            let list = match placeholder(&value) {
+                Some((name, 0, end)) if end == value.len() => config.placeholders.get(name),
+                _ => None,
+            };
+            match list {
+                Some(list) => lists.push(list.clone()),
+                None => return Err(ParserError::UnresolvedPlaceholder(value)),
+            }
+        }
+        // The lists are kept by the field, not by its configuration
+        config.placeholders = Placeholders::default();
+
+        let version = Self::version_of(&lists);
+        let mut result = Self {
+            key,
+            lists,
+            config,
+            compiled: RwLock::new(Compiled {
+                version,
+                field: None,
+            }),
+        };
+        result
+            .compiled
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .field = result.compile()?;
+        Ok(result)
+    }
+
+    /// Versions only ever increase, so their sum changes whenever any list changes
+    fn version_of(lists: &[PlaceholderList]) -> u64 {
+        lists.iter().map(PlaceholderList::version).sum()
+    }
+
+    fn compile(&self) -> Result<Option<Arc<Field>>, ParserError> {
+        let values: Vec<FieldValue> = self
+            .lists
+            .iter()
+            .flat_map(|list| list.values().iter().cloned().collect::<Vec<_>>())
+            .map(FieldValue::from)
+            .collect();
+        if values.is_empty() {
+            return Ok(None);
+        }
+        self.config
+            .scoped(|| Field::new(&self.key, values))
+            .map(|field| Some(Arc::new(field)))
+    }
+
+    /// The field compiled from the current values of the lists. If they cannot be compiled,
+    /// e.g. because of an invalid regular expression, the previous values stay in use.
+    pub(crate) fn current(&self) -> Option<Arc<Field>> {
+        let version = Self::version_of(&self.lists);
+        {
+            let compiled = self.compiled.read().unwrap_or_else(PoisonError::into_inner);
+            if compiled.version == version {
+                return compiled.field.clone();
+            }
+        }
+
+        let mut compiled = self
+            .compiled
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if compiled.version != version {
+            if let Ok(field) = self.compile() {
+                compiled.field = field;
+            }
+            compiled.version = version;
+        }
+        compiled.field.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, RuleSet};
+
+    fn config(lists: impl IntoIterator<Item = PlaceholderList>) -> EngineConfig {
+        let mut placeholders = Placeholders::new();
+        for list in lists {
+            placeholders.insert(list);
+        }
+        EngineConfig {
+            placeholders,
+            ..Default::default()
+        }
+    }
+
+    fn rule(field: &str, values: &str) -> String {
+        format!(
+            "title: Placeholders\nlogsource:\ndetection:\n    selection:\n        {}: {}\n    condition: selection\n",
+            field, values
+        )
+    }
+
+    #[test]
+    fn test_parse_values() {
+        let text = "# comment\n a \n\nb\n";
+        assert_eq!(parse_values(text, false), ["a", "b"]);
+        let csv = "#ip,comment\n10.0.0.1,office\n\"a \"\"b\"\", c\",x\n,empty\n";
+        assert_eq!(parse_values(csv, true), ["10.0.0.1", "a \"b\", c"]);
+    }
+
+    #[test]
+    fn test_live_values() {
+        let admins = PlaceholderList::new("admins", ["root"]);
+        let hosts = PlaceholderList::new("hosts", Vec::<String>::new());
+        let lists = config([admins.clone(), hosts.clone()]);
+        let rule_set = RuleSet::new(vec![lists
+            .rule_from_yaml(&rule("User|expand|endswith", "['%admins%', '%hosts%']"))
+            .unwrap()]);
+
+        let event = Event::from([("User", "DOMAIN\\Admin")]);
+        assert!(rule_set.evaluate(&event).is_empty());
+        admins.set(["admin", "root"]);
+        assert_eq!(rule_set.evaluate(&event).len(), 1);
+        admins.set(Vec::<String>::new());
+        assert!(rule_set.evaluate(&event).is_empty());
+        hosts.set(["\\admin"]);
+        assert_eq!(rule_set.evaluate(&event).len(), 1);
+
+        // An invalid value keeps the previous values
+        let patterns = PlaceholderList::new("patterns", ["^a"]);
+        let regex = config([patterns.clone()])
+            .rule_from_yaml(&rule("User|expand|re", "'%patterns%'"))
+            .unwrap();
+        patterns.set(["("]);
+        assert!(regex.is_match(&Event::from([("User", "admin")])));
+    }
+
+    #[test]
+    fn test_unresolved_placeholder() {
+        let admins = config([PlaceholderList::new("admins", ["root"])]);
+        for values in ["'%users%'", "'C:\\%admins%'", "5"] {
+            let err = admins
+                .rule_from_yaml(&rule("User|expand", values))
+                .unwrap_err();
+            assert!(err.to_string().contains("is not a placeholder"), "{}", err);
+        }
+        // Invalid values fail the rule when it is compiled
+        let patterns = config([PlaceholderList::new("patterns", ["("])]);
+        assert!(patterns
+            .rule_from_yaml(&rule("User|expand|re", "'%patterns%'"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_files() {
+        let dir = std::env::temp_dir().join(format!("sigma-placeholders-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("admins.txt"), "root\n").unwrap();
+        fs::write(dir.join("hosts.CSV"), "#host,owner\nsrv01,it\n").unwrap();
+        fs::write(dir.join("notes.md"), "not a list").unwrap();
+
+        let mut placeholders = Placeholders::new();
+        assert_eq!(placeholders.load_dir(&dir).unwrap(), 2);
+        assert_eq!(
+            placeholders.names().collect::<Vec<_>>(),
+            ["admins", "hosts"]
+        );
+        assert_eq!(
+            *placeholders.get("hosts").unwrap().values(),
+            ["srv01".to_string()]
+        );
+        let config = EngineConfig {
+            placeholders: placeholders.clone(),
+            ..Default::default()
+        };
+        let rule = config
+            .rule_from_yaml(&rule("User|expand", "'%admins%'"))
+            .unwrap();
+        assert!(!rule.is_match(&Event::from([("User", "admin")])));
+
+        fs::write(dir.join("admins.txt"), "root\nadmin\n").unwrap();
+        assert_eq!(placeholders.reload().unwrap(), 1);
+        assert_eq!(placeholders.reload().unwrap(), 0);
+        assert!(rule.is_match(&Event::from([("User", "admin")])));
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(
+            placeholders.reload(),
+            Err(PlaceholderError::Io(..))
+        ));
+        // The values of lists that cannot be read are kept
+        assert!(rule.is_match(&Event::from([("User", "admin")])));
+        assert!(PlaceholderList::new("x", ["a"])
+            .reload()
+            .is_ok_and(|changed| !changed));
+    }
+}
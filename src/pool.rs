@@ -0,0 +1,194 @@
+//! Reuse events to avoid allocating memory for every parsed event.
+//!
+//! Parsing an event allocates a map for its fields, which is freed again once the event was
+//! evaluated. At millions of events per second, this churn of allocations costs a noticeable
+//! share of the throughput. An [`EventPool`] keeps evaluated events: [`EventPool::get`] returns
+//! an empty event with the memory of a previous one, and dropping the [`PooledEvent`] returns it
+//! to the pool. [`Event::fill_from_json`] parses into the memory of an event without an
+//! intermediate JSON value.
+//!
+//! The pool can be shared by threads, and keeps at most [`EventPool::with_max_idle`] events.
+//!
+//! # Example
+//! ```rust
+//! # #[cfg(feature = "serde_json")]
+//! # {
+//! use sigma_rust::pool::EventPool;
+//! use sigma_rust::{rule_from_yaml, RuleSet};
+//!
+//! let rule = rule_from_yaml(
+//!     r#"
+//! title: Whoami
+//! logsource:
+//! detection:
+//!     selection:
+//!         Image|endswith: '\whoami.exe'
+//!     condition: selection
+//! "#,
+//! )
+//! .unwrap();
+//! let rule_set = RuleSet::new(vec![rule]);
+//!
+//! let pool = EventPool::new();
+//! let lines = [r#"{"Image": "C:\\whoami.exe"}"#, r#"{"Image": "C:\\cmd.exe"}"#];
+//! let mut matches = 0;
+//! for line in lines {
+//!     let event = pool.parse_json(line).unwrap();
+//!     matches += rule_set.evaluate(&event).len();
+//! }
+//! assert_eq!(matches, 1);
+//! assert_eq!(pool.idle(), 1);
+//! # }
+//! ```
+use crate::event::Event;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, PoisonError};
+
+/// The default maximum number of idle events of a pool
+pub const DEFAULT_MAX_IDLE: usize = 1024;
+
+/// A pool of reusable events, see the [module documentation](self)
+#[derive(Debug)]
+pub struct EventPool {
+    idle: Mutex<Vec<Event>>,
+    max_idle: usize,
+}
+
+impl Default for EventPool {
+    fn default() -> Self {
+        Self {
+            idle: Mutex::new(vec![]),
+            max_idle: DEFAULT_MAX_IDLE,
+        }
+    }
+}
+
+impl EventPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep at most `max_idle` returned events, further events are freed. Should be at least
+    /// the number of events in flight at the same time, e.g. the batch size times the number
+    /// of threads.
+    pub fn with_max_idle(mut self, max_idle: usize) -> Self {
+        self.max_idle = max_idle;
+        self
+    }
+
+    /// An empty event, reusing the memory of a returned event if there is one
+    pub fn get(&self) -> PooledEvent<'_> {
+        let event = self
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop()
+            .unwrap_or_default();
+        PooledEvent { pool: self, event }
+    }
+
+    /// An event parsed from a JSON object with [`Event::fill_from_json`]. If the JSON is not a
+    /// valid event, the event is returned to the pool right away.
+    #[cfg(feature = "serde_json")]
+    pub fn parse_json<J: AsRef<[u8]>>(
+        &self,
+        json: J,
+    ) -> Result<PooledEvent<'_>, serde_json::Error> {
+        let mut event = self.get();
+        event.fill_from_json(json)?;
+        Ok(event)
+    }
+
+    /// The number of events waiting to be reused
+    pub fn idle(&self) -> usize {
+        self.idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    fn put(&self, mut event: Event) {
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        if idle.len() < self.max_idle {
+            event.clear();
+            idle.push(event);
+        }
+    }
+}
+
+/// An event of an [`EventPool`] that is returned to the pool when dropped
+#[derive(Debug)]
+pub struct PooledEvent<'a> {
+    pool: &'a EventPool,
+    event: Event,
+}
+
+impl PooledEvent<'_> {
+    /// Take the event out of the pool, e.g. to keep it after it matched
+    pub fn into_inner(mut self) -> Event {
+        let event = mem::take(&mut self.event);
+        // The event left behind is empty and has no memory worth reusing
+        mem::forget(self);
+        event
+    }
+}
+
+impl Deref for PooledEvent<'_> {
+    type Target = Event;
+
+    fn deref(&self) -> &Event {
+        &self.event
+    }
+}
+
+impl DerefMut for PooledEvent<'_> {
+    fn deref_mut(&mut self) -> &mut Event {
+        &mut self.event
+    }
+}
+
+impl Drop for PooledEvent<'_> {
+    fn drop(&mut self) {
+        self.pool.put(mem::take(&mut self.event));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reuse() {
+        let pool = EventPool::new().with_max_idle(2);
+        let mut first = pool.get();
+        first.insert("Image", "C:\\cmd.exe");
+        let second = pool.get();
+        let third = pool.get();
+        assert_eq!(pool.idle(), 0);
+        drop((first, second, third));
+        assert_eq!(pool.idle(), 2);
+
+        // Returned events are empty
+        let event = pool.get();
+        assert_eq!(*event, Event::new());
+        assert_eq!(pool.idle(), 1);
+
+        let kept = event.into_inner();
+        assert_eq!(kept, Event::new());
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_parse_json() {
+        use crate::EventValue;
+
+        let pool = EventPool::new();
+        let event = pool.parse_json(r#"{"User": "root"}"#).unwrap();
+        assert_eq!(event.get("User"), Some(&EventValue::from("root")));
+        drop(event);
+        assert!(pool.parse_json("not json").is_err());
+        assert_eq!(pool.idle(), 1);
+    }
+}
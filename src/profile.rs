@@ -0,0 +1,215 @@
+//! Profile-guided ordering of condition evaluation.
+//!
+//! While evaluating events with [`crate::RuleSet::evaluate_profiled`], per-rule and
+//! per-selection hit statistics are recorded into a [`Profile`]. The profile can be exported,
+//! e.g. as JSON, and applied to a rule set on the next startup with
+//! [`crate::RuleSet::apply_profile`]. The conditions of the rules are then reordered, so the
+//! selection that most likely decides the result of an `and` or `or` is evaluated first.
+//!
+//! Reordering never changes which events a rule matches, only how fast this is determined.
+use crate::rule::Rule;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Hit statistics of a rule set, keyed by rule id (or the title for rules without an id)
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub rules: HashMap<String, RuleProfile>,
+}
+
+/// Hit statistics of a single rule
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleProfile {
+    /// How often the rule was evaluated. Rules skipped by the prefilter are not counted.
+    pub evaluations: u64,
+    /// How often the rule matched
+    pub matches: u64,
+    /// The total evaluation time in nanoseconds
+    pub nanos: u64,
+    /// The statistics of the selections of the rule, keyed by selection name
+    pub selections: HashMap<String, SelectionProfile>,
+    /// Whether the rule was disabled when it was last seen, see [`crate::RuleSet::set_enabled`]
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+/// Hit statistics of a single selection
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SelectionProfile {
+    /// How often the selection was evaluated
+    pub evaluations: u64,
+    /// How often the selection matched
+    pub matches: u64,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the statistics of a rule
+    pub fn rule(&self, rule: &Rule) -> Option<&RuleProfile> {
+        self.rules.get(rule_key(rule))
+    }
+
+    /// Add the statistics of another profile, e.g. of another worker, to this profile
+    pub fn merge(&mut self, other: &Profile) {
+        for (key, other) in &other.rules {
+            let rule = self.rules.entry(key.clone()).or_default();
+            rule.evaluations += other.evaluations;
+            rule.matches += other.matches;
+            rule.nanos += other.nanos;
+            rule.disabled |= other.disabled;
+            for (name, other) in &other.selections {
+                let selection = rule.selections.entry(name.clone()).or_default();
+                selection.evaluations += other.evaluations;
+                selection.matches += other.matches;
+            }
+        }
+    }
+
+    /// Serialize the profile to JSON
+    #[cfg(feature = "serde_json")]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    /// Deserialize a profile from JSON
+    #[cfg(feature = "serde_json")]
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub(crate) fn record(&mut self, rule: &Rule, matched: bool, elapsed: Duration) {
+        let profile = self.rule_mut(rule);
+        profile.disabled = false;
+        profile.evaluations += 1;
+        profile.matches += matched as u64;
+        profile.nanos = profile
+            .nanos
+            .saturating_add(elapsed.as_nanos().try_into().unwrap_or(u64::MAX));
+    }
+
+    // The key is inserted above, returning the lookup of `get_mut` instead does not borrow check
+    #[allow(clippy::unwrap_used)]
+    pub(crate) fn rule_mut(&mut self, rule: &Rule) -> &mut RuleProfile {
+        // Avoid allocating the key for rules that were already recorded
+        if !self.rules.contains_key(rule_key(rule)) {
+            self.rules
+                .insert(rule_key(rule).to_string(), RuleProfile::default());
+        }
+        self.rules.get_mut(rule_key(rule)).unwrap()
+    }
+}
+
+impl RuleProfile {
+    /// The fraction of evaluations in which the rule matched
+    pub fn match_rate(&self) -> Option<f64> {
+        rate(self.matches, self.evaluations)
+    }
+
+    /// The mean evaluation time of the rule
+    pub fn mean_duration(&self) -> Option<Duration> {
+        (self.evaluations > 0).then(|| Duration::from_nanos(self.nanos / self.evaluations))
+    }
+
+    pub(crate) fn record_selection(&mut self, name: &str, matched: bool) {
+        let selection = match self.selections.get_mut(name) {
+            Some(selection) => selection,
+            None => self.selections.entry(name.to_string()).or_default(),
+        };
+        selection.evaluations += 1;
+        selection.matches += matched as u64;
+    }
+}
+
+impl SelectionProfile {
+    /// The fraction of evaluations in which the selection matched
+    pub fn match_rate(&self) -> Option<f64> {
+        rate(self.matches, self.evaluations)
+    }
+}
+
+fn rate(matches: u64, evaluations: u64) -> Option<f64> {
+    (evaluations > 0).then(|| matches as f64 / evaluations as f64)
+}
+
+/// Run `f` and measure how long it took
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub(crate) fn timed<R>(f: impl FnOnce() -> R) -> (R, Duration) {
+    let start = std::time::Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+/// There is no monotonic clock on `wasm32-unknown-unknown`, so no time is recorded
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) fn timed<R>(f: impl FnOnce() -> R) -> (R, Duration) {
+    (f(), Duration::ZERO)
+}
+
+/// Returns a function that tells whether `budget` has passed since it was created
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub(crate) fn deadline(budget: Duration) -> impl Fn() -> bool {
+    let start = std::time::Instant::now();
+    move || start.elapsed() >= budget
+}
+
+/// There is no monotonic clock on `wasm32-unknown-unknown`, so the budget never passes
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) fn deadline(_budget: Duration) -> impl Fn() -> bool {
+    || false
+}
+
+pub(crate) fn rule_key(rule: &Rule) -> &str {
+    rule.id.as_deref().unwrap_or(&rule.title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let mut a = Profile::new();
+        a.rules.insert(
+            "rule".to_string(),
+            RuleProfile {
+                evaluations: 4,
+                matches: 1,
+                nanos: 100,
+                selections: HashMap::from([(
+                    "selection".to_string(),
+                    SelectionProfile {
+                        evaluations: 4,
+                        matches: 2,
+                    },
+                )]),
+                disabled: false,
+            },
+        );
+        let mut b = a.clone();
+        b.merge(&a);
+
+        let rule = &b.rules["rule"];
+        assert_eq!(rule.evaluations, 8);
+        assert_eq!(rule.match_rate(), Some(0.25));
+        assert_eq!(rule.mean_duration(), Some(Duration::from_nanos(25)));
+        assert_eq!(rule.selections["selection"].match_rate(), Some(0.5));
+        assert_eq!(RuleProfile::default().match_rate(), None);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_json_roundtrip() {
+        let mut profile = Profile::new();
+        profile
+            .rules
+            .entry("rule".to_string())
+            .or_default()
+            .record_selection("selection", true);
+        let json = profile.to_json().unwrap();
+        assert_eq!(Profile::from_json(&json).unwrap(), profile);
+    }
+}
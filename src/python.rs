@@ -0,0 +1,198 @@
+//! Python bindings built with [PyO3](https://pyo3.rs).
+//!
+//! With the `python` feature enabled, the crate can be built into the `sigma_rust` Python
+//! module with [maturin](https://www.maturin.rs), e.g. to test rules converted with pySigma
+//! against sample events. Events are passed as JSON strings or dictionaries and matches are
+//! returned as dictionaries.
+//!
+//! ```python
+//! from sigma_rust import Rule, RuleSet
+//!
+//! rule = Rule(yaml)
+//! rule.is_match({"Image": "C:\\whoami.exe"})
+//!
+//! rule_set = RuleSet([yaml])
+//! for match in rule_set.evaluate('{"Image": "C:\\\\whoami.exe"}'):
+//!     print(match["title"])
+//! ```
+// The code generated by `#[pymethods]` converts the errors of `PyResult` into `PyErr`
+#![allow(clippy::useless_conversion)]
+use crate::result::MatchResult;
+use crate::{event_from_json, rule_from_yaml, Event};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+
+/// A compiled rule
+#[pyclass(name = "Rule", module = "sigma_rust")]
+pub struct PyRule(crate::Rule);
+
+#[pymethods]
+impl PyRule {
+    /// Parse and compile a rule from YAML
+    #[new]
+    fn new(yaml: &str) -> PyResult<Self> {
+        Ok(Self(rule_from_yaml(yaml).map_err(to_py_error)?))
+    }
+
+    #[getter]
+    fn title(&self) -> String {
+        self.0.title.clone()
+    }
+
+    #[getter]
+    fn id(&self) -> Option<String> {
+        self.0.id.clone()
+    }
+
+    /// Check if the rule matches an event given as JSON or dictionary
+    fn is_match(&self, event: &Bound<'_, PyAny>) -> PyResult<bool> {
+        Ok(self.0.is_match(&to_event(event)?))
+    }
+
+    /// Evaluate the rule against an event and return the match as dictionary, or `None` if
+    /// the rule does not match
+    fn evaluate(&self, py: Python<'_>, event: &Bound<'_, PyAny>) -> PyResult<Option<PyObject>> {
+        self.0
+            .evaluate(&to_event(event)?)
+            .map(|result| to_python(py, &result.to_json()))
+            .transpose()
+    }
+}
+
+/// A collection of rules that are evaluated together, see [`crate::RuleSet`]
+#[pyclass(name = "RuleSet", module = "sigma_rust")]
+pub struct PyRuleSet(crate::RuleSet);
+
+#[pymethods]
+impl PyRuleSet {
+    /// Parse and compile a list of rules given as YAML
+    #[new]
+    fn new(rules: Vec<String>) -> PyResult<Self> {
+        let rules = rules
+            .iter()
+            .map(|yaml| rule_from_yaml(yaml))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(to_py_error)?;
+        Ok(Self(crate::RuleSet::new(rules)))
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Evaluate all rules against an event and return the matches as a list of dictionaries
+    fn evaluate(&self, py: Python<'_>, event: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let results = self.0.evaluate(&to_event(event)?);
+        to_python(py, &matches_to_json(&results))
+    }
+
+    /// Evaluate all rules against a list of events and return a list with the matches of
+    /// every event
+    fn evaluate_batch(&self, py: Python<'_>, events: Vec<Bound<'_, PyAny>>) -> PyResult<PyObject> {
+        let events = events
+            .iter()
+            .map(|event| to_event(event))
+            .collect::<PyResult<Vec<_>>>()?;
+        let results = py.allow_threads(|| self.0.evaluate_batch(&events));
+        to_python(
+            py,
+            &serde_json::Value::Array(results.iter().map(|r| matches_to_json(r)).collect()),
+        )
+    }
+}
+
+/// The `sigma_rust` Python module
+#[pymodule]
+fn sigma_rust(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyRule>()?;
+    module.add_class::<PyRuleSet>()?;
+    Ok(())
+}
+
+fn to_py_error(err: impl std::error::Error) -> PyErr {
+    PyValueError::new_err(err.to_string())
+}
+
+/// Convert an event given as JSON string or as any object that `json.dumps` accepts
+fn to_event(event: &Bound<'_, PyAny>) -> PyResult<Event> {
+    let json = match event.downcast::<PyString>() {
+        Ok(json) => json.to_cow()?.into_owned(),
+        Err(_) => event
+            .py()
+            .import_bound("json")?
+            .call_method1("dumps", (event,))?
+            .extract()?,
+    };
+    event_from_json(&json).map_err(to_py_error)
+}
+
+fn to_python(py: Python<'_>, value: &serde_json::Value) -> PyResult<PyObject> {
+    Ok(py
+        .import_bound("json")?
+        .call_method1("loads", (value.to_string(),))?
+        .unbind())
+}
+
+fn matches_to_json(results: &[MatchResult]) -> serde_json::Value {
+    serde_json::Value::Array(results.iter().map(MatchResult::to_json).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyDict;
+
+    const RULE: &str = r#"
+title: Whoami
+id: 1a2b
+level: high
+logsource:
+fields:
+    - Image
+detection:
+    selection:
+        Image|endswith: '\whoami.exe'
+    condition: selection
+"#;
+
+    #[test]
+    fn test_bindings() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let rule = PyRule::new(RULE).unwrap();
+            let event = PyDict::new_bound(py);
+            event.set_item("Image", "C:\\whoami.exe").unwrap();
+            assert!(rule.is_match(&event).unwrap());
+            let json = PyString::new_bound(py, r#"{"Image": "C:\\explorer.exe"}"#);
+            assert!(!rule.is_match(&json).unwrap());
+            assert!(rule.evaluate(py, &json).unwrap().is_none());
+
+            let rule_set = PyRuleSet::new(vec![RULE.to_string()]).unwrap();
+            assert_eq!(rule_set.__len__(), 1);
+            let matches = rule_set.evaluate(py, &event).unwrap();
+            let matches = matches.bind(py);
+            assert_eq!(matches.len().unwrap(), 1);
+            let result = matches.get_item(0).unwrap();
+            assert_eq!(
+                result
+                    .get_item("fields")
+                    .unwrap()
+                    .get_item("Image")
+                    .unwrap()
+                    .extract::<String>()
+                    .unwrap(),
+                "C:\\whoami.exe"
+            );
+
+            let batch = rule_set
+                .evaluate_batch(py, vec![event.into_any(), json.into_any()])
+                .unwrap();
+            let batch = batch.bind(py);
+            assert_eq!(batch.len().unwrap(), 2);
+            assert_eq!(batch.get_item(1).unwrap().len().unwrap(), 0);
+
+            assert!(PyRule::new("title: Broken").is_err());
+        });
+    }
+}
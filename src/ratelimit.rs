@@ -0,0 +1,392 @@
+//! Limit the number of alerts a single rule can raise.
+//!
+//! A noisy rule can flood the destination of the alerts, e.g. when a misconfigured host
+//! triggers it thousands of times a minute. The [`RateLimiter`] is applied after matching and
+//! lets at most `N` matches per interval through for every rule, or for every combination of a
+//! rule and the values of grouping fields, using a token bucket. Suppressed matches are
+//! counted and reported as an [`Overflow`] summary once the rule raises alerts again, or when
+//! [`RateLimiter::overflows`] is called.
+//!
+//! [`RateLimitedSink`] applies a limiter in front of an [`AlertSink`], which receives the
+//! summaries with [`AlertSink::send_overflow`].
+use crate::result::MatchResult;
+use crate::sink::{AlertSink, SinkError};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// A summary of the matches of a rule that were suppressed by a [`RateLimiter`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overflow {
+    /// The first match of the rule and group, without context events
+    pub result: MatchResult,
+    /// The grouping fields and their values, see [`RateLimiter::with_group_by`]
+    pub group: Vec<(String, String)>,
+    /// The number of suppressed matches
+    pub suppressed: u64,
+}
+
+impl Overflow {
+    /// A JSON representation of the summary, as written by the built-in sinks
+    #[cfg(feature = "serde_json")]
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "overflow": {
+                "id": self.result.rule_id,
+                "title": self.result.rule_title,
+                "level": self.result.level,
+                "group": self
+                    .group
+                    .iter()
+                    .map(|(field, value)| (field.clone(), value.clone().into()))
+                    .collect::<serde_json::Map<_, _>>(),
+                "suppressed": self.suppressed,
+            }
+        })
+    }
+}
+
+/// Whether a match may be sent, as decided by [`RateLimiter::check`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    /// Send the match. If matches of the same rule and group were suppressed before, their
+    /// summary should be sent as well.
+    Allow(Option<Box<Overflow>>),
+    /// Drop the match, it is counted in the next summary
+    Suppress,
+}
+
+/// A token bucket per rule and group that lets at most `max_alerts` matches per interval pass.
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::ratelimit::{Decision, RateLimiter};
+/// use sigma_rust::{rule_from_yaml, Event};
+/// use std::time::{Duration, Instant};
+///
+/// let rule = rule_from_yaml(
+///     r#"
+/// title: Whoami
+/// logsource:
+/// detection:
+///     selection:
+///         Image|endswith: '\whoami.exe'
+///     condition: selection
+/// "#,
+/// )
+/// .unwrap();
+/// let result = rule
+///     .evaluate(&Event::from([("Image", "C:\\whoami.exe")]))
+///     .unwrap();
+///
+/// let mut limiter = RateLimiter::new(1, Duration::from_secs(60));
+/// let now = Instant::now();
+/// assert_eq!(limiter.check(&result, now), Decision::Allow(None));
+/// assert_eq!(limiter.check(&result, now), Decision::Suppress);
+///
+/// let Decision::Allow(Some(overflow)) = limiter.check(&result, now + Duration::from_secs(60))
+/// else {
+///     panic!("expected a summary");
+/// };
+/// assert_eq!(overflow.suppressed, 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    max_alerts: u32,
+    interval: Duration,
+    group_by: Vec<String>,
+    buckets: HashMap<BucketKey, Bucket>,
+}
+
+/// The rule key and the values of the grouping fields
+type BucketKey = (String, Vec<String>);
+
+#[derive(Debug, Clone)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    first: MatchResult,
+    suppressed: u64,
+}
+
+impl RateLimiter {
+    /// Let at most `max_alerts` matches of each rule pass per `interval`. Up to `max_alerts`
+    /// matches may pass at once, afterwards the allowance is replenished evenly over the
+    /// interval.
+    pub fn new(max_alerts: u32, interval: Duration) -> Self {
+        Self {
+            max_alerts: max_alerts.max(1),
+            interval,
+            group_by: vec![],
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Limit the matches of each rule separately for every value of `field`, e.g. per host.
+    /// The value is taken from [`MatchResult::fields`], so the field has to be listed in the
+    /// `fields` of the rules. May be called several times to group by a combination of fields.
+    pub fn with_group_by<S: Into<String>>(mut self, field: S) -> Self {
+        self.group_by.push(field.into());
+        self
+    }
+
+    pub fn max_alerts(&self) -> u32 {
+        self.max_alerts
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Decide whether the match may be sent at the time `now`
+    pub fn check(&mut self, result: &MatchResult, now: Instant) -> Decision {
+        let rule = result.rule_id.as_deref().unwrap_or(&result.rule_title);
+        let group: Vec<String> = self
+            .group_by
+            .iter()
+            .map(|field| {
+                result
+                    .fields
+                    .get(field)
+                    .map(|value| value.value_to_string())
+                    .unwrap_or_default()
+            })
+            .collect();
+        let max_alerts = self.max_alerts as f64;
+        let bucket = self
+            .buckets
+            .entry((rule.to_string(), group.clone()))
+            .or_insert_with(|| Bucket {
+                tokens: max_alerts,
+                updated: now,
+                first: MatchResult {
+                    context: vec![],
+                    ..result.clone()
+                },
+                suppressed: 0,
+            });
+        bucket.refill(now, max_alerts, self.interval);
+
+        if bucket.tokens < 1.0 {
+            bucket.suppressed += 1;
+            return Decision::Suppress;
+        }
+        bucket.tokens -= 1.0;
+        if bucket.suppressed == 0 {
+            return Decision::Allow(None);
+        }
+        Decision::Allow(Some(Box::new(Overflow {
+            result: bucket.first.clone(),
+            group: self.group_by.iter().cloned().zip(group).collect(),
+            suppressed: std::mem::take(&mut bucket.suppressed),
+        })))
+    }
+
+    /// Take the summaries of all suppressed matches that were not reported yet, e.g. before
+    /// shutting down. Rules that did not raise alerts for a whole interval are forgotten.
+    pub fn overflows(&mut self, now: Instant) -> Vec<Overflow> {
+        let max_alerts = self.max_alerts as f64;
+        let mut overflows = vec![];
+        self.buckets.retain(|(_, group), bucket| {
+            if bucket.suppressed > 0 {
+                overflows.push(Overflow {
+                    result: bucket.first.clone(),
+                    group: self.group_by.iter().cloned().zip(group.clone()).collect(),
+                    suppressed: std::mem::take(&mut bucket.suppressed),
+                });
+            }
+            bucket.refill(now, max_alerts, self.interval);
+            bucket.tokens < max_alerts
+        });
+        overflows
+    }
+
+    /// The number of rules and groups that are tracked
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant, max_alerts: f64, interval: Duration) {
+        let elapsed = now.saturating_duration_since(self.updated);
+        let refill = if interval.is_zero() {
+            max_alerts
+        } else {
+            elapsed.as_secs_f64() / interval.as_secs_f64() * max_alerts
+        };
+        self.tokens = (self.tokens + refill).min(max_alerts);
+        self.updated = now;
+    }
+}
+
+/// Applies a [`RateLimiter`] in front of a sink. Suppressed matches are reported to the sink
+/// with [`AlertSink::send_overflow`] when the rule raises alerts again, or when the sink is
+/// flushed.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+#[derive(Debug)]
+pub struct RateLimitedSink<S> {
+    limiter: RateLimiter,
+    sink: S,
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+impl<S: AlertSink> RateLimitedSink<S> {
+    pub fn new(limiter: RateLimiter, sink: S) -> Self {
+        Self { limiter, sink }
+    }
+
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+impl<S: AlertSink> AlertSink for RateLimitedSink<S> {
+    fn send(&mut self, result: &MatchResult) -> Result<(), SinkError> {
+        match self.limiter.check(result, Instant::now()) {
+            Decision::Allow(overflow) => {
+                if let Some(overflow) = overflow {
+                    self.sink.send_overflow(&overflow)?;
+                }
+                self.sink.send(result)
+            }
+            Decision::Suppress => Ok(()),
+        }
+    }
+
+    fn send_overflow(&mut self, overflow: &Overflow) -> Result<(), SinkError> {
+        self.sink.send_overflow(overflow)
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        for overflow in self.limiter.overflows(Instant::now()) {
+            self.sink.send_overflow(&overflow)?;
+        }
+        self.sink.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result::whoami_match;
+
+    #[test]
+    fn test_token_bucket() {
+        let mut limiter = RateLimiter::new(2, Duration::from_secs(10));
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let a = whoami_match("ws-01");
+
+        assert_eq!(limiter.check(&a, at(0)), Decision::Allow(None));
+        assert_eq!(limiter.check(&a, at(0)), Decision::Allow(None));
+        assert_eq!(limiter.check(&a, at(1)), Decision::Suppress);
+        assert_eq!(limiter.check(&a, at(4)), Decision::Suppress);
+        // One token is replenished every 5 seconds
+        let Decision::Allow(Some(overflow)) = limiter.check(&a, at(5)) else {
+            panic!("expected a summary");
+        };
+        assert_eq!(overflow.suppressed, 2);
+        assert_eq!(overflow.result.rule_id.as_deref(), Some("1a2b"));
+        assert!(overflow.group.is_empty());
+        assert_eq!(limiter.check(&a, at(6)), Decision::Suppress);
+
+        assert_eq!(limiter.overflows(at(6))[0].suppressed, 1);
+        assert!(limiter.overflows(at(6)).is_empty());
+        // Full buckets are forgotten
+        assert_eq!(limiter.len(), 1);
+        limiter.overflows(at(100));
+        assert!(limiter.is_empty());
+    }
+
+    #[test]
+    fn test_group_by() {
+        let mut limiter = RateLimiter::new(0, Duration::from_secs(60)).with_group_by("Computer");
+        assert_eq!(limiter.max_alerts(), 1);
+        let now = Instant::now();
+
+        assert_eq!(
+            limiter.check(&whoami_match("ws-01"), now),
+            Decision::Allow(None)
+        );
+        assert_eq!(
+            limiter.check(&whoami_match("ws-02"), now),
+            Decision::Allow(None)
+        );
+        assert_eq!(
+            limiter.check(&whoami_match("ws-01"), now),
+            Decision::Suppress
+        );
+
+        let overflows = limiter.overflows(now);
+        assert_eq!(overflows.len(), 1);
+        assert_eq!(
+            overflows[0].group,
+            vec![("Computer".to_string(), "ws-01".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_sink() {
+        #[derive(Default)]
+        struct Recorder {
+            alerts: usize,
+            overflows: Vec<u64>,
+        }
+
+        impl AlertSink for Recorder {
+            fn send(&mut self, _: &MatchResult) -> Result<(), SinkError> {
+                self.alerts += 1;
+                Ok(())
+            }
+
+            fn send_overflow(&mut self, overflow: &Overflow) -> Result<(), SinkError> {
+                self.overflows.push(overflow.suppressed);
+                Ok(())
+            }
+        }
+
+        let limiter = RateLimiter::new(3, Duration::from_secs(3600));
+        let mut sink = RateLimitedSink::new(limiter, Recorder::default());
+        for _ in 0..10 {
+            sink.send(&whoami_match("ws-01")).unwrap();
+        }
+        sink.flush().unwrap();
+        assert_eq!(sink.get_ref().alerts, 3);
+        assert_eq!(sink.get_ref().overflows, vec![7]);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_overflow_json() {
+        let overflow = Overflow {
+            result: whoami_match("ws-01"),
+            group: vec![("Computer".to_string(), "ws-01".to_string())],
+            suppressed: 5,
+        };
+        assert_eq!(
+            overflow.to_json(),
+            serde_json::json!({
+                "overflow": {
+                    "id": "1a2b",
+                    "title": "Whoami",
+                    "level": null,
+                    "group": {"Computer": "ws-01"},
+                    "suppressed": 5,
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! The regular expression engine.
+//!
+//! The `regex` feature uses the [regex](https://docs.rs/regex) crate. Without it, the `regex-lite`
+//! feature uses the much smaller [regex-lite](https://docs.rs/regex-lite) crate, which trades
+//! matching speed and Unicode support for binary size. Both are exposed with the same interface:
+//! regex-lite has no lazy DFA, so its DFA size limit is ignored, and regex sets are emulated by
+//! matching their regular expressions one after another.
+#[cfg(not(any(feature = "regex", feature = "regex-lite")))]
+compile_error!("either the `regex` or the `regex-lite` feature must be enabled");
+
+#[cfg(feature = "regex")]
+pub(crate) use regex::{escape, Error, Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+
+#[cfg(not(feature = "regex"))]
+pub(crate) use lite::{RegexBuilder, RegexSet, RegexSetBuilder};
+#[cfg(not(feature = "regex"))]
+pub(crate) use regex_lite::{escape, Error, Regex};
+
+#[cfg(not(feature = "regex"))]
+mod lite {
+    use regex_lite::{Error, Regex};
+
+    /// A [`regex_lite::RegexBuilder`] that accepts the DFA size limit of the `regex` crate
+    pub(crate) struct RegexBuilder(regex_lite::RegexBuilder);
+
+    impl RegexBuilder {
+        pub(crate) fn new(pattern: &str) -> Self {
+            Self(regex_lite::RegexBuilder::new(pattern))
+        }
+
+        pub(crate) fn case_insensitive(&mut self, yes: bool) -> &mut Self {
+            self.0.case_insensitive(yes);
+            self
+        }
+
+        pub(crate) fn size_limit(&mut self, limit: usize) -> &mut Self {
+            self.0.size_limit(limit);
+            self
+        }
+
+        pub(crate) fn dfa_size_limit(&mut self, _limit: usize) -> &mut Self {
+            self
+        }
+
+        pub(crate) fn build(&self) -> Result<Regex, Error> {
+            self.0.build()
+        }
+    }
+
+    /// Matches a list of regular expressions one after another
+    #[derive(Debug, Clone)]
+    pub(crate) struct RegexSet(Vec<Regex>);
+
+    impl RegexSet {
+        pub(crate) fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        pub(crate) fn is_match(&self, haystack: &str) -> bool {
+            self.0.iter().any(|regex| regex.is_match(haystack))
+        }
+
+        pub(crate) fn matches(&self, haystack: &str) -> SetMatches {
+            SetMatches(
+                self.0
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, regex)| regex.is_match(haystack))
+                    .map(|(i, _)| i)
+                    .collect(),
+            )
+        }
+    }
+
+    /// The indices of the regular expressions of a [`RegexSet`] that matched
+    pub(crate) struct SetMatches(Vec<usize>);
+
+    impl SetMatches {
+        pub(crate) fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+            self.0.iter().copied()
+        }
+    }
+
+    pub(crate) struct RegexSetBuilder {
+        patterns: Vec<String>,
+        size_limit: Option<usize>,
+    }
+
+    impl RegexSetBuilder {
+        pub(crate) fn new<I, S>(patterns: I) -> Self
+        where
+            I: IntoIterator<Item = S>,
+            S: AsRef<str>,
+        {
+            Self {
+                patterns: patterns
+                    .into_iter()
+                    .map(|p| p.as_ref().to_string())
+                    .collect(),
+                size_limit: None,
+            }
+        }
+
+        pub(crate) fn size_limit(&mut self, limit: usize) -> &mut Self {
+            self.size_limit = Some(limit);
+            self
+        }
+
+        pub(crate) fn dfa_size_limit(&mut self, _limit: usize) -> &mut Self {
+            self
+        }
+
+        pub(crate) fn build(&self) -> Result<RegexSet, Error> {
+            let mut result = Vec::with_capacity(self.patterns.len());
+            for pattern in &self.patterns {
+                let mut builder = regex_lite::RegexBuilder::new(pattern);
+                if let Some(limit) = self.size_limit {
+                    builder.size_limit(limit);
+                }
+                result.push(builder.build()?);
+            }
+            Ok(RegexSet(result))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regex_set() {
+        let set = RegexSetBuilder::new(["^a", "b$", "c"])
+            .size_limit(1 << 20)
+            .dfa_size_limit(1 << 20)
+            .build()
+            .unwrap();
+        assert_eq!(set.len(), 3);
+        assert!(set.is_match("xxb"));
+        assert!(!set.is_match("xyz"));
+        assert_eq!(set.matches("acb").iter().collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(set.matches("bca").iter().collect::<Vec<_>>(), [2]);
+        assert!(RegexSetBuilder::new(["("]).build().is_err());
+
+        let regex = RegexBuilder::new(&escape("a.b"))
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        assert!(regex.is_match("A.B"));
+        assert!(!regex.is_match("axb"));
+    }
+}
@@ -0,0 +1,412 @@
+//! Render rules into a Markdown or HTML catalog of detections.
+//!
+//! The catalog indexes the rules by logsource and by the
+//! [MITRE ATT&CK](https://attack.mitre.org) techniques of their `attack.tNNNN` tags and documents
+//! every rule with its metadata and a plain-words summary of its detection. The summary is
+//! produced from the compiled condition and selections, so it describes what the engine
+//! actually matches, e.g. including the expanded variants of `base64offset` values.
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::report::Report;
+//! use sigma_rust::rule_from_yaml;
+//!
+//! let rule = rule_from_yaml(
+//!     r#"
+//! title: Whoami
+//! level: high
+//! tags:
+//!     - attack.discovery
+//!     - attack.t1033
+//! logsource:
+//!     category: process_creation
+//!     product: windows
+//! detection:
+//!     selection:
+//!         Image|endswith: '\whoami.exe'
+//!     condition: selection
+//! "#,
+//! )
+//! .unwrap();
+//!
+//! let markdown = Report::new([&rule]).with_title("Detections").to_markdown();
+//! assert!(markdown.contains("### T1033"));
+//! assert!(markdown.contains(r"Image ends with '\whoami.exe'"));
+//! ```
+use crate::backend::to_summary;
+use crate::rule::{Logsource, Rule};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// The heading of the rules that do not reference an ATT&CK technique
+const NO_TECHNIQUE: &str = "No technique";
+
+/// A catalog of rules, see the [module documentation](self)
+#[derive(Debug)]
+pub struct Report<'a> {
+    title: String,
+    /// The rules sorted by title
+    rules: Vec<&'a Rule>,
+}
+
+/// The rules of a catalog grouped by logsource and by technique. The values are indices into
+/// the sorted rules.
+struct Index {
+    logsources: Vec<(String, Vec<usize>)>,
+    techniques: Vec<(String, Vec<usize>)>,
+}
+
+impl<'a> Report<'a> {
+    pub fn new<I: IntoIterator<Item = &'a Rule>>(rules: I) -> Self {
+        let mut rules: Vec<&Rule> = rules.into_iter().collect();
+        rules.sort_by(|a, b| a.title.cmp(&b.title));
+        Self {
+            title: "Detection rules".to_string(),
+            rules,
+        }
+    }
+
+    /// Set the title of the catalog
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    /// Render the catalog as Markdown
+    pub fn to_markdown(&self) -> String {
+        let index = self.index();
+        let mut out = String::new();
+        let _ = writeln!(out, "# {}\n", self.title);
+        let _ = writeln!(out, "{} rules\n", self.rules.len());
+
+        for (heading, groups) in [
+            ("By logsource", &index.logsources),
+            ("By ATT&CK technique", &index.techniques),
+        ] {
+            let _ = writeln!(out, "## {}\n", heading);
+            for (group, rules) in groups {
+                let _ = writeln!(out, "### {}\n", group);
+                for &i in rules {
+                    let rule = self.rules[i];
+                    let _ = writeln!(
+                        out,
+                        "- [{}](#{}){}",
+                        markdown_text(&rule.title),
+                        anchor(i),
+                        level(rule).map_or(String::new(), |level| format!(" ({})", level))
+                    );
+                }
+                out.push('\n');
+            }
+        }
+
+        let _ = writeln!(out, "## Rules\n");
+        for (i, rule) in self.rules.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "### <a id=\"{}\"></a>{}\n",
+                anchor(i),
+                markdown_text(&rule.title)
+            );
+            if let Some(description) = &rule.description {
+                let _ = writeln!(out, "{}\n", markdown_text(description.trim()));
+            }
+            let _ = writeln!(out, "| | |\n|---|---|");
+            for (name, value) in metadata(rule) {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} |",
+                    name,
+                    markdown_text(&value).replace('|', "\\|")
+                );
+            }
+            let _ = writeln!(
+                out,
+                "\n**Detection**\n\n```text\n{}\n```\n",
+                to_summary(rule)
+            );
+            for (heading, items) in [
+                ("False positives", &rule.falsepositives),
+                ("References", &rule.references),
+            ] {
+                if let Some(items) = items.as_ref().filter(|items| !items.is_empty()) {
+                    let _ = writeln!(out, "**{}**\n", heading);
+                    for item in items {
+                        let _ = writeln!(out, "- {}", markdown_text(item));
+                    }
+                    out.push('\n');
+                }
+            }
+        }
+        out
+    }
+
+    /// Render the catalog as a standalone HTML page
+    pub fn to_html(&self) -> String {
+        let index = self.index();
+        let mut out = String::new();
+        let title = html(&self.title);
+        let _ = writeln!(
+            out,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>",
+            title
+        );
+        let _ = writeln!(out, "<h1>{}</h1>\n<p>{} rules</p>", title, self.rules.len());
+
+        for (heading, groups) in [
+            ("By logsource", &index.logsources),
+            ("By ATT&amp;CK technique", &index.techniques),
+        ] {
+            let _ = writeln!(out, "<h2>{}</h2>", heading);
+            for (group, rules) in groups {
+                let _ = writeln!(out, "<h3>{}</h3>\n<ul>", html(group));
+                for &i in rules {
+                    let rule = self.rules[i];
+                    let _ = writeln!(
+                        out,
+                        "<li><a href=\"#{}\">{}</a>{}</li>",
+                        anchor(i),
+                        html(&rule.title),
+                        level(rule).map_or(String::new(), |level| format!(" ({})", level))
+                    );
+                }
+                let _ = writeln!(out, "</ul>");
+            }
+        }
+
+        let _ = writeln!(out, "<h2>Rules</h2>");
+        for (i, rule) in self.rules.iter().enumerate() {
+            let _ = writeln!(out, "<h3 id=\"{}\">{}</h3>", anchor(i), html(&rule.title));
+            if let Some(description) = &rule.description {
+                let _ = writeln!(out, "<p>{}</p>", html(description.trim()));
+            }
+            let _ = writeln!(out, "<table>");
+            for (name, value) in metadata(rule) {
+                let _ = writeln!(out, "<tr><th>{}</th><td>{}</td></tr>", name, html(&value));
+            }
+            let _ = writeln!(out, "</table>");
+            let _ = writeln!(
+                out,
+                "<h4>Detection</h4>\n<pre>{}</pre>",
+                html(&to_summary(rule))
+            );
+            for (heading, items) in [
+                ("False positives", &rule.falsepositives),
+                ("References", &rule.references),
+            ] {
+                if let Some(items) = items.as_ref().filter(|items| !items.is_empty()) {
+                    let _ = writeln!(out, "<h4>{}</h4>\n<ul>", heading);
+                    for item in items {
+                        let _ = writeln!(out, "<li>{}</li>", html(item));
+                    }
+                    let _ = writeln!(out, "</ul>");
+                }
+            }
+        }
+        let _ = writeln!(out, "</body>\n</html>");
+        out
+    }
+
+    fn index(&self) -> Index {
+        let mut logsources: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        let mut techniques: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        let mut unmapped = vec![];
+        for (i, rule) in self.rules.iter().enumerate() {
+            logsources
+                .entry(logsource(&rule.logsource))
+                .or_default()
+                .push(i);
+
+            let rule_techniques = self::techniques(rule);
+            if rule_techniques.is_empty() {
+                unmapped.push(i);
+            }
+            for technique in rule_techniques {
+                techniques.entry(technique).or_default().push(i);
+            }
+        }
+
+        let mut techniques: Vec<_> = techniques.into_iter().collect();
+        // List the rules without technique last
+        if !unmapped.is_empty() {
+            techniques.push((NO_TECHNIQUE.to_string(), unmapped));
+        }
+        Index {
+            logsources: logsources.into_iter().collect(),
+            techniques,
+        }
+    }
+}
+
+/// The ATT&CK techniques of the tags of a rule, e.g. `T1059.001` for `attack.t1059.001`
+fn techniques(rule: &Rule) -> Vec<String> {
+    let mut result: Vec<String> = rule
+        .tags
+        .iter()
+        .flatten()
+        .filter_map(|tag| {
+            let id = tag.strip_prefix("attack.t")?;
+            id.starts_with(|c: char| c.is_ascii_digit())
+                .then(|| format!("T{}", id))
+        })
+        .collect();
+    result.sort();
+    result.dedup();
+    result
+}
+
+fn logsource(logsource: &Logsource) -> String {
+    let parts: Vec<&str> = [&logsource.product, &logsource.category, &logsource.service]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    if parts.is_empty() {
+        "Any logsource".to_string()
+    } else {
+        parts.join(" / ")
+    }
+}
+
+fn level(rule: &Rule) -> Option<String> {
+    rule.level
+        .and_then(|level| serde_yml::to_value(level).ok())
+        .and_then(|value| value.as_str().map(str::to_string))
+}
+
+/// The metadata of a rule that is set, as pairs of name and value
+fn metadata(rule: &Rule) -> Vec<(&'static str, String)> {
+    let status = rule
+        .status
+        .and_then(|status| serde_yml::to_value(status).ok())
+        .and_then(|value| value.as_str().map(str::to_string));
+    [
+        ("ID", rule.id.clone()),
+        ("Status", status),
+        ("Level", level(rule)),
+        ("Author", rule.author.clone()),
+        ("Date", rule.date.clone()),
+        ("Modified", rule.modified.clone()),
+        ("Logsource", Some(logsource(&rule.logsource))),
+        ("Tags", rule.tags.as_ref().map(|tags| tags.join(", "))),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| Some((name, value?)))
+    .collect()
+}
+
+fn anchor(index: usize) -> String {
+    format!("rule-{}", index + 1)
+}
+
+/// Escape the characters that would be interpreted as Markdown or HTML
+fn markdown_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '#' | '<' | '>' => {
+                result.push('\\');
+                result.push(c);
+            }
+            '\n' => result.push(' '),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+fn html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(all(test, feature = "base64"))]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    fn rules() -> Vec<Rule> {
+        [
+            r#"
+title: Whoami <execution>
+id: 1a2b
+status: test
+level: high
+description: Detects whoami
+author: Alice
+tags:
+    - attack.discovery
+    - attack.t1033
+    - attack.t1059.001
+logsource:
+    category: process_creation
+    product: windows
+detection:
+    selection:
+        Image|endswith: '\whoami.exe'
+    condition: selection
+falsepositives:
+    - Admin scripts
+"#,
+            r#"
+title: Encoded command
+logsource:
+    category: process_creation
+    product: windows
+detection:
+    selection:
+        CommandLine|base64offset|contains: IEX
+    condition: selection
+"#,
+            r#"
+title: Any keyword
+logsource:
+detection:
+    keywords:
+        - mimikatz
+    condition: keywords
+"#,
+        ]
+        .iter()
+        .map(|yaml| rule_from_yaml(yaml).unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn test_markdown() {
+        let rules = rules();
+        let markdown = Report::new(&rules).to_markdown();
+        assert!(markdown.starts_with("# Detection rules\n\n3 rules\n"));
+
+        // Sorted by title, so the anchors are stable
+        assert!(markdown.contains(
+            "### windows / process_creation\n\n- [Encoded command](#rule-2)\n- [Whoami \\<execution\\>](#rule-3) (high)\n"
+        ));
+        assert!(markdown.contains("### Any logsource\n\n- [Any keyword](#rule-1)\n"));
+        assert!(markdown.contains("### T1033\n\n- [Whoami \\<execution\\>](#rule-3) (high)\n"));
+        assert!(markdown.contains("### T1059.001\n"));
+        assert!(markdown.contains(
+            "### No technique\n\n- [Any keyword](#rule-1)\n- [Encoded command](#rule-2)\n"
+        ));
+
+        assert!(markdown.contains("| Status | test |\n| Level | high |\n| Author | Alice |\n"));
+        assert!(markdown.contains("```text\nany value matches 'mimikatz'\n```"));
+        assert!(markdown.contains(
+            "CommandLine contains 'suvy' or CommandLine contains 'lfw' or CommandLine contains 'jrv'"
+        ));
+        assert!(markdown.contains("**False positives**\n\n- Admin scripts\n"));
+    }
+
+    #[test]
+    fn test_html() {
+        let rules = rules();
+        let html = Report::new(&rules).with_title("Catalog & more").to_html();
+        assert!(html.contains("<title>Catalog &amp; more</title>"));
+        assert!(html.contains("<h3 id=\"rule-3\">Whoami &lt;execution&gt;</h3>"));
+        assert!(html.contains("<tr><th>ID</th><td>1a2b</td></tr>"));
+        assert!(html.contains(r"<pre>Image ends with '\whoami.exe'</pre>"));
+        assert!(html.trim_end().ends_with("</html>"));
+    }
+}
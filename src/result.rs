@@ -0,0 +1,193 @@
+use crate::event::{Event, EventValue};
+use crate::rule::{Level, Logsource, Rule, Status};
+use std::collections::HashMap;
+
+/// The fields holding the time an event happened, in the order they are looked up
+const TIMESTAMP_FIELDS: [&str; 2] = ["@timestamp", "timestamp"];
+
+/// A `MatchResult` describes a rule that matched an event.
+///
+/// It carries a copy of the rule metadata that is relevant for alerting as well as
+/// the values of all event fields listed in the `fields` attribute of the rule,
+/// so it can be handed to output renderers or sinks without keeping the rule around.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchResult {
+    /// The id of the matching rule
+    pub rule_id: Option<String>,
+    /// The title of the matching rule
+    pub rule_title: String,
+    /// The description of the matching rule
+    pub rule_description: Option<String>,
+    /// The author of the matching rule
+    pub rule_author: Option<String>,
+    /// The status of the matching rule
+    pub status: Option<Status>,
+    /// The level of the matching rule
+    pub level: Option<Level>,
+    /// The tags of the matching rule
+    pub tags: Vec<String>,
+    /// The references of the matching rule
+    pub references: Vec<String>,
+    /// The logsource of the matching rule
+    pub logsource: Logsource,
+    /// The values of the event fields that are listed in the `fields` attribute of the rule.
+    /// Fields that are not present in the event are omitted.
+    pub fields: HashMap<String, EventValue>,
+    /// The time the matching event happened, i.e. the value of its `@timestamp` or `timestamp`
+    /// field. `None` if the event has neither.
+    pub timestamp: Option<EventValue>,
+    /// Events that preceded the matching event for the same entity, oldest first.
+    /// This is only populated if a [`crate::context::ContextWindow`] is used.
+    pub context: Vec<Event>,
+    /// Values added after matching, e.g. the names of ATT&CK techniques or the criticality of
+    /// the asset. This is only populated if an [`crate::enrich::Annotator`] is used.
+    pub annotations: HashMap<String, EventValue>,
+}
+
+impl MatchResult {
+    /// Create a new match result for a rule and the event it matched
+    pub(crate) fn new(rule: &Rule, event: &Event) -> Self {
+        let fields = rule
+            .fields
+            .iter()
+            .flatten()
+            .filter_map(|name| event.get(name).map(|v| (name.to_string(), v.clone())))
+            .collect();
+
+        Self {
+            rule_id: rule.id.clone(),
+            rule_title: rule.title.clone(),
+            rule_description: rule.description.clone(),
+            rule_author: rule.author.clone(),
+            status: rule.status,
+            level: rule.level,
+            tags: rule.tags.clone().unwrap_or_default(),
+            references: rule.references.clone().unwrap_or_default(),
+            logsource: rule.logsource.clone(),
+            fields,
+            timestamp: TIMESTAMP_FIELDS
+                .iter()
+                .find_map(|name| event.get(name))
+                .cloned(),
+            context: vec![],
+            annotations: HashMap::new(),
+        }
+    }
+
+    /// The MITRE ATT&CK technique ids of tags such as `attack.t1059.001`, in uppercase
+    pub fn attack_techniques(&self) -> Vec<String> {
+        self.tags
+            .iter()
+            .filter_map(|t| t.strip_prefix("attack."))
+            .filter(|t| {
+                let mut chars = t.chars();
+                matches!(chars.next(), Some('t' | 'T'))
+                    && chars.next().is_some_and(|c| c.is_ascii_digit())
+            })
+            .map(|t| t.to_uppercase())
+            .collect()
+    }
+
+    /// A compact JSON representation of the match with the id, title, level, tags and fields
+    /// of the rule, as returned by the language bindings. Annotations are included if there
+    /// are any.
+    #[cfg(feature = "serde_json")]
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::{json, Value};
+        let mut json = json!({
+            "id": self.rule_id,
+            "title": self.rule_title,
+            "level": self.level,
+            "tags": self.tags,
+            "fields": self
+                .fields
+                .iter()
+                .map(|(k, v)| (k.clone(), Value::from(v)))
+                .collect::<serde_json::Map<_, _>>(),
+        });
+        if let (false, Some(object)) = (self.annotations.is_empty(), json.as_object_mut()) {
+            object.insert(
+                "annotations".to_string(),
+                self.annotations
+                    .iter()
+                    .map(|(k, v)| (k.clone(), Value::from(v)))
+                    .collect::<serde_json::Map<_, _>>()
+                    .into(),
+            );
+        }
+        json
+    }
+}
+
+/// A match of the rule `Whoami` with the id `1a2b` on a host, for the tests of the modules that
+/// handle match results
+#[cfg(test)]
+pub(crate) fn whoami_match(host: &str) -> MatchResult {
+    let rule = crate::rule_from_yaml(
+        r#"
+title: Whoami
+id: 1a2b
+logsource:
+fields:
+    - Computer
+detection:
+    selection:
+        Image|endswith: '\whoami.exe'
+    condition: selection
+"#,
+    )
+    .unwrap();
+    rule.evaluate(&Event::from([
+        ("Image", "C:\\whoami.exe"),
+        ("Computer", host),
+    ]))
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    #[test]
+    fn test_match_result_extracts_fields() {
+        let rule = rule_from_yaml(
+            r#"
+        title: Extract fields
+        id: 5a0a8e1c-2a52-4d55-a5a7-41a2b5c4f0e1
+        level: high
+        tags:
+            - attack.execution
+        logsource:
+            product: windows
+        detection:
+            selection:
+                Image|endswith: '\cmd.exe'
+            condition: selection
+        fields:
+            - Image
+            - User
+            - ParentImage
+        "#,
+        )
+        .unwrap();
+
+        let event = Event::from([("Image", "C:\\cmd.exe"), ("User", "admin")]);
+        let result = rule.evaluate(&event).unwrap();
+
+        assert_eq!(
+            result.rule_id.as_deref(),
+            Some("5a0a8e1c-2a52-4d55-a5a7-41a2b5c4f0e1")
+        );
+        assert_eq!(result.rule_title, "Extract fields");
+        assert_eq!(result.level, Some(Level::High));
+        assert_eq!(result.tags, vec!["attack.execution"]);
+        assert_eq!(result.logsource.product.as_deref(), Some("windows"));
+        assert_eq!(result.fields.len(), 2);
+        assert_eq!(result.fields["Image"], EventValue::from("C:\\cmd.exe"));
+        assert_eq!(result.fields["User"], EventValue::from("admin"));
+
+        let event = Event::from([("Image", "C:\\calc.exe")]);
+        assert!(rule.evaluate(&event).is_none());
+    }
+}
@@ -0,0 +1,347 @@
+//! Dispatch match results to different destinations by the metadata of the matching rule.
+//!
+//! A [`Router`] holds a list of [`Route`]s, each paired with a destination such as an alert
+//! sink. Routes select results by the tags, level and logsource of the rule, e.g. critical
+//! matches go to a pager while informational ones go to a data lake. The router is generic
+//! over the destination type, so it can hold channels, writers or any custom sink.
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::routing::{Route, Router};
+//! use sigma_rust::{rule_from_yaml, Event, Level, RuleSet};
+//!
+//! let rule = rule_from_yaml(
+//!     r#"
+//! title: Whoami
+//! level: critical
+//! tags:
+//!     - attack.discovery
+//! logsource:
+//!     category: process_creation
+//! detection:
+//!     selection:
+//!         Image|endswith: '\whoami.exe'
+//!     condition: selection
+//! "#,
+//! )
+//! .unwrap();
+//! let rule_set = RuleSet::new(vec![rule]);
+//!
+//! let mut router = Router::new()
+//!     .route(Route::new().min_level(Level::High), "pager")
+//!     .route(Route::new().tag("attack.*"), "attack")
+//!     .default_destination("data lake");
+//!
+//! let mut sent = vec![];
+//! for result in rule_set.evaluate(&Event::from([("Image", "C:\\whoami.exe")])) {
+//!     router.dispatch(&result, |destination, result| sent.push((*destination, result.rule_title.clone())));
+//! }
+//! assert_eq!(sent.len(), 2);
+//! assert_eq!(sent[0].0, "pager");
+//! assert_eq!(sent[1].0, "attack");
+//! ```
+use crate::ratelimit::Overflow;
+use crate::result::MatchResult;
+use crate::rule::{Level, Logsource};
+use crate::sink::{AlertSink, SinkError};
+
+/// Selects match results by the metadata of the matching rule. All conditions of a route must
+/// hold, and a route without conditions selects every result.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Route {
+    tags: Vec<String>,
+    min_level: Option<Level>,
+    max_level: Option<Level>,
+    logsource: Option<Logsource>,
+}
+
+impl Route {
+    /// Create a route that selects every match result
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Select results whose rule has this tag. A trailing `*` selects all tags with the prefix,
+    /// e.g. `attack.*`. If several tags are given, any of them has to be present. Tags are
+    /// compared case-insensitively.
+    pub fn tag<S: Into<String>>(mut self, tag: S) -> Self {
+        self.tags.push(tag.into().to_lowercase());
+        self
+    }
+
+    /// Select results whose rule has at least this level. Rules without a level are not
+    /// selected.
+    pub fn min_level(mut self, level: Level) -> Self {
+        self.min_level = Some(level);
+        self
+    }
+
+    /// Select results whose rule has at most this level. Rules without a level are not
+    /// selected.
+    pub fn max_level(mut self, level: Level) -> Self {
+        self.max_level = Some(level);
+        self
+    }
+
+    /// Select results whose rule has the category, product and service of the logsource,
+    /// as far as they are set. They are compared case-insensitively.
+    pub fn logsource(mut self, logsource: Logsource) -> Self {
+        self.logsource = Some(logsource);
+        self
+    }
+
+    /// Whether the route selects the match result
+    pub fn matches(&self, result: &MatchResult) -> bool {
+        let tags_match = self.tags.is_empty()
+            || self.tags.iter().any(|pattern| {
+                result
+                    .tags
+                    .iter()
+                    .any(|tag| tag_matches(pattern, &tag.to_lowercase()))
+            });
+        let level = result.level.map(|level| level as u8);
+        let min_level_match = self
+            .min_level
+            .map_or(true, |min| level.is_some_and(|level| level >= min as u8));
+        let max_level_match = self
+            .max_level
+            .map_or(true, |max| level.is_some_and(|level| level <= max as u8));
+        let logsource_match = self.logsource.as_ref().map_or(true, |logsource| {
+            equal(&logsource.category, &result.logsource.category)
+                && equal(&logsource.product, &result.logsource.product)
+                && equal(&logsource.service, &result.logsource.service)
+        });
+        tags_match && min_level_match && max_level_match && logsource_match
+    }
+}
+
+fn tag_matches(pattern: &str, tag: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => tag.starts_with(prefix),
+        None => pattern == tag,
+    }
+}
+
+/// Whether an optional value of a route is unset or equal to the value of the result
+fn equal(route: &Option<String>, result: &Option<String>) -> bool {
+    match (route, result) {
+        (None, _) => true,
+        (Some(route), Some(result)) => route.eq_ignore_ascii_case(result),
+        (Some(_), None) => false,
+    }
+}
+
+/// Dispatches match results to the destinations of the routes that select them.
+///
+/// By default, a result is sent to every matching route in the order the routes were added.
+/// With [`Router::first_match`], only the first matching route receives it. Results that no
+/// route selects are sent to the default destination, if one is set.
+///
+/// A router of [`AlertSink`]s is a sink itself, so it can be passed to
+/// [`crate::MatchStream::send_to`].
+#[derive(Debug, Clone)]
+pub struct Router<D> {
+    routes: Vec<(Route, D)>,
+    default: Option<D>,
+    first_match: bool,
+}
+
+impl<D> Default for Router<D> {
+    fn default() -> Self {
+        Self {
+            routes: vec![],
+            default: None,
+            first_match: false,
+        }
+    }
+}
+
+impl<D> Router<D> {
+    /// Create a router without routes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send the results selected by `route` to `destination`
+    pub fn route(mut self, route: Route, destination: D) -> Self {
+        self.routes.push((route, destination));
+        self
+    }
+
+    /// Send the results that no route selects to `destination`
+    pub fn default_destination(mut self, destination: D) -> Self {
+        self.default = Some(destination);
+        self
+    }
+
+    /// Only send a result to the first route that selects it
+    pub fn first_match(mut self) -> Self {
+        self.first_match = true;
+        self
+    }
+
+    /// Call `send` with every destination the result is routed to and return their number
+    pub fn dispatch<F>(&mut self, result: &MatchResult, mut send: F) -> usize
+    where
+        F: FnMut(&mut D, &MatchResult),
+    {
+        let mut sent = 0;
+        for (route, destination) in self.routes.iter_mut() {
+            if route.matches(result) {
+                send(destination, result);
+                sent += 1;
+                if self.first_match {
+                    break;
+                }
+            }
+        }
+        if sent == 0 {
+            if let Some(destination) = &mut self.default {
+                send(destination, result);
+                sent += 1;
+            }
+        }
+        sent
+    }
+
+    /// The destinations of all routes followed by the default destination, e.g. to flush them
+    pub fn destinations_mut(&mut self) -> impl Iterator<Item = &mut D> {
+        self.routes
+            .iter_mut()
+            .map(|(_, destination)| destination)
+            .chain(self.default.as_mut())
+    }
+}
+
+/// Sends every match to the sinks it is routed to
+impl<D: AlertSink> AlertSink for Router<D> {
+    fn send(&mut self, result: &MatchResult) -> Result<(), SinkError> {
+        let mut error = None;
+        self.dispatch(result, |sink, result| {
+            if let Err(e) = sink.send(result) {
+                error.get_or_insert(e);
+            }
+        });
+        error.map_or(Ok(()), Err)
+    }
+
+    /// The summary is routed like the first suppressed match of the rule
+    fn send_overflow(&mut self, overflow: &Overflow) -> Result<(), SinkError> {
+        let mut error = None;
+        self.dispatch(&overflow.result, |sink, _| {
+            if let Err(e) = sink.send_overflow(overflow) {
+                error.get_or_insert(e);
+            }
+        });
+        error.map_or(Ok(()), Err)
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        self.destinations_mut()
+            .map(|sink| sink.flush())
+            .fold(Ok(()), Result::and)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rule_from_yaml, Event};
+
+    fn result(level: Option<&str>, tags: &[&str], product: &str) -> MatchResult {
+        let mut yaml = format!(
+            "title: Test\nlogsource:\n    product: {}\ndetection:\n    selection:\n        Image: x\n    condition: selection\n",
+            product
+        );
+        if let Some(level) = level {
+            yaml.push_str(&format!("level: {}\n", level));
+        }
+        if !tags.is_empty() {
+            yaml.push_str(&format!("tags: [{}]\n", tags.join(", ")));
+        }
+        let rule = rule_from_yaml(&yaml).unwrap();
+        rule.evaluate(&Event::from([("Image", "x")])).unwrap()
+    }
+
+    #[test]
+    fn test_route() {
+        let critical = result(Some("critical"), &["attack.T1059"], "windows");
+        let low = result(Some("low"), &["cve.2024"], "linux");
+        let none = result(None, &[], "Windows");
+
+        assert!(Route::new().matches(&none));
+        let route = Route::new().min_level(Level::High);
+        assert!(route.matches(&critical));
+        assert!(!route.matches(&low));
+        assert!(!route.matches(&none));
+
+        let route = Route::new().max_level(Level::Medium);
+        assert!(!route.matches(&critical));
+        assert!(route.matches(&low));
+
+        let route = Route::new().tag("ATTACK.*");
+        assert!(route.matches(&critical));
+        assert!(!route.matches(&low));
+        let route = Route::new().tag("attack.t1059").tag("cve.2024");
+        assert!(route.matches(&critical));
+        assert!(route.matches(&low));
+        assert!(!Route::new().tag("attack").matches(&critical));
+
+        let route = Route::new().logsource(Logsource::new(None, Some("windows"), None));
+        assert!(route.matches(&critical));
+        assert!(!route.matches(&low));
+        assert!(route.matches(&none));
+        assert!(!route.min_level(Level::Informational).matches(&none));
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let critical = result(Some("critical"), &["attack.t1059"], "windows");
+        let informational = result(Some("informational"), &[], "windows");
+        let low = result(Some("low"), &[], "windows");
+
+        let router = Router::new()
+            .route(Route::new().min_level(Level::High), vec![])
+            .route(Route::new().tag("attack.*"), vec![])
+            .route(Route::new().max_level(Level::Informational), vec![])
+            .default_destination(vec![]);
+        let titles = |mut router: Router<Vec<String>>| {
+            for result in [&critical, &informational, &low] {
+                router.dispatch(result, |destination, result| {
+                    destination.push(format!("{:?}", result.level.unwrap()))
+                });
+            }
+            router
+                .destinations_mut()
+                .map(|d| d.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            titles(router.clone()),
+            vec![
+                vec!["Critical"],
+                vec!["Critical"],
+                vec!["Informational"],
+                vec!["Low"]
+            ]
+        );
+        assert_eq!(
+            titles(router.first_match()),
+            vec![vec!["Critical"], vec![], vec!["Informational"], vec!["Low"]]
+        );
+
+        let mut router: Router<()> = Router::new();
+        assert_eq!(router.dispatch(&low, |_, _| {}), 0);
+
+        let mut router = Router::new()
+            .route(Route::new().min_level(Level::High), vec![])
+            .default_destination(vec![]);
+        router.send(&critical).unwrap();
+        router.send(&low).unwrap();
+        router.flush().unwrap();
+        let sinks: Vec<Vec<MatchResult>> = router.destinations_mut().map(std::mem::take).collect();
+        assert_eq!(sinks[0][0].rule_title, "Test");
+        assert_eq!(sinks[1][0].level, Some(Level::Low));
+    }
+}
@@ -1,7 +1,16 @@
+mod builder;
+
+use crate::config::SpecMode;
+use crate::cost::RuleCost;
 use crate::detection::Detection;
+use crate::evaluation::EvalContext;
 use crate::event::Event;
+use crate::explain::{self, Explanation};
+use crate::result::MatchResult;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+
+pub use builder::RuleBuilder;
 
 /// Declares the status of the rule
 #[derive(Serialize, Deserialize, PartialEq, Copy, Clone, Debug)]
@@ -28,7 +37,7 @@ pub enum Status {
 ///   - id: 929a690e-bef0-4204-a928-ef5e620d6fcc
 ///     type: obsolete
 /// ```
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Related {
     pub id: String,
     #[serde(rename = "type")]
@@ -36,7 +45,7 @@ pub struct Related {
 }
 
 /// The related type describes the relationship between the rule and the referred rule.
-#[derive(Deserialize, PartialEq, Copy, Clone, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Copy, Clone, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum RelatedType {
     /// The rule was derived from the referred rule or rules, which may remain active.
@@ -53,7 +62,7 @@ pub enum RelatedType {
 
 /// The logsource describes the log data on which the detection is meant to be applied to.
 /// It describes the log source, the platform, the application and the type that is required in the detection.
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
 pub struct Logsource {
     /// The category value is used to select all log files written of a logical group.
     /// This may cover one or more sources of information depending on the system.
@@ -70,10 +79,49 @@ pub struct Logsource {
     pub definition: Option<String>,
 }
 
+impl Logsource {
+    /// Create a logsource from its category, product and service
+    pub fn new(category: Option<&str>, product: Option<&str>, service: Option<&str>) -> Self {
+        Self {
+            category: category.map(str::to_string),
+            product: product.map(str::to_string),
+            service: service.map(str::to_string),
+            definition: None,
+        }
+    }
+
+    /// Check if every attribute that is set in this logsource is also set to the same value,
+    /// ignoring ASCII case, in the `other` logsource. A logsource without attributes
+    /// matches any other logsource.
+    ///
+    /// # Example
+    /// ```rust
+    /// use sigma_rust::Logsource;
+    ///
+    /// let rule = Logsource::new(Some("process_creation"), Some("windows"), None);
+    /// let events = Logsource::new(Some("process_creation"), Some("Windows"), Some("sysmon"));
+    /// assert!(rule.matches(&events));
+    /// assert!(!events.matches(&rule));
+    /// ```
+    pub fn matches(&self, other: &Logsource) -> bool {
+        [
+            (&self.category, &other.category),
+            (&self.product, &other.product),
+            (&self.service, &other.service),
+        ]
+        .into_iter()
+        .all(|(expected, actual)| match (expected, actual) {
+            (None, _) => true,
+            (Some(expected), Some(actual)) => expected.eq_ignore_ascii_case(actual),
+            (Some(_), None) => false,
+        })
+    }
+}
+
 /// The level describes the criticality of a triggered rule.
 /// While low and medium level events have an informative character,
 /// events with high and critical level should lead to immediate reviews by security analysts.
-#[derive(Serialize, Deserialize, PartialEq, Copy, Clone, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Copy, Clone, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum Level {
     /// Rule is intended for enrichment of events, e.g. by tagging them. No case or alerting should be triggered by such rules because it is expected that a huge amount of events will match these rules.
@@ -92,35 +140,46 @@ pub enum Level {
 ///
 /// The full specification can be found at:
 /// <https://github.com/SigmaHQ/sigma-specification/blob/main/specification/sigma-rules-specification.md>
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Rule {
     /// A brief title for the rule that should contain what the rule is supposed to detect (max. 256 characters)
     pub title: String,
     /// Sigma rules should be identified by a globally unique identifier in the id attribute.
     /// For this purpose randomly generated UUIDs (version 4) is used.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
     /// name is a unique human-readable name that can be used instead of the id as a reference in correlation rules.
     /// The goal is to improve the readability of correlation rules.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
     /// To be able to keep track of the relationships between detections, Sigma rules may also contain references to related rule identifiers in the related attribute.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub related: Option<Vec<Related>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub taxonomy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub status: Option<Status>,
     /// A short and accurate description of the rule and the malicious or suspicious activity that can be detected (max. 65,535 characters)
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
     /// License of the rule according to <https://spdx.dev/learn/handling-license-info/> format.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub license: Option<String>,
     /// Creator of the rule. (can be a name, nickname, twitter handle...etc)
     /// If there is more than one, they are separated by a comma.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub author: Option<String>,
     /// References to the sources that the rule was derived from.
     /// These could be blog articles, technical papers, presentations or even tweets.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub references: Option<Vec<String>>,
     /// Creation date of the rule.
     /// Use the ISO 8601 date with separator format : YYYY-MM-DD
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub date: Option<String>,
     /// Last modification date of the rule.
     /// Use the ISO 8601 date with separator format : YYYY-MM-DD
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub modified: Option<String>,
     /// This section describes the log data on which the detection is meant to be applied to.
     /// It describes the log source, the platform, the application and the type that is required in the detection.
@@ -129,26 +188,38 @@ pub struct Rule {
     pub detection: Detection,
     /// A list of log fields that could be interesting for further analysis of the event
     /// and should be displayed to the analyst.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub fields: Option<Vec<String>>,
     /// A list of known false positives that may occur.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub falsepositives: Option<Vec<String>>,
     /// The level field contains one of five string values.
     /// It describes the criticality of a triggered rule.
     /// While low and medium level events have an informative character,
     /// events with high and critical level should lead to immediate reviews by security analysts.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub level: Option<Level>,
     ///  Tags should generally follow this syntax:
     /// * Character set: lower-case letters, numerals, underscores and hyphens
     /// * no spaces
     /// * Tags are namespaced, the dot is used as separator. e.g. attack.t1234 refers to technique 1234 in the namespace attack; Namespaces may also be nested
     /// * Keep tags short, e.g. numeric identifiers instead of long sentences
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub tags: Option<Vec<String>>,
+    /// The [`SpecMode`] the rule was parsed with
+    #[serde(skip, default = "crate::config::active_spec_mode")]
+    pub spec_mode: SpecMode,
     /// Capture any additional fields
     #[serde(flatten)]
     pub custom_fields: HashMap<String, serde_yml::Value>,
 }
 
 impl Rule {
+    /// Start building a rule in Rust code, see [`RuleBuilder`]
+    pub fn builder() -> RuleBuilder {
+        RuleBuilder::new()
+    }
+
     /// Check if the event matches the rule
     ///
     /// # Example
@@ -175,6 +246,126 @@ impl Rule {
     pub fn is_match(&self, event: &Event) -> bool {
         self.detection.evaluate(event)
     }
+
+    pub(crate) fn is_match_in(&self, ctx: &EvalContext) -> bool {
+        self.detection.evaluate_in(ctx)
+    }
+
+    /// The priority of the rule within a [`crate::RuleSet`], see
+    /// [`crate::EvaluationOrder::Priority`]. An integer `priority` attribute of the rule takes
+    /// precedence, otherwise it is derived from the level, from 0 for rules without a level
+    /// to 5 for critical rules.
+    pub fn priority(&self) -> i64 {
+        self.custom_fields
+            .get("priority")
+            .and_then(serde_yml::Value::as_i64)
+            .unwrap_or_else(|| self.level.map_or(0, |level| level as i64 + 1))
+    }
+
+    /// Warnings about the deprecated Sigma v1 syntax of the rule, which was rewritten because
+    /// the rule was parsed with [`SpecMode::Legacy`]
+    pub fn legacy_warnings(&self) -> &[String] {
+        self.detection.legacy_warnings()
+    }
+
+    /// The names of the event fields the selections of the rule read, including the fields
+    /// referenced with the `fieldref` modifier. Nested fields are named by their path, e.g.
+    /// `Event.ID`. Keyword selections read every field of the event, see
+    /// [`Rule::has_keywords`].
+    ///
+    /// # Example
+    /// ```rust
+    /// use sigma_rust::rule_from_yaml;
+    ///
+    /// let rule = rule_from_yaml(
+    ///     r#"
+    /// title: Renamed binary
+    /// logsource:
+    /// detection:
+    ///     selection:
+    ///         Image|endswith: '\cmd.exe'
+    ///         OriginalFileName|fieldref|endswith: ParentImage
+    ///     filter:
+    ///         Image|startswith: 'C:\Windows\'
+    ///     condition: selection and not filter
+    /// "#,
+    /// )
+    /// .unwrap();
+    /// let fields: Vec<_> = rule.referenced_fields().into_iter().collect();
+    /// assert_eq!(fields, ["Image", "OriginalFileName", "ParentImage"]);
+    /// assert!(!rule.has_keywords());
+    /// ```
+    pub fn referenced_fields(&self) -> BTreeSet<String> {
+        self.detection
+            .fields()
+            .flat_map(|field| field.referenced_names())
+            .collect()
+    }
+
+    /// Whether the rule has a keyword selection, which is matched against the values of all
+    /// fields of the event
+    pub fn has_keywords(&self) -> bool {
+        self.detection.has_keywords()
+    }
+
+    /// Estimate how expensive the rule is to evaluate, see [`crate::cost`]
+    pub fn cost(&self) -> RuleCost {
+        RuleCost::of(&self.detection)
+    }
+
+    /// Explain why the rule does or does not match an event: the result of every evaluated
+    /// selection and, if the rule does not match, the first selection that failed with the
+    /// failing field and the value of the event it was compared to.
+    ///
+    /// # Example
+    /// ```rust
+    /// use sigma_rust::explain::{FieldMismatch, Mismatch};
+    /// use sigma_rust::{rule_from_yaml, Event, EventValue};
+    ///
+    /// let rule = rule_from_yaml(
+    ///     r#"
+    /// title: Whoami
+    /// logsource:
+    /// detection:
+    ///     selection:
+    ///         Image|endswith: '\whoami.exe'
+    ///         User|startswith: 'NT AUTHORITY'
+    ///     condition: selection
+    /// "#,
+    /// )
+    /// .unwrap();
+    ///
+    /// let event = Event::from([("Image", "C:\\whoami.exe"), ("User", "Administrator")]);
+    /// let explanation = rule.explain(&event);
+    /// assert!(!explanation.matched);
+    /// let Some(Mismatch::Unmatched {
+    ///     field: Some(FieldMismatch { field, actual, .. }),
+    ///     ..
+    /// }) = &explanation.mismatch
+    /// else {
+    ///     unreachable!()
+    /// };
+    /// assert_eq!(field, "User|startswith");
+    /// assert_eq!(actual, &Some(EventValue::from("Administrator")));
+    /// assert_eq!(
+    ///     explanation.mismatch.unwrap().to_string(),
+    ///     "selection 'selection' did not match: User|startswith expected 'NT AUTHORITY', got 'Administrator'"
+    /// );
+    /// ```
+    pub fn explain(&self, event: &Event) -> Explanation {
+        explain::explain(&self.detection, event)
+    }
+
+    /// Evaluate the rule against an event and return a [`MatchResult`] if it matches.
+    /// The result contains the rule metadata and the values of the event fields
+    /// listed in the `fields` attribute of the rule.
+    pub fn evaluate(&self, event: &Event) -> Option<MatchResult> {
+        if self.is_match(event) {
+            Some(MatchResult::new(self, event))
+        } else {
+            None
+        }
+    }
 }
 
 #[cfg(test)]
@@ -184,6 +375,54 @@ mod tests {
     use crate::selection::Selection;
     use crate::wildcard::WildcardToken;
 
+    #[cfg(all(feature = "cidr", feature = "base64"))]
+    #[test]
+    fn test_serialize_round_trip() {
+        let rule_yaml = r#"
+        title: Round trip
+        id: fb97a1c5-9e86-4e15-9fd9-7d82a05a384e
+        related:
+            - id: ab97a1c5-9e86-4e15-9fd9-7d82a05a384e
+              type: derived
+        status: test
+        logsource:
+            category: process_creation
+            product: windows
+        detection:
+            selection:
+                - Image|endswith: '\whoami.exe'
+                  CommandLine|base64offset|contains: whoami
+                - ParentImage|re: '(?i)\\cmd\.exe$'
+                  SourceIp|cidr:
+                      - 10.0.0.0/8
+                      - 192.168.0.0/16
+            filter:
+                EventID: 4688
+                User: null
+                Hidden|exists: false
+            keywords:
+                - mimikatz
+                - 1337
+            condition: (selection or keywords) and not filter
+        level: high
+        tags:
+            - attack.t1033
+        custom_field: some value
+        "#;
+        let rule: Rule = serde_yml::from_str(rule_yaml).unwrap();
+        let cloned = rule.clone();
+        assert_eq!(rule, cloned);
+
+        let yaml = serde_yml::to_string(&rule).unwrap();
+        assert!(!yaml.contains("spec_mode"), "{}", yaml);
+        assert!(!yaml.contains("description"), "{}", yaml);
+        let reparsed: Rule = serde_yml::from_str(&yaml).unwrap();
+        assert_eq!(rule, reparsed);
+
+        let other: Rule = serde_yml::from_str(&rule_yaml.replace("4688", "4689")).unwrap();
+        assert_ne!(rule, other);
+    }
+
     #[test]
     fn test_load_from_yaml() {
         let rule_yaml = r#"
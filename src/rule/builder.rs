@@ -0,0 +1,320 @@
+use crate::config;
+use crate::detection::Detection;
+use crate::error::ParserError;
+use crate::rule::{Level, Logsource, Rule, Status};
+use serde_yml::{Mapping, Value};
+use std::collections::HashMap;
+
+/// Builds a [`Rule`] in Rust code instead of parsing it from YAML, e.g. for generated rules and
+/// tests. Selections are compiled by [`RuleBuilder::build`] just like the selections of a
+/// parsed rule, so they support the same field modifiers and fail with the same errors.
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::{Event, Level, Rule};
+///
+/// let rule = Rule::builder()
+///     .title("Whoami")
+///     .level(Level::Low)
+///     .selection("selection", [("Image|endswith", "\\whoami.exe")])
+///     .selection("filter", [("User", vec!["SYSTEM", "LOCAL SERVICE"])])
+///     .condition("selection and not filter")
+///     .build()
+///     .unwrap();
+///
+/// assert!(rule.is_match(&Event::from([("Image", r"C:\Windows\System32\whoami.exe")])));
+/// ```
+#[derive(Debug, Default)]
+pub struct RuleBuilder {
+    title: String,
+    id: Option<String>,
+    name: Option<String>,
+    status: Option<Status>,
+    description: Option<String>,
+    author: Option<String>,
+    references: Option<Vec<String>>,
+    date: Option<String>,
+    modified: Option<String>,
+    logsource: Logsource,
+    falsepositives: Option<Vec<String>>,
+    level: Option<Level>,
+    tags: Option<Vec<String>>,
+    /// The selections in the order they were added, checked for duplicates when building
+    selections: Vec<(String, Result<Value, ParserError>)>,
+    condition: Option<String>,
+}
+
+impl RuleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn status(mut self, status: Status) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    /// Add a reference, e.g. the URL of a report the rule was derived from
+    pub fn reference(mut self, reference: impl Into<String>) -> Self {
+        self.references
+            .get_or_insert_with(Vec::new)
+            .push(reference.into());
+        self
+    }
+
+    /// Set the creation date, formatted as YYYY-MM-DD
+    pub fn date(mut self, date: impl Into<String>) -> Self {
+        self.date = Some(date.into());
+        self
+    }
+
+    /// Set the date of the last modification, formatted as YYYY-MM-DD
+    pub fn modified(mut self, modified: impl Into<String>) -> Self {
+        self.modified = Some(modified.into());
+        self
+    }
+
+    pub fn logsource(mut self, logsource: Logsource) -> Self {
+        self.logsource = logsource;
+        self
+    }
+
+    /// Add a known false positive
+    pub fn falsepositive(mut self, falsepositive: impl Into<String>) -> Self {
+        self.falsepositives
+            .get_or_insert_with(Vec::new)
+            .push(falsepositive.into());
+        self
+    }
+
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Add a tag, e.g. `attack.t1033`
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.get_or_insert_with(Vec::new).push(tag.into());
+        self
+    }
+
+    /// Add a selection of fields that must all match. Keys are field names with optional
+    /// modifiers, e.g. `Image|endswith`. A value is a single value or a list of values, e.g.
+    /// `"cmd.exe"`, `4688` or `vec!["a", "b"]`.
+    pub fn selection<I, K, V>(mut self, name: impl Into<String>, fields: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        let name = name.into();
+        let mut mapping = Mapping::new();
+        let mut result = Ok(());
+        for (key, value) in fields {
+            let key = key.into();
+            if mapping.contains_key(key.as_str()) && result.is_ok() {
+                result = Err(ParserError::DuplicateField(name.clone(), key.clone()));
+            }
+            mapping.insert(Value::String(key), value.into());
+        }
+        let selection = result.map(|_| Value::Mapping(mapping));
+        self.selections.push((name, selection));
+        self
+    }
+
+    /// Add a selection of keywords, of which at least one must match any value of the event.
+    /// Keywords are case-insensitive and need wildcards to match parts of values, e.g. `*evil*`.
+    pub fn keywords<I, V>(mut self, name: impl Into<String>, keywords: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<Value>,
+    {
+        let keywords = keywords.into_iter().map(Into::into).collect();
+        self.selections
+            .push((name.into(), Ok(Value::Sequence(keywords))));
+        self
+    }
+
+    /// Set the condition that combines the selections, e.g. `selection and not filter`
+    pub fn condition(mut self, condition: impl Into<String>) -> Self {
+        self.condition = Some(condition.into());
+        self
+    }
+
+    /// Compile the selections and the condition. Fails like [`crate::rule_from_yaml`] for
+    /// invalid selections, and with [`ParserError::UnexpectedToken`] if no condition was set.
+    pub fn build(self) -> Result<Rule, ParserError> {
+        let mut selections = HashMap::with_capacity(self.selections.len());
+        for (name, selection) in self.selections {
+            if selections.contains_key(&name) {
+                return Err(ParserError::DuplicateSelection(name));
+            }
+            selections.insert(name, selection?);
+        }
+        let condition = self
+            .condition
+            .ok_or_else(|| ParserError::UnexpectedToken("end of condition".to_string()))?;
+
+        Ok(Rule {
+            title: self.title,
+            id: self.id,
+            name: self.name,
+            related: None,
+            taxonomy: None,
+            status: self.status,
+            description: self.description,
+            license: None,
+            author: self.author,
+            references: self.references,
+            date: self.date,
+            modified: self.modified,
+            logsource: self.logsource,
+            detection: Detection::from_values(selections, condition)?,
+            fields: None,
+            falsepositives: self.falsepositives,
+            level: self.level,
+            tags: self.tags,
+            spec_mode: config::active_spec_mode(),
+            custom_fields: HashMap::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rule_from_yaml, Event};
+
+    #[test]
+    fn test_build() {
+        let rule = Rule::builder()
+            .title("Whoami")
+            .id("5a0a8e1c-2a52-4d55-a5a7-41a2b5c4f0e1")
+            .status(Status::Test)
+            .logsource(Logsource::new(
+                Some("process_creation"),
+                Some("windows"),
+                None,
+            ))
+            .level(Level::High)
+            .tag("attack.discovery")
+            .tag("attack.t1033")
+            .reference("https://attack.mitre.org/techniques/T1033/")
+            .selection(
+                "selection",
+                [
+                    ("Image|endswith", Value::from("\\whoami.exe")),
+                    (
+                        "CommandLine|contains|all",
+                        Value::from(vec!["/user", "/all"]),
+                    ),
+                ],
+            )
+            .selection("filter", [("ProcessId", 4)])
+            .keywords("keywords", ["*mimikatz*"])
+            .condition("selection and not filter or keywords")
+            .build()
+            .unwrap();
+
+        assert_eq!(rule.title, "Whoami");
+        assert_eq!(rule.level, Some(Level::High));
+        assert_eq!(rule.tags.as_ref().unwrap().len(), 2);
+        assert_eq!(rule.logsource.product.as_deref(), Some("windows"));
+        assert_eq!(rule.detection.get_selections().len(), 3);
+
+        let event = Event::from([
+            ("Image", r"C:\Windows\System32\whoami.exe"),
+            ("CommandLine", "whoami /USER /ALL"),
+        ]);
+        assert!(rule.is_match(&event));
+        let mut filtered = event.clone();
+        filtered.insert("ProcessId", 4);
+        assert!(!rule.is_match(&filtered));
+        assert!(rule.is_match(&Event::from([("CommandLine", "mimikatz.exe")])));
+    }
+
+    #[test]
+    fn test_same_as_yaml() {
+        let built = Rule::builder()
+            .title("Keywords")
+            .keywords("keywords", [Value::from("*evil*"), Value::from(1337)])
+            .condition("keywords")
+            .build()
+            .unwrap();
+        let parsed = rule_from_yaml(
+            "title: Keywords\nlogsource:\ndetection:\n    keywords:\n        - '*evil*'\n        - 1337\n    condition: keywords\n",
+        )
+        .unwrap();
+        for event in [
+            Event::from([("a", "some evil value")]),
+            Event::from([("a", 1337)]),
+            Event::from([("a", "benign")]),
+        ] {
+            assert_eq!(built.is_match(&event), parsed.is_match(&event));
+        }
+        assert_eq!(built.logsource, parsed.logsource);
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = Rule::builder()
+            .selection("selection", [("Image|unknown", "x")])
+            .condition("selection")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ParserError::UnknownModifier(_)));
+
+        let err = Rule::builder()
+            .selection("selection", [("Image", "a")])
+            .selection("selection", [("Image", "b")])
+            .condition("selection")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ParserError::DuplicateSelection(name) if name == "selection"));
+
+        let err = Rule::builder()
+            .selection("selection", [("Image", "a"), ("Image", "b")])
+            .condition("selection")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ParserError::DuplicateField(..)));
+
+        let err = Rule::builder()
+            .selection("selection", [("Image", "a")])
+            .condition("selection and filter")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ParserError::UndefinedIdentifiers(_)));
+
+        let err = Rule::builder()
+            .selection("selection", [("Image", "a")])
+            .build()
+            .unwrap_err();
+        assert_eq!(err.code(), "TAU0012");
+    }
+}
@@ -0,0 +1,1475 @@
+#[cfg(feature = "async")]
+mod async_stream;
+mod index;
+mod interner;
+mod prefilter;
+mod stream;
+
+use crate::evaluation::EvalContext;
+use crate::event::Event;
+use crate::profile::{deadline, rule_key, timed, Profile};
+use crate::result::MatchResult;
+use crate::rule::{Logsource, Rule};
+use crate::ruleset::index::FieldIndex;
+use crate::ruleset::interner::{FieldInterner, SelectionInterner};
+use crate::ruleset::prefilter::Prefilter;
+use crate::taxonomy::Taxonomy;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+#[cfg(feature = "async")]
+pub use async_stream::{AsyncMatchStream, DEFAULT_YIELD_BUDGET};
+pub use stream::MatchStream;
+
+/// A collection of rules that are evaluated together.
+///
+/// When the rule set is created, the literals of all rules are compiled into a single
+/// Aho-Corasick automaton. During evaluation this automaton is used as a prefilter, so only
+/// rules whose literals appear in the event get fully evaluated.
+/// Field names are interned, so each field is looked up at most once per event,
+/// no matter how many rules reference it. Selections that are equal in several rules, e.g.
+/// filters copied between rules, are evaluated at most once per event as well, see
+/// [`RuleSet::shared_selections`]. Additionally, an inverted index maps field names to
+/// the rules requiring them, so rules are skipped if none of their required fields is present.
+/// Events with a logsource are only evaluated against the rules whose logsource matches it,
+/// see [`crate::taxonomy`].
+///
+/// # Order of matches
+/// The matches of an event are returned in the order the rules were added to the rule set,
+/// no matter whether they are evaluated sequentially, in parallel or as a stream, so the
+/// output is reproducible. Use [`RuleSet::with_match_order`] to return the most severe
+/// matches first instead.
+///
+/// # Latency-sensitive deployments
+/// With [`RuleSet::with_evaluation_order`] the rules with the highest [`Rule::priority`] are
+/// evaluated first, and [`RuleSet::with_match_limit`] stops evaluating an event after a number
+/// of matches, e.g. after the first one. Together they return the most important matches of
+/// an event without evaluating the remaining rules. [`RuleSet::with_time_budget`] bounds the
+/// time spent on a single event by [`RuleSet::evaluate_budgeted`].
+///
+/// # Sharing between threads
+/// Evaluation only borrows the rule set immutably and keeps all per-event state on the stack
+/// of the caller, so a rule set is `Send + Sync` and can be shared by worker threads behind an
+/// [`std::sync::Arc`] without cloning the compiled rules.
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::{rule_from_yaml, Event, RuleSet};
+///
+/// let rule = rule_from_yaml(
+///     r#"
+/// title: Whoami
+/// logsource:
+///     category: process_creation
+/// detection:
+///     selection:
+///         Image|endswith: '\whoami.exe'
+///     condition: selection
+/// "#,
+/// )
+/// .unwrap();
+///
+/// let rule_set = RuleSet::new(vec![rule]);
+/// let event = Event::from([("Image", "C:\\Windows\\System32\\whoami.exe")]);
+/// let matches = rule_set.evaluate(&event);
+/// assert_eq!(matches.len(), 1);
+/// assert_eq!(matches[0].rule_title, "Whoami");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    prefilter: Prefilter,
+    interner: FieldInterner,
+    selections: SelectionInterner,
+    index: FieldIndex,
+    taxonomy: Taxonomy,
+    order: MatchOrder,
+    evaluation_order: EvaluationOrder,
+    /// The indices of the rules in the order they are evaluated
+    evaluation: Vec<usize>,
+    match_limit: Option<usize>,
+    time_budget: Option<Duration>,
+    /// Whether each rule was disabled with [`RuleSet::set_enabled`]
+    disabled: Vec<bool>,
+    #[cfg(feature = "parallel")]
+    parallel: bool,
+}
+
+/// The matches of an event evaluated within a time budget, see [`RuleSet::evaluate_budgeted`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BudgetedMatches {
+    pub matches: Vec<MatchResult>,
+    /// Whether the budget passed before all rules were evaluated. The matches are then those of
+    /// the rules evaluated so far, in the order given by [`RuleSet::with_evaluation_order`].
+    pub budget_exceeded: bool,
+}
+
+/// The order in which the matches of an event are returned, see [`RuleSet::with_match_order`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MatchOrder {
+    /// In the order the rules were added to the rule set
+    #[default]
+    LoadOrder,
+    /// By the level of the rule, most severe first. Rules without a level come last and
+    /// matches of the same level are in load order.
+    Severity,
+}
+
+/// The order in which the rules are evaluated, see [`RuleSet::with_evaluation_order`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EvaluationOrder {
+    /// In the order the rules were added to the rule set
+    #[default]
+    LoadOrder,
+    /// By [`Rule::priority`], highest first. Rules of the same priority are evaluated in
+    /// load order.
+    Priority,
+}
+
+impl RuleSet {
+    /// Create a new rule set from a list of rules
+    pub fn new(mut rules: Vec<Rule>) -> Self {
+        let prefilter = Prefilter::new(&rules);
+        let interner = FieldInterner::new(&mut rules);
+        let selections = SelectionInterner::new(&mut rules);
+        let index = FieldIndex::new(&rules, &interner);
+        let evaluation = (0..rules.len()).collect();
+        let disabled = vec![false; rules.len()];
+        Self {
+            rules,
+            prefilter,
+            interner,
+            selections,
+            index,
+            taxonomy: Taxonomy::default(),
+            order: MatchOrder::default(),
+            evaluation_order: EvaluationOrder::default(),
+            evaluation,
+            match_limit: None,
+            time_budget: None,
+            disabled,
+            #[cfg(feature = "parallel")]
+            parallel: false,
+        }
+    }
+
+    /// Use custom definitions to decide which rules apply to the logsource of an event
+    pub fn with_taxonomy(mut self, taxonomy: Taxonomy) -> Self {
+        self.taxonomy = taxonomy;
+        self
+    }
+
+    /// Set the order in which the matches of an event are returned by all evaluation methods
+    pub fn with_match_order(mut self, order: MatchOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// The order of the matches of an event, see [`RuleSet::with_match_order`]
+    pub fn match_order(&self) -> MatchOrder {
+        self.order
+    }
+
+    /// Set the order in which the rules are evaluated. This only changes the matches of an
+    /// event if a match limit is set, see [`RuleSet::with_match_limit`].
+    pub fn with_evaluation_order(mut self, order: EvaluationOrder) -> Self {
+        self.evaluation_order = order;
+        self.sort_evaluation();
+        self
+    }
+
+    fn sort_evaluation(&mut self) {
+        self.evaluation.sort_unstable();
+        if self.evaluation_order == EvaluationOrder::Priority {
+            // The sort is stable, so rules of the same priority stay in load order
+            let rules = &self.rules;
+            self.evaluation.sort_by_cached_key(|i| {
+                std::cmp::Reverse(rules.get(*i).map_or(i64::MIN, Rule::priority))
+            })
+        }
+    }
+
+    /// The order in which the rules are evaluated, see [`RuleSet::with_evaluation_order`]
+    pub fn evaluation_order(&self) -> EvaluationOrder {
+        self.evaluation_order
+    }
+
+    /// Stop evaluating an event after `limit` rules matched it, in the order given by
+    /// [`RuleSet::with_evaluation_order`]. A limit of 1 means that the first match wins.
+    pub fn with_match_limit(mut self, limit: usize) -> Self {
+        self.match_limit = Some(limit.max(1));
+        self
+    }
+
+    /// The maximum number of matches per event, see [`RuleSet::with_match_limit`]
+    pub fn match_limit(&self) -> Option<usize> {
+        self.match_limit
+    }
+
+    /// Stop evaluating an event with [`RuleSet::evaluate_budgeted`] once `budget` has passed.
+    /// The time is checked before each rule, so a single rule is never interrupted; bound the
+    /// time of regular expressions with [`crate::EngineConfig::regex_max_input_len`].
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// The time budget per event, see [`RuleSet::with_time_budget`]
+    pub fn time_budget(&self) -> Option<Duration> {
+        self.time_budget
+    }
+
+    /// Add a rule after the existing ones without recompiling them. The field index and the
+    /// shared selections are updated in place, and the literals of added rules are kept in a
+    /// small separate prefilter, which is merged into the prefilter of the other rules once it
+    /// holds a few hundred rules.
+    ///
+    /// # Example
+    /// ```rust
+    /// use sigma_rust::{rule_from_yaml, Event, RuleSet};
+    ///
+    /// let rule = |title: &str, image: &str| {
+    ///     rule_from_yaml(&format!(
+    ///         "title: {}\nlogsource:\ndetection:\n    selection:\n        Image|endswith: '{}'\n    condition: selection",
+    ///         title, image
+    ///     ))
+    ///     .unwrap()
+    /// };
+    ///
+    /// let mut rule_set = RuleSet::new(vec![rule("Whoami", "whoami.exe")]);
+    /// rule_set.insert(rule("Net", "net.exe"));
+    /// assert_eq!(rule_set.evaluate(&Event::from([("Image", "C:\\net.exe")]))[0].rule_title, "Net");
+    ///
+    /// assert_eq!(rule_set.remove("Whoami").len(), 1);
+    /// assert!(rule_set.evaluate(&Event::from([("Image", "C:\\whoami.exe")])).is_empty());
+    /// ```
+    pub fn insert(&mut self, mut rule: Rule) {
+        self.interner.intern_rule(&mut rule);
+        self.rules.push(rule);
+        let i = self.rules.len() - 1;
+        self.selections.intern_rule(&mut self.rules, i);
+        self.prefilter.insert(&self.rules);
+        self.index.insert(&self.rules, &self.interner);
+        self.disabled.push(false);
+        self.evaluation.push(i);
+        self.sort_evaluation();
+    }
+
+    /// Remove the rules with the given id, or title for rules without an id, and return them.
+    /// The indexes of the remaining rules are updated in place without recompiling them.
+    pub fn remove(&mut self, rule_id: &str) -> Vec<Rule> {
+        let mut removed = vec![];
+        for i in (0..self.rules.len()).rev() {
+            if !self
+                .rules
+                .get(i)
+                .is_some_and(|rule| rule_key(rule) == rule_id)
+            {
+                continue;
+            }
+            removed.push(self.rules.remove(i));
+            self.prefilter.remove(i);
+            self.index.remove(i);
+            self.selections.remove(i);
+            self.disabled.remove(i);
+            self.evaluation.retain(|rule| *rule != i);
+            self.evaluation
+                .iter_mut()
+                .filter(|rule| **rule > i)
+                .for_each(|rule| *rule -= 1);
+        }
+        removed.reverse();
+        removed
+    }
+
+    /// Enable or disable the rules with the given id, or title for rules without an id.
+    /// Disabled rules are skipped by all evaluation methods and never match until they are
+    /// enabled again. Returns whether the rule set contains such a rule.
+    ///
+    /// # Example
+    /// ```rust
+    /// use sigma_rust::{rule_from_yaml, Event, RuleSet};
+    ///
+    /// let rule = rule_from_yaml(
+    ///     r#"
+    /// title: Noisy
+    /// id: 5f2c
+    /// logsource:
+    /// detection:
+    ///     selection:
+    ///         Image|endswith: '.exe'
+    ///     condition: selection
+    /// "#,
+    /// )
+    /// .unwrap();
+    ///
+    /// let mut rule_set = RuleSet::new(vec![rule]);
+    /// let event = Event::from([("Image", "C:\\cmd.exe")]);
+    /// assert!(rule_set.set_enabled("5f2c", false));
+    /// assert!(rule_set.evaluate(&event).is_empty());
+    /// assert_eq!(rule_set.is_enabled("5f2c"), Some(false));
+    /// ```
+    pub fn set_enabled(&mut self, rule_id: &str, enabled: bool) -> bool {
+        let mut found = false;
+        for (rule, disabled) in self.rules.iter().zip(self.disabled.iter_mut()) {
+            if rule_key(rule) == rule_id {
+                *disabled = !enabled;
+                found = true;
+            }
+        }
+        found
+    }
+
+    /// Whether the rule with the given id, or title for rules without an id, is enabled.
+    /// Returns `None` if the rule set contains no such rule.
+    pub fn is_enabled(&self, rule_id: &str) -> Option<bool> {
+        self.rules
+            .iter()
+            .zip(&self.disabled)
+            .find(|(rule, _)| rule_key(rule) == rule_id)
+            .map(|(_, disabled)| !disabled)
+    }
+
+    /// The number of rules that are disabled, see [`RuleSet::set_enabled`]
+    pub fn disabled_len(&self) -> usize {
+        self.disabled.iter().filter(|disabled| **disabled).count()
+    }
+
+    /// Whether each rule is disabled, in load order
+    #[cfg(feature = "snapshot")]
+    pub(crate) fn disabled(&self) -> &[bool] {
+        &self.disabled
+    }
+
+    /// Disable the rules by their position in load order, e.g. when restoring a snapshot
+    #[cfg(feature = "snapshot")]
+    pub(crate) fn with_disabled(mut self, disabled: Vec<bool>) -> Self {
+        if disabled.len() == self.rules.len() {
+            self.disabled = disabled;
+        }
+        self
+    }
+
+    /// Evaluate the candidate rules in evaluation order until the match limit is reached and
+    /// return the matching rules in load order
+    fn matches_in<'a>(&'a self, ctx: &EvalContext, candidates: &[bool]) -> Vec<&'a Rule> {
+        self.matches_until(ctx, candidates, || false).0
+    }
+
+    /// Like [`RuleSet::matches_in`] but stops before the next rule once `expired` returns
+    /// true. Returns whether rules were left unevaluated because of it.
+    fn matches_until<'a>(
+        &'a self,
+        ctx: &EvalContext,
+        candidates: &[bool],
+        expired: impl Fn() -> bool,
+    ) -> (Vec<&'a Rule>, bool) {
+        let mut matched = vec![];
+        let mut exceeded = false;
+        for &i in &self.evaluation {
+            if self.match_limit == Some(matched.len()) {
+                break;
+            }
+            if let (Some(true), Some(rule)) = (candidates.get(i), self.rules.get(i)) {
+                if expired() {
+                    exceeded = true;
+                    break;
+                }
+                if rule.is_match_in(ctx) {
+                    matched.push(i);
+                }
+            }
+        }
+        matched.sort_unstable();
+        let rules = matched.iter().filter_map(|i| self.rules.get(*i)).collect();
+        (rules, exceeded)
+    }
+
+    /// Sort the matches of a single event according to the match order
+    fn sort_matches(&self, matches: &mut [MatchResult]) {
+        if self.order == MatchOrder::Severity {
+            // The sort is stable, so matches of the same level stay in load order
+            matches.sort_by_key(|m| std::cmp::Reverse(m.level.map_or(0, |l| l as u8 + 1)));
+        }
+    }
+
+    /// The taxonomy used to match the logsources of events, see [`RuleSet::with_taxonomy`]
+    pub fn taxonomy(&self) -> &Taxonomy {
+        &self.taxonomy
+    }
+
+    /// The rules of the rule set
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// The number of rules in the rule set
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Iterate over the rules whose selections read a field of the event, see
+    /// [`Rule::referenced_fields`]. Disabled rules are included.
+    ///
+    /// # Example
+    /// ```rust
+    /// use sigma_rust::{rule_from_yaml, RuleSet};
+    ///
+    /// let rule = |title: &str, field: &str| {
+    ///     rule_from_yaml(&format!(
+    ///         "title: {title}\nlogsource:\ndetection:\n    selection:\n        {field}|contains: x\n    condition: selection"
+    ///     ))
+    ///     .unwrap()
+    /// };
+    /// let rule_set = RuleSet::new(vec![
+    ///     rule("Encoded", "CommandLine"),
+    ///     rule("Temp", "Image"),
+    ///     rule("Whoami", "CommandLine"),
+    /// ]);
+    ///
+    /// let titles: Vec<_> = rule_set
+    ///     .rules_referencing("CommandLine")
+    ///     .map(|rule| rule.title.as_str())
+    ///     .collect();
+    /// assert_eq!(titles, ["Encoded", "Whoami"]);
+    ///
+    /// // The fields to collect to support all rules, with the number of rules reading them
+    /// let fields = rule_set.referenced_fields();
+    /// assert_eq!(fields["CommandLine"], 2);
+    /// assert_eq!(fields["Image"], 1);
+    /// ```
+    pub fn rules_referencing<'a>(&'a self, field: &'a str) -> impl Iterator<Item = &'a Rule> {
+        self.rules.iter().filter(move |rule| {
+            rule.detection
+                .fields()
+                .any(|f| f.referenced_names().any(|name| name == field))
+        })
+    }
+
+    /// The names of all event fields read by the rules, with the number of rules reading each
+    /// of them. Events of a collector must contain these fields to support all rules; rules
+    /// with keyword selections, see [`RuleSet::keyword_rules`], read every field.
+    pub fn referenced_fields(&self) -> BTreeMap<String, usize> {
+        let mut result = BTreeMap::new();
+        for field in self.rules.iter().flat_map(Rule::referenced_fields) {
+            *result.entry(field).or_insert(0) += 1;
+        }
+        result
+    }
+
+    /// Iterate over the rules with keyword selections, which match against the values of all
+    /// fields of an event
+    pub fn keyword_rules(&self) -> impl Iterator<Item = &Rule> {
+        self.rules.iter().filter(|rule| rule.has_keywords())
+    }
+
+    /// The number of distinct selections that occur in more than one rule. Each of them is
+    /// evaluated at most once per event and its result shared by the rules.
+    pub fn shared_selections(&self) -> usize {
+        self.selections.shared()
+    }
+
+    /// A context to evaluate an event with room for the interned fields and shared selections
+    pub(crate) fn context<'a>(&self, event: &'a Event) -> EvalContext<'a> {
+        EvalContext::with_fields(event, self.interner.len()).with_selections(self.selections.len())
+    }
+
+    /// Iterate over all rules that match the event, in the order they were added regardless of
+    /// the match order. At most as many rules as the match limit are returned.
+    pub fn matching_rules<'a>(&'a self, event: &'a Event) -> impl Iterator<Item = &'a Rule> {
+        let ctx = self.context(event);
+        self.matches_in(&ctx, &self.candidates(&ctx)).into_iter()
+    }
+
+    /// Returns for every rule whether it passes the literal prefilter, the field index and
+    /// applies to the logsource of the event
+    fn candidates(&self, ctx: &EvalContext) -> Vec<bool> {
+        let mut scratch = Scratch::default();
+        self.candidates_into(ctx, ctx.event().logsource(), &mut scratch);
+        scratch.candidates
+    }
+
+    fn candidates_into(
+        &self,
+        ctx: &EvalContext,
+        logsource: Option<&Logsource>,
+        scratch: &mut Scratch,
+    ) {
+        self.prefilter.candidates_into(
+            ctx.event(),
+            &mut scratch.candidates,
+            &mut scratch.prefilter,
+        );
+        self.index.candidates_into(ctx, &mut scratch.indexed);
+        let filters = scratch.indexed.iter().zip(&self.disabled);
+        for (candidate, (indexed, disabled)) in scratch.candidates.iter_mut().zip(filters) {
+            *candidate &= indexed & !disabled;
+        }
+
+        let Some(logsource) = logsource else {
+            return;
+        };
+        // Consecutive events mostly share their logsource
+        if scratch.logsource.as_ref() != Some(logsource) {
+            scratch.applicable = self
+                .rules
+                .iter()
+                .map(|rule| self.taxonomy.matches(&rule.logsource, logsource))
+                .collect();
+            scratch.logsource = Some(logsource.clone());
+        }
+        for (candidate, applicable) in scratch.candidates.iter_mut().zip(&scratch.applicable) {
+            *candidate &= applicable;
+        }
+    }
+
+    /// Evaluate all rules against the event and return a result for every matching rule
+    pub fn evaluate(&self, event: &Event) -> Vec<MatchResult> {
+        #[cfg(feature = "parallel")]
+        if self.parallel && self.match_limit.is_none() {
+            return self.evaluate_par(event);
+        }
+        let mut result: Vec<MatchResult> = self
+            .matching_rules(event)
+            .map(|rule| MatchResult::new(rule, event))
+            .collect();
+        self.sort_matches(&mut result);
+        result
+    }
+
+    /// Like [`RuleSet::evaluate`] but stops once the time budget of the rule set has passed and
+    /// returns the matches found so far, see [`RuleSet::with_time_budget`]. This protects
+    /// inline pipelines from the tail latency of pathological events. Without a budget, all
+    /// rules are evaluated.
+    ///
+    /// # Example
+    /// ```rust
+    /// use sigma_rust::{rule_from_yaml, Event, RuleSet};
+    /// use std::time::Duration;
+    ///
+    /// let rule = rule_from_yaml(
+    ///     r#"
+    /// title: Whoami
+    /// logsource:
+    /// detection:
+    ///     selection:
+    ///         Image|endswith: '\whoami.exe'
+    ///     condition: selection
+    /// "#,
+    /// )
+    /// .unwrap();
+    ///
+    /// let rule_set = RuleSet::new(vec![rule]).with_time_budget(Duration::from_millis(5));
+    /// let result = rule_set.evaluate_budgeted(&Event::from([("Image", "C:\\whoami.exe")]));
+    /// if !result.budget_exceeded {
+    ///     assert_eq!(result.matches.len(), 1);
+    /// }
+    /// ```
+    pub fn evaluate_budgeted(&self, event: &Event) -> BudgetedMatches {
+        let ctx = self.context(event);
+        let candidates = self.candidates(&ctx);
+        let (rules, budget_exceeded) = match self.time_budget {
+            Some(budget) => self.matches_until(&ctx, &candidates, deadline(budget)),
+            None => (self.matches_in(&ctx, &candidates), false),
+        };
+        let mut matches: Vec<MatchResult> = rules
+            .into_iter()
+            .map(|rule| MatchResult::new(rule, event))
+            .collect();
+        self.sort_matches(&mut matches);
+        BudgetedMatches {
+            matches,
+            budget_exceeded,
+        }
+    }
+
+    /// Evaluate all rules against a batch of events. The result contains the matches of
+    /// each event at the same index as the event.
+    ///
+    /// This is equivalent to calling [`RuleSet::evaluate`] for every event, but the
+    /// buffers used for field lookups and prefiltering are allocated once for the whole batch.
+    pub fn evaluate_batch(&self, events: &[Event]) -> Vec<Vec<MatchResult>> {
+        #[cfg(feature = "parallel")]
+        if self.parallel {
+            return self.evaluate_par_batch(events);
+        }
+        self.evaluate_chunk(events)
+    }
+
+    /// Evaluate a batch of events sequentially, see [`RuleSet::evaluate_batch`]
+    fn evaluate_chunk(&self, events: &[Event]) -> Vec<Vec<MatchResult>> {
+        let Some(first) = events.first() else {
+            return vec![];
+        };
+        let mut ctx = self.context(first);
+        let mut scratch = Scratch::default();
+
+        let mut result = Vec::with_capacity(events.len());
+        for event in events {
+            ctx.reset(event);
+            self.candidates_into(&ctx, event.logsource(), &mut scratch);
+            let mut matches: Vec<MatchResult> = self
+                .matches_in(&ctx, &scratch.candidates)
+                .into_iter()
+                .map(|rule| MatchResult::new(rule, event))
+                .collect();
+            self.sort_matches(&mut matches);
+            result.push(matches);
+        }
+        result
+    }
+
+    /// Lazily evaluate all rules against a stream of events and return the matches of all
+    /// events in order. In contrast to [`RuleSet::evaluate_batch`], the events are pulled one
+    /// at a time, so the memory use does not depend on the length of the stream.
+    pub fn stream<I>(&self, events: I) -> MatchStream<'_, I::IntoIter>
+    where
+        I: IntoIterator<Item = Event>,
+    {
+        MatchStream::new(self, events.into_iter())
+    }
+
+    /// Like [`RuleSet::evaluate`] but records how often each rule and each of its selections
+    /// was evaluated and matched into `profile`, see [`crate::profile`]
+    pub fn evaluate_profiled(&self, event: &Event, profile: &mut Profile) -> Vec<MatchResult> {
+        let ctx = self.context(event);
+        let candidates = self.candidates(&ctx);
+        for (rule, _) in self.rules.iter().zip(&self.disabled).filter(|(_, d)| **d) {
+            profile.rule_mut(rule).disabled = true;
+        }
+        let mut result = vec![];
+        for &i in &self.evaluation {
+            if self.match_limit == Some(result.len()) {
+                break;
+            }
+            let (Some(true), Some(rule)) = (candidates.get(i), self.rules.get(i)) else {
+                continue;
+            };
+            let rule_profile = profile.rule_mut(rule);
+            let (matched, elapsed) = timed(|| {
+                rule.detection
+                    .evaluate_observed(&ctx, &mut |name, matched| {
+                        rule_profile.record_selection(name, matched)
+                    })
+            });
+            profile.record(rule, matched, elapsed);
+            if matched {
+                result.push((i, MatchResult::new(rule, event)));
+            }
+        }
+        result.sort_unstable_by_key(|(i, _)| *i);
+        let mut result: Vec<MatchResult> = result.into_iter().map(|(_, m)| m).collect();
+        self.sort_matches(&mut result);
+        result
+    }
+
+    /// Reorder the conditions of all rules according to a recorded profile, so the selection
+    /// that most likely decides an `and` or `or` is evaluated first.
+    /// Rules without statistics in the profile are left unchanged.
+    pub fn apply_profile(&mut self, profile: &Profile) {
+        for rule in self.rules.iter_mut() {
+            let Some(stats) = profile.rules.get(rule_key(rule)) else {
+                continue;
+            };
+            rule.detection.reorder(&|name| {
+                stats
+                    .selections
+                    .get(name)
+                    .and_then(|selection| selection.match_rate())
+            });
+        }
+    }
+}
+
+/// Buffers that are reused when evaluating many events
+#[derive(Debug, Default)]
+struct Scratch {
+    candidates: Vec<bool>,
+    indexed: Vec<bool>,
+    prefilter: prefilter::Scratch,
+    /// The logsource for which `applicable` was computed
+    logsource: Option<Logsource>,
+    /// Whether each rule applies to the logsource
+    applicable: Vec<bool>,
+}
+
+/// The number of events evaluated by a single task of [`RuleSet::evaluate_par_batch`]
+#[cfg(feature = "parallel")]
+const PAR_BATCH_SIZE: usize = 64;
+
+#[cfg(feature = "parallel")]
+impl RuleSet {
+    /// Make [`RuleSet::evaluate`] and [`RuleSet::evaluate_batch`] evaluate on the rayon thread
+    /// pool like [`RuleSet::evaluate_par`] and [`RuleSet::evaluate_par_batch`], e.g. when the
+    /// rule set is passed to code that only knows the sequential methods
+    pub fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Whether the rule set evaluates in parallel, see [`RuleSet::with_parallel`]
+    pub fn is_parallel(&self) -> bool {
+        self.parallel
+    }
+
+    /// Evaluate all rules against the event in parallel on the rayon thread pool.
+    /// The results are in the same order as returned by [`RuleSet::evaluate`].
+    /// With a match limit, the rules are evaluated sequentially, as their order matters.
+    pub fn evaluate_par(&self, event: &Event) -> Vec<MatchResult> {
+        if self.match_limit.is_some() {
+            return self.evaluate(event);
+        }
+        let candidates = self.candidates(&self.context(event));
+        let mut result: Vec<MatchResult> = self
+            .rules
+            .par_iter()
+            .zip(candidates.into_par_iter())
+            .filter(|(_, candidate)| *candidate)
+            .map_init(
+                || self.context(event),
+                |ctx, (rule, _)| rule.is_match_in(ctx).then(|| MatchResult::new(rule, event)),
+            )
+            .flatten()
+            .collect();
+        self.sort_matches(&mut result);
+        result
+    }
+
+    /// Evaluate a batch of events in parallel on the rayon thread pool.
+    /// The result contains the matches of each event at the same index as the event.
+    pub fn evaluate_par_batch(&self, events: &[Event]) -> Vec<Vec<MatchResult>> {
+        events
+            .par_chunks(PAR_BATCH_SIZE)
+            .flat_map_iter(|chunk| self.evaluate_chunk(chunk))
+            .collect()
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl RuleSet {
+    /// Evaluate all rules against the events of a JSON file and return the matches of all
+    /// events in order, see [`crate::input`]. Lines that are not valid events are skipped, see
+    /// [`FileEvents::errors`](crate::input::FileEvents::errors) of [`MatchStream::events`].
+    pub fn evaluate_file<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        format: crate::input::Format,
+    ) -> std::io::Result<MatchStream<'_, crate::input::FileEvents>> {
+        Ok(self.stream(crate::input::FileEvents::open(path, format)?))
+    }
+}
+
+#[cfg(feature = "async")]
+impl RuleSet {
+    /// Evaluate all rules against an asynchronous stream of events and return a stream of the
+    /// matches of all events in order, see [`AsyncMatchStream`]
+    pub fn stream_async<S>(&self, events: S) -> AsyncMatchStream<'_, S>
+    where
+        S: futures_core::Stream<Item = Event> + Unpin,
+    {
+        AsyncMatchStream::new(self, events)
+    }
+}
+
+/// Mark the rules of the given indices as candidates for an event
+fn mark(candidates: &mut [bool], rules: &[usize]) {
+    for rule in rules {
+        if let Some(candidate) = candidates.get_mut(*rule) {
+            *candidate = true;
+        }
+    }
+}
+
+impl FromIterator<Rule> for RuleSet {
+    fn from_iter<T: IntoIterator<Item = Rule>>(iter: T) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Level;
+    use crate::rule_from_yaml;
+
+    fn rule(title: &str, detection: &str) -> Rule {
+        rule_from_yaml(&format!(
+            "title: {}\nlogsource:\ndetection:\n{}",
+            title, detection
+        ))
+        .unwrap()
+    }
+
+    fn rule_set() -> RuleSet {
+        RuleSet::new(vec![
+            rule(
+                "contains",
+                "    selection:\n        CommandLine|contains: 'Invoke-Mimikatz'\n    condition: selection",
+            ),
+            rule(
+                "negated",
+                "    selection:\n        Image: 'cmd.exe'\n    condition: not selection",
+            ),
+            rule(
+                "regex",
+                "    selection:\n        CommandLine|re: 'who.mi'\n    condition: selection",
+            ),
+            rule(
+                "keywords",
+                "    keywords:\n        - 'evil'\n        - 'bad*thing'\n    condition: keywords",
+            ),
+            rule(
+                "nested",
+                "    selection:\n        Parent.Image|endswith: '\\\\powershell.exe'\n        Parent.Pid: 4\n    condition: selection",
+            ),
+        ])
+    }
+
+    fn titles(rule_set: &RuleSet, event: &Event) -> Vec<String> {
+        rule_set
+            .evaluate(event)
+            .into_iter()
+            .map(|r| r.rule_title)
+            .collect()
+    }
+
+    #[test]
+    fn test_prefilter_candidates() {
+        let rule_set = rule_set();
+        let event = Event::from([("Image", "cmd.exe")]);
+        assert_eq!(
+            rule_set.prefilter.candidates(&event),
+            vec![false, true, true, false, false]
+        );
+
+        let event = Event::from([("CommandLine", "powershell INVOKE-MIMIKATZ")]);
+        assert_eq!(
+            rule_set.prefilter.candidates(&event),
+            vec![true, true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn test_index_candidates() {
+        let rule_set = rule_set();
+        let candidates = |event: &Event| {
+            let ctx = rule_set.context(event);
+            rule_set.index.candidates(&ctx)
+        };
+
+        assert_eq!(
+            candidates(&Event::from([("CommandLine", "x")])),
+            vec![true, true, true, true, false]
+        );
+        assert_eq!(
+            candidates(&Event::from([("Image", "x")])),
+            vec![false, true, false, true, false]
+        );
+        assert_eq!(
+            candidates(&Event::from([("Parent", "x")])),
+            vec![false, true, false, true, false]
+        );
+    }
+
+    #[test]
+    fn test_interned_fields() {
+        let rule_set = rule_set();
+        // CommandLine, Image, Parent.Image and Parent.Pid
+        assert_eq!(rule_set.interner.len(), 4);
+
+        let mut ids = vec![];
+        for rule in rule_set.rules() {
+            for selection in rule.detection.get_selections().values() {
+                if let crate::selection::Selection::Field(groups) = selection {
+                    for field in groups.iter().flat_map(|g| g.fields.iter()) {
+                        ids.push((field.name.as_str(), field.id.unwrap()));
+                    }
+                }
+            }
+        }
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 4);
+    }
+
+    #[test]
+    fn test_referenced_fields() {
+        let rule_set = RuleSet::new(vec![
+            rule(
+                "renamed",
+                "    selection:\n        Image|endswith: '\\cmd.exe'\n        OriginalFileName|fieldref: Image\n    condition: selection",
+            ),
+            rule(
+                "nested",
+                "    selection:\n        Event.ID: 4688\n    condition: selection",
+            ),
+            rule(
+                "keywords",
+                "    keywords:\n        - mimikatz\n    selection:\n        Image|exists: true\n    condition: keywords or selection",
+            ),
+        ]);
+        let referencing = |field| {
+            rule_set
+                .rules_referencing(field)
+                .map(|rule| rule.title.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(referencing("Image"), vec!["renamed", "keywords"]);
+        assert_eq!(referencing("Event.ID"), vec!["nested"]);
+        assert!(referencing("Event").is_empty());
+
+        let fields: Vec<_> = rule_set.referenced_fields().into_iter().collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("Event.ID".to_string(), 1),
+                ("Image".to_string(), 2),
+                ("OriginalFileName".to_string(), 1)
+            ]
+        );
+        let keyword_rules: Vec<_> = rule_set.keyword_rules().map(|r| &r.title).collect();
+        assert_eq!(keyword_rules, vec!["keywords"]);
+    }
+
+    #[test]
+    fn test_shared_selections() {
+        let filter = "    filter:\n        User|startswith: 'svc_'\n";
+        let cased = filter.replace("|startswith", "|startswith|cased");
+        let filtered = |image: &str| {
+            format!(
+                "    selection:\n        Image|endswith: '{}'\n{}    condition: selection and not filter",
+                image, filter
+            )
+        };
+        let rules = vec![
+            rule("whoami", &filtered("whoami.exe")),
+            rule("net", &filtered("net.exe")),
+            // Equal to the filter but with a different name
+            rule(
+                "service",
+                "    service:\n        User|startswith: 'svc_'\n    condition: service",
+            ),
+            // Written with `cased`, but compiled case-insensitively like the filter
+            crate::EngineConfig::builder()
+                .field_policy("User", crate::FieldPolicy::CaseInsensitive)
+                .build()
+                .rule_from_yaml(&format!(
+                    "title: policy\nlogsource:\ndetection:\n{}    condition: filter",
+                    cased
+                ))
+                .unwrap(),
+            rule("cased", &format!("{}    condition: filter", cased)),
+        ];
+        let rule_set = RuleSet::new(rules);
+        assert_eq!(rule_set.shared_selections(), 1);
+
+        let event = Event::from([("Image", "C:\\whoami.exe"), ("User", "SVC_backup")]);
+        assert_eq!(titles(&rule_set, &event), vec!["service", "policy"]);
+        let event = Event::from([("Image", "C:\\net.exe"), ("User", "admin")]);
+        assert_eq!(titles(&rule_set, &event), vec!["net"]);
+
+        // The result of the shared selection is remembered for the rest of the event
+        let ctx = rule_set.context(&event);
+        assert!(!rule_set.rules[2].is_match_in(&ctx));
+        assert!(!ctx.selection(Some(0), || true));
+    }
+
+    #[test]
+    fn test_shared_selections_case_folding() {
+        let detection = "detection:\n    selection:\n        Image|contains: 'straße'\n    condition: not selection";
+        let compile = |title: &str, folding| {
+            crate::EngineConfig::builder()
+                .case_folding(folding)
+                .build()
+                .rule_from_yaml(&format!("title: {}\nlogsource:\n{}", title, detection))
+                .unwrap()
+        };
+        let full = || compile("full", crate::CaseFolding::Full);
+        let simple = || compile("simple", crate::CaseFolding::Simple);
+
+        // Only full case folding matches `ß` with `SS`, so the selections are not shared and
+        // the result does not depend on the order of the rules
+        let event = Event::from([("Image", "C:\\STRASSE.exe")]);
+        for rules in [vec![full(), simple()], vec![simple(), full()]] {
+            let rule_set = RuleSet::new(rules);
+            assert_eq!(rule_set.shared_selections(), 0);
+            assert_eq!(titles(&rule_set, &event), vec!["simple"]);
+        }
+    }
+
+    #[test]
+    fn test_shared_selections_moved_between_rule_sets() {
+        let user = "    selection:\n        User: admin\n    condition: not selection";
+        let image =
+            "    selection:\n        Image|endswith: 'whoami.exe'\n    condition: selection";
+        let mut rule_set = RuleSet::new(vec![
+            rule("user 1", user),
+            rule("user 2", user),
+            rule("image 1", image),
+            rule("image 2", image),
+        ]);
+        assert_eq!(rule_set.shared_selections(), 2);
+
+        // The moved rules share a different selection in the new rule set, which must not
+        // reuse the result of the selection that had its id in the old one
+        let moved: Vec<Rule> = ["user 1", "image 1", "image 2"]
+            .iter()
+            .flat_map(|title| rule_set.remove(title))
+            .collect();
+        let event = Event::from([("User", "root"), ("Image", "C:\\whoami.exe")]);
+        let expected = vec!["user 1", "image 1", "image 2"];
+        assert_eq!(titles(&RuleSet::new(moved.clone()), &event), expected);
+
+        let mut inserted = RuleSet::default();
+        for rule in moved {
+            inserted.insert(rule);
+        }
+        assert_eq!(titles(&inserted, &event), expected);
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let events = [
+            Event::from([("CommandLine", "invoke-mimikatz whoami"), ("Image", "x")]),
+            Event::from([("Image", "cmd.exe"), ("User", "svc_backup")]),
+            Event::from([("Keyword", "EVIL"), ("Image", "net.exe")]),
+            Event::from([("Image", "C:\\net.exe"), ("User", "admin")]),
+        ];
+        let added = |n: usize| {
+            rule(
+                &format!("added {}", n),
+                &format!(
+                    "    selection:\n        Image|endswith: 'net.exe'\n        CommandLine|contains: '<{}>'\n    filter:\n        User|startswith: 'svc_'\n    condition: selection and not filter",
+                    n
+                ),
+            )
+        };
+        let assert_equivalent = |rule_set: &RuleSet| {
+            let rebuilt = RuleSet::new(rule_set.rules().to_vec())
+                .with_evaluation_order(rule_set.evaluation_order());
+            assert_eq!(rule_set.shared_selections(), rebuilt.shared_selections());
+            for event in events.iter() {
+                assert_eq!(rule_set.evaluate(event), rebuilt.evaluate(event));
+                assert_eq!(
+                    rule_set.evaluate_batch(std::slice::from_ref(event)),
+                    vec![rebuilt.evaluate(event)]
+                );
+            }
+        };
+
+        let mut rule_set = rule_set().with_evaluation_order(EvaluationOrder::Priority);
+        rule_set.insert(added(0));
+        rule_set.insert(rule(
+            "net",
+            "    selection:\n        Image|endswith: 'net.exe'\n    condition: selection",
+        ));
+        rule_set.insert(added(1));
+        assert_eq!(rule_set.len(), 8);
+        assert_eq!(rule_set.shared_selections(), 1);
+        assert_eq!(titles(&rule_set, &events[3]), vec!["negated", "net"]);
+        assert_equivalent(&rule_set);
+
+        assert!(rule_set.remove("missing").is_empty());
+        assert_eq!(rule_set.remove("contains")[0].title, "contains");
+        assert_eq!(rule_set.remove("added 0").len(), 1);
+        assert_eq!(rule_set.len(), 6);
+        assert_eq!(titles(&rule_set, &events[0]), vec!["negated", "regex"]);
+        assert_equivalent(&rule_set);
+
+        // Added rules beyond the limit of the separate prefilter are merged into one
+        rule_set.set_enabled("net", false);
+        for n in 2..300 {
+            rule_set.insert(added(n));
+        }
+        assert!(rule_set.prefilter.candidates(&events[2]).iter().any(|c| !c));
+        assert_eq!(rule_set.is_enabled("net"), Some(false));
+        rule_set.set_enabled("net", true);
+        assert_equivalent(
+            &rule_set
+                .clone()
+                .with_evaluation_order(EvaluationOrder::LoadOrder),
+        );
+        let event = Event::from([("Image", "net.exe"), ("CommandLine", "<299>")]);
+        assert_eq!(
+            titles(&rule_set, &event),
+            vec!["negated", "net", "added 299"]
+        );
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let rule_set = rule_set();
+        assert_eq!(rule_set.len(), 5);
+
+        let event = Event::from([("Image", "cmd.exe")]);
+        assert!(titles(&rule_set, &event).is_empty());
+
+        let event = Event::from([("CommandLine", "powershell INVOKE-MIMIKATZ")]);
+        assert_eq!(titles(&rule_set, &event), vec!["contains", "negated"]);
+
+        let event = Event::from([("CommandLine", "whoami"), ("Image", "cmd.exe")]);
+        assert_eq!(titles(&rule_set, &event), vec!["regex"]);
+
+        let event = Event::from([("Image", "cmd.exe"), ("Other", "bad old thing")]);
+        assert_eq!(titles(&rule_set, &event), vec!["keywords"]);
+    }
+
+    #[test]
+    fn test_evaluate_batch() {
+        let rule_set = rule_set();
+        let events = vec![
+            Event::from([("CommandLine", "invoke-mimikatz whoami"), ("Image", "x")]),
+            Event::from([("Image", "cmd.exe")]),
+            Event::from([("CommandLine", "WHOAMI"), ("Image", "cmd.exe")]),
+            Event::from([("Keyword", "EVIL"), ("Image", "cmd.exe")]),
+            Event::new(),
+        ];
+
+        let batch = rule_set.evaluate_batch(&events);
+        assert_eq!(batch.len(), events.len());
+        for (event, results) in events.iter().zip(batch) {
+            assert_eq!(results, rule_set.evaluate(event));
+        }
+        assert!(rule_set.evaluate_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_match_order() {
+        let levels = [
+            None,
+            Some(Level::Low),
+            Some(Level::Critical),
+            Some(Level::Low),
+        ];
+        let rules = levels
+            .iter()
+            .enumerate()
+            .map(|(i, level)| {
+                let mut rule = rule(
+                    &i.to_string(),
+                    "    selection:\n        Image|endswith: '.exe'\n    condition: selection",
+                );
+                rule.level = *level;
+                rule
+            })
+            .collect();
+        let event = Event::from([("Image", "cmd.exe")]);
+
+        let rule_set = RuleSet::new(rules);
+        assert_eq!(rule_set.match_order(), MatchOrder::LoadOrder);
+        assert_eq!(titles(&rule_set, &event), vec!["0", "1", "2", "3"]);
+
+        let rule_set = rule_set.with_match_order(MatchOrder::Severity);
+        let expected = vec!["2", "1", "3", "0"];
+        assert_eq!(titles(&rule_set, &event), expected);
+        let titles = |matches: Vec<MatchResult>| -> Vec<String> {
+            matches.into_iter().map(|r| r.rule_title).collect()
+        };
+        assert_eq!(
+            titles(
+                rule_set
+                    .evaluate_batch(std::slice::from_ref(&event))
+                    .remove(0)
+            ),
+            expected
+        );
+        assert_eq!(titles(rule_set.stream([event.clone()]).collect()), expected);
+        assert_eq!(
+            titles(rule_set.evaluate_profiled(&event, &mut Profile::new())),
+            expected
+        );
+        #[cfg(feature = "parallel")]
+        assert_eq!(titles(rule_set.evaluate_par(&event)), expected);
+    }
+
+    #[test]
+    fn test_match_limit() {
+        let rules = [
+            ("low", "level: low\n"),
+            ("explicit", "level: low\npriority: 10\n"),
+            ("none", ""),
+            ("critical", "level: critical\n"),
+        ]
+        .into_iter()
+        .map(|(title, attributes)| {
+            rule_from_yaml(&format!(
+                "title: {}\n{}logsource:\ndetection:\n    selection:\n        Image|endswith: '.exe'\n    condition: selection",
+                title, attributes
+            ))
+            .unwrap()
+        })
+        .collect::<Vec<Rule>>();
+        assert_eq!(
+            rules.iter().map(Rule::priority).collect::<Vec<i64>>(),
+            vec![2, 10, 0, 5]
+        );
+        let event = Event::from([("Image", "cmd.exe")]);
+
+        // Without a limit, the evaluation order does not change the matches
+        let rule_set = RuleSet::new(rules.clone()).with_evaluation_order(EvaluationOrder::Priority);
+        assert_eq!(rule_set.match_limit(), None);
+        assert_eq!(
+            titles(&rule_set, &event),
+            vec!["low", "explicit", "none", "critical"]
+        );
+
+        let rule_set = rule_set.with_match_limit(2);
+        assert_eq!(rule_set.evaluation_order(), EvaluationOrder::Priority);
+        // The matches are still returned in load order
+        assert_eq!(titles(&rule_set, &event), vec!["explicit", "critical"]);
+        assert_eq!(rule_set.matching_rules(&event).count(), 2);
+        assert_eq!(rule_set.stream([event.clone()]).count(), 2);
+        assert_eq!(
+            rule_set.evaluate_profiled(&event, &mut Profile::new()),
+            rule_set.evaluate(&event)
+        );
+        #[cfg(feature = "parallel")]
+        assert_eq!(rule_set.evaluate_par(&event), rule_set.evaluate(&event));
+
+        let rule_set = RuleSet::new(rules).with_match_limit(0);
+        assert_eq!(rule_set.match_limit(), Some(1));
+        assert_eq!(titles(&rule_set, &event), vec!["low"]);
+        assert!(titles(&rule_set, &Event::from([("Image", "cmd.dll")])).is_empty());
+    }
+
+    #[test]
+    fn test_time_budget() {
+        let rule_set = rule_set();
+        let event = Event::from([("CommandLine", "invoke-mimikatz whoami"), ("Image", "x")]);
+        let unlimited = rule_set.evaluate_budgeted(&event);
+        assert_eq!(rule_set.time_budget(), None);
+        assert!(!unlimited.budget_exceeded);
+        assert_eq!(unlimited.matches, rule_set.evaluate(&event));
+
+        let rule_set = rule_set.with_time_budget(Duration::ZERO);
+        let exceeded = rule_set.evaluate_budgeted(&event);
+        assert!(exceeded.budget_exceeded && exceeded.matches.is_empty());
+        // Events without candidate rules never exceed the budget
+        assert!(
+            !RuleSet::new(vec![])
+                .with_time_budget(Duration::ZERO)
+                .evaluate_budgeted(&event)
+                .budget_exceeded
+        );
+        assert!(
+            !rule_set
+                .with_time_budget(Duration::from_secs(60))
+                .evaluate_budgeted(&event)
+                .budget_exceeded
+        );
+
+        // The budget expires after the first two candidates
+        let rule_set = self::rule_set();
+        let ctx = rule_set.context(&event);
+        let candidates = rule_set.candidates(&ctx);
+        let checks = std::cell::Cell::new(0);
+        let (rules, exceeded) = rule_set.matches_until(&ctx, &candidates, || {
+            checks.set(checks.get() + 1);
+            checks.get() > 2
+        });
+        assert!(exceeded);
+        let titles: Vec<&str> = rules.iter().map(|rule| rule.title.as_str()).collect();
+        assert_eq!(titles, vec!["contains", "negated"]);
+    }
+
+    #[test]
+    fn test_set_enabled() {
+        let mut rule_set = rule_set();
+        let event = Event::from([("CommandLine", "powershell INVOKE-MIMIKATZ")]);
+        assert_eq!(rule_set.disabled_len(), 0);
+        assert!(!rule_set.set_enabled("unknown", false));
+        assert_eq!(rule_set.is_enabled("unknown"), None);
+
+        assert!(rule_set.set_enabled("contains", false));
+        assert_eq!(rule_set.is_enabled("contains"), Some(false));
+        assert_eq!(rule_set.disabled_len(), 1);
+        assert_eq!(titles(&rule_set, &event), vec!["negated"]);
+        assert_eq!(rule_set.stream([event.clone()]).count(), 1);
+
+        let mut profile = Profile::new();
+        assert_eq!(rule_set.evaluate_profiled(&event, &mut profile).len(), 1);
+        assert!(profile.rules["contains"].disabled);
+        assert_eq!(profile.rules["contains"].evaluations, 0);
+
+        assert!(rule_set.set_enabled("contains", true));
+        assert_eq!(rule_set.disabled_len(), 0);
+        assert_eq!(titles(&rule_set, &event), vec!["contains", "negated"]);
+        rule_set.evaluate_profiled(&event, &mut profile);
+        assert!(!profile.rules["contains"].disabled);
+    }
+
+    #[test]
+    fn test_shared_between_threads() {
+        let rule_set = std::sync::Arc::new(rule_set());
+        let event = Event::from([("CommandLine", "powershell INVOKE-MIMIKATZ")]);
+        let expected = rule_set.evaluate(&event);
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let rule_set = std::sync::Arc::clone(&rule_set);
+                let event = event.clone();
+                std::thread::spawn(move || rule_set.evaluate(&event))
+            })
+            .collect();
+        for worker in workers {
+            assert_eq!(worker.join().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_profile() {
+        let mut rule_set = RuleSet::new(vec![rule(
+            "profiled",
+            "    common:\n        Image|endswith: '.exe'\n    rare:\n        CommandLine|re: 'mimi.atz'\n    condition: common and rare",
+        )]);
+        let events = [
+            Event::from([("Image", "cmd.exe"), ("CommandLine", "whoami")]),
+            Event::from([("Image", "cmd.exe"), ("CommandLine", "mimikatz")]),
+            Event::from([("Image", "cmd.exe"), ("CommandLine", "mimikatz.exe")]),
+            Event::from([("Image", "x.exe"), ("CommandLine", "x.exe")]),
+            Event::from([("Image", "x.dll"), ("CommandLine", "x.dll")]),
+        ];
+
+        let mut profile = Profile::new();
+        for event in events.iter() {
+            rule_set.evaluate_profiled(event, &mut profile);
+        }
+        let stats = &profile.rules["profiled"];
+        // The last event is skipped by the prefilter
+        assert_eq!(stats.evaluations, 4);
+        assert_eq!(stats.matches, 2);
+        assert_eq!(stats.selections["common"].evaluations, 4);
+        assert_eq!(stats.selections["common"].matches, 4);
+        assert_eq!(stats.selections["rare"].evaluations, 4);
+
+        rule_set.apply_profile(&profile);
+        let mut reordered = Profile::new();
+        for event in events.iter() {
+            assert_eq!(
+                rule_set.evaluate_profiled(event, &mut reordered),
+                rule_set.evaluate(event)
+            );
+        }
+        let stats = &reordered.rules["profiled"];
+        assert_eq!(stats.matches, 2);
+        // The rare selection is evaluated first and short-circuits the condition
+        assert_eq!(stats.selections["rare"].evaluations, 4);
+        assert_eq!(stats.selections["common"].evaluations, 2);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_evaluate_par() {
+        let rule_set = rule_set();
+        let events = vec![
+            Event::from([("CommandLine", "invoke-mimikatz whoami"), ("Image", "x")]),
+            Event::from([("CommandLine", "WHOAMI"), ("Image", "cmd.exe")]),
+            Event::from([("Keyword", "EVIL"), ("Image", "cmd.exe")]),
+            Event::from([("Image", "cmd.exe")]),
+        ];
+
+        for event in events.iter() {
+            assert_eq!(rule_set.evaluate_par(event), rule_set.evaluate(event));
+        }
+
+        let batch = rule_set.evaluate_par_batch(&events);
+        assert_eq!(batch.len(), events.len());
+        for (event, results) in events.iter().zip(batch) {
+            assert_eq!(results, rule_set.evaluate(event));
+        }
+        assert_eq!(
+            rule_set
+                .evaluate_par(&events[0])
+                .into_iter()
+                .map(|r| r.rule_title)
+                .collect::<Vec<String>>(),
+            vec!["contains", "negated", "regex"]
+        );
+
+        let parallel = rule_set.clone().with_parallel(true);
+        assert!(parallel.is_parallel() && !rule_set.is_parallel());
+        assert_eq!(
+            parallel.evaluate_batch(&events),
+            rule_set.evaluate_batch(&events)
+        );
+        for event in events.iter() {
+            assert_eq!(parallel.evaluate(event), rule_set.evaluate(event));
+        }
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_evaluate_nested() {
+        let rule_set = rule_set();
+        let event: Event = crate::event_from_json(
+            r#"{"Image": "cmd.exe", "Parent": {"Image": "C:\\PowerShell.exe", "Pid": 4}}"#,
+        )
+        .unwrap();
+        assert_eq!(titles(&rule_set, &event), vec!["nested"]);
+    }
+
+    #[test]
+    fn test_prefilter_is_consistent() {
+        let rule_set = rule_set();
+        let events = [
+            Event::from([("CommandLine", "invoke-mimikatz"), ("Image", "x")]),
+            Event::from([("CommandLine", "WHOAMI"), ("Image", "cmd.exe")]),
+            Event::from([("Keyword", "EVIL"), ("Image", "cmd.exe")]),
+            Event::from([("Image", "cmd.exe")]),
+            Event::new(),
+        ];
+
+        for event in events.iter() {
+            let expected: Vec<&str> = rule_set
+                .rules()
+                .iter()
+                .filter(|r| r.is_match(event))
+                .map(|r| r.title.as_str())
+                .collect();
+            assert_eq!(titles(&rule_set, event), expected);
+        }
+    }
+
+    #[test]
+    fn test_logsource() {
+        let rule = |title: &str, logsource: &str| {
+            rule_from_yaml(&format!(
+                "title: {}\nlogsource:\n{}\ndetection:\n    selection:\n        Image|exists: true\n    condition: selection",
+                title, logsource
+            ))
+            .unwrap()
+        };
+        let rule_set = RuleSet::new(vec![
+            rule(
+                "windows",
+                "    product: windows\n    category: process_creation",
+            ),
+            rule("linux", "    product: linux"),
+            rule("any", ""),
+        ]);
+        let event = Event::from([("Image", "x")]);
+        assert_eq!(titles(&rule_set, &event), vec!["windows", "linux", "any"]);
+
+        let windows = event.clone().with_logsource(Logsource::new(
+            Some("process_creation"),
+            Some("Windows"),
+            Some("sysmon"),
+        ));
+        let linux = event.with_logsource(Logsource::new(None, Some("linux"), None));
+        assert_eq!(titles(&rule_set, &windows), vec!["windows", "any"]);
+        assert_eq!(titles(&rule_set, &linux), vec!["linux", "any"]);
+        assert_eq!(
+            rule_set.evaluate_batch(&[windows.clone(), linux.clone(), windows.clone()]),
+            vec![
+                rule_set.evaluate(&windows),
+                rule_set.evaluate(&linux),
+                rule_set.evaluate(&windows)
+            ]
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use crate::context::ContextWindow;
+use crate::enrich::{Annotator, Annotators, Enricher, Enrichers};
+use crate::event::Event;
+use crate::result::MatchResult;
+use crate::rule::Logsource;
+use crate::ruleset::{RuleSet, Scratch};
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The default number of rules evaluated before [`AsyncMatchStream`] yields to the runtime
+pub const DEFAULT_YIELD_BUDGET: usize = 1024;
+
+/// The asynchronous counterpart of [`crate::MatchStream`], created by [`RuleSet::stream_async`].
+///
+/// Events are polled from the underlying stream one at a time. Evaluating an event against a
+/// large rule set can take a while, so after evaluating a budget of rules the stream yields to
+/// the runtime and continues with the next poll. This keeps a single task from blocking the
+/// other tasks of the executor thread, e.g. of a tokio-based collector.
+///
+/// # Example
+/// ```rust
+/// use futures::executor::block_on;
+/// use futures::stream::{self, StreamExt};
+/// use sigma_rust::{rule_from_yaml, Event, RuleSet};
+///
+/// let rule = rule_from_yaml(
+///     r#"
+/// title: Whoami
+/// logsource:
+///     category: process_creation
+/// detection:
+///     selection:
+///         Image|endswith: '\whoami.exe'
+///     condition: selection
+/// "#,
+/// )
+/// .unwrap();
+///
+/// let rule_set = RuleSet::new(vec![rule]);
+/// let events = stream::iter([
+///     Event::from([("Image", "C:\\explorer.exe")]),
+///     Event::from([("Image", "C:\\whoami.exe")]),
+/// ]);
+/// let matches: Vec<_> = block_on(rule_set.stream_async(events).collect());
+/// assert_eq!(matches.len(), 1);
+/// assert_eq!(matches[0].rule_title, "Whoami");
+/// ```
+#[derive(Debug)]
+pub struct AsyncMatchStream<'r, S> {
+    rule_set: &'r RuleSet,
+    events: S,
+    scratch: Scratch,
+    /// Matches of completed events that were not yet returned
+    pending: VecDeque<MatchResult>,
+    /// The event being evaluated, the position of the next rule in evaluation order and the
+    /// indices of the matching rules so far
+    current: Option<(Event, usize, Vec<usize>)>,
+    context: Option<ContextWindow>,
+    /// The logsource of events that do not carry their own
+    logsource: Option<Logsource>,
+    /// Run on every event before it is matched
+    enrichers: Enrichers,
+    /// Run on every match before it is returned
+    annotators: Annotators,
+    budget: usize,
+}
+
+impl<'r, S: Stream<Item = Event> + Unpin> AsyncMatchStream<'r, S> {
+    pub(crate) fn new(rule_set: &'r RuleSet, events: S) -> Self {
+        Self {
+            rule_set,
+            events,
+            scratch: Scratch::default(),
+            pending: VecDeque::new(),
+            current: None,
+            context: None,
+            logsource: None,
+            enrichers: Enrichers::new(),
+            annotators: Annotators::new(),
+            budget: DEFAULT_YIELD_BUDGET,
+        }
+    }
+
+    /// Attach the preceding events of the same entity to every match, see [`ContextWindow`]
+    pub fn with_context(mut self, window: ContextWindow) -> Self {
+        self.context = Some(window);
+        self
+    }
+
+    /// Treat all events of the stream that do not carry a logsource as collected from
+    /// `logsource`, see [`crate::MatchStream::with_logsource`]
+    pub fn with_logsource(mut self, logsource: Logsource) -> Self {
+        self.logsource = Some(logsource);
+        self
+    }
+
+    /// Run `enricher` on every event before it is matched, see
+    /// [`crate::MatchStream::with_enricher`]
+    pub fn with_enricher<E: Enricher + 'static>(mut self, enricher: E) -> Self {
+        self.enrichers.push(enricher);
+        self
+    }
+
+    /// Run `annotator` on every match before it is returned, see
+    /// [`crate::MatchStream::with_annotator`]
+    pub fn with_annotator<A: Annotator + 'static>(mut self, annotator: A) -> Self {
+        self.annotators.push(annotator);
+        self
+    }
+
+    /// Yield to the runtime after evaluating `budget` rules, [`DEFAULT_YIELD_BUDGET`] by
+    /// default. Smaller budgets reduce the latency of other tasks at the cost of throughput.
+    pub fn with_yield_budget(mut self, budget: usize) -> Self {
+        self.budget = budget.max(1);
+        self
+    }
+
+    /// The context window, if one was attached with [`AsyncMatchStream::with_context`]
+    pub fn context(&self) -> Option<&ContextWindow> {
+        self.context.as_ref()
+    }
+
+    fn start(&mut self, event: Event) {
+        let ctx = self.rule_set.context(&event);
+        let logsource = event.logsource().or(self.logsource.as_ref());
+        self.rule_set
+            .candidates_into(&ctx, logsource, &mut self.scratch);
+        self.current = Some((event, 0, vec![]));
+    }
+
+    /// Evaluate the rules of the current event until it is complete or the budget is spent
+    fn resume(&mut self, budget: &mut usize) {
+        let Some((event, next, matches)) = &mut self.current else {
+            return;
+        };
+        let rule_set = self.rule_set;
+        let ctx = rule_set.context(event);
+        for &i in rule_set.evaluation.iter().skip(*next) {
+            if rule_set.match_limit == Some(matches.len()) {
+                break;
+            }
+            if let (Some(true), Some(rule)) =
+                (self.scratch.candidates.get(i), rule_set.rules.get(i))
+            {
+                if *budget == 0 {
+                    return;
+                }
+                *budget -= 1;
+                if rule.is_match_in(&ctx) {
+                    matches.push(i);
+                }
+            }
+            *next += 1;
+        }
+
+        if let Some((event, _, mut indices)) = self.current.take() {
+            indices.sort_unstable();
+            let mut matches: Vec<MatchResult> = indices
+                .into_iter()
+                .filter_map(|i| rule_set.rules.get(i))
+                .map(|rule| MatchResult::new(rule, &event))
+                .collect();
+            rule_set.sort_matches(&mut matches);
+            if let Some(window) = &mut self.context {
+                window.process(&event, &mut matches);
+            }
+            for result in &mut matches {
+                self.annotators.annotate(result);
+            }
+            self.pending.extend(matches);
+        }
+    }
+}
+
+impl<S: Stream<Item = Event> + Unpin> Stream for AsyncMatchStream<'_, S> {
+    type Item = MatchResult;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let mut budget = this.budget;
+        loop {
+            if let Some(result) = this.pending.pop_front() {
+                return Poll::Ready(Some(result));
+            }
+            if budget == 0 {
+                // Let the runtime schedule other tasks before continuing
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            if this.current.is_none() {
+                match Pin::new(&mut this.events).poll_next(cx) {
+                    Poll::Ready(Some(event)) => {
+                        let event = this.enrichers.enrich(event);
+                        this.start(event);
+                    }
+                    Poll::Ready(None) => return Poll::Ready(None),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            this.resume(&mut budget);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+    use futures::executor::block_on;
+    use futures::stream::{self, StreamExt};
+    use futures::task::noop_waker;
+
+    fn rule_set(rules: usize) -> RuleSet {
+        RuleSet::new(
+            (0..rules)
+                .map(|i| {
+                    rule_from_yaml(&format!(
+                        "title: rule {}\nlogsource:\ndetection:\n    selection:\n        Image|endswith: '{}.exe'\n    condition: selection",
+                        i,
+                        i % 3
+                    ))
+                    .unwrap()
+                })
+                .collect(),
+        )
+    }
+
+    fn events() -> Vec<Event> {
+        (0..10)
+            .map(|i| Event::from([("Image", format!("C:\\{}.exe", i))]))
+            .collect()
+    }
+
+    #[test]
+    fn test_same_as_stream() {
+        let rule_set = rule_set(10);
+        let expected: Vec<MatchResult> = rule_set.stream(events()).collect();
+        assert_eq!(expected.len(), 10);
+        for budget in [1, 3, DEFAULT_YIELD_BUDGET] {
+            let matches: Vec<MatchResult> = block_on(
+                rule_set
+                    .stream_async(stream::iter(events()))
+                    .with_yield_budget(budget)
+                    .collect(),
+            );
+            assert_eq!(matches, expected);
+        }
+
+        let rule_set = rule_set.with_match_limit(2);
+        let expected: Vec<MatchResult> = rule_set.stream(events()).collect();
+        assert_eq!(expected.len(), 6);
+        let matches: Vec<MatchResult> = block_on(
+            rule_set
+                .stream_async(stream::iter(events()))
+                .with_yield_budget(1)
+                .collect(),
+        );
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_yields() {
+        let rule_set = rule_set(10);
+        let mut stream = rule_set
+            .stream_async(stream::iter([Event::from([("Image", "C:\\1.exe")])]))
+            .with_yield_budget(1);
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        // Only the three rules ending with 1 are candidates, so it takes three polls to evaluate them
+        assert!(stream.poll_next_unpin(&mut cx).is_pending());
+        assert!(stream.poll_next_unpin(&mut cx).is_pending());
+        let Poll::Ready(Some(first)) = stream.poll_next_unpin(&mut cx) else {
+            panic!("expected a match");
+        };
+        assert_eq!(first.rule_title, "rule 1");
+        assert!(matches!(
+            stream.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(_))
+        ));
+        assert!(matches!(
+            stream.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(_))
+        ));
+        assert!(matches!(stream.poll_next_unpin(&mut cx), Poll::Ready(None)));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::evaluation::{EvalContext, FieldId};
+use crate::rule::Rule;
+use crate::ruleset::interner::FieldInterner;
+use crate::ruleset::mark;
+use std::collections::HashMap;
+
+/// An inverted index from field names to the rules that require them.
+///
+/// Every rule whose detection can be reduced to a set of required fields (see
+/// [`crate::detection::Detection::required_fields`]) is only evaluated if at least one
+/// of these fields is present in the event. All other rules are always evaluated.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FieldIndex {
+    /// The interned field names together with the rules requiring them
+    fields: Vec<(FieldId, String, Vec<usize>)>,
+    /// Whether a rule is always evaluated
+    unindexed: Vec<bool>,
+}
+
+impl FieldIndex {
+    pub(crate) fn new(rules: &[Rule], interner: &FieldInterner) -> Self {
+        let mut field_rules: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut unindexed = Vec::with_capacity(rules.len());
+
+        for (i, rule) in rules.iter().enumerate() {
+            match rule.detection.required_fields() {
+                Some(required) => {
+                    unindexed.push(false);
+                    for name in required {
+                        let rules = field_rules.entry(name).or_default();
+                        if rules.last() != Some(&i) {
+                            rules.push(i);
+                        }
+                    }
+                }
+                None => unindexed.push(true),
+            }
+        }
+
+        let mut fields: Vec<(FieldId, String, Vec<usize>)> = field_rules
+            .into_iter()
+            .filter_map(|(name, rules)| Some((interner.get(&name)?, name, rules)))
+            .collect();
+        fields.sort_by_key(|(id, _, _)| *id);
+
+        Self { fields, unindexed }
+    }
+
+    /// Add the last rule of `rules`, whose fields must already be interned
+    pub(crate) fn insert(&mut self, rules: &[Rule], interner: &FieldInterner) {
+        let Some(rule) = rules.last() else {
+            return;
+        };
+        let i = rules.len() - 1;
+        let Some(required) = rule.detection.required_fields() else {
+            return self.unindexed.push(true);
+        };
+        self.unindexed.push(false);
+        for name in required {
+            match self.fields.iter_mut().find(|(_, field, _)| *field == name) {
+                Some((_, _, rules)) if rules.last() != Some(&i) => rules.push(i),
+                Some(_) => {}
+                None => {
+                    if let Some(id) = interner.get(&name) {
+                        self.fields.push((id, name, vec![i]));
+                    }
+                }
+            }
+        }
+        self.fields.sort_by_key(|(id, _, _)| *id);
+    }
+
+    /// Remove the rule at index `i` and shift the indices of the following rules
+    pub(crate) fn remove(&mut self, i: usize) {
+        if i < self.unindexed.len() {
+            self.unindexed.remove(i);
+        }
+        for (_, _, rules) in self.fields.iter_mut() {
+            rules.retain(|rule| *rule != i);
+            rules
+                .iter_mut()
+                .filter(|rule| **rule > i)
+                .for_each(|rule| *rule -= 1);
+        }
+        self.fields.retain(|(_, _, rules)| !rules.is_empty());
+    }
+
+    /// Returns for every rule whether one of its required fields is present in the event
+    #[cfg(test)]
+    pub(crate) fn candidates(&self, ctx: &EvalContext) -> Vec<bool> {
+        let mut result = vec![];
+        self.candidates_into(ctx, &mut result);
+        result
+    }
+
+    /// Like [`FieldIndex::candidates`] but writes into `result` to reuse its allocation
+    pub(crate) fn candidates_into(&self, ctx: &EvalContext, result: &mut Vec<bool>) {
+        result.clear();
+        result.extend_from_slice(&self.unindexed);
+        for (id, name, rules) in &self.fields {
+            if ctx.get(Some(*id), name).is_some() {
+                mark(result, rules);
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::evaluation::{FieldId, SelectionId};
+use crate::rule::Rule;
+use crate::selection::Selection;
+use std::collections::HashMap;
+
+/// Interns the field names of all rules in a rule set into small integer ids
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FieldInterner {
+    ids: HashMap<String, FieldId>,
+}
+
+impl FieldInterner {
+    /// Intern the field names of all rules and assign the ids to the fields
+    pub(crate) fn new(rules: &mut [Rule]) -> Self {
+        let mut result = Self::default();
+        for rule in rules.iter_mut() {
+            result.intern_rule(rule);
+        }
+        result
+    }
+
+    pub(crate) fn intern_rule(&mut self, rule: &mut Rule) {
+        for field in rule.detection.fields_mut() {
+            field.id = Some(self.intern(&field.name));
+        }
+    }
+
+    fn intern(&mut self, name: &str) -> FieldId {
+        let next = self.ids.len();
+        *self.ids.entry(name.to_string()).or_insert(next)
+    }
+
+    /// Get the id of an interned field name
+    pub(crate) fn get(&self, name: &str) -> Option<FieldId> {
+        self.ids.get(name).copied()
+    }
+
+    /// The number of distinct field names
+    pub(crate) fn len(&self) -> usize {
+        self.ids.len()
+    }
+}
+
+/// Assigns ids to the selections that occur in more than one rule of a rule set, e.g. filters
+/// copied between rules, so their result is evaluated once per event and shared by the rules
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SelectionInterner {
+    /// The distinct selections by their serialized form. The serialized form is only a key to
+    /// find candidates, as selections compiled with different configurations serialize the
+    /// same, so candidates are compared as well.
+    groups: HashMap<String, Vec<SelectionGroup>>,
+    /// The number of assigned ids
+    len: usize,
+}
+
+/// Equal selections of several rules
+#[derive(Debug, Clone)]
+struct SelectionGroup {
+    /// Only assigned once a second rule contains the selection
+    id: Option<SelectionId>,
+    /// The indices of the rules and the names of the selection in them
+    members: Vec<(usize, String)>,
+}
+
+impl SelectionInterner {
+    /// Find the selections that are equal in several rules and assign the ids to the rules
+    pub(crate) fn new(rules: &mut [Rule]) -> Self {
+        let mut result = Self::default();
+        for i in 0..rules.len() {
+            result.intern_rule(rules, i);
+        }
+        result
+    }
+
+    /// Find the selections of the rule at index `i` that are equal to selections of the
+    /// preceding rules. Selections with the `expand` modifier are not shared, as rules
+    /// compiled with different placeholder lists may contain them. Ids assigned by another rule
+    /// set, e.g. to a rule removed from it, are dropped first.
+    pub(crate) fn intern_rule(&mut self, rules: &mut [Rule], i: usize) {
+        if let Some(rule) = rules.get_mut(i) {
+            rule.detection.unshare_selections();
+        }
+        let Some(rule) = rules.get(i) else {
+            return;
+        };
+        let mut assigned: Vec<(usize, String, SelectionId)> = vec![];
+        for (name, selection) in rule.detection.get_selections() {
+            if is_live(selection) {
+                continue;
+            }
+            let Ok(key) = serde_yml::to_string(selection) else {
+                continue;
+            };
+            let groups = self.groups.entry(key).or_default();
+            let equal = groups.iter_mut().find(|group| {
+                group
+                    .members
+                    .first()
+                    .and_then(|(j, other)| rules.get(*j)?.detection.get_selections().get(other))
+                    .is_some_and(|other| other == selection)
+            });
+            let Some(group) = equal else {
+                groups.push(SelectionGroup {
+                    id: None,
+                    members: vec![(i, name.clone())],
+                });
+                continue;
+            };
+            let id = match group.id {
+                Some(id) => id,
+                None => {
+                    let id = self.len;
+                    self.len += 1;
+                    group.id = Some(id);
+                    for (j, other) in &group.members {
+                        assigned.push((*j, other.clone(), id));
+                    }
+                    id
+                }
+            };
+            group.members.push((i, name.clone()));
+            assigned.push((i, name.clone(), id));
+        }
+        for (j, name, id) in assigned {
+            if let Some(rule) = rules.get_mut(j) {
+                rule.detection.share_selection(&name, id);
+            }
+        }
+    }
+
+    /// Remove the rule at index `i` and shift the indices of the following rules. The ids of
+    /// its selections are not reused.
+    pub(crate) fn remove(&mut self, i: usize) {
+        for groups in self.groups.values_mut() {
+            for group in groups.iter_mut() {
+                group.members.retain(|(rule, _)| *rule != i);
+                group
+                    .members
+                    .iter_mut()
+                    .filter(|(rule, _)| *rule > i)
+                    .for_each(|(rule, _)| *rule -= 1);
+            }
+            groups.retain(|group| !group.members.is_empty());
+        }
+        self.groups.retain(|_, groups| !groups.is_empty());
+    }
+
+    /// The number of assigned ids, including those of selections no longer shared
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The number of distinct selections that occur in more than one rule
+    pub(crate) fn shared(&self) -> usize {
+        self.groups
+            .values()
+            .flatten()
+            .filter(|group| group.members.len() > 1)
+            .count()
+    }
+}
+
+fn is_live(selection: &Selection) -> bool {
+    match selection {
+        Selection::Keyword(_) => false,
+        Selection::Field(groups) => groups
+            .iter()
+            .any(|group| group.fields.iter().any(|field| field.live.is_some())),
+    }
+}
@@ -0,0 +1,268 @@
+use crate::event::{Event, EventValue};
+use crate::rule::Rule;
+use crate::ruleset::mark;
+use aho_corasick::AhoCorasick;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// The number of rules added to a rule set after its prefilter was built that are kept in a
+/// separate automaton, see [`Prefilter::insert`]
+const MAX_ADDED_RULES: usize = 256;
+
+/// A shared Aho-Corasick prefilter over the literals of all rules in a rule set.
+///
+/// Every rule whose detection can be reduced to a set of required literals (see
+/// [`crate::detection::Detection::required_literals`]) is only evaluated if at least one
+/// of its literals is contained in one of the event values. All other rules are always
+/// evaluated.
+///
+/// An automaton cannot be extended, so the literals of rules added later are compiled into a
+/// second, small automaton. It is rebuilt on the first evaluation after rules were added, so
+/// adding many rules in a row builds it only once. Once it holds too many rules, both are merged
+/// into one again.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Prefilter {
+    base: Layer,
+    /// The rules added after the base layer was built, with their required literals
+    added: Vec<(usize, Option<Vec<String>>)>,
+    /// The layer of the added rules, built when it is first needed
+    delta: OnceLock<Layer>,
+    /// Whether a rule is always evaluated
+    unfiltered: Vec<bool>,
+}
+
+/// An automaton over the literals of some of the rules
+#[derive(Debug, Clone, Default)]
+struct Layer {
+    automaton: Option<AhoCorasick>,
+    /// Maps the index of a literal in the automaton to the rules requiring it
+    literal_rules: Vec<Vec<usize>>,
+    /// The rules with literals if the automaton could not be built, they are always evaluated
+    fallback: Vec<usize>,
+}
+
+impl Layer {
+    /// Build a layer from the required literals of rules. If the automaton cannot be built,
+    /// all rules with literals are always evaluated.
+    fn new<'a, I>(rules: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, &'a Vec<String>)>,
+    {
+        let mut literals: Vec<&str> = vec![];
+        let mut positions: HashMap<&str, usize> = HashMap::new();
+        let mut literal_rules: Vec<Vec<usize>> = vec![];
+        let mut filtered = vec![];
+
+        for (i, required) in rules {
+            filtered.push(i);
+            for literal in required {
+                match positions.get(literal.as_str()) {
+                    Some(&pos) => {
+                        if let Some(rules) = literal_rules.get_mut(pos) {
+                            rules.push(i);
+                        }
+                    }
+                    None => {
+                        positions.insert(literal, literals.len());
+                        literals.push(literal);
+                        literal_rules.push(vec![i]);
+                    }
+                }
+            }
+        }
+
+        let automaton = if literals.is_empty() {
+            None
+        } else {
+            // Building the automaton only fails if it exceeds internal size limits,
+            // in which case the rules are evaluated without prefiltering.
+            AhoCorasick::new(&literals).ok()
+        };
+        if automaton.is_some() {
+            filtered.clear();
+        }
+
+        Self {
+            automaton,
+            literal_rules,
+            fallback: filtered,
+        }
+    }
+
+    /// Mark the rules of the literals contained in the event
+    fn candidates_into(&self, event: &Event, result: &mut [bool], scratch: &mut Scratch) {
+        let Some(automaton) = &self.automaton else {
+            mark(result, &self.fallback);
+            return;
+        };
+        scratch.found.clear();
+        scratch.found.resize(self.literal_rules.len(), false);
+        for value in event.values() {
+            Self::scan(automaton, value, scratch);
+        }
+        for (rules, found) in self.literal_rules.iter().zip(&scratch.found) {
+            if *found {
+                mark(result, rules);
+            }
+        }
+    }
+
+    fn scan(automaton: &AhoCorasick, value: &EventValue, scratch: &mut Scratch) {
+        match value {
+            EventValue::Value(v) => {
+                scratch.haystack.clear();
+                scratch
+                    .haystack
+                    .extend(v.value_as_str().chars().flat_map(|c| c.to_lowercase()));
+                for m in automaton.find_overlapping_iter(&scratch.haystack) {
+                    if let Some(found) = scratch.found.get_mut(m.pattern().as_usize()) {
+                        *found = true;
+                    }
+                }
+            }
+            EventValue::Sequence(seq) => seq.iter().for_each(|v| Self::scan(automaton, v, scratch)),
+            EventValue::Map(map) => map.values().for_each(|v| Self::scan(automaton, v, scratch)),
+        }
+    }
+
+    /// Forget the rule at index `i` and shift the indices of the following rules. Its literals
+    /// stay in the automaton until it is rebuilt.
+    fn remove(&mut self, i: usize) {
+        for rules in self.literal_rules.iter_mut().chain([&mut self.fallback]) {
+            rules.retain(|rule| *rule != i);
+            rules
+                .iter_mut()
+                .filter(|rule| **rule > i)
+                .for_each(|rule| *rule -= 1);
+        }
+    }
+}
+
+impl Prefilter {
+    pub(crate) fn new(rules: &[Rule]) -> Self {
+        let required: Vec<Option<Vec<String>>> = rules
+            .iter()
+            .map(|rule| rule.detection.required_literals())
+            .collect();
+        let base = Layer::new(
+            required
+                .iter()
+                .enumerate()
+                .filter_map(|(i, literals)| Some((i, literals.as_ref()?))),
+        );
+        Self {
+            base,
+            added: vec![],
+            delta: OnceLock::new(),
+            unfiltered: required.iter().map(Option::is_none).collect(),
+        }
+    }
+
+    /// Add the last rule of `rules` without rebuilding the automaton of the other rules
+    pub(crate) fn insert(&mut self, rules: &[Rule]) {
+        let Some(rule) = rules.last() else {
+            return;
+        };
+        if self.added.len() >= MAX_ADDED_RULES {
+            *self = Self::new(rules);
+            return;
+        }
+        let required = rule.detection.required_literals();
+        self.unfiltered.push(required.is_none());
+        self.added.push((rules.len() - 1, required));
+        self.delta = OnceLock::new();
+    }
+
+    fn delta(&self) -> &Layer {
+        self.delta.get_or_init(|| {
+            Layer::new(
+                self.added
+                    .iter()
+                    .filter_map(|(i, literals)| Some((*i, literals.as_ref()?))),
+            )
+        })
+    }
+
+    /// Remove the rule at index `i` and shift the indices of the following rules
+    pub(crate) fn remove(&mut self, i: usize) {
+        if i < self.unfiltered.len() {
+            self.unfiltered.remove(i);
+        }
+        self.base.remove(i);
+        if let Some(delta) = self.delta.get_mut() {
+            delta.remove(i);
+        }
+        self.added.retain(|(rule, _)| *rule != i);
+        self.added
+            .iter_mut()
+            .filter(|(rule, _)| *rule > i)
+            .for_each(|(rule, _)| *rule -= 1);
+    }
+
+    /// Returns for every rule whether it has to be evaluated for the event
+    #[cfg(test)]
+    pub(crate) fn candidates(&self, event: &Event) -> Vec<bool> {
+        let mut result = vec![];
+        self.candidates_into(event, &mut result, &mut Scratch::default());
+        result
+    }
+
+    /// Like [`Prefilter::candidates`] but writes into `result` and reuses the buffers of
+    /// `scratch`, so evaluating many events does not allocate for every event
+    pub(crate) fn candidates_into(
+        &self,
+        event: &Event,
+        result: &mut Vec<bool>,
+        scratch: &mut Scratch,
+    ) {
+        result.clear();
+        result.extend_from_slice(&self.unfiltered);
+        self.base.candidates_into(event, result, scratch);
+        self.delta().candidates_into(event, result, scratch);
+    }
+}
+
+/// Buffers of the prefilter that are reused across events
+#[derive(Debug, Default)]
+pub(crate) struct Scratch {
+    found: Vec<bool>,
+    haystack: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    fn rule(image: &str) -> Rule {
+        rule_from_yaml(&format!(
+            r#"
+        title: {image}
+        logsource:
+        detection:
+            selection:
+                Image|endswith: '{image}'
+            condition: selection
+        "#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_delta_is_built_lazily() {
+        let mut rules = vec![rule("whoami.exe")];
+        let mut prefilter = Prefilter::new(&rules);
+        for image in ["net.exe", "cmd.exe"] {
+            rules.push(rule(image));
+            prefilter.insert(&rules);
+        }
+        assert!(prefilter.delta.get().is_none());
+
+        let event = Event::from([("Image", "C:\\cmd.exe")]);
+        assert_eq!(prefilter.candidates(&event), vec![false, false, true]);
+        assert!(prefilter.delta.get().is_some());
+
+        prefilter.remove(1);
+        assert_eq!(prefilter.candidates(&event), vec![false, true]);
+    }
+}
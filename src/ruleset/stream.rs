@@ -0,0 +1,296 @@
+use crate::context::ContextWindow;
+use crate::enrich::{Annotator, Annotators, Enricher, Enrichers};
+use crate::event::Event;
+use crate::result::MatchResult;
+use crate::rule::Logsource;
+use crate::ruleset::{RuleSet, Scratch};
+use crate::sink::{AlertSink, SinkError};
+use std::collections::VecDeque;
+
+/// A pull-based evaluation of a stream of events, created by [`RuleSet::stream`].
+///
+/// Events are pulled from the underlying iterator one at a time and only as many as needed
+/// to produce the next match, so the memory use only depends on the rule set and the largest
+/// event, not on the length of the stream. This makes it suitable for tailing large log files.
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::{rule_from_yaml, Event, RuleSet};
+///
+/// let rule = rule_from_yaml(
+///     r#"
+/// title: Whoami
+/// logsource:
+///     category: process_creation
+/// detection:
+///     selection:
+///         Image|endswith: '\whoami.exe'
+///     condition: selection
+/// "#,
+/// )
+/// .unwrap();
+///
+/// let rule_set = RuleSet::new(vec![rule]);
+/// let events = (0..).map(|i| Event::from([("Image", format!("C:\\tool{}.exe", i % 1000))]));
+/// let first = rule_set
+///     .stream(events.take(10_000).chain([Event::from([("Image", "C:\\whoami.exe")])]))
+///     .next()
+///     .unwrap();
+/// assert_eq!(first.rule_title, "Whoami");
+/// ```
+#[derive(Debug)]
+pub struct MatchStream<'r, I> {
+    rule_set: &'r RuleSet,
+    events: I,
+    scratch: Scratch,
+    /// Matches of the last event that were not yet returned
+    pending: VecDeque<MatchResult>,
+    context: Option<ContextWindow>,
+    /// The logsource of events that do not carry their own
+    logsource: Option<Logsource>,
+    /// Run on every event before it is matched
+    enrichers: Enrichers,
+    /// Run on every match before it is returned
+    annotators: Annotators,
+}
+
+impl<'r, I: Iterator<Item = Event>> MatchStream<'r, I> {
+    pub(crate) fn new(rule_set: &'r RuleSet, events: I) -> Self {
+        Self {
+            rule_set,
+            events,
+            scratch: Scratch::default(),
+            pending: VecDeque::new(),
+            context: None,
+            logsource: None,
+            enrichers: Enrichers::new(),
+            annotators: Annotators::new(),
+        }
+    }
+
+    /// Attach the preceding events of the same entity to every match, see [`ContextWindow`].
+    /// Use [`ContextWindow::with_max_entities`] to keep the memory use bounded.
+    pub fn with_context(mut self, window: ContextWindow) -> Self {
+        self.context = Some(window);
+        self
+    }
+
+    /// Treat all events of the stream that do not carry a logsource as collected from
+    /// `logsource`, so only the rules that apply to it are evaluated, see [`crate::taxonomy`]
+    pub fn with_logsource(mut self, logsource: Logsource) -> Self {
+        self.logsource = Some(logsource);
+        self
+    }
+
+    /// Run `enricher` on every event before it is matched, after the enrichers added before.
+    /// The matches carry the fields of the enriched event, see [`crate::enrich`].
+    pub fn with_enricher<E: Enricher + 'static>(mut self, enricher: E) -> Self {
+        self.enrichers.push(enricher);
+        self
+    }
+
+    /// Run `annotator` on every match before it is returned, after the annotators added
+    /// before, e.g. to add the names of ATT&CK techniques, see [`crate::enrich`]
+    pub fn with_annotator<A: Annotator + 'static>(mut self, annotator: A) -> Self {
+        self.annotators.push(annotator);
+        self
+    }
+
+    /// The context window, if one was attached with [`MatchStream::with_context`]
+    pub fn context(&self) -> Option<&ContextWindow> {
+        self.context.as_ref()
+    }
+
+    /// The events the stream pulls from, e.g. to inspect the errors of
+    /// [`RuleSet::evaluate_file`] after all matches were read
+    pub fn events(&self) -> &I {
+        &self.events
+    }
+
+    /// Send all matches of the stream to `sink` and flush it afterwards. Returns the number of
+    /// matches, or the first error of the sink.
+    pub fn send_to<S: AlertSink + ?Sized>(self, sink: &mut S) -> Result<usize, SinkError> {
+        let mut sent = 0;
+        for result in self {
+            sink.send(&result)?;
+            sent += 1;
+        }
+        sink.flush()?;
+        Ok(sent)
+    }
+
+    fn evaluate(&mut self, event: &Event) {
+        let rule_set = self.rule_set;
+        let ctx = rule_set.context(event);
+        let logsource = event.logsource().or(self.logsource.as_ref());
+        rule_set.candidates_into(&ctx, logsource, &mut self.scratch);
+        self.pending.extend(
+            rule_set
+                .matches_in(&ctx, &self.scratch.candidates)
+                .into_iter()
+                .map(|rule| MatchResult::new(rule, event)),
+        );
+        rule_set.sort_matches(self.pending.make_contiguous());
+        if let Some(window) = &mut self.context {
+            window.process(event, self.pending.make_contiguous());
+        }
+        for result in &mut self.pending {
+            self.annotators.annotate(result);
+        }
+    }
+}
+
+impl<I: Iterator<Item = Event>> Iterator for MatchStream<'_, I> {
+    type Item = MatchResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(result) = self.pending.pop_front() {
+                return Some(result);
+            }
+            let event = self.enrichers.enrich(self.events.next()?);
+            self.evaluate(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    fn rule_set() -> RuleSet {
+        RuleSet::new(vec![
+            rule_from_yaml(
+                "title: cmd\nlogsource:\ndetection:\n    selection:\n        Image|endswith: 'cmd.exe'\n    condition: selection",
+            )
+            .unwrap(),
+            rule_from_yaml(
+                "title: any\nlogsource:\ndetection:\n    selection:\n        Image|exists: true\n    condition: selection",
+            )
+            .unwrap(),
+        ])
+    }
+
+    #[test]
+    fn test_stream() {
+        let rule_set = rule_set();
+        let events = vec![
+            Event::from([("Image", "cmd.exe")]),
+            Event::from([("User", "alice")]),
+            Event::from([("Image", "explorer.exe")]),
+        ];
+
+        let expected: Vec<MatchResult> = rule_set
+            .evaluate_batch(&events)
+            .into_iter()
+            .flatten()
+            .collect();
+        let streamed: Vec<MatchResult> = rule_set.stream(events).collect();
+        assert_eq!(streamed, expected);
+        assert_eq!(streamed.len(), 3);
+    }
+
+    #[test]
+    fn test_stream_is_lazy() {
+        let rule_set = rule_set();
+        let mut pulled = 0;
+        let events = std::iter::repeat_with(|| {
+            pulled += 1;
+            Event::from([("Image", "cmd.exe")])
+        });
+
+        let first: Vec<MatchResult> = rule_set.stream(events).take(3).collect();
+        assert_eq!(first.len(), 3);
+        assert_eq!(pulled, 2);
+    }
+
+    #[test]
+    fn test_stream_with_context() {
+        let rule_set = rule_set();
+        let events = vec![
+            Event::from([("Computer", "a"), ("User", "alice")]),
+            Event::from([("Computer", "b"), ("User", "bob")]),
+            Event::from([("Computer", "a"), ("Image", "cmd.exe")]),
+        ];
+
+        let mut stream = rule_set
+            .stream(events.clone())
+            .with_context(ContextWindow::new(["Computer"], 4).with_max_entities(1));
+        let results: Vec<MatchResult> = stream.by_ref().collect();
+        assert_eq!(results.len(), 2);
+        // Entity a was evicted when b was recorded
+        assert!(results.iter().all(|r| r.context.is_empty()));
+        assert_eq!(stream.context().unwrap().entities(), 1);
+
+        let results: Vec<MatchResult> = rule_set
+            .stream(events.clone())
+            .with_context(ContextWindow::new(["Computer"], 4))
+            .collect();
+        assert!(results.iter().all(|r| r.context == events[..1]));
+    }
+
+    #[test]
+    fn test_stream_with_enricher() {
+        #[derive(Debug)]
+        struct Tag;
+
+        impl Enricher for Tag {
+            fn enrich(&self, mut event: Event) -> Event {
+                event.insert("Image", "cmd.exe");
+                event
+            }
+        }
+
+        let rule_set = rule_set();
+        let events = vec![Event::from([("User", "alice")])];
+        assert_eq!(rule_set.stream(events.clone()).count(), 0);
+        let results: Vec<MatchResult> = rule_set.stream(events).with_enricher(Tag).collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].rule_title, "cmd");
+    }
+
+    #[test]
+    fn test_stream_with_annotator() {
+        #[derive(Debug)]
+        struct Title;
+
+        impl Annotator for Title {
+            fn annotate(&self, result: &mut MatchResult) {
+                let title = result.rule_title.to_uppercase();
+                result.annotations.insert("title".to_string(), title.into());
+            }
+        }
+
+        let rule_set = rule_set();
+        let events = vec![Event::from([("Image", "cmd.exe")])];
+        let results: Vec<MatchResult> = rule_set.stream(events).with_annotator(Title).collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].annotations["title"], "CMD".into());
+        assert_eq!(results[1].annotations["title"], "ANY".into());
+    }
+
+    #[test]
+    fn test_stream_with_logsource() {
+        let rule_set = RuleSet::new(vec![rule_from_yaml(
+            "title: cmd\nlogsource:\n    product: windows\ndetection:\n    selection:\n        Image|endswith: 'cmd.exe'\n    condition: selection",
+        )
+        .unwrap()]);
+        let linux = Logsource::new(None, Some("linux"), None);
+        let windows = Logsource::new(None, Some("windows"), None);
+        let events = vec![
+            Event::from([("Image", "cmd.exe")]),
+            Event::from([("Image", "cmd.exe")]).with_logsource(windows.clone()),
+        ];
+
+        assert_eq!(rule_set.stream(events.clone()).count(), 2);
+        assert_eq!(
+            rule_set
+                .stream(events.clone())
+                .with_logsource(linux)
+                .count(),
+            1
+        );
+        assert_eq!(rule_set.stream(events).with_logsource(windows).count(), 2);
+    }
+}
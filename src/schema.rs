@@ -0,0 +1,202 @@
+//! A [JSON Schema](https://json-schema.org) of the rules accepted by this crate.
+//!
+//! Editors and validation services can use the schema for completion and validation of rule
+//! files, e.g. with the `# yaml-language-server: $schema=sigma-rule.json` comment of the YAML
+//! language server. It follows what the parser implements rather than the full specification:
+//! field names may only carry the modifiers in [`MODIFIERS`], and the values of a field must be
+//! scalars or lists of scalars.
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::schema::rule_schema;
+//!
+//! let schema = rule_schema();
+//! assert_eq!(schema["required"][0], "title");
+//! println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+//! ```
+use serde_json::{json, Value};
+
+/// All field modifiers supported by the engine, in lowercase. `expand` is only accepted when
+/// its placeholders are resolved by a [`crate::pipeline::Pipeline`] or are lists of
+/// [`crate::EngineConfig::placeholders`].
+pub const MODIFIERS: &[&str] = crate::field::MODIFIERS;
+
+const STATUSES: &[&str] = &[
+    "stable",
+    "test",
+    "experimental",
+    "deprecated",
+    "unsupported",
+];
+const LEVELS: &[&str] = &["informational", "low", "medium", "high", "critical"];
+const RELATED_TYPES: &[&str] = &["derived", "obsolete", "merged", "renamed", "similar"];
+
+/// The regular expression the keys of a field selection must match, i.e. a field name followed
+/// by any number of supported modifiers
+pub fn field_name_pattern() -> String {
+    format!("^[^|]*(\\|({}))*$", MODIFIERS.join("|"))
+}
+
+/// The JSON Schema (draft 2020-12) of a rule
+pub fn rule_schema() -> Value {
+    let string = |description: &str| json!({ "type": "string", "description": description });
+    let strings = |description: &str| json!({ "type": "array", "items": { "type": "string" }, "description": description });
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Sigma rule",
+        "description": "A Sigma rule as accepted by sigma-rust",
+        "type": "object",
+        "required": ["title", "logsource", "detection"],
+        "properties": {
+            "title": {
+                "type": "string",
+                "maxLength": 256,
+                "description": "A brief title for the rule that should contain what the rule is supposed to detect",
+            },
+            "id": string("A globally unique identifier of the rule, usually a random UUID"),
+            "name": string("A unique human-readable name that can be used instead of the id in correlation rules"),
+            "related": {
+                "type": "array",
+                "description": "References to related rules",
+                "items": {
+                    "type": "object",
+                    "required": ["id", "type"],
+                    "properties": {
+                        "id": { "type": "string" },
+                        "type": { "enum": RELATED_TYPES },
+                    },
+                },
+            },
+            "taxonomy": string("The taxonomy of the field names used in the detection"),
+            "status": { "enum": STATUSES, "description": "The maturity of the rule" },
+            "description": string("A short description of the rule and the activity it detects"),
+            "license": string("The license of the rule as SPDX identifier"),
+            "author": string("The authors of the rule, separated by commas"),
+            "references": strings("References to the sources that the rule was derived from"),
+            "date": string("The creation date of the rule as YYYY-MM-DD"),
+            "modified": string("The last modification date of the rule as YYYY-MM-DD"),
+            "logsource": { "$ref": "#/$defs/logsource" },
+            "detection": { "$ref": "#/$defs/detection" },
+            "fields": strings("Fields of the event that are interesting for further analysis"),
+            "falsepositives": strings("Known false positives"),
+            "level": { "enum": LEVELS, "description": "The criticality of a triggered rule" },
+            "tags": {
+                "type": "array",
+                "description": "Namespaced tags, e.g. attack.t1059",
+                "items": { "type": "string", "pattern": "^[a-z0-9_.-]+$" },
+            },
+        },
+        "$defs": {
+            "logsource": {
+                "type": "object",
+                "description": "The log data the detection is meant to be applied to",
+                "properties": {
+                    "category": string("A logical group of log sources, e.g. process_creation"),
+                    "product": string("The product that writes the logs, e.g. windows"),
+                    "service": string("A subset of the logs of the product, e.g. sysmon"),
+                    "definition": string("Additional information on the log source"),
+                },
+            },
+            "detection": {
+                "type": "object",
+                "description": "Named selections combined by a condition",
+                "required": ["condition"],
+                "properties": {
+                    "condition": string("Combines the selections, e.g. 'selection and not filter' or '1 of selection_*'"),
+                },
+                "additionalProperties": { "$ref": "#/$defs/selection" },
+            },
+            "selection": {
+                "oneOf": [
+                    {
+                        "type": "array",
+                        "description": "Keywords searched in all values of the event",
+                        "minItems": 1,
+                        "items": { "type": ["string", "number", "boolean"] },
+                    },
+                    { "$ref": "#/$defs/field_group" },
+                    {
+                        "type": "array",
+                        "description": "Field groups combined with OR",
+                        "minItems": 1,
+                        "items": { "$ref": "#/$defs/field_group" },
+                    },
+                ],
+            },
+            "field_group": {
+                "type": "object",
+                "description": "Fields combined with AND. The keys are field names followed by modifiers, e.g. Image|endswith",
+                "propertyNames": { "pattern": field_name_pattern() },
+                "additionalProperties": {
+                    "oneOf": [
+                        { "$ref": "#/$defs/value" },
+                        { "type": "array", "minItems": 1, "items": { "$ref": "#/$defs/value" } },
+                    ],
+                },
+            },
+            "value": { "type": ["string", "number", "boolean", "null"] },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::re::Regex;
+    use crate::rule::{Level, RelatedType, Status};
+
+    #[cfg(all(feature = "cidr", feature = "base64"))]
+    #[test]
+    fn test_modifiers_are_supported() {
+        use crate::field::Modifier;
+        use std::str::FromStr;
+
+        for modifier in MODIFIERS {
+            let name = match *modifier {
+                // The utf16 modifiers require base64
+                "utf16" | "utf16be" | "utf16le" | "wide" => format!("Field|base64|{}", modifier),
+                _ => format!("Field|{}", modifier),
+            };
+            assert!(Modifier::from_str(&name).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_enums_match_the_rule_types() {
+        for status in STATUSES {
+            serde_json::from_value::<Status>(json!(status)).unwrap();
+        }
+        for level in LEVELS {
+            serde_json::from_value::<Level>(json!(level)).unwrap();
+        }
+        for related_type in RELATED_TYPES {
+            serde_json::from_value::<RelatedType>(json!(related_type)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_field_name_pattern() {
+        let pattern = Regex::new(&field_name_pattern()).unwrap();
+        assert!(pattern.is_match("Image"));
+        assert!(pattern.is_match("Image|endswith"));
+        assert!(pattern.is_match("CommandLine|base64offset|utf16le|contains"));
+        assert!(pattern.is_match("process.executable|re"));
+        assert!(!pattern.is_match("Image|endswit"));
+        assert!(!pattern.is_match("Image|"));
+    }
+
+    #[test]
+    fn test_schema() {
+        let schema = rule_schema();
+        assert_eq!(
+            schema["$defs"]["field_group"]["propertyNames"]["pattern"],
+            field_name_pattern()
+        );
+        assert_eq!(schema["properties"]["level"]["enum"][4], "critical");
+        assert_eq!(
+            schema["properties"]["logsource"]["$ref"],
+            "#/$defs/logsource"
+        );
+    }
+}
@@ -0,0 +1,370 @@
+//! Risk-based scoring of rule matches.
+//!
+//! Instead of alerting on every single match, the [`RiskScorer`] accumulates the matches
+//! of an entity (e.g. a host or a user) within a sliding time window into a risk score.
+//! Each match contributes a weight depending on the level of the matching rule.
+//!
+//! Entities without matches within the window are forgotten as the observed timestamps advance,
+//! and the number of tracked entities can be capped with [`RiskScorer::with_max_entities`], so
+//! the memory use of the scorer does not grow with the number of distinct entities.
+use crate::event::Event;
+use crate::lru::Lru;
+use crate::result::MatchResult;
+use crate::rule::Level;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// The cumulative risk of an entity within the scoring window
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskScore {
+    /// The entity key, i.e. the values of the key fields joined by `|`
+    pub entity: String,
+    /// The sum of the weights of all matches within the window, saturating at `u32::MAX`
+    pub score: u32,
+    /// The number of matches within the window
+    pub matches: usize,
+    /// The number of distinct rules that matched within the window
+    pub distinct_rules: usize,
+}
+
+#[derive(Debug)]
+struct Contribution {
+    timestamp: SystemTime,
+    rule: String,
+    weight: u32,
+}
+
+/// Combines rule matches on the same entity within a time window into a risk score.
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::scoring::RiskScorer;
+/// use sigma_rust::{rule_from_yaml, Event, Level};
+/// use std::time::{Duration, SystemTime};
+///
+/// let rule = rule_from_yaml(
+///     r#"
+/// title: Whoami
+/// level: high
+/// logsource:
+///     category: process_creation
+/// detection:
+///     selection:
+///         Image|endswith: '\whoami.exe'
+///     condition: selection
+/// "#,
+/// )
+/// .unwrap();
+///
+/// let mut scorer = RiskScorer::new(["Computer"], Duration::from_secs(3600));
+/// scorer.set_weight(Level::High, 40);
+///
+/// let event = Event::from([("Computer", "ws-01"), ("Image", "C:\\whoami.exe")]);
+/// let result = rule.evaluate(&event).unwrap();
+/// let now = SystemTime::now();
+///
+/// scorer.observe(&event, &result, now);
+/// let score = scorer.observe(&event, &result, now).unwrap();
+/// assert_eq!(score.entity, "ws-01");
+/// assert_eq!(score.score, 80);
+/// ```
+#[derive(Debug)]
+pub struct RiskScorer {
+    key_fields: Vec<String>,
+    window: Duration,
+    weights: HashMap<Level, u32>,
+    default_weight: u32,
+    /// The contributions of each entity, the entity whose last match was observed longest ago
+    /// is evicted first
+    entities: Lru<Vec<Contribution>>,
+    /// The timestamp at which all entities were last expired
+    expired: Option<SystemTime>,
+}
+
+impl RiskScorer {
+    /// Create a new scorer that groups matches by the values of `key_fields` and
+    /// only considers matches within `window`.
+    ///
+    /// The default weights are 1 for informational, 5 for low, 10 for medium,
+    /// 25 for high and 50 for critical rules. Rules without a level have a weight of 1.
+    pub fn new<I, S>(key_fields: I, window: Duration) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            key_fields: key_fields.into_iter().map(|s| s.into()).collect(),
+            window,
+            weights: HashMap::from([
+                (Level::Informational, 1),
+                (Level::Low, 5),
+                (Level::Medium, 10),
+                (Level::High, 25),
+                (Level::Critical, 50),
+            ]),
+            default_weight: 1,
+            entities: Lru::default(),
+            expired: None,
+        }
+    }
+
+    /// Track at most `max_entities` entities. When a new entity would exceed the limit,
+    /// the entity whose last match was observed longest ago is forgotten.
+    /// With a limit of 0, no entity is tracked and [`RiskScorer::observe`] always returns `None`.
+    pub fn with_max_entities(mut self, max_entities: usize) -> Self {
+        self.entities.set_max(max_entities);
+        self
+    }
+
+    /// Set the weight for matches of rules with the given level
+    pub fn set_weight(&mut self, level: Level, weight: u32) {
+        self.weights.insert(level, weight);
+    }
+
+    /// Set the weight for matches of rules without a level
+    pub fn set_default_weight(&mut self, weight: u32) {
+        self.default_weight = weight;
+    }
+
+    fn weight(&self, level: Option<Level>) -> u32 {
+        level
+            .and_then(|l| self.weights.get(&l).copied())
+            .unwrap_or(self.default_weight)
+    }
+
+    /// Compute the entity key of an event.
+    /// Returns `None` if the event contains none of the key fields.
+    pub fn entity_key(&self, event: &Event) -> Option<String> {
+        event.entity_key(&self.key_fields)
+    }
+
+    /// Record a match of `result` on `event` that happened at `timestamp` and return the
+    /// updated risk score of the entity. Matches may be observed out of order. Once per window,
+    /// the contributions of all entities that are older than the window are discarded.
+    /// Returns `None` if the event contains none of the key fields or no entity may be tracked.
+    pub fn observe(
+        &mut self,
+        event: &Event,
+        result: &MatchResult,
+        timestamp: SystemTime,
+    ) -> Option<RiskScore> {
+        let entity = self.entity_key(event)?;
+        let contribution = Contribution {
+            timestamp,
+            rule: result
+                .rule_id
+                .clone()
+                .unwrap_or_else(|| result.rule_title.clone()),
+            weight: self.weight(result.level),
+        };
+
+        if self.expired.map_or(true, |expired| {
+            timestamp
+                .duration_since(expired)
+                .is_ok_and(|age| age > self.window)
+        }) {
+            self.expire(timestamp);
+            self.expired = Some(timestamp);
+        }
+
+        self.entities.touch(&entity, Vec::new)?.push(contribution);
+
+        self.score(&entity, timestamp)
+    }
+
+    /// Get the risk score of an entity at the point in time `now`.
+    /// Contributions that are older than the window are discarded.
+    pub fn score(&mut self, entity: &str, now: SystemTime) -> Option<RiskScore> {
+        let window = self.window;
+        let contributions = self.entities.get_mut(entity)?;
+        contributions.retain(|c| within(window, c, now));
+
+        if contributions.is_empty() {
+            self.entities.remove(entity);
+            return None;
+        }
+
+        let mut rules: Vec<&str> = contributions.iter().map(|c| c.rule.as_str()).collect();
+        rules.sort_unstable();
+        rules.dedup();
+
+        Some(RiskScore {
+            entity: entity.to_string(),
+            score: contributions
+                .iter()
+                .fold(0, |score: u32, c| score.saturating_add(c.weight)),
+            matches: contributions.len(),
+            distinct_rules: rules.len(),
+        })
+    }
+
+    /// Discard all contributions that are older than the window at the point in time `now`
+    pub fn expire(&mut self, now: SystemTime) {
+        let window = self.window;
+        self.entities.retain(|contributions| {
+            contributions.retain(|c| within(window, c, now));
+            !contributions.is_empty()
+        });
+    }
+
+    /// The number of entities currently tracked
+    pub fn entities(&self) -> usize {
+        self.entities.len()
+    }
+}
+
+/// Whether a contribution is not older than the window at the point in time `now`
+fn within(window: Duration, contribution: &Contribution, now: SystemTime) -> bool {
+    now.duration_since(contribution.timestamp)
+        .map_or(true, |age| age <= window)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    fn result(level: &str, id: &str) -> MatchResult {
+        let rule = rule_from_yaml(&format!(
+            r#"
+        title: Rule {id}
+        id: {id}
+        level: {level}
+        logsource:
+        detection:
+            selection:
+                Image: a
+            condition: selection
+        "#
+        ))
+        .unwrap();
+        rule.evaluate(&Event::from([("Image", "a")])).unwrap()
+    }
+
+    #[test]
+    fn test_accumulate_within_window() {
+        let mut scorer = RiskScorer::new(["Computer", "User"], Duration::from_secs(60));
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let event = Event::from([("Computer", "ws-01"), ("User", "bob")]);
+
+        let score = scorer.observe(&event, &result("low", "r1"), start).unwrap();
+        assert_eq!(score.entity, "ws-01|bob");
+        assert_eq!(score.score, 5);
+
+        let score = scorer
+            .observe(
+                &event,
+                &result("critical", "r2"),
+                start + Duration::from_secs(30),
+            )
+            .unwrap();
+        assert_eq!(score.score, 55);
+        assert_eq!(score.matches, 2);
+        assert_eq!(score.distinct_rules, 2);
+
+        // The first match falls out of the window
+        let score = scorer
+            .observe(
+                &event,
+                &result("critical", "r2"),
+                start + Duration::from_secs(61),
+            )
+            .unwrap();
+        assert_eq!(score.score, 100);
+        assert_eq!(score.matches, 2);
+        assert_eq!(score.distinct_rules, 1);
+
+        assert!(scorer
+            .score("ws-01|bob", start + Duration::from_secs(1000))
+            .is_none());
+    }
+
+    #[test]
+    fn test_entities_are_separated() {
+        let mut scorer = RiskScorer::new(["Computer"], Duration::from_secs(60));
+        scorer.set_weight(Level::Medium, 7);
+        let now = SystemTime::now();
+
+        let a = Event::from([("Computer", "a")]);
+        let b = Event::from([("Computer", "b")]);
+        scorer.observe(&a, &result("medium", "r1"), now);
+        scorer.observe(&a, &result("medium", "r1"), now);
+        let score = scorer.observe(&b, &result("medium", "r1"), now).unwrap();
+        assert_eq!(score.score, 7);
+        assert_eq!(scorer.score("a", now).unwrap().score, 14);
+
+        let no_key = Event::from([("User", "bob")]);
+        assert!(scorer
+            .observe(&no_key, &result("medium", "r1"), now)
+            .is_none());
+
+        scorer.expire(now + Duration::from_secs(120));
+        assert!(scorer.score("a", now).is_none());
+    }
+
+    #[test]
+    fn test_out_of_order_and_saturating() {
+        let mut scorer = RiskScorer::new(["Computer"], Duration::from_secs(60));
+        scorer.set_weight(Level::Critical, u32::MAX);
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let event = Event::from([("Computer", "a")]);
+
+        scorer.observe(
+            &event,
+            &result("low", "r1"),
+            start + Duration::from_secs(50),
+        );
+        // Observed later, but happened before the first match
+        scorer.observe(&event, &result("medium", "r2"), start);
+        let score = scorer
+            .observe(
+                &event,
+                &result("low", "r1"),
+                start + Duration::from_secs(100),
+            )
+            .unwrap();
+        assert_eq!(score.score, 10);
+        assert_eq!(score.matches, 2);
+
+        let score = scorer
+            .observe(
+                &event,
+                &result("critical", "r3"),
+                start + Duration::from_secs(100),
+            )
+            .unwrap();
+        assert_eq!(score.score, u32::MAX);
+    }
+
+    #[test]
+    fn test_entities_are_evicted() {
+        let mut scorer =
+            RiskScorer::new(["Computer"], Duration::from_secs(60)).with_max_entities(2);
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let observe = |scorer: &mut RiskScorer, computer: &str, secs: u64| {
+            scorer.observe(
+                &Event::from([("Computer", computer)]),
+                &result("low", "r1"),
+                start + Duration::from_secs(secs),
+            )
+        };
+
+        observe(&mut scorer, "a", 0);
+        observe(&mut scorer, "b", 1);
+        observe(&mut scorer, "a", 2);
+        // b was observed longest ago and is evicted
+        observe(&mut scorer, "c", 3);
+        assert_eq!(scorer.entities(), 2);
+        assert!(scorer.score("b", start).is_none());
+        assert_eq!(scorer.score("a", start).unwrap().matches, 2);
+
+        // Entities without matches within the window are forgotten once the window passed
+        observe(&mut scorer, "d", 100);
+        assert_eq!(scorer.entities(), 1);
+
+        let mut scorer =
+            RiskScorer::new(["Computer"], Duration::from_secs(60)).with_max_entities(0);
+        assert!(observe(&mut scorer, "a", 0).is_none());
+        assert_eq!(scorer.entities(), 0);
+    }
+}
@@ -3,22 +3,52 @@ use crate::error::SelectionError::{
     InvalidKeywordSelection, InvalidSelectionType, MixedKeywordAndFieldlist,
     SelectionContainsNoFields,
 };
+use crate::evaluation::EvalContext;
 use crate::event::Event;
 use crate::field::Field;
+use crate::wildcard::{tokenize, WildcardPattern};
+use crate::{cache, config};
+use serde::ser::{Serialize, SerializeMap, Serializer};
 use serde::Deserialize;
 use serde_yml::Value;
 use serde_yml::Value::{Mapping, Sequence};
+use std::sync::Arc;
 
 /// A field group is a collection of fields that are to be combined with AND
 /// In other words a fields group translates to a YAML dictionary
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FieldGroup {
     pub fields: Vec<Field>,
 }
 
 impl FieldGroup {
-    fn evaluate(&self, event: &Event) -> bool {
-        self.fields.iter().all(|field| field.evaluate(event))
+    fn evaluate(&self, ctx: &EvalContext) -> bool {
+        self.fields.iter().all(|field| field.evaluate_in(ctx))
+    }
+
+    /// All fields of a group must match, so the literals of any single field are required.
+    /// The field whose shortest literal is the longest is chosen as it is the most selective.
+    fn required_literals(&self) -> Option<Vec<String>> {
+        self.fields
+            .iter()
+            .filter_map(|f| f.required_literals())
+            .max_by_key(|literals| literals.iter().map(String::len).min().unwrap_or(0))
+    }
+
+    /// All fields of a group must match, so any field that has to be present is required
+    fn required_field(&self) -> Option<String> {
+        self.fields.iter().find_map(|f| f.required_field())
+    }
+}
+
+/// Serializes the group as a mapping of the keys of its fields to their values
+impl Serialize for FieldGroup {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+        for field in &self.fields {
+            map.serialize_entry(&field.to_string(), &field.to_yaml())?;
+        }
+        map.end()
     }
 }
 
@@ -42,13 +72,68 @@ struct SelectionProxy {
     value: Value,
 }
 
-#[derive(Debug, Deserialize)]
+/// A keyword of a keyword selection together with its compiled case-insensitive pattern
+#[derive(Debug, Clone)]
+pub struct Keyword {
+    pub value: String,
+    pub(crate) pattern: Arc<WildcardPattern>,
+}
+
+impl From<String> for Keyword {
+    fn from(value: String) -> Self {
+        let pattern = config::with_active(|config| {
+            cache::wildcard_pattern(tokenize(&value, true), true, config)
+        });
+        Self { value, pattern }
+    }
+}
+
+impl From<&str> for Keyword {
+    fn from(value: &str) -> Self {
+        Self::from(value.to_string())
+    }
+}
+
+/// The pattern of a keyword is compiled from its value, so keywords are compared by value
+impl PartialEq for Keyword {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl Serialize for Keyword {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.value)
+    }
+}
+
+impl PartialEq<&str> for Keyword {
+    fn eq(&self, other: &&str) -> bool {
+        self.value == *other
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 #[serde(try_from = "SelectionProxy")]
 pub enum Selection {
-    Keyword(Vec<String>),
+    Keyword(Vec<Keyword>),
     Field(Vec<FieldGroup>),
 }
 
+/// Serializes the selection as written in a rule: a list of keywords, a single mapping of
+/// fields or a list of such mappings
+impl Serialize for Selection {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Keyword(keywords) => keywords.serialize(serializer),
+            Self::Field(groups) => match groups.as_slice() {
+                [group] => group.serialize(serializer),
+                groups => groups.serialize(serializer),
+            },
+        }
+    }
+}
+
 impl TryFrom<SelectionProxy> for Selection {
     type Error = ParserError;
 
@@ -68,14 +153,14 @@ impl TryFrom<Value> for Selection {
                         SelectionContainsNoFields(),
                     ));
                 }
-                let is_keyword_selection = !seq[0].is_mapping();
+                let is_keyword_selection = !seq.first().is_some_and(Value::is_mapping);
                 if is_keyword_selection {
                     let mut keywords = vec![];
                     for value in seq.iter() {
                         match value {
-                            Value::String(s) => keywords.push(s.to_string()),
-                            Value::Number(n) => keywords.push(n.to_string()),
-                            Value::Bool(b) => keywords.push(b.to_string()),
+                            Value::String(s) => keywords.push(Keyword::from(s.as_str())),
+                            Value::Number(n) => keywords.push(Keyword::from(n.to_string())),
+                            Value::Bool(b) => keywords.push(Keyword::from(b.to_string())),
                             _ => {
                                 return Err(Self::Error::SelectionParsingError(
                                     String::new(),
@@ -117,11 +202,77 @@ impl TryFrom<Value> for Selection {
 
 impl Selection {
     pub(crate) fn evaluate(&self, event: &Event) -> bool {
+        self.evaluate_in(&EvalContext::new(event))
+    }
+
+    pub(crate) fn evaluate_in(&self, ctx: &EvalContext) -> bool {
         match &self {
-            Self::Keyword(keywords) => event
+            Self::Keyword(keywords) => ctx
+                .event()
                 .values()
-                .any(|v| keywords.iter().any(|kw| v.contains_keyword(kw))),
-            Self::Field(field_groups) => field_groups.iter().any(|g| g.evaluate(event)),
+                .any(|v| keywords.iter().any(|kw| v.contains_keyword(&kw.pattern))),
+            Self::Field(field_groups) => field_groups.iter().any(|g| g.evaluate(ctx)),
+        }
+    }
+
+    /// Iterate over all fields of the selection
+    pub(crate) fn fields(&self) -> impl Iterator<Item = &Field> {
+        let groups: &[FieldGroup] = match self {
+            Self::Keyword(_) => &[],
+            Self::Field(field_groups) => field_groups,
+        };
+        groups.iter().flat_map(|g| &g.fields)
+    }
+
+    /// Iterate over all fields of the selection
+    pub(crate) fn fields_mut(&mut self) -> impl Iterator<Item = &mut Field> {
+        let groups: &mut [FieldGroup] = match self {
+            Self::Keyword(_) => &mut [],
+            Self::Field(field_groups) => field_groups,
+        };
+        groups.iter_mut().flat_map(|g| g.fields.iter_mut())
+    }
+
+    /// The number of keywords or field values of the selection
+    pub(crate) fn value_count(&self) -> usize {
+        match self {
+            Self::Keyword(keywords) => keywords.len(),
+            Self::Field(field_groups) => field_groups
+                .iter()
+                .flat_map(|g| &g.fields)
+                .map(|field| field.values.len())
+                .sum(),
+        }
+    }
+
+    /// Returns a list of lowercase literals of which at least one must be contained in a
+    /// value of the event if the selection matches, see [`Field::required_literals`].
+    pub(crate) fn required_literals(&self) -> Option<Vec<String>> {
+        let mut result = vec![];
+        match &self {
+            Self::Keyword(keywords) => {
+                for keyword in keywords {
+                    result.push(keyword.pattern.required_literal()?);
+                }
+            }
+            Self::Field(field_groups) => {
+                for group in field_groups {
+                    result.extend(group.required_literals()?);
+                }
+            }
+        }
+        Some(result)
+    }
+
+    /// Returns a list of field names of which at least one must be present in the event
+    /// if the selection matches. Returns `None` for keyword selections.
+    pub(crate) fn required_fields(&self) -> Option<Vec<String>> {
+        match &self {
+            Self::Keyword(_) => None,
+            Self::Field(field_groups) => field_groups
+                .iter()
+                .map(FieldGroup::required_field)
+                .collect(),
         }
     }
 }
@@ -136,11 +287,7 @@ mod tests {
 
     #[test]
     fn test_keyword_selection() {
-        let selection = Selection::Keyword(vec![
-            "test".to_string(),
-            "l?nux".to_string(),
-            "arch *".to_string(),
-        ]);
+        let selection = Selection::Keyword(vec!["test".into(), "l?nux".into(), "arch *".into()]);
 
         let event = Event::from([("key", "test")]);
         assert!(selection.evaluate(&event));
@@ -161,6 +308,7 @@ mod tests {
         assert!(!selection.evaluate(&event));
     }
 
+    #[cfg(feature = "cidr")]
     #[test]
     fn test_fields_selection() {
         let selection = Selection::Field(vec![FieldGroup {
@@ -181,6 +329,45 @@ mod tests {
         assert!(!selection.evaluate(&event));
     }
 
+    #[test]
+    fn test_required_literals() {
+        let selection = Selection::Keyword(vec!["Test".into(), "arch *".into()]);
+        assert_eq!(
+            selection.required_literals(),
+            Some(vec!["test".to_string(), "arch ".to_string()])
+        );
+
+        let selection = Selection::Keyword(vec!["test".into(), "*".into()]);
+        assert_eq!(selection.required_literals(), None);
+
+        let selection = Selection::Field(vec![
+            FieldGroup {
+                fields: vec![
+                    Field::new("a|contains", vec![FieldValue::from("xy")]).unwrap(),
+                    Field::new("b|contains", vec![FieldValue::from("long")]).unwrap(),
+                    Field::new("c|re", vec![FieldValue::from("longer")]).unwrap(),
+                ],
+            },
+            FieldGroup {
+                fields: vec![Field::new("d", vec![FieldValue::from("d*")]).unwrap()],
+            },
+        ]);
+        assert_eq!(
+            selection.required_literals(),
+            Some(vec!["long".to_string(), "d".to_string()])
+        );
+
+        let selection = Selection::Field(vec![
+            FieldGroup {
+                fields: vec![Field::new("a", vec![FieldValue::from("a")]).unwrap()],
+            },
+            FieldGroup {
+                fields: vec![Field::new("b", vec![FieldValue::from(1)]).unwrap()],
+            },
+        ]);
+        assert_eq!(selection.required_literals(), None);
+    }
+
     #[test]
     fn test_new_keyword_selection() {
         let keywords = vec!["test".to_string(), "linux".to_string(), "arch".to_string()];
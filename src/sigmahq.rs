@@ -0,0 +1,385 @@
+//! Download pinned releases of the [SigmaHQ](https://github.com/SigmaHQ/sigma) rule repository.
+//!
+//! A [`SigmaRelease`] is identified by its tag, e.g. `r2024-09-02`, and the SHA-256 checksum of
+//! the release archive, so a deployment always evaluates the same rules and a tampered or
+//! truncated download is rejected. Archives are cached, by default below `$XDG_CACHE_HOME` or
+//! `~/.cache`, and only downloaded once.
+//!
+//! # Example
+//! ```rust,no_run
+//! use sigma_rust::sigmahq::{Package, SigmaRelease};
+//!
+//! let release = SigmaRelease::new(
+//!     "r2024-09-02",
+//!     "0000000000000000000000000000000000000000000000000000000000000000",
+//! )
+//! .with_package(Package::CorePlus);
+//! let rules = release.load().unwrap();
+//! for (path, error) in &rules.skipped {
+//!     eprintln!("skipping {}: {}", path, error);
+//! }
+//! let rule_set = rules.into_rule_set();
+//! ```
+use crate::rule::Rule;
+use crate::rule_from_yaml;
+use crate::RuleSet;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+const DEFAULT_BASE_URL: &str = "https://github.com/SigmaHQ/sigma/releases/download";
+
+/// Errors raised when a release cannot be downloaded or loaded
+#[derive(Debug, thiserror::Error)]
+pub enum FetchError {
+    #[error("Invalid release tag '{0}'")]
+    InvalidTag(String),
+
+    #[error("Invalid SHA-256 checksum '{0}', expected 64 hexadecimal digits")]
+    InvalidChecksum(String),
+
+    #[error("Failed to download {0}: {1}")]
+    Http(String, String),
+
+    #[error("Checksum mismatch of {url}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("Failed to read the release archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// The rule packages published with every SigmaHQ release
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Package {
+    /// Stable rules of high and critical level
+    #[default]
+    Core,
+    /// The core rules and stable rules of medium level
+    CorePlus,
+    /// The core+ rules and experimental and test rules
+    CorePlusPlus,
+    /// Rules for specific threats, e.g. malware campaigns or exploits
+    EmergingThreats,
+    /// All rules, including threat hunting rules
+    All,
+}
+
+impl Package {
+    /// The file name of the release asset
+    pub fn asset(&self) -> &'static str {
+        match self {
+            Self::Core => "sigma_core.zip",
+            Self::CorePlus => "sigma_core+.zip",
+            Self::CorePlusPlus => "sigma_core++.zip",
+            Self::EmergingThreats => "sigma_emerging_threats_addon.zip",
+            Self::All => "sigma_all_rules.zip",
+        }
+    }
+}
+
+/// A pinned SigmaHQ release, see the [module documentation](self)
+#[derive(Debug, Clone)]
+pub struct SigmaRelease {
+    tag: String,
+    sha256: String,
+    package: Package,
+    cache_dir: Option<PathBuf>,
+    base_url: String,
+}
+
+/// The rules of a release
+#[derive(Debug)]
+pub struct ReleaseRules {
+    pub rules: Vec<Rule>,
+    /// The paths within the archive and errors of the rules that could not be parsed
+    pub skipped: Vec<(String, String)>,
+}
+
+impl ReleaseRules {
+    pub fn into_rule_set(self) -> RuleSet {
+        RuleSet::new(self.rules)
+    }
+}
+
+impl SigmaRelease {
+    /// Pin a release by its tag and the SHA-256 checksum of the archive of the package, which
+    /// is [`Package::Core`] unless changed with [`SigmaRelease::with_package`]
+    pub fn new(tag: &str, sha256: &str) -> Self {
+        Self {
+            tag: tag.to_string(),
+            sha256: sha256.to_ascii_lowercase(),
+            package: Package::default(),
+            cache_dir: None,
+            base_url: DEFAULT_BASE_URL.to_string(),
+        }
+    }
+
+    /// Download another package of the release. The checksum must be the one of its archive.
+    pub fn with_package(mut self, package: Package) -> Self {
+        self.package = package;
+        self
+    }
+
+    /// Cache the archive in this directory instead of the user's cache directory
+    pub fn with_cache_dir<P: Into<PathBuf>>(mut self, cache_dir: P) -> Self {
+        self.cache_dir = Some(cache_dir.into());
+        self
+    }
+
+    /// Download from a mirror that has the same layout as the GitHub releases, i.e.
+    /// `<base_url>/<tag>/<asset>`
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// The URL the archive is downloaded from
+    pub fn url(&self) -> String {
+        format!("{}/{}/{}", self.base_url, self.tag, self.package.asset())
+    }
+
+    /// Return the path of the cached archive, downloading it if it is not cached yet or the
+    /// cached file does not match the checksum
+    pub fn fetch(&self) -> Result<PathBuf, FetchError> {
+        if self.tag.is_empty()
+            || !self
+                .tag
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            || self.tag.starts_with('.')
+        {
+            return Err(FetchError::InvalidTag(self.tag.clone()));
+        }
+        if self.sha256.len() != 64 || !self.sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(FetchError::InvalidChecksum(self.sha256.clone()));
+        }
+
+        let dir = self.cache_dir.clone().unwrap_or_else(default_cache_dir);
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}-{}", self.tag, self.package.asset()));
+        if path.is_file() && sha256_file(&path)? == self.sha256 {
+            return Ok(path);
+        }
+
+        // Download next to the final path, so the rename is atomic and concurrent processes
+        // never see a partial archive
+        let url = self.url();
+        let partial = path.with_extension(format!("part{}", std::process::id()));
+        let result = download(&url, &partial).and_then(|actual| {
+            if actual == self.sha256 {
+                Ok(fs::rename(&partial, &path)?)
+            } else {
+                Err(FetchError::ChecksumMismatch {
+                    url: url.clone(),
+                    expected: self.sha256.clone(),
+                    actual,
+                })
+            }
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        result.map(|_| path)
+    }
+
+    /// Fetch the release and parse all of its rules. Rules that cannot be parsed are skipped
+    /// and reported in [`ReleaseRules::skipped`].
+    pub fn load(&self) -> Result<ReleaseRules, FetchError> {
+        let mut archive = zip::ZipArchive::new(File::open(self.fetch()?)?)?;
+        let mut names: Vec<String> = archive
+            .file_names()
+            .filter(|name| name.ends_with(".yml") || name.ends_with(".yaml"))
+            .map(str::to_string)
+            .collect();
+        names.sort();
+
+        let mut result = ReleaseRules {
+            rules: vec![],
+            skipped: vec![],
+        };
+        for name in names {
+            let mut yaml = String::new();
+            archive.by_name(&name)?.read_to_string(&mut yaml)?;
+            match rule_from_yaml(&yaml) {
+                Ok(rule) => result.rules.push(rule),
+                Err(err) => result.skipped.push((name, err.to_string())),
+            }
+        }
+        Ok(result)
+    }
+}
+
+fn default_cache_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir);
+    base.join("sigma-rust")
+}
+
+/// Download the URL into a file and return the SHA-256 checksum of its content
+fn download(url: &str, path: &PathBuf) -> Result<String, FetchError> {
+    let response = ureq::get(url)
+        .call()
+        .map_err(|e| FetchError::Http(url.to_string(), e.to_string()))?;
+    let mut reader = response.into_reader();
+    let mut file = File::create(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        file.write_all(&buffer[..n])?;
+    }
+    file.sync_all()?;
+    Ok(hex(&hasher.finalize()))
+}
+
+fn sha256_file(path: &PathBuf) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Event;
+    use std::io::{BufRead, BufReader, Cursor};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn archive() -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("rules/windows/whoami.yml", options).unwrap();
+        zip.write_all(
+            br#"
+title: Whoami
+logsource:
+    category: process_creation
+detection:
+    selection:
+        Image|endswith: '\whoami.exe'
+    condition: selection
+"#,
+        )
+        .unwrap();
+        zip.start_file("rules/windows/broken.yml", options).unwrap();
+        zip.write_all(b"title: Broken").unwrap();
+        zip.start_file("README.md", options).unwrap();
+        zip.write_all(b"not a rule").unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    /// Serve the body to any number of requests and count them
+    fn serve(body: Vec<u8>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sigmahq-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_load() {
+        let archive = archive();
+        let sha256 = hex(&Sha256::digest(&archive));
+        let (url, requests) = serve(archive);
+        let dir = cache_dir("load");
+
+        let release = SigmaRelease::new("r2024-09-02", &sha256.to_uppercase())
+            .with_cache_dir(&dir)
+            .with_base_url(&format!("{}/", url));
+        assert_eq!(release.url(), format!("{}/r2024-09-02/sigma_core.zip", url));
+
+        let rules = release.load().unwrap();
+        assert_eq!(rules.rules.len(), 1);
+        assert_eq!(rules.skipped.len(), 1);
+        assert_eq!(rules.skipped[0].0, "rules/windows/broken.yml");
+        let rule_set = rules.into_rule_set();
+        let event = Event::from([("Image", "C:\\whoami.exe")]);
+        assert_eq!(rule_set.evaluate(&event).len(), 1);
+
+        // The archive is cached
+        assert!(dir.join("r2024-09-02-sigma_core.zip").is_file());
+        release.load().unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // A corrupted cache is downloaded again
+        fs::write(dir.join("r2024-09-02-sigma_core.zip"), "corrupted").unwrap();
+        release.load().unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let (url, _) = serve(archive());
+        let dir = cache_dir("mismatch");
+        let release = SigmaRelease::new("r2024-09-02", &"0".repeat(64))
+            .with_package(Package::All)
+            .with_cache_dir(&dir)
+            .with_base_url(&url);
+
+        let err = release.fetch().unwrap_err();
+        assert!(matches!(err, FetchError::ChecksumMismatch { .. }));
+        // Neither the archive nor the partial download is kept
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_pins() {
+        let release = SigmaRelease::new("../etc", &"0".repeat(64));
+        assert!(matches!(release.fetch(), Err(FetchError::InvalidTag(_))));
+
+        let release = SigmaRelease::new("r2024-09-02", "abc");
+        assert!(matches!(
+            release.fetch(),
+            Err(FetchError::InvalidChecksum(_))
+        ));
+    }
+}
@@ -0,0 +1,410 @@
+//! Destinations for the matches of a rule set.
+//!
+//! An [`AlertSink`] receives match results one at a time, e.g. from
+//! [`crate::MatchStream::send_to`] or a [`crate::routing::Router`] of sinks. Implement the trait
+//! to deliver alerts to a custom destination. The following sinks are built in:
+//! * [`JsonLinesSink`] writes one JSON document per match and line to stdout, a file or any
+//!   writer (requires the `serde_json` feature)
+//! * [`WebhookSink`] posts every match as a JSON document to an HTTP endpoint (requires the
+//!   `webhook` feature)
+//!
+//! Wrap a sink in a [`crate::ratelimit::RateLimitedSink`] to limit the number of alerts of noisy
+//! rules, or collapse repeated matches with a [`crate::dedup::Deduplicator`] before sending them.
+//! A `Vec<MatchResult>` collects the matches it receives.
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::sink::{AlertSink, SinkError};
+//! use sigma_rust::{rule_from_yaml, Event, MatchResult, RuleSet};
+//!
+//! /// Keeps the titles of the matched rules
+//! struct Titles(Vec<String>);
+//!
+//! impl AlertSink for Titles {
+//!     fn send(&mut self, result: &MatchResult) -> Result<(), SinkError> {
+//!         self.0.push(result.rule_title.clone());
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let rule = rule_from_yaml(
+//!     r#"
+//! title: Whoami
+//! logsource:
+//! detection:
+//!     selection:
+//!         Image|endswith: '\whoami.exe'
+//!     condition: selection
+//! "#,
+//! )
+//! .unwrap();
+//! let rule_set = RuleSet::new(vec![rule]);
+//!
+//! let mut sink = Titles(vec![]);
+//! let events = vec![Event::from([("Image", "C:\\whoami.exe")])];
+//! assert_eq!(rule_set.stream(events.into_iter()).send_to(&mut sink).unwrap(), 1);
+//! assert_eq!(sink.0, ["Whoami"]);
+//! ```
+use crate::ratelimit::Overflow;
+use crate::result::MatchResult;
+use std::io;
+
+/// Errors raised when a sink cannot deliver a match
+#[derive(Debug, thiserror::Error)]
+pub enum SinkError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error("Failed to post to {0}: {1}")]
+    Http(String, String),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// A destination for match results
+pub trait AlertSink {
+    /// Deliver a match. Sinks may buffer it until [`AlertSink::flush`] is called.
+    fn send(&mut self, result: &MatchResult) -> Result<(), SinkError>;
+
+    /// Deliver the summary of matches that were suppressed by a rate limit, see
+    /// [`crate::ratelimit`]. Sinks that cannot represent summaries ignore them.
+    fn send_overflow(&mut self, overflow: &Overflow) -> Result<(), SinkError> {
+        let _ = overflow;
+        Ok(())
+    }
+
+    /// Deliver all buffered matches
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+impl<S: AlertSink + ?Sized> AlertSink for Box<S> {
+    fn send(&mut self, result: &MatchResult) -> Result<(), SinkError> {
+        (**self).send(result)
+    }
+
+    fn send_overflow(&mut self, overflow: &Overflow) -> Result<(), SinkError> {
+        (**self).send_overflow(overflow)
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        (**self).flush()
+    }
+}
+
+impl<S: AlertSink + ?Sized> AlertSink for &mut S {
+    fn send(&mut self, result: &MatchResult) -> Result<(), SinkError> {
+        (**self).send(result)
+    }
+
+    fn send_overflow(&mut self, overflow: &Overflow) -> Result<(), SinkError> {
+        (**self).send_overflow(overflow)
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        (**self).flush()
+    }
+}
+
+/// Collects the matches, e.g. for tests
+impl AlertSink for Vec<MatchResult> {
+    fn send(&mut self, result: &MatchResult) -> Result<(), SinkError> {
+        self.push(result.clone());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde_json")]
+mod json_lines {
+    use super::{AlertSink, SinkError};
+    use crate::output::OutputFormat;
+    use crate::ratelimit::Overflow;
+    use crate::result::MatchResult;
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, BufWriter, Write};
+    use std::path::Path;
+
+    /// A sink that writes matches to stdout
+    pub type StdoutSink = JsonLinesSink<io::Stdout>;
+
+    /// A sink that appends matches to a file
+    pub type FileSink = JsonLinesSink<BufWriter<File>>;
+
+    /// Writes one JSON document per match and line. By default, the document is
+    /// [`MatchResult::to_json`], use [`JsonLinesSink::with_format`] to write ECS or OCSF alerts.
+    #[derive(Debug)]
+    pub struct JsonLinesSink<W> {
+        writer: W,
+        format: Option<OutputFormat>,
+    }
+
+    impl<W: Write> JsonLinesSink<W> {
+        pub fn new(writer: W) -> Self {
+            Self {
+                writer,
+                format: None,
+            }
+        }
+
+        /// Write the matches in an alert format
+        pub fn with_format(mut self, format: OutputFormat) -> Self {
+            self.format = Some(format);
+            self
+        }
+
+        pub fn get_ref(&self) -> &W {
+            &self.writer
+        }
+
+        pub fn into_inner(self) -> W {
+            self.writer
+        }
+    }
+
+    impl StdoutSink {
+        pub fn stdout() -> Self {
+            Self::new(io::stdout())
+        }
+    }
+
+    impl FileSink {
+        /// Append to the file at `path`, which is created if it does not exist
+        pub fn file<P: AsRef<Path>>(path: P) -> Result<Self, SinkError> {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            Ok(Self::new(BufWriter::new(file)))
+        }
+    }
+
+    impl<W: Write> AlertSink for JsonLinesSink<W> {
+        fn send(&mut self, result: &MatchResult) -> Result<(), SinkError> {
+            let document = match self.format {
+                Some(format) => format.render(result),
+                None => result.to_json(),
+            };
+            writeln!(self.writer, "{}", document)?;
+            Ok(())
+        }
+
+        fn send_overflow(&mut self, overflow: &Overflow) -> Result<(), SinkError> {
+            writeln!(self.writer, "{}", overflow.to_json())?;
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), SinkError> {
+            Ok(self.writer.flush()?)
+        }
+    }
+}
+
+#[cfg(feature = "serde_json")]
+pub use json_lines::{FileSink, JsonLinesSink, StdoutSink};
+
+#[cfg(feature = "webhook")]
+mod webhook {
+    use super::{AlertSink, SinkError};
+    use crate::output::OutputFormat;
+    use crate::ratelimit::Overflow;
+    use crate::result::MatchResult;
+    use std::time::Duration;
+
+    const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Posts every match as a JSON document to an HTTP endpoint, e.g. a Slack or SOAR webhook.
+    /// The document is the same as written by [`super::JsonLinesSink`].
+    pub struct WebhookSink {
+        url: String,
+        headers: Vec<(String, String)>,
+        format: Option<OutputFormat>,
+        agent: ureq::Agent,
+    }
+
+    impl WebhookSink {
+        pub fn new<S: Into<String>>(url: S) -> Self {
+            Self {
+                url: url.into(),
+                headers: vec![],
+                format: None,
+                agent: agent(DEFAULT_TIMEOUT),
+            }
+        }
+
+        /// Post the matches in an alert format
+        pub fn with_format(mut self, format: OutputFormat) -> Self {
+            self.format = Some(format);
+            self
+        }
+
+        /// Send a header with every request, e.g. for authorization
+        pub fn with_header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+            self.headers.push((name.into(), value.into()));
+            self
+        }
+
+        /// The timeout of a request, 10 seconds by default
+        pub fn with_timeout(mut self, timeout: Duration) -> Self {
+            self.agent = agent(timeout);
+            self
+        }
+    }
+
+    // The values of the headers are often credentials and must not end up in logs
+    impl std::fmt::Debug for WebhookSink {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let headers: Vec<(&str, &str)> = self
+                .headers
+                .iter()
+                .map(|(name, _)| (name.as_str(), "<redacted>"))
+                .collect();
+            f.debug_struct("WebhookSink")
+                .field("url", &self.url)
+                .field("headers", &headers)
+                .field("format", &self.format)
+                .finish_non_exhaustive()
+        }
+    }
+
+    fn agent(timeout: Duration) -> ureq::Agent {
+        ureq::AgentBuilder::new().timeout(timeout).build()
+    }
+
+    impl WebhookSink {
+        fn post(&self, document: serde_json::Value) -> Result<(), SinkError> {
+            let mut request = self
+                .agent
+                .post(&self.url)
+                .set("Content-Type", "application/json");
+            for (name, value) in &self.headers {
+                request = request.set(name, value);
+            }
+            request
+                .send_string(&document.to_string())
+                .map_err(|e| SinkError::Http(self.url.clone(), e.to_string()))?;
+            Ok(())
+        }
+    }
+
+    impl AlertSink for WebhookSink {
+        fn send(&mut self, result: &MatchResult) -> Result<(), SinkError> {
+            self.post(match self.format {
+                Some(format) => format.render(result),
+                None => result.to_json(),
+            })
+        }
+
+        fn send_overflow(&mut self, overflow: &Overflow) -> Result<(), SinkError> {
+            self.post(overflow.to_json())
+        }
+    }
+}
+
+#[cfg(feature = "webhook")]
+pub use webhook::WebhookSink;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result::whoami_match;
+
+    #[test]
+    fn test_boxed() {
+        let mut received: Vec<MatchResult> = vec![];
+        let mut sinks: Vec<Box<dyn AlertSink + '_>> = vec![Box::new(&mut received)];
+        for sink in sinks.iter_mut() {
+            sink.send(&whoami_match("ws-01")).unwrap();
+            sink.flush().unwrap();
+        }
+        drop(sinks);
+        assert_eq!(received, [whoami_match("ws-01")]);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_json_lines() {
+        use crate::output::OutputFormat;
+
+        let mut sink = JsonLinesSink::new(vec![]);
+        sink.send(&whoami_match("ws-01")).unwrap();
+        sink.send(&whoami_match("ws-01")).unwrap();
+        let out = String::from_utf8(sink.into_inner()).unwrap();
+        let lines: Vec<serde_json::Value> = out
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], whoami_match("ws-01").to_json());
+
+        let mut sink = JsonLinesSink::new(vec![]).with_format(OutputFormat::Ecs);
+        sink.send(&whoami_match("ws-01")).unwrap();
+        let ecs: serde_json::Value = serde_json::from_slice(sink.get_ref()).unwrap();
+        assert_eq!(ecs["rule"]["name"], "Whoami");
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_file() {
+        let path = std::env::temp_dir().join(format!("tau-sink-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        for _ in 0..2 {
+            let mut sink = FileSink::file(&path).unwrap();
+            sink.send(&whoami_match("ws-01")).unwrap();
+            sink.flush().unwrap();
+        }
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written.lines().count(), 2);
+    }
+
+    #[cfg(feature = "webhook")]
+    #[test]
+    fn test_webhook() {
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut headers = vec![];
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                headers.push(line.trim().to_lowercase());
+            }
+            let length: usize = headers
+                .iter()
+                .find_map(|h| h.strip_prefix("content-length: "))
+                .unwrap()
+                .parse()
+                .unwrap();
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            (headers, body)
+        });
+
+        let mut sink = WebhookSink::new(&url).with_header("Authorization", "Bearer token");
+        let debug = format!("{:?}", sink);
+        assert!(debug.contains("Authorization") && !debug.contains("token"));
+        sink.send(&whoami_match("ws-01")).unwrap();
+        let (headers, body) = server.join().unwrap();
+        assert_eq!(headers[0], "post /alerts http/1.1");
+        assert!(headers.contains(&"authorization: bearer token".to_string()));
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, whoami_match("ws-01").to_json());
+
+        // Nothing listens on the port anymore
+        assert!(matches!(
+            sink.send(&whoami_match("ws-01")),
+            Err(SinkError::Http(failed, _)) if failed == url
+        ));
+    }
+}
@@ -0,0 +1,537 @@
+//! Binary snapshots of compiled rule sets for fast cold starts.
+//!
+//! Parsing thousands of YAML rules and expanding their `base64offset` and `windash` values
+//! dominates the startup time of large deployments. A snapshot stores the rules after these
+//! steps, i.e. the expanded values, the wildcard tokens, the sources of the regular expressions
+//! and the parsed CIDRs, encoded with [postcard](https://docs.rs/postcard). Restoring a
+//! snapshot only compiles the regular expressions and the prefilter of the rule set.
+//!
+//! Snapshots are meant as a cache next to the rule files, not as an exchange format: a snapshot
+//! written by another version of this crate is rejected with
+//! [`SnapshotError::UnsupportedVersion`] and should be rebuilt from the rules.
+//!
+//! Rules disabled with [`RuleSet::set_enabled`] stay disabled when the snapshot is restored.
+//! The taxonomy of a rule set and condition orders of an applied profile are not part of the
+//! snapshot. Apply them again with [`RuleSet::with_taxonomy`] and [`RuleSet::apply_profile`].
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::{rule_from_yaml, Event, RuleSet};
+//!
+//! let rule = rule_from_yaml(
+//!     r#"
+//! title: Encoded whoami
+//! logsource:
+//!     category: process_creation
+//! detection:
+//!     selection:
+//!         CommandLine|base64offset|contains: whoami
+//!     condition: selection
+//! "#,
+//! )
+//! .unwrap();
+//!
+//! let bytes = RuleSet::new(vec![rule]).to_snapshot().unwrap();
+//! let rule_set = RuleSet::from_snapshot(&bytes).unwrap();
+//! let event = Event::from([("CommandLine", "powershell -e d2hvYW1p")]);
+//! assert_eq!(rule_set.evaluate(&event).len(), 1);
+//! ```
+use crate::basevalue::BaseValue;
+use crate::cache;
+use crate::config::{self, SpecMode};
+use crate::detection::Detection;
+use crate::field::{Field, FieldValue, Modifier};
+use crate::rule::{Level, Logsource, Related, Rule, Status};
+use crate::ruleset::RuleSet;
+use crate::selection::{FieldGroup, Keyword, Selection};
+use crate::wildcard::WildcardToken;
+#[cfg(feature = "cidr")]
+use cidr::IpCidr;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Identifies snapshots written by this crate
+const MAGIC: &[u8; 4] = b"SGRS";
+
+/// Incremented whenever the layout of the snapshot changes
+const FORMAT_VERSION: u32 = 8;
+
+/// The errors that can occur when writing or restoring a snapshot
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("The data is not a rule set snapshot")]
+    InvalidHeader,
+    #[error("Unsupported snapshot format version {0}, expected {FORMAT_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("Failed to encode or decode the snapshot: {0}")]
+    Encoding(#[from] postcard::Error),
+    #[error("Failed to restore rule '{0}': {1}")]
+    InvalidRule(String, String),
+}
+
+#[derive(Serialize, Deserialize)]
+struct RuleSetSnapshot {
+    rules: Vec<RuleSnapshot>,
+    /// Whether each rule was disabled with [`RuleSet::set_enabled`]
+    disabled: Vec<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RuleSnapshot {
+    title: String,
+    id: Option<String>,
+    name: Option<String>,
+    related: Option<Vec<Related>>,
+    taxonomy: Option<String>,
+    status: Option<Status>,
+    description: Option<String>,
+    license: Option<String>,
+    author: Option<String>,
+    references: Option<Vec<String>>,
+    date: Option<String>,
+    modified: Option<String>,
+    logsource: Logsource,
+    selections: Vec<(String, SelectionSnapshot)>,
+    condition: String,
+    fields: Option<Vec<String>>,
+    falsepositives: Option<Vec<String>>,
+    level: Option<Level>,
+    tags: Option<Vec<String>>,
+    spec_mode: SpecMode,
+    /// The custom fields as YAML, as their values cannot be decoded from a binary format
+    custom_fields: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum SelectionSnapshot {
+    Keyword(Vec<(String, Vec<WildcardToken>)>),
+    Field(Vec<Vec<FieldSnapshot>>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct FieldSnapshot {
+    name: String,
+    modifier: Modifier,
+    values: Vec<ValueSnapshot>,
+    regex_max_input_len: Option<usize>,
+    source: Vec<BaseValue>,
+    numbers: Vec<BaseValue>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum ValueSnapshot {
+    Base(BaseValue),
+    WildcardPattern(Vec<WildcardToken>),
+    Regex(String),
+    #[cfg(feature = "cidr")]
+    Cidr(String),
+}
+
+impl RuleSet {
+    /// Encode the compiled rules into a binary snapshot, see [`crate::snapshot`]
+    pub fn to_snapshot(&self) -> Result<Vec<u8>, SnapshotError> {
+        let snapshot = RuleSetSnapshot {
+            rules: self
+                .rules()
+                .iter()
+                .map(RuleSnapshot::try_from)
+                .collect::<Result<_, _>>()?,
+            disabled: self.disabled().to_vec(),
+        };
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        Ok(postcard::to_extend(&snapshot, bytes)?)
+    }
+
+    /// Restore a rule set from a snapshot written by [`RuleSet::to_snapshot`]. Regular
+    /// expressions and wildcard patterns are compiled with the default [`crate::EngineConfig`],
+    /// use [`crate::EngineConfig::rule_set_from_snapshot`] to change the limits.
+    pub fn from_snapshot(bytes: &[u8]) -> Result<RuleSet, SnapshotError> {
+        let payload = bytes
+            .strip_prefix(MAGIC.as_slice())
+            .ok_or(SnapshotError::InvalidHeader)?;
+        let (version, payload) = payload
+            .split_first_chunk::<4>()
+            .ok_or(SnapshotError::InvalidHeader)?;
+        let version = u32::from_le_bytes(*version);
+        if version != FORMAT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let snapshot: RuleSetSnapshot = postcard::from_bytes(payload)?;
+        let rules = snapshot
+            .rules
+            .into_iter()
+            .map(Rule::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(RuleSet::new(rules).with_disabled(snapshot.disabled))
+    }
+}
+
+impl TryFrom<&Rule> for RuleSnapshot {
+    type Error = SnapshotError;
+
+    fn try_from(rule: &Rule) -> Result<Self, Self::Error> {
+        let custom_fields = if rule.custom_fields.is_empty() {
+            None
+        } else {
+            Some(
+                serde_yml::to_string(&rule.custom_fields)
+                    .map_err(|e| SnapshotError::InvalidRule(rule.title.clone(), e.to_string()))?,
+            )
+        };
+        let mut selections: Vec<(String, SelectionSnapshot)> = rule
+            .detection
+            .get_selections()
+            .iter()
+            .map(|(name, selection)| (name.clone(), SelectionSnapshot::from(selection)))
+            .collect();
+        // Make snapshots of the same rules byte for byte identical
+        selections.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(Self {
+            title: rule.title.clone(),
+            id: rule.id.clone(),
+            name: rule.name.clone(),
+            related: rule.related.as_ref().map(|related| {
+                related
+                    .iter()
+                    .map(|r| Related {
+                        id: r.id.clone(),
+                        related_type: r.related_type,
+                    })
+                    .collect()
+            }),
+            taxonomy: rule.taxonomy.clone(),
+            status: rule.status,
+            description: rule.description.clone(),
+            license: rule.license.clone(),
+            author: rule.author.clone(),
+            references: rule.references.clone(),
+            date: rule.date.clone(),
+            modified: rule.modified.clone(),
+            logsource: rule.logsource.clone(),
+            selections,
+            condition: rule.detection.get_condition().to_string(),
+            fields: rule.fields.clone(),
+            falsepositives: rule.falsepositives.clone(),
+            level: rule.level,
+            tags: rule.tags.clone(),
+            spec_mode: rule.spec_mode,
+            custom_fields,
+        })
+    }
+}
+
+impl TryFrom<RuleSnapshot> for Rule {
+    type Error = SnapshotError;
+
+    fn try_from(snapshot: RuleSnapshot) -> Result<Self, Self::Error> {
+        let title = snapshot.title;
+        let error = |message: String| SnapshotError::InvalidRule(title.clone(), message);
+
+        let mut selections = HashMap::with_capacity(snapshot.selections.len());
+        for (name, selection) in snapshot.selections {
+            selections.insert(name, selection.restore().map_err(error)?);
+        }
+        let detection =
+            Detection::new(selections, snapshot.condition).map_err(|e| error(e.to_string()))?;
+        let custom_fields = match snapshot.custom_fields {
+            Some(yaml) => serde_yml::from_str(&yaml).map_err(|e| error(e.to_string()))?,
+            None => HashMap::new(),
+        };
+
+        Ok(Rule {
+            title,
+            id: snapshot.id,
+            name: snapshot.name,
+            related: snapshot.related,
+            taxonomy: snapshot.taxonomy,
+            status: snapshot.status,
+            description: snapshot.description,
+            license: snapshot.license,
+            author: snapshot.author,
+            references: snapshot.references,
+            date: snapshot.date,
+            modified: snapshot.modified,
+            logsource: snapshot.logsource,
+            detection,
+            fields: snapshot.fields,
+            falsepositives: snapshot.falsepositives,
+            level: snapshot.level,
+            tags: snapshot.tags,
+            spec_mode: snapshot.spec_mode,
+            custom_fields,
+        })
+    }
+}
+
+impl From<&Selection> for SelectionSnapshot {
+    fn from(selection: &Selection) -> Self {
+        match selection {
+            Selection::Keyword(keywords) => Self::Keyword(
+                keywords
+                    .iter()
+                    .map(|keyword| (keyword.value.clone(), keyword.pattern.to_vec()))
+                    .collect(),
+            ),
+            Selection::Field(groups) => Self::Field(
+                groups
+                    .iter()
+                    .map(|group| group.fields.iter().map(FieldSnapshot::from).collect())
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl SelectionSnapshot {
+    fn restore(self) -> Result<Selection, String> {
+        match self {
+            Self::Keyword(keywords) => Ok(Selection::Keyword(
+                keywords
+                    .into_iter()
+                    .map(|(value, tokens)| Keyword {
+                        value,
+                        pattern: config::with_active(|config| {
+                            cache::wildcard_pattern(tokens, true, config)
+                        }),
+                    })
+                    .collect(),
+            )),
+            Self::Field(groups) => {
+                let mut result = Vec::with_capacity(groups.len());
+                for fields in groups {
+                    let fields = fields
+                        .into_iter()
+                        .map(FieldSnapshot::restore)
+                        .collect::<Result<_, _>>()?;
+                    result.push(FieldGroup { fields });
+                }
+                Ok(Selection::Field(result))
+            }
+        }
+    }
+}
+
+impl From<&Field> for FieldSnapshot {
+    fn from(field: &Field) -> Self {
+        Self {
+            name: field.name.clone(),
+            modifier: field.modifier.clone(),
+            values: field
+                .values
+                .iter()
+                .map(|value| match value {
+                    FieldValue::Base(base) => ValueSnapshot::Base(base.clone()),
+                    FieldValue::WildcardPattern(pattern) => {
+                        ValueSnapshot::WildcardPattern(pattern.to_vec())
+                    }
+                    FieldValue::Regex(regex) => ValueSnapshot::Regex(regex.as_str().to_string()),
+                    #[cfg(feature = "cidr")]
+                    FieldValue::Cidr(cidr) => ValueSnapshot::Cidr(cidr.to_string()),
+                })
+                .collect(),
+            regex_max_input_len: field.regex_max_input_len,
+            source: field.source.clone(),
+            numbers: field.numbers.clone(),
+        }
+    }
+}
+
+impl FieldSnapshot {
+    fn restore(self) -> Result<Field, String> {
+        let lowercase = !self.modifier.cased;
+        let values = config::with_active(|config| {
+            self.values
+                .into_iter()
+                .map(|value| {
+                    Ok(match value {
+                        ValueSnapshot::Base(base) => FieldValue::Base(base),
+                        ValueSnapshot::WildcardPattern(tokens) => FieldValue::WildcardPattern(
+                            cache::wildcard_pattern(tokens, lowercase, config),
+                        ),
+                        ValueSnapshot::Regex(pattern) => FieldValue::Regex(
+                            cache::regex(&pattern, config).map_err(|e| e.to_string())?,
+                        ),
+                        #[cfg(feature = "cidr")]
+                        ValueSnapshot::Cidr(cidr) => {
+                            FieldValue::Cidr(cidr.parse::<IpCidr>().map_err(|e| e.to_string())?)
+                        }
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()
+        })?;
+        Field::from_compiled(
+            self.name,
+            self.modifier,
+            values,
+            self.regex_max_input_len,
+            self.source,
+            self.numbers,
+        )
+        .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Event;
+    use crate::rule_from_yaml;
+
+    const RULE: &str = r#"
+title: Suspicious command
+id: 5f6a
+status: test
+level: high
+tags:
+    - attack.execution
+logsource:
+    category: process_creation
+    product: windows
+detection:
+    keywords:
+        - '*mimikatz*'
+    encoded:
+        CommandLine|base64offset|contains: whoami
+    flags:
+        CommandLine|windash|contains: ' -enc '
+    regex:
+        Image|re:
+            - '(?i)\\powershell\.exe$'
+            - '(?i)\\pwsh\.exe$'
+    network:
+        DestinationIp|cidr: 10.0.0.0/8
+    cased:
+        User|cased: Admin*
+    condition: keywords or encoded or flags or (regex and network) or cased
+custom: value
+"#;
+
+    fn round_trip(rules: Vec<Rule>) -> RuleSet {
+        let bytes = RuleSet::new(rules).to_snapshot().unwrap();
+        RuleSet::from_snapshot(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let rule_set = round_trip(vec![rule_from_yaml(RULE).unwrap()]);
+        let rule = &rule_set.rules()[0];
+        assert_eq!(rule.title, "Suspicious command");
+        assert_eq!(rule.id.as_deref(), Some("5f6a"));
+        assert_eq!(rule.status, Some(Status::Test));
+        assert_eq!(rule.level, Some(Level::High));
+        assert_eq!(rule.logsource.product.as_deref(), Some("windows"));
+        assert_eq!(rule.custom_fields["custom"], "value");
+        assert_eq!(rule.detection.get_selections().len(), 6);
+
+        let events = [
+            (Event::from([("CommandLine", "run MIMIKATZ now")]), true),
+            (
+                Event::from([("CommandLine", "powershell -e d2hvYW1p")]),
+                true,
+            ),
+            (Event::from([("CommandLine", "powershell /enc AAAA")]), true),
+            (Event::from([("CommandLine", "powershell -e AAAA")]), false),
+            (
+                Event::from([("Image", "C:\\pwsh.exe"), ("DestinationIp", "10.1.2.3")]),
+                true,
+            ),
+            (
+                Event::from([("Image", "C:\\pwsh.exe"), ("DestinationIp", "11.1.2.3")]),
+                false,
+            ),
+            (Event::from([("User", "Administrator")]), true),
+            (Event::from([("User", "administrator")]), false),
+        ];
+        for (event, expected) in events {
+            assert_eq!(
+                rule_set.evaluate(&event).len() == 1,
+                expected,
+                "{:?}",
+                event
+            );
+        }
+    }
+
+    #[test]
+    fn test_disabled_rules() {
+        let mut rule_set = RuleSet::new(vec![rule_from_yaml(RULE).unwrap()]);
+        assert!(rule_set.set_enabled("5f6a", false));
+        let rule_set = RuleSet::from_snapshot(&rule_set.to_snapshot().unwrap()).unwrap();
+        assert_eq!(rule_set.is_enabled("5f6a"), Some(false));
+        let event = Event::from([("CommandLine", "run MIMIKATZ now")]);
+        assert!(rule_set.evaluate(&event).is_empty());
+    }
+
+    #[test]
+    fn test_snapshots_are_deterministic() {
+        let a = RuleSet::new(vec![rule_from_yaml(RULE).unwrap()]);
+        let b = round_trip(vec![rule_from_yaml(RULE).unwrap()]);
+        assert_eq!(a.to_snapshot().unwrap(), b.to_snapshot().unwrap());
+    }
+
+    #[test]
+    fn test_engine_config() {
+        let bytes = RuleSet::new(vec![rule_from_yaml(RULE).unwrap()])
+            .to_snapshot()
+            .unwrap();
+        let config = crate::EngineConfig {
+            regex_size_limit: 1,
+            ..Default::default()
+        };
+        let err = config.rule_set_from_snapshot(&bytes).unwrap_err();
+        assert!(
+            matches!(err, SnapshotError::InvalidRule(ref title, _) if title == "Suspicious command")
+        );
+    }
+
+    #[test]
+    fn test_placeholders() {
+        use crate::placeholder::{PlaceholderList, Placeholders};
+
+        let admins = PlaceholderList::new("admins", ["root"]);
+        let mut placeholders = Placeholders::new();
+        placeholders.insert(admins.clone());
+        let config = crate::EngineConfig {
+            placeholders,
+            ..Default::default()
+        };
+        let rule = config
+            .rule_from_yaml(&RULE.replace("User|cased: Admin*", "User|expand: '%admins%'"))
+            .unwrap();
+        let bytes = RuleSet::new(vec![rule]).to_snapshot().unwrap();
+
+        assert!(RuleSet::from_snapshot(&bytes).is_err());
+        let rule_set = config.rule_set_from_snapshot(&bytes).unwrap();
+        let event = Event::from([("User", "admin")]);
+        assert!(rule_set.evaluate(&event).is_empty());
+        admins.set(["admin"]);
+        assert_eq!(rule_set.evaluate(&event).len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_snapshots() {
+        assert!(matches!(
+            RuleSet::from_snapshot(b"not a snapshot"),
+            Err(SnapshotError::InvalidHeader)
+        ));
+
+        let mut bytes = RuleSet::default().to_snapshot().unwrap();
+        bytes[MAGIC.len()] = 0xff;
+        assert!(matches!(
+            RuleSet::from_snapshot(&bytes),
+            Err(SnapshotError::UnsupportedVersion(_))
+        ));
+
+        let mut bytes = RuleSet::new(vec![rule_from_yaml(RULE).unwrap()])
+            .to_snapshot()
+            .unwrap();
+        bytes.truncate(bytes.len() / 2);
+        assert!(matches!(
+            RuleSet::from_snapshot(&bytes),
+            Err(SnapshotError::Encoding(_))
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+//! Matching the logsources of rules against the logsources of events.
+//!
+//! If an event carries a logsource, see [`crate::Event::with_logsource`], a
+//! [`crate::RuleSet`] only evaluates the rules whose logsource matches it. By default, each
+//! attribute of the logsource of a rule must be set to the same value for the event, see
+//! [`Logsource::matches`]. Events without a logsource are evaluated against all rules.
+//!
+//! A [`Taxonomy`] adds custom definitions of which event logsources provide the events of a
+//! rule logsource. This is required if the events are labelled with another taxonomy than
+//! the rules, e.g. if Sysmon events are labelled with their service, but the rules use
+//! generic categories like `process_creation`:
+//!
+//! ```yaml
+//! - logsource:
+//!     category: process_creation
+//!     product: windows
+//!   sources:
+//!     - product: windows
+//!       service: sysmon
+//!     - product: windows
+//!       service: security
+//! ```
+use crate::rule::Logsource;
+use serde::Deserialize;
+
+/// A definition of the event logsources that provide the events of a rule logsource
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TaxonomyEntry {
+    /// The logsource of the rules the definition applies to. Rules match the definition if
+    /// their logsource has all of its attributes.
+    pub logsource: Logsource,
+    /// Events with any of these logsources are evaluated against the matching rules
+    pub sources: Vec<Logsource>,
+}
+
+/// Custom definitions of how the logsources of rules relate to the logsources of events
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::taxonomy::Taxonomy;
+/// use sigma_rust::{rule_from_yaml, Event, Logsource, RuleSet};
+///
+/// let rule = rule_from_yaml(
+///     r#"
+/// title: Whoami
+/// logsource:
+///     category: process_creation
+///     product: windows
+/// detection:
+///     selection:
+///         Image|endswith: '\whoami.exe'
+///     condition: selection
+/// "#,
+/// )
+/// .unwrap();
+///
+/// let sysmon = Logsource::new(None, Some("windows"), Some("sysmon"));
+/// let event = Event::from([("Image", "C:\\whoami.exe")]).with_logsource(sysmon.clone());
+///
+/// let rule_set = RuleSet::new(vec![rule]);
+/// assert!(rule_set.evaluate(&event).is_empty());
+///
+/// let mut taxonomy = Taxonomy::new();
+/// taxonomy.add(
+///     Logsource::new(Some("process_creation"), Some("windows"), None),
+///     sysmon,
+/// );
+/// let rule_set = rule_set.with_taxonomy(taxonomy);
+/// assert_eq!(rule_set.evaluate(&event).len(), 1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Taxonomy {
+    entries: Vec<TaxonomyEntry>,
+}
+
+impl Taxonomy {
+    /// Create a taxonomy without custom definitions
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a list of definitions from a YAML string
+    pub fn from_yaml(yaml: &str) -> Result<Self, serde_yml::Error> {
+        Ok(Self {
+            entries: serde_yml::from_str(yaml)?,
+        })
+    }
+
+    /// Declare that events of the `source` logsource provide the events of rules with the
+    /// `logsource`
+    pub fn add(&mut self, logsource: Logsource, source: Logsource) {
+        match self.entries.iter_mut().find(|e| e.logsource == logsource) {
+            Some(entry) => entry.sources.push(source),
+            None => self.entries.push(TaxonomyEntry {
+                logsource,
+                sources: vec![source],
+            }),
+        }
+    }
+
+    /// The definitions of the taxonomy
+    pub fn entries(&self) -> &[TaxonomyEntry] {
+        &self.entries
+    }
+
+    /// Check if a rule with the logsource `rule` applies to an event with the logsource `event`
+    pub fn matches(&self, rule: &Logsource, event: &Logsource) -> bool {
+        rule.matches(event)
+            || self.entries.iter().any(|entry| {
+                entry.logsource.matches(rule)
+                    && entry.sources.iter().any(|source| source.matches(event))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let taxonomy = Taxonomy::from_yaml(
+            r#"
+- logsource:
+    category: process_creation
+  sources:
+    - product: windows
+      service: sysmon
+    - product: linux
+      service: auditd
+"#,
+        )
+        .unwrap();
+        let rule = Logsource::new(Some("process_creation"), Some("windows"), None);
+
+        assert!(taxonomy.matches(
+            &rule,
+            &Logsource::new(None, Some("windows"), Some("Sysmon"))
+        ));
+        assert!(taxonomy.matches(
+            &rule,
+            &Logsource::new(Some("process_creation"), Some("windows"), None)
+        ));
+        assert!(!taxonomy.matches(&rule, &Logsource::new(None, Some("windows"), None)));
+        assert!(!taxonomy.matches(
+            &Logsource::new(Some("file_event"), None, None),
+            &Logsource::new(None, Some("windows"), Some("sysmon"))
+        ));
+        // Rules without logsource attributes apply to all events
+        assert!(taxonomy.matches(&Logsource::default(), &Logsource::default()));
+    }
+
+    #[test]
+    fn test_add() {
+        let mut taxonomy = Taxonomy::new();
+        let rule = Logsource::new(Some("dns"), None, None);
+        taxonomy.add(rule.clone(), Logsource::new(None, None, Some("bind")));
+        taxonomy.add(rule.clone(), Logsource::new(None, None, Some("unbound")));
+        assert_eq!(taxonomy.entries().len(), 1);
+        assert!(taxonomy.matches(&rule, &Logsource::new(None, Some("linux"), Some("unbound"))));
+    }
+}
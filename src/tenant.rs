@@ -0,0 +1,230 @@
+//! Independent rule sets of several tenants in one process.
+//!
+//! A detection service for many customers loads the rules of each customer into its own
+//! [`RuleSet`], registered under a tenant name in [`Tenants`]. Events of a tenant are only
+//! evaluated against the rules of that tenant, and the events, matches and evaluation time are
+//! counted per tenant.
+//!
+//! Compiled wildcard patterns and regular expressions are shared by all rules of the process,
+//! so tenants that use the same rules, e.g. the public Sigma repository with a few custom
+//! rules, do not multiply the memory use of the patterns.
+use crate::event::Event;
+use crate::profile::timed;
+use crate::result::MatchResult;
+use crate::ruleset::RuleSet;
+use std::collections::hash_map::{Entry, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// The rule sets of all tenants, keyed by tenant name.
+///
+/// Evaluating only needs a shared reference, so the tenants can be evaluated from several
+/// threads at once, e.g. behind an `Arc`.
+///
+/// # Example
+/// ```rust
+/// use sigma_rust::tenant::Tenants;
+/// use sigma_rust::{rule_from_yaml, Event, RuleSet};
+///
+/// let rule = rule_from_yaml(
+///     r#"
+/// title: Whoami
+/// logsource:
+/// detection:
+///     selection:
+///         Image|endswith: '\whoami.exe'
+///     condition: selection
+/// "#,
+/// )
+/// .unwrap();
+///
+/// let mut tenants = Tenants::new();
+/// tenants.insert("acme", RuleSet::new(vec![rule]));
+/// tenants.insert("globex", RuleSet::new(vec![]));
+///
+/// let event = Event::from([("Image", "C:\\whoami.exe")]);
+/// assert_eq!(tenants.evaluate("acme", &event).unwrap().len(), 1);
+/// assert!(tenants.evaluate("globex", &event).unwrap().is_empty());
+/// assert!(tenants.evaluate("initech", &event).is_none());
+/// assert_eq!(tenants.metrics("acme").unwrap().matches, 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct Tenants {
+    tenants: HashMap<String, Tenant>,
+}
+
+#[derive(Debug, Default)]
+struct Tenant {
+    rule_set: RuleSet,
+    events: AtomicU64,
+    matches: AtomicU64,
+    nanos: AtomicU64,
+    rule_set_updates: u64,
+}
+
+/// Counters of the work done for a tenant since it was added
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TenantMetrics {
+    /// The number of evaluated events
+    pub events: u64,
+    /// The number of matches
+    pub matches: u64,
+    /// The total evaluation time in nanoseconds
+    pub nanos: u64,
+    /// How often the rule set of the tenant was replaced
+    pub rule_set_updates: u64,
+}
+
+impl TenantMetrics {
+    /// The mean evaluation time of an event
+    pub fn mean_duration(&self) -> Option<Duration> {
+        (self.events > 0).then(|| Duration::from_nanos(self.nanos / self.events))
+    }
+}
+
+impl Tenants {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the rule set of a tenant and return its previous rule set. The metrics of an
+    /// existing tenant are kept.
+    pub fn insert<S: Into<String>>(&mut self, tenant: S, rule_set: RuleSet) -> Option<RuleSet> {
+        match self.tenants.entry(tenant.into()) {
+            Entry::Occupied(mut existing) => {
+                let existing = existing.get_mut();
+                existing.rule_set_updates += 1;
+                Some(std::mem::replace(&mut existing.rule_set, rule_set))
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(Tenant {
+                    rule_set,
+                    ..Default::default()
+                });
+                None
+            }
+        }
+    }
+
+    /// Remove a tenant with its rule set and metrics
+    pub fn remove(&mut self, tenant: &str) -> Option<RuleSet> {
+        self.tenants.remove(tenant).map(|tenant| tenant.rule_set)
+    }
+
+    pub fn rule_set(&self, tenant: &str) -> Option<&RuleSet> {
+        self.tenants.get(tenant).map(|tenant| &tenant.rule_set)
+    }
+
+    /// Get the rule set of a tenant to change it in place, e.g. with
+    /// [`RuleSet::set_enabled`]
+    pub fn rule_set_mut(&mut self, tenant: &str) -> Option<&mut RuleSet> {
+        self.tenants
+            .get_mut(tenant)
+            .map(|tenant| &mut tenant.rule_set)
+    }
+
+    /// Evaluate the rules of a tenant against an event. Returns `None` if the tenant does not
+    /// exist.
+    pub fn evaluate(&self, tenant: &str, event: &Event) -> Option<Vec<MatchResult>> {
+        let tenant = self.tenants.get(tenant)?;
+        let (results, elapsed) = timed(|| tenant.rule_set.evaluate(event));
+        tenant.events.fetch_add(1, Ordering::Relaxed);
+        tenant
+            .matches
+            .fetch_add(results.len() as u64, Ordering::Relaxed);
+        tenant.nanos.fetch_add(
+            elapsed.as_nanos().try_into().unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+        Some(results)
+    }
+
+    /// The metrics of a tenant
+    pub fn metrics(&self, tenant: &str) -> Option<TenantMetrics> {
+        self.tenants.get(tenant).map(|tenant| TenantMetrics {
+            events: tenant.events.load(Ordering::Relaxed),
+            matches: tenant.matches.load(Ordering::Relaxed),
+            nanos: tenant.nanos.load(Ordering::Relaxed),
+            rule_set_updates: tenant.rule_set_updates,
+        })
+    }
+
+    /// The names of all tenants in arbitrary order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.tenants.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tenants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tenants.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule_from_yaml;
+
+    fn rule(title: &str, image: &str) -> crate::Rule {
+        rule_from_yaml(&format!(
+            "title: {}\nlogsource:\ndetection:\n    selection:\n        Image|endswith: '{}'\n    condition: selection",
+            title, image
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_isolation() {
+        let mut tenants = Tenants::new();
+        assert!(tenants
+            .insert("a", RuleSet::new(vec![rule("cmd", "cmd.exe")]))
+            .is_none());
+        tenants.insert(
+            "b",
+            RuleSet::new(vec![rule("cmd", "cmd.exe"), rule("whoami", "whoami.exe")]),
+        );
+        assert_eq!(tenants.len(), 2);
+
+        let whoami = Event::from([("Image", "whoami.exe")]);
+        assert!(tenants.evaluate("a", &whoami).unwrap().is_empty());
+        assert_eq!(
+            tenants.evaluate("b", &whoami).unwrap()[0].rule_title,
+            "whoami"
+        );
+
+        tenants
+            .rule_set_mut("b")
+            .unwrap()
+            .set_enabled("whoami", false);
+        assert!(tenants.evaluate("b", &whoami).unwrap().is_empty());
+        assert_eq!(tenants.rule_set("a").unwrap().disabled_len(), 0);
+
+        let a = tenants.metrics("a").unwrap();
+        assert_eq!((a.events, a.matches, a.rule_set_updates), (1, 0, 0));
+        let b = tenants.metrics("b").unwrap();
+        assert_eq!((b.events, b.matches), (2, 1));
+        assert!(b.mean_duration().is_some());
+        assert_eq!(TenantMetrics::default().mean_duration(), None);
+    }
+
+    #[test]
+    fn test_replace_and_remove() {
+        let mut tenants = Tenants::new();
+        tenants.insert("a", RuleSet::new(vec![]));
+        tenants.evaluate("a", &Event::from([("Image", "cmd.exe")]));
+
+        let previous = tenants.insert("a", RuleSet::new(vec![rule("cmd", "cmd.exe")]));
+        assert_eq!(previous.unwrap().len(), 0);
+        let metrics = tenants.metrics("a").unwrap();
+        assert_eq!((metrics.events, metrics.rule_set_updates), (1, 1));
+
+        assert_eq!(tenants.remove("a").unwrap().len(), 1);
+        assert!(tenants.remove("a").is_none());
+        assert!(tenants.metrics("a").is_none());
+        assert!(tenants.is_empty());
+        assert_eq!(tenants.names().count(), 0);
+    }
+}
@@ -0,0 +1,123 @@
+//! Native timestamps in events, enabled by the `chrono` or `time` feature.
+//!
+//! `chrono::DateTime` and `time::OffsetDateTime` values are stored as
+//! [`BaseValue::Timestamp`]. The `gt`, `gte`, `lt` and `lte` modifiers compare them with
+//! RFC 3339 timestamps of rules by their point in time, regardless of the offset. All other
+//! modifiers match the RFC 3339 representation in UTC, e.g. `2024-05-01T12:00:00Z`.
+use crate::basevalue::BaseValue;
+use std::time::SystemTime;
+
+/// Parse an RFC 3339 timestamp, e.g. `2024-05-01T14:00:00+02:00`
+#[cfg(feature = "chrono")]
+pub(crate) fn parse(s: &str) -> Option<SystemTime> {
+    chrono::DateTime::parse_from_rfc3339(s).ok().map(Into::into)
+}
+
+/// Parse an RFC 3339 timestamp, e.g. `2024-05-01T14:00:00+02:00`
+#[cfg(not(feature = "chrono"))]
+pub(crate) fn parse(s: &str) -> Option<SystemTime> {
+    time::OffsetDateTime::parse(s, &time::format_description::well_known::Rfc3339)
+        .ok()
+        .map(Into::into)
+}
+
+/// Format a timestamp in RFC 3339 in UTC
+#[cfg(feature = "chrono")]
+pub(crate) fn format(timestamp: &SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(*timestamp)
+        .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)
+}
+
+/// Format a timestamp in RFC 3339 in UTC
+#[cfg(not(feature = "chrono"))]
+pub(crate) fn format(timestamp: &SystemTime) -> String {
+    time::OffsetDateTime::from(*timestamp)
+        .format(&time::format_description::well_known::Rfc3339)
+        .unwrap_or_default()
+}
+
+impl From<SystemTime> for BaseValue {
+    fn from(timestamp: SystemTime) -> Self {
+        Self::Timestamp(timestamp)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for BaseValue {
+    fn from(timestamp: chrono::DateTime<Tz>) -> Self {
+        Self::Timestamp(timestamp.into())
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for BaseValue {
+    fn from(timestamp: time::OffsetDateTime) -> Self {
+        Self::Timestamp(timestamp.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rule_from_yaml, Event};
+    use std::time::Duration;
+
+    fn at(seconds: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        assert_eq!(parse("1970-01-01T01:00:00+01:00"), Some(at(0)));
+        assert_eq!(parse("2024-05-01"), None);
+        assert_eq!(format(&at(1714564800)), "2024-05-01T12:00:00Z");
+        assert_eq!(
+            BaseValue::Timestamp(at(1714564800)),
+            BaseValue::from("2024-05-01T14:00:00+02:00")
+        );
+        assert!(BaseValue::Timestamp(at(1)) > BaseValue::from("1970-01-01T00:00:00Z"));
+        assert!(BaseValue::Timestamp(at(1))
+            .partial_cmp(&BaseValue::from("yesterday"))
+            .is_none());
+    }
+
+    #[test]
+    fn test_compare_in_rule() {
+        let rule = rule_from_yaml(
+            r#"
+title: Late logon
+logsource:
+detection:
+    selection:
+        LogonTime|gte: '2024-05-01T14:00:00+02:00'
+        LogonTime|startswith: '2024-05-01T'
+    condition: selection
+"#,
+        )
+        .unwrap();
+
+        assert!(rule.is_match(&Event::from([("LogonTime", at(1714564800))])));
+        assert!(!rule.is_match(&Event::from([("LogonTime", at(1714564799))])));
+        assert!(!rule.is_match(&Event::from([("LogonTime", "2024-05-01T12:00:00Z")])));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono() {
+        let timestamp = chrono::DateTime::parse_from_rfc3339("2024-05-01T14:00:00+02:00").unwrap();
+        assert_eq!(
+            BaseValue::from(timestamp),
+            BaseValue::Timestamp(at(1714564800))
+        );
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn test_time() {
+        let timestamp = time::OffsetDateTime::from_unix_timestamp(1714564800).unwrap();
+        assert_eq!(
+            BaseValue::from(timestamp),
+            BaseValue::Timestamp(at(1714564800))
+        );
+    }
+}
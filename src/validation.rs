@@ -0,0 +1,668 @@
+//! Validate rules beyond what is required to parse them.
+//!
+//! Rules that cannot be parsed are reported as errors. Parsed rules are linted for issues that
+//! do not prevent their evaluation but likely are mistakes or violate the conventions of the
+//! [Sigma specification](https://github.com/SigmaHQ/sigma-specification), e.g. a missing
+//! `id` or selections that are not used by the condition. Each issue carries a stable code, so
+//! tooling can filter them.
+//!
+//! Regular expressions are checked against the subset of regular expression features every
+//! Sigma backend must support. Features outside of it, e.g. look-arounds, backreferences or
+//! named groups, are reported as `non-portable-regex` warnings, as the rule would not work with
+//! other backends. tau evaluates the features the `regex` crate supports, e.g. named groups,
+//! but look-arounds and backreferences make a rule invalid for tau as well. Wildcards in values of
+//! `cidr` and numeric comparisons, or at the start of regular expressions, are reported as
+//! `wildcard-in-value` warnings.
+//!
+//! The condition is analysed for parts that cannot work as intended: conditions that can never
+//! match, e.g. `selection and not selection`, operands that do not change the result, e.g.
+//! `selection or (selection and filter)`, quantifiers like `1 of sel_*` without a matching
+//! selection, and fields with the `all` modifier that must equal several different values.
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::validation::{Severity, Validator};
+//!
+//! let mut validator = Validator::new();
+//! let issues = validator.validate_yaml(
+//!     r#"
+//! title: Whoami
+//! logsource:
+//!     category: process_creation
+//! detection:
+//!     selection:
+//!         Image|endswith: '\whoami.exe'
+//!     filter:
+//!         User: SYSTEM
+//!     condition: selection
+//! "#,
+//! );
+//! let codes: Vec<&str> = issues.iter().map(|issue| issue.code).collect();
+//! assert_eq!(codes, ["missing-id", "missing-level", "unused-selection"]);
+//! assert!(issues.iter().all(|issue| issue.severity == Severity::Warning));
+//! ```
+use crate::basevalue::BaseValue;
+use crate::detection::Finding;
+use crate::field::FieldValue;
+use crate::rule::{Rule, Status};
+use crate::rule_from_yaml;
+use crate::selection::Selection;
+use serde_yml::Value;
+use std::collections::HashSet;
+use std::fmt;
+
+/// The maximum length of a rule title according to the specification
+const MAX_TITLE_LENGTH: usize = 256;
+
+/// The modifiers whose values are never wildcard patterns
+const WILDCARD_FREE_MODIFIERS: [&str; 6] = ["cidr", "gt", "gte", "lt", "lte", "subdomainof"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The rule works, but likely contains a mistake
+    Warning,
+    /// The rule cannot be used
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found in a rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// A short identifier of the kind of issue, e.g. `missing-id`
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl Issue {
+    fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)
+    }
+}
+
+/// Validates rules. The validator remembers the ids of all validated rules, so rules that
+/// reuse the id of another rule are reported.
+#[derive(Debug, Default)]
+pub struct Validator {
+    ids: HashSet<String>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse and validate a rule given as YAML
+    pub fn validate_yaml(&mut self, yaml: &str) -> Vec<Issue> {
+        match rule_from_yaml(yaml) {
+            Ok(rule) => self.validate(&rule),
+            Err(err) => {
+                let mut issues = vec![Issue::error("invalid-rule", err.to_string())];
+                // Values that do not compile may still be explained by the lints
+                if let Ok(rule) = serde_yml::from_str::<Value>(yaml) {
+                    for (field, pattern) in raw_values(&rule, "re") {
+                        issues.extend(regex_issue(field, pattern));
+                        issues.extend(wildcard_issue(field, "re", pattern));
+                    }
+                    for modifier in WILDCARD_FREE_MODIFIERS {
+                        for (field, value) in raw_values(&rule, modifier) {
+                            issues.extend(wildcard_issue(field, modifier, value));
+                        }
+                    }
+                }
+                issues
+            }
+        }
+    }
+
+    /// Validate a parsed rule
+    pub fn validate(&mut self, rule: &Rule) -> Vec<Issue> {
+        let mut issues = vec![];
+
+        match &rule.id {
+            None => issues.push(Issue::warning("missing-id", "the rule has no id")),
+            Some(id) => {
+                if !is_uuid(id) {
+                    issues.push(Issue::warning(
+                        "invalid-id",
+                        format!("the id '{}' is not a UUID", id),
+                    ));
+                }
+                if !self.ids.insert(id.clone()) {
+                    issues.push(Issue::error(
+                        "duplicate-id",
+                        format!("the id '{}' is used by another rule", id),
+                    ));
+                }
+            }
+        }
+
+        if rule.title.chars().count() > MAX_TITLE_LENGTH {
+            issues.push(Issue::warning(
+                "long-title",
+                format!("the title is longer than {} characters", MAX_TITLE_LENGTH),
+            ));
+        }
+        if rule.level.is_none() {
+            issues.push(Issue::warning("missing-level", "the rule has no level"));
+        }
+        if let Some(status @ (Status::Deprecated | Status::Unsupported)) = rule.status {
+            issues.push(Issue::warning(
+                "inactive-status",
+                format!("the status of the rule is {:?}", status).to_lowercase(),
+            ));
+        }
+
+        for warning in rule.legacy_warnings() {
+            issues.push(Issue::warning("legacy-syntax", warning.clone()));
+        }
+
+        let logsource = &rule.logsource;
+        if logsource.category.is_none()
+            && logsource.product.is_none()
+            && logsource.service.is_none()
+        {
+            issues.push(Issue::warning(
+                "empty-logsource",
+                "the logsource has no category, product or service",
+            ));
+        }
+
+        for (attribute, date) in [("date", &rule.date), ("modified", &rule.modified)] {
+            if let Some(date) = date.as_ref().filter(|d| !is_date(d)) {
+                issues.push(Issue::warning(
+                    "invalid-date",
+                    format!(
+                        "the {} '{}' is not formatted as YYYY-MM-DD",
+                        attribute, date
+                    ),
+                ));
+            }
+        }
+
+        for tag in rule.tags.iter().flatten() {
+            let valid = !tag.is_empty()
+                && tag.chars().all(|c| {
+                    c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-' | '.')
+                });
+            if !valid {
+                issues.push(Issue::warning(
+                    "invalid-tag",
+                    format!(
+                        "the tag '{}' contains characters other than lowercase letters, digits, '_', '-' and '.'",
+                        tag
+                    ),
+                ));
+            }
+        }
+
+        for selection in rule.detection.get_selections().values() {
+            let Selection::Field(groups) = selection else {
+                continue;
+            };
+            for field in groups.iter().flat_map(|group| &group.fields) {
+                let Some(modifier) = &field.modifier.match_modifier else {
+                    continue;
+                };
+                for value in &field.values {
+                    match value {
+                        FieldValue::Regex(regex) => {
+                            issues.extend(regex_issue(&field.name, regex.as_str()));
+                        }
+                        // Permissive rules may compare numbers with strings
+                        FieldValue::Base(BaseValue::String(s)) => {
+                            issues.extend(wildcard_issue(&field.name, &modifier.to_string(), s))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        for finding in rule.detection.analyze() {
+            issues.push(match finding {
+                Finding::EmptyQuantifier(quantifier) => Issue::warning(
+                    "empty-quantifier",
+                    format!("'{}' of the condition matches no selection", quantifier),
+                ),
+                Finding::ContradictoryField { selection, field } => Issue::warning(
+                    "contradictory-field",
+                    format!(
+                        "the field '{}' of the selection '{}' must equal several different values at once",
+                        field, selection
+                    ),
+                ),
+                Finding::Unsatisfiable => {
+                    Issue::warning("unsatisfiable-condition", "the condition can never match")
+                }
+                Finding::Redundant(operand) => Issue::warning(
+                    "redundant-condition",
+                    format!("'{}' does not change the result of the condition", operand),
+                ),
+            });
+        }
+
+        for name in rule.detection.unused_selections() {
+            issues.push(Issue::warning(
+                "unused-selection",
+                format!("the selection '{}' is not used by the condition", name),
+            ));
+        }
+
+        issues
+    }
+}
+
+fn regex_issue(field: &str, pattern: &str) -> Option<Issue> {
+    let features = non_portable_regex_features(pattern);
+    if features.is_empty() {
+        return None;
+    }
+    Some(Issue::warning(
+        "non-portable-regex",
+        format!(
+            "the regular expression '{}' of the field '{}' uses {}, which other backends do not have to support",
+            pattern,
+            field,
+            features.join(", ")
+        ),
+    ))
+}
+
+/// Report `*` and `?` in values of modifiers that do not support wildcards. Regular
+/// expressions are only reported if they use `*` or `?` where a regular expression cannot, e.g.
+/// at the start, which suggests a wildcard pattern.
+fn wildcard_issue(field: &str, modifier: &str, value: &str) -> Option<Issue> {
+    if modifier == "re" {
+        let glob = value.starts_with(['*', '?'])
+            || value.contains("|*")
+            || value.contains("|?")
+            || value.contains("(*");
+        return glob.then(|| {
+            Issue::warning(
+                "wildcard-in-value",
+                format!(
+                    "the regular expression '{}' of the field '{}' uses '*' or '?' as a wildcard, which are '.*' and '.' in regular expressions",
+                    value, field
+                ),
+            )
+        });
+    }
+    if !WILDCARD_FREE_MODIFIERS.contains(&modifier) || !value.contains(['*', '?']) {
+        return None;
+    }
+    Some(Issue::warning(
+        "wildcard-in-value",
+        format!(
+            "the value '{}' of the field '{}' contains a wildcard, which the modifier '{}' does not support",
+            value, field, modifier
+        ),
+    ))
+}
+
+/// The values of the fields with a modifier of the detection of a rule that could not be
+/// parsed, with their field names
+fn raw_values<'a>(rule: &'a Value, modifier: &str) -> Vec<(&'a str, &'a str)> {
+    let mut result = vec![];
+    let Some(detection) = rule.get("detection").and_then(Value::as_mapping) else {
+        return result;
+    };
+    for selection in detection.values() {
+        let groups = match selection {
+            Value::Mapping(_) => std::slice::from_ref(selection),
+            Value::Sequence(groups) => groups.as_slice(),
+            _ => continue,
+        };
+        for (name, values) in groups.iter().filter_map(Value::as_mapping).flatten() {
+            let Some(name) = name.as_str() else {
+                continue;
+            };
+            if !name
+                .split('|')
+                .skip(1)
+                .any(|m| m.eq_ignore_ascii_case(modifier))
+            {
+                continue;
+            }
+            let field = name.split('|').next().unwrap_or_default();
+            let values = match values {
+                Value::Sequence(values) => values.as_slice(),
+                value => std::slice::from_ref(value),
+            };
+            result.extend(values.iter().filter_map(Value::as_str).map(|p| (field, p)));
+        }
+    }
+    result
+}
+
+/// The features of a regular expression outside of the subset required by the Sigma
+/// specification, i.e. the PCRE syntax without look-arounds, backreferences, atomic groups,
+/// possessive quantifiers, named groups, Unicode classes and flags other than `i`, `m` and `s`
+fn non_portable_regex_features(pattern: &str) -> Vec<&'static str> {
+    let mut features = vec![];
+    let mut add = |feature| {
+        if !features.contains(&feature) {
+            features.push(feature);
+        }
+    };
+    let chars: Vec<char> = pattern.chars().collect();
+    let rest = |i: usize| -> String { chars[i..].iter().take(4).collect() };
+    let mut in_class = false;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => {
+                match chars.get(i + 1) {
+                    Some('1'..='9') => add("backreferences"),
+                    Some('k' | 'g') => add("backreferences"),
+                    Some('p' | 'P') => add("Unicode classes"),
+                    _ => {}
+                }
+                i += 2;
+                continue;
+            }
+            '[' if !in_class => in_class = true,
+            ']' if in_class => in_class = false,
+            '(' if !in_class => {
+                let group = rest(i);
+                if group.starts_with("(?=") || group.starts_with("(?!") {
+                    add("look-aheads");
+                } else if group.starts_with("(?<=") || group.starts_with("(?<!") {
+                    add("look-behinds");
+                } else if group.starts_with("(?>") {
+                    add("atomic groups");
+                } else if group.starts_with("(?P<")
+                    || group.starts_with("(?<")
+                    || group.starts_with("(?'")
+                {
+                    add("named groups");
+                } else if group.starts_with("(?") {
+                    let mut flags = chars[i + 2..]
+                        .iter()
+                        .take_while(|c| c.is_ascii_alphabetic() || **c == '-');
+                    if flags.any(|c| !matches!(c, 'i' | 'm' | 's' | '-')) {
+                        add("flags other than i, m and s");
+                    }
+                }
+            }
+            '*' | '+' | '?' | '}' if !in_class && chars.get(i + 1) == Some(&'+') => {
+                add("possessive quantifiers");
+                i += 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    features
+}
+
+fn is_uuid(id: &str) -> bool {
+    let groups: Vec<&str> = id.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn is_date(date: &str) -> bool {
+    let bytes = date.as_bytes();
+    bytes.len() == 10
+        && bytes.iter().enumerate().all(|(i, b)| match i {
+            4 | 7 => *b == b'-',
+            _ => b.is_ascii_digit(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(issues: &[Issue]) -> Vec<&'static str> {
+        issues.iter().map(|issue| issue.code).collect()
+    }
+
+    #[test]
+    fn test_valid_rule() {
+        let mut validator = Validator::new();
+        let yaml = r#"
+title: Whoami
+id: 5a0a8e1c-2a52-4d55-a5a7-41a2b5c4f0e1
+status: test
+date: 2024-01-31
+level: high
+tags:
+    - attack.t1033
+logsource:
+    category: process_creation
+detection:
+    selection_img:
+        Image|endswith: '\whoami.exe'
+    selection_cli:
+        CommandLine|contains: '/all'
+    filter:
+        User: SYSTEM
+    condition: all of selection_* and not filter
+"#;
+        assert_eq!(validator.validate_yaml(yaml), vec![]);
+        // The id is remembered
+        assert_eq!(codes(&validator.validate_yaml(yaml)), ["duplicate-id"]);
+    }
+
+    #[test]
+    fn test_lints() {
+        let issues = Validator::new().validate_yaml(
+            r#"
+title: Whoami
+id: 1234
+status: deprecated
+date: 2024/01/31
+tags:
+    - Attack.T1033
+logsource:
+detection:
+    selection:
+        Image|endswith: '\whoami.exe'
+    unused:
+        User: SYSTEM
+    condition: selection
+"#,
+        );
+        assert_eq!(
+            codes(&issues),
+            [
+                "invalid-id",
+                "missing-level",
+                "inactive-status",
+                "empty-logsource",
+                "invalid-date",
+                "invalid-tag",
+                "unused-selection"
+            ]
+        );
+        assert_eq!(
+            issues[2].to_string(),
+            "warning[inactive-status]: the status of the rule is deprecated"
+        );
+    }
+
+    #[test]
+    fn test_invalid_rule() {
+        let issues = Validator::new().validate_yaml("title: Broken\ndetection:\n    condition: x");
+        assert_eq!(codes(&issues), ["invalid-rule"]);
+        assert_eq!(issues[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_legacy_syntax() {
+        let config = crate::EngineConfig {
+            spec_mode: crate::SpecMode::Legacy,
+            ..Default::default()
+        };
+        let rule = config
+            .rule_from_yaml(
+                "title: Legacy\nlogsource:\n    product: windows\nlevel: low\ndetection:\n    selection:\n        User: admin\n    condition: selection | count() > 5\n",
+            )
+            .unwrap();
+        let issues = Validator::new().validate(&rule);
+        assert_eq!(codes(&issues), ["missing-id", "legacy-syntax"]);
+        assert_eq!(issues[1].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_quantifiers() {
+        let issues = Validator::new().validate_yaml(
+            r#"
+title: Quantifiers
+logsource:
+    product: windows
+detection:
+    sel_a:
+        A: 1
+    sel_b:
+        B: 1
+    other:
+        C: 1
+    condition: 1 of sel_*
+"#,
+        );
+        assert_eq!(
+            issues.last().unwrap().message,
+            "the selection 'other' is not used by the condition"
+        );
+    }
+
+    #[test]
+    fn test_condition_analysis() {
+        let rule = |condition: &str| {
+            format!(
+                "title: Condition\nlogsource:\n    product: windows\nlevel: low\ndetection:\n    selection:\n        Image|all:\n            - a.exe\n            - b.exe\n    filter:\n        User: SYSTEM\n    condition: {}\n",
+                condition
+            )
+        };
+        let issues = Validator::new().validate_yaml(&rule("selection or 1 of other_*"));
+        assert_eq!(
+            codes(&issues),
+            [
+                "missing-id",
+                "empty-quantifier",
+                "contradictory-field",
+                "unsatisfiable-condition",
+                "unused-selection"
+            ]
+        );
+        assert_eq!(
+            issues[2].message,
+            "the field 'Image' of the selection 'selection' must equal several different values at once"
+        );
+
+        let yaml = rule("filter or filter and not selection").replace("Image|all", "Image");
+        let issues = Validator::new().validate_yaml(&yaml);
+        assert_eq!(codes(&issues), ["missing-id", "redundant-condition"]);
+        assert_eq!(
+            issues[1].message,
+            "'filter and not selection' does not change the result of the condition"
+        );
+    }
+
+    #[test]
+    fn test_wildcard_in_value() {
+        let rule = |field: &str, value: &str| {
+            format!(
+                "title: Wildcards\nlogsource:\n    product: windows\nlevel: low\ndetection:\n    selection:\n        {}: '{}'\n    condition: selection\n",
+                field, value
+            )
+        };
+        let issues = Validator::new().validate_yaml(&rule("SourceIp|cidr", "10.0.*"));
+        assert_eq!(codes(&issues), ["invalid-rule", "wildcard-in-value"]);
+        assert_eq!(
+            issues[1].message,
+            "the value '10.0.*' of the field 'SourceIp' contains a wildcard, which the modifier 'cidr' does not support"
+        );
+
+        let issues = Validator::new().validate_yaml(&rule("CommandLine|re", r"*\\cmd.exe"));
+        assert_eq!(codes(&issues), ["invalid-rule", "wildcard-in-value"]);
+        let issues = Validator::new().validate_yaml(&rule("CommandLine|re", r"(?i)cmd.*exe?"));
+        assert_eq!(codes(&issues), ["missing-id"]);
+
+        // Permissive rules can be parsed, but the comparison never matches as intended
+        let config = crate::EngineConfig {
+            spec_mode: crate::SpecMode::Permissive,
+            ..Default::default()
+        };
+        let rule = config.rule_from_yaml(&rule("Port|gte", "10*")).unwrap();
+        let issues = Validator::new().validate(&rule);
+        assert_eq!(codes(&issues), ["missing-id", "wildcard-in-value"]);
+    }
+
+    #[test]
+    fn test_non_portable_regex_features() {
+        for (pattern, expected) in [
+            (r"^c:\\windows\\(system32|syswow64)\\[a-z]+\.exe$", vec![]),
+            (r"(?i)who(?:ami)?.*?\d{2,}[(?<=]", vec![]),
+            (r"(?ims-s)a", vec![]),
+            (r"foo(?=bar)|(?!baz)", vec!["look-aheads"]),
+            (r"(?<!\\)cmd(?<=x)", vec!["look-behinds"]),
+            (r"(a)\1|\k<name>", vec!["backreferences"]),
+            (r"(?>a+)b", vec!["atomic groups"]),
+            (r"a++b?+", vec!["possessive quantifiers"]),
+            (r"(?P<name>a)(?<other>b)", vec!["named groups"]),
+            (r"\p{Greek}", vec!["Unicode classes"]),
+            (r"(?x) a b", vec!["flags other than i, m and s"]),
+        ] {
+            assert_eq!(
+                non_portable_regex_features(pattern),
+                expected,
+                "{}",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn test_non_portable_regex() {
+        let rule = |pattern: &str| {
+            format!(
+                "title: Regex\nlogsource:\n    product: windows\nlevel: low\ndetection:\n    selection:\n        CommandLine|re: '{}'\n    condition: selection\n",
+                pattern
+            )
+        };
+        let issues = Validator::new().validate_yaml(&rule(r"(?P<cmd>cmd|powershell)\.exe"));
+        assert_eq!(codes(&issues), ["missing-id", "non-portable-regex"]);
+        assert_eq!(
+            issues[1].message,
+            r"the regular expression '(?P<cmd>cmd|powershell)\.exe' of the field 'CommandLine' uses named groups, which other backends do not have to support"
+        );
+
+        // tau cannot evaluate look-arounds either, but the lint explains the parse error
+        let issues = Validator::new().validate_yaml(&rule(r"cmd(?!\.exe)"));
+        assert_eq!(codes(&issues), ["invalid-rule", "non-portable-regex"]);
+    }
+}
@@ -0,0 +1,236 @@
+//! Maintained value lists that rules reference by name.
+//!
+//! A value `listref:<name>`, e.g. `Image|endswith: 'listref:windows_lolbins'`, is replaced by
+//! the values of the list of that name in [`EngineConfig::value_lists`](crate::EngineConfig)
+//! when the rule is compiled, and the values are then transformed and compiled like values
+//! written in the rule. This keeps long allow and deny lists out of the rules and in one place.
+//!
+//! In contrast to the `expand` modifier and its [placeholder lists](crate::placeholder), the
+//! values are resolved once: changing a list takes effect when the rules are compiled again,
+//! and a compiled rule does not depend on the list anymore. Lists are either added with their
+//! values, or looked up from a [`ValueListProvider`], e.g. one reading them from a database.
+//! A reference to a list that no provider has fails to compile with
+//! [`ParserError::UnresolvedValueList`], and so does a list without values, like a field
+//! without values.
+//!
+//! # Example
+//! ```rust
+//! use sigma_rust::valuelist::ValueLists;
+//! use sigma_rust::{EngineConfig, Event};
+//!
+//! let mut value_lists = ValueLists::new();
+//! value_lists.insert("lolbins", ["\\certutil.exe", "\\mshta.exe"]);
+//!
+//! let config = EngineConfig {
+//!     value_lists,
+//!     ..Default::default()
+//! };
+//! let rule = config
+//!     .rule_from_yaml(
+//!         r#"
+//! title: Living off the land
+//! logsource:
+//!     category: process_creation
+//! detection:
+//!     selection:
+//!         Image|endswith: 'listref:lolbins'
+//!     condition: selection
+//! "#,
+//!     )
+//!     .unwrap();
+//!
+//! assert!(rule.is_match(&Event::from([("Image", "C:\\Windows\\mshta.exe")])));
+//! assert!(!rule.is_match(&Event::from([("Image", "C:\\Windows\\notepad.exe")])));
+//! ```
+use crate::basevalue::BaseValue;
+use crate::error::ParserError;
+use crate::field::FieldValue;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+
+/// The prefix of a value that references a value list
+pub const LIST_REF_PREFIX: &str = "listref:";
+
+/// Looks up value lists by name, e.g. from a database or a threat intelligence platform
+pub trait ValueListProvider: fmt::Debug + Send + Sync {
+    /// The values of the list of that name, `None` if the provider does not have it
+    fn values(&self, name: &str) -> Option<Vec<String>>;
+}
+
+impl ValueListProvider for HashMap<String, Vec<String>> {
+    fn values(&self, name: &str) -> Option<Vec<String>> {
+        self.get(name).cloned()
+    }
+}
+
+impl ValueListProvider for BTreeMap<String, Vec<String>> {
+    fn values(&self, name: &str) -> Option<Vec<String>> {
+        self.get(name).cloned()
+    }
+}
+
+/// The value lists available to rules. Lists added with [`ValueLists::insert`] take
+/// precedence, then the providers are asked in the order they were registered.
+#[derive(Debug, Clone, Default)]
+pub struct ValueLists {
+    lists: BTreeMap<String, Arc<[String]>>,
+    providers: Vec<Arc<dyn ValueListProvider>>,
+}
+
+/// Value lists are equal if they have equal lists and the same providers
+impl PartialEq for ValueLists {
+    fn eq(&self, other: &Self) -> bool {
+        self.lists == other.lists
+            && self.providers.len() == other.providers.len()
+            && self
+                .providers
+                .iter()
+                .zip(&other.providers)
+                .all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+}
+
+impl ValueLists {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a list, replacing a previous list of the same name
+    pub fn insert<S, I, V>(&mut self, name: S, values: I)
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        let values = values.into_iter().map(Into::into).collect();
+        self.lists.insert(name.into(), values);
+    }
+
+    /// Look up the lists that were not added with [`ValueLists::insert`] from a provider
+    pub fn register<P: ValueListProvider + 'static>(&mut self, provider: P) {
+        self.providers.push(Arc::new(provider));
+    }
+
+    /// The values of the list of that name
+    pub fn get(&self, name: &str) -> Option<Vec<String>> {
+        if let Some(values) = self.lists.get(name) {
+            return Some(values.to_vec());
+        }
+        self.providers
+            .iter()
+            .find_map(|provider| provider.values(name))
+    }
+
+    /// The names of the added lists in alphabetical order, without those of the providers
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.lists.keys().map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lists.is_empty() && self.providers.is_empty()
+    }
+
+    /// Replace the list references among the values of a field with the values of the lists
+    pub(crate) fn resolve(&self, values: Vec<FieldValue>) -> Result<Vec<FieldValue>, ParserError> {
+        if !values.iter().any(|value| list_ref(value).is_some()) {
+            return Ok(values);
+        }
+        let mut result = Vec::with_capacity(values.len());
+        for value in values {
+            match list_ref(&value) {
+                Some(name) => {
+                    let list = self
+                        .get(name)
+                        .ok_or_else(|| ParserError::UnresolvedValueList(name.to_string()))?;
+                    result.extend(list.into_iter().map(FieldValue::from));
+                }
+                None => result.push(value),
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// The name of the list a value references
+fn list_ref(value: &FieldValue) -> Option<&str> {
+    match value {
+        FieldValue::Base(BaseValue::String(s)) => s
+            .strip_prefix(LIST_REF_PREFIX)
+            .filter(|name| !name.is_empty()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EngineConfig, Event};
+
+    fn yaml(values: &str) -> String {
+        format!(
+            "title: Test\nlogsource:\ndetection:\n    selection:\n        Image|endswith: {}\n    condition: selection\n",
+            values
+        )
+    }
+
+    #[derive(Debug)]
+    struct Uppercase;
+
+    impl ValueListProvider for Uppercase {
+        fn values(&self, name: &str) -> Option<Vec<String>> {
+            name.strip_prefix("upper_")
+                .map(|name| vec![format!("\\{}.exe", name.to_uppercase())])
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut value_lists = ValueLists::new();
+        value_lists.insert("lolbins", ["\\certutil.exe", "\\mshta.exe"]);
+        value_lists.insert("upper_cmd", ["\\command.com"]);
+        value_lists.register(Uppercase);
+        value_lists.register(BTreeMap::from([(
+            "shells".to_string(),
+            vec!["\\bash.exe".to_string()],
+        )]));
+        let config = EngineConfig::builder().value_lists(value_lists).build();
+
+        let rule = config
+            .rule_from_yaml(&yaml(
+                "['listref:lolbins', '\\whoami.exe', 'listref:shells']",
+            ))
+            .unwrap();
+        for image in ["C:\\mshta.exe", "C:\\whoami.exe", "C:\\bash.exe"] {
+            assert!(rule.is_match(&Event::from([("Image", image)])), "{}", image);
+        }
+        assert!(!rule.is_match(&Event::from([("Image", "C:\\cmd.exe")])));
+
+        // Added lists take precedence over providers
+        let rule = config
+            .rule_from_yaml(&yaml("['listref:upper_cmd', 'listref:upper_sh']"))
+            .unwrap();
+        assert!(rule.is_match(&Event::from([("Image", "C:\\command.com")])));
+        assert!(!rule.is_match(&Event::from([("Image", "C:\\cmd.exe")])));
+        assert!(rule.is_match(&Event::from([("Image", "C:\\sh.exe")])));
+    }
+
+    #[test]
+    fn test_unresolved() {
+        let err = crate::rule_from_yaml(&yaml("'listref:lolbins'"))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("The value list 'lolbins' is not provided by the engine config"),
+            "{}",
+            err
+        );
+
+        let mut value_lists = ValueLists::new();
+        value_lists.insert("empty", Vec::<String>::new());
+        let config = EngineConfig::builder().value_lists(value_lists).build();
+        assert!(config.rule_from_yaml(&yaml("'listref:empty'")).is_err());
+        // Only complete references are resolved
+        assert!(config.rule_from_yaml(&yaml("'listref:'")).is_ok());
+    }
+}
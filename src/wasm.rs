@@ -0,0 +1,165 @@
+//! JavaScript bindings built with [wasm-bindgen](https://github.com/rustwasm/wasm-bindgen).
+//!
+//! With the `wasm` feature enabled, rules can be compiled and evaluated from JavaScript,
+//! e.g. to test rules in the browser or to match events in Node.js. Events are passed as JSON
+//! strings and matches are returned as a JSON array.
+//!
+//! A Node.js package is built with
+//! ```bash
+//! cargo rustc --lib --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib
+//! wasm-bindgen --target nodejs --out-dir pkg target/wasm32-unknown-unknown/release/sigma_rust.wasm
+//! ```
+//!
+//! ```js
+//! import { Rule, RuleSet } from "sigma-rust";
+//!
+//! const rule = new Rule(yaml);
+//! rule.isMatch('{"Image": "C:\\\\whoami.exe"}');
+//!
+//! const ruleSet = new RuleSet([yaml]);
+//! const matches = JSON.parse(ruleSet.evaluate('{"Image": "C:\\\\whoami.exe"}'));
+//! ```
+use crate::result::MatchResult;
+use crate::{event_from_json, events_from_json, rule_from_yaml};
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+/// A compiled rule
+#[wasm_bindgen(js_name = Rule)]
+pub struct JsRule(crate::Rule);
+
+#[wasm_bindgen(js_class = Rule)]
+impl JsRule {
+    /// Parse and compile a rule from YAML
+    #[wasm_bindgen(constructor)]
+    pub fn new(yaml: &str) -> Result<JsRule, JsError> {
+        Ok(Self(rule_from_yaml(yaml).map_err(to_js_error)?))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn title(&self) -> String {
+        self.0.title.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn id(&self) -> Option<String> {
+        self.0.id.clone()
+    }
+
+    /// Check if the rule matches an event given as JSON
+    #[wasm_bindgen(js_name = isMatch)]
+    pub fn is_match(&self, event: &str) -> Result<bool, JsError> {
+        Ok(self
+            .0
+            .is_match(&event_from_json(event).map_err(to_js_error)?))
+    }
+
+    /// Evaluate the rule against an event given as JSON and return the match as JSON,
+    /// or `undefined` if the rule does not match
+    pub fn evaluate(&self, event: &str) -> Result<Option<String>, JsError> {
+        let event = event_from_json(event).map_err(to_js_error)?;
+        Ok(self
+            .0
+            .evaluate(&event)
+            .map(|result| result.to_json().to_string()))
+    }
+}
+
+/// A collection of rules that are evaluated together, see [`crate::RuleSet`]
+#[wasm_bindgen(js_name = RuleSet)]
+pub struct JsRuleSet(crate::RuleSet);
+
+#[wasm_bindgen(js_class = RuleSet)]
+impl JsRuleSet {
+    /// Parse and compile a list of rules given as YAML
+    #[wasm_bindgen(constructor)]
+    pub fn new(rules: Vec<String>) -> Result<JsRuleSet, JsError> {
+        let rules = rules
+            .iter()
+            .map(|yaml| rule_from_yaml(yaml))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(to_js_error)?;
+        Ok(Self(crate::RuleSet::new(rules)))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Evaluate all rules against an event given as JSON and return the matches as a JSON array
+    pub fn evaluate(&self, event: &str) -> Result<String, JsError> {
+        let event = event_from_json(event).map_err(to_js_error)?;
+        Ok(matches_to_json(&self.0.evaluate(&event)).to_string())
+    }
+
+    /// Evaluate all rules against a JSON array of events and return a JSON array with the
+    /// matches of every event
+    #[wasm_bindgen(js_name = evaluateBatch)]
+    pub fn evaluate_batch(&self, events: &str) -> Result<String, JsError> {
+        let events = events_from_json(events).map_err(to_js_error)?;
+        let results = self.0.evaluate_batch(&events);
+        Ok(Value::Array(results.iter().map(|r| matches_to_json(r)).collect()).to_string())
+    }
+}
+
+fn to_js_error(err: impl std::error::Error) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+fn matches_to_json(results: &[MatchResult]) -> Value {
+    Value::Array(results.iter().map(MatchResult::to_json).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Event;
+    use serde_json::json;
+
+    #[test]
+    fn test_matches_to_json() {
+        let rule_set = JsRuleSet::new(vec![r#"
+title: Whoami
+id: 1a2b
+level: high
+logsource:
+fields:
+    - Image
+detection:
+    selection:
+        Image|endswith: '\whoami.exe'
+    condition: selection
+"#
+        .to_string()])
+        .unwrap();
+        assert_eq!(rule_set.length(), 1);
+
+        let results = rule_set
+            .0
+            .evaluate(&Event::from([("Image", "C:\\whoami.exe")]));
+        assert_eq!(
+            matches_to_json(&results),
+            json!([{
+                "id": "1a2b",
+                "title": "Whoami",
+                "level": "high",
+                "tags": [],
+                "fields": {"Image": "C:\\whoami.exe"},
+            }])
+        );
+        assert_eq!(
+            rule_set
+                .evaluate(r#"{"Image": "C:\\explorer.exe"}"#)
+                .unwrap(),
+            "[]"
+        );
+
+        let results = rule_set
+            .evaluate_batch(r#"[{"Image": "C:\\whoami.exe"}, {"Image": "C:\\explorer.exe"}]"#)
+            .unwrap();
+        let results: Value = serde_json::from_str(&results).unwrap();
+        assert_eq!(results[0][0]["title"], "Whoami");
+        assert_eq!(results[1], json!([]));
+    }
+}
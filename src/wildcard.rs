@@ -1,10 +1,250 @@
-#[derive(Debug, PartialEq)]
+use crate::config::EngineConfig;
+use crate::re::{self, Regex, RegexBuilder};
+use memchr::{memchr, memchr2, memmem};
+use std::ops::Deref;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
 pub enum WildcardToken {
     Star,
     QuestionMark,
     Pattern(Vec<char>),
 }
 
+/// A tokenized wildcard pattern.
+///
+/// Patterns created by the `contains`, `startswith` and `endswith` modifiers or plain
+/// values consist of a single literal with optional leading and trailing stars. If this
+/// literal is ASCII, it is matched against ASCII haystacks with SIMD accelerated byte
+/// searches instead of the generic token matcher.
+///
+/// Other patterns can optionally be lowered to a regular expression, which the `regex` crate
+/// executes as a lazy DFA, see [`crate::WildcardMode::Dfa`].
+#[derive(Debug)]
+pub struct WildcardPattern {
+    tokens: Vec<WildcardToken>,
+    literal: Option<Literal>,
+    dfa: Option<Regex>,
+    /// The tokens with full case folding if non-ASCII haystacks are matched case-insensitively
+    /// on grapheme cluster boundaries, see [`crate::CaseFolding::Full`]
+    folded: Option<Vec<WildcardToken>>,
+}
+
+#[derive(Debug)]
+enum Literal {
+    Equals(Vec<u8>),
+    StartsWith(Vec<u8>),
+    EndsWith(Vec<u8>),
+    Contains(Box<memmem::Finder<'static>>),
+}
+
+impl WildcardPattern {
+    pub(crate) fn new(tokens: Vec<WildcardToken>) -> Self {
+        let literal = match tokens.as_slice() {
+            [WildcardToken::Pattern(p)] => ascii_bytes(p).map(Literal::Equals),
+            [WildcardToken::Pattern(p), WildcardToken::Star] => {
+                ascii_bytes(p).map(Literal::StartsWith)
+            }
+            [WildcardToken::Star, WildcardToken::Pattern(p)] => {
+                ascii_bytes(p).map(Literal::EndsWith)
+            }
+            [WildcardToken::Star, WildcardToken::Pattern(p), WildcardToken::Star] => ascii_bytes(p)
+                .map(|p| Literal::Contains(Box::new(memmem::Finder::new(&p).into_owned()))),
+            _ => None,
+        };
+        Self {
+            tokens,
+            literal,
+            dfa: None,
+            folded: None,
+        }
+    }
+
+    /// Like [`WildcardPattern::new`] but non-ASCII haystacks are matched with full case folding
+    /// on grapheme cluster boundaries, see [`match_graphemes`]. The tokens must be lowercase.
+    pub(crate) fn with_graphemes(tokens: Vec<WildcardToken>) -> Self {
+        let folded = tokens
+            .iter()
+            .map(|token| match token {
+                WildcardToken::Pattern(p) => {
+                    WildcardToken::Pattern(p.iter().copied().flat_map(fold_full).collect())
+                }
+                token => token.clone(),
+            })
+            .collect();
+        Self {
+            folded: Some(folded),
+            ..Self::new(tokens)
+        }
+    }
+
+    /// Whether the pattern matches with full case folding, see [`WildcardPattern::with_graphemes`]
+    pub(crate) fn is_folded(&self) -> bool {
+        self.folded.is_some()
+    }
+
+    /// Like [`WildcardPattern::new`] but patterns without a literal fast path are compiled to a
+    /// lazy DFA. If the DFA exceeds the regex limits of the config, the token matcher is used,
+    /// as it is with `regex-lite`, which has no lazy DFA and folds the case of ASCII only.
+    pub(crate) fn with_dfa(
+        tokens: Vec<WildcardToken>,
+        lowercase: bool,
+        config: &EngineConfig,
+    ) -> Self {
+        let mut result = Self::new(tokens);
+        if cfg!(feature = "regex") && result.literal.is_none() {
+            result.dfa = RegexBuilder::new(&to_regex(&result.tokens))
+                .case_insensitive(lowercase)
+                .size_limit(config.regex_size_limit)
+                .dfa_size_limit(config.regex_dfa_size_limit)
+                .build()
+                .ok();
+        }
+        result
+    }
+
+    /// Check if the haystack matches the pattern. If `lowercase` is set, the pattern
+    /// must have been tokenized in lowercase.
+    pub(crate) fn is_match(&self, haystack: &str, lowercase: bool) -> bool {
+        match &self.literal {
+            // Non-ASCII characters may lowercase to ASCII characters (e.g. the Kelvin sign),
+            // so the fast path is only taken for ASCII haystacks
+            Some(literal) if haystack.is_ascii() => {
+                literal.is_match(haystack.as_bytes(), lowercase)
+            }
+            _ => match (&self.dfa, &self.folded) {
+                (Some(dfa), _) => dfa.is_match(haystack),
+                (None, Some(folded)) if lowercase => match_graphemes(folded, haystack),
+                (None, _) => match_tokenized(&self.tokens, haystack, lowercase),
+            },
+        }
+    }
+
+    /// Whether matching the haystack case-insensitively would lowercase it character by
+    /// character. In this case it is cheaper to match a haystack that was lowercased once
+    /// with [`fold_case`] using [`WildcardPattern::is_match_folded`].
+    pub(crate) fn folds_case(&self, haystack: &str) -> bool {
+        self.dfa.is_none()
+            && self.folded.is_none()
+            && !(self.literal.is_some() && haystack.is_ascii())
+    }
+
+    /// The longest literal of the pattern for substring prefilters, see [`longest_literal`].
+    /// Returns `None` for patterns matched with full case folding, which may match a haystack
+    /// that does not contain the lowercase literal, e.g. `ss` matches `ß`.
+    pub(crate) fn required_literal(&self) -> Option<String> {
+        match self.folded {
+            Some(_) => None,
+            None => longest_literal(&self.tokens),
+        }
+    }
+
+    /// Same as `is_match(haystack, true)` for a haystack that was lowercased with [`fold_case`]
+    pub(crate) fn is_match_folded(&self, folded: &str) -> bool {
+        match &self.literal {
+            Some(literal) if folded.is_ascii() => literal.is_match(folded.as_bytes(), false),
+            _ => match &self.dfa {
+                Some(dfa) => dfa.is_match(folded),
+                None => match_tokenized(&self.tokens, folded, false),
+            },
+        }
+    }
+}
+
+impl Deref for WildcardPattern {
+    type Target = [WildcardToken];
+
+    fn deref(&self) -> &Self::Target {
+        &self.tokens
+    }
+}
+
+impl Literal {
+    fn is_match(&self, haystack: &[u8], ignore_case: bool) -> bool {
+        let eq = |a: &[u8], b: &[u8]| {
+            if ignore_case {
+                a.eq_ignore_ascii_case(b)
+            } else {
+                a == b
+            }
+        };
+
+        match self {
+            Self::Equals(needle) => eq(haystack, needle),
+            Self::StartsWith(needle) => haystack
+                .get(..needle.len())
+                .is_some_and(|prefix| eq(prefix, needle)),
+            Self::EndsWith(needle) => haystack
+                .len()
+                .checked_sub(needle.len())
+                .and_then(|start| haystack.get(start..))
+                .is_some_and(|suffix| eq(suffix, needle)),
+            Self::Contains(finder) if ignore_case => {
+                contains_ignore_ascii_case(haystack, finder.needle())
+            }
+            Self::Contains(finder) => finder.find(haystack).is_some(),
+        }
+    }
+}
+
+/// Lower wildcard tokens to an anchored regular expression
+pub(crate) fn to_regex(tokens: &[WildcardToken]) -> String {
+    let mut result = String::from("(?s)^");
+    for token in tokens {
+        match token {
+            WildcardToken::Star => result.push_str(".*"),
+            WildcardToken::QuestionMark => result.push('.'),
+            WildcardToken::Pattern(p) => {
+                result.push_str(&re::escape(&p.iter().collect::<String>()))
+            }
+        }
+    }
+    result.push('$');
+    result
+}
+
+fn ascii_bytes(chars: &[char]) -> Option<Vec<u8>> {
+    chars
+        .iter()
+        .map(|c| c.is_ascii().then_some(*c as u8))
+        .collect()
+}
+
+/// Find an ASCII needle in an ASCII haystack ignoring case. Candidate positions are located
+/// by searching both cases of the first byte of the needle with `memchr2`.
+fn contains_ignore_ascii_case(haystack: &[u8], needle: &[u8]) -> bool {
+    let Some(&first) = needle.first() else {
+        return true;
+    };
+    let (lower, upper) = (first.to_ascii_lowercase(), first.to_ascii_uppercase());
+    let Some(last_start) = haystack.len().checked_sub(needle.len()) else {
+        return false;
+    };
+    let mut start = 0;
+
+    // Candidates are searched among the positions at which the needle still fits
+    while let Some(window) = haystack.get(start..=last_start) {
+        let found = if lower == upper {
+            memchr(lower, window)
+        } else {
+            memchr2(lower, upper, window)
+        };
+        let Some(offset) = found else {
+            return false;
+        };
+
+        let pos = start + offset;
+        if haystack
+            .get(pos..pos + needle.len())
+            .is_some_and(|candidate| candidate.eq_ignore_ascii_case(needle))
+        {
+            return true;
+        }
+        start = pos + 1;
+    }
+    false
+}
+
 /// Tokenize a string into a list of WildcardTokens
 /// This method also takes care of converting escape sequences (backslashes) into the actual characters
 /// See: https://github.com/SigmaHQ/sigma-specification/blob/main/specification/sigma-rules-specification.md#escaping
@@ -79,7 +319,10 @@ pub(crate) fn tokenize(s: &str, lowercase: bool) -> Vec<WildcardToken> {
     result
 }
 
-fn match_tokens(haystack_iterator: impl Iterator<Item = char>, tokens: &[WildcardToken]) -> bool {
+fn match_tokens(
+    haystack_iterator: impl Iterator<Item = char> + Clone,
+    tokens: &[WildcardToken],
+) -> bool {
     let mut starmode = false;
     let mut haystack_iterator = haystack_iterator.peekable();
 
@@ -93,21 +336,21 @@ fn match_tokens(haystack_iterator: impl Iterator<Item = char>, tokens: &[Wildcar
             }
             WildcardToken::Pattern(p) if starmode => {
                 starmode = false;
-                let mut buffer = Vec::with_capacity(p.len());
 
-                while let Some(haystack_char) = haystack_iterator.next() {
-                    if buffer.len() == p.len() {
-                        buffer.remove(0);
-                    }
-                    buffer.push(haystack_char);
-
-                    // Loop till a match is found
-                    // If we process the last token, make sure we loop till the end of the haystack
-                    if buffer == *p && (!is_last_token || haystack_iterator.peek().is_none()) {
+                // Advance the haystack till the pattern matches at the current position
+                // If we process the last token, make sure the match ends with the haystack
+                loop {
+                    let mut probe = haystack_iterator.clone();
+                    if p.iter().all(|c| probe.next() == Some(*c))
+                        && (!is_last_token || probe.peek().is_none())
+                    {
+                        haystack_iterator = probe;
                         continue 'outer;
                     }
+                    if haystack_iterator.next().is_none() {
+                        return false;
+                    }
                 }
-                return false;
             }
             WildcardToken::Pattern(p) => {
                 for c in p {
@@ -131,6 +374,7 @@ fn match_tokens(haystack_iterator: impl Iterator<Item = char>, tokens: &[Wildcar
 
     haystack_iterator.peek().is_none()
 }
+
 pub(crate) fn match_tokenized(tokens: &[WildcardToken], haystack: &str, lowercase: bool) -> bool {
     if lowercase {
         match_tokens(haystack.chars().flat_map(|c| c.to_lowercase()), tokens)
@@ -139,6 +383,181 @@ pub(crate) fn match_tokenized(tokens: &[WildcardToken], haystack: &str, lowercas
     }
 }
 
+/// Lowercase a string the same way patterns are lowercased by [`tokenize`]. In contrast to
+/// [`str::to_lowercase`], every character is lowercased on its own.
+pub(crate) fn fold_case(s: &str) -> String {
+    lowercase_chars(s).collect()
+}
+
+fn lowercase_chars(s: &str) -> impl Iterator<Item = char> + Clone + '_ {
+    s.chars().flat_map(char::to_lowercase)
+}
+
+fn lowercase_chars_rev(s: &str) -> impl Iterator<Item = char> + '_ {
+    s.chars().rev().flat_map(|c| c.to_lowercase().rev())
+}
+
+/// Case-insensitive `starts_with` that lowercases both strings while comparing instead of
+/// allocating lowercase copies
+pub(crate) fn starts_with_ignore_case(haystack: &str, prefix: &str) -> bool {
+    let mut haystack = lowercase_chars(haystack);
+    lowercase_chars(prefix).all(|c| haystack.next() == Some(c))
+}
+
+/// Case-insensitive `ends_with`, see [`starts_with_ignore_case`]
+pub(crate) fn ends_with_ignore_case(haystack: &str, suffix: &str) -> bool {
+    let mut haystack = lowercase_chars_rev(haystack);
+    lowercase_chars_rev(suffix).all(|c| haystack.next() == Some(c))
+}
+
+/// Case-insensitive `contains`, see [`starts_with_ignore_case`]
+pub(crate) fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    let mut haystack = lowercase_chars(haystack);
+    loop {
+        let mut probe = haystack.clone();
+        if lowercase_chars(needle).all(|c| probe.next() == Some(c)) {
+            return true;
+        }
+        if haystack.next().is_none() {
+            return false;
+        }
+    }
+}
+
+/// Fold the case of a character with full case folding, approximated by lowercasing,
+/// uppercasing and lowercasing it again, e.g. `ß` and `ẞ` fold to `ss` like `SS`, and `ς` to
+/// `σ` like `Σ`
+fn fold_full(c: char) -> impl Iterator<Item = char> {
+    c.to_lowercase()
+        .flat_map(char::to_uppercase)
+        .flat_map(char::to_lowercase)
+}
+
+/// Whether a character continues the grapheme cluster of the previous character. This covers
+/// combining marks, joiners, variation selectors, emoji modifiers and tags, but not the
+/// script specific rules of extended grapheme clusters, e.g. for Hangul syllables.
+fn extends_grapheme(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{0483}'..='\u{0489}'
+            | '\u{0591}'..='\u{05BD}'
+            | '\u{0610}'..='\u{061A}'
+            | '\u{064B}'..='\u{065F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{200C}'..='\u{200D}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE00}'..='\u{FE0F}'
+            | '\u{FE20}'..='\u{FE2F}'
+            | '\u{1F3FB}'..='\u{1F3FF}'
+            | '\u{E0020}'..='\u{E007F}'
+            | '\u{E0100}'..='\u{E01EF}'
+    )
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    matches!(c, '\u{1F1E6}'..='\u{1F1FF}')
+}
+
+/// Fold a haystack with [`fold_full`] and mark the positions of the folded characters at which
+/// a grapheme cluster starts. Pairs of regional indicators form a flag, and the character after
+/// a zero width joiner continues an emoji sequence. The end of the haystack is a boundary too.
+fn fold_graphemes(haystack: &str) -> (Vec<char>, Vec<bool>) {
+    let mut chars = Vec::with_capacity(haystack.len());
+    let mut boundaries = Vec::with_capacity(haystack.len() + 1);
+    let mut previous = None;
+    let mut regional_indicators = 0;
+    for c in haystack.chars() {
+        let boundary = match previous {
+            None => true,
+            Some(previous) => {
+                !(extends_grapheme(c)
+                    || previous == '\u{200D}'
+                    || (is_regional_indicator(c) && regional_indicators % 2 == 1))
+            }
+        };
+        if is_regional_indicator(c) {
+            regional_indicators += 1;
+        } else {
+            regional_indicators = 0;
+        }
+        for (i, folded) in fold_full(c).enumerate() {
+            chars.push(folded);
+            boundaries.push(boundary && i == 0);
+        }
+        previous = Some(c);
+    }
+    boundaries.push(true);
+    (chars, boundaries)
+}
+
+/// Match tokens folded with [`fold_full`] against a haystack with full case folding. The tokens
+/// only match on grapheme cluster boundaries, so a pattern never ends within a character that
+/// folds to several characters or before a combining mark, and `?` matches a whole grapheme
+/// cluster. Instead of backtracking, all positions at which the tokens so far can end are
+/// tracked, so matching takes at most the length of the haystack times the pattern length.
+pub(crate) fn match_graphemes(tokens: &[WildcardToken], haystack: &str) -> bool {
+    let (chars, boundaries) = fold_graphemes(haystack);
+    let is_boundary = |i: usize| boundaries.get(i).copied().unwrap_or(false);
+    let mut positions = vec![false; boundaries.len()];
+    if let Some(start) = positions.first_mut() {
+        *start = true;
+    }
+
+    for token in tokens {
+        let mut next = vec![false; boundaries.len()];
+        let starts = positions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, reached)| reached.then_some(i));
+        match token {
+            WildcardToken::Star => {
+                if let Some(first) = positions.iter().position(|reached| *reached) {
+                    for (i, position) in next.iter_mut().enumerate().skip(first) {
+                        *position = is_boundary(i);
+                    }
+                }
+            }
+            WildcardToken::QuestionMark => {
+                for start in starts {
+                    if let Some(end) = (start + 1..boundaries.len()).find(|&i| is_boundary(i)) {
+                        if let Some(position) = next.get_mut(end) {
+                            *position = true;
+                        }
+                    }
+                }
+            }
+            WildcardToken::Pattern(p) => {
+                for start in starts {
+                    let end = start + p.len();
+                    if chars.get(start..end) == Some(p.as_slice()) && is_boundary(end) {
+                        if let Some(position) = next.get_mut(end) {
+                            *position = true;
+                        }
+                    }
+                }
+            }
+        }
+        positions = next;
+    }
+    positions.last().copied().unwrap_or(false)
+}
+
+/// Returns the longest literal part of a tokenized pattern in lowercase.
+/// Every string that matches the pattern contains this literal (after lowercasing),
+/// which makes it suitable for substring prefilters.
+pub(crate) fn longest_literal(tokens: &[WildcardToken]) -> Option<String> {
+    tokens
+        .iter()
+        .filter_map(|t| match t {
+            WildcardToken::Pattern(p) => Some(p),
+            _ => None,
+        })
+        .max_by_key(|p| p.len())
+        .map(|p| p.iter().flat_map(|c| c.to_lowercase()).collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -148,6 +567,164 @@ mod tests {
         match_tokenized(&tokens, haystack, false)
     }
 
+    #[test]
+    fn test_literal_fast_path() {
+        let cases = [
+            ("foo", "FOO", true),
+            ("foo", "fooo", false),
+            ("foo*", "Foobar", true),
+            ("foo*", "fo", false),
+            ("*bar", "FOOBAR", true),
+            ("*bar", "barfoo", false),
+            ("*oob*", "fOOBar", true),
+            ("*oob*", "foo bar", false),
+            ("*.*", "a.b", true),
+            ("*o*", "", false),
+        ];
+        for (pattern, haystack, expected) in cases {
+            let tokens = tokenize(pattern, true);
+            let wildcard = WildcardPattern::new(tokens.clone());
+            assert!(wildcard.literal.is_some(), "{}", pattern);
+            assert_eq!(
+                wildcard.is_match(haystack, true),
+                expected,
+                "{} {}",
+                pattern,
+                haystack
+            );
+            assert_eq!(
+                match_tokenized(&tokens, haystack, true),
+                expected,
+                "{} {}",
+                pattern,
+                haystack
+            );
+        }
+
+        let cased = WildcardPattern::new(tokenize("*Bar*", false));
+        assert!(cased.is_match("FooBarBaz", false));
+        assert!(!cased.is_match("foobarbaz", false));
+
+        // Non-ASCII haystacks and patterns fall back to the token matcher
+        assert!(WildcardPattern::new(tokenize("*k*", true)).is_match("\u{212A}", true));
+        let umlaut = WildcardPattern::new(tokenize("*Ä*", true));
+        assert!(umlaut.literal.is_none());
+        assert!(umlaut.is_match("xäx", true));
+        assert!(WildcardPattern::new(tokenize("a?c", true))
+            .literal
+            .is_none());
+    }
+
+    #[test]
+    fn test_dfa() {
+        let config = EngineConfig::default();
+        let cases = [
+            ("a*b?c", "a__bxc", true),
+            ("a*b?c", "a__bc", false),
+            ("*a*b*c*", "xxAxxBxxC", true),
+            ("*a*b*c*", "xxcxxbxxa", false),
+            ("*.exe?x", "C:\\A.EXEZX", true),
+            ("?", "\n", true),
+            ("a.c?", "abcd", false),
+        ];
+        for (pattern, haystack, expected) in cases {
+            let wildcard = WildcardPattern::with_dfa(tokenize(pattern, true), true, &config);
+            assert_eq!(
+                wildcard.dfa.is_some(),
+                cfg!(feature = "regex"),
+                "{}",
+                pattern
+            );
+            assert_eq!(
+                wildcard.is_match(haystack, true),
+                expected,
+                "{} {}",
+                pattern,
+                haystack
+            );
+        }
+
+        assert_eq!(to_regex(&tokenize("a*b?c", false)), "(?s)^a.*b.c$");
+        let cased = WildcardPattern::with_dfa(tokenize("A*b", false), false, &config);
+        assert!(cased.is_match("Axxb", false));
+        assert!(!cased.is_match("axxb", false));
+        // Literal patterns keep using the substring search
+        assert!(
+            WildcardPattern::with_dfa(tokenize("*abc*", true), true, &config)
+                .dfa
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_is_match_folded() {
+        let config = EngineConfig::default();
+        let cases = [
+            ("*.exe", "C:\\A.EXE"),
+            ("*.exe", "C:\\Ä.EXE"),
+            ("*ä*", "xÄx"),
+            ("a*b?c", "A__BXC"),
+            ("k", "\u{212A}"),
+            ("σ", "Σ"),
+            ("*abc*", "xxABD"),
+        ];
+        for (pattern, haystack) in cases {
+            for wildcard in [
+                WildcardPattern::new(tokenize(pattern, true)),
+                WildcardPattern::with_dfa(tokenize(pattern, true), true, &config),
+            ] {
+                assert_eq!(
+                    wildcard.is_match_folded(&fold_case(haystack)),
+                    wildcard.is_match(haystack, true),
+                    "{} {}",
+                    pattern,
+                    haystack
+                );
+            }
+        }
+        assert_eq!(fold_case("ÄbΣ"), "äbσ");
+
+        let literal = WildcardPattern::new(tokenize("*.exe", true));
+        assert!(!literal.folds_case("C:\\A.EXE"));
+        assert!(literal.folds_case("C:\\Ä.EXE"));
+        assert!(WildcardPattern::new(tokenize("a*b", true)).folds_case("AxB"));
+    }
+
+    #[test]
+    fn test_contains_ignore_ascii_case() {
+        assert!(contains_ignore_ascii_case(b"abcabd", b"ABD"));
+        assert!(contains_ignore_ascii_case(b"xx-1-yy", b"-1-"));
+        assert!(!contains_ignore_ascii_case(b"abcab", b"abd"));
+        assert!(!contains_ignore_ascii_case(b"ab", b"abc"));
+        assert!(contains_ignore_ascii_case(b"ab", b""));
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        assert!(starts_with_ignore_case("FooBar", "fOO"));
+        assert!(!starts_with_ignore_case("Fo", "foo"));
+        assert!(ends_with_ignore_case("FooBar", "BAR"));
+        assert!(!ends_with_ignore_case("ar", "bar"));
+        assert!(contains_ignore_case("FooBarBaz", "RBA"));
+        assert!(contains_ignore_case("ÄÖÜ", "öü"));
+        assert!(!contains_ignore_case("FooBarBaz", "bax"));
+        assert!(contains_ignore_case("", ""));
+    }
+
+    #[test]
+    fn test_longest_literal() {
+        assert_eq!(longest_literal(&tokenize("", false)), None);
+        assert_eq!(longest_literal(&tokenize("*?*", false)), None);
+        assert_eq!(
+            longest_literal(&tokenize("*/Tmp/*.EXE", false)),
+            Some("/tmp/".to_string())
+        );
+        assert_eq!(
+            longest_literal(&tokenize("ab?cde", true)),
+            Some("cde".to_string())
+        );
+    }
+
     #[test]
     fn test_tokenize() {
         assert_eq!(tokenize("", false), vec![]);
@@ -432,4 +1009,45 @@ mod tests {
         assert!(!wildcard_match("?*??ll*??*w\n", "hello!"));
         assert!(!wildcard_match("??*``*", r"``\ȣ?"));
     }
+
+    #[test]
+    fn test_match_graphemes() {
+        let matches = |pattern: &str, haystack: &str| {
+            WildcardPattern::with_graphemes(tokenize(pattern, true)).is_match(haystack, true)
+        };
+        // Case folding that changes the length
+        assert!(matches("*strasse", "C:\\STRAẞE"));
+        assert!(matches("straße*", "STRASSE.exe"));
+        assert!(matches("*ß*", "xSSx"));
+        assert!(!WildcardPattern::new(tokenize("*strasse", true)).is_match("STRAẞE", true));
+        // A pattern does not end within a character that folds to several characters
+        assert!(!matches("*s", "straße"));
+        assert!(!matches("stras*", "STRAßE"));
+        assert!(matches("stra?e", "straße"));
+        assert!(matches("ΣΊΣΥΦΟΣ", "σίσυφος"));
+
+        // A pattern does not match a character without its combining marks
+        let decomposed = "cafe\u{301}";
+        assert!(!matches("*cafe", decomposed));
+        assert!(!matches("cafe*", decomposed));
+        assert!(matches("*cafe\u{301}", decomposed));
+        assert!(matches("caf?", decomposed));
+        assert!(WildcardPattern::new(tokenize("cafe*", true)).is_match(decomposed, true));
+
+        // Emoji sequences and flags are single grapheme clusters
+        assert!(!matches("*👩*", "👩\u{200D}💻"));
+        assert!(matches("?", "👩\u{200D}💻"));
+        assert!(matches("?", "🇩🇪"));
+        assert!(!matches("*🇪🇩*", "🇩🇪🇩🇪"));
+        assert!(matches("🇩🇪?", "🇩🇪🇩🇪"));
+
+        // ASCII patterns and haystacks keep the literal fast paths
+        assert!(matches("*\\cmd.exe", "C:\\Windows\\CMD.EXE"));
+        assert!(matches("", ""));
+        assert!(!matches("?", ""));
+        assert!(matches("*", ""));
+        assert!(WildcardPattern::with_graphemes(tokenize("*cmd*", true))
+            .required_literal()
+            .is_none());
+    }
 }
@@ -0,0 +1,74 @@
+use sigma_rust::{rule_from_yaml, Event};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts the allocations of the current thread
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.set(ALLOCATIONS.get() + 1);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations<R>(f: impl FnOnce() -> R) -> usize {
+    let before = ALLOCATIONS.get();
+    std::hint::black_box(f());
+    ALLOCATIONS.get() - before
+}
+
+#[test]
+fn test_non_matching_event_does_not_allocate() {
+    let rule = rule_from_yaml(
+        r#"
+title: Allocation free evaluation
+logsource:
+detection:
+    selection_image:
+        Image|endswith:
+            - '\cmd.exe'
+            - '\powershell.exe'
+        CommandLine|contains|all:
+            - 'invoke'
+            - 'mimikatz'
+    selection_regex:
+        CommandLine|re:
+            - 'who.mi'
+            - 'net\s+user'
+    selection_wildcard:
+        ParentImage: 'c:\windows\*\svc?ost.exe'
+    selection_fieldref:
+        User|fieldref|contains: TargetUser
+    keywords:
+        - 'sekurlsa*'
+    condition: 1 of selection_* or keywords or (selection_image and not selection_regex)
+"#,
+    )
+    .unwrap();
+
+    let event = Event::from([
+        ("Image", "C:\\Windows\\explorer.exe"),
+        ("CommandLine", "explorer.exe /separate"),
+        ("ParentImage", "C:\\Windows\\System32\\userinit.exe"),
+        ("User", "ALICE"),
+        ("TargetUser", "bob"),
+    ]);
+
+    // Make sure allocations are counted at all
+    assert!(allocations(|| vec![0u8; 16]) > 0);
+    // The regex crate lazily allocates its matching caches on first use
+    assert!(!rule.is_match(&event));
+    assert_eq!(allocations(|| rule.is_match(&event)), 0);
+}
@@ -0,0 +1,273 @@
+#![cfg(feature = "serde_json")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A directory with a rule tree and an event file that is removed when dropped
+struct Fixture(PathBuf);
+
+impl Fixture {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("tau-{}-{}", name, std::process::id()));
+        fs::create_dir_all(dir.join("rules/windows")).unwrap();
+        fs::write(
+            dir.join("rules/windows/whoami.yml"),
+            r#"
+title: Whoami
+id: 1a2b
+level: high
+logsource:
+    category: process_creation
+detection:
+    selection:
+        Image|endswith: '\whoami.exe'
+    condition: selection
+"#,
+        )
+        .unwrap();
+        fs::write(dir.join("rules/README.md"), "not a rule").unwrap();
+        fs::write(
+            dir.join("events.ndjson"),
+            "{\"Image\": \"C:\\\\whoami.exe\"}\n{\"Image\": \"cmd.exe\"}\n",
+        )
+        .unwrap();
+        Self(dir)
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.0.join(name).to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn tau(args: &[&str]) -> (i32, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_tau"))
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn test_match() {
+    let fixture = Fixture::new("match");
+    let rules = fixture.path("rules");
+    let events = fixture.path("events.ndjson");
+
+    let (code, out, err) = tau(&["match", "--rules", &rules, "--events", &events]);
+    assert_eq!(code, 0, "{}", err);
+    assert_eq!(out, "1: [high] Whoami (1a2b)\n");
+
+    let (code, out, _) = tau(&[
+        "match", "--rules", &rules, "--events", &events, "--format", "json",
+    ]);
+    assert_eq!(code, 0);
+    let result: serde_json::Value = serde_json::from_str(out.trim()).unwrap();
+    assert_eq!(result["line"], 1);
+    assert_eq!(result["title"], "Whoami");
+}
+
+#[test]
+fn test_match_loader() {
+    let fixture = Fixture::new("loader");
+    let rules = fixture.path("rules");
+    fs::create_dir_all(fixture.path("filters")).unwrap();
+    fs::write(
+        fixture.path("filters/system.yml"),
+        "filter:\n    User: SYSTEM\n",
+    )
+    .unwrap();
+    fs::write(
+        fixture.path("rules/windows/net.yml"),
+        r#"
+title: Net
+id: 3c4d
+level: low
+logsource:
+    category: process_creation
+detection:
+    tau-include: ../../filters/system.yml
+    selection:
+        Image|endswith: '\net.exe'
+    condition: selection and not filter
+---
+title: Broken
+"#,
+    )
+    .unwrap();
+    fs::write(
+        fixture.path("events.ndjson"),
+        "{\"Image\": \"C:\\\\net.exe\", \"User\": \"SYSTEM\"}\n{\"Image\": \"C:\\\\net.exe\", \"User\": \"bob\"}\n",
+    )
+    .unwrap();
+
+    let events = fixture.path("events.ndjson");
+    let (code, out, err) = tau(&["match", "--rules", &rules, "--events", &events]);
+    assert_eq!(code, 0, "{}", err);
+    assert_eq!(out, "2: [low] Net (3c4d)\n", "{}", err);
+    assert!(
+        err.contains("net.yml:") && err.contains(": error"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_usage_errors() {
+    let (code, _, err) = tau(&["match"]);
+    assert_eq!(code, 2);
+    assert!(err.contains("--rules is required"));
+
+    let (code, _, err) = tau(&["frobnicate"]);
+    assert_eq!(code, 2);
+    assert!(err.contains("unknown command"));
+
+    let (code, out, _) = tau(&["help", "match"]);
+    assert_eq!(code, 0);
+    assert!(out.starts_with("Usage: tau match"));
+}
+
+#[test]
+fn test_check() {
+    let fixture = Fixture::new("check");
+    let rules = fixture.path("rules");
+
+    // The fixture rule has an id that is not a UUID
+    let (code, out, err) = tau(&["check", &rules]);
+    assert_eq!(code, 0, "{}", err);
+    assert!(out.contains("whoami.yml: warning[invalid-id]"));
+    assert!(out.ends_with("checked 1 rules: 0 errors, 1 warnings\n"));
+
+    let (code, _, _) = tau(&["check", "--deny-warnings", &rules]);
+    assert_eq!(code, 1);
+
+    let (code, out, _) = tau(&["check", "--errors-only", &rules]);
+    assert_eq!(code, 0);
+    assert_eq!(out, "checked 1 rules: 0 errors, 1 warnings\n");
+
+    let (code, out, _) = tau(&["check", "--errors-only", "--coverage", &rules]);
+    assert_eq!(code, 0);
+    assert!(out.contains("coverage: 1 fully supported, 0 degraded, 0 unsupported\n"));
+    assert!(out.contains("  modifier endswith: 1 rules\n"));
+
+    fs::write(fixture.path("rules/broken.yml"), "title: Broken").unwrap();
+    let (code, out, _) = tau(&["check", &rules]);
+    assert_eq!(code, 1);
+    assert!(out.contains("broken.yml: error[invalid-rule]"));
+
+    let (code, _, err) = tau(&["check"]);
+    assert_eq!(code, 2);
+    assert!(err.contains("no rule paths given"));
+}
+
+#[test]
+fn test_convert() {
+    let fixture = Fixture::new("convert");
+    let rules = fixture.path("rules");
+
+    let (code, out, err) = tau(&["convert", "--target", "kql", &rules]);
+    assert_eq!(code, 0, "{}", err);
+    assert_eq!(out, "Image endswith @\"\\whoami.exe\"\n");
+
+    fs::write(
+        fixture.path("ecs.yml"),
+        r#"
+name: ecs
+priority: 10
+transformations:
+    - type: field_name_mapping
+      mapping:
+          Image: process.executable
+"#,
+    )
+    .unwrap();
+    let pipeline = fixture.path("ecs.yml");
+    let (code, out, err) = tau(&[
+        "convert",
+        "--target",
+        "kql",
+        "--pipeline",
+        &pipeline,
+        &rules,
+    ]);
+    assert_eq!(code, 0, "{}", err);
+    assert_eq!(
+        out,
+        "[@\"process.executable\"] endswith @\"\\whoami.exe\"\n"
+    );
+
+    let (code, _, err) = tau(&["convert", "--target", "splunk", &rules]);
+    assert_eq!(code, 2);
+    assert!(err.contains("unknown target"));
+}
+
+#[test]
+fn test_bench() {
+    let fixture = Fixture::new("bench");
+    let rules = fixture.path("rules");
+    let events = fixture.path("events.ndjson");
+
+    let (code, out, err) = tau(&["bench", "--rules", &rules, "--events", &events]);
+    assert_eq!(code, 0, "{}", err);
+    assert!(out.starts_with("1 rules, 2 events, 3 iterations\n"));
+    assert!(out.contains("1 matches per iteration"));
+    assert!(out.contains("Whoami (1a2b)"));
+
+    let (code, out, _) = tau(&[
+        "bench",
+        "--rules",
+        &rules,
+        "--events",
+        &events,
+        "--iterations",
+        "1",
+        "--format",
+        "json",
+    ]);
+    assert_eq!(code, 0);
+    let report: serde_json::Value = serde_json::from_str(out.trim()).unwrap();
+    assert_eq!(report["iterations"], 1);
+    assert_eq!(report["slowest"][0]["title"], "Whoami");
+}
+
+#[test]
+fn test_schema() {
+    let (code, out, err) = tau(&["schema"]);
+    assert_eq!(code, 0, "{}", err);
+    let schema: serde_json::Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(schema["title"], "Sigma rule");
+    assert!(schema["$defs"]["field_group"]["propertyNames"]["pattern"]
+        .as_str()
+        .unwrap()
+        .contains("base64offset"));
+}
+
+#[test]
+fn test_report() {
+    let fixture = Fixture::new("report");
+    let rules = fixture.path("rules");
+
+    let (code, out, err) = tau(&["report", &rules]);
+    assert_eq!(code, 0, "{}", err);
+    assert!(out.starts_with("# Detection rules\n"));
+    assert!(out.contains("### process_creation\n\n- [Whoami](#rule-1) (high)\n"));
+    assert!(out.contains("Image ends with '\\whoami.exe'"));
+
+    let (code, out, err) = tau(&["report", "--format", "html", "--title", "Catalog", &rules]);
+    assert_eq!(code, 0, "{}", err);
+    assert!(out.contains("<h1>Catalog</h1>"));
+
+    let (code, _, err) = tau(&["report", "--format", "pdf", &rules]);
+    assert_eq!(code, 2);
+    assert!(err.contains("unknown format"));
+}
@@ -0,0 +1,30 @@
+#![cfg(feature = "ffi")]
+
+use std::fs;
+use std::path::Path;
+
+/// `include/sigma_rust.h` must match the C interface. Run the test with `UPDATE_HEADER=1` to
+/// regenerate it.
+#[test]
+fn test_header_is_up_to_date() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let config = cbindgen::Config::from_file(root.join("cbindgen.toml")).unwrap();
+    let mut header = vec![];
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(root.join("src/ffi.rs"))
+        .generate()
+        .unwrap()
+        .write(&mut header);
+    let header = String::from_utf8(header).unwrap();
+
+    let path = root.join("include/sigma_rust.h");
+    if std::env::var_os("UPDATE_HEADER").is_some() {
+        fs::write(&path, &header).unwrap();
+    }
+    assert_eq!(
+        fs::read_to_string(&path).unwrap_or_default(),
+        header,
+        "include/sigma_rust.h is outdated, run the test with UPDATE_HEADER=1"
+    );
+}
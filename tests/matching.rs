@@ -208,3 +208,52 @@ fn test_match_exists_modifier() {
     assert!(!rule.is_match(&event_2));
     assert!(!rule.is_match(&event_3));
 }
+
+#[test]
+fn test_match_yaml_anchors() {
+    let yaml = r#"
+        title: Anchors and merge keys
+        logsource:
+        detection:
+            shells: &shells
+                - '\cmd.exe'
+                - '\powershell.exe'
+            selection_parent: &parent
+                ParentImage|endswith: '\explorer.exe'
+                User: admin
+            selection_shell:
+                <<: *parent
+                Image|endswith: *shells
+            selection_service:
+                - <<: *parent
+                  User: SYSTEM
+                  Image|endswith: *shells
+            condition: 1 of selection_shell*
+    "#;
+
+    let rule = rule_from_yaml(yaml).unwrap();
+    let event_1 = Event::from([
+        ("ParentImage", "C:\\explorer.exe"),
+        ("User", "admin"),
+        ("Image", "C:\\powershell.exe"),
+    ]);
+    let event_2 = Event::from([
+        ("ParentImage", "C:\\explorer.exe"),
+        ("User", "admin"),
+        ("Image", "C:\\notepad.exe"),
+    ]);
+    let event_3 = Event::from([("ParentImage", "C:\\explorer.exe"), ("User", "admin")]);
+
+    assert!(rule.is_match(&event_1));
+    assert!(!rule.is_match(&event_2));
+    assert!(!rule.is_match(&event_3));
+
+    let rule = rule_from_yaml(&yaml.replace("1 of selection_shell*", "selection_service")).unwrap();
+    let event_4 = Event::from([
+        ("ParentImage", "C:\\explorer.exe"),
+        ("User", "SYSTEM"),
+        ("Image", "C:\\cmd.exe"),
+    ]);
+    assert!(!rule.is_match(&event_1));
+    assert!(rule.is_match(&event_4));
+}